@@ -0,0 +1,203 @@
+//! Cancelling a device prompt from outside the worker.
+//!
+//! While the device waits on a button press the worker is blocked reading the
+//! transport, so a `Cancel` can't be queued behind the operation. Instead the
+//! handle raises a shared flag. `CancellingAdapter` reads in short slices while
+//! a prompt is on screen and writes `Cancel` as soon as it sees the flag; if
+//! the flag goes up between prompts, the next acknowledgement is sent as
+//! `Cancel` instead. The device then fails the operation with `ActionCancelled`
+//! and returns to its home screen.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::messages::{self, Message};
+use crate::transport::ProtocolAdapter;
+
+/// Cancellation request of one device, shared between its worker and handles
+#[derive(Debug, Clone, Default)]
+pub struct CancelCell(Arc<AtomicBool>);
+
+impl CancelCell {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Take the pending request, if any
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Acknowledgements the host sends to let a prompt continue
+fn continues_prompt(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::ButtonAck(_) | Message::PinMatrixAck(_) | Message::PassphraseAck(_) | Message::CharacterAck(_) | Message::WordAck(_)
+    )
+}
+
+/// Transport wrapper that cancels the prompt on screen once a cancellation was
+/// requested, or the next one if none is
+pub struct CancellingAdapter {
+    inner: Box<dyn ProtocolAdapter + Send>,
+    cancel: CancelCell,
+}
+
+impl CancellingAdapter {
+    pub fn new(inner: Box<dyn ProtocolAdapter + Send>, cancel: CancelCell) -> Self {
+        Self { inner, cancel }
+    }
+}
+
+impl ProtocolAdapter for CancellingAdapter {
+    fn reset(&mut self) -> Result<()> {
+        self.inner.reset()
+    }
+
+    fn send(&mut self, msg: Message) -> Result<()> {
+        self.inner.send(msg)
+    }
+
+    fn handle(&mut self, msg: Message) -> Result<Message> {
+        if continues_prompt(&msg) && self.cancel.take() {
+            log::info!("Cancelling device prompt instead of sending {:?}", msg.message_type());
+            return self.inner.handle(messages::Cancel {}.into());
+        }
+        self.inner.handle_cancellable(msg, &self.cancel)
+    }
+
+    fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{ButtonAck, Failure, GetFeatures};
+
+    /// Records what reaches the device and answers with a failure
+    struct Recorder(Arc<std::sync::Mutex<Vec<Message>>>);
+
+    impl ProtocolAdapter for Recorder {
+        fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn send(&mut self, msg: Message) -> Result<()> {
+            self.0.lock().unwrap().push(msg);
+            Ok(())
+        }
+        fn handle(&mut self, msg: Message) -> Result<Message> {
+            self.send(msg)?;
+            Ok(Failure::default().into())
+        }
+        fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+            self
+        }
+    }
+
+    #[test]
+    fn test_cancel_replaces_next_acknowledgement() {
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let cancel = CancelCell::default();
+        let mut adapter = CancellingAdapter::new(Box::new(Recorder(sent.clone())), cancel.clone());
+
+        adapter.handle(ButtonAck::default().into()).unwrap();
+        cancel.request();
+        // Requests aren't touched, only the answer to the prompt they lead to
+        adapter.handle(GetFeatures::default().into()).unwrap();
+        adapter.handle(ButtonAck::default().into()).unwrap();
+        adapter.handle(ButtonAck::default().into()).unwrap();
+
+        let kinds: Vec<_> = sent.lock().unwrap().iter().map(|m| m.message_type()).collect();
+        let expected: Vec<_> = [
+            ButtonAck::default().into(),
+            GetFeatures::default().into(),
+            Message::from(messages::Cancel {}),
+            ButtonAck::default().into(),
+        ]
+        .iter()
+        .map(|m: &Message| m.message_type())
+        .collect();
+        assert_eq!(kinds, expected);
+        assert!(!cancel.is_requested());
+    }
+
+    /// A device showing a button prompt: reads time out until the host writes
+    /// `Cancel`, which it answers with `ActionCancelled`
+    struct PromptOnScreen {
+        written: Arc<std::sync::Mutex<Vec<Message>>>,
+        answer: Option<Vec<u8>>,
+    }
+
+    impl crate::transport::Transport for PromptOnScreen {
+        type Error = std::io::Error;
+
+        fn write(&mut self, msg: &[u8], _timeout: std::time::Duration) -> Result<usize, Self::Error> {
+            let msg = Message::decode(&mut &msg[..]).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            if matches!(msg, Message::Cancel(_)) {
+                let failure: Message = Failure {
+                    code: Some(messages::FailureType::FailureActionCancelled as i32),
+                    message: Some("Action cancelled by user".to_string()),
+                }
+                .into();
+                let mut answer = Vec::new();
+                failure.encode(&mut answer).unwrap();
+                self.answer = Some(answer);
+            }
+            self.written.lock().unwrap().push(msg);
+            Ok(0)
+        }
+
+        fn read(&mut self, buf: &mut Vec<u8>, timeout: std::time::Duration) -> Result<(), Self::Error> {
+            match self.answer.take() {
+                Some(answer) => {
+                    buf.extend_from_slice(&answer);
+                    Ok(())
+                }
+                None => {
+                    std::thread::sleep(timeout);
+                    Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no answer"))
+                }
+            }
+        }
+
+        fn reset(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn is_timeout(error: &Self::Error) -> bool {
+            error.kind() == std::io::ErrorKind::TimedOut
+        }
+    }
+
+    #[test]
+    fn test_cancel_reaches_prompt_on_screen() {
+        let written = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let device = PromptOnScreen { written: written.clone(), answer: None };
+        let cancel = CancelCell::default();
+        let mut adapter = CancellingAdapter::new(Box::new(device), cancel.clone());
+
+        // The acknowledgement went out before the cancellation: the device is
+        // showing the prompt and the worker waits for the press
+        let waiting = std::thread::spawn(move || adapter.handle(ButtonAck::default().into()));
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(!waiting.is_finished());
+        cancel.request();
+
+        match waiting.join().unwrap().unwrap() {
+            Message::Failure(failure) => assert_eq!(failure.code, Some(messages::FailureType::FailureActionCancelled as i32)),
+            other => panic!("expected ActionCancelled, got {:?}", other.message_type()),
+        }
+        let kinds: Vec<_> = written.lock().unwrap().iter().map(|m| m.message_type()).collect();
+        assert_eq!(kinds, vec![Message::from(ButtonAck::default()).message_type(), Message::from(messages::Cancel {}).message_type()]);
+        assert!(!cancel.is_requested());
+    }
+}
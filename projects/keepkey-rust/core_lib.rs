@@ -5,3 +5,8 @@ pub mod messages;
 pub mod transport;
 pub mod features;
 pub mod device_queue;
+pub mod protocol;
+pub mod screen_hint;
+pub mod cancel;
+pub mod gate;
+pub mod firmware_upload;
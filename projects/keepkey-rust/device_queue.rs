@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::{timeout, sleep};
@@ -7,8 +8,12 @@ use anyhow::{anyhow, Result};
 use tracing::{info, warn, error, debug, instrument};
 
 use crate::messages::{Message, GetFeatures, GetAddress, Features};
-use crate::transport::ProtocolAdapter;
+use crate::transport::{ProtocolAdapter, RetryingTransport, TransportConfig};
 use crate::friendly_usb::FriendlyUsbDevice;
+use crate::protocol::ProtocolVersion;
+use crate::cancel::{CancelCell, CancellingAdapter};
+use crate::gate::{GateCell, MessageGate};
+use crate::screen_hint::{ScreenHint, ScreenHintCell, ScreenHintRecorder};
 
 /// Transport type detection for different KeepKey device modes
 #[derive(Debug, Clone, Copy)]
@@ -27,27 +32,150 @@ const QUEUE_CHANNEL_SIZE: usize = 100;
 const CACHE_MAX_ENTRIES: usize = 256;
 const CACHE_TTL: Duration = Duration::from_secs(30);
 
+/// What a queued operation waits on, which decides how long its caller waits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    GetFeatures,
+    /// Reads that need no user interaction (addresses without display, public keys, ...)
+    Read,
+    /// Address shown on the device screen for the user to verify
+    GetAddressDisplay,
+    Sign,
+    Wipe,
+    /// Setup and settings changes the user confirms on the device (PIN change,
+    /// reset, recovery, labels, policies)
+    Confirm,
+    FirmwareUpdate,
+}
+
+impl OperationKind {
+    /// Classify a raw message sent through the queue
+    pub fn for_message(message: &Message) -> Self {
+        match message {
+            Message::GetFeatures(_) | Message::Initialize(_) => OperationKind::GetFeatures,
+            Message::GetAddress(m) if m.show_display == Some(true) => OperationKind::GetAddressDisplay,
+            Message::SignTx(_) | Message::TxAck(_) | Message::RawTxAck(_) | Message::SignMessage(_) => OperationKind::Sign,
+            Message::WipeDevice(_) => OperationKind::Wipe,
+            Message::ChangePin(_)
+            | Message::ResetDevice(_)
+            | Message::RecoveryDevice(_)
+            | Message::ApplySettings(_)
+            | Message::ApplyPolicies(_)
+            | Message::GetEntropy(_)
+            | Message::PinMatrixAck(_)
+            | Message::CharacterAck(_) => OperationKind::Confirm,
+            Message::FirmwareErase(_) | Message::FirmwareUpload(_) => OperationKind::FirmwareUpdate,
+            _ => OperationKind::Read,
+        }
+    }
+
+    /// Whether the operation waits on a person at the device, so running out of
+    /// time means nobody confirmed rather than a transport problem
+    pub fn requires_confirmation(self) -> bool {
+        matches!(
+            self,
+            OperationKind::GetAddressDisplay | OperationKind::Sign | OperationKind::Wipe | OperationKind::Confirm
+        )
+    }
+}
+
+/// How long callers wait for each kind of operation, queue wait included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutProfile {
+    pub get_features: Duration,
+    pub read: Duration,
+    pub get_address_display: Duration,
+    pub sign: Duration,
+    pub wipe: Duration,
+    pub confirm: Duration,
+    pub firmware_update: Duration,
+}
+
+impl Default for TimeoutProfile {
+    fn default() -> Self {
+        Self {
+            get_features: Duration::from_secs(5),
+            read: DEVICE_OPERATION_TIMEOUT,
+            get_address_display: Duration::from_secs(120),
+            sign: Duration::from_secs(300),
+            wipe: Duration::from_secs(120),
+            confirm: Duration::from_secs(120),
+            firmware_update: Duration::from_secs(120),
+        }
+    }
+}
+
+impl TimeoutProfile {
+    pub fn timeout_for(&self, kind: OperationKind) -> Duration {
+        match kind {
+            OperationKind::GetFeatures => self.get_features,
+            OperationKind::Read => self.read,
+            OperationKind::GetAddressDisplay => self.get_address_display,
+            OperationKind::Sign => self.sign,
+            OperationKind::Wipe => self.wipe,
+            OperationKind::Confirm => self.confirm,
+            OperationKind::FirmwareUpdate => self.firmware_update,
+        }
+    }
+}
+
+/// Why a queued operation gave up waiting. Callers can tell the two apart with
+/// `err.downcast_ref::<QueueTimeout>()`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QueueTimeout {
+    /// The device (or the queue in front of it) didn't answer in time
+    #[error("Device operation timed out after {after:?} ({kind:?})")]
+    Timeout { kind: OperationKind, after: Duration },
+    /// The device was waiting for the user, who didn't confirm in time
+    #[error("Timed out after {after:?} waiting for confirmation on the device ({kind:?})")]
+    ConfirmationTimeout { kind: OperationKind, after: Duration },
+}
+
+impl QueueTimeout {
+    fn new(kind: OperationKind, after: Duration) -> Self {
+        if kind.requires_confirmation() {
+            QueueTimeout::ConfirmationTimeout { kind, after }
+        } else {
+            QueueTimeout::Timeout { kind, after }
+        }
+    }
+}
+
 /// Unique key for caching device responses
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CacheKey {
     device_id: String,
+    /// Master fingerprint of the wallet the response belongs to. Each passphrase
+    /// opens a different wallet on the same device, so derivations are only
+    /// reused for the wallet they were made with. `None` until the fingerprint
+    /// of the current session is known.
+    wallet: Option<u32>,
     operation: String,
     params_hash: u64,
 }
 
 impl CacheKey {
-    fn new(device_id: String, operation: impl Into<String>, params: &[u8]) -> Self {
+    fn new(device_id: String, wallet: Option<u32>, operation: impl Into<String>, params: &[u8]) -> Self {
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
         params.hash(&mut hasher);
         
         Self {
             device_id,
+            wallet,
             operation: operation.into(),
             params_hash: hasher.finish(),
         }
     }
 }
 
+/// BIP-32 fingerprint of a compressed public key (first 4 bytes of its hash160)
+pub fn fingerprint_of(public_key: &[u8]) -> u32 {
+    use ripemd::Ripemd160;
+    use sha2::{Digest, Sha256};
+    let hash = Ripemd160::digest(Sha256::digest(public_key));
+    u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+}
+
 /// Cached response with timestamp
 #[derive(Debug, Clone)]
 pub struct CachedResponse {
@@ -89,6 +217,10 @@ pub enum DeviceCmd {
         enqueued_at: Instant,
         bypass_cache: bool,
     },
+    GetMasterFingerprint {
+        respond_to: oneshot::Sender<Result<u32>>,
+        enqueued_at: Instant,
+    },
     UpdateBootloader {
         target_version: String,
         bootloader_bytes: Vec<u8>,
@@ -101,6 +233,17 @@ pub enum DeviceCmd {
         respond_to: oneshot::Sender<Result<bool>>,
         enqueued_at: Instant,
     },
+    /// Hand the worker to a session: until every clone of the session's handle
+    /// is dropped, only commands sent through it run
+    ExclusiveSession {
+        session_rx: mpsc::Receiver<DeviceCmd>,
+        respond_to: oneshot::Sender<Result<()>>,
+        enqueued_at: Instant,
+    },
+    /// Drop every cached response, e.g. after the host switched networks
+    ClearCache {
+        respond_to: oneshot::Sender<Result<()>>,
+    },
     Shutdown {
         respond_to: oneshot::Sender<Result<()>>,
     },
@@ -112,9 +255,11 @@ impl DeviceCmd {
             DeviceCmd::GetFeatures { enqueued_at, .. } => *enqueued_at,
             DeviceCmd::GetAddress { enqueued_at, .. } => *enqueued_at,
             DeviceCmd::SendRaw { enqueued_at, .. } => *enqueued_at,
+            DeviceCmd::GetMasterFingerprint { enqueued_at, .. } => *enqueued_at,
             DeviceCmd::UpdateBootloader { enqueued_at, .. } => *enqueued_at,
             DeviceCmd::UpdateFirmware { enqueued_at, .. } => *enqueued_at,
-            DeviceCmd::Shutdown { .. } => Instant::now(),
+            DeviceCmd::ExclusiveSession { enqueued_at, .. } => *enqueued_at,
+            DeviceCmd::ClearCache { .. } | DeviceCmd::Shutdown { .. } => Instant::now(),
         }
     }
     
@@ -123,8 +268,11 @@ impl DeviceCmd {
             DeviceCmd::GetFeatures { .. } => "get_features",
             DeviceCmd::GetAddress { .. } => "get_address", 
             DeviceCmd::SendRaw { .. } => "send_raw",
+            DeviceCmd::GetMasterFingerprint { .. } => "get_master_fingerprint",
             DeviceCmd::UpdateBootloader { .. } => "update_bootloader",
             DeviceCmd::UpdateFirmware { .. } => "update_firmware",
+            DeviceCmd::ExclusiveSession { .. } => "exclusive_session",
+            DeviceCmd::ClearCache { .. } => "clear_cache",
             DeviceCmd::Shutdown { .. } => "shutdown",
         }
     }
@@ -134,8 +282,11 @@ impl DeviceCmd {
             DeviceCmd::GetFeatures { .. } => true,
             DeviceCmd::GetAddress { .. } => true,
             DeviceCmd::SendRaw { bypass_cache, .. } => !*bypass_cache,
+            DeviceCmd::GetMasterFingerprint { .. } => true,
             DeviceCmd::UpdateBootloader { .. } => false,
             DeviceCmd::UpdateFirmware { .. } => false,
+            DeviceCmd::ExclusiveSession { .. } => false,
+            DeviceCmd::ClearCache { .. } => false,
             DeviceCmd::Shutdown { .. } => false,
         }
     }
@@ -194,6 +345,19 @@ pub struct DeviceWorker {
     cmd_rx: mpsc::Receiver<DeviceCmd>,
     /// Track if device is in PIN flow mode (ResetDevice, PIN setup, etc)
     is_pin_flow: bool,
+    /// Master fingerprint of the wallet open in the current session; reset
+    /// whenever the session (and so possibly the passphrase) changes
+    wallet_fingerprint: Option<u32>,
+    /// Prompt currently on the device screen, readable through the handle
+    screen_hint: ScreenHintCell,
+    /// Raised through a handle to cancel the prompt of the running command
+    cancel: CancelCell,
+    /// Version the device reported last, deciding which messages it is sent
+    protocol: Option<ProtocolVersion>,
+    /// Whether a GetFeatures was already sent just to learn `protocol`
+    negotiated: bool,
+    /// Write retry behavior of every transport this worker opens
+    transport_config: TransportConfig,
 }
 
 impl DeviceWorker {
@@ -201,6 +365,9 @@ impl DeviceWorker {
         device_id: String,
         device_info: FriendlyUsbDevice,
         cmd_rx: mpsc::Receiver<DeviceCmd>,
+        screen_hint: ScreenHintCell,
+        cancel: CancelCell,
+        transport_config: TransportConfig,
     ) -> Self {
         Self {
             device_id,
@@ -210,6 +377,12 @@ impl DeviceWorker {
             metrics: DeviceQueueMetrics::default(),
             cmd_rx,
             is_pin_flow: false,
+            wallet_fingerprint: None,
+            screen_hint,
+            cancel,
+            protocol: None,
+            negotiated: false,
+            transport_config,
         }
     }
     
@@ -255,6 +428,10 @@ impl DeviceWorker {
                 let result = self.handle_send_raw(message, bypass_cache).await;
                 let _ = respond_to.send(result);
             }
+            DeviceCmd::GetMasterFingerprint { respond_to, .. } => {
+                let result = self.handle_get_master_fingerprint().await;
+                let _ = respond_to.send(result);
+            }
             DeviceCmd::UpdateBootloader { target_version, bootloader_bytes, respond_to, enqueued_at: _ } => {
                 let result = self.handle_update_bootloader(target_version, bootloader_bytes).await;
                 let _ = respond_to.send(result);
@@ -263,6 +440,25 @@ impl DeviceWorker {
                 let result = self.handle_update_firmware(target_version, firmware_bytes).await;
                 let _ = respond_to.send(result);
             }
+            DeviceCmd::ExclusiveSession { mut session_rx, respond_to, .. } => {
+                // The caller gave up while queued: nobody holds the session
+                if respond_to.send(Ok(())).is_ok() {
+                    info!("🔒 Device {} held by an exclusive session", self.device_id);
+                    while let Some(cmd) = session_rx.recv().await {
+                        if let Err(e) = Box::pin(self.process_command(cmd)).await {
+                            error!("❌ Command failed: {}", e);
+                        }
+                    }
+                    info!("🔓 Exclusive session on device {} released", self.device_id);
+                }
+                return Ok(());
+            }
+            DeviceCmd::ClearCache { respond_to } => {
+                info!("🧹 Clearing {} cached responses for device {}", self.cache.len(), self.device_id);
+                self.cache.clear();
+                let _ = respond_to.send(Ok(()));
+                return Ok(());
+            }
             DeviceCmd::Shutdown { respond_to } => {
                 let _ = respond_to.send(Ok(()));
                 return Ok(());
@@ -275,6 +471,9 @@ impl DeviceWorker {
         
         self.metrics.record_operation(queue_wait, device_rtt, total_time);
     
+    // A cancellation only applies to the command it was raised during
+    self.cancel.take();
+    
     // Always drop transport after each command to avoid exclusive handle issues,
     // it will be recreated lazily on the next command.
     if self.transport.is_some() {
@@ -294,7 +493,7 @@ impl DeviceWorker {
                 info!("🔗 Attempting to create transport for device {}", self.device_id);
                 
                 // Try to create transport with current device info
-                let mut transport_result = DeviceQueueFactory::create_transport_for_device(&self.device_info);
+                let mut transport_result = DeviceQueueFactory::create_transport_with_config(&self.device_info, self.transport_config);
                 
                 // If failed and PID is 0x0002, try looking for a device with same serial but different PID
                 // This handles the case where device reconnected after bootloader update
@@ -337,13 +536,15 @@ impl DeviceWorker {
                     
                     if found_reconnected {
                         // Try again with updated device info
-                        transport_result = DeviceQueueFactory::create_transport_for_device(&self.device_info);
+                        transport_result = DeviceQueueFactory::create_transport_with_config(&self.device_info, self.transport_config);
                     }
                 }
                 
                 match transport_result {
                     Ok(transport) => {
-                        self.transport = Some(transport);
+                        self.screen_hint.set(ScreenHint::Idle);
+                        let transport = Box::new(CancellingAdapter::new(transport, self.cancel.clone()));
+                        self.transport = Some(Box::new(ScreenHintRecorder::new(transport, self.screen_hint.clone())));
                         info!("✅ Transport ready for {}", self.device_id);
                     }
                     Err(e) => {
@@ -372,6 +573,61 @@ impl DeviceWorker {
         }
     }
     
+    /// Learn which protocol version the device speaks before `message`, the
+    /// first one checked against it, goes out. Asked at most once, over the
+    /// transport the command then goes on to use, and not at all if a GetFeatures
+    /// already told. If the device doesn't answer, messages go out unchecked
+    /// until a later GetFeatures succeeds.
+    async fn negotiate_protocol(&mut self, message: &Message) {
+        if self.protocol.is_some() || self.negotiated || !Self::needs_protocol(message) {
+            return;
+        }
+        self.negotiated = true;
+        if let Err(e) = self.handle_get_features().await {
+            warn!("⚠️ Protocol negotiation failed for {}: {}", self.device_id, e);
+        }
+    }
+    
+    /// Messages the version matters for. Answers to a device request are left
+    /// out: a GetFeatures in between would abort the flow the device is in.
+    fn needs_protocol(message: &Message) -> bool {
+        !matches!(
+            message,
+            Message::GetFeatures(_)
+                | Message::Initialize(_)
+                | Message::Ping(_)
+                | Message::Cancel(_)
+                | Message::ButtonAck(_)
+                | Message::PinMatrixAck(_)
+                | Message::PassphraseAck(_)
+                | Message::WordAck(_)
+                | Message::CharacterAck(_)
+                | Message::TxAck(_)
+                | Message::RawTxAck(_)
+                | Message::FirmwareErase(_)
+                | Message::FirmwareUpload(_)
+        )
+    }
+    
+    fn record_protocol(&mut self, features: &Features) {
+        let version = ProtocolVersion::from_features(features);
+        if self.protocol != Some(version) {
+            info!("🤝 Device {} speaks {}", self.device_id, version);
+            self.protocol = Some(version);
+        }
+    }
+    
+    /// Refuse messages the negotiated version can't parse
+    fn check_supported(&self, message: &Message) -> Result<()> {
+        match self.protocol {
+            Some(version) => version
+                .capabilities()
+                .check(message)
+                .map_err(|e| anyhow!("Not supported by {} on device {}: {}", version, self.device_id, e)),
+            None => Ok(()),
+        }
+    }
+    
     /// Handle GetFeatures command with caching
     async fn handle_get_features(&mut self) -> Result<Features> {
         // NOTE: We purposely skip normal caching for GetFeatures because features are
@@ -382,11 +638,31 @@ impl DeviceWorker {
         // First attempt the standard GetFeatures call.
         // For OOB bootloaders, we need to handle raw responses directly since
         // the standard handler throws an error on Failure messages
+        // A bootloader known not to understand GetFeatures gets Initialize right away
+        if let Some(version) = self.protocol.filter(|v| !v.capabilities().get_features) {
+            debug!("Using Initialize for {} ({})", self.device_id, version);
+            let transport = self.ensure_transport().await?;
+            let response = transport.with_standard_handler().handle(crate::messages::Initialize {}.into())?;
+            return match response {
+                Message::Features(features) => {
+                    self.record_protocol(&features);
+                    Ok(features)
+                }
+                other => Err(anyhow!("Unexpected response to Initialize: {:?}", other.message_type())),
+            };
+        }
+        
         let transport = self.ensure_transport().await?;
         let response = transport.handle(GetFeatures {}.into())?;
 
         match response {
             Message::Features(features) => {
+                self.record_protocol(&features);
+                // No passphrase in the session (cleared, or the device auto-locked):
+                // the next operation may open a different wallet
+                if features.passphrase_protection.unwrap_or(false) && !features.passphrase_cached.unwrap_or(false) {
+                    self.reset_wallet_session();
+                }
                 return Ok(features);
             }
             // Some very old bootloaders (so-called "OOB bootloader" devices) do not
@@ -418,6 +694,7 @@ impl DeviceWorker {
                         self.device_id
                     );
                     println!("✅ OOB bootloader Initialize fallback successful for device {}", self.device_id);
+                    self.record_protocol(&features);
                     return Ok(features);
                 } else {
                     return Err(anyhow!("Unexpected response to Initialize fallback"));
@@ -449,30 +726,30 @@ impl DeviceWorker {
             params.extend_from_slice(&[sd as u8]);
         }
         
-        let cache_key = CacheKey::new(self.device_id.clone(), "get_address", &params);
+        let cache_key = CacheKey::new(self.device_id.clone(), self.wallet_fingerprint, "get_address", &params);
         
         // Check cache first
-        if let Some(cached) = self.cache.get(&cache_key) {
-            if cached.is_fresh() {
-                self.metrics.record_cache_hit();
-                debug!("💰 Cache hit for GetAddress");
-                return Ok(cached.value.as_str().unwrap_or_default().to_string());
-            }
+        if let Some(address) = self.cached_value(&cache_key) {
+            self.metrics.record_cache_hit();
+            debug!("💰 Cache hit for GetAddress");
+            return Ok(address.as_str().unwrap_or_default().to_string());
         }
         
         self.metrics.record_cache_miss();
         
         // Execute on device
-        let transport = self.ensure_transport().await?;
-        let get_address = GetAddress {
+        let get_address: Message = GetAddress {
             address_n: path,
             coin_name: Some(coin_name),
             script_type,
             show_display,
             ..Default::default()
-        };
+        }.into();
+        self.negotiate_protocol(&get_address).await;
+        self.check_supported(&get_address)?;
+        let transport = self.ensure_transport().await?;
         
-        let response = transport.with_pin_flow_handler().handle(get_address.into())?;
+        let response = transport.with_pin_flow_handler().handle(get_address)?;
         
         match response {
             Message::Address(addr_response) => {
@@ -490,8 +767,70 @@ impl DeviceWorker {
         }
     }
     
+    /// Master fingerprint of the wallet open in the current session, from the
+    /// public key at `m`. Asked of the device once per session.
+    async fn handle_get_master_fingerprint(&mut self) -> Result<u32> {
+        if let Some(fingerprint) = self.wallet_fingerprint {
+            self.metrics.record_cache_hit();
+            return Ok(fingerprint);
+        }
+        self.metrics.record_cache_miss();
+        
+        let transport = self.ensure_transport().await?;
+        let get_public_key = crate::messages::GetPublicKey {
+            address_n: vec![],
+            coin_name: Some("Bitcoin".to_string()),
+            ecdsa_curve_name: Some("secp256k1".to_string()),
+            show_display: Some(false),
+            ..Default::default()
+        };
+        
+        let response = transport.with_pin_flow_handler().handle(get_public_key.into())?;
+        match response {
+            Message::PublicKey(public_key) => match public_key.node.public_key {
+                Some(key) if !key.is_empty() => {
+                    let fingerprint = fingerprint_of(&key);
+                    info!("🔑 Wallet fingerprint for {}: {:08x}", self.device_id, fingerprint);
+                    self.wallet_fingerprint = Some(fingerprint);
+                    Ok(fingerprint)
+                }
+                _ => Err(anyhow!("Device returned no master public key")),
+            },
+            Message::PassphraseRequest(_) => Err(anyhow!("Passphrase entry required before the wallet fingerprint is known")),
+            Message::Failure(f) => Err(anyhow!("Failed to get master public key: {}", f.message())),
+            other => Err(anyhow!("Unexpected response to GetPublicKey: {:?}", other.message_type())),
+        }
+    }
+    
+    /// Forget which wallet is open. Derivations cached for a known wallet stay
+    /// valid (they are keyed by its fingerprint) and are reused if that wallet is
+    /// opened again; ones made before the wallet was known are dropped.
+    fn reset_wallet_session(&mut self) {
+        if let Some(fingerprint) = self.wallet_fingerprint.take() {
+            info!("🔄 Wallet session {:08x} ended for device {}", fingerprint, self.device_id);
+        }
+        self.cache.retain(|key, _| key.wallet.is_some());
+    }
+    
+    /// Messages that start a new session, after which a different passphrase
+    /// (and so a different wallet) may be in use
+    fn is_session_change(message: &Message) -> bool {
+        matches!(message, Message::ClearSession(_) | Message::PassphraseAck(_) | Message::Initialize(_))
+    }
+    
+    fn cached_value(&self, key: &CacheKey) -> Option<serde_json::Value> {
+        self.cache.get(key).filter(|cached| cached.is_fresh()).map(|cached| cached.value.clone())
+    }
+    
     /// Handle raw message sending 
     async fn handle_send_raw(&mut self, message: Message, bypass_cache: bool) -> Result<Message> {
+        self.negotiate_protocol(&message).await;
+        self.check_supported(&message)?;
+        
+        if Self::is_session_change(&message) {
+            self.reset_wallet_session();
+        }
+        
         // Detect if this is a PIN flow related message
         let is_pin_flow_message = matches!(
             &message,
@@ -540,6 +879,10 @@ impl DeviceWorker {
             _ => {}
         }
         
+        if matches!(response, Message::PassphraseRequest(_)) {
+            self.reset_wallet_session();
+        }
+        
         // If this was a mutable operation, purge cache
         if bypass_cache || self.is_mutable_operation(&response) {
             self.cache.clear();
@@ -724,38 +1067,227 @@ impl DeviceWorker {
     }
 }
 
+tokio::task_local! {
+    /// Command channels of the workers the current task holds exclusive
+    /// sessions on, to catch commands sent to them from inside the session
+    static HELD_WORKERS: Vec<mpsc::Sender<DeviceCmd>>;
+}
+
 /// Handle for communicating with a device worker
 #[derive(Clone, Debug)]
 pub struct DeviceQueueHandle {
     device_id: String,
     cmd_tx: mpsc::Sender<DeviceCmd>,
+    timeouts: TimeoutProfile,
+    screen_hint: ScreenHintCell,
+    cancel: CancelCell,
+    /// Vets every raw message before it is queued
+    gate: GateCell,
+    /// When a request through this handle or one of its clones last started or finished
+    last_activity: Arc<std::sync::Mutex<Instant>>,
+    /// When the request waiting on a confirmation gives up, shared between clones
+    confirmation_deadline: Arc<std::sync::Mutex<Option<Instant>>>,
 }
 
 impl DeviceQueueHandle {
     pub fn new(device_id: String, cmd_tx: mpsc::Sender<DeviceCmd>) -> Self {
-        Self { device_id, cmd_tx }
+        Self {
+            device_id,
+            cmd_tx,
+            timeouts: TimeoutProfile::default(),
+            screen_hint: ScreenHintCell::default(),
+            cancel: CancelCell::default(),
+            gate: GateCell::default(),
+            last_activity: Arc::new(std::sync::Mutex::new(Instant::now())),
+            confirmation_deadline: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+    
+    /// Time since the last request through this handle or its clones started or finished
+    pub fn idle_for(&self) -> Duration {
+        match self.last_activity.lock() {
+            Ok(last) => last.elapsed(),
+            Err(poisoned) => poisoned.into_inner().elapsed(),
+        }
+    }
+    
+    /// Whether another clone of this handle exists. A caller that is about to
+    /// use the worker, or is waiting on it, always holds a clone.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.last_activity) > 1
+    }
+    
+    fn touch(&self) {
+        match self.last_activity.lock() {
+            Ok(mut last) => *last = Instant::now(),
+            Err(poisoned) => *poisoned.into_inner() = Instant::now(),
+        }
+    }
+    
+    /// Share `screen_hint` with the worker that records it
+    pub fn with_screen_hint(mut self, screen_hint: ScreenHintCell) -> Self {
+        self.screen_hint = screen_hint;
+        self
+    }
+    
+    /// What the device screen is asking for right now. Doesn't go through the
+    /// queue, so it answers while the worker waits on a button press.
+    pub fn screen_hint(&self) -> ScreenHint {
+        self.screen_hint.get()
+    }
+    
+    /// `ButtonRequest`s the device sends to this worker from now on, with their
+    /// type and data, while the worker answers them
+    pub fn subscribe_button_prompts(&self) -> tokio::sync::broadcast::Receiver<crate::screen_hint::ButtonPrompt> {
+        self.screen_hint.subscribe_button_prompts()
+    }
+    
+    /// Why the device asks for its PIN, for each `PinMatrixRequest` it sends to
+    /// this worker from now on
+    pub fn subscribe_pin_prompts(&self) -> tokio::sync::broadcast::Receiver<crate::screen_hint::PinPurpose> {
+        self.screen_hint.subscribe_pin_prompts()
+    }
+    
+    /// Each label the device asks to confirm from now on, as this worker sent
+    /// it in `ApplySettings`
+    pub fn subscribe_label_prompts(&self) -> tokio::sync::broadcast::Receiver<String> {
+        self.screen_hint.subscribe_label_prompts()
+    }
+    
+    /// Share `cancel` with the worker that acts on it
+    pub fn with_cancel(mut self, cancel: CancelCell) -> Self {
+        self.cancel = cancel;
+        self
+    }
+    
+    /// Cancel whatever the device screen is asking for. The worker writes
+    /// `Cancel` while it waits on a button prompt, so the prompt on screen is
+    /// the one cancelled; PIN, passphrase and recovery word prompts wait on
+    /// the host, so a `Cancel` is queued for them.
+    /// Returns false when nothing is on screen.
+    pub async fn cancel_prompt(&self) -> bool {
+        match self.screen_hint() {
+            ScreenHint::Idle => false,
+            ScreenHint::EnterPin | ScreenHint::EnterPassphrase | ScreenHint::EnterRecoveryWord => {
+                if let Err(e) = self.send_raw(crate::messages::Cancel {}.into(), true).await {
+                    warn!("Failed to cancel prompt on {}: {}", self.device_id, e);
+                }
+                true
+            }
+            _ => {
+                self.cancel.request();
+                true
+            }
+        }
+    }
+    
+    /// Check every raw message sent through this handle, its clones and its
+    /// exclusive sessions with `gate` before queuing it
+    pub fn with_message_gate(mut self, gate: Arc<dyn MessageGate>) -> Self {
+        self.gate = GateCell::new(gate);
+        self
+    }
+    
+    /// Use `timeouts` instead of the defaults for every call made through this handle
+    pub fn with_timeout_profile(mut self, timeouts: TimeoutProfile) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+    
+    pub fn timeout_profile(&self) -> &TimeoutProfile {
+        &self.timeouts
+    }
+    
+    /// When the request waiting on a confirmation on the device times out;
+    /// `None` while no such request runs
+    pub fn confirmation_deadline(&self) -> Option<Instant> {
+        match self.confirmation_deadline.lock() {
+            Ok(deadline) => *deadline,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+    
+    fn set_confirmation_deadline(&self, update: impl FnOnce(&mut Option<Instant>)) {
+        match self.confirmation_deadline.lock() {
+            Ok(mut deadline) => update(&mut deadline),
+            Err(poisoned) => update(&mut poisoned.into_inner()),
+        }
+    }
+    
+    /// Queue `cmd` on the worker. Refused when this task is running an
+    /// exclusive session on the same worker: the command would queue behind the
+    /// session and wait for it forever.
+    async fn send(&self, cmd: DeviceCmd) -> Result<()> {
+        let held = HELD_WORKERS
+            .try_with(|held| held.iter().any(|tx| tx.same_channel(&self.cmd_tx)))
+            .unwrap_or(false);
+        if held {
+            return Err(anyhow!(
+                "Device {} is held by an exclusive session: send through the session's handle",
+                self.device_id
+            ));
+        }
+        self.cmd_tx.send(cmd).await
+            .map_err(|_| anyhow!("Device worker unavailable"))
+    }
+    
+    /// Queue `cmd` and wait for its response, for `timeout_override` or the
+    /// profile's timeout for `kind`
+    async fn request<T>(
+        &self,
+        cmd: DeviceCmd,
+        rx: oneshot::Receiver<Result<T>>,
+        kind: OperationKind,
+        timeout_override: Option<Duration>,
+    ) -> Result<T> {
+        self.touch();
+        let result = async {
+            self.send(cmd).await?;
+            
+            let after = timeout_override.unwrap_or_else(|| self.timeouts.timeout_for(kind));
+            let deadline = kind.requires_confirmation().then(|| Instant::now() + after);
+            if let Some(deadline) = deadline {
+                self.set_confirmation_deadline(|current| *current = Some(deadline));
+            }
+            let response = timeout(after, rx).await;
+            // A later request may have replaced the deadline; leave that one
+            if let Some(deadline) = deadline {
+                self.set_confirmation_deadline(|current| if *current == Some(deadline) { *current = None });
+            }
+            response
+                .map_err(|_| anyhow::Error::new(QueueTimeout::new(kind, after)))?
+                .map_err(|_| anyhow!("Device worker channel closed"))?
+        }
+        .await;
+        self.touch();
+        result
     }
     
     /// Get device features
     #[instrument(level = "debug", skip(self))]
     pub async fn get_features(&self) -> Result<Features> {
+        self.get_features_timeout(None).await
+    }
+    
+    /// Get device features, waiting up to `after` instead of the profile's timeout
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_features_with_timeout(&self, after: Duration) -> Result<Features> {
+        self.get_features_timeout(Some(after)).await
+    }
+    
+    async fn get_features_timeout(&self, timeout_override: Option<Duration>) -> Result<Features> {
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::GetFeatures {
             respond_to: tx,
             enqueued_at: Instant::now(),
         };
-        
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
-            
-        timeout(DEVICE_OPERATION_TIMEOUT, rx).await
-            .map_err(|_| anyhow!("Device operation timed out"))?
-            .map_err(|_| anyhow!("Device worker channel closed"))?
+        self.request(cmd, rx, OperationKind::GetFeatures, timeout_override).await
     }
     
     /// Get address for given path
     #[instrument(level = "debug", skip(self))]
     pub async fn get_address(&self, path: Vec<u32>, coin_name: String, script_type: Option<i32>, show_display: Option<bool>) -> Result<String> {
+        let kind = if show_display == Some(true) { OperationKind::GetAddressDisplay } else { OperationKind::Read };
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::GetAddress {
             path,
@@ -765,18 +1297,38 @@ impl DeviceQueueHandle {
             respond_to: tx,
             enqueued_at: Instant::now(),
         };
-        
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
-            
-        timeout(DEVICE_OPERATION_TIMEOUT, rx).await
-            .map_err(|_| anyhow!("Device operation timed out"))?
-            .map_err(|_| anyhow!("Device worker channel closed"))?
+        self.request(cmd, rx, kind, None).await
     }
     
-    /// Send raw message to device
+    /// Send raw message to device. The timeout follows the message's
+    /// `OperationKind`, so signing and on-device confirmations get minutes.
     #[instrument(level = "debug", skip(self, message))]
     pub async fn send_raw(&self, message: Message, bypass_cache: bool) -> Result<Message> {
+        self.send_raw_timeout(message, bypass_cache, None).await
+    }
+    
+    /// Send raw message to device, waiting up to `after` instead of the profile's timeout
+    #[instrument(level = "debug", skip(self, message))]
+    pub async fn send_raw_with_timeout(&self, message: Message, bypass_cache: bool, after: Duration) -> Result<Message> {
+        self.send_raw_timeout(message, bypass_cache, Some(after)).await
+    }
+    
+    async fn send_raw_timeout(&self, message: Message, bypass_cache: bool, timeout_override: Option<Duration>) -> Result<Message> {
+        if let Err(refused) = self.gate.check(&self.device_id, &message).await {
+            warn!("🚫 {:?} to {} refused: {}", message.message_type(), self.device_id, refused);
+            // A refused acknowledgement leaves the device mid-flow; send it home
+            if matches!(message, Message::TxAck(_) | Message::RawTxAck(_)) {
+                if let Err(e) = self.queue_raw(crate::messages::Cancel {}.into(), true, None).await {
+                    warn!("Failed to cancel the flow on {}: {}", self.device_id, e);
+                }
+            }
+            return Err(refused.into());
+        }
+        self.queue_raw(message, bypass_cache, timeout_override).await
+    }
+    
+    async fn queue_raw(&self, message: Message, bypass_cache: bool, timeout_override: Option<Duration>) -> Result<Message> {
+        let kind = OperationKind::for_message(&message);
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::SendRaw {
             message,
@@ -784,13 +1336,19 @@ impl DeviceQueueHandle {
             enqueued_at: Instant::now(),
             bypass_cache,
         };
-        
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
-            
-        timeout(DEVICE_OPERATION_TIMEOUT, rx).await
-            .map_err(|_| anyhow!("Device operation timed out"))?
-            .map_err(|_| anyhow!("Device worker channel closed"))?
+        self.request(cmd, rx, kind, timeout_override).await
+    }
+    
+    /// Master fingerprint of the wallet open on the device. With passphrase
+    /// protection this differs per passphrase; it is fetched once per session.
+    #[instrument(level = "debug", skip(self))]
+    pub async fn get_master_fingerprint(&self) -> Result<u32> {
+        let (tx, rx) = oneshot::channel();
+        let cmd = DeviceCmd::GetMasterFingerprint {
+            respond_to: tx,
+            enqueued_at: Instant::now(),
+        };
+        self.request(cmd, rx, OperationKind::Read, None).await
     }
     
     /// Update device bootloader
@@ -803,14 +1361,7 @@ impl DeviceQueueHandle {
             respond_to: tx,
             enqueued_at: Instant::now(),
         };
-        
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
-            
-        // Use longer timeout for firmware operations (2 minutes)
-        timeout(Duration::from_secs(120), rx).await
-            .map_err(|_| anyhow!("Bootloader update timed out"))?
-            .map_err(|_| anyhow!("Device worker channel closed"))?
+        self.request(cmd, rx, OperationKind::FirmwareUpdate, None).await
     }
     
     /// Update device firmware
@@ -823,23 +1374,57 @@ impl DeviceQueueHandle {
             respond_to: tx,
             enqueued_at: Instant::now(),
         };
+        self.request(cmd, rx, OperationKind::FirmwareUpdate, None).await
+    }
+    
+    /// Drop the worker's cached addresses and other responses
+    pub async fn clear_cache(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let cmd = DeviceCmd::ClearCache { respond_to: tx };
         
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
+        self.send(cmd).await?;
             
-        // Use longer timeout for firmware operations (2 minutes)
-        timeout(Duration::from_secs(120), rx).await
-            .map_err(|_| anyhow!("Firmware update timed out"))?
+        timeout(Duration::from_secs(5), rx).await
+            .map_err(|_| anyhow!("Clear cache timed out"))?
             .map_err(|_| anyhow!("Device worker channel closed"))?
     }
     
+    /// Run `session` with the worker to itself: commands sent through the handle
+    /// it is given run in order with nothing from other callers in between, so a
+    /// composite flow (several xpubs, then a signature) can't be interleaved.
+    /// Other callers queue behind it as usual. The worker is released when the
+    /// session handle and all its clones are dropped, which also happens when
+    /// `session` panics; don't move a clone into a task that outlives it.
+    ///
+    /// Inside `session`, only use the handle it is given. A command sent through
+    /// the outer handle (`self` or a clone of it) would queue behind the session
+    /// itself and never run, so it fails straight away with an error instead.
+    /// That check doesn't reach tasks spawned by `session`.
+    pub async fn with_exclusive_session<F, Fut, T>(&self, session: F) -> Result<T>
+    where
+        F: FnOnce(DeviceQueueHandle) -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        let (session_tx, session_rx) = mpsc::channel(QUEUE_CHANNEL_SIZE);
+        let (tx, rx) = oneshot::channel();
+        let cmd = DeviceCmd::ExclusiveSession { session_rx, respond_to: tx, enqueued_at: Instant::now() };
+        
+        self.send(cmd).await?;
+        rx.await
+            .map_err(|_| anyhow!("Device worker channel closed"))??;
+        
+        let handle = DeviceQueueHandle { cmd_tx: session_tx, ..self.clone() };
+        let mut held = HELD_WORKERS.try_with(Clone::clone).unwrap_or_default();
+        held.push(self.cmd_tx.clone());
+        Ok(HELD_WORKERS.scope(held, session(handle)).await)
+    }
+    
     /// Shutdown the device worker
     pub async fn shutdown(&self) -> Result<()> {
         let (tx, rx) = oneshot::channel();
         let cmd = DeviceCmd::Shutdown { respond_to: tx };
         
-        self.cmd_tx.send(cmd).await
-            .map_err(|_| anyhow!("Device worker unavailable"))?;
+        self.send(cmd).await?;
             
         timeout(Duration::from_secs(5), rx).await
             .map_err(|_| anyhow!("Shutdown timed out"))?
@@ -857,18 +1442,30 @@ pub struct DeviceQueueFactory;
 impl DeviceQueueFactory {
     /// Spawn a new device worker and return a handle to it
     pub fn spawn_worker(device_id: String, device_info: FriendlyUsbDevice) -> DeviceQueueHandle {
+        Self::spawn_worker_with_config(device_id, device_info, TransportConfig::default())
+    }
+    
+    /// Spawn a worker whose transports retry writes as `transport_config` says
+    pub fn spawn_worker_with_config(device_id: String, device_info: FriendlyUsbDevice, transport_config: TransportConfig) -> DeviceQueueHandle {
         let (cmd_tx, cmd_rx) = mpsc::channel(QUEUE_CHANNEL_SIZE);
         
-        let worker = DeviceWorker::new(device_id.clone(), device_info, cmd_rx);
+        let screen_hint = ScreenHintCell::default();
+        let cancel = CancelCell::default();
+        let worker = DeviceWorker::new(device_id.clone(), device_info, cmd_rx, screen_hint.clone(), cancel.clone(), transport_config);
         
         // Spawn the worker task
         tokio::spawn(worker.run());
         
-        DeviceQueueHandle::new(device_id, cmd_tx)
+        DeviceQueueHandle::new(device_id, cmd_tx).with_screen_hint(screen_hint).with_cancel(cancel)
     }
     
     /// Create transport with WebUSB/USB/HID auto-detection
     pub fn create_transport_for_device(device_info: &FriendlyUsbDevice) -> Result<Box<dyn ProtocolAdapter + Send>> {
+        Self::create_transport_with_config(device_info, TransportConfig::default())
+    }
+    
+    /// `create_transport_for_device` with the write retries of `config`
+    pub fn create_transport_with_config(device_info: &FriendlyUsbDevice, config: TransportConfig) -> Result<Box<dyn ProtocolAdapter + Send>> {
         // Find physical device for transport
         let devices = crate::features::list_devices();
         let physical_device = Self::find_physical_device_by_info(device_info, &devices)?;
@@ -883,12 +1480,12 @@ impl DeviceQueueFactory {
                 match crate::transport::WebUsbTransport::new(&physical_device, 0) {
                     Ok((transport, _, _)) => {
                         info!("✅ Successfully created WebUSB transport for device {}", device_info.unique_id);
-                        Ok(Box::new(transport))
+                        Ok(Box::new(RetryingTransport::new(transport, config)))
                     }
                     Err(webusb_err) => {
                         error!("❌ WebUSB transport creation failed for device {}: {}", device_info.unique_id, webusb_err);
                         warn!("⚠️ WebUSB transport failed for device {}: {}, trying HID fallback", device_info.unique_id, webusb_err);
-                        Self::try_hid_fallback(device_info, webusb_err.to_string())
+                        Self::try_hid_fallback(device_info, webusb_err.to_string(), config)
                     }
                 }
             }
@@ -897,17 +1494,17 @@ impl DeviceQueueFactory {
                 match crate::transport::UsbTransport::new(&physical_device, 0) {
                     Ok((transport, _, _)) => {
                         info!("✅ Created USB transport for device {}", device_info.unique_id);
-                        Ok(Box::new(transport))
+                        Ok(Box::new(RetryingTransport::new(transport, config)))
                     }
                     Err(usb_err) => {
                         warn!("⚠️ USB transport failed for device {}: {}, trying HID fallback", device_info.unique_id, usb_err);
-                        Self::try_hid_fallback(device_info, usb_err.to_string())
+                        Self::try_hid_fallback(device_info, usb_err.to_string(), config)
                     }
                 }
             }
             TransportType::HidOnly => {
                 info!("🎛️ Device requires HID transport, using HID for {}", device_info.unique_id);
-                Self::try_hid_fallback(device_info, "Device requires HID transport".to_string())
+                Self::try_hid_fallback(device_info, "Device requires HID transport".to_string(), config)
             }
         }
     }
@@ -990,7 +1587,7 @@ impl DeviceQueueFactory {
     }
     
     /// Try HID transport as fallback
-    fn try_hid_fallback(device_info: &FriendlyUsbDevice, previous_error: String) -> Result<Box<dyn ProtocolAdapter + Send>> {
+    fn try_hid_fallback(device_info: &FriendlyUsbDevice, previous_error: String, config: TransportConfig) -> Result<Box<dyn ProtocolAdapter + Send>> {
         // Check if this is a Windows FIDO blocklist error
         #[cfg(target_os = "windows")]
         {
@@ -1006,7 +1603,7 @@ impl DeviceQueueFactory {
         match crate::transport::HidTransport::new_for_device(device_info.serial_number.as_deref()) {
             Ok(hid_transport) => {
                 info!("✅ Created HID transport for device {}", device_info.unique_id);
-                Ok(Box::new(hid_transport))
+                Ok(Box::new(RetryingTransport::new(hid_transport, config)))
             }
             Err(hid_err) => {
                 Err(anyhow!("Failed with both primary transport ({}) and HID fallback ({})", previous_error, hid_err))
@@ -1062,4 +1659,322 @@ impl DeviceQueueFactory {
         Err(anyhow!("Physical device not found for {} (VID: 0x{:04x}, PID: 0x{:04x}, Serial: {:?})", 
                     device_info.unique_id, device_info.vid, device_info.pid, device_info.serial_number))
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker() -> DeviceWorker {
+        let (_tx, rx) = mpsc::channel(1);
+        let device = FriendlyUsbDevice::new("test".to_string(), 0x2b24, 0x0002, None, None, None);
+        DeviceWorker::new("test".to_string(), device, rx, ScreenHintCell::default(), CancelCell::default(), TransportConfig::default())
+    }
+
+    /// Worker that runs commands as the real one does, without a device
+    fn spawn_test_worker() -> DeviceQueueHandle {
+        let (cmd_tx, cmd_rx) = mpsc::channel(QUEUE_CHANNEL_SIZE);
+        let device = FriendlyUsbDevice::new("test".to_string(), 0x2b24, 0x0002, None, None, None);
+        let mut worker = DeviceWorker::new("test".to_string(), device, cmd_rx, ScreenHintCell::default(), CancelCell::default(), TransportConfig::default());
+        tokio::spawn(async move {
+            while let Some(cmd) = worker.cmd_rx.recv().await {
+                let _ = worker.process_command(cmd).await;
+            }
+        });
+        DeviceQueueHandle::new("test".to_string(), cmd_tx)
+    }
+
+    fn cache_address(worker: &mut DeviceWorker, params: &[u8], address: &str) {
+        let key = CacheKey::new(worker.device_id.clone(), worker.wallet_fingerprint, "get_address", params);
+        worker.cache.insert(key, CachedResponse::new(serde_json::json!(address)));
+    }
+
+    fn cached_address(worker: &DeviceWorker, params: &[u8]) -> Option<String> {
+        let key = CacheKey::new(worker.device_id.clone(), worker.wallet_fingerprint, "get_address", params);
+        worker.cached_value(&key).and_then(|v| v.as_str().map(str::to_string))
+    }
+
+    #[test]
+    fn test_fingerprint_matches_bip32_test_vector() {
+        // BIP-32 test vector 1, chain m
+        let master = hex::decode("0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2").unwrap();
+        assert_eq!(fingerprint_of(&master), 0x3442193e);
+    }
+
+    #[test]
+    fn test_passphrase_wallets_do_not_share_cached_derivations() {
+        let mut worker = worker();
+        let path = [0x8000_0054u32.to_le_bytes(), 0x8000_0000u32.to_le_bytes()].concat();
+
+        // Passphrase A
+        worker.wallet_fingerprint = Some(0xaaaa_aaaa);
+        cache_address(&mut worker, &path, "bc1q-wallet-a");
+        assert_eq!(cached_address(&worker, &path).as_deref(), Some("bc1q-wallet-a"));
+
+        // Session cleared: nothing is served until the new wallet is known
+        assert!(DeviceWorker::is_session_change(&crate::messages::ClearSession::default().into()));
+        worker.reset_wallet_session();
+        assert_eq!(cached_address(&worker, &path), None);
+
+        // Passphrase B derives the same path to a different address
+        worker.wallet_fingerprint = Some(0xbbbb_bbbb);
+        assert_eq!(cached_address(&worker, &path), None);
+        cache_address(&mut worker, &path, "bc1q-wallet-b");
+        assert_eq!(cached_address(&worker, &path).as_deref(), Some("bc1q-wallet-b"));
+
+        // Back to A: its own derivation, not B's
+        worker.reset_wallet_session();
+        worker.wallet_fingerprint = Some(0xaaaa_aaaa);
+        assert_eq!(cached_address(&worker, &path).as_deref(), Some("bc1q-wallet-a"));
+    }
+
+    #[test]
+    fn test_session_reset_drops_derivations_of_unknown_wallet() {
+        let mut worker = worker();
+        let path = 0u32.to_le_bytes();
+
+        cache_address(&mut worker, &path, "made-before-fingerprint-known");
+        worker.reset_wallet_session();
+        assert_eq!(cached_address(&worker, &path), None);
+        assert!(worker.cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_message_is_refused_before_the_device() {
+        let mut worker = worker();
+        let segwit: Message = GetAddress {
+            script_type: Some(crate::messages::InputScriptType::Spendwitness as i32),
+            ..Default::default()
+        }.into();
+
+        // Nothing negotiated yet: not refused up front
+        assert!(worker.check_supported(&segwit).is_ok());
+
+        let features = Features { major_version: Some(5), minor_version: Some(11), patch_version: Some(0), ..Default::default() };
+        worker.record_protocol(&features);
+        // Fails without opening a transport (there is no device to open)
+        let err = worker.handle_send_raw(segwit, false).await.unwrap_err();
+        assert!(err.to_string().contains("firmware 5.11.0"), "{}", err);
+        assert!(worker.transport.is_none());
+    }
+
+    #[test]
+    fn test_protocol_is_not_asked_in_the_middle_of_a_device_flow() {
+        assert!(DeviceWorker::needs_protocol(&GetAddress::default().into()));
+        assert!(DeviceWorker::needs_protocol(&crate::messages::SignTx::default().into()));
+        assert!(!DeviceWorker::needs_protocol(&crate::messages::PinMatrixAck::default().into()));
+        assert!(!DeviceWorker::needs_protocol(&crate::messages::TxAck::default().into()));
+        assert!(!DeviceWorker::needs_protocol(&GetFeatures::default().into()));
+    }
+
+    #[test]
+    fn test_operation_kinds_and_timeouts() {
+        let profile = TimeoutProfile::default();
+        let kind_of = |message: Message| OperationKind::for_message(&message);
+
+        assert_eq!(kind_of(GetFeatures::default().into()), OperationKind::GetFeatures);
+        assert_eq!(profile.timeout_for(OperationKind::GetFeatures), Duration::from_secs(5));
+
+        let mut get_address = GetAddress::default();
+        assert_eq!(kind_of(get_address.clone().into()), OperationKind::Read);
+        get_address.show_display = Some(true);
+        assert_eq!(kind_of(get_address.into()), OperationKind::GetAddressDisplay);
+        assert_eq!(profile.timeout_for(OperationKind::GetAddressDisplay), Duration::from_secs(120));
+        // The device asks before it hands out entropy
+        assert_eq!(kind_of(crate::messages::GetEntropy { size: 32 }.into()), OperationKind::Confirm);
+
+        assert_eq!(kind_of(crate::messages::SignTx::default().into()), OperationKind::Sign);
+        assert_eq!(kind_of(crate::messages::TxAck::default().into()), OperationKind::Sign);
+        assert_eq!(profile.timeout_for(OperationKind::Sign), Duration::from_secs(300));
+        assert_eq!(kind_of(crate::messages::WipeDevice::default().into()), OperationKind::Wipe);
+        assert_eq!(profile.timeout_for(OperationKind::Wipe), Duration::from_secs(120));
+
+        assert!(OperationKind::Sign.requires_confirmation());
+        assert!(!OperationKind::GetFeatures.requires_confirmation());
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_distinguish_confirmation_from_transport() {
+        // A worker that never answers
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Some(cmd) = cmd_rx.recv().await {
+                held.push(cmd);
+            }
+        });
+        let handle = DeviceQueueHandle::new("test".to_string(), cmd_tx).with_timeout_profile(TimeoutProfile {
+            get_features: Duration::from_millis(10),
+            sign: Duration::from_millis(10),
+            ..TimeoutProfile::default()
+        });
+
+        let err = handle.get_features().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<QueueTimeout>(), Some(QueueTimeout::Timeout { .. })));
+
+        let signing = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_raw(crate::messages::SignTx::default().into(), true).await }
+        });
+        let deadline = loop {
+            match handle.confirmation_deadline() {
+                Some(deadline) => break deadline,
+                None => tokio::task::yield_now().await,
+            }
+        };
+        assert!(deadline <= Instant::now() + Duration::from_millis(10));
+        let err = signing.await.unwrap().unwrap_err();
+        assert!(matches!(err.downcast_ref::<QueueTimeout>(), Some(QueueTimeout::ConfirmationTimeout { .. })));
+        // The deadline goes with the request
+        assert_eq!(handle.confirmation_deadline(), None);
+
+        // A per-call override wins over the profile
+        let err = handle.get_features_with_timeout(Duration::from_millis(20)).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<QueueTimeout>(),
+            Some(&QueueTimeout::Timeout { kind: OperationKind::GetFeatures, after: Duration::from_millis(20) })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_tracks_clones_and_activity() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let DeviceCmd::GetFeatures { respond_to, .. } = cmd {
+                    let _ = respond_to.send(Ok(Features::default()));
+                }
+            }
+        });
+        let handle = DeviceQueueHandle::new("test".to_string(), cmd_tx);
+        assert!(!handle.is_shared());
+        
+        let caller = handle.clone();
+        assert!(handle.is_shared());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(handle.idle_for() >= Duration::from_millis(30));
+        
+        // Activity through a clone counts for every clone
+        caller.get_features().await.unwrap();
+        assert!(handle.idle_for() < Duration::from_millis(30));
+        drop(caller);
+        assert!(!handle.is_shared());
+    }
+
+    /// Refuses wipes and signature acknowledgements
+    struct NoWipes;
+
+    #[async_trait::async_trait]
+    impl MessageGate for NoWipes {
+        async fn check(&self, _device_id: &str, message: &Message) -> Result<(), String> {
+            match message {
+                Message::WipeDevice(_) | Message::TxAck(_) => Err("PolicyDenied: no wipes".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gate_refuses_raw_messages_before_queuing() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel(8);
+        let sent = Arc::new(std::sync::Mutex::new(Vec::new()));
+        tokio::spawn({
+            let sent = sent.clone();
+            async move {
+                while let Some(cmd) = cmd_rx.recv().await {
+                    if let DeviceCmd::SendRaw { message, respond_to, .. } = cmd {
+                        sent.lock().unwrap().push(message.message_type());
+                        let _ = respond_to.send(Ok(crate::messages::Success::default().into()));
+                    }
+                }
+            }
+        });
+        let handle = DeviceQueueHandle::new("test".to_string(), cmd_tx).with_message_gate(Arc::new(NoWipes));
+
+        let err = handle.send_raw(crate::messages::WipeDevice::default().into(), true).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<crate::gate::MessageRefused>().map(|e| e.0.as_str()), Some("PolicyDenied: no wipes"));
+        assert!(sent.lock().unwrap().is_empty());
+
+        // Clones share the gate
+        assert!(handle.clone().send_raw(crate::messages::WipeDevice::default().into(), true).await.is_err());
+        assert!(sent.lock().unwrap().is_empty());
+
+        // A refused acknowledgement cancels the flow it was part of
+        assert!(handle.send_raw(crate::messages::TxAck::default().into(), false).await.is_err());
+        handle.send_raw(crate::messages::Ping::default().into(), true).await.unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [crate::messages::MessageType::Cancel, crate::messages::MessageType::Ping]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_session_runs_without_interleaving() {
+        let handle = spawn_test_worker();
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // Queues once the session holds the worker
+        let (held_tx, held_rx) = oneshot::channel();
+        let outsider = {
+            let (handle, order) = (handle.clone(), order.clone());
+            tokio::spawn(async move {
+                held_rx.await.unwrap();
+                handle.clear_cache().await.unwrap();
+                order.lock().unwrap().push("outsider");
+            })
+        };
+        handle
+            .with_exclusive_session(|session| {
+                let order = order.clone();
+                async move {
+                    held_tx.send(()).unwrap();
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    session.clear_cache().await.unwrap();
+                    session.clear_cache().await.unwrap();
+                    order.lock().unwrap().push("session");
+                }
+            })
+            .await
+            .unwrap();
+        outsider.await.unwrap();
+        assert_eq!(*order.lock().unwrap(), ["session", "outsider"]);
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_session_released_after_panic() {
+        let handle = spawn_test_worker();
+        let panicking = handle.clone();
+        let outcome = tokio::spawn(async move {
+            panicking
+                .with_exclusive_session(|session| async move {
+                    let _held = session;
+                    panic!("flow failed")
+                })
+                .await
+        })
+        .await;
+        assert!(outcome.unwrap_err().is_panic());
+
+        // The worker takes other callers again
+        tokio::time::timeout(Duration::from_secs(1), handle.clear_cache()).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_outer_handle_inside_exclusive_session_fails_fast() {
+        let handle = spawn_test_worker();
+        let outer = handle.clone();
+        let (from_outer, from_session) = handle
+            .with_exclusive_session(|session| async move {
+                let from_outer = tokio::time::timeout(Duration::from_secs(1), outer.clear_cache()).await;
+                (from_outer, session.clear_cache().await)
+            })
+            .await
+            .unwrap();
+        let error = from_outer.expect("must fail, not wait for the session").unwrap_err();
+        assert!(error.to_string().contains("held by an exclusive session"), "{}", error);
+        assert!(from_session.is_ok());
+
+        // Only while the session runs
+        handle.clear_cache().await.unwrap();
+    }
+}
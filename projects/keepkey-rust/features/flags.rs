@@ -0,0 +1,237 @@
+//! Persistent device flags.
+//!
+//! KeepKey firmware stores its persistent toggles (experimental features,
+//! advanced mode, ...) as named policies set with `ApplyPolicies`. This module
+//! maps them onto a `u32` bit set so callers can treat them as flags, and
+//! `FirmwareFlags` names the bits for display.
+
+use serde::{Deserialize, Serialize};
+
+/// A policy exposed as a flag bit
+#[derive(Debug, Clone, Copy)]
+pub struct PolicyFlag {
+    pub bit: u32,
+    /// Policy name as reported in `Features.policies`
+    pub policy: &'static str,
+    /// First firmware version that understands the policy
+    pub min_firmware: (u64, u64, u64),
+}
+
+pub const FLAG_SHAPESHIFT: u32 = 1 << 0;
+pub const FLAG_PIN_CACHING: u32 = 1 << 1;
+pub const FLAG_EXPERIMENTAL: u32 = 1 << 2;
+pub const FLAG_ADVANCED_MODE: u32 = 1 << 3;
+
+pub const POLICY_FLAGS: &[PolicyFlag] = &[
+    PolicyFlag { bit: FLAG_SHAPESHIFT, policy: "ShapeShift", min_firmware: (4, 0, 0) },
+    PolicyFlag { bit: FLAG_PIN_CACHING, policy: "Pin Caching", min_firmware: (5, 0, 0) },
+    PolicyFlag { bit: FLAG_EXPERIMENTAL, policy: "Experimental", min_firmware: (6, 0, 0) },
+    PolicyFlag { bit: FLAG_ADVANCED_MODE, policy: "AdvancedMode", min_firmware: (6, 1, 0) },
+];
+
+/// The flag bits as named toggles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareFlags {
+    pub shapeshift: bool,
+    pub pin_caching: bool,
+    pub experimental: bool,
+    pub advanced_mode: bool,
+}
+
+impl FirmwareFlags {
+    /// Name the known bits of `flags`; unknown bits are dropped
+    pub fn from_bits(flags: u32) -> Self {
+        Self {
+            shapeshift: flags & FLAG_SHAPESHIFT != 0,
+            pin_caching: flags & FLAG_PIN_CACHING != 0,
+            experimental: flags & FLAG_EXPERIMENTAL != 0,
+            advanced_mode: flags & FLAG_ADVANCED_MODE != 0,
+        }
+    }
+
+    /// The bit set, as `apply_flags` takes it
+    pub fn bits(&self) -> u32 {
+        [
+            (self.shapeshift, FLAG_SHAPESHIFT),
+            (self.pin_caching, FLAG_PIN_CACHING),
+            (self.experimental, FLAG_EXPERIMENTAL),
+            (self.advanced_mode, FLAG_ADVANCED_MODE),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, bit)| flags | bit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagState {
+    Enabled,
+    /// Supported by the firmware but off
+    Disabled,
+    /// The firmware is too old for it (or can't apply policies at all)
+    Unsupported,
+}
+
+/// One flag as a settings toggle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlagStatus {
+    pub policy: &'static str,
+    pub bit: u32,
+    pub state: FlagState,
+    /// First firmware version with the flag, e.g. "6.1.0"
+    pub min_firmware: String,
+}
+
+/// State of every known flag given the enabled bits and the bits the
+/// firmware supports. An enabled flag is reported enabled even if `supported`
+/// says otherwise, since the device is the authority on what it has set.
+pub fn flag_statuses(flags: u32, supported: u32) -> Vec<FlagStatus> {
+    POLICY_FLAGS
+        .iter()
+        .map(|flag| {
+            let state = if flags & flag.bit != 0 {
+                FlagState::Enabled
+            } else if supported & flag.bit != 0 {
+                FlagState::Disabled
+            } else {
+                FlagState::Unsupported
+            };
+            let (major, minor, patch) = flag.min_firmware;
+            FlagStatus { policy: flag.policy, bit: flag.bit, state, min_firmware: format!("{}.{}.{}", major, minor, patch) }
+        })
+        .collect()
+}
+
+/// Flag bits for a list of enabled policy names (unknown policies are ignored)
+pub fn flags_from_policies(enabled: &[String]) -> u32 {
+    POLICY_FLAGS
+        .iter()
+        .filter(|flag| enabled.iter().any(|p| p == flag.policy))
+        .fold(0, |flags, flag| flags | flag.bit)
+}
+
+/// Flag bits for the raw `Features.policies` reported by the device
+pub fn flags_from_policy_types(policies: &[crate::messages::PolicyType]) -> u32 {
+    POLICY_FLAGS
+        .iter()
+        .filter(|flag| policies.iter().any(|p| p.enabled() && p.policy_name() == flag.policy))
+        .fold(0, |flags, flag| flags | flag.bit)
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.trim().trim_start_matches('v').split('.').map(|p| p.parse::<u64>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Some((major, minor, patch)),
+        _ => None,
+    }
+}
+
+/// Bits the given firmware version supports
+pub fn supported_flags(firmware_version: &str) -> u32 {
+    let Some(version) = parse_version(firmware_version) else {
+        return 0;
+    };
+    POLICY_FLAGS
+        .iter()
+        .filter(|flag| version >= flag.min_firmware)
+        .fold(0, |flags, flag| flags | flag.bit)
+}
+
+/// Reject bits that are unknown or not supported by the firmware
+pub fn validate_flags(flags: u32, firmware_version: &str) -> Result<(), String> {
+    let known = POLICY_FLAGS.iter().fold(0, |all, flag| all | flag.bit);
+    let unknown = flags & !known;
+    if unknown != 0 {
+        return Err(format!("Unknown flag bits 0x{:08x} (known flags: 0x{:08x})", unknown, known));
+    }
+
+    let unsupported: Vec<String> = POLICY_FLAGS
+        .iter()
+        .filter(|flag| flags & flag.bit != 0 && supported_flags(firmware_version) & flag.bit == 0)
+        .map(|flag| {
+            let (major, minor, patch) = flag.min_firmware;
+            format!("'{}' (0x{:x}) needs firmware {}.{}.{}", flag.policy, flag.bit, major, minor, patch)
+        })
+        .collect();
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Flags not supported by firmware {}: {}",
+            firmware_version,
+            unsupported.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Policies to send to move from `current` to `desired`, as (policy name, enabled)
+pub fn policy_changes(current: u32, desired: u32) -> Vec<(&'static str, bool)> {
+    POLICY_FLAGS
+        .iter()
+        .filter(|flag| (current ^ desired) & flag.bit != 0)
+        .map(|flag| (flag.policy, desired & flag.bit != 0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_round_trip_through_policies() {
+        let policies = vec!["Experimental".to_string(), "Pin Caching".to_string(), "Other".to_string()];
+        assert_eq!(flags_from_policies(&policies), FLAG_EXPERIMENTAL | FLAG_PIN_CACHING);
+
+        assert_eq!(
+            policy_changes(FLAG_PIN_CACHING, FLAG_EXPERIMENTAL),
+            vec![("Pin Caching", false), ("Experimental", true)]
+        );
+        assert!(policy_changes(FLAG_EXPERIMENTAL, FLAG_EXPERIMENTAL).is_empty());
+    }
+
+    #[test]
+    fn test_firmware_flags_from_bitmask() {
+        let flags = FirmwareFlags::from_bits(0b1010);
+        assert_eq!(
+            flags,
+            FirmwareFlags { shapeshift: false, pin_caching: true, experimental: false, advanced_mode: true }
+        );
+        // Unknown bits don't survive naming
+        assert_eq!(FirmwareFlags::from_bits(0b1010 | 1 << 20).bits(), 0b1010);
+
+        // Toggling a named flag and sending it back through apply_flags
+        // changes exactly that policy
+        let desired = FirmwareFlags { experimental: true, ..flags };
+        assert!(validate_flags(desired.bits(), "7.10.0").is_ok());
+        assert_eq!(policy_changes(flags.bits(), desired.bits()), vec![("Experimental", true)]);
+        let applied: Vec<String> = POLICY_FLAGS
+            .iter()
+            .filter(|flag| desired.bits() & flag.bit != 0)
+            .map(|flag| flag.policy.to_string())
+            .collect();
+        assert_eq!(FirmwareFlags::from_bits(flags_from_policies(&applied)), desired);
+
+        let states: Vec<_> = flag_statuses(flags.bits(), supported_flags("6.0.4")).into_iter().map(|s| s.state).collect();
+        assert_eq!(
+            states,
+            vec![FlagState::Disabled, FlagState::Enabled, FlagState::Disabled, FlagState::Enabled]
+        );
+        let states: Vec<_> = flag_statuses(FLAG_PIN_CACHING, supported_flags("5.0.0")).into_iter().map(|s| s.state).collect();
+        assert_eq!(
+            states,
+            vec![FlagState::Disabled, FlagState::Enabled, FlagState::Unsupported, FlagState::Unsupported]
+        );
+    }
+
+    #[test]
+    fn test_validate_flags() {
+        assert!(validate_flags(FLAG_EXPERIMENTAL | FLAG_ADVANCED_MODE, "7.10.0").is_ok());
+        assert!(validate_flags(0, "0.0.0").is_ok());
+
+        let err = validate_flags(1 << 31, "7.10.0").unwrap_err();
+        assert!(err.contains("Unknown flag bits"));
+
+        let err = validate_flags(FLAG_ADVANCED_MODE, "6.0.4").unwrap_err();
+        assert!(err.contains("AdvancedMode"));
+    }
+}
@@ -11,6 +11,9 @@ use crate::messages::{Initialize, Message};
 use crate::transport::{ProtocolAdapter, UsbTransport, HidTransport};
 use crate::friendly_usb::FriendlyUsbDevice;
 
+pub mod flags;
+pub mod naming;
+pub mod sanitize;
 
 const TAG: &str = " | features | ";
 const DEVICE_IDS: &[(u16, u16)] = &[(0x2b24, 0x0001), (0x2b24, 0x0002)];
@@ -33,6 +36,14 @@ struct CachedDeviceInfo {
 static DEVICE_CACHE: Lazy<Arc<Mutex<HashMap<String, CachedDeviceInfo>>>> = 
     Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
+/// USB ports (bus, port path) a KeepKey has been enumerated on. A device that
+/// shows up in raw DFU mode on one of these is the KeepKey that was there.
+static KEEPKEY_PORTS: Lazy<Mutex<HashSet<(u8, Vec<u8>)>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+fn usb_port(device: &Device<GlobalContext>) -> Option<(u8, Vec<u8>)> {
+    device.port_numbers().ok().map(|ports| (device.bus_number(), ports))
+}
+
 /// Clean expired entries from the device cache (older than 30 seconds)
 fn clean_device_cache() {
     if let Ok(mut cache) = DEVICE_CACHE.lock() {
@@ -105,6 +116,23 @@ pub struct DeviceFeatures {
     pub auto_lock_delay_ms: Option<u64>,
     /// Enabled policies
     pub policies: Vec<String>,
+    /// Enabled policies as flag bits (see `features::flags`)
+    #[serde(default)]
+    pub flags: u32,
+    /// Bitcoin networks the firmware derives and signs for ("mainnet",
+    /// "testnet"), from its coin table
+    #[serde(default)]
+    pub networks: Vec<String>,
+}
+
+/// Networks in a firmware's coin table: "mainnet" for Bitcoin, "testnet" for Testnet
+pub fn networks_from_coins(coins: &[crate::messages::CoinType]) -> Vec<String> {
+    let has = |name: &str| coins.iter().any(|coin| coin.coin_name.as_deref() == Some(name));
+    [("Bitcoin", "mainnet"), ("Testnet", "testnet")]
+        .into_iter()
+        .filter(|(coin, _)| has(coin))
+        .map(|(_, network)| network.to_string())
+        .collect()
 }
 
 /// Get device features from a specific KeepKey device
@@ -225,7 +253,10 @@ pub fn get_device_features_for_device(target_device: &FriendlyUsbDevice) -> Resu
 
     // Extract features from response
     let features = match features_msg {
-        Message::Features(f) => f,
+        Message::Features(mut f) => {
+            sanitize::sanitize_features(&mut f);
+            f
+        }
         _ => return Err(anyhow!("Unexpected response from device {}", target_device.unique_id)),
     };
 
@@ -262,6 +293,8 @@ pub fn get_device_features_for_device(target_device: &FriendlyUsbDevice) -> Resu
         passphrase_cached: features.passphrase_cached.unwrap_or(false),
         wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
         auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
+        flags: flags::flags_from_policy_types(&features.policies),
+        networks: networks_from_coins(&features.coins),
         policies: features
             .policies
             .into_iter()
@@ -300,7 +333,10 @@ pub fn get_device_features_impl() -> Result<DeviceFeatures> {
 
     // Extract features from response
     let features = match features_msg {
-        Message::Features(f) => f,
+        Message::Features(mut f) => {
+            sanitize::sanitize_features(&mut f);
+            f
+        }
         _ => return Err(anyhow!("Unexpected response from device")),
     };
 
@@ -337,6 +373,8 @@ pub fn get_device_features_impl() -> Result<DeviceFeatures> {
         passphrase_cached: features.passphrase_cached.unwrap_or(false),
         wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
         auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
+        flags: flags::flags_from_policy_types(&features.policies),
+        networks: networks_from_coins(&features.coins),
         policies: features
             .policies
             .into_iter()
@@ -465,7 +503,10 @@ pub fn get_device_features_via_hid(target_device: &FriendlyUsbDevice) -> Result<
                 match adapter.handle(init_msg) {
                     Ok(features_msg) => {
                         let features = match features_msg {
-                            Message::Features(f) => f,
+                            Message::Features(mut f) => {
+                                sanitize::sanitize_features(&mut f);
+                                f
+                            }
                             _ => return Err(anyhow!("Unexpected response from device {} via HID", target_device.unique_id)),
                         };
                         let device_features = DeviceFeatures {
@@ -496,6 +537,8 @@ pub fn get_device_features_via_hid(target_device: &FriendlyUsbDevice) -> Result<
                             passphrase_cached: features.passphrase_cached.unwrap_or(false),
                             wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
                             auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
+                            flags: flags::flags_from_policy_types(&features.policies),
+                            networks: networks_from_coins(&features.coins),
                             policies: features
                                 .policies
                                 .into_iter()
@@ -531,7 +574,10 @@ pub fn get_device_features_via_hid(target_device: &FriendlyUsbDevice) -> Result<
                         match adapter.handle(init_msg) {
                             Ok(features_msg) => {
                                 let features = match features_msg {
-                                    Message::Features(f) => f,
+                                    Message::Features(mut f) => {
+                                        sanitize::sanitize_features(&mut f);
+                                        f
+                                    }
                                     _ => continue, // try next
                                 };
                                 let device_features = DeviceFeatures {
@@ -562,6 +608,8 @@ pub fn get_device_features_via_hid(target_device: &FriendlyUsbDevice) -> Result<
                                     passphrase_cached: features.passphrase_cached.unwrap_or(false),
                                     wipe_code_protection: features.wipe_code_protection.unwrap_or(false),
                                     auto_lock_delay_ms: features.auto_lock_delay_ms.map(|ms| ms as u64),
+                                    flags: flags::flags_from_policy_types(&features.policies),
+                                    networks: networks_from_coins(&features.coins),
                                     policies: features
                                         .policies
                                         .into_iter()
@@ -701,6 +749,10 @@ pub fn list_connected_devices() -> Vec<FriendlyUsbDevice> {
                 }
                 seen_bus_addr.insert(bus_addr_key.clone());
                 
+                if let Some(port) = usb_port(device) {
+                    KEEPKEY_PORTS.lock().unwrap_or_else(|e| e.into_inner()).insert(port);
+                }
+                
                 let friendly_device = device_to_friendly_with_cache(device);
                 current_devices.push(friendly_device);
             }
@@ -710,6 +762,97 @@ pub fn list_connected_devices() -> Vec<FriendlyUsbDevice> {
     current_devices
 }
 
+/// DfuSe description of a DFU device's internal flash (interface 0, alt 0)
+fn dfu_flash_layout(device: &Device<GlobalContext>) -> Option<String> {
+    let timeout = std::time::Duration::from_millis(100);
+    let handle = device.open().ok()?;
+    let lang = *handle.read_languages(timeout).ok()?.first()?;
+    let config = device.active_config_descriptor().ok()?;
+    let interface = config.interfaces().next()?;
+    let descriptor = interface.descriptors().find(|d| d.setting_number() == 0)?;
+    handle.read_interface_string(lang, &descriptor, timeout).ok()
+}
+
+/// Whether a USB device is a KeepKey in raw DFU mode, and not just any STM32
+/// sitting in its system bootloader
+fn is_keepkey_dfu_device(device: &Device<GlobalContext>) -> bool {
+    let Ok(desc) = device.device_descriptor() else {
+        return false;
+    };
+    if !crate::friendly_usb::is_dfu_mode(desc.vendor_id(), desc.product_id()) {
+        return false;
+    }
+    let seen_on_port = usb_port(device)
+        .is_some_and(|port| KEEPKEY_PORTS.lock().unwrap_or_else(|e| e.into_inner()).contains(&port));
+    crate::friendly_usb::is_keepkey_dfu(
+        desc.vendor_id(),
+        desc.product_id(),
+        dfu_flash_layout(device).as_deref(),
+        seen_on_port,
+    )
+}
+
+/// List KeepKeys stuck in raw STM32 DFU mode (e.g. after an interrupted update)
+///
+/// These don't show up in `list_connected_devices()` because they no longer
+/// enumerate with the KeepKey VID, and they don't speak the protobuf protocol.
+/// The DFU vid/pid is shared by every STM32, so a device is only listed once it
+/// is identified as a KeepKey (see `friendly_usb::is_keepkey_dfu`).
+pub fn list_dfu_devices() -> Vec<FriendlyUsbDevice> {
+    let devices = match rusb::devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            log::warn!("{TAG} Failed to enumerate USB devices for DFU scan: {}", e);
+            return Vec::new();
+        }
+    };
+
+    devices
+        .iter()
+        .filter(is_keepkey_dfu_device)
+        .map(|device| device_to_friendly(&device))
+        .collect()
+}
+
+/// Flash a recovery image onto a KeepKey that is stuck in DFU mode.
+///
+/// `image` is a raw flash image of `transport::dfu::RECOVERY_IMAGE_SIZE` bytes
+/// starting at the beginning of internal flash; only its bootstrap and
+/// bootloader sectors are written. The image is checked (size, vector table,
+/// released bootloader) and the device identified as a KeepKey before anything
+/// is written; either failing refuses the flash.
+/// `progress` is called with (bytes_written, total_bytes).
+pub fn recover_dfu_device(
+    target_device: &FriendlyUsbDevice,
+    image: &[u8],
+    progress: impl FnMut(usize, usize),
+) -> Result<()> {
+    let bootloader_version = crate::transport::dfu::check_recovery_image(image)?;
+
+    let devices = rusb::devices()?;
+    let device = devices
+        .iter()
+        .find(|device| {
+            device
+                .device_descriptor()
+                .map(|desc| crate::friendly_usb::is_dfu_mode(desc.vendor_id(), desc.product_id()))
+                .unwrap_or(false)
+                && device_to_friendly(device).unique_id == target_device.unique_id
+        })
+        .ok_or_else(|| anyhow!("DFU device {} not found", target_device.unique_id))?;
+    if !is_keepkey_dfu_device(&device) {
+        return Err(anyhow!("Refusing to flash {}: it can't be identified as a KeepKey", target_device.unique_id));
+    }
+
+    log::info!(
+        "{TAG} Flashing recovery image (bootloader v{}) to DFU device {}",
+        bootloader_version,
+        target_device.unique_id
+    );
+    let dfu = crate::transport::dfu::DfuDevice::open(&device)?;
+    dfu.flash(&crate::transport::dfu::recovery_regions(image), progress)
+}
+
 /// Convert a USB device to FriendlyUsbDevice with caching for stability
 fn device_to_friendly_with_cache(device: &rusb::Device<rusb::GlobalContext>) -> FriendlyUsbDevice {
     let desc = device.device_descriptor().unwrap();
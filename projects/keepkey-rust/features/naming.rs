@@ -0,0 +1,129 @@
+//! How a device is named in status lines, logs and events.
+
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+use super::DeviceFeatures;
+
+/// What a naming strategy gets to work with
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceNameInput<'a> {
+    pub label: Option<&'a str>,
+    pub model: Option<&'a str>,
+    /// The device's own id (`Features.device_id`), falling back to the USB id
+    pub device_id: Option<&'a str>,
+}
+
+pub type DeviceNameFn = Arc<dyn Fn(&DeviceNameInput) -> String + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub enum DeviceNameStrategy {
+    /// The label as set on the device, "Unlabeled" when empty
+    #[default]
+    RawLabel,
+    /// The label, or "<model> <last 4 of id>" (e.g. "K1-14AM 3F2A") when empty
+    LabelOrModelId,
+    Custom(DeviceNameFn),
+}
+
+impl std::fmt::Debug for DeviceNameStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceNameStrategy::RawLabel => write!(f, "RawLabel"),
+            DeviceNameStrategy::LabelOrModelId => write!(f, "LabelOrModelId"),
+            DeviceNameStrategy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl DeviceNameStrategy {
+    /// Parse the `device_name_strategy` config value. `Custom` can only be set from code.
+    pub fn from_config(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "raw_label" | "rawlabel" => Some(DeviceNameStrategy::RawLabel),
+            "label_or_model_id" | "labelormodelid" => Some(DeviceNameStrategy::LabelOrModelId),
+            _ => None,
+        }
+    }
+
+    pub fn display_name(&self, input: &DeviceNameInput) -> String {
+        let label = input.label.map(str::trim).filter(|l| !l.is_empty());
+        match self {
+            DeviceNameStrategy::RawLabel => label.unwrap_or("Unlabeled").to_string(),
+            DeviceNameStrategy::LabelOrModelId => match label {
+                Some(label) => label.to_string(),
+                None => {
+                    let model = input.model.map(str::trim).filter(|m| !m.is_empty()).unwrap_or("KeepKey");
+                    match input.device_id.map(last_four).filter(|id| !id.is_empty()) {
+                        Some(suffix) => format!("{} {}", model, suffix),
+                        None => model.to_string(),
+                    }
+                }
+            },
+            DeviceNameStrategy::Custom(f) => f(input),
+        }
+    }
+}
+
+fn last_four(id: &str) -> String {
+    let chars: Vec<char> = id.trim().chars().collect();
+    chars[chars.len().saturating_sub(4)..].iter().collect::<String>().to_uppercase()
+}
+
+static DEVICE_NAME_STRATEGY: Lazy<RwLock<DeviceNameStrategy>> =
+    Lazy::new(|| RwLock::new(DeviceNameStrategy::default()));
+
+pub fn set_device_name_strategy(strategy: DeviceNameStrategy) {
+    log::info!("Device name strategy set to {:?}", strategy);
+    match DEVICE_NAME_STRATEGY.write() {
+        Ok(mut guard) => *guard = strategy,
+        Err(poisoned) => *poisoned.into_inner() = strategy,
+    }
+}
+
+pub fn device_name_strategy() -> DeviceNameStrategy {
+    match DEVICE_NAME_STRATEGY.read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Display name using the configured strategy
+pub fn display_name(input: &DeviceNameInput) -> String {
+    device_name_strategy().display_name(input)
+}
+
+/// Display name for converted features; `fallback_id` is used when the device
+/// didn't report its own id (e.g. the USB unique id)
+pub fn display_name_for_features(features: &DeviceFeatures, fallback_id: Option<&str>) -> String {
+    display_name(&DeviceNameInput {
+        label: features.label.as_deref(),
+        model: features.model.as_deref(),
+        device_id: features.device_id.as_deref().or(fallback_id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input<'a>(label: Option<&'a str>) -> DeviceNameInput<'a> {
+        DeviceNameInput { label, model: Some("K1-14AM"), device_id: Some("343737340F4736331F003B00") }
+    }
+
+    #[test]
+    fn test_name_strategies() {
+        assert_eq!(DeviceNameStrategy::RawLabel.display_name(&input(None)), "Unlabeled");
+        assert_eq!(DeviceNameStrategy::RawLabel.display_name(&input(Some(""))), "Unlabeled");
+        assert_eq!(DeviceNameStrategy::RawLabel.display_name(&input(Some("Savings"))), "Savings");
+
+        assert_eq!(DeviceNameStrategy::LabelOrModelId.display_name(&input(None)), "K1-14AM 3B00");
+        assert_eq!(DeviceNameStrategy::LabelOrModelId.display_name(&input(Some("Savings"))), "Savings");
+
+        let custom = DeviceNameStrategy::Custom(Arc::new(|i: &DeviceNameInput| format!("KK-{}", i.label.unwrap_or("?"))));
+        assert_eq!(custom.display_name(&input(None)), "KK-?");
+
+        assert!(matches!(DeviceNameStrategy::from_config("label_or_model_id"), Some(DeviceNameStrategy::LabelOrModelId)));
+        assert!(DeviceNameStrategy::from_config("nope").is_none());
+    }
+}
@@ -0,0 +1,96 @@
+//! Cleaning of the strings a device reports about itself.
+//!
+//! Labels, vendor and model names end up in status lines, event payloads and
+//! log files. A corrupt or malicious device could put terminal escapes, line
+//! breaks or bidi overrides in them to garble the UI or forge log lines, so
+//! they are stripped before a `Features` message is used.
+//!
+//! prost refuses to decode a `string` field that isn't valid UTF-8, so
+//! `Message::decode` re-encodes the string fields of a `Features` message with
+//! invalid sequences replaced by U+FFFD before decoding it (see
+//! `messages::encoding`). What reaches [`sanitize_features`] is always valid
+//! UTF-8.
+
+use crate::messages::Features;
+
+/// Characters that reorder or hide text when rendered
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{061C}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// `s` without control characters (C0, DEL and C1, which covers ESC and line
+/// breaks) and bidi controls
+pub fn sanitize_device_string(s: &str) -> String {
+    s.chars().filter(|&c| !c.is_control() && !is_bidi_control(c)).collect()
+}
+
+fn sanitize_field(field: &mut Option<String>) {
+    if let Some(value) = field {
+        let clean = sanitize_device_string(value);
+        if clean != *value {
+            log::warn!("Stripped control characters from a device-reported string");
+            *value = clean;
+        }
+    }
+}
+
+/// Sanitize every string field of a `Features` message in place
+pub fn sanitize_features(features: &mut Features) {
+    sanitize_field(&mut features.vendor);
+    sanitize_field(&mut features.device_id);
+    sanitize_field(&mut features.language);
+    sanitize_field(&mut features.label);
+    sanitize_field(&mut features.model);
+    sanitize_field(&mut features.firmware_variant);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adversarial_labels() {
+        // ANSI escapes that would recolor a terminal and clear the screen
+        assert_eq!(sanitize_device_string("\u{1b}[31mSavings\u{1b}[2J"), "[31mSavings[2J");
+        // A forged log line and an embedded NUL
+        assert_eq!(sanitize_device_string("Savings\n2024-01-01 INFO wiped\r\0"), "Savings2024-01-01 INFO wiped");
+        // C1 controls and DEL
+        assert_eq!(sanitize_device_string("a\u{9b}b\u{7f}c\u{85}"), "abc");
+        // Right-to-left override that would render "Savings" backwards
+        assert_eq!(sanitize_device_string("\u{202e}sgnivaS\u{2069}"), "sgnivaS");
+        // Ordinary labels are untouched, non-ASCII included
+        assert_eq!(sanitize_device_string("Cold storage №2 – Zoë 🔑"), "Cold storage №2 – Zoë 🔑");
+
+        let mut features = Features {
+            label: Some("My\tKeepKey\u{1b}]0;pwned\u{7}".to_string()),
+            vendor: Some("keepkey.com".to_string()),
+            device_id: Some("343737340F4736331F003B00\n".to_string()),
+            ..Default::default()
+        };
+        sanitize_features(&mut features);
+        assert_eq!(features.label.as_deref(), Some("MyKeepKey]0;pwned"));
+        assert_eq!(features.vendor.as_deref(), Some("keepkey.com"));
+        assert_eq!(features.device_id.as_deref(), Some("343737340F4736331F003B00"));
+        assert_eq!(features.model, None);
+    }
+
+    #[test]
+    fn test_invalid_utf8_label_bytes_are_decoded_then_sanitized() {
+        use crate::messages::{Message, MessageType};
+
+        // label (field 10) with an invalid byte and an escape sequence
+        let label = b"Sav\xffings\x1b[2J";
+        let mut body = vec![0x52, label.len() as u8];
+        body.extend_from_slice(label);
+        let mut frame = vec![b'#', b'#', 0, MessageType::Features as u8];
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+
+        let Message::Features(mut features) = Message::decode(&mut frame.as_slice()).unwrap() else {
+            panic!("expected Features");
+        };
+        assert_eq!(features.label.as_deref(), Some("Sav\u{fffd}ings\u{1b}[2J"));
+        sanitize_features(&mut features);
+        assert_eq!(features.label.as_deref(), Some("Sav\u{fffd}ings[2J"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Retry policy for firmware uploads.
+//!
+//! A failed upload is either *transient* (the transport dropped: unplugged
+//! cable, flaky hub, timeout) and safe to retry, or *fatal* (the bootloader
+//! rejected the image: bad signature, wrong model, hash mismatch, user
+//! cancelled) and must never be retried automatically.
+//!
+//! Resuming from the last acknowledged offset would avoid re-sending the whole
+//! image, but every KeepKey bootloader to date takes the image as a single
+//! `FirmwareUpload` message after a `FirmwareErase`, with no offset field, so a
+//! retry always restarts from zero. `bootloader_supports_resume` is the single
+//! place to change once a bootloader gains offset-based uploads.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadFailure {
+    /// Transport-level failure; reconnecting and uploading again is safe
+    Transient,
+    /// Rejected by the bootloader or the user; retrying can't help
+    Fatal,
+}
+
+/// Lower-cased fragments of errors the bootloader reports for a bad image or
+/// a refused update
+const FATAL_MARKERS: &[&str] = &[
+    "signature",
+    "fingerprint",
+    "hash mismatch",
+    "invalid firmware",
+    "wrong model",
+    "not compatible",
+    "cancelled",
+    "canceled",
+    "action cancelled",
+    "firmware update failed:",
+    "firmware erase failed:",
+];
+
+/// Lower-cased fragments of transport failures
+const TRANSIENT_MARKERS: &[&str] = &[
+    "timed out",
+    "timeout",
+    "no such device",
+    "disconnected",
+    "device not found",
+    "pipe",
+    "i/o",
+    "io error",
+    "transport",
+    "worker unavailable",
+    "channel closed",
+    "broken",
+    "reset",
+];
+
+/// Classify an upload error message. Unknown errors are treated as fatal so a
+/// device is never re-flashed without a clear reason.
+pub fn classify_upload_error(error: &str) -> UploadFailure {
+    let error = error.to_ascii_lowercase();
+    if FATAL_MARKERS.iter().any(|marker| error.contains(marker)) {
+        return UploadFailure::Fatal;
+    }
+    if TRANSIENT_MARKERS.iter().any(|marker| error.contains(marker)) {
+        return UploadFailure::Transient;
+    }
+    UploadFailure::Fatal
+}
+
+/// Whether a bootloader can continue an interrupted upload from an offset.
+/// No released KeepKey bootloader can (see module docs).
+pub fn bootloader_supports_resume(_bootloader_version: Option<&str>) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_upload_error() {
+        assert_eq!(classify_upload_error("Device operation timed out"), UploadFailure::Transient);
+        assert_eq!(classify_upload_error("Error during firmware upload: No such device (it may have been disconnected)"), UploadFailure::Transient);
+        assert_eq!(classify_upload_error("Device worker channel closed"), UploadFailure::Transient);
+
+        assert_eq!(classify_upload_error("Firmware update failed: Invalid signature"), UploadFailure::Fatal);
+        assert_eq!(classify_upload_error("Firmware update failed: Action cancelled by user"), UploadFailure::Fatal);
+        // A device-reported failure wins over a transport-looking word in its text
+        assert_eq!(classify_upload_error("Firmware update failed: transport hash mismatch"), UploadFailure::Fatal);
+        assert_eq!(classify_upload_error("something unexpected"), UploadFailure::Fatal);
+    }
+}
@@ -3,6 +3,32 @@ use serde::{Deserialize, Serialize};
 /// Vendor ID for KeepKey devices
 pub const KEEPKEY_VID: u16 = 0x2b24;
 
+/// STM32 system-memory DFU bootloader. A KeepKey whose flash was left incomplete
+/// by an interrupted update can come back with this ID instead of its own.
+pub const STM32_DFU_VID: u16 = 0x0483;
+pub const STM32_DFU_PID: u16 = 0xdf11;
+
+/// DfuSe description of the internal flash of the STM32F205 (1MB) a KeepKey is
+/// built on, as reported by the system bootloader's first alternate setting
+pub const KEEPKEY_DFU_FLASH_LAYOUT: &str = "@Internal Flash  /0x08000000/04*016Kg,01*064Kg,07*128Kg";
+
+/// Whether a vid/pid pair identifies a device sitting in raw DFU mode
+///
+/// Every STM32 in its system bootloader reports this pair, so on its own it
+/// doesn't say the device is a KeepKey; see `is_keepkey_dfu`.
+pub fn is_dfu_mode(vid: u16, pid: u16) -> bool {
+    vid == STM32_DFU_VID && pid == STM32_DFU_PID
+}
+
+/// Whether a DFU device is positively a KeepKey: it reports the DFU vid/pid,
+/// its flash layout is the KeepKey's MCU, and a KeepKey was enumerated on the
+/// same USB port before it dropped into DFU mode.
+pub fn is_keepkey_dfu(vid: u16, pid: u16, flash_layout: Option<&str>, keepkey_seen_on_port: bool) -> bool {
+    is_dfu_mode(vid, pid)
+        && flash_layout.is_some_and(|layout| layout.trim() == KEEPKEY_DFU_FLASH_LAYOUT)
+        && keepkey_seen_on_port
+}
+
 /// User-friendly representation of a USB device.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -44,4 +70,51 @@ impl FriendlyUsbDevice {
             is_keepkey: vid == KEEPKEY_VID,
         }
     }
+
+    /// Whether this device is in raw DFU mode and won't answer protobuf messages
+    pub fn is_dfu_mode(&self) -> bool {
+        is_dfu_mode(self.vid, self.pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(vid: u16, pid: u16) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new("test".to_string(), vid, pid, None, None, None)
+    }
+
+    #[test]
+    fn test_dfu_mode_detection() {
+        assert!(is_dfu_mode(0x0483, 0xdf11));
+        assert!(device(STM32_DFU_VID, STM32_DFU_PID).is_dfu_mode());
+
+        // Regular KeepKey bootloader/firmware PIDs are not DFU
+        assert!(!device(KEEPKEY_VID, 0x0001).is_dfu_mode());
+        assert!(!device(KEEPKEY_VID, 0x0002).is_dfu_mode());
+
+        // Other STM32 devices and mismatched pairs are not DFU
+        assert!(!is_dfu_mode(0x0483, 0x5740));
+        assert!(!is_dfu_mode(KEEPKEY_VID, STM32_DFU_PID));
+    }
+
+    #[test]
+    fn test_keepkey_dfu_needs_positive_identification() {
+        let layout = Some(KEEPKEY_DFU_FLASH_LAYOUT);
+        assert!(is_keepkey_dfu(STM32_DFU_VID, STM32_DFU_PID, layout, true));
+
+        // Any STM32 in DFU mode reports the same vid/pid: without the port history
+        // or with another part's flash it isn't treated as a KeepKey
+        assert!(!is_keepkey_dfu(STM32_DFU_VID, STM32_DFU_PID, layout, false));
+        assert!(!is_keepkey_dfu(STM32_DFU_VID, STM32_DFU_PID, None, true));
+        assert!(!is_keepkey_dfu(
+            STM32_DFU_VID,
+            STM32_DFU_PID,
+            Some("@Internal Flash  /0x08000000/064*0002Kg"),
+            true
+        ));
+
+        assert!(!is_keepkey_dfu(KEEPKEY_VID, 0x0001, layout, true));
+    }
 }
@@ -0,0 +1,48 @@
+//! Vetting raw messages before they are queued for a device.
+//!
+//! An application installs a `MessageGate` on a worker's handle to refuse or
+//! hold messages by type (a wipe, a PIN change, a signature...) whatever code
+//! path sends them. Clones of the handle and its exclusive sessions share the
+//! gate, so there is no way around it short of spawning a new worker.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::messages::Message;
+
+/// Decides whether a message may be sent to a device
+#[async_trait]
+pub trait MessageGate: Send + Sync {
+    /// `Err(reason)` refuses `message`; it is then never sent. May wait, e.g.
+    /// for someone to approve the message.
+    async fn check(&self, device_id: &str, message: &Message) -> Result<(), String>;
+}
+
+/// Error of a `send_raw` whose message the gate refused
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{0}")]
+pub struct MessageRefused(pub String);
+
+/// The gate of one worker's handles; lets everything through until one is set
+#[derive(Clone, Default)]
+pub struct GateCell(Option<Arc<dyn MessageGate>>);
+
+impl GateCell {
+    pub fn new(gate: Arc<dyn MessageGate>) -> Self {
+        Self(Some(gate))
+    }
+
+    pub async fn check(&self, device_id: &str, message: &Message) -> Result<(), MessageRefused> {
+        match &self.0 {
+            Some(gate) => gate.check(device_id, message).await.map_err(MessageRefused),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for GateCell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("GateCell").field(&self.0.is_some()).finish()
+    }
+}
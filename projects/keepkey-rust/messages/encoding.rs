@@ -62,9 +62,103 @@ impl Message {
             return Err(DecodeError::new("buffer too short"));
         }
 
-        Self::decode_as_type(
-            buf,
-            MessageType::from_i32(msg_type).ok_or_else(|| DecodeError::new("bad message type"))?,
-        )
+        let message_type = MessageType::from_i32(msg_type).ok_or_else(|| DecodeError::new("bad message type"))?;
+        if message_type == MessageType::Features {
+            // A corrupt label shouldn't make the whole device unreadable
+            let body = buf.copy_to_bytes(buf.remaining());
+            return Self::decode_as_type(&mut body.clone(), message_type).or_else(|e| {
+                let repaired = lossy_string_fields(&body, FEATURES_STRING_FIELDS).ok_or(e)?;
+                log::warn!("Features message carried invalid UTF-8; decoded it lossily");
+                Self::decode_as_type(&mut repaired.as_slice(), message_type)
+            });
+        }
+        Self::decode_as_type(buf, message_type)
+    }
+}
+
+/// Field numbers of the `string` fields of `Features`: vendor, device_id,
+/// language, label, model and firmware_variant
+const FEATURES_STRING_FIELDS: &[u64] = &[1, 6, 9, 10, 21, 22];
+
+/// Re-encode a message body with invalid UTF-8 in the given top-level string
+/// fields replaced by U+FFFD. `None` if the body isn't well-formed protobuf or
+/// nothing needed replacing.
+fn lossy_string_fields(mut body: &[u8], fields: &[u64]) -> Option<Vec<u8>> {
+    use prost::encoding::{decode_varint, encode_varint};
+
+    let mut out = Vec::with_capacity(body.len());
+    let mut repaired = false;
+    while !body.is_empty() {
+        let key = decode_varint(&mut body).ok()?;
+        encode_varint(key, &mut out);
+        let len = match key & 0x7 {
+            0 => {
+                let value = decode_varint(&mut body).ok()?;
+                encode_varint(value, &mut out);
+                0
+            }
+            1 => 8,
+            5 => 4,
+            2 => {
+                let len = usize::try_from(decode_varint(&mut body).ok()?).ok()?;
+                let value = body.get(..len)?;
+                body = &body[len..];
+                if fields.contains(&(key >> 3)) && std::str::from_utf8(value).is_err() {
+                    let lossy = String::from_utf8_lossy(value);
+                    encode_varint(lossy.len() as u64, &mut out);
+                    out.extend_from_slice(lossy.as_bytes());
+                    repaired = true;
+                } else {
+                    encode_varint(len as u64, &mut out);
+                    out.extend_from_slice(value);
+                }
+                continue;
+            }
+            _ => return None,
+        };
+        out.extend_from_slice(body.get(..len)?);
+        body = &body[len..];
+    }
+    repaired.then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Features;
+
+    fn framed(body: &[u8]) -> Vec<u8> {
+        let mut buf = vec![b'#', b'#', 0, MessageType::Features as u8];
+        buf.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        buf.extend_from_slice(body);
+        buf
+    }
+
+    #[test]
+    fn test_invalid_utf8_label_decodes_lossily() {
+        // vendor "keepkey.com", major_version 7, label with a lone continuation
+        // byte and a truncated 3 byte sequence, initialized
+        let mut body = vec![0x0a, 11];
+        body.extend_from_slice(b"keepkey.com");
+        body.extend_from_slice(&[0x10, 7]);
+        let label = b"Sav\x80ings\xe2\x82";
+        body.extend_from_slice(&[0x52, label.len() as u8]);
+        body.extend_from_slice(label);
+        body.extend_from_slice(&[0x60, 1]);
+
+        let Message::Features(features) = Message::decode(&mut framed(&body).as_slice()).unwrap() else {
+            panic!("expected Features");
+        };
+        assert_eq!(features.label.as_deref(), Some("Sav\u{fffd}ings\u{fffd}"));
+        assert_eq!(features.vendor.as_deref(), Some("keepkey.com"));
+        assert_eq!(features.major_version, Some(7));
+        assert_eq!(features.initialized, Some(true));
+
+        // Valid messages decode as before, and garbage is still an error
+        let valid = Features { label: Some("Savings".to_string()), ..Default::default() };
+        let mut buf = Vec::new();
+        Message::from(valid.clone()).encode(&mut buf).unwrap();
+        assert!(matches!(Message::decode(&mut buf.as_slice()).unwrap(), Message::Features(f) if f == valid));
+        assert!(Message::decode(&mut framed(&[0x52, 10, 0xff]).as_slice()).is_err());
     }
 }
@@ -0,0 +1,279 @@
+//! Which messages a device's firmware understands.
+//!
+//! Message types and fields were added across firmware releases, and old
+//! bootloaders predate `GetFeatures` entirely. The worker negotiates a
+//! `ProtocolVersion` from the device's features before the first message that
+//! depends on it and checks every message against the resulting `Capabilities`,
+//! instead of discovering the mismatch from an "Unknown message" failure.
+
+use serde::{Deserialize, Serialize};
+
+use crate::features::DeviceFeatures;
+use crate::messages::{Features, InputScriptType, Message, OutputScriptType};
+
+/// Version reported in `Features`: the firmware version, or the bootloader's
+/// in bootloader mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub bootloader_mode: bool,
+}
+
+impl ProtocolVersion {
+    pub fn from_features(features: &Features) -> Self {
+        Self {
+            major: features.major_version.unwrap_or(0),
+            minor: features.minor_version.unwrap_or(0),
+            patch: features.patch_version.unwrap_or(0),
+            bootloader_mode: features.bootloader_mode.unwrap_or(false),
+        }
+    }
+
+    /// From the converted features' `major.minor.patch` version string;
+    /// `None` if it doesn't parse
+    pub fn from_device_features(features: &DeviceFeatures) -> Option<Self> {
+        let mut parts = features.version.trim().trim_start_matches('v').split('.').map(|part| part.parse::<u32>().ok());
+        let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        Some(Self { major, minor, patch, bootloader_mode: features.bootloader_mode })
+    }
+
+    fn at_least(&self, version: (u32, u32, u32)) -> bool {
+        (self.major, self.minor, self.patch) >= version
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        if self.bootloader_mode {
+            return Capabilities {
+                get_features: self.major >= 2,
+                wallet: false,
+                segwit: false,
+                policies: false,
+                auto_lock_delay: false,
+            };
+        }
+        Capabilities {
+            get_features: true,
+            wallet: true,
+            segwit: self.at_least(SEGWIT_FIRMWARE),
+            policies: self.at_least(POLICIES_FIRMWARE),
+            auto_lock_delay: self.at_least(AUTO_LOCK_DELAY_FIRMWARE),
+        }
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = if self.bootloader_mode { "bootloader" } else { "firmware" };
+        write!(f, "{} {}.{}.{}", mode, self.major, self.minor, self.patch)
+    }
+}
+
+/// First firmware with native and nested segwit script types
+const SEGWIT_FIRMWARE: (u32, u32, u32) = (6, 0, 0);
+/// First firmware with `ApplyPolicies` (see `features::flags`)
+const POLICIES_FIRMWARE: (u32, u32, u32) = (4, 0, 0);
+/// First firmware that accepts `ApplySettings.auto_lock_delay_ms`
+const AUTO_LOCK_DELAY_FIRMWARE: (u32, u32, u32) = (6, 1, 0);
+
+/// Messages and fields a negotiated version supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// `GetFeatures` is understood; v1 ("OOB") bootloaders only answer `Initialize`
+    pub get_features: bool,
+    /// Wallet messages (addresses, signing, settings); none work in bootloader mode
+    pub wallet: bool,
+    pub segwit: bool,
+    pub policies: bool,
+    pub auto_lock_delay: bool,
+}
+
+impl Capabilities {
+    /// Why the firmware can't take `message`, or `Ok` if it can
+    pub fn check(&self, message: &Message) -> Result<(), String> {
+        match message {
+            Message::Initialize(_) | Message::Ping(_) | Message::Cancel(_) | Message::ButtonAck(_) => Ok(()),
+            Message::FirmwareErase(_) | Message::FirmwareUpload(_) => Ok(()),
+            Message::GetFeatures(_) if !self.get_features => {
+                Err("GetFeatures is not supported by this bootloader; use Initialize".to_string())
+            }
+            Message::GetFeatures(_) => Ok(()),
+            _ if !self.wallet => Err(format!("{:?} is not available in bootloader mode", message.message_type())),
+            Message::ApplyPolicies(_) if !self.policies => Err("ApplyPolicies needs firmware 4.0.0 or newer".to_string()),
+            Message::ApplySettings(m) if m.auto_lock_delay_ms.is_some() && !self.auto_lock_delay => {
+                Err("Auto-lock delay needs firmware 6.1.0 or newer".to_string())
+            }
+            Message::GetAddress(m) if !self.segwit && is_segwit_input(m.script_type) => {
+                Err("Segwit addresses need firmware 6.0.0 or newer".to_string())
+            }
+            Message::GetPublicKey(m) if !self.segwit && is_segwit_input(m.script_type) => {
+                Err("Segwit public keys need firmware 6.0.0 or newer".to_string())
+            }
+            Message::TxAck(ack) if !self.segwit => {
+                let uses_segwit = ack.tx.as_ref().is_some_and(|tx| {
+                    tx.inputs.iter().any(|input| is_segwit_input(input.script_type))
+                        || tx.outputs.iter().any(|output| is_segwit_output(output.script_type))
+                });
+                if uses_segwit {
+                    Err("Segwit inputs and change need firmware 6.0.0 or newer".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Operations the frontend offers, for asking ahead whether a device can do them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceOperation {
+    GetFeatures,
+    GetAddress,
+    GetXpub,
+    SegwitAddress,
+    SignTransaction,
+    SegwitSigning,
+    SignMessage,
+    ChangeLabel,
+    ChangePin,
+    AutoLockDelay,
+    ApplyPolicies,
+    WipeDevice,
+    ResetDevice,
+    RecoverDevice,
+    /// Recovery dry run, checking a backup against the stored seed
+    SeedCheck,
+    ChangeWipeCode,
+}
+
+/// Answer to "can this device do that?"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    Unsupported,
+    /// The version table doesn't say; try it and handle the failure
+    Unknown,
+}
+
+impl From<bool> for Support {
+    fn from(supported: bool) -> Self {
+        if supported {
+            Support::Supported
+        } else {
+            Support::Unsupported
+        }
+    }
+}
+
+impl Capabilities {
+    /// Whether `operation` is available, from the same table `check` uses
+    pub fn supports(&self, operation: DeviceOperation) -> Support {
+        match operation {
+            DeviceOperation::GetFeatures => self.get_features.into(),
+            _ if !self.wallet => Support::Unsupported,
+            DeviceOperation::SegwitAddress | DeviceOperation::SegwitSigning => self.segwit.into(),
+            DeviceOperation::AutoLockDelay => self.auto_lock_delay.into(),
+            DeviceOperation::ApplyPolicies => self.policies.into(),
+            // Which firmware introduced these isn't recorded
+            DeviceOperation::SeedCheck | DeviceOperation::ChangeWipeCode => Support::Unknown,
+            DeviceOperation::GetAddress
+            | DeviceOperation::GetXpub
+            | DeviceOperation::SignTransaction
+            | DeviceOperation::SignMessage
+            | DeviceOperation::ChangeLabel
+            | DeviceOperation::ChangePin
+            | DeviceOperation::WipeDevice
+            | DeviceOperation::ResetDevice
+            | DeviceOperation::RecoverDevice => Support::Supported,
+        }
+    }
+}
+
+fn is_segwit_input(script_type: Option<i32>) -> bool {
+    matches!(
+        script_type.and_then(InputScriptType::from_i32),
+        Some(InputScriptType::Spendwitness | InputScriptType::Spendp2shwitness)
+    )
+}
+
+fn is_segwit_output(script_type: i32) -> bool {
+    matches!(
+        OutputScriptType::from_i32(script_type),
+        Some(OutputScriptType::Paytowitness | OutputScriptType::Paytop2shwitness)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{ApplySettings, GetAddress, GetFeatures, Initialize, SignTx, TransactionType, TxAck, TxInputType};
+
+    fn version(major: u32, minor: u32, patch: u32, bootloader_mode: bool) -> ProtocolVersion {
+        ProtocolVersion { major, minor, patch, bootloader_mode }
+    }
+
+    fn segwit_address() -> Message {
+        GetAddress { script_type: Some(InputScriptType::Spendwitness as i32), ..Default::default() }.into()
+    }
+
+    #[test]
+    fn test_known_versions() {
+        // v1 bootloader: Initialize only, nothing wallet related
+        let oob = version(1, 0, 3, true).capabilities();
+        assert!(oob.check(&Initialize::default().into()).is_ok());
+        assert!(oob.check(&GetFeatures::default().into()).is_err());
+        assert!(oob.check(&segwit_address()).is_err());
+
+        let bootloader = version(2, 1, 4, true).capabilities();
+        assert!(bootloader.check(&GetFeatures::default().into()).is_ok());
+        assert!(bootloader.check(&SignTx::default().into()).is_err());
+
+        // 5.x firmware predates segwit and the auto-lock setting
+        let legacy = version(5, 11, 0, false).capabilities();
+        assert!(legacy.check(&segwit_address()).is_err());
+        assert!(legacy.check(&GetAddress::default().into()).is_ok());
+        let segwit_input = TxAck {
+            tx: Some(TransactionType {
+                inputs: vec![TxInputType { script_type: Some(InputScriptType::Spendp2shwitness as i32), ..Default::default() }],
+                ..Default::default()
+            }),
+        };
+        assert!(legacy.check(&segwit_input.clone().into()).is_err());
+        let auto_lock = ApplySettings { auto_lock_delay_ms: Some(60_000), ..Default::default() };
+        assert!(legacy.check(&auto_lock.clone().into()).is_err());
+        assert!(legacy.check(&ApplySettings { label: Some("KK".to_string()), ..Default::default() }.into()).is_ok());
+
+        let current = version(7, 10, 0, false).capabilities();
+        assert!(current.check(&segwit_address()).is_ok());
+        assert!(current.check(&segwit_input.into()).is_ok());
+        assert!(current.check(&auto_lock.into()).is_ok());
+        assert!(current.get_features && current.policies);
+    }
+
+    #[test]
+    fn test_operation_support_across_versions() {
+        let legacy = version(5, 11, 0, false).capabilities();
+        assert_eq!(legacy.supports(DeviceOperation::SignTransaction), Support::Supported);
+        assert_eq!(legacy.supports(DeviceOperation::SegwitAddress), Support::Unsupported);
+        assert_eq!(legacy.supports(DeviceOperation::AutoLockDelay), Support::Unsupported);
+        assert_eq!(legacy.supports(DeviceOperation::ApplyPolicies), Support::Supported);
+
+        let current = version(7, 10, 0, false).capabilities();
+        assert_eq!(current.supports(DeviceOperation::SegwitSigning), Support::Supported);
+        assert_eq!(current.supports(DeviceOperation::AutoLockDelay), Support::Supported);
+        // Not in the version table: don't guess
+        assert_eq!(current.supports(DeviceOperation::ChangeWipeCode), Support::Unknown);
+
+        let bootloader = version(2, 1, 4, true).capabilities();
+        assert_eq!(bootloader.supports(DeviceOperation::GetFeatures), Support::Supported);
+        assert_eq!(bootloader.supports(DeviceOperation::ChangeWipeCode), Support::Unsupported);
+        assert_eq!(version(1, 0, 3, true).capabilities().supports(DeviceOperation::GetFeatures), Support::Unsupported);
+    }
+}
@@ -0,0 +1,433 @@
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::messages::{ButtonRequest, Message, PinMatrixRequestType};
+use crate::transport::ProtocolAdapter;
+
+/// What the device screen is currently asking the user to do, derived from the
+/// last prompt the device sent to the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenHint {
+    /// No prompt pending
+    Idle,
+    ConfirmOutput,
+    ConfirmFee,
+    ConfirmTransaction,
+    ShowAddress,
+    SignMessage,
+    EnterPin,
+    EnterPassphrase,
+    /// Recovery: the host is entering the sentence through the cipher
+    EnterRecoveryWord,
+    /// Setup: the user is writing down the recovery sentence
+    ConfirmRecoveryWord,
+    ConfirmReset,
+    ConfirmWipe,
+    ConfirmFirmwareUpdate,
+    ConfirmPinChange,
+    ConfirmSettings,
+    /// A prompt without a more specific hint
+    ConfirmAction,
+}
+
+impl ScreenHint {
+    /// Hint for a `ButtonRequest` code (`ButtonRequestType` in types.proto)
+    pub fn from_button_request(code: Option<i32>) -> Self {
+        match code {
+            Some(2) => ScreenHint::ConfirmFee,
+            Some(3) | Some(12) | Some(13) => ScreenHint::ConfirmOutput,
+            Some(4) => ScreenHint::ConfirmReset,
+            Some(5) => ScreenHint::ConfirmRecoveryWord,
+            Some(6) => ScreenHint::ConfirmWipe,
+            Some(8) => ScreenHint::ConfirmTransaction,
+            Some(9) | Some(11) => ScreenHint::ConfirmFirmwareUpdate,
+            Some(10) => ScreenHint::ShowAddress,
+            Some(14) | Some(15) | Some(16) | Some(17) | Some(29) | Some(31) => ScreenHint::ConfirmSettings,
+            Some(18) | Some(28) => ScreenHint::SignMessage,
+            Some(21) => ScreenHint::EnterRecoveryWord,
+            Some(24) | Some(25) | Some(26) | Some(36) | Some(37) => ScreenHint::ConfirmPinChange,
+            _ => ScreenHint::ConfirmAction,
+        }
+    }
+
+    /// Hint for a message the device sent; anything that isn't a prompt means
+    /// the screen went back to idle
+    pub fn from_message(msg: &Message) -> Self {
+        match msg {
+            Message::ButtonRequest(req) => Self::from_button_request(req.code),
+            Message::PinMatrixRequest(_) => ScreenHint::EnterPin,
+            Message::PassphraseRequest(_) => ScreenHint::EnterPassphrase,
+            Message::CharacterRequest(_) | Message::WordRequest(_) => ScreenHint::EnterRecoveryWord,
+            _ => ScreenHint::Idle,
+        }
+    }
+}
+
+/// Why the device asks for a button press: `ButtonRequestType` in types.proto
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonRequestKind {
+    Other,
+    /// The fee is above the firmware's threshold and has to be confirmed on its own
+    FeeOverThreshold,
+    ConfirmOutput,
+    ResetDevice,
+    ConfirmWord,
+    WipeDevice,
+    ProtectCall,
+    SignTx,
+    FirmwareCheck,
+    Address,
+    FirmwareErase,
+    ConfirmTransferToAccount,
+    ConfirmTransferToNodePath,
+    ChangeLabel,
+    ChangeLanguage,
+    EnablePassphrase,
+    DisablePassphrase,
+    EncryptAndSignMessage,
+    EncryptMessage,
+    ImportPrivateKey,
+    ImportRecoverySentence,
+    SignIdentity,
+    Ping,
+    RemovePin,
+    ChangePin,
+    CreatePin,
+    GetEntropy,
+    SignMessage,
+    ApplyPolicies,
+    AutoLockDelayMs,
+    U2fCounter,
+    ConfirmEosAction,
+    ConfirmEosBudget,
+    ConfirmMemo,
+    RemoveWipeCode,
+    ChangeWipeCode,
+    CreateWipeCode,
+    /// Missing, or a code newer than this table
+    Unknown,
+}
+
+impl ButtonRequestKind {
+    pub fn from_code(code: Option<i32>) -> Self {
+        match code {
+            Some(1) => ButtonRequestKind::Other,
+            Some(2) => ButtonRequestKind::FeeOverThreshold,
+            Some(3) => ButtonRequestKind::ConfirmOutput,
+            Some(4) => ButtonRequestKind::ResetDevice,
+            Some(5) => ButtonRequestKind::ConfirmWord,
+            Some(6) => ButtonRequestKind::WipeDevice,
+            Some(7) => ButtonRequestKind::ProtectCall,
+            Some(8) => ButtonRequestKind::SignTx,
+            Some(9) => ButtonRequestKind::FirmwareCheck,
+            Some(10) => ButtonRequestKind::Address,
+            Some(11) => ButtonRequestKind::FirmwareErase,
+            Some(12) => ButtonRequestKind::ConfirmTransferToAccount,
+            Some(13) => ButtonRequestKind::ConfirmTransferToNodePath,
+            Some(14) => ButtonRequestKind::ChangeLabel,
+            Some(15) => ButtonRequestKind::ChangeLanguage,
+            Some(16) => ButtonRequestKind::EnablePassphrase,
+            Some(17) => ButtonRequestKind::DisablePassphrase,
+            Some(18) => ButtonRequestKind::EncryptAndSignMessage,
+            Some(19) => ButtonRequestKind::EncryptMessage,
+            Some(20) => ButtonRequestKind::ImportPrivateKey,
+            Some(21) => ButtonRequestKind::ImportRecoverySentence,
+            Some(22) => ButtonRequestKind::SignIdentity,
+            Some(23) => ButtonRequestKind::Ping,
+            Some(24) => ButtonRequestKind::RemovePin,
+            Some(25) => ButtonRequestKind::ChangePin,
+            Some(26) => ButtonRequestKind::CreatePin,
+            Some(27) => ButtonRequestKind::GetEntropy,
+            Some(28) => ButtonRequestKind::SignMessage,
+            Some(29) => ButtonRequestKind::ApplyPolicies,
+            Some(31) => ButtonRequestKind::AutoLockDelayMs,
+            Some(32) => ButtonRequestKind::U2fCounter,
+            Some(33) => ButtonRequestKind::ConfirmEosAction,
+            Some(34) => ButtonRequestKind::ConfirmEosBudget,
+            Some(35) => ButtonRequestKind::ConfirmMemo,
+            Some(36) => ButtonRequestKind::RemoveWipeCode,
+            Some(37) => ButtonRequestKind::ChangeWipeCode,
+            Some(38) => ButtonRequestKind::CreateWipeCode,
+            _ => ButtonRequestKind::Unknown,
+        }
+    }
+}
+
+/// A `ButtonRequest` as the device sent it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonPrompt {
+    pub request_type: ButtonRequestKind,
+    /// Raw `ButtonRequestType` code, for codes `request_type` doesn't know
+    pub code: Option<i32>,
+    /// The request's `data`, e.g. the amount or address being confirmed
+    pub context: Option<String>,
+}
+
+impl ButtonPrompt {
+    pub fn from_request(request: &ButtonRequest) -> Self {
+        Self {
+            request_type: ButtonRequestKind::from_code(request.code),
+            code: request.code,
+            context: request.data.clone().filter(|data| !data.is_empty()),
+        }
+    }
+}
+
+/// What a `PinMatrixRequest` asks for, from its `type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinPurpose {
+    /// The current PIN, to unlock or to authorize changing it
+    Unlock,
+    /// First entry of a new PIN
+    NewPin,
+    /// The new PIN again
+    ConfirmNewPin,
+}
+
+impl PinPurpose {
+    /// Requests without a type come from firmware that only ever asks for the current PIN
+    pub fn from_request_type(request_type: Option<i32>) -> Self {
+        match request_type.and_then(PinMatrixRequestType::from_i32) {
+            Some(PinMatrixRequestType::NewFirst) => PinPurpose::NewPin,
+            Some(PinMatrixRequestType::NewSecond) => PinPurpose::ConfirmNewPin,
+            Some(PinMatrixRequestType::Current) | None => PinPurpose::Unlock,
+        }
+    }
+}
+
+/// Prompts kept for a subscriber that falls behind
+const PROMPT_CAPACITY: usize = 16;
+
+/// Latest hint of one device, shared between its worker and its handles so it
+/// can be read while the worker is blocked waiting on the user. Button, PIN and
+/// label confirmation requests are also published to subscribers as they arrive.
+#[derive(Debug, Clone)]
+pub struct ScreenHintCell {
+    hint: Arc<Mutex<ScreenHint>>,
+    button_prompts: broadcast::Sender<ButtonPrompt>,
+    pin_prompts: broadcast::Sender<PinPurpose>,
+    label_prompts: broadcast::Sender<String>,
+}
+
+impl Default for ScreenHintCell {
+    fn default() -> Self {
+        Self {
+            hint: Arc::new(Mutex::new(ScreenHint::Idle)),
+            button_prompts: broadcast::channel(PROMPT_CAPACITY).0,
+            pin_prompts: broadcast::channel(PROMPT_CAPACITY).0,
+            label_prompts: broadcast::channel(PROMPT_CAPACITY).0,
+        }
+    }
+}
+
+impl ScreenHintCell {
+    pub fn get(&self) -> ScreenHint {
+        match self.hint.lock() {
+            Ok(hint) => *hint,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    pub fn set(&self, hint: ScreenHint) {
+        match self.hint.lock() {
+            Ok(mut current) => *current = hint,
+            Err(poisoned) => *poisoned.into_inner() = hint,
+        }
+    }
+
+    /// Button requests the device sends from now on
+    pub fn subscribe_button_prompts(&self) -> broadcast::Receiver<ButtonPrompt> {
+        self.button_prompts.subscribe()
+    }
+
+    fn publish_button_prompt(&self, prompt: ButtonPrompt) {
+        // Nobody listening is fine
+        let _ = self.button_prompts.send(prompt);
+    }
+
+    /// PIN matrix requests the device sends from now on
+    pub fn subscribe_pin_prompts(&self) -> broadcast::Receiver<PinPurpose> {
+        self.pin_prompts.subscribe()
+    }
+
+    fn publish_pin_prompt(&self, purpose: PinPurpose) {
+        let _ = self.pin_prompts.send(purpose);
+    }
+
+    /// Labels the device asks to confirm from now on, exactly as they were
+    /// sent in `ApplySettings`
+    pub fn subscribe_label_prompts(&self) -> broadcast::Receiver<String> {
+        self.label_prompts.subscribe()
+    }
+
+    fn publish_label_prompt(&self, label: String) {
+        let _ = self.label_prompts.send(label);
+    }
+}
+
+/// Transport wrapper that records every prompt coming back from the device
+pub struct ScreenHintRecorder {
+    inner: Box<dyn ProtocolAdapter + Send>,
+    hint: ScreenHintCell,
+    /// Label of the `ApplySettings` in flight, until the device answers with
+    /// something other than a button request
+    sent_label: Option<String>,
+}
+
+impl ScreenHintRecorder {
+    pub fn new(inner: Box<dyn ProtocolAdapter + Send>, hint: ScreenHintCell) -> Self {
+        Self { inner, hint, sent_label: None }
+    }
+
+    /// Remember the label of an outgoing `ApplySettings`. Acks keep it: they
+    /// continue the same exchange.
+    fn track_sent(&mut self, msg: &Message) {
+        match msg {
+            Message::ApplySettings(settings) => self.sent_label = settings.label.clone(),
+            Message::ButtonAck(_) | Message::PinMatrixAck(_) | Message::PassphraseAck(_) => {}
+            _ => self.sent_label = None,
+        }
+    }
+}
+
+impl ProtocolAdapter for ScreenHintRecorder {
+    fn reset(&mut self) -> Result<()> {
+        self.hint.set(ScreenHint::Idle);
+        self.inner.reset()
+    }
+
+    fn send(&mut self, msg: Message) -> Result<()> {
+        self.track_sent(&msg);
+        self.inner.send(msg)
+    }
+
+    fn handle(&mut self, msg: Message) -> Result<Message> {
+        self.track_sent(&msg);
+        match self.inner.handle(msg) {
+            Ok(out) => {
+                self.hint.set(ScreenHint::from_message(&out));
+                match &out {
+                    Message::ButtonRequest(request) => {
+                        let prompt = ButtonPrompt::from_request(request);
+                        if prompt.request_type == ButtonRequestKind::ChangeLabel {
+                            if let Some(label) = self.sent_label.clone() {
+                                self.hint.publish_label_prompt(label);
+                            }
+                        }
+                        self.hint.publish_button_prompt(prompt);
+                    }
+                    Message::PinMatrixRequest(request) => {
+                        self.hint.publish_pin_prompt(PinPurpose::from_request_type(request.r#type))
+                    }
+                    Message::PassphraseRequest(_) => {}
+                    _ => self.sent_label = None,
+                }
+                Ok(out)
+            }
+            Err(e) => {
+                self.hint.set(ScreenHint::Idle);
+                self.sent_label = None;
+                Err(e)
+            }
+        }
+    }
+
+    fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{ApplySettings, ButtonAck, Features, PinMatrixRequest, Success};
+
+    #[test]
+    fn test_hint_follows_device_prompts() {
+        let button = |code| Message::from(ButtonRequest { code: Some(code), ..Default::default() });
+        assert_eq!(ScreenHint::from_message(&button(3)), ScreenHint::ConfirmOutput);
+        assert_eq!(ScreenHint::from_message(&button(10)), ScreenHint::ShowAddress);
+        assert_eq!(ScreenHint::from_message(&button(8)), ScreenHint::ConfirmTransaction);
+        assert_eq!(ScreenHint::from_message(&button(1)), ScreenHint::ConfirmAction);
+        assert_eq!(ScreenHint::from_button_request(None), ScreenHint::ConfirmAction);
+        assert_eq!(ScreenHint::from_message(&PinMatrixRequest::default().into()), ScreenHint::EnterPin);
+        assert_eq!(ScreenHint::from_message(&Features::default().into()), ScreenHint::Idle);
+
+        let cell = ScreenHintCell::default();
+        let shared = cell.clone();
+        shared.set(ScreenHint::EnterPin);
+        assert_eq!(cell.get(), ScreenHint::EnterPin);
+        assert_eq!(serde_json::to_value(ScreenHint::ConfirmOutput).unwrap(), "confirm_output");
+    }
+
+    #[test]
+    fn test_button_prompts_are_published() {
+        let request = ButtonRequest { code: Some(2), data: Some("0.0012 BTC".to_string()) };
+        let prompt = ButtonPrompt::from_request(&request);
+        assert_eq!(prompt.request_type, ButtonRequestKind::FeeOverThreshold);
+        assert_eq!(prompt.context.as_deref(), Some("0.0012 BTC"));
+        assert_eq!(ButtonRequestKind::from_code(Some(30)), ButtonRequestKind::Unknown);
+        assert_eq!(ButtonRequestKind::from_code(Some(38)), ButtonRequestKind::CreateWipeCode);
+        assert_eq!(serde_json::to_value(ButtonRequestKind::FeeOverThreshold).unwrap(), "fee_over_threshold");
+
+        let cell = ScreenHintCell::default();
+        let mut prompts = cell.clone().subscribe_button_prompts();
+        cell.publish_button_prompt(prompt.clone());
+        assert_eq!(prompts.try_recv().unwrap(), prompt);
+    }
+
+    #[test]
+    fn test_pin_purpose_from_request_type() {
+        assert_eq!(PinPurpose::from_request_type(Some(1)), PinPurpose::Unlock);
+        assert_eq!(PinPurpose::from_request_type(Some(2)), PinPurpose::NewPin);
+        assert_eq!(PinPurpose::from_request_type(Some(3)), PinPurpose::ConfirmNewPin);
+        assert_eq!(PinPurpose::from_request_type(None), PinPurpose::Unlock);
+        assert_eq!(serde_json::to_value(PinPurpose::ConfirmNewPin).unwrap(), "confirm_new_pin");
+
+        let cell = ScreenHintCell::default();
+        let mut prompts = cell.subscribe_pin_prompts();
+        cell.publish_pin_prompt(PinPurpose::NewPin);
+        assert_eq!(prompts.try_recv().unwrap(), PinPurpose::NewPin);
+    }
+
+    /// Answers each message with the next scripted reply
+    struct Scripted(std::collections::VecDeque<Message>);
+
+    impl ProtocolAdapter for Scripted {
+        fn reset(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn send(&mut self, _msg: Message) -> Result<()> {
+            Ok(())
+        }
+        fn handle(&mut self, _msg: Message) -> Result<Message> {
+            Ok(self.0.pop_front().expect("unscripted message"))
+        }
+        fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter {
+            self
+        }
+    }
+
+    #[test]
+    fn test_label_confirmation_uses_sent_label() {
+        let change_label = || Message::from(ButtonRequest { code: Some(14), data: None });
+        let replies = vec![change_label(), Success::default().into(), change_label()];
+        let cell = ScreenHintCell::default();
+        let mut labels = cell.subscribe_label_prompts();
+        let mut recorder = ScreenHintRecorder::new(Box::new(Scripted(replies.into())), cell.clone());
+
+        let settings = ApplySettings { label: Some("Cold storage".to_string()), ..Default::default() };
+        recorder.handle(settings.into()).unwrap();
+        assert_eq!(labels.try_recv().unwrap(), "Cold storage");
+        recorder.handle(ButtonAck::default().into()).unwrap();
+
+        // A label prompt after the exchange ended has no sent label to mirror
+        recorder.handle(ApplySettings::default().into()).unwrap();
+        assert!(labels.try_recv().is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Minimal DfuSe (ST's DFU 1.1a extension) flasher.
+//!
+//! Used only to recover devices that an interrupted update left in the STM32
+//! system bootloader. Normal bootloader/firmware updates go through the
+//! protobuf `FirmwareErase`/`FirmwareUpload` path instead.
+
+use anyhow::{anyhow, bail, Result};
+use core::time::Duration;
+use rusb::{Device, DeviceHandle, UsbContext};
+use sha2::{Digest, Sha256};
+
+/// Start of internal flash on the STM32F205
+pub const FLASH_BASE: u32 = 0x0800_0000;
+
+/// STM32F205 (1MB) sector start offsets, relative to `FLASH_BASE`
+const SECTOR_OFFSETS: [u32; 12] = [
+    0x0_0000, 0x0_4000, 0x0_8000, 0x0_C000, 0x1_0000, 0x2_0000,
+    0x4_0000, 0x6_0000, 0x8_0000, 0xA_0000, 0xC_0000, 0xE_0000,
+];
+const FLASH_SIZE: u32 = 0x10_0000;
+
+/// Bootstrap code in sector 0, where the MCU boots from
+const BOOTSTRAP_OFFSET: usize = 0x0_0000;
+const BOOTSTRAP_SIZE: usize = 0x0_4000;
+/// Bootloader in sectors 5 and 6. The device hashes this whole region into
+/// `Features.bootloader_hash`.
+const BOOTLOADER_OFFSET: usize = 0x2_0000;
+const BOOTLOADER_SIZE: usize = 0x4_0000;
+/// A recovery image covers flash from the bootstrap up to the end of the
+/// bootloader. The storage sectors in between hold the seed and are never written.
+pub const RECOVERY_IMAGE_SIZE: usize = BOOTLOADER_OFFSET + BOOTLOADER_SIZE;
+
+/// SRAM of the STM32F205; the initial stack pointer may sit at its very end
+const SRAM_START: u32 = 0x2000_0000;
+const SRAM_END: u32 = 0x2002_0000;
+
+const TRANSFER_SIZE: usize = 2048;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// DFU class requests
+const DFU_DNLOAD: u8 = 1;
+const DFU_GETSTATUS: u8 = 3;
+const DFU_CLRSTATUS: u8 = 4;
+const DFU_ABORT: u8 = 6;
+
+const REQUEST_OUT: u8 = 0x21; // host-to-device | class | interface
+const REQUEST_IN: u8 = 0xa1; // device-to-host | class | interface
+
+// DFU states
+const STATE_DFU_IDLE: u8 = 2;
+const STATE_DNLOAD_SYNC: u8 = 3;
+const STATE_DNBUSY: u8 = 4;
+const STATE_DNLOAD_IDLE: u8 = 5;
+const STATE_DFU_ERROR: u8 = 10;
+
+// DfuSe commands sent as block 0 downloads
+const DFUSE_SET_ADDRESS: u8 = 0x21;
+const DFUSE_ERASE: u8 = 0x41;
+
+/// Why a recovery image was refused. Nothing is written to the device for any of these.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RecoveryImageError {
+    #[error("Recovery image must be {expected} bytes, got {actual}")]
+    WrongSize { expected: usize, actual: usize },
+    #[error("Recovery image doesn't start with an STM32F205 vector table")]
+    NotBootable,
+    #[error("Recovery image bootloader {hash} is not a released KeepKey bootloader")]
+    UnknownBootloader { hash: String },
+}
+
+/// Bootloader hashes (hex) to release versions, from the bundled releases.json
+static KNOWN_BOOTLOADERS: once_cell::sync::Lazy<Vec<(String, String)>> = once_cell::sync::Lazy::new(|| {
+    let releases: serde_json::Value = match serde_json::from_str(include_str!("../firmware/releases.json")) {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!("Bundled releases.json is invalid: {}", e);
+            return Vec::new();
+        }
+    };
+    releases["hashes"]["bootloader"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(hash, version)| {
+            Some((hash.to_ascii_lowercase(), version.as_str()?.trim_start_matches('v').to_string()))
+        })
+        .collect()
+});
+
+/// Check a recovery image before anything touches the device: its size, that
+/// it boots on the KeepKey's MCU, and that its bootloader is one KeepKey
+/// released. Returns the bootloader version.
+pub fn check_recovery_image(image: &[u8]) -> Result<String, RecoveryImageError> {
+    check_recovery_image_with(image, |hash| {
+        KNOWN_BOOTLOADERS
+            .iter()
+            .find(|(known, _)| known == hash)
+            .map(|(_, version)| version.clone())
+    })
+}
+
+fn check_recovery_image_with(
+    image: &[u8],
+    bootloader_version: impl Fn(&str) -> Option<String>,
+) -> Result<String, RecoveryImageError> {
+    if image.len() != RECOVERY_IMAGE_SIZE {
+        return Err(RecoveryImageError::WrongSize { expected: RECOVERY_IMAGE_SIZE, actual: image.len() });
+    }
+
+    // Vector table: initial stack pointer in SRAM, Thumb reset handler in the bootstrap sector
+    let word = |offset: usize| u32::from_le_bytes([image[offset], image[offset + 1], image[offset + 2], image[offset + 3]]);
+    let (stack_pointer, reset_handler) = (word(BOOTSTRAP_OFFSET), word(BOOTSTRAP_OFFSET + 4));
+    let bootstrap = FLASH_BASE + BOOTSTRAP_OFFSET as u32..FLASH_BASE + (BOOTSTRAP_OFFSET + BOOTSTRAP_SIZE) as u32;
+    if !(SRAM_START..=SRAM_END).contains(&stack_pointer)
+        || reset_handler & 1 == 0
+        || !bootstrap.contains(&(reset_handler & !1))
+    {
+        return Err(RecoveryImageError::NotBootable);
+    }
+
+    let hash = hex::encode(Sha256::digest(&image[BOOTLOADER_OFFSET..]));
+    bootloader_version(&hash).ok_or(RecoveryImageError::UnknownBootloader { hash })
+}
+
+/// The parts of a checked recovery image that get written: the bootstrap and
+/// the bootloader, as (flash address, data)
+pub fn recovery_regions(image: &[u8]) -> [(u32, &[u8]); 2] {
+    [
+        (FLASH_BASE + BOOTSTRAP_OFFSET as u32, &image[BOOTSTRAP_OFFSET..BOOTSTRAP_OFFSET + BOOTSTRAP_SIZE]),
+        (FLASH_BASE + BOOTLOADER_OFFSET as u32, &image[BOOTLOADER_OFFSET..]),
+    ]
+}
+
+struct DfuStatus {
+    status: u8,
+    state: u8,
+    poll_timeout: Duration,
+}
+
+pub struct DfuDevice<T: UsbContext> {
+    handle: DeviceHandle<T>,
+    interface: u8,
+}
+
+impl<T: UsbContext> DfuDevice<T> {
+    /// Open a device in DFU mode and bring it to the dfuIDLE state
+    pub fn open(device: &Device<T>) -> Result<Self> {
+        let handle = device.open()?;
+
+        match handle.set_auto_detach_kernel_driver(true) {
+            Err(rusb::Error::NotSupported) => Ok(()),
+            x => x,
+        }?;
+
+        // Interface 0, alt setting 0 is the internal flash on STM32 parts
+        handle.claim_interface(0)?;
+        handle.set_alternate_setting(0, 0)?;
+
+        let dfu = Self { handle, interface: 0 };
+        dfu.reset_to_idle()?;
+        Ok(dfu)
+    }
+
+    /// Erase the sectors covered by each (address, data) region and write it there,
+    /// then leave DFU mode from the first region's address. Sectors outside the
+    /// regions are left untouched.
+    /// `progress` is called with (bytes_written, total_bytes).
+    pub fn flash(&self, regions: &[(u32, &[u8])], mut progress: impl FnMut(usize, usize)) -> Result<()> {
+        let Some(&(start, _)) = regions.first() else {
+            bail!("Nothing to flash");
+        };
+        let mut sectors = Vec::new();
+        for &(base, data) in regions {
+            if data.is_empty() {
+                bail!("Empty flash region at 0x{:08x}", base);
+            }
+            let end = base
+                .checked_add(data.len() as u32)
+                .filter(|end| base >= FLASH_BASE && *end <= FLASH_BASE + FLASH_SIZE)
+                .ok_or_else(|| anyhow!("Flash region at 0x{:08x} does not fit in flash", base))?;
+            sectors.extend(sectors_in_range(base, end));
+        }
+
+        for sector in sectors {
+            log::info!("DFU: erasing sector at 0x{:08x}", sector);
+            self.dfuse_command(DFUSE_ERASE, sector)?;
+        }
+
+        let total = regions.iter().map(|(_, data)| data.len()).sum();
+        let mut written = 0;
+        for &(base, data) in regions {
+            for (i, chunk) in data.chunks(TRANSFER_SIZE).enumerate() {
+                // With the address pointer set per chunk, block 2 always writes at the pointer
+                self.dfuse_command(DFUSE_SET_ADDRESS, base + (i * TRANSFER_SIZE) as u32)?;
+                self.download(2, chunk)?;
+                written += chunk.len();
+                progress(written, total);
+            }
+        }
+
+        // Leave DFU: point back at the start and send a zero-length download.
+        // The device resets during manifestation, so the final status may fail.
+        self.dfuse_command(DFUSE_SET_ADDRESS, start)?;
+        self.handle
+            .write_control(REQUEST_OUT, DFU_DNLOAD, 0, self.interface as u16, &[], TIMEOUT)?;
+        let _ = self.get_status();
+
+        Ok(())
+    }
+
+    fn get_status(&self) -> Result<DfuStatus> {
+        let mut buf = [0u8; 6];
+        let n = self
+            .handle
+            .read_control(REQUEST_IN, DFU_GETSTATUS, 0, self.interface as u16, &mut buf, TIMEOUT)?;
+        if n < 6 {
+            bail!("Short DFU status response ({} bytes)", n);
+        }
+        Ok(DfuStatus {
+            status: buf[0],
+            poll_timeout: Duration::from_millis(u32::from_le_bytes([buf[1], buf[2], buf[3], 0]) as u64),
+            state: buf[4],
+        })
+    }
+
+    fn reset_to_idle(&self) -> Result<()> {
+        let status = self.get_status()?;
+        if status.state == STATE_DFU_ERROR {
+            self.handle
+                .write_control(REQUEST_OUT, DFU_CLRSTATUS, 0, self.interface as u16, &[], TIMEOUT)?;
+        } else if status.state != STATE_DFU_IDLE {
+            self.handle
+                .write_control(REQUEST_OUT, DFU_ABORT, 0, self.interface as u16, &[], TIMEOUT)?;
+        }
+
+        let status = self.get_status()?;
+        if status.state != STATE_DFU_IDLE {
+            bail!("Device did not return to dfuIDLE (state {})", status.state);
+        }
+        Ok(())
+    }
+
+    fn dfuse_command(&self, command: u8, address: u32) -> Result<()> {
+        let mut payload = [0u8; 5];
+        payload[0] = command;
+        payload[1..].copy_from_slice(&address.to_le_bytes());
+        self.download(0, &payload)
+    }
+
+    fn download(&self, block: u16, data: &[u8]) -> Result<()> {
+        self.handle
+            .write_control(REQUEST_OUT, DFU_DNLOAD, block, self.interface as u16, data, TIMEOUT)?;
+        self.wait_for_download()
+    }
+
+    fn wait_for_download(&self) -> Result<()> {
+        loop {
+            let status = self.get_status()?;
+            if status.status != 0 {
+                bail!("DFU error status {} (state {})", status.status, status.state);
+            }
+            match status.state {
+                STATE_DNLOAD_IDLE | STATE_DFU_IDLE => return Ok(()),
+                STATE_DNLOAD_SYNC | STATE_DNBUSY => std::thread::sleep(status.poll_timeout),
+                state => bail!("Unexpected DFU state {} during download", state),
+            }
+        }
+    }
+}
+
+/// Start addresses of every flash sector overlapping [start, end)
+fn sectors_in_range(start: u32, end: u32) -> Vec<u32> {
+    SECTOR_OFFSETS
+        .iter()
+        .enumerate()
+        .filter_map(|(i, offset)| {
+            let sector_start = FLASH_BASE + offset;
+            let sector_end = SECTOR_OFFSETS.get(i + 1).map(|o| FLASH_BASE + o).unwrap_or(FLASH_BASE + FLASH_SIZE);
+            (sector_start < end && sector_end > start).then_some(sector_start)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bootable image whose bootloader the lookup below knows
+    fn image() -> Vec<u8> {
+        let mut image = vec![0xff; RECOVERY_IMAGE_SIZE];
+        image[0..4].copy_from_slice(&SRAM_END.to_le_bytes());
+        image[4..8].copy_from_slice(&(FLASH_BASE + 0x1c1).to_le_bytes());
+        image[BOOTLOADER_OFFSET..BOOTLOADER_OFFSET + 4].copy_from_slice(b"boot");
+        image
+    }
+
+    fn known(image: &[u8]) -> impl Fn(&str) -> Option<String> {
+        let hash = hex::encode(Sha256::digest(&image[BOOTLOADER_OFFSET..]));
+        move |h: &str| (h == hash).then(|| "2.1.4".to_string())
+    }
+
+    #[test]
+    fn test_recovery_image_accepted() {
+        let image = image();
+        assert_eq!(check_recovery_image_with(&image, known(&image)), Ok("2.1.4".to_string()));
+
+        // Only the bootstrap and bootloader sectors are erased; storage (1-4) is kept
+        let sectors: Vec<u32> = recovery_regions(&image)
+            .iter()
+            .flat_map(|(base, data)| sectors_in_range(*base, base + data.len() as u32))
+            .collect();
+        assert_eq!(sectors, vec![0x0800_0000, 0x0802_0000, 0x0804_0000]);
+    }
+
+    #[test]
+    fn test_recovery_image_wrong_size_refused() {
+        let image = image();
+        let lookup = known(&image);
+        assert_eq!(
+            check_recovery_image_with(&image[..RECOVERY_IMAGE_SIZE - 1], &lookup),
+            Err(RecoveryImageError::WrongSize { expected: RECOVERY_IMAGE_SIZE, actual: RECOVERY_IMAGE_SIZE - 1 })
+        );
+        assert!(matches!(check_recovery_image_with(&[], &lookup), Err(RecoveryImageError::WrongSize { .. })));
+
+        let mut padded = image.clone();
+        padded.extend_from_slice(&[0xff; 16]);
+        assert!(matches!(check_recovery_image_with(&padded, &lookup), Err(RecoveryImageError::WrongSize { .. })));
+
+        // A KeepKey firmware/updater file isn't a raw flash image
+        let mut firmware = vec![0u8; RECOVERY_IMAGE_SIZE];
+        firmware[..4].copy_from_slice(b"KPKY");
+        assert_eq!(check_recovery_image_with(&firmware, &lookup), Err(RecoveryImageError::NotBootable));
+    }
+
+    #[test]
+    fn test_recovery_image_not_bootable_refused() {
+        let good = image();
+        let lookup = known(&good);
+
+        let mut stack_in_flash = good.clone();
+        stack_in_flash[0..4].copy_from_slice(&FLASH_BASE.to_le_bytes());
+        assert_eq!(check_recovery_image_with(&stack_in_flash, &lookup), Err(RecoveryImageError::NotBootable));
+
+        // An ARM (not Thumb) reset handler
+        let mut arm_reset = good.clone();
+        arm_reset[4..8].copy_from_slice(&(FLASH_BASE + 0x1c0).to_le_bytes());
+        assert_eq!(check_recovery_image_with(&arm_reset, &lookup), Err(RecoveryImageError::NotBootable));
+
+        // A reset handler outside the bootstrap sector, e.g. an image built for another part
+        let mut elsewhere = good;
+        elsewhere[4..8].copy_from_slice(&(FLASH_BASE + 0x8001).to_le_bytes());
+        assert_eq!(check_recovery_image_with(&elsewhere, &lookup), Err(RecoveryImageError::NotBootable));
+    }
+
+    #[test]
+    fn test_recovery_image_unknown_bootloader_refused() {
+        let good = image();
+        let mut tampered = good.clone();
+        tampered[BOOTLOADER_OFFSET + 0x100] ^= 0x01;
+        assert!(matches!(
+            check_recovery_image_with(&tampered, known(&good)),
+            Err(RecoveryImageError::UnknownBootloader { .. })
+        ));
+
+        // The bundled release table doesn't know a made-up bootloader either
+        assert!(matches!(check_recovery_image(&good), Err(RecoveryImageError::UnknownBootloader { .. })));
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Result};
 use hidapi::{HidApi, HidDevice};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use log::{debug, info, warn, error};
 
@@ -9,6 +9,10 @@ use super::Transport;
 const KEEPKEY_VID: u16 = 0x2B24;
 const KEEPKEY_PIDS: &[u16] = &[0x0001, 0x0002]; // Legacy and bootloader PIDs
 const HID_REPORT_SIZE: usize = 64;
+/// v4 response header: [0x3f][0x23][0x23][msg_type(2)][length(4)]
+const HID_HEADER_SIZE: usize = 9;
+/// How long to keep waiting for the rest of a message once its header has arrived
+const INCOMPLETE_MESSAGE_TIMEOUT: Duration = Duration::from_secs(2);
 
 // Windows HID fix: Use different report ID handling for Windows vs other platforms
 #[cfg(target_os = "windows")]
@@ -25,6 +29,118 @@ pub enum HidError {
     Io(#[from] std::io::Error),
     #[error("Other error: {0}")]
     Other(String),
+    /// Nothing arrived before the read timeout
+    #[error("{0}")]
+    Timeout(String),
+}
+
+/// Reassembles v4 HID reports into a single `##`-framed message.
+///
+/// Under load hidapi can hand back a 64-byte report across more than one read,
+/// so the assembler tracks its offset within the current report instead of
+/// assuming every read starts on a report boundary. Continuation markers are
+/// only stripped at the start of a report, and report padding after the last
+/// message byte is ignored.
+#[derive(Debug, Default)]
+pub struct ReportAssembler {
+    header: Vec<u8>,
+    msg_length: Option<usize>,
+    data: Vec<u8>,
+    report_offset: usize,
+    reports: usize,
+}
+
+impl ReportAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the bytes returned by one read. Returns `true` once the full message
+    /// has been framed; bytes past the end of the message are discarded.
+    pub fn push(&mut self, fragment: &[u8]) -> Result<bool, HidError> {
+        for &byte in fragment {
+            if self.is_complete() {
+                break;
+            }
+
+            let at_report_start = self.report_offset == 0;
+            if at_report_start {
+                self.reports += 1;
+            }
+            self.report_offset = (self.report_offset + 1) % HID_REPORT_SIZE;
+
+            if self.msg_length.is_none() {
+                let expected = match self.header.len() {
+                    0 => Some(0x3f),
+                    1 | 2 => Some(0x23),
+                    _ => None,
+                };
+                if let Some(expected) = expected {
+                    if byte != expected {
+                        self.header.push(byte);
+                        return Err(HidError::Other(format!(
+                            "Invalid response header: {}",
+                            self.header.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+                        )));
+                    }
+                }
+                self.header.push(byte);
+                if self.header.len() == HID_HEADER_SIZE {
+                    let length = u32::from_be_bytes([self.header[5], self.header[6], self.header[7], self.header[8]]);
+                    self.msg_length = Some(length as usize);
+                }
+                continue;
+            }
+
+            // Skip the '?' prefix byte in continuation reports
+            if at_report_start && byte == b'?' {
+                continue;
+            }
+            self.data.push(byte);
+        }
+
+        Ok(self.is_complete())
+    }
+
+    /// Whether any part of the header has been received yet.
+    pub fn is_started(&self) -> bool {
+        !self.header.is_empty()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        matches!(self.msg_length, Some(len) if self.data.len() >= len)
+    }
+
+    /// Message type from the header, once it has been fully received.
+    pub fn msg_type(&self) -> Option<u16> {
+        self.msg_length.map(|_| u16::from_be_bytes([self.header[3], self.header[4]]))
+    }
+
+    pub fn msg_length(&self) -> Option<usize> {
+        self.msg_length
+    }
+
+    pub fn bytes_received(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Number of HID reports the message has spanned so far.
+    pub fn reports(&self) -> usize {
+        self.reports
+    }
+
+    /// The reassembled message in the `##` format `Message::decode` expects, or
+    /// `None` if it is still incomplete.
+    pub fn into_message(self) -> Option<Vec<u8>> {
+        if !self.is_complete() {
+            return None;
+        }
+        let mut buf = Vec::with_capacity(8 + self.data.len());
+        buf.extend_from_slice(b"##");
+        buf.extend_from_slice(&self.header[3..HID_HEADER_SIZE]);
+        buf.extend_from_slice(&self.data);
+        Some(buf)
+    }
 }
 
 pub struct HidTransport {
@@ -362,7 +478,7 @@ impl Transport for HidTransport {
                 timeout_ms
             );
             
-            return Err(HidError::Other(helpful_message));
+            return Err(HidError::Timeout(helpful_message));
         }
         
         info!("HID Read: Received first packet ({} bytes)", size);
@@ -373,70 +489,49 @@ impl Transport for HidTransport {
             .collect();
         debug!("HID Read: First 16 bytes: {}", preview.join(" "));
         
-        // v4 response format: [0x3f][0x23][0x23][msg_type(2)][length(4)][data...]
-        if size < 9 || packet[0] != 0x3f || packet[1] != 0x23 || packet[2] != 0x23 {
+        // Reports can arrive split across reads under load; accumulate fragments until the
+        // whole message is framed instead of treating a short read as a malformed response.
+        let mut assembler = ReportAssembler::new();
+        if let Err(e) = assembler.push(&packet[..size]) {
             error!("HID Read: Invalid response header");
-            return Err(HidError::Other(format!(
-                "Invalid response header: {:02x} {:02x} {:02x}",
-                packet[0], packet[1], packet[2]
-            )));
+            return Err(e);
         }
         
-        // Extract message type and length from header  
-        let msg_type = u16::from_be_bytes([packet[3], packet[4]]);
-        let msg_length = u32::from_be_bytes([packet[5], packet[6], packet[7], packet[8]]) as usize;
-        
-        info!("HID Read: Message type {} (0x{:04x}), length: {} bytes", 
-              msg_type, msg_type, msg_length);
-        
-        // Convert to v5 format for the protocol adapter
-        // Start with ## header that Message::decode expects
-        buf.clear();
-        buf.push(b'#');
-        buf.push(b'#');
-        buf.extend_from_slice(&packet[3..9]); // msg_type(2) + length(4)
-        
-        // Copy initial data from first packet
-        let first_chunk_size = (size - 9).min(msg_length);
-        buf.extend_from_slice(&packet[9..9 + first_chunk_size]);
-        
-        let mut bytes_received = first_chunk_size;
-        debug!("HID Read: First packet contained {} bytes of message data", first_chunk_size);
-        
-        // Read continuation packets if needed
-        let mut packet_count = 1;
-        while bytes_received < msg_length {
-            packet.fill(0);
+        let deadline = Instant::now() + INCOMPLETE_MESSAGE_TIMEOUT;
+        while !assembler.is_complete() {
+            if Instant::now() >= deadline {
+                error!("HID Read: Incomplete message after {} packets", assembler.reports());
+                return Err(HidError::Other(match assembler.msg_length() {
+                    Some(expected) => format!(
+                        "Incomplete message: expected {} bytes, got {}",
+                        expected, assembler.bytes_received()
+                    ),
+                    None => "Incomplete message: response header was truncated".to_string(),
+                }));
+            }
             
-            debug!("HID Read: Reading continuation packet {} (need {} more bytes)...", 
-                   packet_count + 1, msg_length - bytes_received);
-                   
+            packet.fill(0);
             let cont_size = self.device
                 .read_timeout(&mut packet, 100)
                 .map_err(|e| HidError::Other(format!("HID continuation read failed: {}", e)))?;
             
             if cont_size > 0 {
-                // Skip the '?' prefix byte in continuation packets
-                let data_start = if packet[0] == b'?' { 1 } else { 0 };
-                let remaining = msg_length - bytes_received;
-                let to_copy = (cont_size - data_start).min(remaining);
-                
-                debug!("HID Read: Continuation packet {} has {} bytes, copying {} bytes", 
-                       packet_count + 1, cont_size, to_copy);
-                
-                buf.extend_from_slice(&packet[data_start..data_start + to_copy]);
-                bytes_received += to_copy;
-                packet_count += 1;
-            } else if bytes_received < msg_length {
-                // Still need more data but didn't get any
-                error!("HID Read: Incomplete message after {} packets", packet_count);
-                return Err(HidError::Other(format!(
-                    "Incomplete message: expected {} bytes, got {}",
-                    msg_length, bytes_received
-                )));
+                debug!("HID Read: Fragment of {} bytes (have {} of {:?} message bytes)", 
+                       cont_size, assembler.bytes_received(), assembler.msg_length());
+                assembler.push(&packet[..cont_size])?;
             }
         }
         
+        if let Some(msg_type) = assembler.msg_type() {
+            info!("HID Read: Message type {} (0x{:04x}), length: {} bytes", 
+                  msg_type, msg_type, assembler.bytes_received());
+        }
+        let packet_count = assembler.reports();
+        
+        // Convert to v5 format for the protocol adapter
+        *buf = assembler.into_message()
+            .ok_or_else(|| HidError::Other("Incomplete message".to_string()))?;
+        
         info!("HID Read: Complete. Received {} bytes in {} packets", buf.len() - 2, packet_count);
         
         Ok(())
@@ -466,4 +561,67 @@ impl Transport for HidTransport {
         
         Ok(())
     }
-} 
\ No newline at end of file
+    
+    fn is_timeout(error: &Self::Error) -> bool {
+        matches!(error, HidError::Timeout(_))
+    }
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame `data` as v4 reports the way the device sends them.
+    fn reports(msg_type: u16, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut first = vec![0u8; HID_REPORT_SIZE];
+        first[..3].copy_from_slice(&[0x3f, 0x23, 0x23]);
+        first[3..5].copy_from_slice(&msg_type.to_be_bytes());
+        first[5..9].copy_from_slice(&(data.len() as u32).to_be_bytes());
+        let first_chunk = (HID_REPORT_SIZE - HID_HEADER_SIZE).min(data.len());
+        first[9..9 + first_chunk].copy_from_slice(&data[..first_chunk]);
+
+        let mut out = vec![first];
+        for chunk in data[first_chunk..].chunks(HID_REPORT_SIZE - 1) {
+            let mut report = vec![0u8; HID_REPORT_SIZE];
+            report[0] = b'?';
+            report[1..1 + chunk.len()].copy_from_slice(chunk);
+            out.push(report);
+        }
+        out
+    }
+
+    #[test]
+    fn test_reassembles_fragmented_reports() {
+        // Includes '?' bytes in the payload so only report-leading markers get stripped
+        let data: Vec<u8> = (0..150u32).map(|i| if i % 10 == 0 { b'?' } else { i as u8 }).collect();
+        let stream: Vec<u8> = reports(17, &data).concat();
+
+        // Split mid-header and at offsets that don't line up with report boundaries
+        let mut assembler = ReportAssembler::new();
+        let mut complete = false;
+        for fragment in [&stream[..4], &stream[4..40], &stream[40..64], &stream[64..100], &stream[100..]] {
+            assert!(!complete);
+            complete = assembler.push(fragment).unwrap();
+        }
+        assert!(complete);
+        assert_eq!(assembler.msg_type(), Some(17));
+        assert_eq!(assembler.reports(), 3);
+
+        let msg = assembler.into_message().unwrap();
+        assert_eq!(&msg[..2], b"##");
+        assert_eq!(&msg[2..4], &17u16.to_be_bytes());
+        assert_eq!(&msg[4..8], &150u32.to_be_bytes());
+        assert_eq!(&msg[8..], &data[..]);
+    }
+
+    #[test]
+    fn test_incomplete_and_malformed_reports() {
+        let stream: Vec<u8> = reports(2, &[0xaa; 80]).concat();
+        let mut assembler = ReportAssembler::new();
+        assert!(!assembler.push(&stream[..70]).unwrap());
+        assert_eq!(assembler.bytes_received(), 55 + 5);
+        assert!(assembler.into_message().is_none());
+
+        assert!(ReportAssembler::new().push(&[0x3f, 0x23, 0x00]).is_err());
+    }
+}
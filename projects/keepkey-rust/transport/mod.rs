@@ -2,12 +2,16 @@ pub mod protocol_adapter;
 pub mod usb;
 pub mod webusb;
 pub mod hid;
+pub mod dfu;
+pub mod retry;
 
 pub use protocol_adapter::*;
 pub use usb::*;
 pub use webusb::*;
 pub use hid::*;
+pub use retry::{RetryingTransport, TransportConfig};
 
+use crate::cancel::CancelCell;
 use crate::messages::{self, Message};
 use anyhow::{anyhow, bail, Result};
 use core::time::Duration;
@@ -19,6 +23,12 @@ pub trait Transport {
     fn write(&mut self, msg: &[u8], timeout: Duration) -> Result<usize, Self::Error>;
     fn read(&mut self, buf: &mut Vec<u8>, timeout: Duration) -> Result<(), Self::Error>;
     fn reset(&mut self) -> Result<(), Self::Error>;
+    /// Whether `error` is a `read` that ran out of time without receiving
+    /// anything, so reading again is safe
+    fn is_timeout(error: &Self::Error) -> bool {
+        let _ = error;
+        false
+    }
 }
 
 pub fn standard_message_handler(msg: &Message) -> Result<Option<Message>> {
@@ -166,6 +176,13 @@ pub trait ProtocolAdapter {
     fn reset(&mut self) -> Result<()>;
     fn send(&mut self, msg: Message) -> Result<()>;
     fn handle(&mut self, msg: Message) -> Result<Message>;
+    /// `handle`, writing a `Cancel` as soon as `cancel` is requested while
+    /// the device hasn't answered yet. Adapters that can't read in between
+    /// writes just `handle`.
+    fn handle_cancellable(&mut self, msg: Message, cancel: &CancelCell) -> Result<Message> {
+        let _ = cancel;
+        self.handle(msg)
+    }
     fn as_mut_dyn(&mut self) -> &mut dyn ProtocolAdapter;
     fn with_handler<'a: 'b, 'b>(
         &'a mut self,
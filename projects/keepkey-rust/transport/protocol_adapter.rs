@@ -1,6 +1,9 @@
 use super::{ProtocolAdapter, Transport};
-use crate::messages::Message;
+use crate::cancel::CancelCell;
+use crate::messages::{self, Message};
 use anyhow::{anyhow, Result};
+use core::time::Duration;
+use std::time::Instant;
 
 use log::{info, debug};
 
@@ -43,27 +46,65 @@ where
         let mut in_buf = Vec::<u8>::new();
         self.read(&mut in_buf, read_timeout)?;
         
-        info!("ProtocolAdapter::handle: Received {} bytes response", in_buf.len());
+        decode_response(&in_buf)
+    }
 
-        let out = Message::decode(&mut in_buf.as_slice()).map_err(|x| anyhow!(x))?;
-        info!("ProtocolAdapter::handle: Decoded response type: {:?}", out.message_type());
+    fn handle_cancellable(&mut self, msg: Message, cancel: &CancelCell) -> Result<Message> {
+        info!("ProtocolAdapter::handle_cancellable: Processing message type: {:?}", msg.message_type());
         
-        // Clean, concise logging with key info
-        match &out {
-            Message::Features(features) => {
-                let version = format!("{}.{}.{}", 
-                    features.major_version.unwrap_or(0),
-                    features.minor_version.unwrap_or(0), 
-                    features.patch_version.unwrap_or(0)
-                );
-                let label = features.label.as_deref().unwrap_or("Unlabeled");
-                let initialized = if features.initialized.unwrap_or(false) { "✅" } else { "⚠️" };
-                println!("<- Features: {} v{} {}", label, version, initialized);
-            },
-            _ => {
-                println!("<- {:?}", out.message_type());
+        let deadline = Instant::now() + msg.read_timeout();
+        self.send(msg)?;
+
+        // Wait in slices so a cancellation reaches the device while its prompt is on screen
+        let mut in_buf = Vec::<u8>::new();
+        loop {
+            // A zero timeout would wait forever on libusb
+            let slice = deadline.saturating_duration_since(Instant::now()).clamp(Duration::from_millis(1), CANCEL_POLL_INTERVAL);
+            match self.read(&mut in_buf, slice) {
+                Ok(()) => break,
+                Err(e) if T::is_timeout(&e) && Instant::now() < deadline => {
+                    in_buf.clear();
+                    if cancel.take() {
+                        info!("ProtocolAdapter::handle_cancellable: Cancelling the prompt on screen");
+                        self.send(messages::Cancel {}.into())?;
+                    }
+                }
+                Err(e) => return Err(e.into()),
             }
         }
-        Ok(out)
+        
+        decode_response(&in_buf)
+    }
+}
+
+/// How often a read waiting on the device checks for a cancellation
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn decode_response(in_buf: &[u8]) -> Result<Message> {
+    info!("ProtocolAdapter::handle: Received {} bytes response", in_buf.len());
+
+    let out = Message::decode(&mut &in_buf[..]).map_err(|x| anyhow!(x))?;
+    info!("ProtocolAdapter::handle: Decoded response type: {:?}", out.message_type());
+    
+    // Clean, concise logging with key info
+    match &out {
+        Message::Features(features) => {
+            let version = format!("{}.{}.{}", 
+                features.major_version.unwrap_or(0),
+                features.minor_version.unwrap_or(0), 
+                features.patch_version.unwrap_or(0)
+            );
+            let label = crate::features::naming::display_name(&crate::features::naming::DeviceNameInput {
+                label: features.label.as_deref(),
+                model: features.model.as_deref(),
+                device_id: features.device_id.as_deref(),
+            });
+            let initialized = if features.initialized.unwrap_or(false) { "✅" } else { "⚠️" };
+            println!("<- Features: {} v{} {}", label, version, initialized);
+        },
+        _ => {
+            println!("<- {:?}", out.message_type());
+        }
     }
+    Ok(out)
 }
@@ -0,0 +1,184 @@
+//! Write retries for flaky USB setups.
+//!
+//! Every retry of a write that keeps failing adds `retry_delay` plus another
+//! write timeout before the caller sees the error, so raising `write_retries`
+//! trades latency on real failures (unplugged device, wedged firmware) for
+//! fewer spurious errors behind bad hubs and cables. Fail-fast integrations
+//! keep the default of no retries.
+//!
+//! A write is retried as a whole: the message goes out again from its first
+//! report, whose `##` header makes the device drop whatever it had buffered.
+
+use std::time::Duration;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::Transport;
+
+/// Transport settings a worker applies to every transport it opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransportConfig {
+    /// Extra attempts after a failed write; 0 fails on the first error
+    pub write_retries: u32,
+    /// Per-attempt write timeout; `None` keeps each message's own timeout
+    #[serde(default, with = "opt_millis")]
+    pub write_timeout: Option<Duration>,
+    /// Pause before each retry, giving the endpoint time to recover
+    #[serde(with = "millis")]
+    pub retry_delay: Duration,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            write_retries: 0,
+            write_timeout: None,
+            retry_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+mod opt_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&(value.as_millis() as u64)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+    }
+}
+
+/// Transport that retries failed writes as `config` says; reads and resets
+/// pass straight through
+pub struct RetryingTransport<T> {
+    inner: T,
+    config: TransportConfig,
+}
+
+impl<T> RetryingTransport<T> {
+    pub fn new(inner: T, config: TransportConfig) -> Self {
+        Self { inner, config }
+    }
+}
+
+impl<T: Transport> Transport for RetryingTransport<T> {
+    type Error = T::Error;
+
+    fn write(&mut self, msg: &[u8], timeout: Duration) -> Result<usize, Self::Error> {
+        let timeout = self.config.write_timeout.unwrap_or(timeout);
+        let mut attempt = 0;
+        loop {
+            match self.inner.write(msg, timeout) {
+                Ok(written) => return Ok(written),
+                Err(e) if attempt < self.config.write_retries => {
+                    attempt += 1;
+                    warn!("USB write failed ({}), retry {} of {}", e, attempt, self.config.write_retries);
+                    std::thread::sleep(self.config.retry_delay);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn read(&mut self, buf: &mut Vec<u8>, timeout: Duration) -> Result<(), Self::Error> {
+        self.inner.read(buf, timeout)
+    }
+
+    fn reset(&mut self) -> Result<(), Self::Error> {
+        self.inner.reset()
+    }
+
+    fn is_timeout(error: &Self::Error) -> bool {
+        T::is_timeout(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails the first `failures` writes, then accepts everything
+    struct FlakyTransport {
+        failures: u32,
+        writes: u32,
+        timeouts: Vec<Duration>,
+    }
+
+    impl Transport for FlakyTransport {
+        type Error = std::io::Error;
+
+        fn write(&mut self, msg: &[u8], timeout: Duration) -> Result<usize, Self::Error> {
+            self.writes += 1;
+            self.timeouts.push(timeout);
+            if self.writes <= self.failures {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "write timed out"));
+            }
+            Ok(msg.len())
+        }
+
+        fn read(&mut self, _buf: &mut Vec<u8>, _timeout: Duration) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn reset(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn flaky(failures: u32, write_retries: u32) -> RetryingTransport<FlakyTransport> {
+        let config = TransportConfig { write_retries, retry_delay: Duration::ZERO, ..Default::default() };
+        RetryingTransport::new(FlakyTransport { failures, writes: 0, timeouts: Vec::new() }, config)
+    }
+
+    #[test]
+    fn test_write_retries_are_respected() {
+        let mut transport = flaky(2, 2);
+        assert_eq!(transport.write(b"##msg", Duration::from_secs(1)).unwrap(), 5);
+        assert_eq!(transport.inner.writes, 3);
+
+        // One retry short: the caller sees the error after exactly retries + 1 writes
+        let mut transport = flaky(3, 2);
+        assert!(transport.write(b"##msg", Duration::from_secs(1)).is_err());
+        assert_eq!(transport.inner.writes, 3);
+
+        // Fail-fast default
+        let mut transport = flaky(1, 0);
+        assert!(transport.write(b"##msg", Duration::from_secs(1)).is_err());
+        assert_eq!(transport.inner.writes, 1);
+    }
+
+    #[test]
+    fn test_write_timeout_override() {
+        let mut transport = flaky(0, 0);
+        transport.config.write_timeout = Some(Duration::from_millis(250));
+        transport.write(b"##msg", Duration::from_secs(5)).unwrap();
+        assert_eq!(transport.inner.timeouts, vec![Duration::from_millis(250)]);
+
+        let json = serde_json::to_value(TransportConfig::default()).unwrap();
+        assert_eq!(json, serde_json::json!({ "writeRetries": 0, "writeTimeout": null, "retryDelay": 50 }));
+        let parsed: TransportConfig = serde_json::from_value(serde_json::json!({ "writeRetries": 3, "retryDelay": 100 })).unwrap();
+        assert_eq!(parsed.write_retries, 3);
+        assert_eq!(parsed.write_timeout, None);
+    }
+}
@@ -201,4 +201,8 @@ impl<T: UsbContext> Transport for UsbTransport<T> {
             buf.fill(0);
         }
     }
+
+    fn is_timeout(error: &Self::Error) -> bool {
+        matches!(error, rusb::Error::Timeout)
+    }
 }
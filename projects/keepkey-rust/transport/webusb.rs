@@ -208,4 +208,8 @@ impl<T: UsbContext> Transport for WebUsbTransport<T> {
             buf.fill(0);
         }
     }
+
+    fn is_timeout(error: &Self::Error) -> bool {
+        matches!(error, rusb::Error::Timeout)
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::ChainProvider;
+use crate::commands::{BitcoinUtxoInput, BitcoinUtxoOutput, DeviceRequest, DeviceRequestWrapper, DeviceResponse};
+
+/// Why a node refused a transaction, from its rejection message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    InsufficientFee,
+    /// An input is already spent, in a block or by another mempool transaction
+    DoubleSpend,
+    /// Locktime or relative locktime not reached yet
+    NonFinal,
+    AlreadyBroadcast,
+    Invalid,
+}
+
+impl RejectReason {
+    /// Classify a Bitcoin Core rejection (`sendrawtransaction` reject reasons)
+    pub fn from_node_message(message: &str) -> Self {
+        let message = message.to_lowercase();
+        if ["min relay fee not met", "mempool min fee not met", "insufficient fee", "fee not met"]
+            .iter()
+            .any(|m| message.contains(m))
+        {
+            RejectReason::InsufficientFee
+        } else if ["non-final", "non-bip68-final"].iter().any(|m| message.contains(m)) {
+            RejectReason::NonFinal
+        } else if ["txn-already-in-mempool", "txn-already-known", "already in block chain", "transaction already in"]
+            .iter()
+            .any(|m| message.contains(m))
+        {
+            RejectReason::AlreadyBroadcast
+        } else if ["txn-mempool-conflict", "missingorspent", "missing-inputs", "missing inputs", "bad-txns-spends-conflicting-tx"]
+            .iter()
+            .any(|m| message.contains(m))
+        {
+            RejectReason::DoubleSpend
+        } else {
+            RejectReason::Invalid
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            RejectReason::InsufficientFee => "fee too low",
+            RejectReason::DoubleSpend => "inputs already spent",
+            RejectReason::NonFinal => "transaction is not final yet (locktime)",
+            RejectReason::AlreadyBroadcast => "transaction already broadcast",
+            RejectReason::Invalid => "transaction invalid",
+        }
+    }
+}
+
+/// Why a broadcast failed. A timeout is not a rejection: the transaction may
+/// still have reached the network, so it must not be re-signed blindly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastError {
+    Timeout(String),
+    /// The backend couldn't be reached or failed on its side
+    Network(String),
+    Rejected { reason: RejectReason, message: String },
+}
+
+impl std::fmt::Display for BroadcastError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BroadcastError::Timeout(e) => write!(f, "Broadcast timed out, the transaction may still propagate: {}", e),
+            BroadcastError::Network(e) => write!(f, "Broadcast failed: {}", e),
+            BroadcastError::Rejected { reason, message } => write!(f, "Transaction rejected ({}): {}", reason.describe(), message),
+        }
+    }
+}
+
+impl std::error::Error for BroadcastError {}
+
+/// Submit a signed transaction through `provider`
+pub async fn broadcast<P: ChainProvider>(provider: &P, raw_tx_hex: &str) -> Result<String, BroadcastError> {
+    let raw_tx_hex = raw_tx_hex.trim();
+    if raw_tx_hex.is_empty() || hex::decode(raw_tx_hex).is_err() {
+        return Err(BroadcastError::Rejected { reason: RejectReason::Invalid, message: "Not a hex-encoded transaction".to_string() });
+    }
+    provider.broadcast_tx(raw_tx_hex).await
+}
+
+/// Broadcast through the provider this build is configured with
+async fn broadcast_configured(raw_tx_hex: &str) -> Result<String, String> {
+    #[cfg(feature = "esplora")]
+    {
+        let provider = super::esplora::EsploraProvider::new(crate::commands::esplora_url())?;
+        broadcast(&provider, raw_tx_hex).await.map_err(|e| e.to_string())
+    }
+    #[cfg(not(feature = "esplora"))]
+    {
+        let _ = raw_tx_hex;
+        Err("No chain provider configured: this build has no Esplora support".to_string())
+    }
+}
+
+async fn broadcast_and_emit(app: &AppHandle, raw_tx_hex: &str) -> Result<String, String> {
+    let txid = broadcast_configured(raw_tx_hex).await?;
+    println!("📡 Broadcast transaction {}", txid);
+    if let Err(e) = app.emit("tx:broadcast", serde_json::json!({ "txid": txid })) {
+        eprintln!("Failed to emit tx:broadcast event: {}", e);
+    }
+    Ok(txid)
+}
+
+/// Broadcast a signed transaction and return its txid. Signing never
+/// broadcasts on its own, so air-gapped workflows simply don't call this.
+#[tauri::command]
+pub async fn broadcast_transaction(raw_tx_hex: String, app: AppHandle) -> Result<String, String> {
+    broadcast_and_emit(&app, &raw_tx_hex).await
+}
+
+/// Run a `SignTransaction` request through the device queue under `request_id`
+/// and return the signed transaction hex
+pub(crate) async fn sign_through_queue(
+    app: &AppHandle,
+    device_id: String,
+    request_id: String,
+    request: DeviceRequest,
+) -> Result<String, String> {
+    let wrapper = DeviceRequestWrapper { device_id, request_id: request_id.clone(), request };
+    crate::device::queue::add_to_device_queue(wrapper, app.state(), app.state(), app.clone()).await?;
+
+    let response = app
+        .state::<Arc<tokio::sync::Mutex<HashMap<String, DeviceResponse>>>>()
+        .lock()
+        .await
+        .get(&request_id)
+        .cloned();
+    match response {
+        Some(DeviceResponse::SignedTransaction { signed_tx, success: true, .. }) => Ok(signed_tx),
+        Some(DeviceResponse::SignedTransaction { error, .. }) => Err(error.unwrap_or_else(|| "Signing failed".to_string())),
+        _ => Err("No signing response recorded".to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedAndBroadcast {
+    pub signed_tx: String,
+    pub txid: String,
+}
+
+/// Sign on the device, then broadcast. Goes through the device queue like any
+/// other signing request, so PIN and update gating apply. If the broadcast
+/// fails the signed transaction is still in the `device:response` event and
+/// can be passed to `broadcast_transaction` again.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn sign_and_broadcast(
+    unique_id: Option<String>,
+    coin: String,
+    inputs: Vec<BitcoinUtxoInput>,
+    outputs: Vec<BitcoinUtxoOutput>,
+    version: Option<u32>,
+    lock_time: Option<u32>,
+    verify_signatures: Option<bool>,
+    verify_change: Option<bool>,
+    app: AppHandle,
+) -> Result<SignedAndBroadcast, String> {
+    let request = DeviceRequest::SignTransaction {
+        coin,
+        inputs,
+        outputs,
+        version: version.unwrap_or(1),
+        lock_time: lock_time.unwrap_or(0),
+        verify_signatures: verify_signatures.unwrap_or(false),
+        verify_change: verify_change.unwrap_or(false),
+    };
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let signed_tx = sign_through_queue(&app, unique_id.unwrap_or_default(), request_id, request).await?;
+
+    let txid = broadcast_and_emit(&app, &signed_tx)
+        .await
+        .map_err(|e| format!("Signed but not broadcast: {}", e))?;
+    Ok(SignedAndBroadcast { signed_tx, txid })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChainProvider;
+
+    #[test]
+    fn test_classifies_node_rejections() {
+        use RejectReason::*;
+        let cases = [
+            ("sendrawtransaction RPC error: {\"code\":-26,\"message\":\"min relay fee not met, 100 < 141\"}", InsufficientFee),
+            ("mempool min fee not met", InsufficientFee),
+            ("insufficient fee, rejecting replacement abc", InsufficientFee),
+            ("bad-txns-inputs-missingorspent", DoubleSpend),
+            ("txn-mempool-conflict", DoubleSpend),
+            ("non-final", NonFinal),
+            ("non-BIP68-final", NonFinal),
+            ("txn-already-in-mempool", AlreadyBroadcast),
+            ("Transaction already in block chain", AlreadyBroadcast),
+            ("mandatory-script-verify-flag-failed", Invalid),
+        ];
+        for (message, reason) in cases {
+            assert_eq!(RejectReason::from_node_message(message), reason, "{}", message);
+        }
+
+        let timeout = BroadcastError::Timeout("operation timed out".to_string());
+        assert!(timeout.to_string().contains("may still propagate"));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_through_provider() {
+        let provider = MockChainProvider::default();
+        let txid = broadcast(&provider, " 0100 ").await.unwrap();
+        assert_eq!(txid.len(), 64);
+        assert_eq!(provider.broadcasts.lock().unwrap().as_slice(), ["0100".to_string()]);
+
+        // Garbage never reaches the provider
+        let err = broadcast(&provider, "not hex").await.unwrap_err();
+        assert!(matches!(err, BroadcastError::Rejected { reason: RejectReason::Invalid, .. }));
+        assert_eq!(provider.broadcasts.lock().unwrap().len(), 1);
+    }
+}
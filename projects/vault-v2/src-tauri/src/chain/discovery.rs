@@ -0,0 +1,279 @@
+//! BIP44 account discovery: find the accounts of a script type that have been
+//! used so a wallet can import every funded account, not just account 0.
+//!
+//! The device is asked once per account for its xpub; addresses are derived
+//! and looked up on the host.
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPubKey};
+use bitcoin::{Address, Network, PublicKey};
+use keepkey_rust::messages::{self, Message};
+use serde::Serialize;
+use std::future::Future;
+use std::str::FromStr;
+use tauri::State;
+
+use super::ChainProvider;
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::network::NetworkMode;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Unused receive addresses in a row after which an account counts as empty
+pub const ADDRESS_GAP_LIMIT: u32 = 20;
+/// BIP44 stops at the first unused account
+pub const DEFAULT_MAX_EMPTY_ACCOUNTS: u32 = 1;
+/// Upper bound on `max_accounts`, to keep a discovery from running for ever
+pub const MAX_DISCOVERY_ACCOUNTS: u32 = 100;
+
+/// An account that has transaction history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredAccount {
+    pub account: u32,
+    pub path: String,
+    pub xpub: String,
+    /// Lowest receive address index with history
+    pub first_used_index: u32,
+}
+
+/// BIP purpose of a single-sig `script_type`
+pub fn purpose(script_type: &str) -> Result<u32, String> {
+    match script_type {
+        "p2pkh" => Ok(44),
+        "p2sh-p2wpkh" => Ok(49),
+        "p2wpkh" => Ok(84),
+        other => Err(format!("Unsupported script type for account discovery: {}", other)),
+    }
+}
+
+/// `m/purpose'/coin_type'/account'`
+pub fn account_path(script_type: &str, mode: NetworkMode, account: u32) -> Result<Vec<u32>, String> {
+    if account & HARDENED != 0 {
+        return Err(format!("Account index {} is out of range", account));
+    }
+    Ok(vec![purpose(script_type)? | HARDENED, mode.coin_type() | HARDENED, account | HARDENED])
+}
+
+/// Network addresses are encoded for; regtest has its own `bcrt1` prefix
+/// even though the device works with the Testnet coin
+fn address_network(mode: NetworkMode) -> Network {
+    match mode {
+        NetworkMode::Mainnet => Network::Bitcoin,
+        NetworkMode::Testnet => Network::Testnet,
+        NetworkMode::Regtest => Network::Regtest,
+    }
+}
+
+/// Receive address `index` of the account `key`
+pub fn receive_address(key: &ExtendedPubKey, script_type: &str, index: u32, mode: NetworkMode) -> Result<String, String> {
+    let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+    let child = key
+        .derive_pub(&secp, &[ChildNumber::from(0), ChildNumber::from(index)])
+        .map_err(|e| format!("Cannot derive address {}: {}", index, e))?;
+    let public_key = PublicKey::new(child.public_key);
+    let network = address_network(mode);
+    let address = match script_type {
+        "p2pkh" => Address::p2pkh(&public_key, network),
+        "p2sh-p2wpkh" => Address::p2shwpkh(&public_key, network).map_err(|e| format!("Cannot build address: {}", e))?,
+        "p2wpkh" => Address::p2wpkh(&public_key, network).map_err(|e| format!("Cannot build address: {}", e))?,
+        other => return Err(format!("Unsupported script type for account discovery: {}", other)),
+    };
+    Ok(address.to_string())
+}
+
+/// First receive address of the account with history, looking no further
+/// than `ADDRESS_GAP_LIMIT` addresses
+async fn first_used_index<P: ChainProvider>(
+    provider: &P,
+    key: &ExtendedPubKey,
+    script_type: &str,
+    mode: NetworkMode,
+) -> Result<Option<u32>, String> {
+    for index in 0..ADDRESS_GAP_LIMIT {
+        let address = receive_address(key, script_type, index, mode)?;
+        if provider.has_history(&address).await? {
+            return Ok(Some(index));
+        }
+    }
+    Ok(None)
+}
+
+/// Check accounts 0.. in order until `max_accounts` were checked or
+/// `max_empty` unused accounts came in a row. `account_xpub` fetches the
+/// xpub of an account index. Used accounts are returned in index order.
+pub async fn discover<P, F, Fut>(
+    provider: &P,
+    script_type: &str,
+    mode: NetworkMode,
+    max_accounts: u32,
+    max_empty: u32,
+    mut account_xpub: F,
+) -> Result<Vec<DiscoveredAccount>, String>
+where
+    P: ChainProvider,
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    purpose(script_type)?;
+    let mut used = Vec::new();
+    let mut empty_in_a_row = 0;
+    for account in 0..max_accounts.min(MAX_DISCOVERY_ACCOUNTS) {
+        let xpub = account_xpub(account).await?;
+        let key = ExtendedPubKey::from_str(&xpub).map_err(|e| format!("Invalid xpub for account {}: {}", account, e))?;
+        match first_used_index(provider, &key, script_type, mode).await? {
+            Some(first_used_index) => {
+                let path = DerivationPath::from(
+                    account_path(script_type, mode, account)?.into_iter().map(ChildNumber::from).collect::<Vec<_>>(),
+                );
+                used.push(DiscoveredAccount { account, path: path.to_string(), xpub, first_used_index });
+                empty_in_a_row = 0;
+            }
+            None => {
+                empty_in_a_row += 1;
+                if empty_in_a_row >= max_empty.max(1) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(used)
+}
+
+/// Ask the device for the xpub of `account` of `script_type`, without display
+pub async fn fetch_account_xpub(
+    queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle,
+    script_type: &str,
+    mode: NetworkMode,
+    account: u32,
+) -> Result<String, String> {
+    let path = account_path(script_type, mode, account)?;
+    let response = queue_handle
+        .send_raw(
+            messages::GetPublicKey {
+                address_n: path,
+                coin_name: Some(mode.coin_name().to_string()),
+                show_display: Some(false),
+                ..Default::default()
+            }
+            .into(),
+            false,
+        )
+        .await
+        .map_err(|e| format!("Failed to get xpub of account {}: {}", account, e))?;
+    match response {
+        Message::PublicKey(public_key) => public_key
+            .xpub
+            .filter(|xpub| !xpub.is_empty())
+            .ok_or_else(|| "Device returned empty xpub".to_string()),
+        Message::Failure(failure) => Err(format!("Device returned error: {}", failure.message.unwrap_or_default())),
+        _ => Err("Unexpected response from device for xpub request".to_string()),
+    }
+}
+
+/// Find the used accounts of `script_type` ("p2pkh", "p2sh-p2wpkh" or
+/// "p2wpkh"). Discovery stops after `max_empty` consecutive unused accounts
+/// (1 by default, as BIP44 specifies) or `max_accounts` accounts.
+#[tauri::command]
+pub async fn discover_accounts(
+    unique_id: String,
+    script_type: String,
+    max_accounts: u32,
+    max_empty: Option<u32>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<DiscoveredAccount>, String> {
+    let mode = crate::commands::network_mode();
+    println!("🔎 Discovering {} accounts of {} on {}", script_type, unique_id, mode);
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+
+    let account_xpub = |account: u32| {
+        let queue_handle = queue_handle.clone();
+        let script_type = script_type.clone();
+        async move { fetch_account_xpub(&queue_handle, &script_type, mode, account).await }
+    };
+
+    #[cfg(feature = "esplora")]
+    {
+        let provider = super::esplora::EsploraProvider::new(crate::commands::esplora_url())?;
+        let accounts = discover(&provider, &script_type, mode, max_accounts, max_empty.unwrap_or(DEFAULT_MAX_EMPTY_ACCOUNTS), account_xpub).await?;
+        println!("🔎 {} used {} account(s) on {}", accounts.len(), script_type, unique_id);
+        Ok(accounts)
+    }
+    #[cfg(not(feature = "esplora"))]
+    {
+        let _ = (account_xpub, max_accounts, max_empty);
+        Err("No chain provider configured: this build has no Esplora support".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChainProvider;
+    use bitcoin::bip32::ExtendedPrivKey;
+
+    /// Account xpubs of a fixed test seed
+    fn account_xpubs(script_type: &str, count: u32) -> Vec<String> {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &[7u8; 32]).unwrap();
+        (0..count)
+            .map(|account| {
+                let path: Vec<ChildNumber> =
+                    account_path(script_type, NetworkMode::Mainnet, account).unwrap().into_iter().map(ChildNumber::from).collect();
+                let key = master.derive_priv(&secp, &path).unwrap();
+                ExtendedPubKey::from_priv(&secp, &key).to_string()
+            })
+            .collect()
+    }
+
+    fn address(xpub: &str, index: u32) -> String {
+        receive_address(&ExtendedPubKey::from_str(xpub).unwrap(), "p2wpkh", index, NetworkMode::Mainnet).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_discovers_used_accounts_in_order() {
+        let xpubs = account_xpubs("p2wpkh", 6);
+        // Accounts 0, 1 and 3 are used; 2 is a gap and 4, 5 are empty
+        let provider = MockChainProvider::default()
+            .with_history(&address(&xpubs[0], 0))
+            .with_history(&address(&xpubs[1], 7))
+            .with_history(&address(&xpubs[3], 2));
+        let mut fetched = Vec::new();
+        let mut fetch = |account: u32| {
+            fetched.push(account);
+            let xpub = xpubs[account as usize].clone();
+            async move { Ok(xpub) }
+        };
+
+        let accounts = discover(&provider, "p2wpkh", NetworkMode::Mainnet, 6, 2, &mut fetch).await.unwrap();
+        let found: Vec<(u32, u32)> = accounts.iter().map(|a| (a.account, a.first_used_index)).collect();
+        assert_eq!(found, [(0, 0), (1, 7), (3, 2)]);
+        assert_eq!(accounts[1].path, "m/84'/0'/1'");
+        // Stopped after two empty accounts in a row, one xpub request per account
+        assert_eq!(fetched, [0, 1, 2, 3, 4, 5]);
+
+        // BIP44's default stops at the gap
+        let accounts = discover(&provider, "p2wpkh", NetworkMode::Mainnet, 6, DEFAULT_MAX_EMPTY_ACCOUNTS, |account| {
+            let xpub = xpubs[account as usize].clone();
+            async move { Ok(xpub) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(accounts.len(), 2);
+    }
+
+    #[test]
+    fn test_account_paths_and_addresses() {
+        assert_eq!(account_path("p2sh-p2wpkh", NetworkMode::Testnet, 2).unwrap(), [49 | HARDENED, 1 | HARDENED, 2 | HARDENED]);
+        assert!(account_path("p2tr", NetworkMode::Mainnet, 0).is_err());
+
+        let xpub = &account_xpubs("p2pkh", 1)[0];
+        let key = ExtendedPubKey::from_str(xpub).unwrap();
+        assert!(receive_address(&key, "p2pkh", 0, NetworkMode::Mainnet).unwrap().starts_with('1'));
+        assert!(receive_address(&key, "p2sh-p2wpkh", 0, NetworkMode::Mainnet).unwrap().starts_with('3'));
+        assert!(receive_address(&key, "p2wpkh", 0, NetworkMode::Regtest).unwrap().starts_with("bcrt1"));
+    }
+}
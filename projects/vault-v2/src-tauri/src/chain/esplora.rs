@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{BroadcastError, ChainProvider, FeeEstimate, RejectReason, Utxo};
+
+pub const DEFAULT_ESPLORA_URL: &str = "https://blockstream.info/api";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `ChainProvider` backed by an Esplora HTTP API (blockstream.info, mempool.space
+/// or a self-hosted instance)
+#[derive(Debug, Clone)]
+pub struct EsploraProvider {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraUtxo {
+    txid: String,
+    vout: u32,
+    value: u64,
+    status: EsploraStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraStatus {
+    confirmed: bool,
+    block_height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraAddress {
+    chain_stats: EsploraAddressStats,
+    mempool_stats: EsploraAddressStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct EsploraAddressStats {
+    tx_count: u64,
+}
+
+impl From<EsploraUtxo> for Utxo {
+    fn from(utxo: EsploraUtxo) -> Self {
+        Utxo {
+            txid: utxo.txid,
+            vout: utxo.vout,
+            value: utxo.value,
+            block_height: utxo.status.block_height.filter(|_| utxo.status.confirmed),
+        }
+    }
+}
+
+impl EsploraProvider {
+    pub fn new(base_url: impl Into<String>) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(Self { base_url: base_url.into().trim_end_matches('/').to_string(), client })
+    }
+
+    async fn get_text(&self, path: &str) -> Result<String, String> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .send()
+            .await
+            .map_err(|e| format!("Esplora request failed: {}", e))?;
+        read_body(response).await
+    }
+}
+
+async fn read_body(response: reqwest::Response) -> Result<String, String> {
+    let status = response.status();
+    let body = response.text().await.map_err(|e| format!("Failed to read Esplora response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Esplora returned {}: {}", status, body.trim()));
+    }
+    Ok(body)
+}
+
+/// Pick the rate for `target_blocks` from Esplora's `fee-estimates` map: the
+/// closest target at or above it, else the longest target available
+fn fee_for_target(estimates: &HashMap<String, f64>, target_blocks: u32) -> Option<f64> {
+    let mut targets: Vec<(u32, f64)> = estimates
+        .iter()
+        .filter_map(|(target, rate)| target.parse::<u32>().ok().map(|target| (target, *rate)))
+        .collect();
+    targets.sort_by_key(|(target, _)| *target);
+    targets
+        .iter()
+        .find(|(target, _)| *target >= target_blocks)
+        .or_else(|| targets.last())
+        .map(|(_, rate)| *rate)
+}
+
+impl ChainProvider for EsploraProvider {
+    async fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        let body = self.get_text(&format!("/address/{}/utxo", address)).await?;
+        let utxos: Vec<EsploraUtxo> =
+            serde_json::from_str(&body).map_err(|e| format!("Unexpected Esplora UTXO response: {}", e))?;
+        Ok(utxos.into_iter().map(Utxo::from).collect())
+    }
+
+    async fn broadcast_tx(&self, tx_hex: &str) -> Result<String, BroadcastError> {
+        let response = self
+            .client
+            .post(format!("{}/tx", self.base_url))
+            .body(tx_hex.to_string())
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    BroadcastError::Timeout(e.to_string())
+                } else {
+                    BroadcastError::Network(format!("Esplora broadcast failed: {}", e))
+                }
+            })?;
+
+        let status = response.status();
+        let body = response.text().await.map_err(|e| {
+            if e.is_timeout() {
+                BroadcastError::Timeout(e.to_string())
+            } else {
+                BroadcastError::Network(format!("Failed to read Esplora response: {}", e))
+            }
+        })?;
+        // Esplora relays the node's rejection as a 400 with the RPC error in the body
+        if status.is_client_error() {
+            return Err(BroadcastError::Rejected { reason: RejectReason::from_node_message(&body), message: body.trim().to_string() });
+        }
+        if !status.is_success() {
+            return Err(BroadcastError::Network(format!("Esplora returned {}: {}", status, body.trim())));
+        }
+        Ok(body.trim().to_string())
+    }
+
+    async fn estimate_fee(&self, target_blocks: u32) -> Result<FeeEstimate, String> {
+        let body = self.get_text("/fee-estimates").await?;
+        let estimates: HashMap<String, f64> =
+            serde_json::from_str(&body).map_err(|e| format!("Unexpected Esplora fee response: {}", e))?;
+        fee_for_target(&estimates, target_blocks)
+            .map(|sat_per_vbyte| FeeEstimate { target_blocks, sat_per_vbyte })
+            .ok_or_else(|| "Esplora returned no fee estimates".to_string())
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<String, String> {
+        Ok(self.get_text(&format!("/tx/{}/hex", txid)).await?.trim().to_string())
+    }
+
+    async fn has_history(&self, address: &str) -> Result<bool, String> {
+        let body = self.get_text(&format!("/address/{}", address)).await?;
+        let stats: EsploraAddress =
+            serde_json::from_str(&body).map_err(|e| format!("Unexpected Esplora address response: {}", e))?;
+        Ok(stats.chain_stats.tx_count + stats.mempool_stats.tx_count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_esplora_responses() {
+        let utxos: Vec<EsploraUtxo> = serde_json::from_str(
+            r#"[{"txid":"aa","vout":0,"status":{"confirmed":true,"block_height":800000,"block_hash":"bb","block_time":1},"value":1500},
+                {"txid":"cc","vout":2,"status":{"confirmed":false},"value":700}]"#,
+        )
+        .unwrap();
+        let utxos: Vec<Utxo> = utxos.into_iter().map(Utxo::from).collect();
+        assert_eq!(utxos[0].block_height, Some(800000));
+        assert!(!utxos[1].is_confirmed());
+
+        let estimates: HashMap<String, f64> = serde_json::from_str(r#"{"1": 30.5, "3": 20.1, "6": 10.0, "144": 1.2}"#).unwrap();
+        assert_eq!(fee_for_target(&estimates, 2), Some(20.1));
+        assert_eq!(fee_for_target(&estimates, 6), Some(10.0));
+        assert_eq!(fee_for_target(&estimates, 1008), Some(1.2));
+        assert_eq!(fee_for_target(&HashMap::new(), 6), None);
+
+        let address: EsploraAddress = serde_json::from_str(
+            r#"{"address":"bc1q","chain_stats":{"funded_txo_count":1,"tx_count":2},"mempool_stats":{"funded_txo_count":0,"tx_count":0}}"#,
+        )
+        .unwrap();
+        assert_eq!(address.chain_stats.tx_count + address.mempool_stats.tx_count, 2);
+    }
+}
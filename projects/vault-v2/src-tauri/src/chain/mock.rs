@@ -0,0 +1,104 @@
+use sha2::Digest;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::{BroadcastError, ChainProvider, FeeEstimate, RejectReason, Utxo};
+
+/// In-memory `ChainProvider` for tests: answers from what was put in, records broadcasts
+#[derive(Debug, Default)]
+pub struct MockChainProvider {
+    pub utxos: HashMap<String, Vec<Utxo>>,
+    pub transactions: HashMap<String, String>,
+    /// sat/vB by confirmation target; the closest target at or above the request is used
+    pub fee_rates: Vec<(u32, f64)>,
+    /// Addresses that were used and are empty now; addresses with UTXOs have history anyway
+    pub history: HashSet<String>,
+    pub broadcasts: Mutex<Vec<String>>,
+}
+
+impl MockChainProvider {
+    pub fn with_utxo(mut self, address: &str, utxo: Utxo) -> Self {
+        self.utxos.entry(address.to_string()).or_default().push(utxo);
+        self
+    }
+
+    pub fn with_tx(mut self, txid: &str, tx_hex: &str) -> Self {
+        self.transactions.insert(txid.to_string(), tx_hex.to_string());
+        self
+    }
+
+    pub fn with_history(mut self, address: &str) -> Self {
+        self.history.insert(address.to_string());
+        self
+    }
+
+    pub fn with_fee_rate(mut self, target_blocks: u32, sat_per_vbyte: f64) -> Self {
+        self.fee_rates.push((target_blocks, sat_per_vbyte));
+        self.fee_rates.sort_by_key(|(target, _)| *target);
+        self
+    }
+}
+
+impl ChainProvider for MockChainProvider {
+    async fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        Ok(self.utxos.get(address).cloned().unwrap_or_default())
+    }
+
+    async fn broadcast_tx(&self, tx_hex: &str) -> Result<String, BroadcastError> {
+        let bytes = hex::decode(tx_hex).map_err(|e| BroadcastError::Rejected {
+            reason: RejectReason::Invalid,
+            message: format!("TX decode failed: {}", e),
+        })?;
+        let txid: Vec<u8> = sha2::Sha256::digest(sha2::Sha256::digest(&bytes)).iter().rev().copied().collect();
+        self.broadcasts.lock().unwrap().push(tx_hex.to_string());
+        Ok(hex::encode(txid))
+    }
+
+    async fn estimate_fee(&self, target_blocks: u32) -> Result<FeeEstimate, String> {
+        self.fee_rates
+            .iter()
+            .find(|(target, _)| *target >= target_blocks)
+            .or_else(|| self.fee_rates.last())
+            .map(|(_, sat_per_vbyte)| FeeEstimate { target_blocks, sat_per_vbyte: *sat_per_vbyte })
+            .ok_or_else(|| "No fee estimates available".to_string())
+    }
+
+    async fn get_tx(&self, txid: &str) -> Result<String, String> {
+        self.transactions.get(txid).cloned().ok_or_else(|| format!("Transaction {} not found", txid))
+    }
+
+    async fn has_history(&self, address: &str) -> Result<bool, String> {
+        Ok(self.history.contains(address) || self.utxos.contains_key(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_answers_from_its_data() {
+        let utxo = Utxo { txid: "ab".repeat(32), vout: 1, value: 5000, block_height: Some(800_000) };
+        let provider = MockChainProvider::default()
+            .with_utxo("bc1qtest", utxo.clone())
+            .with_tx("cd", "0100")
+            .with_fee_rate(1, 20.0)
+            .with_fee_rate(6, 8.0);
+
+        assert_eq!(provider.get_utxos("bc1qtest").await.unwrap(), vec![utxo]);
+        assert!(provider.get_utxos("bc1qother").await.unwrap().is_empty());
+        assert_eq!(provider.get_tx("cd").await.unwrap(), "0100");
+        assert!(provider.get_tx("ef").await.is_err());
+        assert!(provider.has_history("bc1qtest").await.unwrap());
+        assert!(!provider.has_history("bc1qother").await.unwrap());
+
+        assert_eq!(provider.estimate_fee(3).await.unwrap().sat_per_vbyte, 8.0);
+        assert_eq!(provider.estimate_fee(1).await.unwrap().sat_per_vbyte, 20.0);
+        // Beyond the longest target, the cheapest rate is used
+        assert_eq!(provider.estimate_fee(144).await.unwrap().sat_per_vbyte, 8.0);
+
+        let txid = provider.broadcast_tx("0100").await.unwrap();
+        assert_eq!(txid.len(), 64);
+        assert_eq!(provider.broadcasts.lock().unwrap().as_slice(), ["0100".to_string()]);
+    }
+}
@@ -0,0 +1,64 @@
+//! Blockchain data for features that need more than the device: balances,
+//! UTXOs, fee estimates and broadcast.
+//!
+//! Everything goes through `ChainProvider` so integrators can plug in Esplora,
+//! Electrum or their own backend; the device layer never talks to the chain.
+
+pub mod broadcast;
+pub mod discovery;
+#[cfg(feature = "esplora")]
+pub mod esplora;
+#[cfg(test)]
+pub mod mock;
+pub mod receive;
+pub mod tx_stream;
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+pub use broadcast::{BroadcastError, RejectReason};
+
+/// An unspent output of an address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    /// Amount in satoshis
+    pub value: u64,
+    /// `None` while unconfirmed
+    pub block_height: Option<u32>,
+}
+
+impl Utxo {
+    pub fn is_confirmed(&self) -> bool {
+        self.block_height.is_some()
+    }
+}
+
+/// Fee rate in sat/vB for confirmation within `target_blocks`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub target_blocks: u32,
+    pub sat_per_vbyte: f64,
+}
+
+/// Source of chain data. Errors are human-readable strings, like the rest of
+/// the app's command layer. Implementations can use `async fn`; the futures
+/// must be `Send` so callers can run them from Tauri commands.
+pub trait ChainProvider: Send + Sync {
+    /// Unspent outputs of `address`, confirmed and unconfirmed
+    fn get_utxos(&self, address: &str) -> impl Future<Output = Result<Vec<Utxo>, String>> + Send;
+
+    /// Broadcast a signed transaction, returning its txid
+    fn broadcast_tx(&self, tx_hex: &str) -> impl Future<Output = Result<String, BroadcastError>> + Send;
+
+    /// Fee rate for confirmation within `target_blocks`
+    fn estimate_fee(&self, target_blocks: u32) -> impl Future<Output = Result<FeeEstimate, String>> + Send;
+
+    /// Raw transaction hex, e.g. the previous transaction a legacy input needs for signing
+    fn get_tx(&self, txid: &str) -> impl Future<Output = Result<String, String>> + Send;
+
+    /// Whether `address` appears in any transaction, confirmed or not, even if
+    /// it holds nothing now
+    fn has_history(&self, address: &str) -> impl Future<Output = Result<bool, String>> + Send;
+}
@@ -0,0 +1,245 @@
+//! The wallet's "receive" action: the first receive address of an account
+//! with no on-chain history, derived on the device.
+//!
+//! Addresses are derived on the host from the account xpub and looked up with
+//! the `ChainProvider`, scanning forward from the first unused index found
+//! last time (history doesn't go away, so nothing before it needs checking
+//! again). Handing out the first unused address keeps the wallet within the
+//! gap limit: it is never more than one past the last used address, however
+//! often the user asks. The device derives the same address, showing it if
+//! asked, and a mismatch with the host's derivation is an error.
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPubKey};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tauri::State;
+
+use super::discovery::{account_path, receive_address, ADDRESS_GAP_LIMIT};
+use super::ChainProvider;
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::lru::DeviceLru;
+use crate::network::NetworkMode;
+
+/// Addresses looked up in one request before giving up; a chain full of used
+/// addresses beyond this is better served by account discovery
+pub const MAX_SCAN_ADDRESSES: u32 = 50 * ADDRESS_GAP_LIMIT;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressInfo {
+    pub address: String,
+    /// Full path, e.g. "m/84'/0'/0'/0/5"
+    pub path: String,
+    pub index: u32,
+}
+
+/// Where to continue scanning an account of a wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountCursor {
+    pub xpub: String,
+    /// First index that had no history at the last scan
+    pub next_unused: u32,
+}
+
+/// An account of a wallet: (master fingerprint, script type, network, account)
+type CursorKey = (String, String, NetworkMode, u32);
+
+/// Cursors of a device by wallet and account. A passphrase opens another wallet
+/// on the same device, so the master fingerprint is part of the key.
+type DeviceCursors = HashMap<CursorKey, AccountCursor>;
+
+static CURSORS: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<DeviceCursors>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+fn cursor_key(fingerprint: &str, script_type: &str, mode: NetworkMode, account: u32) -> CursorKey {
+    (fingerprint.to_string(), script_type.to_string(), mode, account)
+}
+
+fn cursor(device_id: &str, fingerprint: &str, script_type: &str, mode: NetworkMode, account: u32) -> Option<AccountCursor> {
+    crate::commands::lock_or_recover(&CURSORS, "receive cursors")
+        .peek(device_id)?
+        .get(&cursor_key(fingerprint, script_type, mode, account))
+        .cloned()
+}
+
+fn store_cursor(device_id: &str, fingerprint: &str, script_type: &str, mode: NetworkMode, account: u32, cursor: AccountCursor) {
+    crate::commands::lock_or_recover(&CURSORS, "receive cursors")
+        .get_or_insert_with(device_id, HashMap::new)
+        .insert(cursor_key(fingerprint, script_type, mode, account), cursor);
+}
+
+pub fn forget(device_id: &str) {
+    crate::commands::lock_or_recover(&CURSORS, "receive cursors").remove(device_id);
+}
+
+/// First receive address of the account `xpub` at or after `start` without
+/// history
+pub async fn first_unused_address<P: ChainProvider>(
+    provider: &P,
+    xpub: &str,
+    script_type: &str,
+    mode: NetworkMode,
+    account: u32,
+    start: u32,
+) -> Result<AddressInfo, String> {
+    let key = ExtendedPubKey::from_str(xpub).map_err(|e| format!("Invalid account xpub: {}", e))?;
+    let account_path: Vec<ChildNumber> = account_path(script_type, mode, account)?.into_iter().map(ChildNumber::from).collect();
+    for index in start..start.saturating_add(MAX_SCAN_ADDRESSES) {
+        let address = receive_address(&key, script_type, index, mode)?;
+        if !provider.has_history(&address).await? {
+            let mut path = account_path.clone();
+            path.extend([ChildNumber::from(0), ChildNumber::from(index)]);
+            return Ok(AddressInfo { address, path: DerivationPath::from(path).to_string(), index });
+        }
+    }
+    Err(format!("No unused address among the {} addresses after index {}", MAX_SCAN_ADDRESSES, start))
+}
+
+/// Find the next unused address of `account` with `provider` and have the
+/// device derive it
+pub async fn next_receive_address<P: ChainProvider>(
+    provider: &P,
+    queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle,
+    account: u32,
+    script_type: &str,
+    show_display: Option<bool>,
+) -> Result<AddressInfo, String> {
+    let mode = crate::commands::network_mode();
+    let device_id = queue_handle.device_id().to_string();
+    // Cached by the worker for the passphrase session, so this rarely talks to the device
+    let fingerprint = queue_handle
+        .get_master_fingerprint()
+        .await
+        .map(|fingerprint| format!("{:08x}", fingerprint))
+        .map_err(|e| format!("Failed to get wallet fingerprint: {}", e))?;
+    let cursor = match cursor(&device_id, &fingerprint, script_type, mode, account) {
+        Some(cursor) => cursor,
+        None => AccountCursor {
+            xpub: super::discovery::fetch_account_xpub(queue_handle, script_type, mode, account).await?,
+            next_unused: 0,
+        },
+    };
+    let info = first_unused_address(provider, &cursor.xpub, script_type, mode, account, cursor.next_unused).await?;
+
+    let path = crate::commands::parse_derivation_path(&info.path)?;
+    let on_device = queue_handle
+        .get_address(path, mode.coin_name().to_string(), crate::device::queue::input_script_type(Some(script_type)), show_display)
+        .await
+        .map_err(|e| format!("Failed to get address: {}", e))?;
+    if on_device != info.address {
+        return Err(format!("Device derived {} for {} but the wallet expected {}", on_device, info.path, info.address));
+    }
+
+    store_cursor(&device_id, &fingerprint, script_type, mode, account, AccountCursor { next_unused: info.index, ..cursor });
+    Ok(info)
+}
+
+/// Next unused receive address of `account` on device `unique_id`, for
+/// `script_type` "p2pkh", "p2sh-p2wpkh" or "p2wpkh". With `show_display` the
+/// device shows it for the user to compare.
+#[tauri::command]
+pub async fn get_next_receive_address(
+    unique_id: String,
+    account: u32,
+    script_type: String,
+    show_display: Option<bool>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<AddressInfo, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+
+    #[cfg(feature = "esplora")]
+    {
+        let provider = super::esplora::EsploraProvider::new(crate::commands::esplora_url())?;
+        let info = next_receive_address(&provider, &queue_handle, account, &script_type, show_display).await?;
+        println!("📥 Next receive address of {} account {}: {} ({})", unique_id, account, info.address, info.path);
+        Ok(info)
+    }
+    #[cfg(not(feature = "esplora"))]
+    {
+        let _ = (queue_handle, account, script_type, show_display);
+        Err("No chain provider configured: this build has no Esplora support".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChainProvider;
+
+    /// Account 0 xpub of m/84'/0'/0' for the BIP32 test vector 1 seed
+    fn account_xpub() -> String {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = bitcoin::bip32::ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &seed).unwrap();
+        let path: Vec<ChildNumber> = account_path("p2wpkh", NetworkMode::Mainnet, 0).unwrap().into_iter().map(ChildNumber::from).collect();
+        ExtendedPubKey::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap()).to_string()
+    }
+
+    fn address(xpub: &str, index: u32) -> String {
+        receive_address(&ExtendedPubKey::from_str(xpub).unwrap(), "p2wpkh", index, NetworkMode::Mainnet).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_first_unused_address() {
+        let xpub = account_xpub();
+        // 0-2 used; 4 was paid to before 3, which is still fresh
+        let provider = MockChainProvider::default()
+            .with_history(&address(&xpub, 0))
+            .with_history(&address(&xpub, 1))
+            .with_history(&address(&xpub, 2))
+            .with_history(&address(&xpub, 4));
+
+        let info = first_unused_address(&provider, &xpub, "p2wpkh", NetworkMode::Mainnet, 0, 0).await.unwrap();
+        assert_eq!(info, AddressInfo { address: address(&xpub, 3), path: "m/84'/0'/0'/0/3".to_string(), index: 3 });
+        // Asking again hands out the same address until it is used
+        let again = first_unused_address(&provider, &xpub, "p2wpkh", NetworkMode::Mainnet, 0, info.index).await.unwrap();
+        assert_eq!(again.index, 3);
+
+        // Once 3 is paid to, scanning continues from the cached index past 4
+        let provider = provider.with_history(&address(&xpub, 3));
+        let next = first_unused_address(&provider, &xpub, "p2wpkh", NetworkMode::Mainnet, 0, info.index).await.unwrap();
+        assert_eq!(next.index, 5);
+        assert!(next.address.starts_with("bc1q"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_is_bounded() {
+        let xpub = account_xpub();
+        let mut provider = MockChainProvider::default();
+        for index in 0..MAX_SCAN_ADDRESSES {
+            provider = provider.with_history(&address(&xpub, index));
+        }
+        assert!(first_unused_address(&provider, &xpub, "p2wpkh", NetworkMode::Mainnet, 0, 0).await.is_err());
+        assert!(first_unused_address(&provider, &xpub, "p2tr", NetworkMode::Mainnet, 0, 0).await.is_err());
+    }
+
+    #[test]
+    fn test_cursors_are_per_device_and_account() {
+        let cursor_a = AccountCursor { xpub: "xpub-a".to_string(), next_unused: 7 };
+        store_cursor("receive-test", "0badf00d", "p2wpkh", NetworkMode::Mainnet, 0, cursor_a.clone());
+        assert_eq!(cursor("receive-test", "0badf00d", "p2wpkh", NetworkMode::Mainnet, 0), Some(cursor_a));
+        assert_eq!(cursor("receive-test", "0badf00d", "p2wpkh", NetworkMode::Mainnet, 1), None);
+        assert_eq!(cursor("receive-test", "0badf00d", "p2wpkh", NetworkMode::Testnet, 0), None);
+        forget("receive-test");
+        assert_eq!(cursor("receive-test", "0badf00d", "p2wpkh", NetworkMode::Mainnet, 0), None);
+    }
+
+    #[test]
+    fn test_passphrase_switch_does_not_reuse_cursor() {
+        let standard = AccountCursor { xpub: "xpub-standard".to_string(), next_unused: 12 };
+        store_cursor("receive-switch-test", "11111111", "p2wpkh", NetworkMode::Mainnet, 0, standard.clone());
+
+        // Same device and account, another passphrase: scan the new wallet from its own xpub
+        assert_eq!(cursor("receive-switch-test", "22222222", "p2wpkh", NetworkMode::Mainnet, 0), None);
+        let hidden = AccountCursor { xpub: "xpub-hidden".to_string(), next_unused: 0 };
+        store_cursor("receive-switch-test", "22222222", "p2wpkh", NetworkMode::Mainnet, 0, hidden.clone());
+        assert_eq!(cursor("receive-switch-test", "22222222", "p2wpkh", NetworkMode::Mainnet, 0), Some(hidden));
+
+        // Switching back picks up where the first wallet left off
+        assert_eq!(cursor("receive-switch-test", "11111111", "p2wpkh", NetworkMode::Mainnet, 0), Some(standard));
+        forget("receive-switch-test");
+    }
+}
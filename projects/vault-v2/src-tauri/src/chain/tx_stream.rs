@@ -0,0 +1,159 @@
+//! Streaming export of signed transactions too large for one IPC message.
+//!
+//! `sign_transaction_streaming` signs like `sign_and_broadcast` (without
+//! broadcasting) but hands the signed transaction to the frontend as a series
+//! of `tx:chunk` events followed by `tx:complete`, and leaves it out of the
+//! request's `device:response`. The frontend joins the chunks' `data` in
+//! `index` order. Chunks hold at most `tx_chunk_size` bytes of transaction
+//! (twice as many hex characters).
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{BitcoinUtxoInput, BitcoinUtxoOutput, DeviceRequest};
+
+/// Default for `tx_chunk_size`
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Smaller chunks would only multiply events
+pub const MIN_CHUNK_SIZE: usize = 1024;
+
+static CHUNK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_CHUNK_SIZE);
+
+/// Bytes of transaction per `tx:chunk` event
+pub fn chunk_size() -> usize {
+    CHUNK_SIZE.load(Ordering::Relaxed)
+}
+
+pub fn set_chunk_size(bytes: usize) {
+    CHUNK_SIZE.store(bytes.max(MIN_CHUNK_SIZE), Ordering::Relaxed);
+}
+
+/// Requests whose signed transaction is streamed instead of sent in `device:response`
+static STREAMED_REQUESTS: once_cell::sync::Lazy<std::sync::Mutex<HashSet<String>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashSet::new()));
+
+fn with_streamed<T>(f: impl FnOnce(&mut HashSet<String>) -> T) -> T {
+    match STREAMED_REQUESTS.lock() {
+        Ok(mut requests) => f(&mut requests),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Whether `device:response` for this request should go out without the signed transaction
+pub fn is_streamed(request_id: &str) -> bool {
+    with_streamed(|requests| requests.contains(request_id))
+}
+
+/// Split signed transaction hex into chunks of at most `chunk_size` bytes of
+/// transaction each. Always at least one chunk, so an empty transaction still
+/// completes.
+pub fn chunk_hex(signed_tx: &str, chunk_size: usize) -> Vec<&str> {
+    let chars = chunk_size.max(1) * 2;
+    if signed_tx.is_empty() {
+        return vec![""];
+    }
+    // Hex is ASCII, so byte offsets are character boundaries
+    signed_tx.as_bytes().chunks(chars).map(|chunk| std::str::from_utf8(chunk).unwrap_or_default()).collect()
+}
+
+/// Payload of `tx:chunk`
+pub fn chunk_payload(request_id: &str, index: usize, total_chunks: usize, data: &str) -> serde_json::Value {
+    serde_json::json!({
+        "request_id": request_id,
+        "index": index,
+        "total_chunks": total_chunks,
+        "data": data
+    })
+}
+
+/// Returned by `sign_transaction_streaming` once every chunk was emitted; also
+/// the payload of `tx:complete`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StreamedTransaction {
+    pub request_id: String,
+    pub total_chunks: usize,
+    /// Length of the signed transaction in bytes
+    pub size: usize,
+}
+
+fn emit_chunks(app: &AppHandle, request_id: &str, signed_tx: &str) -> Result<StreamedTransaction, String> {
+    let chunks = chunk_hex(signed_tx, chunk_size());
+    let total_chunks = chunks.len();
+    for (index, data) in chunks.into_iter().enumerate() {
+        app.emit("tx:chunk", chunk_payload(request_id, index, total_chunks, data))
+            .map_err(|e| format!("Failed to emit tx:chunk: {}", e))?;
+    }
+    let complete = StreamedTransaction { request_id: request_id.to_string(), total_chunks, size: signed_tx.len() / 2 };
+    app.emit("tx:complete", &complete).map_err(|e| format!("Failed to emit tx:complete: {}", e))?;
+    Ok(complete)
+}
+
+/// Sign on the device and stream the signed transaction as `tx:chunk` events
+/// ending with `tx:complete`. For transactions with many inputs; small ones
+/// can use the `SignTransaction` queue request and read `device:response`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn sign_transaction_streaming(
+    unique_id: Option<String>,
+    coin: String,
+    inputs: Vec<BitcoinUtxoInput>,
+    outputs: Vec<BitcoinUtxoOutput>,
+    version: Option<u32>,
+    lock_time: Option<u32>,
+    verify_signatures: Option<bool>,
+    verify_change: Option<bool>,
+    app: AppHandle,
+) -> Result<StreamedTransaction, String> {
+    let request = DeviceRequest::SignTransaction {
+        coin,
+        inputs,
+        outputs,
+        version: version.unwrap_or(1),
+        lock_time: lock_time.unwrap_or(0),
+        verify_signatures: verify_signatures.unwrap_or(false),
+        verify_change: verify_change.unwrap_or(false),
+    };
+    let request_id = uuid::Uuid::new_v4().to_string();
+    with_streamed(|requests| requests.insert(request_id.clone()));
+    let signed = super::broadcast::sign_through_queue(&app, unique_id.unwrap_or_default(), request_id.clone(), request).await;
+    with_streamed(|requests| requests.remove(&request_id));
+
+    let streamed = emit_chunks(&app, &request_id, &signed?)?;
+    println!("📦 Streamed signed transaction {} ({} bytes, {} chunks)", request_id, streamed.size, streamed.total_chunks);
+    Ok(streamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_transaction_reassembles() {
+        // A ~1.5 MB transaction, e.g. a consolidation of ~10k inputs
+        let tx: Vec<u8> = (0..1_500_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        let signed_tx = hex::encode(&tx);
+
+        let chunks = chunk_hex(&signed_tx, DEFAULT_CHUNK_SIZE);
+        assert_eq!(chunks.len(), 1_500_000usize.div_ceil(DEFAULT_CHUNK_SIZE));
+        assert!(chunks.iter().all(|chunk| chunk.len() <= DEFAULT_CHUNK_SIZE * 2 && chunk.len() % 2 == 0));
+
+        // What the frontend does with the tx:chunk payloads
+        let payloads: Vec<_> =
+            chunks.iter().enumerate().map(|(index, data)| chunk_payload("req-1", index, chunks.len(), data)).collect();
+        let mut received = payloads.clone();
+        received.reverse();
+        received.sort_by_key(|payload| payload["index"].as_u64().unwrap());
+        let reassembled: String = received.iter().map(|payload| payload["data"].as_str().unwrap()).collect();
+        assert_eq!(hex::decode(reassembled).unwrap(), tx);
+        assert!(payloads.iter().all(|payload| payload["total_chunks"] == chunks.len() && payload["request_id"] == "req-1"));
+    }
+
+    #[test]
+    fn test_small_transactions_are_one_chunk() {
+        assert_eq!(chunk_hex("0100000001", DEFAULT_CHUNK_SIZE), vec!["0100000001"]);
+        assert_eq!(chunk_hex("", DEFAULT_CHUNK_SIZE), vec![""]);
+        assert_eq!(chunk_hex("aabbccdd", 1), vec!["aa", "bb", "cc", "dd"]);
+    }
+}
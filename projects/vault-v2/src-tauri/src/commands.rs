@@ -2,7 +2,7 @@ use tauri::{AppHandle, Emitter, State};
 use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 use keepkey_rust::{
-    device_queue::{DeviceQueueFactory, DeviceQueueHandle},
+    device_queue::{DeviceQueueFactory, DeviceQueueHandle, QueueTimeout},
     features::DeviceFeatures,
 };
 use uuid;
@@ -11,6 +11,7 @@ use std::io::Cursor;
 // Removed unused imports that were moved to device/updates.rs
 use crate::logging::{log_device_request, log_device_response, log_raw_device_message};
 use crate::device;
+use crate::network::NetworkMode;
 use lazy_static;
 use std::path::PathBuf;
 use std::fs;
@@ -22,6 +23,188 @@ use once_cell::sync::Lazy;
 
 pub type DeviceQueueManager = Arc<tokio::sync::Mutex<std::collections::HashMap<String, DeviceQueueHandle>>>;
 
+/// Worker acquisition for the shared device queue map.
+///
+/// All call sites must go through these methods so the existence check and the
+/// insert happen under a single lock - otherwise a connect storm racing a user
+/// command can spawn two workers for the same device.
+pub trait DeviceQueueManagerExt {
+    /// Return the existing worker for `unique_id`, or spawn and register one for `device`
+    async fn get_or_spawn(&self, unique_id: &str, device: &keepkey_rust::friendly_usb::FriendlyUsbDevice) -> DeviceQueueHandle;
+
+    /// Like `get_or_spawn`, but looks the device up among connected devices only when no
+    /// worker exists yet. Returns `None` if there is no worker and the device is not connected.
+    async fn get_or_spawn_by_id(&self, unique_id: &str) -> Option<DeviceQueueHandle>;
+
+    /// Unregister and stop the worker for `unique_id`. Returns whether one existed.
+    async fn remove_and_shutdown(&self, unique_id: &str) -> bool;
+
+    /// Unregister and stop every worker, returning how many were stopped.
+    async fn shutdown_all(&self) -> usize;
+
+    /// Unregister and stop workers nobody has used for `idle_after`, returning their
+    /// device ids. The next request for such a device spawns a fresh worker.
+    async fn reap_idle(&self, idle_after: Duration) -> Vec<String>;
+}
+
+// Invariant: the manager lock is only held to look up, insert or remove handles -
+// never while awaiting a worker or doing USB I/O. Disconnect cleanups and
+// `shutdown_all` can then run concurrently without deadlocking or stopping a worker
+// twice, and a slow operation on one device never delays requests to another:
+// every device has its own worker task, so only the per-device queue serializes.
+
+/// Spawn a worker for `device` with its button and PIN requests forwarded to the UI
+fn spawn_worker(unique_id: &str, device: &keepkey_rust::friendly_usb::FriendlyUsbDevice) -> DeviceQueueHandle {
+    let (timeouts, config) = crate::device::connection::worker_profile(unique_id, transport_config());
+    let gate = Arc::new(crate::device::policy::PolicyGate::new(crate::device::prompts::app()));
+    if let Some(handle) = crate::device::mock::spawn_worker(unique_id) {
+        return handle.with_timeout_profile(timeouts).with_message_gate(gate);
+    }
+    let handle = DeviceQueueFactory::spawn_worker_with_config(unique_id.to_string(), device.clone(), config)
+        .with_timeout_profile(timeouts)
+        .with_message_gate(gate);
+    crate::device::prompts::forward_prompts(&handle);
+    handle
+}
+
+impl DeviceQueueManagerExt for DeviceQueueManager {
+    async fn get_or_spawn(&self, unique_id: &str, device: &keepkey_rust::friendly_usb::FriendlyUsbDevice) -> DeviceQueueHandle {
+        let mut manager = self.lock().await;
+        manager
+            .entry(unique_id.to_string())
+            .or_insert_with(|| spawn_worker(unique_id, device))
+            .clone()
+    }
+
+    async fn get_or_spawn_by_id(&self, unique_id: &str) -> Option<DeviceQueueHandle> {
+        if let Some(handle) = self.lock().await.get(unique_id) {
+            return Some(handle.clone());
+        }
+
+        // Enumerate without the lock so a slow USB scan doesn't stall other devices;
+        // `entry` keeps the first worker if another caller registered one meanwhile
+        let mut devices = keepkey_rust::features::list_connected_devices();
+        devices.extend(crate::device::mock::connected_devices());
+        let device = devices.iter().find(|d| d.unique_id == unique_id)?;
+        let mut manager = self.lock().await;
+        Some(
+            manager
+                .entry(unique_id.to_string())
+                .or_insert_with(|| spawn_worker(unique_id, device))
+                .clone(),
+        )
+    }
+
+    async fn remove_and_shutdown(&self, unique_id: &str) -> bool {
+        let handle = self.lock().await.remove(unique_id);
+        match handle {
+            Some(handle) => {
+                if let Err(e) = handle.shutdown().await {
+                    println!("⚠️ Worker for {} did not shut down cleanly: {}", unique_id, e);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn shutdown_all(&self) -> usize {
+        let handles: Vec<DeviceQueueHandle> = {
+            let mut manager = self.lock().await;
+            manager.drain().map(|(_, handle)| handle).collect()
+        };
+        let count = handles.len();
+        
+        let mut shutdowns = tokio::task::JoinSet::new();
+        for handle in handles {
+            shutdowns.spawn(async move {
+                if let Err(e) = handle.shutdown().await {
+                    println!("⚠️ Worker for {} did not shut down cleanly: {}", handle.device_id(), e);
+                }
+            });
+        }
+        while shutdowns.join_next().await.is_some() {}
+        
+        count
+    }
+
+    async fn reap_idle(&self, idle_after: Duration) -> Vec<String> {
+        // A worker is only reaped while the map holds its sole handle: any caller
+        // using it holds a clone, and once it's removed new callers spawn a new one
+        let reaped: Vec<DeviceQueueHandle> = {
+            let mut manager = self.lock().await;
+            let idle: Vec<String> = manager
+                .iter()
+                .filter(|(id, handle)| {
+                    !handle.is_shared()
+                        && handle.idle_for() >= idle_after
+                        && handle.screen_hint() == keepkey_rust::screen_hint::ScreenHint::Idle
+                        && !is_device_in_pin_flow(id)
+                        && !is_device_in_recovery_flow(id)
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            idle.iter().filter_map(|id| manager.remove(id)).collect()
+        };
+
+        let mut ids = Vec::with_capacity(reaped.len());
+        for handle in reaped {
+            if let Err(e) = handle.shutdown().await {
+                println!("⚠️ Idle worker for {} did not shut down cleanly: {}", handle.device_id(), e);
+            }
+            ids.push(handle.device_id().to_string());
+        }
+        ids
+    }
+}
+
+/// Write retry behavior given to workers when they are spawned
+static TRANSPORT_CONFIG: once_cell::sync::Lazy<std::sync::RwLock<keepkey_rust::transport::TransportConfig>> =
+    once_cell::sync::Lazy::new(|| std::sync::RwLock::new(keepkey_rust::transport::TransportConfig::default()));
+
+pub fn transport_config() -> keepkey_rust::transport::TransportConfig {
+    match TRANSPORT_CONFIG.read() {
+        Ok(config) => *config,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+fn store_transport_config(config: keepkey_rust::transport::TransportConfig) {
+    match TRANSPORT_CONFIG.write() {
+        Ok(mut current) => *current = config,
+        Err(poisoned) => *poisoned.into_inner() = config,
+    }
+}
+
+/// Lock a std mutex, taking it back if a thread panicked while holding it.
+/// A poisoned lock would otherwise fail every later `lock()`, so one panic
+/// would permanently break whatever depends on it; the poison is cleared so
+/// the warning is logged once per panic.
+pub fn lock_or_recover<'a, T>(mutex: &'a std::sync::Mutex<T>, name: &str) -> std::sync::MutexGuard<'a, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            log::warn!("Recovering the {} lock after a panic while it was held", name);
+            mutex.clear_poison();
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Default for `worker_idle_timeout_secs`
+const DEFAULT_WORKER_IDLE_TIMEOUT_SECS: u64 = 60;
+
+static WORKER_IDLE_TIMEOUT_SECS: std::sync::atomic::AtomicU64 =
+    std::sync::atomic::AtomicU64::new(DEFAULT_WORKER_IDLE_TIMEOUT_SECS);
+
+/// How long a device worker may sit unused before it is reaped; `None` when reaping is off
+pub fn worker_idle_timeout() -> Option<Duration> {
+    match WORKER_IDLE_TIMEOUT_SECS.load(std::sync::atomic::Ordering::Relaxed) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
 // Change the response storage to use request_id as key instead of device_id
 #[allow(dead_code)]
 type LastResponsesMap = Arc<tokio::sync::Mutex<std::collections::HashMap<String, DeviceResponse>>>;
@@ -29,6 +212,44 @@ type LastResponsesMap = Arc<tokio::sync::Mutex<std::collections::HashMap<String,
 // Add frontend readiness state and queued events
 lazy_static::lazy_static! {
     static ref FRONTEND_READY_STATE: Arc<tokio::sync::RwLock<FrontendReadyState>> = Arc::new(tokio::sync::RwLock::new(FrontendReadyState::default()));
+    /// Last features seen per device. Filled whenever features are fetched, dropped
+    /// on disconnect and after operations that change them (wipe, setup, recovery, label).
+    static ref FEATURE_CACHE: Mutex<crate::device::lru::DeviceLru<DeviceFeatures>> = Mutex::new(crate::device::lru::DeviceLru::new());
+}
+
+pub fn cache_device_features(device_id: &str, features: &DeviceFeatures) {
+    crate::device::identity::remember(device_id, features);
+    crate::device::updates::remember_installed_firmware(device_id, features);
+    lock_or_recover(&FEATURE_CACHE, "feature cache").insert(device_id, features.clone());
+}
+
+pub fn cached_device_features(device_id: &str) -> Option<DeviceFeatures> {
+    lock_or_recover(&FEATURE_CACHE, "feature cache").get(device_id).cloned()
+}
+
+/// Snapshot of every cached feature set, by device id
+pub fn all_cached_features() -> HashMap<String, DeviceFeatures> {
+    lock_or_recover(&FEATURE_CACHE, "feature cache").to_map()
+}
+
+pub fn invalidate_cached_features(device_id: &str) {
+    if lock_or_recover(&FEATURE_CACHE, "feature cache").remove(device_id).is_some() {
+        log::debug!("Feature cache invalidated for {}", device_id);
+    }
+}
+
+/// Drop the cached features of `device_id`, or of every device when `None`;
+/// returns the devices whose features were dropped
+pub fn clear_cached_features(device_id: Option<&str>) -> Vec<String> {
+    let mut cache = lock_or_recover(&FEATURE_CACHE, "feature cache");
+    match device_id {
+        Some(device_id) => cache.remove(device_id).map(|_| vec![device_id.to_string()]).unwrap_or_default(),
+        None => {
+            let cleared = cache.iter().map(|(device_id, _)| device_id.clone()).collect();
+            cache.clear();
+            cleared
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -84,6 +305,10 @@ pub enum DeviceRequest {
         coin_name: String,
         script_type: Option<String>,
         show_display: Option<bool>,
+        /// Cosigner set of a multisig wallet; `script_type` is then
+        /// "p2sh", "p2wsh" or "p2sh-p2wsh"
+        #[serde(default)]
+        multisig: Option<crate::device::multisig::MultisigConfig>,
     },
     GetFeatures,
     SignTransaction {
@@ -92,6 +317,13 @@ pub enum DeviceRequest {
         outputs: Vec<BitcoinUtxoOutput>,
         version: u32,
         lock_time: u32,
+        /// Verify the returned signatures before handing the transaction back;
+        /// a failed check is reported as a warning and never changes the output
+        #[serde(default)]
+        verify_signatures: bool,
+        /// Show every change address on the device for confirmation before signing
+        #[serde(default)]
+        verify_change: bool,
     },
     SendRaw {
         message_type: String,
@@ -101,6 +333,8 @@ pub enum DeviceRequest {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceRequestWrapper {
+    /// Empty to use the active device
+    #[serde(default)]
     pub device_id: String,
     pub request_id: String,
     pub request: DeviceRequest,
@@ -171,6 +405,10 @@ pub struct DeviceStatus {
     pub bootloader_check: Option<BootloaderCheck>,
     pub firmware_check: Option<FirmwareCheck>,
     pub initialization_check: Option<InitializationCheck>,
+    /// Set when the firmware requires a newer bootloader than the one installed;
+    /// the bootloader has to be updated before anything else
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_mismatch: Option<device::model::VersionMismatch>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -187,6 +425,38 @@ pub struct FirmwareCheck {
     pub current_version: String,
     pub latest_version: String,
     pub needs_update: bool,
+    pub severity: Option<UpdateSeverity>,
+    /// Minimum version from `min_firmware_policy`, if one is configured
+    pub required_version: Option<String>,
+    /// Notes of `latest_version` in the app language, when an update is offered
+    #[serde(default)]
+    pub release_notes: Option<crate::device::release_notes::ReleaseNotes>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateSeverity {
+    Recommended,
+    /// Sensitive operations are refused until the update is installed
+    Mandatory,
+}
+
+/// Errors from host-side policies that refuse an operation before it reaches the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    FirmwareTooOld { current: String, required: String },
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::FirmwareTooOld { current, required } => write!(
+                f,
+                "FirmwareTooOld: firmware {} is below the required minimum {}. Please update your KeepKey firmware.",
+                current, required
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -207,12 +477,15 @@ pub async fn reset_device_queue(
 ) -> Result<(), String> {
     println!("🔄 Resetting device queue for: {}", device_id);
     
-    let mut manager = queue_manager.lock().await;
-    if let Some(handle) = manager.remove(&device_id) {
-        let _ = handle.shutdown().await;
+    if queue_manager.remove_and_shutdown(&device_id).await {
         println!("✅ Device queue reset for: {}", device_id);
     }
     
+    // A device the monitor gave up probing gets a fresh set of attempts
+    if crate::device::probe::with_tracker(|tracker| tracker.reset(&device_id, std::time::Instant::now())) {
+        println!("📡 Device {} will be probed again", device_id);
+    }
+    
     Ok(())
 }
 
@@ -343,18 +616,18 @@ pub async fn get_device_status(
     bootloader_tracker: State<'_, device::updates::BootloaderUpdateTracker>,
 ) -> Result<Option<DeviceStatus>, String> {
     // Rate limit status checks - ignore rapid duplicate requests
-    static LAST_STATUS_CHECK: once_cell::sync::Lazy<Arc<tokio::sync::Mutex<std::collections::HashMap<String, std::time::Instant>>>> = 
-        once_cell::sync::Lazy::new(|| Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new())));
+    static LAST_STATUS_CHECK: once_cell::sync::Lazy<Mutex<device::lru::DeviceLru<std::time::Instant>>> = 
+        once_cell::sync::Lazy::new(|| Mutex::new(device::lru::DeviceLru::new()));
     
     {
-        let mut last_checks = LAST_STATUS_CHECK.lock().await;
-        if let Some(last_check) = last_checks.get(&device_id) {
+        let mut last_checks = lock_or_recover(&LAST_STATUS_CHECK, "status check times");
+        if let Some(last_check) = last_checks.peek(&device_id) {
             if last_check.elapsed() < Duration::from_millis(500) {
                 // Skip if checked within last 500ms
                 return Ok(None);
             }
         }
-        last_checks.insert(device_id.clone(), std::time::Instant::now());
+        last_checks.insert(&device_id, std::time::Instant::now());
     }
     
     println!("Getting device status for: {}", device_id);
@@ -379,18 +652,7 @@ pub async fn get_device_status(
     
     if let Some(device_info) = device_info {
         // Get or create device queue handle
-        let queue_handle = {
-            let mut manager = queue_manager.lock().await;
-            
-            if let Some(handle) = manager.get(&device_id) {
-                handle.clone()
-            } else {
-                // Spawn a new device worker
-                let handle = DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                manager.insert(device_id.clone(), handle.clone());
-                handle
-            }
-        };
+        let queue_handle = queue_manager.get_or_spawn(&device_id, device_info).await;
         
         // Fetch device features through the queue with retry logic
         let features = {
@@ -417,17 +679,15 @@ pub async fn get_device_status(
             for attempt in 1..=max_attempts {
                 println!("🔄 Attempting to get features for device {} (attempt {}/{})", device_id, attempt, max_attempts);
                 
-                match tokio::time::timeout(
-                    Duration::from_secs(10), // Reduced to 10 seconds per attempt for faster retries
-                    queue_handle.get_features()
-                ).await {
-                    Ok(Ok(raw_features)) => {
+                // Reduced to 10 seconds per attempt for faster retries
+                match queue_handle.get_features_with_timeout(Duration::from_secs(10)).await {
+                    Ok(raw_features) => {
                         println!("✅ Successfully got features for device {} on attempt {}", device_id, attempt);
                         // Convert from raw Features message to DeviceFeatures
                         success_features = Some(convert_features_to_device_features(raw_features));
                         break;
                     }
-                    Ok(Err(e)) => {
+                    Err(e) if !e.is::<QueueTimeout>() => {
                         let error_detail = format!("{:?}", e);
                         println!("⚠️ Failed to get features for device {} on attempt {}: {}", device_id, attempt, error_detail);
                         
@@ -546,52 +806,32 @@ pub async fn get_device_info_by_id(
     }
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "get_device_info_by_id"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log get device info error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
+    let queue_handle = match queue_manager.get_or_spawn_by_id(&device_id).await {
+        Some(handle) => handle,
+        None => {
+            let error = format!("Device {} not found", device_id);
+            
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "get_device_info_by_id"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log get device info error response: {}", e);
             }
+            
+            return Err(error);
         }
     };
     
     // Fetch device features through the queue
-    match tokio::time::timeout(
-        Duration::from_secs(30), // Increased from 15 to 30 seconds to match Windows HID timeout
-        queue_handle.get_features()
-    ).await {
-        Ok(Ok(raw_features)) => {
+    // Increased from 15 to 30 seconds to match Windows HID timeout
+    match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+        Ok(raw_features) => {
             // Convert from raw Features message to DeviceFeatures
             let device_features = convert_features_to_device_features(raw_features);
+            cache_device_features(&device_id, &device_features);
             
             // Emit event for frontend listeners (KeepKeyDeviceList etc.)
             let event_payload = serde_json::json!({
@@ -600,6 +840,9 @@ pub async fn get_device_info_by_id(
                 "status": "ready"
             });
             let _ = app.emit("device:features-updated", event_payload);
+            let status = evaluate_device_status(device_id.clone(), Some(&device_features));
+            crate::device::state::settle_from_features(&app, &device_id, &device_features, &status).await;
+            crate::device::attention::refresh(&app, &device_id, &device_features, &status).await;
 
             // Log the successful response
             let response_data = serde_json::json!({
@@ -613,19 +856,18 @@ pub async fn get_device_info_by_id(
             
             Ok(Some(device_features))
         }
-        Ok(Err(e)) => {
+        Err(e) if !e.is::<QueueTimeout>() => {
             let error_msg = e.to_string();
             
-            // Check for device access errors (already claimed)
-            if error_msg.contains("Device Already In Use") || 
-               error_msg.contains("already claimed") ||
-               error_msg.contains("Device Access Failed") ||
-               error_msg.contains("🔒") {
+            // Check for device access errors (already claimed, no permission, ...)
+            if let Some(kind) = crate::device::access_error::classify(&error_msg) {
                 
-                println!("❌ Device {} is already in use by another application: {}", device_id, e);
+                println!("❌ Device {} can't be accessed ({:?}): {}", device_id, kind, e);
                 
                 // Return the detailed error message from our HID transport
-                let user_friendly_error = if error_msg.contains("🔒") {
+                let user_friendly_error = if crate::instance_lock::is_secondary() {
+                    crate::instance_lock::MANAGED_ELSEWHERE_MESSAGE.to_string()
+                } else if error_msg.contains("🔒") || kind != crate::device::access_error::AccessErrorKind::DeviceClaimed {
                     error_msg
                 } else {
                     format!(
@@ -647,7 +889,8 @@ pub async fn get_device_info_by_id(
                 let error_event_payload = serde_json::json!({
                     "deviceId": device_id,
                     "error": user_friendly_error,
-                    "errorType": "DEVICE_CLAIMED",
+                    "errorType": kind,
+                    "hints": crate::device::access_error::hints(kind),
                     "status": "error"
                 });
                 let _ = app.emit("device:access-error", error_event_payload);
@@ -655,7 +898,7 @@ pub async fn get_device_info_by_id(
                 // Log the error response
                 let response_data = serde_json::json!({
                     "error": user_friendly_error,
-                    "errorType": "DEVICE_CLAIMED",
+                    "errorType": kind,
                     "operation": "get_device_info_by_id"
                 });
                 
@@ -689,6 +932,166 @@ pub async fn get_device_info_by_id(
     }
 }
 
+/// Hardware model, revision and bootloader/firmware versions of a device, so the
+/// frontend can show the right illustration and model-specific help
+#[tauri::command]
+pub async fn get_device_model(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<device::model::DeviceModelInfo, String> {
+    println!("Getting device model for: {}", device_id);
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let request_data = serde_json::json!({
+        "device_id": device_id,
+        "operation": "get_device_model"
+    });
+    if let Err(e) = log_device_request(&device_id, &request_id, "GetDeviceModel", &request_data).await {
+        eprintln!("Failed to log get device model request: {}", e);
+    }
+
+    let device_info = keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .find(|d| d.unique_id == device_id)
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    let queue_handle = queue_manager.get_or_spawn(&device_id, &device_info).await;
+
+    let result = match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+        Ok(raw_features) => Ok(device::model::derive_model_info(&raw_features, device_info.vid, device_info.pid)),
+        Err(e) if !e.is::<QueueTimeout>() => Err(format!("Failed to get features for device {}: {}", device_id, e)),
+        Err(_) => Err(format!("Timeout getting features for device {}", device_id)),
+    };
+
+    let response_data = match &result {
+        Ok(info) => serde_json::json!({ "model": info, "operation": "get_device_model" }),
+        Err(e) => serde_json::json!({ "error": e, "operation": "get_device_model" }),
+    };
+    if let Err(e) = log_device_response(&device_id, &request_id, result.is_ok(), &response_data, result.as_ref().err().map(|e| e.as_str())).await {
+        eprintln!("Failed to log get device model response: {}", e);
+    }
+
+    result
+}
+
+/// Which firmware the device's bootloader will accept, so a flash can be
+/// predicted to fail before it is attempted
+#[tauri::command]
+pub async fn get_bootloader_policy(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<device::model::BootloaderPolicy, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+        Ok(raw_features) => Ok(device::model::bootloader_policy_for(&raw_features)),
+        Err(e) if !e.is::<QueueTimeout>() => Err(format!("Failed to get features for device {}: {}", unique_id, e)),
+        Err(_) => Err(format!("Timeout getting features for device {}", unique_id)),
+    }
+}
+
+/// Master fingerprint of the wallet currently open on the device, as 8 hex digits.
+/// The device worker reads it from `m` without a display confirmation and caches
+/// it for the passphrase session, so only the first call per session talks to
+/// the device and a passphrase switch yields the new wallet's fingerprint.
+pub async fn master_fingerprint_hex(device_id: &str, queue_manager: &DeviceQueueManager) -> Result<String, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+    queue_handle
+        .get_master_fingerprint()
+        .await
+        .map(|fingerprint| format!("{:08x}", fingerprint))
+        .map_err(|e| format!("Failed to get wallet fingerprint: {}", e))
+}
+
+/// BIP32 master fingerprint of the open wallet, cheap enough to call at wallet load
+#[tauri::command]
+pub async fn get_master_fingerprint(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    master_fingerprint_hex(&unique_id, &queue_manager).await
+}
+
+/// What the device screen is currently asking the user to do, for kiosk UIs
+/// that show matching instructions. Derived from the last prompt the device
+/// worker saw (button request, PIN matrix, passphrase, recovery cipher); there
+/// is no debug-link transport, so debug firmware is read the same way.
+#[tauri::command]
+pub async fn get_device_screen_hint(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<keepkey_rust::screen_hint::ScreenHint, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    Ok(queue_handle.screen_hint())
+}
+
+/// Make `unique_id` the device used by requests that don't name one, and
+/// remember it (by serial) for the next session
+#[tauri::command]
+pub async fn set_active_device(unique_id: String, app: AppHandle) -> Result<(), String> {
+    let device = keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .find(|d| d.unique_id == unique_id)
+        .ok_or_else(|| format!("Device {} is not connected", unique_id))?;
+
+    if let Err(e) = save_active_device_serial(device.serial_number.as_deref().unwrap_or(&device.unique_id)) {
+        log::warn!("Failed to persist active device: {}", e);
+    }
+    let Some(previous) = crate::device::active::select(&unique_id) else {
+        return Ok(());
+    };
+
+    println!("⭐ Active device is now {}", unique_id);
+    let payload = crate::device::active::active_changed_payload(
+        Some(&unique_id),
+        previous.as_deref(),
+        crate::device::active::ActiveChangeReason::Selected,
+    );
+    emit_or_queue_event(&app, "device:active-changed", payload).await
+}
+
+/// The device used by requests that don't name one, if any is selected
+#[tauri::command]
+pub async fn get_active_device() -> Result<Option<String>, String> {
+    Ok(crate::device::active::active_device())
+}
+
+/// Whether the device holds a seed, answered from the feature cache when possible.
+/// Errors (rather than reporting `false`) when the device can't be reached.
+#[tauri::command]
+pub async fn is_device_initialized(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<bool, String> {
+    if let Some(features) = cached_device_features(&device_id) {
+        return Ok(features.initialized);
+    }
+    
+    println!("Feature cache cold for {}, probing device", device_id);
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+    
+    let raw_features = match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+        Ok(raw_features) => raw_features,
+        Err(e) if !e.is::<QueueTimeout>() => return Err(format!("Device {} is unreachable: {}", device_id, e)),
+        Err(_) => return Err(format!("Device {} is unreachable: timed out getting features", device_id)),
+    };
+    
+    let features = convert_features_to_device_features(raw_features);
+    cache_device_features(&device_id, &features);
+    Ok(features.initialized)
+}
+
 /// Wipe device (factory reset)
 #[tauri::command]
 pub async fn wipe_device(
@@ -710,41 +1113,22 @@ pub async fn wipe_device(
     }
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "wipe_device"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log wipe device error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
+    let queue_handle = match queue_manager.get_or_spawn_by_id(&device_id).await {
+        Some(handle) => handle,
+        None => {
+            let error = format!("Device {} not found", device_id);
+            
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "wipe_device"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log wipe device error response: {}", e);
             }
+            
+            return Err(error);
         }
     };
     
@@ -778,6 +1162,7 @@ pub async fn wipe_device(
             match response {
                 keepkey_rust::messages::Message::Success(_) => {
                     println!("✅ Device {} wiped successfully", device_id);
+                    invalidate_cached_features(&device_id);
                     
                     // Log the successful response
                     let response_data = serde_json::json!({
@@ -850,6 +1235,7 @@ pub async fn set_device_label(
     device_id: String,
     label: String,
     queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
 ) -> Result<(), String> {
     println!("Setting device label for {}: '{}'", device_id, label);
     
@@ -900,41 +1286,22 @@ pub async fn set_device_label(
     }
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "set_device_label"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log set device label error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
+    let queue_handle = match queue_manager.get_or_spawn_by_id(&device_id).await {
+        Some(handle) => handle,
+        None => {
+            let error = format!("Device {} not found", device_id);
+            
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "set_device_label"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log set device label error response: {}", e);
             }
+            
+            return Err(error);
         }
     };
     
@@ -961,6 +1328,9 @@ pub async fn set_device_label(
         eprintln!("Failed to log apply settings raw message: {}", e);
     }
     
+    // The device may reboot after applying settings; remember it to find it again
+    let before = device::reconnect::snapshot(&device_id);
+    
     // Send label update through queue
     match queue_handle.send_raw(apply_settings, true).await {
         Ok(response) => {
@@ -976,6 +1346,14 @@ pub async fn set_device_label(
             match response {
                 keepkey_rust::messages::Message::Success(_) => {
                     println!("✅ Device label set successfully for {}: '{}'", device_id, label);
+                    invalidate_cached_features(&device_id);
+                    device::reconnect::spawn_check_after_operation(
+                        app.clone(),
+                        queue_manager.inner().clone(),
+                        device_id.clone(),
+                        "set_device_label",
+                        before,
+                    );
                     
                     // Log the successful response
                     let response_data = serde_json::json!({
@@ -1043,6 +1421,107 @@ pub async fn set_device_label(
     }
 }
 
+/// Set the device's persistent flags (experimental features, advanced mode, ...).
+/// `flags` is the complete desired set; bits are validated against the firmware
+/// version, the changed policies are applied (the device asks for confirmation),
+/// and the refreshed features are returned.
+#[tauri::command]
+pub async fn apply_flags(
+    device_id: String,
+    flags: u32,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<DeviceFeatures, String> {
+    println!("Applying flags 0x{:08x} to device {}", flags, device_id);
+    
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let request_data = serde_json::json!({
+        "device_id": device_id,
+        "flags": flags,
+        "operation": "apply_flags"
+    });
+    if let Err(e) = log_device_request(&device_id, &request_id, "ApplyFlags", &request_data).await {
+        eprintln!("Failed to log apply flags request: {}", e);
+    }
+    
+    let result = apply_flags_inner(&device_id, flags, &queue_manager).await;
+    
+    let response_data = match &result {
+        Ok(features) => serde_json::json!({ "flags": features.flags, "operation": "apply_flags" }),
+        Err(e) => serde_json::json!({ "error": e, "operation": "apply_flags" }),
+    };
+    if let Err(e) = log_device_response(&device_id, &request_id, result.is_ok(), &response_data, result.as_ref().err().map(|e| e.as_str())).await {
+        eprintln!("Failed to log apply flags response: {}", e);
+    }
+    
+    let features = result?;
+    let status = evaluate_device_status(device_id.clone(), Some(&features));
+    let _ = app.emit("device:features-updated", serde_json::json!({
+        "deviceId": device_id,
+        "features": features,
+        "status": status
+    }));
+    Ok(features)
+}
+
+async fn apply_flags_inner(device_id: &str, flags: u32, queue_manager: &DeviceQueueManager) -> Result<DeviceFeatures, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+    
+    let current = queue_handle.get_features().await
+        .map(convert_features_to_device_features)
+        .map_err(|e| format!("Failed to get features: {}", e))?;
+    if current.bootloader_mode {
+        return Err("Flags can't be applied in bootloader mode".to_string());
+    }
+    keepkey_rust::features::flags::validate_flags(flags, &current.version)?;
+    
+    let changes = keepkey_rust::features::flags::policy_changes(current.flags, flags);
+    if changes.is_empty() {
+        println!("Flags already set on device {}", device_id);
+        return Ok(current);
+    }
+    
+    let apply_policies = keepkey_rust::messages::Message::ApplyPolicies(keepkey_rust::messages::ApplyPolicies {
+        policy: changes
+            .iter()
+            .map(|(name, enabled)| keepkey_rust::messages::PolicyType {
+                policy_name: Some(name.to_string()),
+                enabled: Some(*enabled),
+            })
+            .collect(),
+    });
+    
+    let message_data = serde_json::json!({
+        "message_type": "ApplyPolicies",
+        "policies": changes.iter().map(|(name, enabled)| serde_json::json!({ "name": name, "enabled": enabled })).collect::<Vec<_>>()
+    });
+    if let Err(e) = log_raw_device_message(device_id, "SEND", "ApplyPolicies", &message_data).await {
+        eprintln!("Failed to log apply policies raw message: {}", e);
+    }
+    
+    match queue_handle.send_raw(apply_policies, true).await {
+        Ok(keepkey_rust::messages::Message::Success(_)) => {
+            println!("✅ Flags applied to device {}", device_id);
+        }
+        Ok(keepkey_rust::messages::Message::Failure(failure)) => {
+            return Err(format!("Device rejected flags: {}", failure.message.unwrap_or_default()));
+        }
+        Ok(other) => return Err(format!("Unexpected response from device: {:?}", other.message_type())),
+        Err(e) => return Err(format!("Failed to apply flags: {}", e)),
+    }
+    
+    // Refresh so callers (and the feature cache) see the new state
+    invalidate_cached_features(device_id);
+    let features = queue_handle.get_features().await
+        .map(convert_features_to_device_features)
+        .map_err(|e| format!("Flags applied, but refreshing features failed: {}", e))?;
+    cache_device_features(device_id, &features);
+    Ok(features)
+}
+
 /// Enhanced get_connected_devices that fetches features through the queue
 #[tauri::command]
 pub async fn get_connected_devices_with_features(
@@ -1082,18 +1561,7 @@ pub async fn get_connected_devices_with_features(
             }
             
             // Get or create device queue handle
-            let queue_handle = {
-                let mut manager = queue_manager.lock().await;
-                
-                if let Some(handle) = manager.get(&device_id) {
-                    handle.clone()
-                } else {
-                    // Spawn a new device worker
-                    let handle = DeviceQueueFactory::spawn_worker(device_id.clone(), device.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-            };
+            let queue_handle = queue_manager.get_or_spawn(&device_id, &device).await;
             
             // Try to fetch features through the queue with retry logic
             let features = {
@@ -1103,11 +1571,9 @@ pub async fn get_connected_devices_with_features(
                 for attempt in 1..=3 {
                     println!("🔄 Attempting to get features for device {} (attempt {}/3)", device_id, attempt);
                     
-                    match tokio::time::timeout(
-                        Duration::from_secs(30), // 30 seconds per attempt
-                        queue_handle.get_features()
-                    ).await {
-                        Ok(Ok(raw_features)) => {
+                    // 30 seconds per attempt
+                    match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+                        Ok(raw_features) => {
                             println!("✅ Successfully got features for device {} on attempt {}", device_id, attempt);
                             // Convert from raw Features message to DeviceFeatures
                             let device_features = convert_features_to_device_features(raw_features);
@@ -1125,7 +1591,7 @@ pub async fn get_connected_devices_with_features(
                             success_features = Some(device_features);
                             break;
                         }
-                        Ok(Err(e)) => {
+                        Err(e) if !e.is::<QueueTimeout>() => {
                             println!("⚠️ Failed to get features for device {} on attempt {}: {}", device_id, attempt, e);
                             last_error = Some(format!("Failed to get features: {}", e));
                         }
@@ -1220,6 +1686,7 @@ pub fn evaluate_device_status(device_id: String, features: Option<&DeviceFeature
         bootloader_check: None,
         firmware_check: None,
         initialization_check: None,
+        version_mismatch: None,
     };
     
     if let Some(features) = features {
@@ -1275,6 +1742,13 @@ pub fn evaluate_device_status(device_id: String, features: Option<&DeviceFeature
             }
         };
         
+        // Firmware on a bootloader it doesn't support: the bootloader goes first
+        if !features.bootloader_mode {
+            status.version_mismatch = device::model::installed_bootloader_version(features)
+                .and_then(|bootloader| device::model::firmware_bootloader_mismatch(&features.version, &bootloader));
+        }
+        let needs_bootloader_update = needs_bootloader_update || status.version_mismatch.is_some();
+        
         println!("🔧 Bootloader check: {} -> needs update: {} (bootloader_mode: {})", 
                 current_bootloader_version, needs_bootloader_update, features.bootloader_mode);
         
@@ -1314,10 +1788,29 @@ pub fn evaluate_device_status(device_id: String, features: Option<&DeviceFeature
         
         let latest_version = "7.10.0".to_string(); // Latest firmware version
         
+        // A configured minimum turns the update from recommended into mandatory
+        let min_firmware = get_min_firmware_policy();
+        let below_policy = min_firmware.as_ref()
+            .map(|required| firmware_below_minimum(&current_firmware_version, required))
+            .unwrap_or(false);
+        let needs_firmware_update = needs_firmware_update || below_policy;
+        let severity = if below_policy {
+            Some(UpdateSeverity::Mandatory)
+        } else if needs_firmware_update {
+            Some(UpdateSeverity::Recommended)
+        } else {
+            None
+        };
+        
         status.firmware_check = Some(FirmwareCheck {
             current_version: current_firmware_version.clone(),
             latest_version: latest_version.clone(),
             needs_update: needs_firmware_update,
+            severity,
+            required_version: min_firmware.map(|v| v.to_string()),
+            release_notes: needs_firmware_update
+                .then(|| crate::device::release_notes::release_notes(&latest_version, &preferred_language()))
+                .flatten(),
         });
         status.needs_firmware_update = needs_firmware_update;
         
@@ -1383,8 +1876,111 @@ pub fn evaluate_device_status(device_id: String, features: Option<&DeviceFeature
     status
 }
 
+const MIN_FIRMWARE_POLICY_KEY: &str = "min_firmware_policy";
+
+/// The minimum firmware policy as last loaded or set. Every status check
+/// consults it, so the config file is only read the first time.
+static MIN_FIRMWARE_POLICY: once_cell::sync::Lazy<std::sync::RwLock<Option<semver::Version>>> =
+    once_cell::sync::Lazy::new(|| std::sync::RwLock::new(load_config().ok().and_then(|config| parse_min_firmware_policy(&config))));
+
+/// Read the `min_firmware_policy` preference from `config`; unset, empty or
+/// invalid means off
+fn parse_min_firmware_policy(config: &serde_json::Value) -> Option<semver::Version> {
+    let value = config.get(MIN_FIRMWARE_POLICY_KEY)?.as_str()?.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match semver::Version::parse(value) {
+        Ok(version) => Some(version),
+        Err(e) => {
+            log::warn!("Ignoring invalid min_firmware_policy '{}': {}", value, e);
+            None
+        }
+    }
+}
+
+/// Minimum firmware version required for sensitive operations, if the policy is enabled
+pub fn get_min_firmware_policy() -> Option<semver::Version> {
+    match MIN_FIRMWARE_POLICY.read() {
+        Ok(policy) => policy.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn store_min_firmware_policy(policy: Option<semver::Version>) {
+    match MIN_FIRMWARE_POLICY.write() {
+        Ok(mut current) => *current = policy,
+        Err(poisoned) => *poisoned.into_inner() = policy,
+    }
+}
+
+/// Whether a reported firmware version is below `required`.
+/// Unparseable versions (bootloader mode, "Unknown") count as below.
+fn firmware_below_minimum(current: &str, required: &semver::Version) -> bool {
+    match semver::Version::parse(current) {
+        Ok(current) => current < *required,
+        Err(_) => true,
+    }
+}
+
+/// Refuse a sensitive operation if the device firmware is below the policy minimum.
+/// `features` is `None` when the firmware version couldn't be determined.
+pub fn check_min_firmware_policy(features: Option<&DeviceFeatures>) -> Result<(), PolicyError> {
+    check_firmware_policy(features, get_min_firmware_policy().as_ref())
+}
+
+/// `check_min_firmware_policy` against `required`; `None` lets everything through
+fn check_firmware_policy(features: Option<&DeviceFeatures>, required: Option<&semver::Version>) -> Result<(), PolicyError> {
+    let Some(required) = required else {
+        return Ok(());
+    };
+    
+    let current = match features {
+        Some(features) if !features.bootloader_mode => features.version.clone(),
+        Some(_) => "bootloader mode".to_string(),
+        None => "unknown".to_string(),
+    };
+    
+    if firmware_below_minimum(&current, required) {
+        Err(PolicyError::FirmwareTooOld { current, required: required.to_string() })
+    } else {
+        Ok(())
+    }
+}
+
+/// Get the configured minimum firmware version (None = policy off)
+#[tauri::command]
+pub async fn get_min_firmware_policy_setting() -> Result<Option<String>, String> {
+    Ok(get_min_firmware_policy().map(|v| v.to_string()))
+}
+
+/// Set the minimum firmware version for sensitive operations; `None` turns the policy off
+#[tauri::command]
+pub async fn set_min_firmware_policy_setting(version: Option<String>) -> Result<(), String> {
+    let version = version.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if let Some(ref v) = version {
+        semver::Version::parse(v)
+            .map_err(|e| format!("Invalid firmware version '{}': {}", v, e))?;
+    }
+    
+    let mut config = load_config()?;
+    if let Some(obj) = config.as_object_mut() {
+        match version {
+            Some(ref v) => { obj.insert(MIN_FIRMWARE_POLICY_KEY.to_string(), serde_json::Value::String(v.clone())); }
+            None => { obj.remove(MIN_FIRMWARE_POLICY_KEY); }
+        }
+    }
+    
+    save_config(&config)?;
+    store_min_firmware_policy(parse_min_firmware_policy(&config));
+    log::info!("min_firmware_policy set to {:?}", version);
+    Ok(())
+}
+
 /// Convert raw Features message to DeviceFeatures
-pub fn convert_features_to_device_features(raw_features: keepkey_rust::messages::Features) -> DeviceFeatures {
+pub fn convert_features_to_device_features(mut raw_features: keepkey_rust::messages::Features) -> DeviceFeatures {
+    // Strings come from the device and end up in events, status lines and logs
+    keepkey_rust::features::sanitize::sanitize_features(&mut raw_features);
     DeviceFeatures {
         label: raw_features.label,
         vendor: raw_features.vendor,
@@ -1401,7 +1997,12 @@ pub fn convert_features_to_device_features(raw_features: keepkey_rust::messages:
         ),
         firmware_hash: raw_features.firmware_hash.map(hex::encode),
         bootloader_hash: raw_features.bootloader_hash.clone().map(hex::encode),
-        bootloader_version: None, // TODO: Implement proper hash-to-version mapping if needed
+        bootloader_version: raw_features
+            .bootloader_hash
+            .as_ref()
+            .map(hex::encode)
+            .and_then(|hash| crate::device::model::bootloader_version_from_hash(&hash))
+            .map(str::to_string),
         initialized: raw_features.initialized.unwrap_or(false),
         imported: raw_features.imported,
         no_backup: raw_features.no_backup.unwrap_or(false),
@@ -1411,6 +2012,8 @@ pub fn convert_features_to_device_features(raw_features: keepkey_rust::messages:
         passphrase_cached: raw_features.passphrase_cached.unwrap_or(false),
         wipe_code_protection: raw_features.wipe_code_protection.unwrap_or(false),
         auto_lock_delay_ms: raw_features.auto_lock_delay_ms.map(|ms| ms as u64),
+        flags: keepkey_rust::features::flags::flags_from_policy_types(&raw_features.policies),
+        networks: keepkey_rust::features::networks_from_coins(&raw_features.coins),
         policies: raw_features
             .policies
             .into_iter()
@@ -1741,12 +2344,365 @@ fn load_config() -> Result<serde_json::Value, String> {
             "notifications": true
         }));
     }
-    
-    let config_str = fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
-    
-    serde_json::from_str(&config_str)
-        .map_err(|e| format!("Failed to parse config file: {}", e))
+    
+    let config_str = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read config file: {}", e))?;
+    
+    serde_json::from_str(&config_str)
+        .map_err(|e| format!("Failed to parse config file: {}", e))
+}
+
+/// App language from the `language` preference (`en` when unset)
+pub fn preferred_language() -> String {
+    load_config()
+        .ok()
+        .and_then(|config| config.get("language").and_then(|v| v.as_str()).map(|s| s.to_string()))
+        .filter(|language| !language.trim().is_empty())
+        .unwrap_or_else(|| "en".to_string())
+}
+
+const DEVICE_NAME_STRATEGY_KEY: &str = "device_name_strategy";
+
+/// Apply the `device_name_strategy` preference (`raw_label` when unset or invalid).
+/// A `Custom` strategy set from code is left alone unless the preference is set.
+pub fn apply_device_name_strategy_from_config() {
+    let Some(value) = load_config()
+        .ok()
+        .and_then(|config| config.get(DEVICE_NAME_STRATEGY_KEY).and_then(|v| v.as_str()).map(str::to_string))
+    else {
+        return;
+    };
+    
+    match keepkey_rust::features::naming::DeviceNameStrategy::from_config(&value) {
+        Some(strategy) => keepkey_rust::features::naming::set_device_name_strategy(strategy),
+        None => log::warn!("Ignoring invalid device_name_strategy '{}'", value),
+    }
+}
+
+/// Whether the device monitor reports the devices present at startup as one
+/// `devices:initial-snapshot` instead of per-device events (off by default)
+pub fn coalesce_initial_scan_enabled() -> bool {
+    load_config()
+        .ok()
+        .and_then(|config| config.get("coalesce_initial_scan").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Whether `operation:countdown` reports the time left while the device waits
+/// on a confirmation (off by default)
+pub fn confirmation_countdown_enabled() -> bool {
+    load_config()
+        .ok()
+        .and_then(|config| config.get("confirmation_countdown").and_then(|v| v.as_bool()))
+        .unwrap_or(false)
+}
+
+/// Whether the device monitor skips `device:features-updated` when a probe
+/// returns the same features and status as the last one (on by default)
+pub fn dedupe_features_updated_enabled() -> bool {
+    load_config()
+        .ok()
+        .and_then(|config| config.get("dedupe_features_updated").and_then(|v| v.as_bool()))
+        .unwrap_or(true)
+}
+
+/// Whether closing the window cancels prompts waiting on a device (on by
+/// default; kiosk setups that recreate the window turn it off)
+pub fn cancel_on_window_close_enabled() -> bool {
+    load_config()
+        .ok()
+        .and_then(|config| config.get("cancel_on_window_close").and_then(|v| v.as_bool()))
+        .unwrap_or(true)
+}
+
+/// Esplora instance of the current network that transactions are broadcast
+/// through (`esplora_url`, `esplora_url_testnet` or `esplora_url_regtest`;
+/// defaults to Blockstream's public API, or a local electrs for regtest)
+#[cfg(feature = "esplora")]
+pub fn esplora_url() -> String {
+    let mode = network_mode();
+    load_config()
+        .ok()
+        .and_then(|config| config.get(mode.esplora_url_key()).and_then(|v| v.as_str()).map(|s| s.to_string()))
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| mode.default_esplora_url().to_string())
+}
+
+const NETWORK_MODE_KEY: &str = "network_mode";
+
+/// Network the app works on, from the `network_mode` preference (mainnet when
+/// unset or invalid)
+pub fn network_mode() -> NetworkMode {
+    load_config()
+        .ok()
+        .and_then(|config| config.get(NETWORK_MODE_KEY).and_then(|v| v.as_str()).and_then(NetworkMode::from_config))
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub async fn get_network_mode() -> Result<NetworkMode, String> {
+    Ok(network_mode())
+}
+
+/// Switch networks. Addresses and xpubs the device workers cached belong to the
+/// previous network, so they are dropped.
+#[tauri::command]
+pub async fn set_network_mode(
+    mode: NetworkMode,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let previous = network_mode();
+    let mut config = load_config()?;
+    if let Some(obj) = config.as_object_mut() {
+        obj.insert(NETWORK_MODE_KEY.to_string(), Value::String(mode.as_str().to_string()));
+    }
+    save_config(&config)?;
+    if previous == mode {
+        return Ok(());
+    }
+
+    println!("🌐 Switching network mode: {} -> {}", previous, mode);
+    let handles: Vec<DeviceQueueHandle> = queue_manager.lock().await.values().cloned().collect();
+    for handle in handles {
+        if let Err(e) = handle.clear_cache().await {
+            eprintln!("Failed to clear cache of device {}: {}", handle.device_id(), e);
+        }
+    }
+    let _ = app.emit("network:changed", serde_json::json!({ "mode": mode, "previous": previous }));
+    Ok(())
+}
+
+const PROBE_MAX_ATTEMPTS_KEY: &str = "probe_max_attempts";
+
+/// Parse a `probe_max_attempts` value: a whole number of at least 1
+fn parse_probe_max_attempts(value: &str) -> Option<u32> {
+    value.trim().parse::<u32>().ok().filter(|n| *n >= 1)
+}
+
+/// Apply the `probe_max_attempts` preference: how many failed feature probes a
+/// connected device gets before `device:probe-failed` (default 5)
+pub fn apply_probe_policy_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(PROBE_MAX_ATTEMPTS_KEY).cloned()) else {
+        return;
+    };
+    
+    let max_attempts = match &value {
+        Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()).filter(|n| *n >= 1),
+        Value::String(s) => parse_probe_max_attempts(s),
+        _ => None,
+    };
+    match max_attempts {
+        Some(max_attempts) => crate::device::probe::set_max_attempts(max_attempts),
+        None => log::warn!("Ignoring invalid probe_max_attempts '{}'", value),
+    }
+}
+
+const WORKER_IDLE_TIMEOUT_KEY: &str = "worker_idle_timeout_secs";
+
+/// Parse a `worker_idle_timeout_secs` value: whole seconds, 0 to never reap
+fn parse_worker_idle_timeout(value: &str) -> Option<u64> {
+    value.trim().parse::<u64>().ok()
+}
+
+/// Apply the `worker_idle_timeout_secs` preference: how long an unused device
+/// worker is kept before it is stopped (default 60, 0 keeps workers until disconnect)
+pub fn apply_worker_idle_timeout_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(WORKER_IDLE_TIMEOUT_KEY).cloned()) else {
+        return;
+    };
+    
+    let secs = match &value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => parse_worker_idle_timeout(s),
+        _ => None,
+    };
+    match secs {
+        Some(secs) => WORKER_IDLE_TIMEOUT_SECS.store(secs, std::sync::atomic::Ordering::Relaxed),
+        None => log::warn!("Ignoring invalid worker_idle_timeout_secs '{}'", value),
+    }
+}
+
+const TX_CHUNK_SIZE_KEY: &str = "tx_chunk_size";
+
+/// Parse a `tx_chunk_size` value: bytes of transaction per `tx:chunk` event
+fn parse_tx_chunk_size(value: &str) -> Option<usize> {
+    value.trim().parse::<usize>().ok().filter(|bytes| *bytes >= crate::chain::tx_stream::MIN_CHUNK_SIZE)
+}
+
+/// Apply the `tx_chunk_size` preference: how much of a streamed signed
+/// transaction each `tx:chunk` event carries (default 64 KiB, at least 1 KiB)
+pub fn apply_tx_chunk_size_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(TX_CHUNK_SIZE_KEY).cloned()) else {
+        return;
+    };
+
+    let bytes = match &value {
+        Value::Number(n) => n.as_u64().and_then(|n| parse_tx_chunk_size(&n.to_string())),
+        Value::String(s) => parse_tx_chunk_size(s),
+        _ => None,
+    };
+    match bytes {
+        Some(bytes) => crate::chain::tx_stream::set_chunk_size(bytes),
+        None => log::warn!("Ignoring invalid tx_chunk_size '{}'", value),
+    }
+}
+
+const EVENT_LOG_CAPACITY_KEY: &str = "event_log_capacity";
+
+/// Parse an `event_log_capacity` value: a whole number of at least 1
+fn parse_event_log_capacity(value: &str) -> Option<usize> {
+    value.trim().parse::<usize>().ok().filter(|n| *n >= 1)
+}
+
+/// Apply the `event_log_capacity` preference: how many device events the
+/// in-memory event log keeps (default 500)
+pub fn apply_event_log_capacity_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(EVENT_LOG_CAPACITY_KEY).cloned()) else {
+        return;
+    };
+    
+    let capacity = match &value {
+        Value::Number(n) => n.as_u64().and_then(|n| usize::try_from(n).ok()).filter(|n| *n >= 1),
+        Value::String(s) => parse_event_log_capacity(s),
+        _ => None,
+    };
+    match capacity {
+        Some(capacity) => crate::event_log::with_log(|log| log.set_capacity(capacity)),
+        None => log::warn!("Ignoring invalid event_log_capacity '{}'", value),
+    }
+}
+
+const TRANSPORT_CONFIG_KEY: &str = "transport_config";
+
+/// Apply the `transport_config` preference: write retries, per-attempt write
+/// timeout and retry delay of device workers spawned from now on
+pub fn apply_transport_config_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(TRANSPORT_CONFIG_KEY).cloned()) else {
+        return;
+    };
+    
+    match serde_json::from_value(value.clone()) {
+        Ok(config) => store_transport_config(config),
+        Err(e) => log::warn!("Ignoring invalid transport_config '{}': {}", value, e),
+    }
+}
+
+/// Write retry behavior new device workers get
+#[tauri::command]
+pub async fn get_transport_config() -> Result<keepkey_rust::transport::TransportConfig, String> {
+    Ok(transport_config())
+}
+
+/// Change the write retry behavior and remember it. More retries ride out
+/// flaky hubs and cables but make real failures (unplugged device, wedged
+/// firmware) take longer to surface; 0 fails on the first write error.
+/// Workers already running keep their settings until they are respawned.
+#[tauri::command]
+pub async fn set_transport_config(config: keepkey_rust::transport::TransportConfig) -> Result<(), String> {
+    if config.write_timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return Err("write_timeout must be greater than zero".to_string());
+    }
+    
+    let mut stored = load_config()?;
+    if let Some(obj) = stored.as_object_mut() {
+        let value = serde_json::to_value(config).map_err(|e| format!("Failed to serialize transport config: {}", e))?;
+        obj.insert(TRANSPORT_CONFIG_KEY.to_string(), value);
+    }
+    save_config(&stored)?;
+    
+    store_transport_config(config);
+    println!("🔧 Transport config for new workers: {:?}", config);
+    Ok(())
+}
+
+const TIMEOUT_MODE_KEY: &str = "timeout_mode";
+
+/// Apply the `timeout_mode` preference: `adaptive` (default) scales timeouts
+/// and write retries with each device's diagnosed connection quality, `static`
+/// uses the same profile for every device
+pub fn apply_timeout_mode_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(TIMEOUT_MODE_KEY).cloned()) else {
+        return;
+    };
+    
+    match value.as_str().and_then(crate::device::connection::TimeoutMode::from_config) {
+        Some(mode) => crate::device::connection::set_timeout_mode(mode),
+        None => log::warn!("Ignoring invalid timeout_mode '{}'", value),
+    }
+}
+
+const DEVICE_CACHE_CAPACITY_KEY: &str = "device_cache_capacity";
+
+/// Parse a `device_cache_capacity` value: a whole number of at least 1
+fn parse_device_cache_capacity(value: &str) -> Option<usize> {
+    value.trim().parse::<usize>().ok().filter(|n| *n >= 1)
+}
+
+/// Apply the `device_cache_capacity` preference: how many devices each
+/// per-device cache remembers before evicting the least recently seen one
+/// (default 64; connected and active devices are always kept)
+pub fn apply_device_cache_capacity_from_config() {
+    let Some(value) = load_config().ok().and_then(|config| config.get(DEVICE_CACHE_CAPACITY_KEY).cloned()) else {
+        return;
+    };
+    
+    let capacity = match &value {
+        Value::Number(n) => n.as_u64().and_then(|n| usize::try_from(n).ok()).filter(|n| *n >= 1),
+        Value::String(s) => parse_device_cache_capacity(s),
+        _ => None,
+    };
+    match capacity {
+        Some(capacity) => crate::device::lru::set_capacity(capacity),
+        None => log::warn!("Ignoring invalid device_cache_capacity '{}'", value),
+    }
+}
+
+/// Apply the `event_payload_limits` preference: the most bytes of payload an
+/// event may carry before its features are withheld, per event name with an
+/// optional `default`
+pub fn apply_event_payload_limits_from_config() {
+    let key = crate::payload_limits::EVENT_PAYLOAD_LIMITS_KEY;
+    let Some(value) = load_config().ok().and_then(|config| config.get(key).cloned()) else {
+        return;
+    };
+    
+    match crate::payload_limits::PayloadLimits::from_config(&value) {
+        Some(limits) => crate::payload_limits::set_payload_limits(limits),
+        None => log::warn!("Ignoring invalid {} '{}'", key, value),
+    }
+}
+
+const ACTIVE_DEVICE_SERIAL_KEY: &str = "active_device_serial";
+
+/// Serial of the device that was last made active, so it becomes active again
+/// when it reconnects after a restart
+pub fn last_active_device_serial() -> Option<String> {
+    load_config()
+        .ok()
+        .and_then(|config| config.get(ACTIVE_DEVICE_SERIAL_KEY).and_then(|v| v.as_str()).map(str::to_string))
+}
+
+fn save_active_device_serial(serial: &str) -> Result<(), String> {
+    let mut config = load_config()?;
+    if let Some(obj) = config.as_object_mut() {
+        obj.insert(ACTIVE_DEVICE_SERIAL_KEY.to_string(), Value::String(serial.to_string()));
+    }
+    save_config(&config)
+}
+
+/// Stop restoring `serial` as the active device. Returns whether it was the remembered one.
+pub fn forget_active_device_serial(serial: &str) -> Result<bool, String> {
+    let mut config = load_config()?;
+    let remembered = config.get(ACTIVE_DEVICE_SERIAL_KEY).and_then(|v| v.as_str()) == Some(serial);
+    if !remembered {
+        return Ok(false);
+    }
+    if let Some(obj) = config.as_object_mut() {
+        obj.remove(ACTIVE_DEVICE_SERIAL_KEY);
+    }
+    save_config(&config)?;
+    Ok(true)
 }
 
 /// Save configuration to file
@@ -1817,8 +2773,67 @@ pub async fn get_preference(key: String) -> Result<Option<String>, String> {
 /// Set a preference value
 #[tauri::command]
 pub async fn set_preference(key: String, value: String) -> Result<(), String> {
+    let name_strategy = if key == DEVICE_NAME_STRATEGY_KEY {
+        Some(
+            keepkey_rust::features::naming::DeviceNameStrategy::from_config(&value)
+                .ok_or_else(|| format!("Unknown device_name_strategy '{}' (expected raw_label or label_or_model_id)", value))?,
+        )
+    } else {
+        None
+    };
+    let probe_max_attempts = if key == PROBE_MAX_ATTEMPTS_KEY {
+        Some(
+            parse_probe_max_attempts(&value)
+                .ok_or_else(|| format!("Invalid probe_max_attempts '{}' (expected a whole number of at least 1)", value))?,
+        )
+    } else {
+        None
+    };
+
+    let worker_idle_timeout = if key == WORKER_IDLE_TIMEOUT_KEY {
+        Some(
+            parse_worker_idle_timeout(&value)
+                .ok_or_else(|| format!("Invalid worker_idle_timeout_secs '{}' (expected whole seconds, 0 to disable)", value))?,
+        )
+    } else {
+        None
+    };
+    let tx_chunk_size = if key == TX_CHUNK_SIZE_KEY {
+        Some(
+            parse_tx_chunk_size(&value)
+                .ok_or_else(|| format!("Invalid tx_chunk_size '{}' (expected a whole number of bytes, at least 1024)", value))?,
+        )
+    } else {
+        None
+    };
+    let event_log_capacity = if key == EVENT_LOG_CAPACITY_KEY {
+        Some(
+            parse_event_log_capacity(&value)
+                .ok_or_else(|| format!("Invalid event_log_capacity '{}' (expected a whole number of at least 1)", value))?,
+        )
+    } else {
+        None
+    };
+    let device_cache_capacity = if key == DEVICE_CACHE_CAPACITY_KEY {
+        Some(
+            parse_device_cache_capacity(&value)
+                .ok_or_else(|| format!("Invalid device_cache_capacity '{}' (expected a whole number of at least 1)", value))?,
+        )
+    } else {
+        None
+    };
+    let min_firmware_policy_changed = key == MIN_FIRMWARE_POLICY_KEY;
+    let timeout_mode = if key == TIMEOUT_MODE_KEY {
+        Some(
+            crate::device::connection::TimeoutMode::from_config(&value)
+                .ok_or_else(|| format!("Unknown timeout_mode '{}' (expected adaptive or static)", value))?,
+        )
+    } else {
+        None
+    };
+
     let mut config = load_config()?;
-    
+
     if let Some(obj) = config.as_object_mut() {
         // Try to parse as different types
         let parsed_value = if value == "true" || value == "false" {
@@ -1828,11 +2843,36 @@ pub async fn set_preference(key: String, value: String) -> Result<(), String> {
         } else {
             serde_json::Value::String(value)
         };
-        
+
         obj.insert(key, parsed_value);
     }
-    
+
     save_config(&config)?;
+
+    if let Some(strategy) = name_strategy {
+        keepkey_rust::features::naming::set_device_name_strategy(strategy);
+    }
+    if let Some(max_attempts) = probe_max_attempts {
+        crate::device::probe::set_max_attempts(max_attempts);
+    }
+    if let Some(secs) = worker_idle_timeout {
+        WORKER_IDLE_TIMEOUT_SECS.store(secs, std::sync::atomic::Ordering::Relaxed);
+    }
+    if let Some(bytes) = tx_chunk_size {
+        crate::chain::tx_stream::set_chunk_size(bytes);
+    }
+    if let Some(capacity) = event_log_capacity {
+        crate::event_log::with_log(|log| log.set_capacity(capacity));
+    }
+    if let Some(capacity) = device_cache_capacity {
+        crate::device::lru::set_capacity(capacity);
+    }
+    if let Some(mode) = timeout_mode {
+        crate::device::connection::set_timeout_mode(mode);
+    }
+    if min_firmware_policy_changed {
+        store_min_firmware_policy(parse_min_firmware_policy(&config));
+    }
     Ok(())
 }
 
@@ -1986,30 +3026,15 @@ pub async fn initialize_device_pin(
     }
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id)
-                .ok_or_else(|| {
-                    // Clean up session on device not found
-                    let mut sessions = PIN_SESSIONS.lock().unwrap_or_else(|_| panic!("Failed to lock PIN sessions"));
-                    sessions.remove(&session_id);
-                    format!("Device {} not found", device_id)
-                })?;
-            
-            // Spawn a new device worker
-            let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-            manager.insert(device_id.clone(), handle.clone());
-            handle
-        }
-    };
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| {
+            // Clean up session on device not found
+            let mut sessions = lock_or_recover(&PIN_SESSIONS, "PIN sessions");
+            sessions.remove(&session_id);
+            format!("Device {} not found", device_id)
+        })?;
     
     // Create ResetDevice message with PIN protection enabled
     let reset_device = keepkey_rust::messages::ResetDevice {
@@ -2039,11 +3064,9 @@ pub async fn initialize_device_pin(
                 keepkey_rust::messages::Message::Success(_) => {
                     log::info!("Device reset completed without PIN request");
                     // Mark as completed
-                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.current_step = PinStep::Completed;
-                            session.is_active = false;
-                        }
+                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                        session.current_step = PinStep::Completed;
+                        session.is_active = false;
                     }
                     Ok(session)
                 }
@@ -2145,11 +3168,9 @@ pub async fn send_pin_matrix_response(
                                 log::info!("✅ PIN unlock successful, device is now unlocked");
                                 
                                 // Update session state to completed
-                                if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                    if let Some(session) = sessions.get_mut(&session_id) {
-                                        session.current_step = PinStep::Completed;
-                                        session.is_active = false;
-                                    }
+                                if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                    session.current_step = PinStep::Completed;
+                                    session.is_active = false;
                                 }
                                 // Unmark device from PIN flow - PIN unlock completed
                                 let _ = unmark_device_in_pin_flow(&device_id);
@@ -2164,11 +3185,9 @@ pub async fn send_pin_matrix_response(
                                 log::error!("❌ PIN unlock failed - device still locked");
                                 
                                 // Update session state to failed
-                                if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                    if let Some(session) = sessions.get_mut(&session_id) {
-                                        session.current_step = PinStep::Failed;
-                                        session.is_active = false;
-                                    }
+                                if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                    session.current_step = PinStep::Failed;
+                                    session.is_active = false;
                                 }
                                 // Unmark device from PIN flow on failure
                                 let _ = unmark_device_in_pin_flow(&device_id);
@@ -2180,11 +3199,9 @@ pub async fn send_pin_matrix_response(
                             log::error!("❌ PIN unlock failed: {}", f.message.as_deref().unwrap_or("Unknown error"));
                             
                             // Update session state to failed  
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Failed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Failed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow on failure
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2195,11 +3212,9 @@ pub async fn send_pin_matrix_response(
                             log::error!("❌ Unexpected response to PIN unlock: {:?}", response);
                             
                             // Update session state to failed
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Failed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Failed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow on failure
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2216,10 +3231,8 @@ pub async fn send_pin_matrix_response(
                                 Some(3) => {  // NewSecond = 3 (PIN confirmation)
                                     log::info!("✅ First PIN accepted, device requesting confirmation");
                                     // Update session state
-                                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                        if let Some(session) = sessions.get_mut(&session_id) {
-                                            session.current_step = PinStep::AwaitingSecond;
-                                        }
+                                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                        session.current_step = PinStep::AwaitingSecond;
                                     }
                                     
                                     Ok(PinMatrixResult {
@@ -2232,10 +3245,8 @@ pub async fn send_pin_matrix_response(
                                 _ => {
                                     log::warn!("Unexpected PIN matrix request type: {:?}", pmr.r#type);
                                     // Update session state
-                                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                        if let Some(session) = sessions.get_mut(&session_id) {
-                                            session.current_step = PinStep::AwaitingSecond;
-                                        }
+                                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                        session.current_step = PinStep::AwaitingSecond;
                                     }
                                     Ok(PinMatrixResult {
                                         success: true,
@@ -2249,12 +3260,11 @@ pub async fn send_pin_matrix_response(
                         keepkey_rust::messages::Message::EntropyRequest(_) | 
                         keepkey_rust::messages::Message::Success(_) => {
                             log::info!("✅ PIN creation completed in single step");
+                            invalidate_cached_features(&device_id);
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Completed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Completed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow - PIN creation completed
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2268,11 +3278,9 @@ pub async fn send_pin_matrix_response(
                         }
                         keepkey_rust::messages::Message::Failure(f) => {
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Failed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Failed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow on failure
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2281,10 +3289,8 @@ pub async fn send_pin_matrix_response(
                         _ => {
                             log::warn!("Unexpected response to first PIN: {:?}", response);
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::AwaitingSecond;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::AwaitingSecond;
                             }
                             Ok(PinMatrixResult {
                                 success: true,
@@ -2301,11 +3307,9 @@ pub async fn send_pin_matrix_response(
                         keepkey_rust::messages::Message::EntropyRequest(_) => {
                             log::info!("✅ PIN confirmation accepted, device requesting entropy (handled automatically)");
                             // Update session state to completed
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Completed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Completed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow - PIN creation completed
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2319,12 +3323,11 @@ pub async fn send_pin_matrix_response(
                         }
                         keepkey_rust::messages::Message::Success(_) => {
                             log::info!("✅ PIN confirmation accepted, device initialization completed");
+                            invalidate_cached_features(&device_id);
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Completed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Completed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow - PIN creation completed
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2338,11 +3341,9 @@ pub async fn send_pin_matrix_response(
                         }
                         keepkey_rust::messages::Message::Failure(f) => {
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Failed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Failed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow on failure
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2351,11 +3352,9 @@ pub async fn send_pin_matrix_response(
                         _ => {
                             log::warn!("Unexpected response during PIN confirmation: {:?}", response);
                             // Update session state
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Completed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Completed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow - assuming completion
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2380,11 +3379,9 @@ pub async fn send_pin_matrix_response(
         Err(e) => {
             log::error!("Failed to send PIN matrix response: {}", e);
             // Update session state
-            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                if let Some(session) = sessions.get_mut(&session_id) {
-                    session.current_step = PinStep::Failed;
-                    session.is_active = false;
-                }
+            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                session.current_step = PinStep::Failed;
+                session.is_active = false;
             }
             // Unmark device from PIN flow on communication error
             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2474,29 +3471,11 @@ pub async fn send_pin_unlock_response(
     
     log::info!("Converted positions to PIN string for device communication: {}", pin_string);
     
-    // Get or create device queue handle  
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        match manager.get(&device_id) {
-            Some(handle) => handle.clone(),
-            None => {
-                // Find the device by ID
-                let devices = keepkey_rust::features::list_connected_devices();
-                let device_info = devices
-                    .iter()
-                    .find(|d| d.unique_id == device_id)
-                    .ok_or_else(|| format!("Device {} not found", device_id))?;
-                
-                // Spawn a new device worker using the factory
-                let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(
-                    device_id.clone(),
-                    device_info.clone()
-                );
-                manager.insert(device_id.clone(), handle.clone());
-                handle
-            }
-        }
-    };
+    // Get or create device queue handle
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
     
     // Try a simple GetFeatures with PIN to unlock device
     let get_features = keepkey_rust::messages::Message::GetFeatures(
@@ -2524,11 +3503,9 @@ pub async fn send_pin_unlock_response(
                                         log::info!("✅ PIN unlock successful, device is now unlocked");
                                         
                                         // Update session state to completed
-                                        if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                            if let Some(session) = sessions.get_mut(&session_id) {
-                                                session.current_step = PinStep::Completed;
-                                                session.is_active = false;
-                                            }
+                                        if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                            session.current_step = PinStep::Completed;
+                                            session.is_active = false;
                                         }
                                         // Unmark device from PIN flow - PIN unlock completed
                                         let _ = unmark_device_in_pin_flow(&device_id);
@@ -2543,11 +3520,9 @@ pub async fn send_pin_unlock_response(
                                         log::error!("❌ PIN unlock failed - device still locked");
                                         
                                         // Update session state to failed
-                                        if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                            if let Some(session) = sessions.get_mut(&session_id) {
-                                                session.current_step = PinStep::Failed;
-                                                session.is_active = false;
-                                            }
+                                        if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                            session.current_step = PinStep::Failed;
+                                            session.is_active = false;
                                         }
                                         // Unmark device from PIN flow on failure
                                         let _ = unmark_device_in_pin_flow(&device_id);
@@ -2559,11 +3534,9 @@ pub async fn send_pin_unlock_response(
                                     log::error!("❌ PIN unlock failed: {}", f.message.as_deref().unwrap_or("Unknown error"));
                                     
                                     // Update session state to failed
-                                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                        if let Some(session) = sessions.get_mut(&session_id) {
-                                            session.current_step = PinStep::Failed;
-                                            session.is_active = false;
-                                        }
+                                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                        session.current_step = PinStep::Failed;
+                                        session.is_active = false;
                                     }
                                     // Unmark device from PIN flow on failure
                                     let _ = unmark_device_in_pin_flow(&device_id);
@@ -2574,11 +3547,9 @@ pub async fn send_pin_unlock_response(
                                     log::error!("❌ Unexpected response to PIN unlock: {:?}", features_response);
                                     
                                     // Update session state to failed
-                                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                        if let Some(session) = sessions.get_mut(&session_id) {
-                                            session.current_step = PinStep::Failed;
-                                            session.is_active = false;
-                                        }
+                                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                        session.current_step = PinStep::Failed;
+                                        session.is_active = false;
                                     }
                                     // Unmark device from PIN flow on failure
                                     let _ = unmark_device_in_pin_flow(&device_id);
@@ -2591,11 +3562,9 @@ pub async fn send_pin_unlock_response(
                             log::error!("Failed to send PIN to device: {}", e);
                             
                             // Update session state to failed
-                            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                                if let Some(session) = sessions.get_mut(&session_id) {
-                                    session.current_step = PinStep::Failed;
-                                    session.is_active = false;
-                                }
+                            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                                session.current_step = PinStep::Failed;
+                                session.is_active = false;
                             }
                             // Unmark device from PIN flow on failure
                             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2611,11 +3580,9 @@ pub async fn send_pin_unlock_response(
                         log::info!("✅ Device is already unlocked");
                         
                         // Update session state to completed
-                        if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                            if let Some(session) = sessions.get_mut(&session_id) {
-                                session.current_step = PinStep::Completed;
-                                session.is_active = false;
-                            }
+                        if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                            session.current_step = PinStep::Completed;
+                            session.is_active = false;
                         }
                         // Unmark device from PIN flow - already unlocked
                         let _ = unmark_device_in_pin_flow(&device_id);
@@ -2630,11 +3597,9 @@ pub async fn send_pin_unlock_response(
                         log::error!("❌ Device claims no PIN protection but is not unlocked");
                         
                         // Update session state to failed
-                        if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                            if let Some(session) = sessions.get_mut(&session_id) {
-                                session.current_step = PinStep::Failed;
-                                session.is_active = false;
-                            }
+                        if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                            session.current_step = PinStep::Failed;
+                            session.is_active = false;
                         }
                         // Unmark device from PIN flow on failure  
                         let _ = unmark_device_in_pin_flow(&device_id);
@@ -2646,11 +3611,9 @@ pub async fn send_pin_unlock_response(
                     log::error!("❌ Unexpected initial response from device: {:?}", response);
                     
                     // Update session state to failed
-                    if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.current_step = PinStep::Failed;
-                            session.is_active = false;
-                        }
+                    if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                        session.current_step = PinStep::Failed;
+                        session.is_active = false;
                     }
                     // Unmark device from PIN flow on failure
                     let _ = unmark_device_in_pin_flow(&device_id);
@@ -2663,11 +3626,9 @@ pub async fn send_pin_unlock_response(
             log::error!("Failed to communicate with device for PIN unlock: {}", e);
             
             // Update session state to failed
-            if let Ok(mut sessions) = PIN_SESSIONS.lock() {
-                if let Some(session) = sessions.get_mut(&session_id) {
-                    session.current_step = PinStep::Failed;  
-                    session.is_active = false;
-                }
+            if let Some(session) = lock_or_recover(&PIN_SESSIONS, "PIN sessions").get_mut(&session_id) {
+                session.current_step = PinStep::Failed;  
+                session.is_active = false;
             }
             // Unmark device from PIN flow on failure
             let _ = unmark_device_in_pin_flow(&device_id);
@@ -2746,7 +3707,7 @@ pub async fn complete_wallet_creation(device_id: String) -> Result<(), String> {
 
 /// Mark device as being in PIN flow to prevent duplicate operations
 pub fn mark_device_in_pin_flow(device_id: &str) -> Result<(), String> {
-    let mut flows = DEVICE_PIN_FLOWS.lock().map_err(|_| "Failed to lock device PIN flows".to_string())?;
+    let mut flows = lock_or_recover(&DEVICE_PIN_FLOWS, "PIN flows");
     flows.insert(device_id.to_string());
     log::info!("Device {} marked as in PIN flow", device_id);
     Ok(())
@@ -2754,16 +3715,12 @@ pub fn mark_device_in_pin_flow(device_id: &str) -> Result<(), String> {
 
 /// Check if device is currently in PIN flow
 pub fn is_device_in_pin_flow(device_id: &str) -> bool {
-    if let Ok(flows) = DEVICE_PIN_FLOWS.lock() {
-        flows.contains(device_id)
-    } else {
-        false
-    }
+    lock_or_recover(&DEVICE_PIN_FLOWS, "PIN flows").contains(device_id)
 }
 
 /// Remove device from PIN flow state
 pub fn unmark_device_in_pin_flow(device_id: &str) -> Result<(), String> {
-    let mut flows = DEVICE_PIN_FLOWS.lock().map_err(|_| "Failed to lock device PIN flows".to_string())?;
+    let mut flows = lock_or_recover(&DEVICE_PIN_FLOWS, "PIN flows");
     flows.remove(device_id);
     log::info!("Device {} removed from PIN flow", device_id);
     Ok(())
@@ -2895,30 +3852,15 @@ pub async fn start_device_recovery(
     mark_device_in_recovery_flow(&device_id)?;
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id)
-                .ok_or_else(|| {
-                    // Clean up session on device not found
-                    let mut sessions = RECOVERY_SESSIONS.lock().unwrap_or_else(|_| panic!("Failed to lock recovery sessions"));
-                    sessions.remove(&session_id);
-                    format!("Device {} not found", device_id)
-                })?;
-            
-            // Spawn a new device worker
-            let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-            manager.insert(device_id.clone(), handle.clone());
-            handle
-        }
-    };
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| {
+            // Clean up session on device not found
+            let mut sessions = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions");
+            sessions.remove(&session_id);
+            format!("Device {} not found", device_id)
+        })?;
     
     // Create RecoveryDevice message - minimal essential parameters only
     let recovery_device = keepkey_rust::messages::RecoveryDevice {
@@ -2950,11 +3892,9 @@ pub async fn start_device_recovery(
                     log::info!("Device ready for character input: word {}, char {}", 
                         req.word_pos, req.character_pos);
                     // Update session state
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(s) = sessions.get_mut(&session_id) {
-                            s.current_word = req.word_pos;
-                            s.current_character = req.character_pos;
-                        }
+                    if let Some(s) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        s.current_word = req.word_pos;
+                        s.current_character = req.character_pos;
                     }
                     Ok(session)
                 }
@@ -2965,9 +3905,7 @@ pub async fn start_device_recovery(
                 }
                 keepkey_rust::messages::Message::Failure(f) => {
                     // Clean up session only on actual device failure
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        sessions.remove(&session_id);
-                    }
+                    lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").remove(&session_id);
                     let _ = unmark_device_in_recovery_flow(&device_id);
                     Err(format!("Device rejected recovery: {}", f.message.unwrap_or_default()))
                 }
@@ -3073,11 +4011,9 @@ pub async fn send_recovery_character(
             match response {
                 keepkey_rust::messages::Message::CharacterRequest(req) => {
                     // Update session state
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.current_word = req.word_pos;
-                            session.current_character = req.character_pos;
-                        }
+                    if let Some(session) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        session.current_word = req.word_pos;
+                        session.current_character = req.character_pos;
                     }
                     
                     Ok(RecoveryProgress {
@@ -3090,10 +4026,9 @@ pub async fn send_recovery_character(
                 }
                 keepkey_rust::messages::Message::Success(_) => {
                     // Recovery completed successfully
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.is_active = false;
-                        }
+                    invalidate_cached_features(&device_id);
+                    if let Some(session) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        session.is_active = false;
                     }
                     
                     // Remove from recovery flow
@@ -3109,10 +4044,8 @@ pub async fn send_recovery_character(
                 }
                 keepkey_rust::messages::Message::Failure(f) => {
                     // Mark session as failed
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.is_active = false;
-                        }
+                    if let Some(session) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        session.is_active = false;
                     }
                     
                     // Remove from recovery flow
@@ -3221,11 +4154,9 @@ pub async fn send_recovery_pin_response(
                 }
                 keepkey_rust::messages::Message::CharacterRequest(req) => {
                     // Ready for character input
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.current_word = req.word_pos;
-                            session.current_character = req.character_pos;
-                        }
+                    if let Some(session) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        session.current_word = req.word_pos;
+                        session.current_character = req.character_pos;
                     }
                     
                     Ok(RecoveryProgress {
@@ -3238,10 +4169,9 @@ pub async fn send_recovery_pin_response(
                 }
                 keepkey_rust::messages::Message::Success(_) => {
                     // Recovery completed
-                    if let Ok(mut sessions) = RECOVERY_SESSIONS.lock() {
-                        if let Some(session) = sessions.get_mut(&session_id) {
-                            session.is_active = false;
-                        }
+                    invalidate_cached_features(&device_id);
+                    if let Some(session) = lock_or_recover(&RECOVERY_SESSIONS, "recovery sessions").get_mut(&session_id) {
+                        session.is_active = false;
                     }
                     
                     let _ = unmark_device_in_recovery_flow(&device_id);
@@ -3399,30 +4329,15 @@ pub async fn start_seed_verification(
     mark_device_in_recovery_flow(&device_id)?;
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id)
-                .ok_or_else(|| {
-                    // Clean up session on device not found
-                    let mut sessions = VERIFICATION_SESSIONS.lock().unwrap_or_else(|_| panic!("Failed to lock verification sessions"));
-                    sessions.remove(&session_id);
-                    format!("Device {} not found", device_id)
-                })?;
-            
-            // Spawn a new device worker
-            let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-            manager.insert(device_id.clone(), handle.clone());
-            handle
-        }
-    };
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| {
+            // Clean up session on device not found
+            let mut sessions = lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions");
+            sessions.remove(&session_id);
+            format!("Device {} not found", device_id)
+        })?;
     
     // Create RecoveryDevice message with dry_run = true
     let recovery_device = keepkey_rust::messages::RecoveryDevice {
@@ -3454,20 +4369,16 @@ pub async fn start_seed_verification(
                     log::info!("Device ready for character input (PIN already verified): word {}, char {}", 
                         req.word_pos, req.character_pos);
                     // Update session state
-                    if let Ok(mut sessions) = VERIFICATION_SESSIONS.lock() {
-                        if let Some(s) = sessions.get_mut(&session_id) {
-                            s.current_word = req.word_pos;
-                            s.current_character = req.character_pos;
-                            s.pin_verified = true;
-                        }
+                    if let Some(s) = lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").get_mut(&session_id) {
+                        s.current_word = req.word_pos;
+                        s.current_character = req.character_pos;
+                        s.pin_verified = true;
                     }
                     Ok(session)
                 }
                 keepkey_rust::messages::Message::Failure(f) => {
                     // Clean up on failure
-                    if let Ok(mut sessions) = VERIFICATION_SESSIONS.lock() {
-                        sessions.remove(&session_id);
-                    }
+                    lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").remove(&session_id);
                     let _ = unmark_device_in_recovery_flow(&device_id);
                     Err(format!("Device rejected seed verification: {}", f.message.unwrap_or_default()))
                 }
@@ -3479,46 +4390,177 @@ pub async fn start_seed_verification(
         }
         Err(e) => {
             // Clean up on error
-            if let Ok(mut sessions) = VERIFICATION_SESSIONS.lock() {
-                sessions.remove(&session_id);
-            }
+            lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").remove(&session_id);
             let _ = unmark_device_in_recovery_flow(&device_id);
             Err(format!("Failed to start seed verification: {}", e))
         }
     }
 }
 
-/// Send verification character input
+/// Active verification session of `session_id` and the queue of its device
+async fn verification_session_queue(
+    session_id: &str,
+    queue_manager: &DeviceQueueManager,
+) -> Result<(SeedVerificationSession, DeviceQueueHandle), String> {
+    let session = {
+        let sessions = VERIFICATION_SESSIONS.lock()
+            .map_err(|_| "Failed to lock verification sessions".to_string())?;
+        
+        let session = sessions.get(session_id)
+            .ok_or_else(|| "Verification session not found".to_string())?;
+        
+        if !session.is_active {
+            return Err("Verification session is not active".to_string());
+        }
+        session.clone()
+    };
+    
+    let canonical_device_id = get_canonical_device_id(&session.device_id);
+    let queue_handle = {
+        let manager = queue_manager.lock().await;
+        manager.get(&canonical_device_id)
+            .or_else(|| manager.get(&session.device_id))
+            .ok_or_else(|| format!("Device queue not found for device: {} (canonical: {})", session.device_id, canonical_device_id))?
+            .clone()
+    };
+    Ok((session, queue_handle))
+}
+
+/// End a verification session once the device reported its outcome
+fn finish_seed_verification(session_id: &str, device_id: &str, outcome: &crate::device::seed_check::SeedCheckOutcome) {
+    log::info!("Seed verification {} for device {} finished: {:?}", session_id, device_id, outcome);
+    lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").remove(session_id);
+    let _ = unmark_device_in_recovery_flow(device_id);
+}
+
+/// Send verification character input. The device answers the last one with
+/// the outcome of the check: `is_complete` with no `error` when the sentence
+/// matches, otherwise `error` says why not.
 #[tauri::command]
 pub async fn send_verification_character(
     session_id: String,
     character: Option<String>,
     action: Option<RecoveryAction>,
-    _queue_manager: tauri::State<'_, DeviceQueueManager>,
+    queue_manager: tauri::State<'_, DeviceQueueManager>,
 ) -> Result<RecoveryProgress, String> {
     log::info!("Sending verification character for session: {} - char: {:?}, action: {:?}", 
         session_id, character, action);
     
-    // Similar implementation to send_recovery_character but for verification sessions
-    // Implementation would be similar to the recovery character function above
-    // For brevity, I'll implement a simplified version
+    let (session, queue_handle) = verification_session_queue(&session_id, &queue_manager).await?;
+    
+    let character_ack = match action {
+        Some(RecoveryAction::Done) => keepkey_rust::messages::CharacterAck {
+            character: None,
+            delete: Some(false),
+            done: Some(true),
+        },
+        Some(RecoveryAction::Delete) => keepkey_rust::messages::CharacterAck {
+            character: None,
+            delete: Some(true),
+            done: Some(false),
+        },
+        Some(RecoveryAction::Space) => keepkey_rust::messages::CharacterAck {
+            character: Some(" ".to_string()),
+            delete: Some(false),
+            done: Some(false),
+        },
+        None => {
+            let ch = character.ok_or_else(|| "No character or action provided".to_string())?;
+            if ch.len() != 1 || !ch.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err("Invalid character. Must be a single letter a-z".to_string());
+            }
+            keepkey_rust::messages::CharacterAck {
+                character: Some(ch.to_lowercase()),
+                delete: Some(false),
+                done: Some(false),
+            }
+        }
+    };
+    
+    let response = queue_handle
+        .send_raw(keepkey_rust::messages::Message::CharacterAck(character_ack), false)
+        .await
+        .map_err(|e| format!("Failed to send character: {}", e))?;
+    
+    if let keepkey_rust::messages::Message::CharacterRequest(req) = &response {
+        if let Some(s) = lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").get_mut(&session_id) {
+            s.current_word = req.word_pos;
+            s.current_character = req.character_pos;
+        }
+        return Ok(RecoveryProgress {
+            word_pos: req.word_pos,
+            character_pos: req.character_pos,
+            auto_completed: false,
+            is_complete: false,
+            error: None,
+        });
+    }
     
-    Err("Verification character sending not yet implemented".to_string())
+    let outcome = crate::device::seed_check::SeedCheckOutcome::from_response(&response)
+        .ok_or_else(|| format!("Unexpected response: {:?}", response))?;
+    finish_seed_verification(&session_id, &session.device_id, &outcome);
+    Ok(RecoveryProgress {
+        word_pos: session.current_word,
+        character_pos: session.current_character,
+        auto_completed: false,
+        is_complete: true,
+        error: outcome.error_message(),
+    })
 }
 
-/// Send PIN matrix response during seed verification
+/// Send PIN matrix response during seed verification. `false` when the PIN was
+/// wrong and the device asks again.
 #[tauri::command]
 pub async fn send_verification_pin(
     session_id: String,
     positions: Vec<u8>,
-    _queue_manager: tauri::State<'_, DeviceQueueManager>,
+    queue_manager: tauri::State<'_, DeviceQueueManager>,
 ) -> Result<bool, String> {
     log::info!("Sending verification PIN for session: {} with {} positions", session_id, positions.len());
     
-    // Similar implementation to send_recovery_pin_response but for verification sessions
-    // For brevity, I'll implement a simplified version
+    if positions.is_empty() || positions.len() > 9 {
+        return Err("PIN must be between 1 and 9 digits".to_string());
+    }
+    if positions.iter().any(|&pos| !(1..=9).contains(&pos)) {
+        return Err("Invalid PIN position: positions must be 1-9".to_string());
+    }
+    
+    let (session, queue_handle) = verification_session_queue(&session_id, &queue_manager).await?;
+    
+    let pin: String = positions.iter().map(|&pos| (b'0' + pos) as char).collect();
+    let mut response = queue_handle
+        .send_raw(keepkey_rust::messages::PinMatrixAck { pin }.into(), false)
+        .await
+        .map_err(|e| format!("Failed to send verification PIN: {}", e))?;
+    // The device may ask to confirm the check on screen before phrase entry
+    while let keepkey_rust::messages::Message::ButtonRequest(_) = response {
+        response = queue_handle
+            .send_raw(keepkey_rust::messages::ButtonAck::default().into(), false)
+            .await
+            .map_err(|e| format!("Failed to confirm seed verification: {}", e))?;
+    }
     
-    Err("Verification PIN sending not yet implemented".to_string())
+    match response {
+        keepkey_rust::messages::Message::CharacterRequest(req) => {
+            if let Some(s) = lock_or_recover(&VERIFICATION_SESSIONS, "verification sessions").get_mut(&session_id) {
+                s.current_word = req.word_pos;
+                s.current_character = req.character_pos;
+                s.pin_verified = true;
+            }
+            Ok(true)
+        }
+        keepkey_rust::messages::Message::PinMatrixRequest(_) => Ok(false),
+        keepkey_rust::messages::Message::Failure(f) => {
+            // Either way the dry run is over on the device
+            let outcome = crate::device::seed_check::SeedCheckOutcome::from_failure(&f);
+            finish_seed_verification(&session_id, &session.device_id, &outcome);
+            match outcome {
+                crate::device::seed_check::SeedCheckOutcome::Aborted => Err(outcome.error_message().unwrap_or_default()),
+                _ => Err(format!("Invalid PIN: {}", f.message.unwrap_or_default())),
+            }
+        }
+        other => Err(format!("Unexpected response to verification PIN: {:?}", other)),
+    }
 }
 
 /// Get seed verification status
@@ -3587,7 +4629,7 @@ pub async fn force_cleanup_seed_verification(device_id: String) -> Result<bool,
 
 /// Mark device as being in recovery flow to prevent duplicate operations
 pub fn mark_device_in_recovery_flow(device_id: &str) -> Result<(), String> {
-    let mut flows = RECOVERY_DEVICE_FLOWS.lock().map_err(|_| "Failed to lock recovery device flows".to_string())?;
+    let mut flows = lock_or_recover(&RECOVERY_DEVICE_FLOWS, "recovery flows");
     flows.insert(device_id.to_string());
     log::info!("Device {} marked as in recovery flow", device_id);
     Ok(())
@@ -3595,31 +4637,24 @@ pub fn mark_device_in_recovery_flow(device_id: &str) -> Result<(), String> {
 
 /// Check if device is currently in recovery flow
 pub fn is_device_in_recovery_flow(device_id: &str) -> bool {
-    if let Ok(flows) = RECOVERY_DEVICE_FLOWS.lock() {
-        flows.contains(device_id)
-    } else {
-        false
-    }
+    lock_or_recover(&RECOVERY_DEVICE_FLOWS, "recovery flows").contains(device_id)
 }
 
 /// Remove device from recovery flow state
 pub fn unmark_device_in_recovery_flow(device_id: &str) -> Result<(), String> {
-    let mut flows = RECOVERY_DEVICE_FLOWS.lock().map_err(|_| "Failed to lock recovery device flows".to_string())?;
+    let mut flows = lock_or_recover(&RECOVERY_DEVICE_FLOWS, "recovery flows");
     flows.remove(device_id);
     log::info!("Device {} removed from recovery flow", device_id);
     
     // Also clean up any aliases
-    if let Ok(mut aliases) = RECOVERY_DEVICE_ALIASES.lock() {
-        aliases.retain(|_, v| v != device_id);
-    }
+    lock_or_recover(&RECOVERY_DEVICE_ALIASES, "recovery device aliases").retain(|_, v| v != device_id);
     
     Ok(())
 }
 
 /// Add device ID alias for recovery flow
 pub fn add_recovery_device_alias(alias_id: &str, canonical_id: &str) -> Result<(), String> {
-    let mut aliases = RECOVERY_DEVICE_ALIASES.lock()
-        .map_err(|_| "Failed to lock recovery device aliases".to_string())?;
+    let mut aliases = lock_or_recover(&RECOVERY_DEVICE_ALIASES, "recovery device aliases");
     aliases.insert(alias_id.to_string(), canonical_id.to_string());
     log::info!("Added recovery device alias: {} -> {}", alias_id, canonical_id);
     Ok(())
@@ -3627,11 +4662,9 @@ pub fn add_recovery_device_alias(alias_id: &str, canonical_id: &str) -> Result<(
 
 /// Get canonical device ID from alias
 pub fn get_canonical_device_id(device_id: &str) -> String {
-    if let Ok(aliases) = RECOVERY_DEVICE_ALIASES.lock() {
-        if let Some(canonical) = aliases.get(device_id) {
-            log::info!("Resolved device alias {} to canonical ID {}", device_id, canonical);
-            return canonical.clone();
-        }
+    if let Some(canonical) = lock_or_recover(&RECOVERY_DEVICE_ALIASES, "recovery device aliases").get(device_id) {
+        log::info!("Resolved device alias {} to canonical ID {}", device_id, canonical);
+        return canonical.clone();
     }
     device_id.to_string()
 }
@@ -3650,6 +4683,11 @@ pub fn are_devices_potentially_same(id1: &str, id2: &str) -> bool {
         return true;
     }
     
+    // Devices that reported their hardware id settle it either way
+    if let Some(same) = crate::device::identity::same_hardware(id1, id2) {
+        return same;
+    }
+    
     // Check if they're both KeepKey devices (same VID/PID)
     let keepkey_pattern = "keepkey_2b24_0002_";
     if id1.contains(keepkey_pattern) && id2.contains(keepkey_pattern) {
@@ -3696,8 +4734,7 @@ pub async fn send_pin_matrix_ack(
     log::info!("Sending PIN matrix ACK with {} digits", pin.len());
     
     // Get device queue handle
-    let queue_manager_guard = queue_manager.lock().await;
-    let queue_handle = queue_manager_guard.get(&device_id)
+    let queue_handle = queue_manager.get_or_spawn_by_id(&device_id).await
         .ok_or_else(|| {
             let _ = unmark_device_in_pin_flow(&device_id);
             format!("Device not found: {}", device_id)
@@ -3766,8 +4803,7 @@ pub async fn trigger_pin_request(
     mark_device_in_pin_flow(&device_id)?;
     
     // Get device queue handle
-    let queue_manager_guard = queue_manager.lock().await;
-    let queue_handle = queue_manager_guard.get(&device_id)
+    let queue_handle = queue_manager.get_or_spawn_by_id(&device_id).await
         .ok_or_else(|| {
             // Clean up PIN flow marking on error
             let _ = unmark_device_in_pin_flow(&device_id);
@@ -3845,6 +4881,8 @@ pub async fn test_bootloader_mode_device_status() -> Result<String, String> {
         wipe_code_protection: false,
         auto_lock_delay_ms: None,
         policies: vec![],
+        flags: 0,
+        networks: Vec::new(),
     };
     
     // Test the evaluation
@@ -3898,6 +4936,8 @@ pub async fn test_oob_device_status_evaluation() -> Result<String, String> {
         wipe_code_protection: false,
         auto_lock_delay_ms: None,
         policies: vec![],
+        flags: 0,
+        networks: Vec::new(),
     };
     
     // Test the evaluation
@@ -3959,10 +4999,7 @@ pub async fn check_device_pin_ready(
             }
             
             // Check if we can communicate with the device
-            let queue_handle = {
-                let manager = queue_manager.lock().await;
-                manager.get(&device_id).cloned()
-            };
+            let queue_handle = queue_manager.get_or_spawn_by_id(&device_id).await;
             
             if queue_handle.is_none() {
                 log::info!("No queue handle available for device {}", device_id);
@@ -3982,16 +5019,16 @@ pub async fn check_device_pin_ready(
 /// Clear all device-related caches (used for backend restart)
 pub async fn clear_all_device_caches() {
     // Clear PIN flow devices
-    if let Ok(mut pin_flows) = DEVICE_PIN_FLOWS.lock() {
-        println!("  📋 Clearing {} device PIN flow(s)", pin_flows.len());
-        pin_flows.clear();
-    }
+    let pin_flows = std::mem::take(&mut *lock_or_recover(&DEVICE_PIN_FLOWS, "PIN flows"));
+    println!("  📋 Clearing {} device PIN flow(s)", pin_flows.len());
     
     // Clear PIN sessions
-    if let Ok(mut pin_sessions) = PIN_SESSIONS.lock() {
-        println!("  📋 Clearing {} PIN session(s)", pin_sessions.len());
-        pin_sessions.clear();
-    }
+    let pin_sessions = std::mem::take(&mut *lock_or_recover(&PIN_SESSIONS, "PIN sessions"));
+    println!("  📋 Clearing {} PIN session(s)", pin_sessions.len());
+    
+    // Clear cached features
+    let feature_cache = std::mem::take(&mut *lock_or_recover(&FEATURE_CACHE, "feature cache"));
+    println!("  📋 Clearing {} cached feature set(s)", feature_cache.len());
     
     // Clear frontend ready state and queued events
     let mut state = FRONTEND_READY_STATE.write().await;
@@ -4001,4 +5038,52 @@ pub async fn clear_all_device_caches() {
     drop(state); // Explicitly drop to release the lock
     
     println!("  ✅ All device caches cleared");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firmware(version: &str, bootloader_mode: bool) -> DeviceFeatures {
+        let mut features = convert_features_to_device_features(keepkey_rust::messages::Features::default());
+        features.version = version.to_string();
+        features.bootloader_mode = bootloader_mode;
+        features
+    }
+
+    #[test]
+    fn test_firmware_below_minimum() {
+        let required = semver::Version::new(7, 7, 0);
+        assert!(firmware_below_minimum("7.6.9", &required));
+        assert!(!firmware_below_minimum("7.7.0", &required));
+        assert!(!firmware_below_minimum("7.10.0", &required));
+        // What a device in bootloader mode or without features reports
+        assert!(firmware_below_minimum("bootloader mode", &required));
+        assert!(firmware_below_minimum("Unknown", &required));
+    }
+
+    #[test]
+    fn test_firmware_policy_gate() {
+        let required = semver::Version::new(7, 7, 0);
+        assert_eq!(check_firmware_policy(Some(&firmware("7.10.0", false)), Some(&required)), Ok(()));
+        assert_eq!(
+            check_firmware_policy(Some(&firmware("7.6.0", false)), Some(&required)),
+            Err(PolicyError::FirmwareTooOld { current: "7.6.0".to_string(), required: "7.7.0".to_string() })
+        );
+        // The version a bootloader reports is its own, not the firmware's
+        assert!(check_firmware_policy(Some(&firmware("7.10.0", true)), Some(&required)).is_err());
+        assert!(check_firmware_policy(None, Some(&required)).is_err());
+        // Policy off
+        assert_eq!(check_firmware_policy(Some(&firmware("6.0.0", false)), None), Ok(()));
+        assert_eq!(check_firmware_policy(None, None), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_min_firmware_policy() {
+        let policy = |value: serde_json::Value| parse_min_firmware_policy(&serde_json::json!({ MIN_FIRMWARE_POLICY_KEY: value }));
+        assert_eq!(policy(serde_json::json!("7.7.0")), Some(semver::Version::new(7, 7, 0)));
+        assert_eq!(policy(serde_json::json!(" ")), None);
+        assert_eq!(policy(serde_json::json!("seven")), None);
+        assert_eq!(parse_min_firmware_policy(&serde_json::json!({})), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! BIP-380 output descriptors of single-sig accounts, for watch-only wallets in
+//! Bitcoin Core (`importdescriptors`) and other descriptor-based software.
+//!
+//! Keys are always written as `xpub`/`tpub`: descriptors carry the script type
+//! themselves and Core rejects SLIP-132 `ypub`/`zpub` keys.
+
+use bitcoin::bip32::ExtendedPubKey;
+use bitcoin::Network;
+use keepkey_rust::messages::{self, Message};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::network::NetworkMode;
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+
+/// Receive and change descriptors of one account, each with its checksum
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletDescriptors {
+    /// Receive chain, `.../0/*`
+    pub external: String,
+    /// Change chain, `.../1/*`
+    pub internal: String,
+}
+
+fn polymod(c: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd];
+    let top = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (i, generator) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 != 0 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+/// BIP-380 checksum of a descriptor without its `#...` suffix; `None` if it
+/// has characters descriptors can't contain
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch)? as u64;
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Some((0..8).map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char).collect())
+}
+
+/// `descriptor#checksum`
+pub fn with_checksum(descriptor: &str) -> Result<String, String> {
+    let checksum = descriptor_checksum(descriptor).ok_or_else(|| format!("Invalid character in descriptor {}", descriptor))?;
+    Ok(format!("{}#{}", descriptor, checksum))
+}
+
+/// Whether a `descriptor#checksum` string carries the right checksum
+pub fn verify_checksum(descriptor: &str) -> bool {
+    match descriptor.rsplit_once('#') {
+        Some((body, checksum)) => descriptor_checksum(body).is_some_and(|expected| expected == checksum),
+        None => false,
+    }
+}
+
+/// `xpub`, `ypub`, `zpub` (or testnet equivalents) as a key for `mode`
+fn parse_account_key(key: &str, mode: NetworkMode) -> Result<ExtendedPubKey, String> {
+    let mut data = bitcoin::base58::decode_check(key).map_err(|e| format!("Invalid extended public key: {}", e))?;
+    if data.len() != 78 {
+        return Err("Invalid extended public key length".to_string());
+    }
+    // The version only says which network and script type the key is for;
+    // the descriptor states both, so decode it as a plain xpub
+    data[..4].copy_from_slice(&XPUB_VERSION);
+    let mut key = ExtendedPubKey::decode(&data).map_err(|e| format!("Invalid extended public key: {}", e))?;
+    key.network = match mode {
+        NetworkMode::Mainnet => Network::Bitcoin,
+        NetworkMode::Testnet | NetworkMode::Regtest => Network::Testnet,
+    };
+    Ok(key)
+}
+
+/// Descriptors of the account at `path` (`m/purpose'/coin'/account'`) with
+/// key `account_xpub`, from the wallet with master fingerprint `fingerprint`
+pub fn account_descriptors(
+    script_type: &str,
+    fingerprint: &str,
+    path: &[u32],
+    account_xpub: &str,
+    mode: NetworkMode,
+) -> Result<WalletDescriptors, String> {
+    let key = parse_account_key(account_xpub, mode)?;
+    if key.depth as usize != path.len() {
+        return Err(format!("Account key has depth {} but the path has {} levels", key.depth, path.len()));
+    }
+    let origin: Vec<String> = path
+        .iter()
+        .map(|i| if i & 0x8000_0000 != 0 { format!("{}'", i & !0x8000_0000) } else { i.to_string() })
+        .collect();
+    let (open, close) = match script_type {
+        "p2pkh" => ("pkh(", ")"),
+        "p2sh-p2wpkh" => ("sh(wpkh(", "))"),
+        "p2wpkh" => ("wpkh(", ")"),
+        other => return Err(format!("Unsupported script type for descriptors: {}", other)),
+    };
+    let chain = |index: u32| {
+        with_checksum(&format!("{}[{}/{}]{}/{}/*{}", open, fingerprint, origin.join("/"), key, index, close))
+    };
+    Ok(WalletDescriptors { external: chain(0)?, internal: chain(1)? })
+}
+
+/// Receive and change descriptors of `account` for `script_type` (`p2pkh`,
+/// `p2sh-p2wpkh` or `p2wpkh`) on the current network, ready for Bitcoin Core's
+/// `importdescriptors`
+#[tauri::command]
+pub async fn export_descriptors(
+    unique_id: String,
+    account: u32,
+    script_type: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<WalletDescriptors, String> {
+    let mode = crate::commands::network_mode();
+    let path = crate::chain::discovery::account_path(&script_type, mode, account)?;
+    let fingerprint = crate::commands::master_fingerprint_hex(&unique_id, &queue_manager).await?;
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    let response = queue_handle
+        .send_raw(
+            messages::GetPublicKey {
+                address_n: path.clone(),
+                coin_name: Some(mode.coin_name().to_string()),
+                show_display: Some(false),
+                ..Default::default()
+            }
+            .into(),
+            false,
+        )
+        .await
+        .map_err(|e| format!("Failed to get account xpub: {}", e))?;
+    let xpub = match response {
+        Message::PublicKey(public_key) => public_key
+            .xpub
+            .filter(|xpub| !xpub.is_empty())
+            .ok_or_else(|| "Device returned empty xpub".to_string())?,
+        Message::Failure(failure) => return Err(format!("Device returned error: {}", failure.message.unwrap_or_default())),
+        _ => return Err("Unexpected response from device for xpub request".to_string()),
+    };
+
+    let descriptors = account_descriptors(&script_type, &fingerprint, &path, &xpub, mode)?;
+    println!("📜 Exported {} account {} descriptors of {}", script_type, account, unique_id);
+    Ok(descriptors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Account keys of the "abandon ... about" test mnemonic
+    const FINGERPRINT: &str = "73c5da0a";
+    const BIP44_XPUB: &str = "xpub6BosfCnifzxcFwrSzQiqu2DBVTshkCXacvNsWGYJVVhhawA7d4R5WSWGFNbi8Aw6ZRc1brxMyWMzG3DSSSSoekkudhUd9yLb6qx39T9nMdj";
+    const BIP49_XPUB: &str = "xpub6C6nQwHaWbSrzs5tZ1q7m5R9cPK9eYpNMFesiXsYrgc1P8bvLLAet9JfHjYXKjToD8cBRswJXXbbFpXgwsswVPAZzKMa1jUp2kVkGVUaJa7";
+    const BIP84_XPUB: &str = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+    const BIP84_ZPUB: &str = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+    const BIP84_TESTNET_TPUB: &str = "tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M";
+
+    fn descriptors(script_type: &str, xpub: &str, mode: NetworkMode) -> WalletDescriptors {
+        let path = crate::chain::discovery::account_path(script_type, mode, 0).unwrap();
+        account_descriptors(script_type, FINGERPRINT, &path, xpub, mode).unwrap()
+    }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        // BIP-380 and Bitcoin Core's doc/descriptors.md
+        assert_eq!(descriptor_checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+        assert_eq!(
+            with_checksum("wpkh([d34db33f/84h/0h/0h]xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*)").unwrap(),
+            "wpkh([d34db33f/84h/0h/0h]xpub6DJ2dNUysrn5Vt36jH2KLBT2i1auw1tTSSomg8PhqNiUtx8QX2SvC9nrHu81fT41fvDUnhMjEzQgXnQjKEu3oaqMSzhSrHMxyyoEAmUHQbY/0/*)#cjjspncu"
+        );
+        assert!(verify_checksum("raw(deadbeef)#89f8spxm"));
+        assert!(!verify_checksum("raw(deadbeef)#89f8spxn"));
+        assert!(!verify_checksum("raw(deadbeef)"));
+        assert_eq!(descriptor_checksum("raw(deadbeef)\u{e9}"), None);
+    }
+
+    #[test]
+    fn test_single_sig_descriptors() {
+        assert_eq!(
+            descriptors("p2pkh", BIP44_XPUB, NetworkMode::Mainnet),
+            WalletDescriptors {
+                external: format!("pkh([73c5da0a/44'/0'/0']{}/0/*)#8w4z8fed", BIP44_XPUB),
+                internal: format!("pkh([73c5da0a/44'/0'/0']{}/1/*)#k6sr6uf4", BIP44_XPUB),
+            }
+        );
+        assert_eq!(
+            descriptors("p2sh-p2wpkh", BIP49_XPUB, NetworkMode::Mainnet),
+            WalletDescriptors {
+                external: format!("sh(wpkh([73c5da0a/49'/0'/0']{}/0/*))#gvfpdstz", BIP49_XPUB),
+                internal: format!("sh(wpkh([73c5da0a/49'/0'/0']{}/1/*))#ad8h407a", BIP49_XPUB),
+            }
+        );
+        let native = WalletDescriptors {
+            external: format!("wpkh([73c5da0a/84'/0'/0']{}/0/*)#wc3n3van", BIP84_XPUB),
+            internal: format!("wpkh([73c5da0a/84'/0'/0']{}/1/*)#lv5jvedt", BIP84_XPUB),
+        };
+        assert_eq!(descriptors("p2wpkh", BIP84_XPUB, NetworkMode::Mainnet), native);
+        // A SLIP-132 key is written as the xpub Core expects
+        assert_eq!(descriptors("p2wpkh", BIP84_ZPUB, NetworkMode::Mainnet), native);
+
+        assert_eq!(
+            descriptors("p2wpkh", BIP84_TESTNET_TPUB, NetworkMode::Testnet).external,
+            format!("wpkh([73c5da0a/84'/1'/0']{}/0/*)#2ag6nxcd", BIP84_TESTNET_TPUB)
+        );
+        for descriptor in [&native.external, &native.internal] {
+            assert!(verify_checksum(descriptor));
+        }
+    }
+
+    #[test]
+    fn test_rejects_mismatched_keys() {
+        let path = crate::chain::discovery::account_path("p2wpkh", NetworkMode::Mainnet, 0).unwrap();
+        assert!(account_descriptors("p2tr", FINGERPRINT, &path, BIP84_XPUB, NetworkMode::Mainnet).is_err());
+        assert!(account_descriptors("p2wpkh", FINGERPRINT, &path[..2], BIP84_XPUB, NetworkMode::Mainnet).is_err());
+        assert!(account_descriptors("p2wpkh", FINGERPRINT, &path, "xpub-not-base58", NetworkMode::Mainnet).is_err());
+    }
+}
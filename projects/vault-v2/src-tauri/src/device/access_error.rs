@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+/// Why a device couldn't be opened, sent as `errorType` on `device:access-error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AccessErrorKind {
+    /// Another application holds the device
+    DeviceClaimed,
+    /// The OS refused to open the device for this user
+    PermissionDenied,
+    /// No usable driver is bound to the device
+    DriverMissing,
+    /// The device went away or reset while it was being opened
+    TransportReset,
+    /// The device didn't answer while it was being opened
+    Timeout,
+}
+
+/// One step the user can take to resolve an access error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hint {
+    /// Stable identifier the frontend can key translations or icons on
+    pub id: String,
+    pub title: String,
+    pub detail: String,
+    /// Shell command that applies the fix, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl Hint {
+    fn new(id: &str, title: &str, detail: &str) -> Self {
+        Self { id: id.to_string(), title: title.to_string(), detail: detail.to_string(), command: None }
+    }
+
+    fn with_command(mut self, command: &str) -> Self {
+        self.command = Some(command.to_string());
+        self
+    }
+}
+
+/// Markers the transports put in errors for devices they couldn't open
+fn is_access_failure(error: &str) -> bool {
+    error.contains("Device Already In Use")
+        || error.contains("already claimed")
+        || error.contains("Device Access Failed")
+        || error.contains("🔒")
+}
+
+/// Classify an error from opening or probing a device. `None` for errors that
+/// aren't about getting access to the device.
+pub fn classify(error: &str) -> Option<AccessErrorKind> {
+    let lower = error.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+
+    // The transport's "already in use" message wraps the OS error, so look at
+    // the specific causes before settling on a claimed device
+    if has(&["permission denied", "access denied", "insufficient permissions", "0x00000005"]) {
+        return Some(AccessErrorKind::PermissionDenied);
+    }
+    if has(&["driver", "not supported", "entity not found"]) {
+        return Some(AccessErrorKind::DriverMissing);
+    }
+    if !is_access_failure(error) {
+        return None;
+    }
+    if has(&["pipe error", "no such device", "device has been disconnected", "reset"]) {
+        return Some(AccessErrorKind::TransportReset);
+    }
+    if has(&["timed out", "timeout"]) {
+        return Some(AccessErrorKind::Timeout);
+    }
+    Some(AccessErrorKind::DeviceClaimed)
+}
+
+/// Steps the frontend shows for `kind`, most likely fix first
+pub fn hints(kind: AccessErrorKind) -> Vec<Hint> {
+    let reconnect = Hint::new("reconnect", "Reconnect your KeepKey", "Unplug the device, wait a few seconds and plug it back in.");
+    match kind {
+        AccessErrorKind::DeviceClaimed => vec![
+            Hint::new(
+                "close-other-apps",
+                "Close other wallet applications",
+                "KeepKey Desktop, KeepKey Bridge or another wallet may be connected to the device.",
+            ),
+            reconnect,
+        ],
+        AccessErrorKind::PermissionDenied => {
+            let mut hints = Vec::new();
+            if cfg!(target_os = "linux") {
+                hints.push(
+                    Hint::new(
+                        "install-udev-rules",
+                        "Install the KeepKey udev rules",
+                        "Linux only lets root open USB devices without a udev rule granting access. Install it, then reconnect the device.",
+                    )
+                    .with_command(&crate::device::udev::install_command()),
+                );
+            }
+            if cfg!(target_os = "windows") {
+                hints.push(Hint::new(
+                    "windows-fido-filter",
+                    "Use the HID connection",
+                    "Windows may block direct USB access to security keys. Reconnecting lets the app fall back to HID.",
+                ));
+            }
+            hints.push(Hint::new(
+                "close-other-apps",
+                "Close other wallet applications",
+                "Some systems report a device held by another application as access denied.",
+            ));
+            hints.push(reconnect);
+            hints
+        }
+        AccessErrorKind::DriverMissing => vec![
+            Hint::new(
+                "check-driver",
+                "Check the USB driver",
+                "Make sure no other driver (e.g. one installed by Zadig) is bound to the KeepKey, then reconnect it.",
+            ),
+            reconnect,
+        ],
+        AccessErrorKind::TransportReset => vec![
+            Hint::new("check-cable", "Check the cable", "Connect the KeepKey directly to the computer instead of through a hub."),
+            reconnect,
+        ],
+        AccessErrorKind::Timeout => vec![
+            reconnect,
+            Hint::new("check-cable", "Check the cable", "Connect the KeepKey directly to the computer instead of through a hub."),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_use(details: &str) -> String {
+        format!("🔒 KeepKey Device Already In Use\n\nSolutions:\n1. Close KeepKey Desktop app completely\n\nTechnical details: {}", details)
+    }
+
+    #[test]
+    fn test_classify_access_errors() {
+        assert_eq!(classify(&in_use("hidapi error: device busy")), Some(AccessErrorKind::DeviceClaimed));
+        assert_eq!(classify("USB device already claimed"), Some(AccessErrorKind::DeviceClaimed));
+        assert_eq!(classify(&in_use("Permission denied (os error 13)")), Some(AccessErrorKind::PermissionDenied));
+        assert_eq!(classify("Access denied (insufficient permissions)"), Some(AccessErrorKind::PermissionDenied));
+        assert_eq!(classify("Operation not supported or unimplemented on this platform"), Some(AccessErrorKind::DriverMissing));
+        assert_eq!(classify(&in_use("Pipe error")), Some(AccessErrorKind::TransportReset));
+        assert_eq!(classify(&in_use("Operation timed out")), Some(AccessErrorKind::Timeout));
+        // Disconnects and timeouts outside of opening the device aren't access errors
+        assert_eq!(classify("No such device (it may have been disconnected)"), None);
+        assert_eq!(classify("Timeout while fetching device features"), None);
+    }
+
+    #[test]
+    fn test_hints() {
+        let permission = hints(AccessErrorKind::PermissionDenied);
+        let udev = permission.iter().find(|h| h.id == "install-udev-rules");
+        assert_eq!(udev.is_some(), cfg!(target_os = "linux"));
+        if let Some(udev) = udev {
+            assert!(udev.command.as_deref().unwrap().contains("udevadm"));
+        }
+        assert_eq!(hints(AccessErrorKind::DeviceClaimed)[0].id, "close-other-apps");
+
+        let json = serde_json::to_value(AccessErrorKind::DeviceClaimed).unwrap();
+        assert_eq!(json, "DEVICE_CLAIMED");
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+/// Why the active device changed, reported with `device:active-changed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActiveChangeReason {
+    /// Picked by the user through `set_active_device`
+    Selected,
+    /// The active device disconnected; the UI should ask for a new one
+    Disconnected,
+    /// The device active in an earlier session (matched by serial) reconnected
+    Restored,
+    /// The active device was removed with `forget_device`
+    Forgotten,
+}
+
+/// The device used by operations that don't name one
+#[derive(Debug, Default)]
+pub struct ActiveDevice {
+    current: Option<String>,
+}
+
+impl ActiveDevice {
+    pub fn get(&self) -> Option<String> {
+        self.current.clone()
+    }
+
+    /// Make `device_id` active. Returns the previously active device if this
+    /// changed the selection.
+    pub fn select(&mut self, device_id: &str) -> Option<Option<String>> {
+        if self.current.as_deref() == Some(device_id) {
+            return None;
+        }
+        Some(self.current.replace(device_id.to_string()))
+    }
+
+    /// Clear the selection if `device_id` is the active device
+    pub fn clear_if(&mut self, device_id: &str) -> bool {
+        if self.current.as_deref() != Some(device_id) {
+            return false;
+        }
+        self.current = None;
+        true
+    }
+}
+
+static ACTIVE_DEVICE: once_cell::sync::Lazy<std::sync::Mutex<ActiveDevice>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(ActiveDevice::default()));
+
+fn with_active<T>(f: impl FnOnce(&mut ActiveDevice) -> T) -> T {
+    match ACTIVE_DEVICE.lock() {
+        Ok(mut active) => f(&mut active),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+pub fn active_device() -> Option<String> {
+    with_active(|active| active.get())
+}
+
+pub fn select(device_id: &str) -> Option<Option<String>> {
+    with_active(|active| active.select(device_id))
+}
+
+pub fn clear_if(device_id: &str) -> bool {
+    with_active(|active| active.clear_if(device_id))
+}
+
+/// Payload of `device:active-changed`; `unique_id` is null when no device is active
+pub fn active_changed_payload(
+    unique_id: Option<&str>,
+    previous: Option<&str>,
+    reason: ActiveChangeReason,
+) -> serde_json::Value {
+    serde_json::json!({
+        "unique_id": unique_id,
+        "previous": previous,
+        "reason": reason
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_and_clear() {
+        let mut active = ActiveDevice::default();
+        assert_eq!(active.select("A"), Some(None));
+        // Re-selecting the active device is not a change
+        assert_eq!(active.select("A"), None);
+        assert_eq!(active.select("B"), Some(Some("A".to_string())));
+
+        // Only the active device's disconnect clears the selection
+        assert!(!active.clear_if("A"));
+        assert_eq!(active.get(), Some("B".to_string()));
+        assert!(active.clear_if("B"));
+        assert_eq!(active.get(), None);
+
+        let json = active_changed_payload(None, Some("B"), ActiveChangeReason::Disconnected);
+        assert_eq!(json["unique_id"], serde_json::Value::Null);
+        assert_eq!(json["previous"], "B");
+        assert_eq!(json["reason"], "disconnected");
+    }
+}
@@ -0,0 +1,220 @@
+use keepkey_rust::features::DeviceFeatures;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use tauri::AppHandle;
+
+use crate::commands::{DeviceStatus, UpdateSeverity};
+
+/// Something the user has to act on before a device is fully usable; drives
+/// the notification badge through `device:needs-attention`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttentionReason {
+    /// Firmware must be installed: missing (bootloader mode) or below the configured minimum
+    FirmwareUpdate,
+    BootloaderUpdate,
+    /// The firmware requires a newer bootloader than the one installed
+    FirmwareBootloaderMismatch,
+    /// Never set up, or set up without a recovery backup
+    NotInitialized,
+    /// PIN protected and not unlocked
+    Locked,
+    /// Connected but not answering feature probes
+    Unreachable,
+    /// A newer firmware is available but not required
+    UpdateAvailable,
+}
+
+/// Reasons a probed device needs attention, from its evaluated status
+pub fn attention_reasons(features: &DeviceFeatures, status: &DeviceStatus) -> Vec<AttentionReason> {
+    let mut reasons = Vec::new();
+    if status.needs_firmware_update {
+        let required = features.bootloader_mode
+            || status.firmware_check.as_ref().and_then(|c| c.severity) == Some(UpdateSeverity::Mandatory);
+        reasons.push(if required { AttentionReason::FirmwareUpdate } else { AttentionReason::UpdateAvailable });
+    }
+    if status.needs_bootloader_update {
+        reasons.push(AttentionReason::BootloaderUpdate);
+    }
+    if status.version_mismatch.is_some() {
+        reasons.push(AttentionReason::FirmwareBootloaderMismatch);
+    }
+    if status.needs_initialization {
+        reasons.push(AttentionReason::NotInitialized);
+    }
+    if features.initialized && features.pin_protection && !features.pin_cached {
+        reasons.push(AttentionReason::Locked);
+    }
+    reasons.sort();
+    reasons
+}
+
+/// Last reasons reported per device, so each change is reported once
+#[derive(Debug, Default)]
+pub struct AttentionTracker {
+    reported: HashMap<String, BTreeSet<AttentionReason>>,
+}
+
+impl AttentionTracker {
+    /// Record the device's current reasons. Returns them if they differ from the
+    /// last report; an empty list is returned when a device stops needing attention.
+    pub fn update(&mut self, device_id: &str, reasons: &[AttentionReason]) -> Option<Vec<AttentionReason>> {
+        let current: BTreeSet<AttentionReason> = reasons.iter().copied().collect();
+        let previous = self.reported.get(device_id);
+        if previous.map_or(current.is_empty(), |previous| *previous == current) {
+            return None;
+        }
+
+        if current.is_empty() {
+            self.reported.remove(device_id);
+        } else {
+            self.reported.insert(device_id.to_string(), current.clone());
+        }
+        Some(current.into_iter().collect())
+    }
+
+    /// Forget a disconnected device; `device:disconnected` already clears its badge
+    pub fn remove(&mut self, device_id: &str) {
+        self.reported.remove(device_id);
+    }
+}
+
+static ATTENTION: once_cell::sync::Lazy<std::sync::Mutex<AttentionTracker>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(AttentionTracker::default()));
+
+/// Apply `reasons` to the app-wide tracker, returning them if they changed
+pub fn update(device_id: &str, reasons: &[AttentionReason]) -> Option<Vec<AttentionReason>> {
+    match ATTENTION.lock() {
+        Ok(mut tracker) => tracker.update(device_id, reasons),
+        Err(poisoned) => poisoned.into_inner().update(device_id, reasons),
+    }
+}
+
+pub fn forget(device_id: &str) {
+    match ATTENTION.lock() {
+        Ok(mut tracker) => tracker.remove(device_id),
+        Err(poisoned) => poisoned.into_inner().remove(device_id),
+    }
+}
+
+/// Payload of `device:needs-attention`
+pub fn needs_attention_payload(device_id: &str, reasons: &[AttentionReason]) -> serde_json::Value {
+    serde_json::json!({
+        "unique_id": device_id,
+        "reasons": reasons
+    })
+}
+
+/// Report attention changes for features fetched outside the monitor (e.g. by
+/// `get_device_info_by_id` after a PIN unlock)
+pub async fn refresh(app: &AppHandle, device_id: &str, features: &DeviceFeatures, status: &DeviceStatus) {
+    let Some(reasons) = update(device_id, &attention_reasons(features, status)) else {
+        return;
+    };
+    if let Err(e) = crate::commands::emit_or_queue_event(app, "device:needs-attention", needs_attention_payload(device_id, &reasons)).await {
+        eprintln!("Failed to emit device:needs-attention: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AttentionReason::*;
+
+    fn features(initialized: bool, pin_cached: bool) -> DeviceFeatures {
+        crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features {
+            initialized: Some(initialized),
+            pin_protection: Some(true),
+            pin_cached: Some(pin_cached),
+            major_version: Some(7),
+            minor_version: Some(9),
+            patch_version: Some(0),
+            ..Default::default()
+        })
+    }
+
+    fn status(features: &DeviceFeatures, firmware_update: Option<UpdateSeverity>) -> DeviceStatus {
+        DeviceStatus {
+            device_id: "A".to_string(),
+            connected: true,
+            features: Some(features.clone()),
+            needs_bootloader_update: false,
+            needs_firmware_update: firmware_update.is_some(),
+            needs_initialization: !features.initialized,
+            needs_pin_unlock: false,
+            bootloader_check: None,
+            firmware_check: Some(crate::commands::FirmwareCheck {
+                current_version: "7.9.0".to_string(),
+                latest_version: "7.10.0".to_string(),
+                needs_update: firmware_update.is_some(),
+                severity: firmware_update,
+                required_version: None,
+                release_notes: None,
+            }),
+            initialization_check: None,
+            version_mismatch: None,
+        }
+    }
+
+    #[test]
+    fn test_reasons_from_status() {
+        let ready = features(true, true);
+        assert_eq!(attention_reasons(&ready, &status(&ready, None)), Vec::<AttentionReason>::new());
+        assert_eq!(attention_reasons(&ready, &status(&ready, Some(UpdateSeverity::Recommended))), vec![UpdateAvailable]);
+        assert_eq!(attention_reasons(&ready, &status(&ready, Some(UpdateSeverity::Mandatory))), vec![FirmwareUpdate]);
+
+        let locked = features(true, false);
+        assert_eq!(attention_reasons(&locked, &status(&locked, None)), vec![Locked]);
+
+        let blank = features(false, false);
+        assert_eq!(attention_reasons(&blank, &status(&blank, None)), vec![NotInitialized]);
+
+        let mut bootloader = features(true, true);
+        bootloader.bootloader_mode = true;
+        let mut bootloader_status = status(&bootloader, Some(UpdateSeverity::Recommended));
+        bootloader_status.needs_bootloader_update = true;
+        assert_eq!(attention_reasons(&bootloader, &bootloader_status), vec![FirmwareUpdate, BootloaderUpdate]);
+    }
+
+    #[test]
+    fn test_firmware_on_old_bootloader() {
+        // 7.9.0 left on bootloader 1.0.3 by an interrupted update
+        let mut stranded = features(true, true);
+        stranded.bootloader_hash = Some("cb222548a39ff6cbe2ae2f02c8d431c9ae0df850f814444911f521b95ab02f4c".to_string());
+        let status = crate::commands::evaluate_device_status("A".to_string(), Some(&stranded));
+        let mismatch = status.version_mismatch.clone().unwrap();
+        assert_eq!((mismatch.bootloader.as_str(), mismatch.required_bootloader.as_str()), ("1.0.3", "2.0.0"));
+        assert!(status.needs_bootloader_update);
+        assert!(attention_reasons(&stranded, &status).contains(&FirmwareBootloaderMismatch));
+
+        stranded.bootloader_hash = Some("fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb".to_string());
+        let status = crate::commands::evaluate_device_status("A".to_string(), Some(&stranded));
+        assert_eq!(status.version_mismatch, None);
+    }
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let mut tracker = AttentionTracker::default();
+
+        // Nothing to report for a device that never needed attention
+        assert_eq!(tracker.update("A", &[]), None);
+
+        assert_eq!(tracker.update("A", &[Locked, UpdateAvailable]), Some(vec![Locked, UpdateAvailable]));
+        // Same set in a different order is not a change
+        assert_eq!(tracker.update("A", &[UpdateAvailable, Locked]), None);
+
+        assert_eq!(tracker.update("A", &[UpdateAvailable]), Some(vec![UpdateAvailable]));
+        // Cleared: reported once with no reasons
+        assert_eq!(tracker.update("A", &[]), Some(vec![]));
+        assert_eq!(tracker.update("A", &[]), None);
+
+        // Reconnecting starts over
+        tracker.update("A", &[Unreachable]);
+        tracker.remove("A");
+        assert_eq!(tracker.update("A", &[Unreachable]), Some(vec![Unreachable]));
+
+        let json = serde_json::to_value(needs_attention_payload("A", &[FirmwareUpdate, NotInitialized])).unwrap();
+        assert_eq!(json["unique_id"], "A");
+        assert_eq!(json["reasons"], serde_json::json!(["firmware_update", "not_initialized"]));
+    }
+}
@@ -0,0 +1,146 @@
+use keepkey_rust::messages::{Message, Ping};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::lru::DeviceLru;
+use crate::logging::{log_device_request, log_device_response};
+
+/// Round-trips with an empty ping, used for latency
+const LATENCY_ROUNDS: usize = 10;
+/// Round-trips with a full ping payload, used for throughput
+const THROUGHPUT_ROUNDS: usize = 10;
+/// Largest message the firmware echoes back in a Ping
+const PING_PAYLOAD_SIZE: usize = 256;
+
+/// Below this a full firmware upload takes several minutes - almost always a
+/// bad cable or hub rather than the device
+pub const LOW_THROUGHPUT_BYTES_PER_SEC: f64 = 2_000.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IoBenchmark {
+    pub device_id: String,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: f64,
+    pub throughput_bytes_per_sec: f64,
+    pub round_trips: usize,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Last benchmark per device, included in the support bundle
+static LAST_BENCHMARKS: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<IoBenchmark>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+pub fn last_benchmarks() -> HashMap<String, IoBenchmark> {
+    crate::commands::lock_or_recover(&LAST_BENCHMARKS, "benchmarks").to_map()
+}
+
+pub fn forget(device_id: &str) {
+    crate::commands::lock_or_recover(&LAST_BENCHMARKS, "benchmarks").remove(device_id);
+}
+
+pub(crate) fn ping(message: String) -> Message {
+    Message::Ping(Ping {
+        message: Some(message),
+        button_protection: Some(false),
+        pin_protection: Some(false),
+        passphrase_protection: Some(false),
+        wipe_code_protection: Some(false),
+    })
+}
+
+async fn round_trip(queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle, payload: &str) -> Result<Duration, String> {
+    let started = Instant::now();
+    match queue_handle.send_raw(ping(payload.to_string()), true).await {
+        Ok(Message::Success(_)) => Ok(started.elapsed()),
+        Ok(Message::Failure(failure)) => Err(format!("Device rejected ping: {}", failure.message.unwrap_or_default())),
+        Ok(other) => Err(format!("Unexpected response to ping: {:?}", other.message_type())),
+        Err(e) => Err(format!("Ping failed: {}", e)),
+    }
+}
+
+/// Measure USB latency and throughput with Ping round-trips through the device queue.
+///
+/// Ping doesn't touch device storage and needs no button press (GetEntropy asks
+/// for confirmation on KeepKey, so it isn't used). Emits `device:io-warning` when
+/// throughput is below `LOW_THROUGHPUT_BYTES_PER_SEC`.
+#[tauri::command]
+pub async fn benchmark_device_io(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<IoBenchmark, String> {
+    println!("⏱️ Benchmarking USB I/O for device: {}", device_id);
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let request_data = serde_json::json!({
+        "device_id": device_id,
+        "operation": "benchmark_device_io"
+    });
+    if let Err(e) = log_device_request(&device_id, &request_id, "BenchmarkDeviceIo", &request_data).await {
+        eprintln!("Failed to log benchmark request: {}", e);
+    }
+
+    let result = run_benchmark(&device_id, &queue_manager).await;
+
+    let response_data = match &result {
+        Ok(benchmark) => serde_json::json!({ "benchmark": benchmark, "operation": "benchmark_device_io" }),
+        Err(e) => serde_json::json!({ "error": e, "operation": "benchmark_device_io" }),
+    };
+    if let Err(e) = log_device_response(&device_id, &request_id, result.is_ok(), &response_data, result.as_ref().err().map(|e| e.as_str())).await {
+        eprintln!("Failed to log benchmark response: {}", e);
+    }
+
+    let benchmark = result?;
+    println!(
+        "⏱️ Device {}: avg latency {:.1}ms, throughput {:.0} B/s",
+        device_id, benchmark.avg_latency_ms, benchmark.throughput_bytes_per_sec
+    );
+
+    crate::commands::lock_or_recover(&LAST_BENCHMARKS, "benchmarks").insert(&device_id, benchmark.clone());
+
+    if benchmark.throughput_bytes_per_sec < LOW_THROUGHPUT_BYTES_PER_SEC {
+        println!("⚠️ Low USB throughput for device {}", device_id);
+        let _ = app.emit("device:io-warning", serde_json::json!({
+            "deviceId": device_id,
+            "throughputBytesPerSec": benchmark.throughput_bytes_per_sec,
+            "thresholdBytesPerSec": LOW_THROUGHPUT_BYTES_PER_SEC,
+            "message": "USB connection is very slow. Try a different cable or plug the KeepKey directly into your computer instead of a hub."
+        }));
+    }
+
+    Ok(benchmark)
+}
+
+async fn run_benchmark(device_id: &str, queue_manager: &DeviceQueueManager) -> Result<IoBenchmark, String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    let mut latencies = Vec::with_capacity(LATENCY_ROUNDS);
+    for _ in 0..LATENCY_ROUNDS {
+        latencies.push(round_trip(&queue_handle, "").await?);
+    }
+
+    // The payload goes out and is echoed back, so each round moves it twice
+    let payload = "k".repeat(PING_PAYLOAD_SIZE);
+    let mut transfer_time = Duration::ZERO;
+    for _ in 0..THROUGHPUT_ROUNDS {
+        transfer_time += round_trip(&queue_handle, &payload).await?;
+    }
+    let bytes_moved = (PING_PAYLOAD_SIZE * 2 * THROUGHPUT_ROUNDS) as f64;
+
+    let to_ms = |d: &Duration| d.as_secs_f64() * 1000.0;
+    Ok(IoBenchmark {
+        device_id: device_id.to_string(),
+        avg_latency_ms: latencies.iter().map(to_ms).sum::<f64>() / latencies.len() as f64,
+        max_latency_ms: latencies.iter().map(to_ms).fold(0.0, f64::max),
+        throughput_bytes_per_sec: bytes_moved / transfer_time.as_secs_f64().max(f64::EPSILON),
+        round_trips: LATENCY_ROUNDS + THROUGHPUT_ROUNDS,
+        timestamp: chrono::Utc::now(),
+    })
+}
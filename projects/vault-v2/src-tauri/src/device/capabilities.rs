@@ -0,0 +1,132 @@
+//! Ask ahead whether a device can do an operation, so the frontend can disable
+//! what its firmware doesn't have instead of finding out from an
+//! "Unknown message" failure. Answers come from the worker's capability table
+//! in `keepkey_rust::protocol`.
+
+use keepkey_rust::features::flags::{self, FirmwareFlags, FlagStatus};
+use keepkey_rust::features::DeviceFeatures;
+use keepkey_rust::protocol::{DeviceOperation, ProtocolVersion, Support};
+use serde::Serialize;
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// Support of `operation` on a device with `features`; `Unknown` when the
+/// reported version can't be read
+pub fn operation_support(features: &DeviceFeatures, operation: DeviceOperation) -> Support {
+    match ProtocolVersion::from_device_features(features) {
+        Some(version) => version.capabilities().supports(operation),
+        None => Support::Unknown,
+    }
+}
+
+/// Cached features of `unique_id`, fetched if there are none
+async fn device_features(unique_id: &str, queue_manager: &DeviceQueueManager) -> Result<DeviceFeatures, String> {
+    if let Some(features) = crate::commands::cached_device_features(unique_id) {
+        return Ok(features);
+    }
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    let features = queue_handle
+        .get_features()
+        .await
+        .map(crate::commands::convert_features_to_device_features)
+        .map_err(|e| format!("Failed to get features for device {}: {}", unique_id, e))?;
+    crate::commands::cache_device_features(unique_id, &features);
+    Ok(features)
+}
+
+/// Whether device `unique_id` supports `op`, from its cached features (fetched
+/// if there are none). No message for `op` itself is sent.
+#[tauri::command]
+pub async fn supports_operation(
+    unique_id: String,
+    op: DeviceOperation,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Support, String> {
+    let features = device_features(&unique_id, &queue_manager).await?;
+    Ok(operation_support(&features, op))
+}
+
+/// The device's flags, read side of `apply_flags`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FirmwareFlagsReport {
+    /// The bit set `apply_flags` takes
+    pub bits: u32,
+    pub enabled: FirmwareFlags,
+    /// Every known flag, with whether this firmware can turn it on
+    pub flags: Vec<FlagStatus>,
+}
+
+pub fn firmware_flags_report(features: &DeviceFeatures) -> FirmwareFlagsReport {
+    // A firmware without ApplyPolicies (or in bootloader mode) can't change any
+    let supported = match operation_support(features, DeviceOperation::ApplyPolicies) {
+        Support::Unsupported => 0,
+        Support::Supported | Support::Unknown => flags::supported_flags(&features.version),
+    };
+    FirmwareFlagsReport {
+        bits: features.flags,
+        enabled: FirmwareFlags::from_bits(features.flags),
+        flags: flags::flag_statuses(features.flags, supported),
+    }
+}
+
+/// Which optional firmware features (experimental, advanced mode, ...) are on
+/// for device `unique_id`, and which of the rest its firmware supports
+#[tauri::command]
+pub async fn get_firmware_flags(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<FirmwareFlagsReport, String> {
+    let features = device_features(&unique_id, &queue_manager).await?;
+    Ok(firmware_flags_report(&features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(version: &str, bootloader_mode: bool) -> DeviceFeatures {
+        let mut features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features::default());
+        features.version = version.to_string();
+        features.bootloader_mode = bootloader_mode;
+        features
+    }
+
+    #[test]
+    fn test_operation_support_from_features() {
+        assert_eq!(operation_support(&features("5.11.0", false), DeviceOperation::SegwitAddress), Support::Unsupported);
+        assert_eq!(operation_support(&features("7.10.0", false), DeviceOperation::SegwitAddress), Support::Supported);
+        assert_eq!(operation_support(&features("6.0.4", false), DeviceOperation::AutoLockDelay), Support::Unsupported);
+        assert_eq!(operation_support(&features("2.1.4", true), DeviceOperation::SignTransaction), Support::Unsupported);
+        assert_eq!(operation_support(&features("7.10.0", false), DeviceOperation::ChangeWipeCode), Support::Unknown);
+        assert_eq!(operation_support(&features("unknown", false), DeviceOperation::GetAddress), Support::Unknown);
+    }
+
+    #[test]
+    fn test_firmware_flags_report() {
+        use keepkey_rust::features::flags::{FlagState, FLAG_EXPERIMENTAL};
+
+        let mut device = features("6.0.4", false);
+        device.flags = FLAG_EXPERIMENTAL;
+        let report = firmware_flags_report(&device);
+        assert_eq!(report.bits, FLAG_EXPERIMENTAL);
+        assert!(report.enabled.experimental && !report.enabled.advanced_mode);
+        let states: Vec<_> = report.flags.iter().map(|flag| (flag.policy, flag.state)).collect();
+        assert_eq!(
+            states,
+            vec![
+                ("ShapeShift", FlagState::Disabled),
+                ("Pin Caching", FlagState::Disabled),
+                ("Experimental", FlagState::Enabled),
+                ("AdvancedMode", FlagState::Unsupported),
+            ]
+        );
+
+        // Nothing can be toggled from the bootloader
+        let report = firmware_flags_report(&features("2.1.4", true));
+        assert!(report.flags.iter().all(|flag| flag.state == FlagState::Unsupported));
+    }
+}
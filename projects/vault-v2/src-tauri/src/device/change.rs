@@ -0,0 +1,98 @@
+use keepkey_rust::messages::{InputScriptType, OutputScriptType};
+use tauri::State;
+
+use crate::commands::{BitcoinUtxoOutput, DeviceQueueManager, DeviceQueueManagerExt};
+
+/// A change output as the device derives it: signing pays to `path` with
+/// `output_script_type`, and `GetAddress` with `input_script_type` shows the
+/// same address on screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeAddress {
+    pub path: Vec<u32>,
+    pub input_script_type: InputScriptType,
+    pub output_script_type: OutputScriptType,
+}
+
+/// Script types of a change output of `script_type` ("p2pkh" when unset).
+/// Nested segwit ("p2sh" / "p2sh-p2wpkh") pays to p2sh-witness, like the inputs.
+pub fn change_script_types(script_type: Option<&str>) -> (InputScriptType, OutputScriptType) {
+    match script_type.unwrap_or("p2pkh") {
+        "p2sh" | "p2sh-p2wpkh" => (InputScriptType::Spendp2shwitness, OutputScriptType::Paytop2shwitness),
+        "p2wpkh" => (InputScriptType::Spendwitness, OutputScriptType::Paytowitness),
+        _ => (InputScriptType::Spendaddress, OutputScriptType::Paytoaddress),
+    }
+}
+
+/// How the device derives `output`, `None` for outputs that aren't change
+pub fn change_address_for(output: &BitcoinUtxoOutput) -> Option<ChangeAddress> {
+    if output.address_type != "change" {
+        return None;
+    }
+    let (input_script_type, output_script_type) = change_script_types(output.script_type.as_deref());
+    Some(ChangeAddress {
+        path: output.address_n_list.clone().unwrap_or_default(),
+        input_script_type,
+        output_script_type,
+    })
+}
+
+/// Show the change address at `path` on the device so the user can confirm it
+/// belongs to their wallet before signing; returns the address shown
+#[tauri::command]
+pub async fn verify_change_address(
+    unique_id: String,
+    path: String,
+    script_type: String,
+    coin: Option<String>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    let path = crate::commands::parse_derivation_path(&path)?;
+    let coin_name = crate::network::resolve_coin(crate::commands::network_mode(), coin.as_deref(), &path)?;
+    let (input_script_type, _) = change_script_types(Some(&script_type));
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    queue_handle
+        .get_address(path, coin_name, Some(input_script_type as i32), Some(true))
+        .await
+        .map_err(|e| format!("Change address verification failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(address_type: &str, script_type: Option<&str>) -> BitcoinUtxoOutput {
+        BitcoinUtxoOutput {
+            address: String::new(),
+            amount: 1000,
+            address_type: address_type.to_string(),
+            is_change: None,
+            address_n_list: Some(vec![0x80000054, 0x80000000, 0x80000000, 1, 7]),
+            script_type: script_type.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_verified_change_matches_signed_change() {
+        assert_eq!(change_address_for(&output("spend", None)), None);
+
+        // What verify_change_address displays must be what signing pays to
+        for (script_type, input, paid_to) in [
+            ("p2wpkh", InputScriptType::Spendwitness, OutputScriptType::Paytowitness),
+            ("p2sh-p2wpkh", InputScriptType::Spendp2shwitness, OutputScriptType::Paytop2shwitness),
+            ("p2pkh", InputScriptType::Spendaddress, OutputScriptType::Paytoaddress),
+        ] {
+            let change = change_address_for(&output("change", Some(script_type))).unwrap();
+            assert_eq!(change.path, vec![0x80000054, 0x80000000, 0x80000000, 1, 7]);
+            assert_eq!(change_script_types(Some(script_type)), (input, paid_to));
+            assert_eq!((change.input_script_type, change.output_script_type), (input, paid_to));
+
+            let signed = crate::device::queue::build_tx_output(&output("change", Some(script_type)));
+            assert_eq!(signed.address_n, change.path);
+            assert_eq!(signed.script_type, paid_to as i32);
+            assert_eq!(signed.address, None);
+        }
+    }
+}
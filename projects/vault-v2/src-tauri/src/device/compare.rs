@@ -0,0 +1,231 @@
+//! Checking that two devices hold the same seed, e.g. a wallet and its backup
+//! device, by deriving the same public key on both. Nothing is shown on
+//! either device.
+
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::messages::{self, Message};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// A device that couldn't derive the key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnavailableDevice {
+    pub device_id: String,
+    /// The device is PIN locked; unlocking it and comparing again will work
+    pub locked: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CompareDevicesError {
+    InvalidPath { message: String },
+    /// Both ids name the same device, which would trivially match
+    SameDevice,
+    /// Not every device answered; `answered` are the ones that did, so the UI
+    /// can say which device to look at
+    Unavailable { unavailable: Vec<UnavailableDevice>, answered: Vec<String> },
+}
+
+impl std::fmt::Display for CompareDevicesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompareDevicesError::InvalidPath { message } => write!(f, "Invalid derivation path: {}", message),
+            CompareDevicesError::SameDevice => write!(f, "Pick two different devices to compare"),
+            CompareDevicesError::Unavailable { unavailable, .. } => {
+                let devices: Vec<String> = unavailable.iter().map(|d| format!("{} ({})", d.device_id, d.message)).collect();
+                write!(f, "Couldn't read the public key of {}", devices.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompareDevicesError {}
+
+/// Compare without an early exit, so the time taken doesn't reveal how much
+/// of the keys matched
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Chain code and public key of a serialized extended key. The version bytes
+/// are left out so SLIP-132 variants of the same key compare equal.
+fn key_material(xpub: &str) -> Result<Vec<u8>, String> {
+    let data = bitcoin::base58::decode_check(xpub).map_err(|e| format!("Device returned an invalid xpub: {}", e))?;
+    if data.len() != 78 {
+        return Err("Device returned an invalid xpub".to_string());
+    }
+    Ok(data[13..].to_vec())
+}
+
+async fn derive_key(handle: &DeviceQueueHandle, path: &[u32], coin_name: &str) -> Result<Vec<u8>, UnavailableDevice> {
+    let unavailable = |locked: bool, message: String| UnavailableDevice {
+        device_id: handle.device_id().to_string(),
+        locked,
+        message,
+    };
+    let request = messages::GetPublicKey {
+        address_n: path.to_vec(),
+        coin_name: Some(coin_name.to_string()),
+        show_display: Some(false),
+        ..Default::default()
+    };
+    match handle.send_raw(request.into(), false).await {
+        Ok(Message::PublicKey(public_key)) => {
+            let xpub = public_key
+                .xpub
+                .filter(|xpub| !xpub.is_empty())
+                .ok_or_else(|| unavailable(false, "Device returned empty xpub".to_string()))?;
+            key_material(&xpub).map_err(|e| unavailable(false, e))
+        }
+        Ok(Message::PinMatrixRequest(_)) => Err(unavailable(true, "Device is locked".to_string())),
+        Ok(Message::Failure(failure)) => {
+            Err(unavailable(false, failure.message.unwrap_or_else(|| "Device returned an error".to_string())))
+        }
+        Ok(other) => Err(unavailable(false, format!("Unexpected response {:?}", other.message_type()))),
+        Err(e) => Err(unavailable(false, e.to_string())),
+    }
+}
+
+/// Derive `path` on both devices at once and compare the keys
+pub async fn compare_handles(
+    a: &DeviceQueueHandle,
+    b: &DeviceQueueHandle,
+    path: &[u32],
+    coin_name: &str,
+) -> Result<bool, CompareDevicesError> {
+    if a.device_id() == b.device_id() {
+        return Err(CompareDevicesError::SameDevice);
+    }
+    let (key_a, key_b) = tokio::join!(derive_key(a, path, coin_name), derive_key(b, path, coin_name));
+    match (key_a, key_b) {
+        (Ok(key_a), Ok(key_b)) => Ok(constant_time_eq(&key_a, &key_b)),
+        (key_a, key_b) => {
+            let mut unavailable = Vec::new();
+            let mut answered = Vec::new();
+            for (handle, key) in [(a, key_a), (b, key_b)] {
+                match key {
+                    Ok(_) => answered.push(handle.device_id().to_string()),
+                    Err(device) => unavailable.push(device),
+                }
+            }
+            Err(CompareDevicesError::Unavailable { unavailable, answered })
+        }
+    }
+}
+
+/// Whether two connected devices derive the same public key at `path`, i.e.
+/// hold the same seed (and passphrase)
+#[tauri::command]
+pub async fn compare_devices(
+    unique_id_a: String,
+    unique_id_b: String,
+    path: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<bool, CompareDevicesError> {
+    let path = crate::commands::parse_derivation_path(&path).map_err(|message| CompareDevicesError::InvalidPath { message })?;
+    if unique_id_a == unique_id_b {
+        return Err(CompareDevicesError::SameDevice);
+    }
+
+    let mut handles = Vec::new();
+    let mut unavailable = Vec::new();
+    for device_id in [&unique_id_a, &unique_id_b] {
+        match queue_manager.get_or_spawn_by_id(device_id).await {
+            Some(handle) => handles.push(handle),
+            None => unavailable.push(UnavailableDevice {
+                device_id: device_id.clone(),
+                locked: false,
+                message: "Device not found".to_string(),
+            }),
+        }
+    }
+    if !unavailable.is_empty() {
+        let answered = handles.iter().map(|h| h.device_id().to_string()).collect();
+        return Err(CompareDevicesError::Unavailable { unavailable, answered });
+    }
+
+    let coin_name = crate::commands::network_mode().coin_name();
+    let matches = compare_handles(&handles[0], &handles[1], &path, coin_name).await?;
+    println!("🔑 Compared {} and {}: {}", unique_id_a, unique_id_b, if matches { "same seed" } else { "different seeds" });
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{self, MockScenario};
+    use keepkey_rust::device_queue::DeviceCmd;
+
+    const PATH: &[u32] = &[0x8000_0054, 0x8000_0000, 0x8000_0000];
+
+    /// A device with another seed: answers every GetPublicKey with `xpub`
+    fn other_seed(device_id: &str, xpub: &'static str) -> DeviceQueueHandle {
+        let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let DeviceCmd::SendRaw { respond_to, .. } = cmd {
+                    let _ = respond_to.send(Ok(messages::PublicKey { xpub: Some(xpub.to_string()), ..Default::default() }.into()));
+                }
+            }
+        });
+        DeviceQueueHandle::new(device_id.to_string(), cmd_tx)
+    }
+
+    /// A second mock device with the same scripted seed under another id
+    fn mock_twin(scenario: MockScenario) -> DeviceQueueHandle {
+        let twin = mock::spawn_worker(&scenario.device_id()).unwrap();
+        let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let DeviceCmd::SendRaw { message, respond_to, .. } = cmd {
+                    let _ = respond_to.send(twin.send_raw(message, false).await);
+                }
+            }
+        });
+        DeviceQueueHandle::new("mock-backup".to_string(), cmd_tx)
+    }
+
+    #[tokio::test]
+    async fn test_devices_sharing_a_seed() {
+        let wallet = mock::spawn_worker(&MockScenario::Ready.device_id()).unwrap();
+        let backup = mock_twin(MockScenario::Ready);
+        assert_eq!(compare_handles(&wallet, &backup, PATH, "Bitcoin").await, Ok(true));
+
+        // BIP32 test vector 2: a different seed
+        let stranger = other_seed(
+            "bus1_addr7",
+            "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+        );
+        assert_eq!(compare_handles(&wallet, &stranger, PATH, "Bitcoin").await, Ok(false));
+        assert_eq!(compare_handles(&wallet, &wallet, PATH, "Bitcoin").await, Err(CompareDevicesError::SameDevice));
+    }
+
+    #[tokio::test]
+    async fn test_locked_device_gives_partial_result() {
+        let wallet = mock::spawn_worker(&MockScenario::Ready.device_id()).unwrap();
+        let locked = mock::spawn_worker(&MockScenario::Locked.device_id()).unwrap();
+        match compare_handles(&wallet, &locked, PATH, "Bitcoin").await {
+            Err(CompareDevicesError::Unavailable { unavailable, answered }) => {
+                assert_eq!(answered, vec!["mock-ready".to_string()]);
+                assert_eq!(unavailable.len(), 1);
+                assert_eq!(unavailable[0].device_id, "mock-locked");
+                assert!(unavailable[0].locked);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2]));
+    }
+}
@@ -0,0 +1,382 @@
+use keepkey_rust::device_queue::TimeoutProfile;
+use keepkey_rust::transport::TransportConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::lru::DeviceLru;
+
+/// Round-trips `diagnose_connection` makes
+const DIAGNOSE_ROUNDS: usize = 40;
+/// A round-trip taking longer than this counts as an error
+const ROUND_TRIP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Consecutive failed round-trips after which the device is assumed gone
+const MAX_CONSECUTIVE_ERRORS: usize = 5;
+const DIAGNOSE_PAYLOAD_SIZE: usize = 64;
+
+/// Transport errors within `DEGRADED_WINDOW` that mark a connection degraded
+/// during normal operation
+pub const DEGRADED_ERROR_THRESHOLD: usize = 5;
+pub const DEGRADED_WINDOW: Duration = Duration::from_secs(120);
+
+const DIRECT_PORT_SUGGESTION: &str =
+    "Plug the KeepKey directly into a USB port on your computer instead of a hub, and try a different cable.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionQuality {
+    Good,
+    /// Occasional errors or jittery latency; works, but retries will happen
+    Marginal,
+    /// Frequent errors or very unstable latency, typically a bad hub or cable
+    Poor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionDiagnosis {
+    pub device_id: String,
+    pub quality: ConnectionQuality,
+    pub round_trips: usize,
+    pub errors: usize,
+    pub avg_latency_ms: f64,
+    /// Standard deviation of successful round-trips
+    pub latency_stddev_ms: f64,
+    pub suggestion: Option<String>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Classify a burst of round-trips from its error rate and latency jitter
+pub fn classify(round_trips: usize, errors: usize, latency_stddev_ms: f64) -> ConnectionQuality {
+    let error_rate = errors as f64 / round_trips.max(1) as f64;
+    if error_rate >= 0.1 || latency_stddev_ms > 50.0 {
+        ConnectionQuality::Poor
+    } else if errors > 0 || latency_stddev_ms > 15.0 {
+        ConnectionQuality::Marginal
+    } else {
+        ConnectionQuality::Good
+    }
+}
+
+fn mean_and_stddev(samples: &[f64]) -> (f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    (mean, variance.sqrt())
+}
+
+/// Last diagnosis per device, included in the support bundle
+static LAST_DIAGNOSES: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<ConnectionDiagnosis>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+pub fn last_diagnoses() -> HashMap<String, ConnectionDiagnosis> {
+    crate::commands::lock_or_recover(&LAST_DIAGNOSES, "connection diagnoses").to_map()
+}
+
+/// Drop a device's last diagnosis and transport error history
+pub fn forget(device_id: &str) {
+    crate::commands::lock_or_recover(&LAST_DIAGNOSES, "connection diagnoses").remove(device_id);
+    crate::commands::lock_or_recover(&TRANSPORT_HEALTH, "transport health").remove(device_id);
+}
+
+/// How a device's operation timeouts and write retries are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutMode {
+    /// Follow the last `diagnose_connection` result: more time and retries on
+    /// a marginal or poor link, tighter timeouts on a good one
+    Adaptive,
+    /// The same profile for every device, whatever its connection
+    Static,
+}
+
+impl TimeoutMode {
+    pub fn from_config(value: &str) -> Option<Self> {
+        match value.trim() {
+            "adaptive" => Some(TimeoutMode::Adaptive),
+            "static" => Some(TimeoutMode::Static),
+            _ => None,
+        }
+    }
+}
+
+static ADAPTIVE_TIMEOUTS: AtomicBool = AtomicBool::new(true);
+
+pub fn timeout_mode() -> TimeoutMode {
+    if ADAPTIVE_TIMEOUTS.load(Ordering::Relaxed) {
+        TimeoutMode::Adaptive
+    } else {
+        TimeoutMode::Static
+    }
+}
+
+pub fn set_timeout_mode(mode: TimeoutMode) {
+    ADAPTIVE_TIMEOUTS.store(mode == TimeoutMode::Adaptive, Ordering::Relaxed);
+}
+
+/// Timeouts are never tightened below this
+const MIN_ADAPTIVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Write retries a marginal and a poor connection get at least
+const MARGINAL_WRITE_RETRIES: u32 = 2;
+const POOR_WRITE_RETRIES: u32 = 4;
+
+/// Scale of transport-bound timeouts for a connection quality. Bounded to
+/// half and twice the static profile.
+fn timeout_factor(quality: ConnectionQuality) -> f64 {
+    match quality {
+        ConnectionQuality::Good => 0.5,
+        ConnectionQuality::Marginal => 1.5,
+        ConnectionQuality::Poor => 2.0,
+    }
+}
+
+/// `base` adjusted to a connection of `quality`. Only operations bound by the
+/// transport are scaled: the ones waiting on a confirmation keep their time,
+/// it is the user's, not the link's.
+pub fn adjusted_timeouts(base: &TimeoutProfile, quality: ConnectionQuality) -> TimeoutProfile {
+    let factor = timeout_factor(quality);
+    let scale = |timeout: Duration| timeout.mul_f64(factor).max(MIN_ADAPTIVE_TIMEOUT.min(timeout));
+    TimeoutProfile {
+        get_features: scale(base.get_features),
+        read: scale(base.read),
+        firmware_update: if factor > 1.0 { scale(base.firmware_update) } else { base.firmware_update },
+        ..*base
+    }
+}
+
+/// `base` with the extra write retries a connection of `quality` needs;
+/// configured retries are never lowered
+pub fn adjusted_transport_config(base: TransportConfig, quality: ConnectionQuality) -> TransportConfig {
+    let write_retries = match quality {
+        ConnectionQuality::Good => base.write_retries,
+        ConnectionQuality::Marginal => base.write_retries.max(MARGINAL_WRITE_RETRIES),
+        ConnectionQuality::Poor => base.write_retries.max(POOR_WRITE_RETRIES),
+    };
+    TransportConfig { write_retries, ..base }
+}
+
+/// Quality the timeouts of `device_id` follow: its last diagnosis, unless the
+/// static profile is configured
+pub fn adaptive_quality(device_id: &str) -> Option<ConnectionQuality> {
+    if timeout_mode() == TimeoutMode::Static {
+        return None;
+    }
+    crate::commands::lock_or_recover(&LAST_DIAGNOSES, "connection diagnoses").peek(device_id).map(|diagnosis| diagnosis.quality)
+}
+
+/// Timeouts and transport settings for a worker of `device_id`
+pub fn worker_profile(device_id: &str, base: TransportConfig) -> (TimeoutProfile, TransportConfig) {
+    let timeouts = TimeoutProfile::default();
+    let Some(quality) = adaptive_quality(device_id) else {
+        return (timeouts, base);
+    };
+    let adjusted = (adjusted_timeouts(&timeouts, quality), adjusted_transport_config(base, quality));
+    println!(
+        "⏱️ {:?} connection to {}: reads time out after {:?}, {} write retries",
+        quality, device_id, adjusted.0.read, adjusted.1.write_retries
+    );
+    adjusted
+}
+
+/// Give the running worker of `device_id` the timeouts of its latest
+/// diagnosis. Write retries are fixed when a worker spawns, so those apply
+/// from its next spawn.
+async fn apply_to_running_worker(queue_manager: &DeviceQueueManager, device_id: &str) {
+    let (timeouts, _) = worker_profile(device_id, crate::commands::transport_config());
+    if let Some(handle) = queue_manager.lock().await.get_mut(device_id) {
+        *handle = handle.clone().with_timeout_profile(timeouts);
+    }
+}
+
+/// Check the USB link to a device with a burst of Ping round-trips. Unlike
+/// `benchmark_device_io` errors don't abort the run, they are what's measured:
+/// hubs that drop packets show up as failed or very slow round-trips.
+#[tauri::command]
+pub async fn diagnose_connection(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<ConnectionDiagnosis, String> {
+    println!("🩺 Diagnosing connection of device: {}", unique_id);
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+
+    let payload = "k".repeat(DIAGNOSE_PAYLOAD_SIZE);
+    let mut latencies = Vec::with_capacity(DIAGNOSE_ROUNDS);
+    let mut errors = 0;
+    let mut consecutive_errors = 0;
+    let mut round_trips = 0;
+    while round_trips < DIAGNOSE_ROUNDS && consecutive_errors < MAX_CONSECUTIVE_ERRORS {
+        round_trips += 1;
+        let started = Instant::now();
+        match queue_handle
+            .send_raw_with_timeout(crate::device::benchmark::ping(payload.clone()), true, ROUND_TRIP_TIMEOUT)
+            .await
+        {
+            Ok(keepkey_rust::messages::Message::Success(_)) => {
+                latencies.push(started.elapsed().as_secs_f64() * 1000.0);
+                consecutive_errors = 0;
+            }
+            Ok(other) => {
+                println!("⚠️ Unexpected response to ping: {:?}", other.message_type());
+                errors += 1;
+                consecutive_errors += 1;
+            }
+            Err(e) => {
+                println!("⚠️ Round-trip {} to {} failed: {}", round_trips, unique_id, e);
+                errors += 1;
+                consecutive_errors += 1;
+            }
+        }
+    }
+    if latencies.is_empty() {
+        return Err(format!("Device {} didn't answer any round-trip", unique_id));
+    }
+
+    let (avg_latency_ms, latency_stddev_ms) = mean_and_stddev(&latencies);
+    let quality = classify(round_trips, errors, latency_stddev_ms);
+    let diagnosis = ConnectionDiagnosis {
+        device_id: unique_id.clone(),
+        quality,
+        round_trips,
+        errors,
+        avg_latency_ms,
+        latency_stddev_ms,
+        suggestion: (quality != ConnectionQuality::Good).then(|| DIRECT_PORT_SUGGESTION.to_string()),
+        timestamp: chrono::Utc::now(),
+    };
+    println!(
+        "🩺 Device {}: {:?} ({}/{} errors, {:.1}±{:.1}ms)",
+        unique_id, quality, errors, round_trips, avg_latency_ms, latency_stddev_ms
+    );
+
+    crate::commands::lock_or_recover(&LAST_DIAGNOSES, "connection diagnoses").insert(&unique_id, diagnosis.clone());
+    apply_to_running_worker(queue_manager.inner(), &unique_id).await;
+    Ok(diagnosis)
+}
+
+/// Errors that come from the USB link rather than the device's answer
+pub fn is_transport_error(error: &str) -> bool {
+    let lower = error.to_lowercase();
+    ["timeout", "timed out", "pipe", "i/o", "io error", "no such device", "transport", "read error", "write error", "overflow"]
+        .iter()
+        .any(|needle| lower.contains(needle))
+}
+
+/// Recent transport errors of one device
+#[derive(Debug, Default)]
+struct TransportHealth {
+    errors: VecDeque<Instant>,
+    reported_at: Option<Instant>,
+}
+
+impl TransportHealth {
+    /// Record an error at `now`; returns the errors in the window when it
+    /// crosses the threshold, at most once per window
+    fn record_error(&mut self, now: Instant) -> Option<usize> {
+        self.errors.push_back(now);
+        while self.errors.front().is_some_and(|at| now.duration_since(*at) > DEGRADED_WINDOW) {
+            self.errors.pop_front();
+        }
+        if self.errors.len() < DEGRADED_ERROR_THRESHOLD {
+            return None;
+        }
+        if self.reported_at.is_some_and(|at| now.duration_since(at) <= DEGRADED_WINDOW) {
+            return None;
+        }
+        self.reported_at = Some(now);
+        Some(self.errors.len())
+    }
+}
+
+static TRANSPORT_HEALTH: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<TransportHealth>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+/// Note a failed operation; emits `device:connection-degraded` once transport
+/// errors exceed `DEGRADED_ERROR_THRESHOLD` within `DEGRADED_WINDOW`
+pub fn record_operation_error(app: &AppHandle, device_id: &str, error: &str) {
+    if !is_transport_error(error) {
+        return;
+    }
+    let crossed = crate::commands::lock_or_recover(&TRANSPORT_HEALTH, "transport health")
+        .get_or_insert_with(device_id, TransportHealth::default)
+        .record_error(Instant::now());
+    if let Some(errors) = crossed {
+        println!("📉 Connection to {} degraded: {} transport errors in {}s", device_id, errors, DEGRADED_WINDOW.as_secs());
+        let _ = app.emit("device:connection-degraded", serde_json::json!({
+            "deviceId": device_id,
+            "errors": errors,
+            "windowSecs": DEGRADED_WINDOW.as_secs(),
+            "suggestion": DIRECT_PORT_SUGGESTION
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_connection() {
+        assert_eq!(classify(40, 0, 2.0), ConnectionQuality::Good);
+        assert_eq!(classify(40, 1, 2.0), ConnectionQuality::Marginal);
+        assert_eq!(classify(40, 0, 20.0), ConnectionQuality::Marginal);
+        assert_eq!(classify(40, 4, 2.0), ConnectionQuality::Poor);
+        assert_eq!(classify(40, 0, 80.0), ConnectionQuality::Poor);
+
+        let (mean, stddev) = mean_and_stddev(&[10.0, 20.0, 30.0]);
+        assert_eq!(mean, 20.0);
+        assert!((stddev - 8.165).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_degraded_reported_once_per_window() {
+        let start = Instant::now();
+        let mut health = TransportHealth::default();
+        for i in 0..DEGRADED_ERROR_THRESHOLD - 1 {
+            assert_eq!(health.record_error(start + Duration::from_secs(i as u64)), None);
+        }
+        assert_eq!(health.record_error(start + Duration::from_secs(10)), Some(DEGRADED_ERROR_THRESHOLD));
+        assert_eq!(health.record_error(start + Duration::from_secs(11)), None);
+
+        // Old errors age out of the window
+        let later = start + DEGRADED_WINDOW + Duration::from_secs(60);
+        assert_eq!(health.record_error(later), None);
+
+        assert!(is_transport_error("Failed to get address: USB read error: Pipe error"));
+        assert!(!is_transport_error("Device returned error: Invalid PIN"));
+    }
+
+    #[test]
+    fn test_timeouts_follow_connection_quality() {
+        let base = TimeoutProfile::default();
+        let poor = adjusted_timeouts(&base, ConnectionQuality::Poor);
+        assert_eq!(poor.read, base.read * 2);
+        assert_eq!(poor.firmware_update, base.firmware_update * 2);
+        assert_eq!(adjusted_timeouts(&base, ConnectionQuality::Marginal).get_features, base.get_features.mul_f64(1.5));
+
+        // A good link fails faster, but confirmations keep the user's time
+        let good = adjusted_timeouts(&base, ConnectionQuality::Good);
+        assert_eq!(good.read, base.read / 2);
+        assert_eq!(good.get_features, Duration::from_millis(2500));
+        assert_eq!((good.sign, good.confirm, good.firmware_update), (base.sign, base.confirm, base.firmware_update));
+        let short = TimeoutProfile { get_features: Duration::from_secs(3), ..base };
+        assert_eq!(adjusted_timeouts(&short, ConnectionQuality::Good).get_features, MIN_ADAPTIVE_TIMEOUT);
+
+        let config = TransportConfig::default();
+        assert_eq!(adjusted_transport_config(config, ConnectionQuality::Good).write_retries, 0);
+        assert_eq!(adjusted_transport_config(config, ConnectionQuality::Poor).write_retries, POOR_WRITE_RETRIES);
+        let configured = TransportConfig { write_retries: 3, ..config };
+        assert_eq!(adjusted_transport_config(configured, ConnectionQuality::Marginal).write_retries, 3);
+
+        assert_eq!(TimeoutMode::from_config("static"), Some(TimeoutMode::Static));
+        assert_eq!(TimeoutMode::from_config("fast"), None);
+    }
+}
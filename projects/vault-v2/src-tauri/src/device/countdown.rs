@@ -0,0 +1,107 @@
+//! Countdown to the timeout of a confirmation on the device.
+//!
+//! A request that waits on the user (signing, showing an address, a wipe or a
+//! settings change) gives up after its operation's timeout in the worker's
+//! timeout profile; the handle exposes when. While the device screen shows a
+//! prompt of such a request, `operation:countdown` reports the time left once
+//! a second, so the UI can say "waiting for confirmation - 110s remaining".
+//! It stops as soon as the prompt is answered or cancelled. Opt-in with the
+//! `confirmation_countdown` preference.
+
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::screen_hint::ScreenHint;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// How often `operation:countdown` is emitted
+pub const COUNTDOWN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How quickly an answered or cancelled prompt stops the countdown
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn countdown_payload(device_id: &str, remaining: Duration) -> serde_json::Value {
+    serde_json::json!({
+        "unique_id": device_id,
+        "remaining_ms": remaining.as_millis() as u64,
+    })
+}
+
+/// Time left to confirm on `handle`'s device, `None` when nothing waits on a
+/// confirmation or the screen shows no prompt
+fn remaining(handle: &DeviceQueueHandle) -> Option<Duration> {
+    if handle.screen_hint() == ScreenHint::Idle {
+        return None;
+    }
+    Some(handle.confirmation_deadline()?.saturating_duration_since(Instant::now()))
+}
+
+/// Call `emit` with the time left every `every` until the prompt is answered,
+/// cancelled or timed out
+pub async fn run_countdown(handle: &DeviceQueueHandle, every: Duration, mut emit: impl FnMut(Duration)) {
+    while let Some(left) = remaining(handle) {
+        emit(left);
+        let next = Instant::now() + every;
+        while Instant::now() < next {
+            tokio::time::sleep(POLL_INTERVAL.min(every)).await;
+            if remaining(handle).is_none() {
+                return;
+            }
+        }
+    }
+}
+
+/// Emit `operation:countdown` for the prompt `handle`'s device just showed.
+/// `running` keeps a device's consecutive prompts to one countdown.
+pub fn spawn_countdown(app: AppHandle, handle: DeviceQueueHandle, running: std::sync::Arc<std::sync::atomic::AtomicBool>) {
+    use std::sync::atomic::Ordering;
+
+    if running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let device_id = handle.device_id().to_string();
+        run_countdown(&handle, COUNTDOWN_INTERVAL, |left| {
+            let _ = app.emit("operation:countdown", countdown_payload(&device_id, left));
+        })
+        .await;
+        running.store(false, Ordering::SeqCst);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{spawn_scripted_worker, MockDevice, MockScenario};
+    use keepkey_rust::messages::{self, Message};
+
+    #[tokio::test]
+    async fn test_countdown_runs_while_confirming() {
+        let handle = spawn_scripted_worker("countdown-test", MockDevice::new(MockScenario::Ready));
+        let signing = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_raw(messages::SignTx::default().into(), false).await }
+        });
+        while handle.screen_hint() == ScreenHint::Idle {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // The mock confirms after 300ms: a few ticks, counting down from the sign timeout
+        let mut ticks = Vec::new();
+        run_countdown(&handle, Duration::from_millis(60), |left| ticks.push(left)).await;
+        let sign_timeout = handle.timeout_profile().sign;
+        assert!(ticks.len() >= 2, "expected several countdown events, got {:?}", ticks);
+        assert!(ticks.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(ticks[0] <= sign_timeout && ticks[0] > sign_timeout - Duration::from_secs(5));
+
+        // It stopped with the confirmation, not at the timeout
+        assert!(matches!(signing.await.unwrap(), Ok(Message::TxRequest(_))));
+        assert_eq!(handle.confirmation_deadline(), None);
+        let mut late = 0;
+        run_countdown(&handle, Duration::from_millis(60), |_| late += 1).await;
+        assert_eq!(late, 0);
+
+        let payload = countdown_payload("countdown-test", Duration::from_millis(110_400));
+        assert_eq!(payload["unique_id"], "countdown-test");
+        assert_eq!(payload["remaining_ms"], 110_400);
+    }
+}
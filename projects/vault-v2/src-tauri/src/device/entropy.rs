@@ -0,0 +1,221 @@
+//! Smoke test of the device's random number generator.
+//!
+//! `check_entropy_quality` reads a few samples of raw RNG output with
+//! `GetEntropy` and runs the frequency (monobit) and runs tests of NIST
+//! SP 800-22 over them. That catches an obviously broken generator - stuck
+//! bits, a constant or strictly alternating output, a sample repeated - and
+//! nothing more: it is not the full SP 800-22 suite, a few kilobytes can't show
+//! subtle bias, and a deterministic but well-mixed stream (a counter run
+//! through a hash) passes. A pass means "not visibly broken", not "good".
+//!
+//! Only fresh `GetEntropy` output is looked at. The seed, and anything derived
+//! from it, is never read. The firmware asks for a button press on every
+//! sample.
+
+use keepkey_rust::messages::{self, Message};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// Samples read from the device, one button press each
+pub const SAMPLES: usize = 4;
+/// Bytes per sample; the firmware caps a single `GetEntropy` at 1024
+pub const SAMPLE_SIZE: u32 = 1024;
+/// Significance level of both tests, the one SP 800-22 recommends
+pub const ALPHA: f64 = 0.01;
+
+/// Outcome of one statistical test
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResult {
+    pub p_value: f64,
+    pub passed: bool,
+}
+
+impl TestResult {
+    fn from_p_value(p_value: f64) -> Self {
+        Self { p_value, passed: p_value >= ALPHA }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntropyReport {
+    pub unique_id: String,
+    pub samples: usize,
+    pub bytes: usize,
+    pub monobit: TestResult,
+    pub runs: TestResult,
+    /// Two samples came back identical
+    pub repeated_samples: bool,
+    pub passed: bool,
+}
+
+impl EntropyReport {
+    /// Run the checks over `samples` read from `unique_id`
+    pub fn evaluate(unique_id: &str, samples: &[Vec<u8>]) -> Self {
+        let data = samples.concat();
+        let repeated_samples = samples.iter().enumerate().any(|(i, sample)| samples[..i].contains(sample));
+        let monobit = monobit_test(&data);
+        let runs = runs_test(&data);
+        Self {
+            unique_id: unique_id.to_string(),
+            samples: samples.len(),
+            bytes: data.len(),
+            monobit,
+            runs,
+            repeated_samples,
+            passed: monobit.passed && runs.passed && !repeated_samples,
+        }
+    }
+}
+
+fn bits(data: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    data.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+}
+
+/// Complementary error function, Chebyshev approximation with a fractional
+/// error below 1.2e-7 (Numerical Recipes' `erfcc`) - plenty for a pass/fail at
+/// `ALPHA`
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07 + t * (-1.135_203_98 + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * poly.exp();
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+/// Frequency (monobit) test, SP 800-22 section 2.1: are ones and zeros about
+/// equally common?
+pub fn monobit_test(data: &[u8]) -> TestResult {
+    let n = (data.len() * 8) as f64;
+    if n == 0.0 {
+        return TestResult::from_p_value(0.0);
+    }
+    let sum: i64 = bits(data).map(|bit| if bit { 1 } else { -1 }).sum();
+    let s_obs = (sum as f64).abs() / n.sqrt();
+    TestResult::from_p_value(erfc(s_obs / std::f64::consts::SQRT_2))
+}
+
+/// Runs test, SP 800-22 section 2.3: do ones and zeros alternate as often as
+/// they should? Fails outright when the frequency is too far off for the test
+/// to apply.
+pub fn runs_test(data: &[u8]) -> TestResult {
+    let n = (data.len() * 8) as f64;
+    if n == 0.0 {
+        return TestResult::from_p_value(0.0);
+    }
+    let pi = bits(data).filter(|bit| *bit).count() as f64 / n;
+    if (pi - 0.5).abs() >= 2.0 / n.sqrt() {
+        return TestResult::from_p_value(0.0);
+    }
+    let bits: Vec<bool> = bits(data).collect();
+    let runs = 1 + bits.windows(2).filter(|pair| pair[0] != pair[1]).count();
+    let expected = 2.0 * n * pi * (1.0 - pi);
+    let p_value = erfc((runs as f64 - expected).abs() / (2.0 * (2.0 * n).sqrt() * pi * (1.0 - pi)));
+    TestResult::from_p_value(p_value)
+}
+
+async fn read_sample(queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle) -> Result<Vec<u8>, String> {
+    match queue_handle.send_raw(messages::GetEntropy { size: SAMPLE_SIZE }.into(), true).await {
+        Ok(Message::Entropy(entropy)) => Ok(entropy.entropy),
+        Ok(Message::Failure(failure)) => Err(format!("Device refused to provide entropy: {}", failure.message.unwrap_or_default())),
+        Ok(other) => Err(format!("Unexpected response to GetEntropy: {:?}", other.message_type())),
+        Err(e) => Err(format!("Failed to read entropy: {}", e)),
+    }
+}
+
+/// Read `SAMPLES` samples of raw RNG output and check them for obvious
+/// defects. A smoke test only; see the module docs for what it can't catch.
+#[tauri::command]
+pub async fn check_entropy_quality(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<EntropyReport, String> {
+    if crate::commands::cached_device_features(&unique_id).is_some_and(|features| features.bootloader_mode) {
+        return Err("The device is in bootloader mode and can't provide entropy".to_string());
+    }
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+
+    println!("🎲 Reading {} entropy samples from device: {}", SAMPLES, unique_id);
+    let mut samples = Vec::with_capacity(SAMPLES);
+    for _ in 0..SAMPLES {
+        samples.push(read_sample(&queue_handle).await?);
+    }
+
+    let report = EntropyReport::evaluate(&unique_id, &samples);
+    println!(
+        "🎲 Device {}: monobit p={:.4}, runs p={:.4}, {}",
+        unique_id,
+        report.monobit.p_value,
+        report.runs.p_value,
+        if report.passed { "passed" } else { "FAILED" }
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    /// 1024-byte samples of a SHA-256 chain: statistically indistinguishable
+    /// from a working RNG at this size
+    fn good_samples() -> Vec<Vec<u8>> {
+        let mut block = Sha256::digest(b"keepkey entropy check").to_vec();
+        let mut data = Vec::new();
+        while data.len() < SAMPLES * SAMPLE_SIZE as usize {
+            data.extend_from_slice(&block);
+            block = Sha256::digest(&block).to_vec();
+        }
+        data.chunks(SAMPLE_SIZE as usize).map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn test_known_good_entropy_passes() {
+        let report = EntropyReport::evaluate("entropy-test", &good_samples());
+        assert_eq!(report.samples, SAMPLES);
+        assert_eq!(report.bytes, SAMPLES * SAMPLE_SIZE as usize);
+        assert!(report.monobit.p_value > 0.5 && report.runs.p_value > 0.5, "{:?}", report);
+        assert!(!report.repeated_samples);
+        assert!(report.passed);
+    }
+
+    #[test]
+    fn test_broken_generators_fail() {
+        let size = SAMPLE_SIZE as usize;
+        // Stuck at zero or one: both tests fail
+        for byte in [0x00, 0xff] {
+            let report = EntropyReport::evaluate("entropy-test", &[vec![byte; size]]);
+            assert!(!report.monobit.passed && !report.runs.passed && !report.passed);
+        }
+        // Perfectly balanced but strictly alternating: only the runs test sees it
+        let alternating: Vec<u8> = [0x55, 0xaa].repeat(size / 2);
+        let report = EntropyReport::evaluate("entropy-test", &[alternating]);
+        assert!(report.monobit.passed);
+        assert!(!report.runs.passed && !report.passed);
+        // Good-looking output that the generator hands out twice
+        let good = good_samples();
+        let report = EntropyReport::evaluate("entropy-test", &[good[0].clone(), good[1].clone(), good[0].clone()]);
+        assert!(report.monobit.passed && report.runs.passed);
+        assert!(report.repeated_samples && !report.passed);
+        // Nothing read is no pass either
+        assert!(!EntropyReport::evaluate("entropy-test", &[]).passed);
+    }
+
+    #[test]
+    fn test_erfc() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157_299_2).abs() < 1e-6);
+        assert!((erfc(-1.0) - 1.842_700_8).abs() < 1e-6);
+    }
+}
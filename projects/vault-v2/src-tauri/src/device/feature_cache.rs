@@ -0,0 +1,105 @@
+//! Resetting the host-side feature cache.
+//!
+//! Most commands answer from the features cached at the last probe, so a
+//! device updated or reconfigured by another application keeps showing its old
+//! state until it reconnects. `clear_feature_cache` drops those entries, and
+//! nothing else: workers, metrics and the active device stay as they are
+//! (unlike `forget_device`), and nothing is sent to the device unless a
+//! refresh is asked for.
+
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::features::DeviceFeatures;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// Read features from the device and cache them
+pub async fn fetch_and_cache(queue_handle: &DeviceQueueHandle) -> Result<DeviceFeatures, String> {
+    let device_id = queue_handle.device_id();
+    let features = queue_handle
+        .get_features()
+        .await
+        .map(crate::commands::convert_features_to_device_features)
+        .map_err(|e| format!("Failed to get features of {}: {}", device_id, e))?;
+    crate::commands::cache_device_features(device_id, &features);
+    Ok(features)
+}
+
+/// Re-read the features of `device_id` and tell the frontend. Devices in the
+/// PIN flow are left alone, like the monitor does.
+async fn refresh(app: &AppHandle, queue_manager: &DeviceQueueManager, device_id: &str) -> Result<(), String> {
+    if crate::commands::is_device_in_pin_flow(device_id) {
+        println!("⏭️ {} is in the PIN flow, not refreshing its features", device_id);
+        return Ok(());
+    }
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+    let features = fetch_and_cache(&queue_handle).await?;
+    let status = crate::commands::evaluate_device_status(device_id.to_string(), Some(&features));
+    let _ = app.emit("device:features-updated", serde_json::json!({
+        "deviceId": device_id,
+        "features": features,
+        "status": status
+    }));
+    Ok(())
+}
+
+/// Drop the cached features of `unique_id`, or of every device when it's
+/// `None`, so the next read goes to the device. With `refresh`, the features
+/// are read again right away (from every device with a running worker when
+/// clearing all) and sent as `device:features-updated`. Returns the devices
+/// whose cached features were dropped.
+#[tauri::command]
+pub async fn clear_feature_cache(
+    unique_id: Option<String>,
+    refresh: Option<bool>,
+    app: AppHandle,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<String>, String> {
+    let cleared = crate::commands::clear_cached_features(unique_id.as_deref());
+    println!("🧹 Cleared cached features of {} device(s)", cleared.len());
+    if !refresh.unwrap_or(false) {
+        return Ok(cleared);
+    }
+
+    let targets: Vec<String> = match unique_id {
+        Some(unique_id) => vec![unique_id],
+        None => queue_manager.lock().await.keys().cloned().collect(),
+    };
+    for device_id in targets {
+        if let Err(e) = self::refresh(&app, &queue_manager, &device_id).await {
+            eprintln!("Failed to refresh features after clearing the cache: {}", e);
+        }
+    }
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{spawn_scripted_worker, MockDevice, MockScenario};
+
+    #[tokio::test]
+    async fn test_next_read_after_clearing_goes_to_the_device() {
+        let id = "feature-cache-test";
+        let handle = spawn_scripted_worker(id, MockDevice::new(MockScenario::Ready));
+        let mut stale = fetch_and_cache(&handle).await.unwrap();
+        stale.version = "7.0.0".to_string();
+        crate::commands::cache_device_features(id, &stale);
+        crate::commands::cache_device_features("feature-cache-other", &stale);
+
+        assert_eq!(crate::commands::clear_cached_features(Some(id)), vec![id.to_string()]);
+        assert!(crate::commands::cached_device_features(id).is_none());
+        // Only the named device is cleared, and clearing twice finds nothing
+        assert!(crate::commands::cached_device_features("feature-cache-other").is_some());
+        assert!(crate::commands::clear_cached_features(Some(id)).is_empty());
+
+        // The cache is cold, so the next read reaches the device
+        assert_eq!(fetch_and_cache(&handle).await.unwrap().version, "7.10.0");
+        assert_eq!(crate::commands::cached_device_features(id).unwrap().version, "7.10.0");
+        crate::commands::invalidate_cached_features(id);
+        crate::commands::invalidate_cached_features("feature-cache-other");
+    }
+}
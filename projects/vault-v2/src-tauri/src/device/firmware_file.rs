@@ -0,0 +1,233 @@
+//! Device-free inspection of KeepKey firmware images (`firmware.keepkey.bin`).
+//!
+//! An image is a 256 byte header followed by the code the bootloader flashes:
+//!
+//! | offset | size | field                                        |
+//! |--------|------|----------------------------------------------|
+//! | 0      | 4    | magic `KPKY`                                 |
+//! | 4      | 4    | code length, little endian                   |
+//! | 8      | 3    | signature indexes into the bootloader's keys |
+//! | 11     | 1    | flags                                        |
+//! | 12     | 52   | reserved                                     |
+//! | 64     | 192  | three 64 byte signatures                     |
+//!
+//! The hash is the sha256 of the code, the same value a device reports as
+//! `Features.firmware_hash` once the image is installed. The bootloader's
+//! signing keys aren't vendored in this tree, so the signatures are checked
+//! against the releases pinned in the bundled releases.json instead: an image
+//! is reported as validly signed when its header carries three signatures and
+//! its code is byte for byte a release KeepKey signed. The bootloader still
+//! verifies the signatures itself before it runs anything.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::device::model::KEEPKEY_MODEL;
+
+const MAGIC: &[u8; 4] = b"KPKY";
+pub const HEADER_SIZE: usize = 256;
+const SIGNATURES_OFFSET: usize = 64;
+const SIGNATURE_SIZE: usize = 64;
+/// Signing keys the bootloader holds; header indexes are 1-based into them
+const BOOTLOADER_KEYS: u8 = 5;
+
+/// Metadata of a firmware image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirmwareMetadata {
+    /// Release version (without `v`) for a known image, `None` otherwise
+    pub version: Option<String>,
+    /// Model the release was built for, `None` for an unknown image
+    pub model: Option<String>,
+    pub signature_valid: bool,
+    /// File size in bytes, header included
+    pub size: u64,
+    /// sha256 of the code, hex encoded
+    pub hash: String,
+}
+
+/// Why a file couldn't be inspected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FirmwareFileError {
+    /// The file couldn't be read
+    Io { message: String },
+    /// The file doesn't start with the `KPKY` magic
+    NotFirmware,
+    /// The file ends before the header or the code it announces
+    Truncated { expected: u64, actual: u64 },
+    /// The file holds more data than the header announces
+    TrailingData { expected: u64, actual: u64 },
+}
+
+impl std::fmt::Display for FirmwareFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FirmwareFileError::Io { message } => write!(f, "Failed to read firmware file: {}", message),
+            FirmwareFileError::NotFirmware => write!(f, "Not a KeepKey firmware image"),
+            FirmwareFileError::Truncated { expected, actual } => {
+                write!(f, "Firmware image is truncated: expected {} bytes, found {}", expected, actual)
+            }
+            FirmwareFileError::TrailingData { expected, actual } => {
+                write!(f, "Firmware image is corrupt: expected {} bytes, found {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FirmwareFileError {}
+
+/// Firmware code hashes to release versions (without `v`): `hashes.firmware`
+/// plus the latest and beta entries of the bundled releases.json
+static KNOWN_FIRMWARE: once_cell::sync::Lazy<Vec<(String, String)>> = once_cell::sync::Lazy::new(|| {
+    let releases: serde_json::Value = match serde_json::from_str(include_str!("../../firmware/releases.json")) {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!("Bundled releases.json is invalid: {}", e);
+            return Vec::new();
+        }
+    };
+    let version = |v: &serde_json::Value| v.as_str().map(|v| v.trim_start_matches('v').to_string());
+    let mut known: Vec<(String, String)> = releases["hashes"]["firmware"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(hash, v)| Some((hash.to_ascii_lowercase(), version(v)?)))
+        .collect();
+    for channel in ["latest", "beta"] {
+        let firmware = &releases[channel]["firmware"];
+        if let (Some(hash), Some(v)) = (firmware["hash"].as_str(), version(&firmware["version"])) {
+            known.push((hash.to_ascii_lowercase(), v));
+        }
+    }
+    known
+});
+
+/// Release version of firmware code with the given sha256 (hex)
+pub fn firmware_version_from_hash(hash: &str) -> Option<&'static str> {
+    let hash = hash.to_ascii_lowercase();
+    KNOWN_FIRMWARE
+        .iter()
+        .find(|(known, _)| *known == hash)
+        .map(|(_, version)| version.as_str())
+}
+
+/// Whether the header's three signature slots are filled: distinct key
+/// indexes within the bootloader's keys, and non-zero signatures
+fn has_signatures(header: &[u8]) -> bool {
+    let indexes = &header[8..11];
+    let indexes_ok = indexes.iter().all(|&i| (1..=BOOTLOADER_KEYS).contains(&i))
+        && indexes[0] != indexes[1]
+        && indexes[0] != indexes[2]
+        && indexes[1] != indexes[2];
+    let signatures_present = header[SIGNATURES_OFFSET..HEADER_SIZE]
+        .chunks(SIGNATURE_SIZE)
+        .all(|signature| signature.iter().any(|&b| b != 0));
+    indexes_ok && signatures_present
+}
+
+/// Parse a firmware image held in memory
+pub fn inspect_firmware(bytes: &[u8]) -> Result<FirmwareMetadata, FirmwareFileError> {
+    let actual = bytes.len() as u64;
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(FirmwareFileError::NotFirmware);
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(FirmwareFileError::Truncated { expected: HEADER_SIZE as u64, actual });
+    }
+    let code_len = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as u64;
+    let expected = HEADER_SIZE as u64 + code_len;
+    if actual < expected {
+        return Err(FirmwareFileError::Truncated { expected, actual });
+    }
+    if actual > expected {
+        return Err(FirmwareFileError::TrailingData { expected, actual });
+    }
+
+    let hash = hex::encode(Sha256::digest(&bytes[HEADER_SIZE..]));
+    let version = firmware_version_from_hash(&hash).map(str::to_string);
+    Ok(FirmwareMetadata {
+        model: version.as_ref().map(|_| KEEPKEY_MODEL.to_string()),
+        signature_valid: version.is_some() && has_signatures(&bytes[..HEADER_SIZE]),
+        version,
+        size: actual,
+        hash,
+    })
+}
+
+/// Read and parse the firmware image at `path`
+pub fn inspect_firmware_path(path: &Path) -> Result<FirmwareMetadata, FirmwareFileError> {
+    let bytes = std::fs::read(path).map_err(|e| FirmwareFileError::Io { message: e.to_string() })?;
+    inspect_firmware(&bytes)
+}
+
+/// Metadata of a firmware image file, without a device
+#[tauri::command]
+pub async fn inspect_firmware_file(path: String) -> Result<FirmwareMetadata, FirmwareFileError> {
+    let path = std::path::PathBuf::from(path);
+    tokio::task::spawn_blocking(move || inspect_firmware_path(&path))
+        .await
+        .map_err(|e| FirmwareFileError::Io { message: e.to_string() })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled_firmware() -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("firmware/v7.10.0/firmware.keepkey.bin");
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn test_inspect_release_image() {
+        let bytes = bundled_firmware();
+        let metadata = inspect_firmware(&bytes).unwrap();
+        assert_eq!(metadata.version.as_deref(), Some("7.10.0"));
+        assert_eq!(metadata.model.as_deref(), Some(KEEPKEY_MODEL));
+        assert!(metadata.signature_valid);
+        assert_eq!(metadata.size, bytes.len() as u64);
+        assert_eq!(metadata.hash, "958764cf3baa53eec0002eab9c54e02ce6f5fdab71e7efbbe723f958e26ff419");
+
+        // The same image read from disk
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("firmware/v7.10.0/firmware.keepkey.bin");
+        assert_eq!(inspect_firmware_path(&path).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_corrupt_images_are_rejected() {
+        let bytes = bundled_firmware();
+
+        let truncated = &bytes[..bytes.len() - 1000];
+        assert_eq!(
+            inspect_firmware(truncated),
+            Err(FirmwareFileError::Truncated { expected: bytes.len() as u64, actual: truncated.len() as u64 })
+        );
+        assert!(matches!(inspect_firmware(&bytes[..100]), Err(FirmwareFileError::Truncated { expected: 256, .. })));
+
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0xff; 16]);
+        assert!(matches!(inspect_firmware(&padded), Err(FirmwareFileError::TrailingData { .. })));
+
+        assert_eq!(inspect_firmware(b"PK\x03\x04 not firmware"), Err(FirmwareFileError::NotFirmware));
+        assert_eq!(inspect_firmware(b""), Err(FirmwareFileError::NotFirmware));
+        assert!(matches!(
+            inspect_firmware_path(Path::new("/nonexistent/firmware.keepkey.bin")),
+            Err(FirmwareFileError::Io { .. })
+        ));
+
+        // A flipped code byte still parses, but no longer matches a signed release
+        let mut tampered = bytes.clone();
+        tampered[HEADER_SIZE + 42] ^= 0x01;
+        let metadata = inspect_firmware(&tampered).unwrap();
+        assert!(!metadata.signature_valid);
+        assert_eq!((metadata.version, metadata.model), (None, None));
+
+        // A release whose signatures were stripped isn't signed either
+        let mut unsigned = bytes;
+        unsigned[8..11].fill(0);
+        assert!(!inspect_firmware(&unsigned).unwrap().signature_valid);
+    }
+}
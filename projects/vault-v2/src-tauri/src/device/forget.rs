@@ -0,0 +1,134 @@
+//! Host-side cleanup for devices the user no longer uses. Nothing is sent to
+//! the device; wiping it is `wipe_device`. BIP-329 labels stay, they belong to
+//! the seed rather than the device (see `labels`), and so does the record of
+//! the device's firmware updates (see `update_history`).
+
+use std::collections::HashSet;
+use tauri::{AppHandle, State};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::active::ActiveChangeReason;
+
+/// Forgotten devices the monitor hasn't rescanned yet. A connected device in
+/// here is dropped from the monitor's known devices so it shows up as new.
+static FORGOTTEN: once_cell::sync::Lazy<std::sync::Mutex<HashSet<String>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashSet::new()));
+
+fn with_forgotten<T>(f: impl FnOnce(&mut HashSet<String>) -> T) -> T {
+    match FORGOTTEN.lock() {
+        Ok(mut forgotten) => f(&mut forgotten),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Devices forgotten since the last call; the monitor drains this every scan
+pub fn take_forgotten() -> HashSet<String> {
+    with_forgotten(std::mem::take)
+}
+
+/// Drop every in-memory cache entry for `unique_id`. Returns whether it was the
+/// active device.
+pub fn forget_host_state(unique_id: &str) -> bool {
+    crate::commands::invalidate_cached_features(unique_id);
+    crate::payload_limits::forget_withheld_features(unique_id);
+    crate::device::identity::forget(unique_id);
+    crate::device::metrics::forget(unique_id);
+    crate::device::state::forget(unique_id);
+    crate::device::probe::with_tracker(|tracker| tracker.remove(unique_id));
+    crate::device::attention::forget(unique_id);
+    crate::device::session::forget_activity(unique_id);
+    crate::device::benchmark::forget(unique_id);
+    crate::device::update_eta::forget(unique_id);
+    crate::chain::receive::forget(unique_id);
+    crate::device::connection::forget(unique_id);
+    crate::device::udev::forget(unique_id);
+    crate::device::queue::forget_device_state(unique_id);
+    with_forgotten(|forgotten| forgotten.insert(unique_id.to_string()));
+    crate::device::active::clear_if(unique_id)
+}
+
+/// Payload of `device:forgotten`
+pub fn forgotten_payload(unique_id: &str, was_connected: bool) -> serde_json::Value {
+    serde_json::json!({
+        "unique_id": unique_id,
+        "was_connected": was_connected
+    })
+}
+
+/// Forget everything the vault remembers about a device: cached features,
+/// metrics, its worker, the active-device selection and the remembered active
+/// serial. A device that is still plugged in is reported as newly connected
+/// on the next scan.
+#[tauri::command]
+pub async fn forget_device(
+    unique_id: String,
+    app: AppHandle,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<(), String> {
+    let mut devices = keepkey_rust::features::list_connected_devices();
+    devices.extend(crate::device::mock::connected_devices());
+    let connected = devices.iter().find(|d| d.unique_id == unique_id);
+    let serial = connected
+        .and_then(|d| d.serial_number.clone())
+        .unwrap_or_else(|| unique_id.clone());
+    let was_connected = connected.is_some();
+
+    if queue_manager.remove_and_shutdown(&unique_id).await {
+        println!("🧹 Stopped worker for forgotten device {}", unique_id);
+    }
+    let was_active = forget_host_state(&unique_id);
+    if let Err(e) = crate::commands::forget_active_device_serial(&serial) {
+        log::warn!("Failed to clear remembered active device: {}", e);
+    }
+    println!("🧹 Forgot device {}", unique_id);
+
+    if was_active {
+        let payload = crate::device::active::active_changed_payload(
+            None,
+            Some(&unique_id),
+            ActiveChangeReason::Forgotten,
+        );
+        crate::commands::emit_or_queue_event(&app, "device:active-changed", payload).await?;
+    }
+    crate::commands::emit_or_queue_event(&app, "device:forgotten", forgotten_payload(&unique_id, was_connected)).await?;
+
+    // Let a connected device come back as new right away rather than at the next poll
+    if was_connected {
+        let _ = crate::event_controller::rescan_devices(app).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::state::DeviceState;
+    use std::time::Instant;
+
+    #[test]
+    fn test_forget_clears_host_caches() {
+        let id = "forget-test-device";
+        let features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features {
+            device_id: Some("F0F0".to_string()),
+            ..Default::default()
+        });
+        crate::commands::cache_device_features(id, &features);
+        crate::device::state::transition(id, DeviceState::Connected).unwrap();
+        crate::device::metrics::record_operation(id, "GetFeatures", true, std::time::Duration::ZERO);
+        crate::device::probe::with_tracker(|tracker| tracker.start(id, Instant::now()));
+        crate::device::active::select(id);
+
+        assert!(forget_host_state(id));
+        assert!(crate::commands::cached_device_features(id).is_none());
+        assert_eq!(crate::device::identity::known_hardware_id(id), None);
+        assert_eq!(crate::device::state::device_state(id), DeviceState::Disconnected);
+        assert!(!crate::device::metrics::knows_device(id));
+        assert_eq!(crate::device::active::active_device(), None);
+        assert!(take_forgotten().contains(id));
+        assert!(!take_forgotten().contains(id));
+
+        // Reappearing starts from scratch: Disconnected -> Connected is allowed again
+        assert!(crate::device::state::transition(id, DeviceState::Connected).unwrap().is_some());
+        crate::device::state::forget(id);
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+use tauri::State;
+
+use keepkey_rust::features::DeviceFeatures;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::lru::DeviceLru;
+
+/// `Features.device_id` of each USB id, learned whenever features are cached.
+/// Unlike the USB id it doesn't change with the port, the serial descriptor or
+/// the bootloader/firmware PID switch.
+static HARDWARE_IDS: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<String>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+fn with_ids<T>(f: impl FnOnce(&mut DeviceLru<String>) -> T) -> T {
+    match HARDWARE_IDS.lock() {
+        Ok(mut ids) => f(&mut ids),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// The device's own id from `features`, `None` for devices that don't report
+/// one (bootloader mode, OOB devices)
+pub fn hardware_id(features: &DeviceFeatures) -> Option<&str> {
+    features.device_id.as_deref().map(str::trim).filter(|id| !id.is_empty())
+}
+
+/// Remember the hardware id `features` report for `unique_id`
+pub fn remember(unique_id: &str, features: &DeviceFeatures) {
+    if let Some(id) = hardware_id(features) {
+        let id = id.to_string();
+        with_ids(|ids| ids.insert(unique_id, id));
+    }
+}
+
+/// Stable identity of `unique_id`: its hardware id when known, the USB id otherwise
+pub fn stable_id(unique_id: &str) -> String {
+    known_hardware_id(unique_id).unwrap_or_else(|| unique_id.to_string())
+}
+
+pub fn known_hardware_id(unique_id: &str) -> Option<String> {
+    with_ids(|ids| ids.get(unique_id).cloned())
+}
+
+pub fn forget(unique_id: &str) {
+    with_ids(|ids| ids.remove(unique_id));
+}
+
+/// Whether two USB ids are the same physical device by hardware id; `None`
+/// when either hardware id isn't known yet
+pub fn same_hardware(a: &str, b: &str) -> Option<bool> {
+    with_ids(|ids| Some(ids.peek(a)? == ids.peek(b)?))
+}
+
+/// The device's internal id (stable across reconnects and ports), or its USB
+/// id for devices that don't report one
+#[tauri::command]
+pub async fn get_device_id(unique_id: String, queue_manager: State<'_, DeviceQueueManager>) -> Result<String, String> {
+    if let Some(id) = known_hardware_id(&unique_id) {
+        return Ok(id);
+    }
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+        Ok(raw_features) => {
+            let features = crate::commands::convert_features_to_device_features(raw_features);
+            crate::commands::cache_device_features(&unique_id, &features);
+            Ok(stable_id(&unique_id))
+        }
+        // Bootloaders that can't answer still have a usable USB identity
+        Err(e) => {
+            println!("⚠️ No features for {} ({}), using its USB id", unique_id, e);
+            Ok(unique_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(device_id: Option<&str>) -> DeviceFeatures {
+        crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features {
+            device_id: device_id.map(str::to_string),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_hardware_id_survives_usb_id_changes() {
+        remember("identity-serial", &features(Some("A1B2C3")));
+        // The same device after a bootloader update enumerates with a bus/addr id
+        remember("keepkey_2b24_0001_bus1_addr7", &features(Some("A1B2C3")));
+        remember("identity-other", &features(Some("FFFF")));
+        remember("identity-bootloader", &features(None));
+
+        assert_eq!(stable_id("identity-serial"), "A1B2C3");
+        assert_eq!(same_hardware("identity-serial", "keepkey_2b24_0001_bus1_addr7"), Some(true));
+        assert_eq!(same_hardware("identity-serial", "identity-other"), Some(false));
+        assert_eq!(same_hardware("identity-serial", "identity-bootloader"), None);
+        // Devices that don't report an id fall back to their USB id
+        assert_eq!(stable_id("identity-bootloader"), "identity-bootloader");
+        assert_eq!(hardware_id(&features(Some("  "))), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Least-recently-seen eviction for the per-device maps. Port changes and
+//! bootloader/firmware PID switches give the same device new ids, so a kiosk
+//! cycling through devices would grow every map that is only cleaned on
+//! disconnect. Connected devices and the active device are pinned: their
+//! entries are never evicted, even if that leaves a map over capacity.
+//!
+//! The state machine needs none of this, it only holds connected devices.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::device::state::DeviceState;
+
+/// Entries each per-device map keeps by default, well above the devices a
+/// single host has connected at once
+pub const DEFAULT_DEVICE_CACHE_CAPACITY: usize = 64;
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_DEVICE_CACHE_CAPACITY);
+
+pub fn capacity() -> usize {
+    CAPACITY.load(Ordering::Relaxed)
+}
+
+pub fn set_capacity(capacity: usize) {
+    CAPACITY.store(capacity.max(1), Ordering::Relaxed);
+}
+
+/// Whether `device_id`'s entries must be kept: it is connected or active.
+/// Takes the state and active-device locks, so those must never be held while
+/// inserting into a `DeviceLru`.
+pub fn is_pinned(device_id: &str) -> bool {
+    crate::device::state::device_state(device_id) != DeviceState::Disconnected
+        || crate::device::active::active_device().as_deref() == Some(device_id)
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    seen: u64,
+}
+
+/// Map from device id to `V` that evicts the least recently seen unpinned
+/// device once it holds more than `capacity()` entries
+#[derive(Debug)]
+pub struct DeviceLru<V> {
+    entries: HashMap<String, Entry<V>>,
+    clock: u64,
+}
+
+impl<V> Default for DeviceLru<V> {
+    fn default() -> Self {
+        Self { entries: HashMap::new(), clock: 0 }
+    }
+}
+
+impl<V> DeviceLru<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Look up `device_id`, marking it as seen
+    pub fn get(&mut self, device_id: &str) -> Option<&V> {
+        self.get_mut(device_id).map(|value| &*value)
+    }
+
+    pub fn get_mut(&mut self, device_id: &str) -> Option<&mut V> {
+        let now = self.tick();
+        let entry = self.entries.get_mut(device_id)?;
+        entry.seen = now;
+        Some(&mut entry.value)
+    }
+
+    /// Look up `device_id` without marking it as seen
+    pub fn peek(&self, device_id: &str) -> Option<&V> {
+        self.entries.get(device_id).map(|entry| &entry.value)
+    }
+
+    pub fn contains(&self, device_id: &str) -> bool {
+        self.entries.contains_key(device_id)
+    }
+
+    /// Insert or replace `device_id`'s value, evicting other devices if the map
+    /// is over the configured capacity. Returns the replaced value.
+    pub fn insert(&mut self, device_id: &str, value: V) -> Option<V> {
+        self.insert_bounded(device_id, value, capacity(), is_pinned)
+    }
+
+    /// `insert` with an explicit capacity and pin check
+    pub fn insert_bounded(
+        &mut self,
+        device_id: &str,
+        value: V,
+        capacity: usize,
+        pinned: impl Fn(&str) -> bool,
+    ) -> Option<V> {
+        let seen = self.tick();
+        let previous = self
+            .entries
+            .insert(device_id.to_string(), Entry { value, seen })
+            .map(|entry| entry.value);
+        self.evict(capacity, device_id, pinned);
+        previous
+    }
+
+    /// `device_id`'s value, inserting `f()` first if it has none
+    pub fn get_or_insert_with(&mut self, device_id: &str, f: impl FnOnce() -> V) -> &mut V {
+        if !self.contains(device_id) {
+            self.insert(device_id, f());
+        }
+        self.get_mut(device_id).expect("entry was just inserted")
+    }
+
+    pub fn remove(&mut self, device_id: &str) -> Option<V> {
+        self.entries.remove(device_id).map(|entry| entry.value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.entries.iter().map(|(id, entry)| (id, &entry.value))
+    }
+
+    /// Drop least recently seen unpinned devices until at most `capacity` are
+    /// left. `keep` is the device just inserted.
+    fn evict(&mut self, capacity: usize, keep: &str, pinned: impl Fn(&str) -> bool) {
+        while self.entries.len() > capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(id, _)| id.as_str() != keep && !pinned(id))
+                .min_by_key(|(_, entry)| entry.seen)
+                .map(|(id, _)| id.clone());
+            let Some(oldest) = oldest else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            log::debug!("Evicted least recently seen device {}", oldest);
+        }
+    }
+}
+
+impl<V: Clone> DeviceLru<V> {
+    /// Snapshot of every entry, by device id
+    pub fn to_map(&self) -> HashMap<String, V> {
+        self.iter().map(|(id, value)| (id.clone(), value.clone())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycling_devices_stays_bounded() {
+        let mut map = DeviceLru::new();
+        let pinned = |id: &str| id == "connected";
+        map.insert_bounded("connected", 0, 8, pinned);
+        for i in 0..1000 {
+            map.insert_bounded(&format!("keepkey_bus1_addr{}", i), i, 8, pinned);
+            assert!(map.len() <= 8);
+        }
+        // The connected device was seen first but is never evicted
+        assert_eq!(map.peek("connected"), Some(&0));
+        assert!(map.contains("keepkey_bus1_addr999"));
+        assert!(!map.contains("keepkey_bus1_addr0"));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_seen() {
+        let mut map = DeviceLru::new();
+        let unpinned = |_: &str| false;
+        map.insert_bounded("A", 1, 2, unpinned);
+        map.insert_bounded("B", 2, 2, unpinned);
+        // Reading A makes B the least recently seen
+        assert_eq!(map.get("A"), Some(&1));
+        map.insert_bounded("C", 3, 2, unpinned);
+        assert!(map.contains("A") && map.contains("C") && !map.contains("B"));
+
+        // With everything else pinned the map may exceed its capacity
+        map.insert_bounded("D", 4, 2, |id: &str| id != "D");
+        assert_eq!(map.len(), 3);
+    }
+}
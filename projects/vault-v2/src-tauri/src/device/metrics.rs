@@ -0,0 +1,273 @@
+//! Prometheus-style counters for fleet and kiosk deployments that scrape the
+//! vault, served as text on the bridge's `/metrics` route and through
+//! `metrics_text`. Both need the `bridge` feature; recording always happens.
+#![cfg_attr(not(feature = "bridge"), allow(dead_code))]
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::time::Duration;
+use tauri::State;
+
+use crate::commands::DeviceQueueManager;
+use crate::device::lru::DeviceLru;
+
+/// Devices that get their own `device_id` label; the rest share `other`, so a
+/// long-running kiosk can't grow the series without bound
+pub const MAX_LABELLED_DEVICES: usize = 32;
+const OTHER_DEVICE: &str = "other";
+
+/// Upper bounds of the latency buckets, in seconds. Signing waits for the user,
+/// so the top buckets are wide.
+pub const LATENCY_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Observations per bucket of `LATENCY_BUCKETS`, not cumulative
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len()];
+        }
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    labelled_devices: HashSet<String>,
+    /// (device_id, operation, outcome)
+    operations: BTreeMap<(String, String, String), u64>,
+    latency: BTreeMap<String, Histogram>,
+    /// Devices that connected before; bounded like the other per-device maps
+    seen_devices: DeviceLru<()>,
+    reconnects: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    /// The `device_id` label for `device_id`
+    fn device_label(&mut self, device_id: &str) -> String {
+        if self.labelled_devices.contains(device_id) {
+            return device_id.to_string();
+        }
+        if self.labelled_devices.len() < MAX_LABELLED_DEVICES {
+            self.labelled_devices.insert(device_id.to_string());
+            return device_id.to_string();
+        }
+        OTHER_DEVICE.to_string()
+    }
+
+    pub fn record_operation(&mut self, device_id: &str, operation: &str, ok: bool, elapsed: Duration) {
+        let device = self.device_label(device_id);
+        let outcome = if ok { "success" } else { "error" };
+        *self.operations.entry((device, operation.to_string(), outcome.to_string())).or_default() += 1;
+        self.latency.entry(operation.to_string()).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    /// A device entered `Connected`; every time after the first is a reconnect
+    pub fn record_connected(&mut self, device_id: &str) {
+        if self.seen_devices.insert(device_id, ()).is_some() {
+            let device = self.device_label(device_id);
+            *self.reconnects.entry(device).or_default() += 1;
+        }
+    }
+
+    /// Drop a device's series, freeing its `device_id` label. Latency histograms
+    /// are per operation and keep its observations.
+    pub fn forget(&mut self, device_id: &str) {
+        self.seen_devices.remove(device_id);
+        if self.labelled_devices.remove(device_id) {
+            self.operations.retain(|(device, _, _), _| device != device_id);
+            self.reconnects.remove(device_id);
+        }
+    }
+}
+
+/// Gauges read when the metrics are rendered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gauges {
+    pub connected_devices: usize,
+    pub workers: usize,
+    pub oob_fallbacks: u64,
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Prometheus text exposition format (version 0.0.4)
+pub fn render(metrics: &Metrics, gauges: Gauges) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP keepkey_connected_devices Devices currently connected.");
+    let _ = writeln!(out, "# TYPE keepkey_connected_devices gauge");
+    let _ = writeln!(out, "keepkey_connected_devices {}", gauges.connected_devices);
+
+    let _ = writeln!(out, "# HELP keepkey_workers Device queue workers running.");
+    let _ = writeln!(out, "# TYPE keepkey_workers gauge");
+    let _ = writeln!(out, "keepkey_workers {}", gauges.workers);
+
+    let _ = writeln!(out, "# HELP keepkey_operations_total Device operations by type and outcome.");
+    let _ = writeln!(out, "# TYPE keepkey_operations_total counter");
+    for ((device, operation, outcome), count) in &metrics.operations {
+        let _ = writeln!(
+            out,
+            "keepkey_operations_total{{device_id=\"{}\",operation=\"{}\",outcome=\"{}\"}} {}",
+            escape_label(device),
+            escape_label(operation),
+            outcome,
+            count
+        );
+    }
+
+    let _ = writeln!(out, "# HELP keepkey_operation_duration_seconds Device operation latency.");
+    let _ = writeln!(out, "# TYPE keepkey_operation_duration_seconds histogram");
+    for (operation, histogram) in &metrics.latency {
+        let operation = escape_label(operation);
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "keepkey_operation_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                operation, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "keepkey_operation_duration_seconds_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+            operation, histogram.count
+        );
+        let _ = writeln!(out, "keepkey_operation_duration_seconds_sum{{operation=\"{}\"}} {}", operation, histogram.sum);
+        let _ = writeln!(out, "keepkey_operation_duration_seconds_count{{operation=\"{}\"}} {}", operation, histogram.count);
+    }
+
+    let _ = writeln!(out, "# HELP keepkey_oob_fallbacks_total Feature probes that fell back to OOB bootloader detection.");
+    let _ = writeln!(out, "# TYPE keepkey_oob_fallbacks_total counter");
+    let _ = writeln!(out, "keepkey_oob_fallbacks_total {}", gauges.oob_fallbacks);
+
+    let _ = writeln!(out, "# HELP keepkey_reconnects_total Times a device connected again after it was first seen.");
+    let _ = writeln!(out, "# TYPE keepkey_reconnects_total counter");
+    for (device, count) in &metrics.reconnects {
+        let _ = writeln!(out, "keepkey_reconnects_total{{device_id=\"{}\"}} {}", escape_label(device), count);
+    }
+
+    out
+}
+
+static METRICS: once_cell::sync::Lazy<std::sync::Mutex<Metrics>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(Metrics::default()));
+
+fn with_metrics<T>(f: impl FnOnce(&mut Metrics) -> T) -> T {
+    match METRICS.lock() {
+        Ok(mut metrics) => f(&mut metrics),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+pub fn record_operation(device_id: &str, operation: &str, ok: bool, elapsed: Duration) {
+    with_metrics(|metrics| metrics.record_operation(device_id, operation, ok, elapsed));
+}
+
+pub fn record_connected(device_id: &str) {
+    with_metrics(|metrics| metrics.record_connected(device_id));
+}
+
+pub fn forget(device_id: &str) {
+    with_metrics(|metrics| metrics.forget(device_id));
+}
+
+/// Whether any series or reconnect bookkeeping exists for `device_id`
+pub fn knows_device(device_id: &str) -> bool {
+    with_metrics(|metrics| metrics.seen_devices.contains(device_id) || metrics.labelled_devices.contains(device_id))
+}
+
+/// Current metrics in the Prometheus text format
+#[cfg(feature = "bridge")]
+pub async fn metrics_text_for(queue_manager: &DeviceQueueManager) -> String {
+    let gauges = Gauges {
+        connected_devices: crate::device::state::connected_device_count(),
+        workers: queue_manager.lock().await.len(),
+        oob_fallbacks: crate::device::oob_stats::snapshot().total.oob_fallback,
+    };
+    with_metrics(|metrics| render(metrics, gauges))
+}
+
+/// Metrics in the Prometheus text format, the same text the bridge serves on `/metrics`
+#[tauri::command]
+pub async fn metrics_text(queue_manager: State<'_, DeviceQueueManager>) -> Result<String, String> {
+    #[cfg(feature = "bridge")]
+    {
+        Ok(metrics_text_for(&queue_manager).await)
+    }
+    #[cfg(not(feature = "bridge"))]
+    {
+        let _ = queue_manager;
+        Err("Metrics export needs the bridge feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let mut metrics = Metrics::default();
+        metrics.record_operation("A", "GetXpub", true, Duration::from_millis(40));
+        metrics.record_operation("A", "GetXpub", true, Duration::from_millis(300));
+        metrics.record_operation("A", "SignTransaction", false, Duration::from_secs(60));
+        metrics.record_connected("A");
+        metrics.record_connected("A");
+
+        let text = render(&metrics, Gauges { connected_devices: 1, workers: 1, oob_fallbacks: 3 });
+        assert!(text.contains("keepkey_connected_devices 1\n"));
+        assert!(text.contains("keepkey_operations_total{device_id=\"A\",operation=\"GetXpub\",outcome=\"success\"} 2\n"));
+        assert!(text.contains("keepkey_operations_total{device_id=\"A\",operation=\"SignTransaction\",outcome=\"error\"} 1\n"));
+        // Buckets are cumulative
+        assert!(text.contains("keepkey_operation_duration_seconds_bucket{operation=\"GetXpub\",le=\"0.05\"} 1\n"));
+        assert!(text.contains("keepkey_operation_duration_seconds_bucket{operation=\"GetXpub\",le=\"0.5\"} 2\n"));
+        // Slower than the top bucket only shows in +Inf
+        assert!(text.contains("keepkey_operation_duration_seconds_bucket{operation=\"SignTransaction\",le=\"30\"} 0\n"));
+        assert!(text.contains("keepkey_operation_duration_seconds_bucket{operation=\"SignTransaction\",le=\"+Inf\"} 1\n"));
+        assert!(text.contains("keepkey_oob_fallbacks_total 3\n"));
+        assert!(text.contains("keepkey_reconnects_total{device_id=\"A\"} 1\n"));
+    }
+
+    #[test]
+    fn test_device_labels_are_bounded() {
+        let mut metrics = Metrics::default();
+        for i in 0..MAX_LABELLED_DEVICES + 5 {
+            metrics.record_operation(&format!("dev{}", i), "GetFeatures", true, Duration::ZERO);
+        }
+        let devices: HashSet<&str> = metrics.operations.keys().map(|(device, _, _)| device.as_str()).collect();
+        assert_eq!(devices.len(), MAX_LABELLED_DEVICES + 1);
+        assert_eq!(metrics.operations[&("other".to_string(), "GetFeatures".to_string(), "success".to_string())], 5);
+        assert_eq!(escape_label("a\"b"), "a\\\"b");
+    }
+
+    #[test]
+    fn test_forget_device() {
+        let mut metrics = Metrics::default();
+        metrics.record_operation("A", "GetXpub", true, Duration::ZERO);
+        metrics.record_connected("A");
+        metrics.record_connected("A");
+        metrics.record_operation("B", "GetXpub", true, Duration::ZERO);
+
+        metrics.forget("A");
+        assert!(metrics.operations.keys().all(|(device, _, _)| device != "A"));
+        assert!(!metrics.reconnects.contains_key("A"));
+        // Coming back after being forgotten is a first connect, not a reconnect
+        metrics.record_connected("A");
+        assert!(!metrics.reconnects.contains_key("A"));
+        assert_eq!(metrics.latency["GetXpub"].count, 2);
+    }
+}
@@ -0,0 +1,428 @@
+//! Scripted virtual devices for building the UI without hardware. A mock device
+//! is listed next to the USB devices, so the device monitor connects, probes
+//! and reports it exactly like a real one; its worker answers from a script
+//! instead of a transport.
+//!
+//! Needs the `mock-device` feature. Start the app with `--mock-device <scenario>`
+//! or call `enable_mock_device`.
+#![cfg_attr(not(feature = "mock-device"), allow(dead_code))]
+
+use keepkey_rust::cancel::CancelCell;
+use keepkey_rust::device_queue::{DeviceCmd, DeviceQueueHandle};
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use keepkey_rust::messages::{self, Message};
+use keepkey_rust::screen_hint::{ScreenHint, ScreenHintCell};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+/// Launch flag that connects a mock device at startup
+pub const MOCK_DEVICE_FLAG: &str = "--mock-device";
+
+const MOCK_ID_PREFIX: &str = "mock-";
+
+/// BIP32 test vector 1, so the xpub is valid but obviously not a wallet
+const MOCK_XPUB: &str = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+const MOCK_ADDRESS: &str = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+/// What a signed transaction comes back as; not a real transaction
+const MOCK_SIGNED_TX: &[u8] = b"mock-signed-transaction";
+
+/// How long the scripted user takes to confirm a transaction on the device
+const MOCK_CONFIRM_DELAY: Duration = Duration::from_millis(300);
+
+/// What the mock device is doing when it connects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockScenario {
+    /// Initialized, unlocked, current firmware
+    Ready,
+    /// In bootloader mode with no firmware
+    NeedsFirmware,
+    /// Current firmware, no seed
+    NeedsInit,
+    /// PIN protected; any PIN unlocks it
+    Locked,
+    /// Every request fails as if another application held the device
+    AccessError,
+}
+
+impl MockScenario {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        serde_json::from_value(serde_json::json!(name.replace('-', "_")))
+            .map_err(|_| format!("Unknown mock device scenario: {}", name))
+    }
+
+    fn name(self) -> String {
+        serde_json::to_value(self).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    pub fn device_id(self) -> String {
+        format!("{}{}", MOCK_ID_PREFIX, self.name().replace('_', "-"))
+    }
+
+    fn from_device_id(device_id: &str) -> Option<Self> {
+        device_id.strip_prefix(MOCK_ID_PREFIX).and_then(|name| Self::parse(name).ok())
+    }
+
+    pub fn usb_device(self) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(
+            self.device_id(),
+            0x2b24,
+            0x0002,
+            Some("KeepKey".to_string()),
+            Some("KeepKey (mock)".to_string()),
+            Some(format!("MOCK{}", self.device_id().to_uppercase())),
+        )
+    }
+}
+
+static MOCK_DEVICES: once_cell::sync::Lazy<std::sync::Mutex<BTreeSet<MockScenario>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(BTreeSet::new()));
+
+fn with_devices<T>(f: impl FnOnce(&mut BTreeSet<MockScenario>) -> T) -> T {
+    match MOCK_DEVICES.lock() {
+        Ok(mut devices) => f(&mut devices),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Mock devices currently plugged in, listed with the USB devices
+pub fn connected_devices() -> Vec<FriendlyUsbDevice> {
+    with_devices(|devices| devices.iter().map(|scenario| scenario.usb_device()).collect())
+}
+
+/// Scenario given with `--mock-device <scenario>` or `--mock-device=<scenario>`
+pub fn scenario_from_args(args: impl IntoIterator<Item = String>) -> Option<Result<MockScenario, String>> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == MOCK_DEVICE_FLAG {
+            return Some(args.next().ok_or_else(|| format!("{} needs a scenario", MOCK_DEVICE_FLAG)).and_then(|s| MockScenario::parse(&s)));
+        }
+        if let Some(name) = arg.strip_prefix(MOCK_DEVICE_FLAG).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(MockScenario::parse(name));
+        }
+    }
+    None
+}
+
+/// Plug in the mock device of `--mock-device`, if the app was started with it
+pub fn enable_from_args() {
+    if !cfg!(feature = "mock-device") {
+        return;
+    }
+    match scenario_from_args(std::env::args().skip(1)) {
+        Some(Ok(scenario)) => {
+            with_devices(|devices| devices.insert(scenario));
+            println!("🧪 Mock device {} connected", scenario.device_id());
+        }
+        Some(Err(e)) => println!("⚠️ {}", e),
+        None => {}
+    }
+}
+
+/// The scripted state of one mock device
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    scenario: MockScenario,
+    initialized: bool,
+    unlocked: bool,
+    label: String,
+    /// Request that got a PIN prompt, answered once the PIN is entered
+    pending: Option<Message>,
+}
+
+impl MockDevice {
+    pub fn new(scenario: MockScenario) -> Self {
+        Self {
+            scenario,
+            initialized: scenario != MockScenario::NeedsInit,
+            unlocked: scenario != MockScenario::Locked,
+            label: "Mock KeepKey".to_string(),
+            pending: None,
+        }
+    }
+
+    fn access_error(&self) -> Option<String> {
+        (self.scenario == MockScenario::AccessError).then(|| {
+            format!("🔒 Device Already In Use: mock device {} is claimed by another application", self.scenario.device_id())
+        })
+    }
+
+    pub fn features(&self) -> Result<messages::Features, String> {
+        if let Some(e) = self.access_error() {
+            return Err(e);
+        }
+        let bootloader_mode = self.scenario == MockScenario::NeedsFirmware;
+        let (major, minor, patch) = if bootloader_mode { (2, 1, 4) } else { (7, 10, 0) };
+        Ok(messages::Features {
+            vendor: Some("keepkey.com".to_string()),
+            major_version: Some(major),
+            minor_version: Some(minor),
+            patch_version: Some(patch),
+            bootloader_mode: Some(bootloader_mode),
+            device_id: Some(format!("MOCK{}", self.scenario.device_id().to_uppercase())),
+            label: (!bootloader_mode).then(|| self.label.clone()),
+            model: Some("K1-14AM".to_string()),
+            initialized: Some(self.initialized && !bootloader_mode),
+            pin_protection: Some(self.scenario == MockScenario::Locked),
+            pin_cached: Some(self.scenario == MockScenario::Locked && self.unlocked),
+            passphrase_protection: Some(false),
+            ..Default::default()
+        })
+    }
+
+    fn needs_pin(message: &Message) -> bool {
+        matches!(
+            message,
+            Message::GetAddress(_) | Message::GetPublicKey(_) | Message::SignTx(_) | Message::ApplySettings(_) | Message::ChangePin(_)
+        )
+    }
+
+    /// The device's answer to `message`
+    pub fn answer(&mut self, message: Message) -> Result<Message, String> {
+        if let Some(e) = self.access_error() {
+            return Err(e);
+        }
+        if self.scenario == MockScenario::NeedsFirmware
+            && !matches!(message, Message::Initialize(_) | Message::GetFeatures(_) | Message::Ping(_))
+        {
+            return Ok(failure("Unknown message"));
+        }
+        if !self.unlocked && Self::needs_pin(&message) {
+            self.pending = Some(message);
+            return Ok(messages::PinMatrixRequest { r#type: Some(1) }.into());
+        }
+        Ok(match message {
+            Message::Initialize(_) | Message::GetFeatures(_) => self.features()?.into(),
+            Message::Ping(ping) => messages::Success { message: ping.message }.into(),
+            Message::PinMatrixAck(_) => {
+                self.unlocked = true;
+                match self.pending.take() {
+                    Some(pending) => return self.answer(pending),
+                    None => self.features()?.into(),
+                }
+            }
+            Message::GetAddress(_) if !self.initialized => failure("Device not initialized"),
+            Message::GetAddress(_) => messages::Address { address: MOCK_ADDRESS.to_string() }.into(),
+            Message::GetPublicKey(_) if !self.initialized => failure("Device not initialized"),
+            Message::GetPublicKey(_) => messages::PublicKey { xpub: Some(MOCK_XPUB.to_string()), ..Default::default() }.into(),
+            Message::SignTx(_) if !self.initialized => failure("Device not initialized"),
+            // Signed in one step: the confirmation is simulated by the worker
+            Message::SignTx(_) => messages::TxRequest {
+                request_type: Some(messages::RequestType::Txfinished as i32),
+                details: None,
+                serialized: Some(messages::TxRequestSerializedType {
+                    signature_index: Some(0),
+                    signature: Some(vec![0x30; 71]),
+                    serialized_tx: Some(MOCK_SIGNED_TX.to_vec()),
+                }),
+            }
+            .into(),
+            Message::ApplySettings(settings) => {
+                if let Some(label) = settings.label {
+                    self.label = label;
+                }
+                success()
+            }
+            Message::WipeDevice(_) => {
+                self.initialized = false;
+                success()
+            }
+            Message::ClearSession(_) => {
+                self.unlocked = self.scenario != MockScenario::Locked;
+                success()
+            }
+            Message::ButtonAck(_) | Message::Cancel(_) | Message::ApplyPolicies(_) => success(),
+            other => failure(&format!("{:?} is not simulated by the mock device", other.message_type())),
+        })
+    }
+}
+
+fn success() -> Message {
+    messages::Success { message: None }.into()
+}
+
+fn failure(message: &str) -> Message {
+    messages::Failure { code: Some(1), message: Some(message.to_string()) }.into()
+}
+
+/// `Failure_ActionCancelled`, as the device answers a cancelled prompt
+fn cancelled() -> Message {
+    messages::Failure { code: Some(4), message: Some("Action cancelled by user".to_string()) }.into()
+}
+
+/// Show a transaction confirmation until the scripted press; false if the
+/// prompt was cancelled through the handle first
+async fn confirm_on_device(screen_hint: &ScreenHintCell, cancel: &CancelCell) -> bool {
+    // Like the real worker, a cancel only applies to the prompt it was raised during
+    cancel.take();
+    screen_hint.set(ScreenHint::ConfirmTransaction);
+    let pressed_at = Instant::now() + MOCK_CONFIRM_DELAY;
+    let confirmed = loop {
+        if cancel.take() {
+            break false;
+        }
+        if Instant::now() >= pressed_at {
+            break true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    screen_hint.set(ScreenHint::Idle);
+    confirmed
+}
+
+/// Worker for a mock device id, `None` for real devices
+pub fn spawn_worker(device_id: &str) -> Option<DeviceQueueHandle> {
+    let scenario = MockScenario::from_device_id(device_id)?;
+    Some(spawn_scripted_worker(device_id, MockDevice::new(scenario)))
+}
+
+/// Worker answering for `device` under any id, so tests can run several
+/// devices of the same scenario side by side
+pub(crate) fn spawn_scripted_worker(device_id: &str, mut device: MockDevice) -> DeviceQueueHandle {
+    let screen_hint = ScreenHintCell::default();
+    let cancel = CancelCell::default();
+    let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel(8);
+    let (worker_hint, worker_cancel) = (screen_hint.clone(), cancel.clone());
+    tauri::async_runtime::spawn(async move {
+        while let Some(cmd) = cmd_rx.recv().await {
+            match cmd {
+                DeviceCmd::GetFeatures { respond_to, .. } => {
+                    let _ = respond_to.send(device.features().map_err(anyhow::Error::msg));
+                }
+                DeviceCmd::GetAddress { respond_to, .. } => {
+                    let address = match device.answer(messages::GetAddress::default().into()) {
+                        Ok(Message::Address(address)) => Ok(address.address),
+                        Ok(other) => Err(anyhow::anyhow!("Mock device answered {:?}", other.message_type())),
+                        Err(e) => Err(anyhow::Error::msg(e)),
+                    };
+                    let _ = respond_to.send(address);
+                }
+                DeviceCmd::SendRaw { message, respond_to, .. } => {
+                    let confirms = matches!(message, Message::SignTx(_)) && device.unlocked && device.initialized;
+                    let answer = if confirms && !confirm_on_device(&worker_hint, &worker_cancel).await {
+                        Ok(cancelled())
+                    } else {
+                        device.answer(message)
+                    };
+                    let _ = respond_to.send(answer.map_err(anyhow::Error::msg));
+                }
+                DeviceCmd::GetMasterFingerprint { respond_to, .. } => {
+                    let _ = respond_to.send(Ok(0x3442_193e));
+                }
+                DeviceCmd::UpdateBootloader { respond_to, .. } | DeviceCmd::UpdateFirmware { respond_to, .. } => {
+                    // Like the real device, only the bootloader accepts an upload;
+                    // the simulated flash doesn't change what the mock reports
+                    let result = if device.scenario == MockScenario::NeedsFirmware {
+                        Ok(true)
+                    } else {
+                        Err(anyhow::anyhow!("The mock device is not in bootloader mode"))
+                    };
+                    let _ = respond_to.send(result);
+                }
+                DeviceCmd::ExclusiveSession { respond_to, .. } => {
+                    let _ = respond_to.send(Err(anyhow::anyhow!("The mock device has no exclusive sessions")));
+                }
+                DeviceCmd::ClearCache { respond_to } => {
+                    let _ = respond_to.send(Ok(()));
+                }
+                DeviceCmd::Shutdown { respond_to } => {
+                    let _ = respond_to.send(Ok(()));
+                    break;
+                }
+            }
+        }
+    });
+    println!("🧪 Spawned mock worker for {}", device_id);
+    DeviceQueueHandle::new(device_id.to_string(), cmd_tx).with_screen_hint(screen_hint).with_cancel(cancel)
+}
+
+/// Plug in a mock device running `scenario`; the device monitor connects it
+/// on its next scan. Returns its device id.
+#[tauri::command]
+pub async fn enable_mock_device(scenario: MockScenario, app: tauri::AppHandle) -> Result<String, String> {
+    #[cfg(feature = "mock-device")]
+    {
+        with_devices(|devices| devices.insert(scenario));
+        println!("🧪 Mock device {} connected", scenario.device_id());
+        let _ = crate::event_controller::rescan_devices(app).await;
+        Ok(scenario.device_id())
+    }
+    #[cfg(not(feature = "mock-device"))]
+    {
+        let _ = (scenario, app);
+        Err("Mock devices need the mock-device feature".to_string())
+    }
+}
+
+/// Unplug the mock device running `scenario`
+#[tauri::command]
+pub async fn disable_mock_device(scenario: MockScenario, app: tauri::AppHandle) -> Result<(), String> {
+    #[cfg(feature = "mock-device")]
+    {
+        if with_devices(|devices| devices.remove(&scenario)) {
+            println!("🧪 Mock device {} disconnected", scenario.device_id());
+            let _ = crate::event_controller::rescan_devices(app).await;
+        }
+        Ok(())
+    }
+    #[cfg(not(feature = "mock-device"))]
+    {
+        let _ = (scenario, app);
+        Err("Mock devices need the mock-device feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address_request() -> Message {
+        messages::GetAddress::default().into()
+    }
+
+    #[test]
+    fn test_scenario_names() {
+        assert_eq!(MockScenario::parse("needs-firmware"), Ok(MockScenario::NeedsFirmware));
+        assert_eq!(MockScenario::AccessError.device_id(), "mock-access-error");
+        assert_eq!(MockScenario::from_device_id("mock-needs-init"), Some(MockScenario::NeedsInit));
+        assert_eq!(MockScenario::from_device_id("bus1_addr4"), None);
+
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(scenario_from_args(args(&["--mock-device", "locked"])), Some(Ok(MockScenario::Locked)));
+        assert_eq!(scenario_from_args(args(&["--mock-device=ready"])), Some(Ok(MockScenario::Ready)));
+        assert!(matches!(scenario_from_args(args(&["--mock-device"])), Some(Err(_))));
+        assert_eq!(scenario_from_args(args(&["--verbose"])), None);
+    }
+
+    #[test]
+    fn test_scenarios_evaluate_like_real_devices() {
+        let status_of = |scenario| {
+            let features = crate::commands::convert_features_to_device_features(MockDevice::new(scenario).features().unwrap());
+            crate::commands::evaluate_device_status(scenario.device_id(), Some(&features))
+        };
+        let ready = status_of(MockScenario::Ready);
+        assert!(!ready.needs_firmware_update && !ready.needs_initialization && !ready.needs_bootloader_update);
+        assert!(status_of(MockScenario::NeedsFirmware).needs_firmware_update);
+        assert!(status_of(MockScenario::NeedsInit).needs_initialization);
+
+        let error = MockDevice::new(MockScenario::AccessError).features().unwrap_err();
+        assert_eq!(
+            crate::device::access_error::classify(&error),
+            Some(crate::device::access_error::AccessErrorKind::DeviceClaimed)
+        );
+    }
+
+    #[test]
+    fn test_locked_device_unlocks_with_any_pin() {
+        let mut device = MockDevice::new(MockScenario::Locked);
+        assert!(matches!(device.answer(address_request()), Ok(Message::PinMatrixRequest(_))));
+        // The PIN answers the request that was waiting on it
+        match device.answer(messages::PinMatrixAck { pin: "1234".to_string() }.into()) {
+            Ok(Message::Address(address)) => assert_eq!(address.address, MOCK_ADDRESS),
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        assert_eq!(device.features().unwrap().pin_cached, Some(true));
+        assert!(matches!(device.answer(address_request()), Ok(Message::Address(_))));
+    }
+}
@@ -1,4 +1,43 @@
+pub mod access_error;
+pub mod active;
+pub mod attention;
+pub mod benchmark;
+pub mod capabilities;
+pub mod change;
+pub mod compare;
+pub mod connection;
+pub mod countdown;
+pub mod entropy;
+pub mod feature_cache;
+pub mod firmware_file;
+pub mod forget;
+pub mod identity;
+pub mod lru;
+pub mod metrics;
+pub mod mock;
+pub mod model;
+pub mod multisig;
+pub mod on_connect;
+pub mod oob_stats;
+pub mod policy;
+pub mod probe;
+pub mod prompts;
+pub mod psbt;
 pub mod queue;
+pub mod ready_timing;
+pub mod reconnect;
+pub mod release_notes;
+pub mod seed_check;
+pub mod session;
+pub mod signatures;
+pub mod signing;
+pub mod state;
+pub mod storage;
+pub mod telemetry;
+pub mod udev;
+pub mod update_cancel;
+pub mod update_eta;
+pub mod update_history;
 pub mod updates;
 
 // Re-export the bootloader update tracker
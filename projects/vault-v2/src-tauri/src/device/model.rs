@@ -0,0 +1,393 @@
+use keepkey_rust::friendly_usb::KEEPKEY_VID;
+use keepkey_rust::messages::Features;
+use serde::{Deserialize, Serialize};
+
+/// Hardware model reported by every retail KeepKey (firmware 6.x+ reports it in `Features.model`)
+pub const KEEPKEY_MODEL: &str = "K1-14AM";
+
+/// Known bootloader hashes (double sha256, as reported in `Features.bootloader_hash`)
+/// and the bootloader version they belong to. Mirrors `hashes.bootloader` in
+/// keepkey-rust/firmware/releases.json.
+const KNOWN_BOOTLOADERS: &[(&str, &str)] = &[
+    ("6397c446f6b9002a8b150bf4b9b4e0bb66800ed099b881ca49700139b0559f10", "1.0.0"),
+    ("f13ce228c0bb2bdbc56bdcb5f4569367f8e3011074ccc63331348deb498f2d8f", "1.0.0"),
+    ("d544b5e06b0c355d68b868ac7580e9bab2d224a1e2440881cc1bca2b816752d5", "1.0.1"),
+    ("ec618836f86423dbd3114c37d6e3e4ffdfb87d9e4c6199cf3e163a67b27498a2", "1.0.1"),
+    ("cd702b91028a2cfa55af43d3407ba0f6f752a4a2be0583a172983b303ab1032e", "1.0.2"),
+    ("bcafb38cd0fbd6e2bdbea89fb90235559fdda360765b74e4a8758b4eff2d4921", "1.0.2"),
+    ("cb222548a39ff6cbe2ae2f02c8d431c9ae0df850f814444911f521b95ab02f4c", "1.0.3"),
+    ("917d1952260c9b89f3a96bea07eea4074afdcc0e8cdd5d064e36868bdd68ba7d", "1.0.3"),
+    ("6465bc505586700a8111c4bf7db6f40af73e720f9e488d20db56135e5a690c4f", "1.0.3"),
+    ("db4bc389335e876e942ae3b12558cecd202b745903e79b34dd2c32532708860e", "1.0.3"),
+    ("2e38950143cf350345a6ddada4c0c4f21eb2ed337309f39c5dbc70b6c091ae00", "1.0.3"),
+    ("83d14cb6c7c48af2a83bc326353ee6b9abdd74cfe47ba567de1cb564da65e8e9", "1.0.3"),
+    ("770b30aaa0be884ee8621859f5d055437f894a5c9c7ca22635e7024e059857b7", "1.0.4"),
+    ("fc4e5c4dc2e5127b6814a3f69424c936f1dc241d1daf2c5a2d8f0728eb69d20d", "1.0.4"),
+    ("e45f587fb07533d832548402d0e71d8e8234881da54d86c4b699c28a6482b0ee", "1.1.0"),
+    ("9bf1580d1b21250f922b68794cdadd6c8e166ae5b15ce160a42f8c44a2f05936", "2.0.0"),
+    ("e1ad2667d1924e4ddbeb623bd6939e94114d8471b84f8fb056e0c9abf0c4e4f4", "2.1.0"),
+    ("a3f8c745ff33cd92a7e95d37c76c65523d258a70352ea44a232038ec4ec38dea", "2.1.1"),
+    ("3b97596ed612aa29a74a7f51f33ea85fd6e0cfe7340dfbb96f0c17077b363498", "2.1.2"),
+    ("e6685ab14844d0a381d658d77e13d6145fe7ae80469e5a5360210ae9c3447a77", "2.1.3"),
+    ("fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb", "2.1.4"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceModelInfo {
+    /// Hardware model, e.g. "K1-14AM"; "unknown" if it can't be determined
+    pub model: String,
+    /// Hardware revision: "legacy" for v1 bootloaders (HID only), "webusb" for v2+
+    pub revision: Option<String>,
+    pub bootloader_version: Option<String>,
+    pub firmware_version: Option<String>,
+    /// SCM revision of the running firmware, hex encoded
+    pub firmware_revision: Option<String>,
+}
+
+/// Bootloader version for a known bootloader hash (hex, case-insensitive)
+pub fn bootloader_version_from_hash(hash: &str) -> Option<&'static str> {
+    let hash = hash.to_ascii_lowercase();
+    KNOWN_BOOTLOADERS
+        .iter()
+        .find(|(known, _)| *known == hash)
+        .map(|(_, version)| *version)
+}
+
+/// Model of the device that shipped with a known bootloader hash
+pub fn model_from_bootloader_hash(hash: &str) -> Option<&'static str> {
+    bootloader_version_from_hash(hash).map(|_| KEEPKEY_MODEL)
+}
+
+/// Hardware revision implied by the bootloader generation. v1 bootloaders only
+/// speak HID; everything from 2.0.0 on ships with the WebUSB-capable hardware.
+pub(crate) fn revision_from_bootloader_version(version: &str) -> &'static str {
+    if version.starts_with('1') {
+        "legacy"
+    } else {
+        "webusb"
+    }
+}
+
+/// Build the model info from raw features and the USB ids the device enumerated with
+pub fn derive_model_info(features: &Features, vid: u16, pid: u16) -> DeviceModelInfo {
+    let bootloader_hash = features.bootloader_hash.as_ref().map(hex::encode);
+    let bootloader_mode = features.bootloader_mode.unwrap_or(false);
+
+    let version = format!(
+        "{}.{}.{}",
+        features.major_version.unwrap_or(0),
+        features.minor_version.unwrap_or(0),
+        features.patch_version.unwrap_or(0)
+    );
+
+    // In bootloader mode the version fields describe the bootloader itself
+    let bootloader_version = bootloader_hash
+        .as_deref()
+        .and_then(bootloader_version_from_hash)
+        .map(str::to_string)
+        .or_else(|| bootloader_mode.then(|| version.clone()));
+    let firmware_version = (!bootloader_mode).then_some(version);
+
+    let model = features
+        .model
+        .clone()
+        .filter(|m| !m.trim().is_empty())
+        .or_else(|| bootloader_hash.as_deref().and_then(model_from_bootloader_hash).map(str::to_string))
+        .or_else(|| (vid == KEEPKEY_VID).then(|| KEEPKEY_MODEL.to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let revision = match bootloader_version.as_deref() {
+        Some(v) => Some(revision_from_bootloader_version(v).to_string()),
+        // PID 0x0001 is the HID-only interface of the original hardware
+        None if vid == KEEPKEY_VID && pid == 0x0001 => Some("legacy".to_string()),
+        None if vid == KEEPKEY_VID && pid == 0x0002 => Some("webusb".to_string()),
+        None => None,
+    };
+
+    DeviceModelInfo {
+        model,
+        revision,
+        bootloader_version,
+        firmware_version,
+        firmware_revision: features.revision.as_ref().map(hex::encode),
+    }
+}
+
+/// Firmware releases that migrated on-device storage to a new layout. Firmware older
+/// than a migration can't read the migrated storage and resets it (seed included)
+/// on first boot.
+const STORAGE_MIGRATIONS: &[&str] = &["6.0.0", "7.0.0"];
+
+/// Oldest firmware each bootloader generation will boot, as (bootloader, firmware).
+/// Older images are refused by the bootloader outright.
+const MIN_FIRMWARE_FOR_BOOTLOADER: &[(&str, &str)] = &[("2.0.0", "6.0.0")];
+
+/// Oldest bootloader each firmware generation runs on, as (firmware, bootloader).
+/// Updates always replace the bootloader first, so a pair below this is what an
+/// interrupted update (or a firmware flashed with another tool) leaves behind.
+const MIN_BOOTLOADER_FOR_FIRMWARE: &[(&str, &str)] = &[("6.0.0", "1.1.0"), ("7.0.0", "2.0.0")];
+
+/// Slots of the vendor public keys compiled into every KeepKey bootloader; a
+/// firmware header names three of them as its signers
+const VENDOR_KEY_SLOTS: &[u32] = &[1, 2, 3, 4, 5];
+
+/// What a bootloader will accept when flashing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootloaderPolicy {
+    /// Bootloader the policy was derived for, `None` if it isn't known
+    pub bootloader_version: Option<String>,
+    /// Oldest firmware the bootloader boots
+    pub min_firmware_version: Option<String>,
+    /// Unsigned images are refused. Known bootloaders boot them after an
+    /// "unofficial firmware" warning (and without the user's storage).
+    pub requires_signature: bool,
+    /// Key slots that may sign firmware for this bootloader
+    pub vendor_keys: Vec<u32>,
+}
+
+/// The flashing policy of `bootloader_version`. No bootloader reports its policy
+/// over USB, so it comes from the version (see `KNOWN_BOOTLOADERS`); an
+/// unidentified bootloader gets the strictest policy.
+pub fn bootloader_policy(bootloader_version: Option<&str>) -> BootloaderPolicy {
+    let Some(bootloader) = bootloader_version.and_then(parse_version) else {
+        return BootloaderPolicy {
+            bootloader_version: None,
+            min_firmware_version: None,
+            requires_signature: true,
+            vendor_keys: VENDOR_KEY_SLOTS.to_vec(),
+        };
+    };
+    let min_firmware_version = MIN_FIRMWARE_FOR_BOOTLOADER
+        .iter()
+        .filter(|(min_bootloader, _)| parse_version(min_bootloader).is_some_and(|min| bootloader >= min))
+        .filter_map(|(_, min_firmware)| parse_version(min_firmware))
+        .max()
+        .map(|v| v.to_string());
+    BootloaderPolicy {
+        bootloader_version: Some(bootloader.to_string()),
+        min_firmware_version,
+        requires_signature: false,
+        vendor_keys: VENDOR_KEY_SLOTS.to_vec(),
+    }
+}
+
+/// Policy of the bootloader a device reports: from its hash, or from the
+/// version fields while it is in bootloader mode
+pub fn bootloader_policy_for(features: &Features) -> BootloaderPolicy {
+    bootloader_policy(derive_model_info(features, KEEPKEY_VID, 0).bootloader_version.as_deref())
+}
+
+/// Firmware installed on a bootloader older than it requires; payload of
+/// `device:incompatible-versions`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionMismatch {
+    pub firmware: String,
+    pub bootloader: String,
+    pub required_bootloader: String,
+}
+
+/// Oldest bootloader `firmware` runs on, `None` when it has no requirement
+pub fn required_bootloader(firmware: &str) -> Option<semver::Version> {
+    let firmware = parse_version(firmware)?;
+    MIN_BOOTLOADER_FOR_FIRMWARE
+        .iter()
+        .filter(|(min_firmware, _)| parse_version(min_firmware).is_some_and(|min| firmware >= min))
+        .filter_map(|(_, min_bootloader)| parse_version(min_bootloader))
+        .max()
+}
+
+/// The mismatch between `firmware` and `bootloader`, if the bootloader is
+/// older than the firmware requires
+pub fn firmware_bootloader_mismatch(firmware: &str, bootloader: &str) -> Option<VersionMismatch> {
+    let required = required_bootloader(firmware)?;
+    let installed = parse_version(bootloader)?;
+    (installed < required).then(|| VersionMismatch {
+        firmware: firmware.trim().trim_start_matches('v').to_string(),
+        bootloader: installed.to_string(),
+        required_bootloader: required.to_string(),
+    })
+}
+
+/// Bootloader version of a device running firmware: from its bootloader hash,
+/// or the reported version when that is already one
+pub fn installed_bootloader_version(features: &keepkey_rust::features::DeviceFeatures) -> Option<String> {
+    features
+        .bootloader_hash
+        .as_deref()
+        .and_then(bootloader_version_from_hash)
+        .map(str::to_string)
+        .or_else(|| features.bootloader_version.clone().filter(|v| parse_version(v).is_some()))
+}
+
+/// Flashing `target` over a newer `current` firmware
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareDowngrade {
+    pub current: String,
+    pub target: String,
+    pub will_wipe: bool,
+    /// Why the bootloader will refuse the image, if it will
+    pub blocked_reason: Option<String>,
+}
+
+fn parse_version(version: &str) -> Option<semver::Version> {
+    semver::Version::parse(version.trim().trim_start_matches('v')).ok()
+}
+
+/// Detect a downgrade and apply the bootloader rules. Returns `None` when `target`
+/// is not older than `current` (or either version can't be parsed).
+pub fn check_firmware_downgrade(current: &str, target: &str, bootloader_version: Option<&str>) -> Option<FirmwareDowngrade> {
+    let current_version = parse_version(current)?;
+    let target_version = parse_version(target)?;
+    if target_version >= current_version {
+        return None;
+    }
+
+    let will_wipe = STORAGE_MIGRATIONS
+        .iter()
+        .filter_map(|v| parse_version(v))
+        .any(|migration| current_version >= migration && target_version < migration);
+
+    let policy = bootloader_policy(bootloader_version);
+    let blocked_reason = policy
+        .min_firmware_version
+        .as_deref()
+        .and_then(parse_version)
+        .filter(|min_firmware| target_version < *min_firmware)
+        .map(|min_firmware| {
+            format!("Bootloader {} does not run firmware older than {}", policy.bootloader_version.unwrap_or_default(), min_firmware)
+        });
+
+    Some(FirmwareDowngrade {
+        current: current_version.to_string(),
+        target: target_version.to_string(),
+        will_wipe,
+        blocked_reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features_with_bootloader(hash: &str) -> Features {
+        Features {
+            bootloader_hash: Some(hex::decode(hash).unwrap()),
+            major_version: Some(7),
+            minor_version: Some(10),
+            patch_version: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_known_bootloader_hashes_map_to_versions() {
+        assert_eq!(
+            bootloader_version_from_hash("fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb"),
+            Some("2.1.4")
+        );
+        assert_eq!(
+            bootloader_version_from_hash("9BF1580D1B21250F922B68794CDADD6C8E166AE5B15CE160A42F8C44A2F05936"),
+            Some("2.0.0")
+        );
+        assert_eq!(
+            bootloader_version_from_hash("6397c446f6b9002a8b150bf4b9b4e0bb66800ed099b881ca49700139b0559f10"),
+            Some("1.0.0")
+        );
+        assert_eq!(bootloader_version_from_hash("deadbeef"), None);
+    }
+
+    #[test]
+    fn test_known_bootloader_hashes_map_to_models() {
+        for (hash, _) in KNOWN_BOOTLOADERS {
+            assert_eq!(model_from_bootloader_hash(hash), Some(KEEPKEY_MODEL));
+        }
+        assert_eq!(model_from_bootloader_hash("deadbeef"), None);
+    }
+
+    #[test]
+    fn test_derive_model_info() {
+        // Modern device: model comes from features, revision from the bootloader generation
+        let mut features = features_with_bootloader("fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb");
+        features.model = Some("K1-14AM".to_string());
+        let info = derive_model_info(&features, KEEPKEY_VID, 0x0002);
+        assert_eq!(info.model, "K1-14AM");
+        assert_eq!(info.revision.as_deref(), Some("webusb"));
+        assert_eq!(info.bootloader_version.as_deref(), Some("2.1.4"));
+        assert_eq!(info.firmware_version.as_deref(), Some("7.10.0"));
+
+        // Old firmware without a model field falls back to the bootloader hash
+        let features = features_with_bootloader("770b30aaa0be884ee8621859f5d055437f894a5c9c7ca22635e7024e059857b7");
+        let info = derive_model_info(&features, KEEPKEY_VID, 0x0001);
+        assert_eq!(info.model, KEEPKEY_MODEL);
+        assert_eq!(info.revision.as_deref(), Some("legacy"));
+        assert_eq!(info.bootloader_version.as_deref(), Some("1.0.4"));
+    }
+
+    #[test]
+    fn test_firmware_bootloader_mismatch() {
+        let mismatch = firmware_bootloader_mismatch("7.10.0", "1.0.3").unwrap();
+        assert_eq!(
+            mismatch,
+            VersionMismatch {
+                firmware: "7.10.0".to_string(),
+                bootloader: "1.0.3".to_string(),
+                required_bootloader: "2.0.0".to_string(),
+            }
+        );
+        assert_eq!(firmware_bootloader_mismatch("7.0.3", "1.1.0").unwrap().required_bootloader, "2.0.0");
+        assert_eq!(firmware_bootloader_mismatch("6.7.0", "1.0.4").unwrap().required_bootloader, "1.1.0");
+
+        // Compatible pairs, and firmware that predates every requirement
+        assert_eq!(firmware_bootloader_mismatch("7.10.0", "2.1.4"), None);
+        assert_eq!(firmware_bootloader_mismatch("6.7.0", "1.1.0"), None);
+        assert_eq!(firmware_bootloader_mismatch("4.0.0", "1.0.3"), None);
+        assert_eq!(firmware_bootloader_mismatch("7.10.0", "unknown"), None);
+    }
+
+    #[test]
+    fn test_bootloader_policies() {
+        let v1 = bootloader_policy(Some("1.0.4"));
+        assert_eq!(v1.min_firmware_version, None);
+        assert!(!v1.requires_signature);
+        assert_eq!(v1.vendor_keys, vec![1, 2, 3, 4, 5]);
+
+        for version in ["2.0.0", "2.1.4"] {
+            let v2 = bootloader_policy(Some(version));
+            assert_eq!(v2.bootloader_version.as_deref(), Some(version));
+            assert_eq!(v2.min_firmware_version.as_deref(), Some("6.0.0"));
+        }
+
+        // Derived from the hash when the device runs firmware
+        let features = features_with_bootloader("fe98454e7ebd4aef4a6db5bd4c60f52cf3f58b974283a7c1e1fcc5fea02cf3eb");
+        assert_eq!(bootloader_policy_for(&features), bootloader_policy(Some("2.1.4")));
+
+        // Unknown hash outside bootloader mode: assume the strictest policy
+        let unknown = bootloader_policy_for(&features_with_bootloader("deadbeef"));
+        assert_eq!(unknown.bootloader_version, None);
+        assert!(unknown.requires_signature);
+    }
+
+    #[test]
+    fn test_check_firmware_downgrade() {
+        assert_eq!(check_firmware_downgrade("7.10.0", "7.10.0", Some("2.1.4")), None);
+        assert_eq!(check_firmware_downgrade("7.9.3", "7.10.0", Some("2.1.4")), None);
+
+        // Same storage layout: no wipe
+        let downgrade = check_firmware_downgrade("7.10.0", "7.7.0", Some("2.1.4")).unwrap();
+        assert!(!downgrade.will_wipe);
+        assert_eq!(downgrade.blocked_reason, None);
+
+        // Crossing the 7.0.0 storage migration wipes the device
+        let downgrade = check_firmware_downgrade("7.1.0", "6.7.0", Some("2.1.4")).unwrap();
+        assert!(downgrade.will_wipe);
+        assert_eq!(downgrade.blocked_reason, None);
+
+        // v2 bootloaders refuse pre-6.0.0 firmware; v1 bootloaders don't
+        let downgrade = check_firmware_downgrade("6.0.0", "5.10.0", Some("2.0.0")).unwrap();
+        assert!(downgrade.will_wipe);
+        assert!(downgrade.blocked_reason.is_some());
+        assert_eq!(check_firmware_downgrade("6.0.0", "5.10.0", Some("1.0.4")).unwrap().blocked_reason, None);
+    }
+}
@@ -0,0 +1,293 @@
+use bitcoin::bip32::{ChildNumber, ExtendedPubKey};
+use bitcoin::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::{Address, Network, ScriptBuf};
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::messages::{self, HdNodePathType, HdNodeType, InputScriptType, Message, MultisigRedeemScriptType};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// Most cosigners a bare `OP_CHECKMULTISIG` script allows
+const MAX_COSIGNERS: usize = 15;
+
+/// An m-of-n multisig wallet this device is a cosigner of. `xpubs` are the
+/// cosigners' account xpubs (any SLIP-132 prefix) in wallet order; addresses
+/// sort the derived keys (BIP67), so the order only matters for `our_index`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigConfig {
+    pub m: u32,
+    pub xpubs: Vec<String>,
+    /// Which of `xpubs` belongs to this device
+    pub our_index: usize,
+}
+
+impl MultisigConfig {
+    fn validate(&self) -> Result<(), String> {
+        let n = self.xpubs.len();
+        if n == 0 || n > MAX_COSIGNERS {
+            return Err(format!("Multisig needs 1 to {} cosigners, got {}", MAX_COSIGNERS, n));
+        }
+        if self.m == 0 || self.m as usize > n {
+            return Err(format!("Invalid multisig threshold {}-of-{}", self.m, n));
+        }
+        if self.our_index >= n {
+            return Err(format!("Cosigner index {} is out of range for {} cosigners", self.our_index, n));
+        }
+        Ok(())
+    }
+
+    fn account_keys(&self) -> Result<Vec<ExtendedPubKey>, String> {
+        self.xpubs.iter().map(|xpub| decode_xpub(xpub)).collect()
+    }
+}
+
+/// Decode an account xpub whatever its SLIP-132 prefix (xpub, ypub, Zpub, ...)
+fn decode_xpub(xpub: &str) -> Result<ExtendedPubKey, String> {
+    let mut payload = bitcoin::base58::decode_check(xpub.trim()).map_err(|e| format!("Invalid xpub {}: {}", xpub, e))?;
+    if payload.len() != 78 {
+        return Err(format!("Invalid xpub {}: wrong length", xpub));
+    }
+    payload[..4].copy_from_slice(&crate::slip132::XPUB);
+    ExtendedPubKey::decode(&payload).map_err(|e| format!("Invalid xpub {}: {}", xpub, e))
+}
+
+fn hd_node(key: &ExtendedPubKey) -> HdNodeType {
+    HdNodeType {
+        depth: key.depth as u32,
+        fingerprint: u32::from_be_bytes(key.parent_fingerprint.to_bytes()),
+        child_num: u32::from(key.child_number),
+        chain_code: key.chain_code.to_bytes().to_vec(),
+        private_key: None,
+        public_key: Some(key.public_key.serialize().to_vec()),
+    }
+}
+
+/// Split a multisig address path into the account path and the unhardened
+/// `change/index` suffix every cosigner derives
+fn split_path(path: &[u32]) -> Result<(&[u32], &[u32]), String> {
+    if path.len() < 2 || path[path.len() - 2..].iter().any(|&i| i & 0x8000_0000 != 0) {
+        return Err("Multisig address path must end in unhardened change/index".to_string());
+    }
+    Ok(path.split_at(path.len() - 2))
+}
+
+/// Cosigner keys at `suffix`, sorted as they appear in the redeem script
+fn sorted_cosigners(keys: &[ExtendedPubKey], suffix: &[u32]) -> Result<Vec<(ExtendedPubKey, bitcoin::PublicKey)>, String> {
+    let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+    let children: Vec<ChildNumber> = suffix.iter().map(|&i| ChildNumber::from(i)).collect();
+    let mut cosigners = keys
+        .iter()
+        .map(|key| {
+            let child = key.derive_pub(&secp, &children).map_err(|e| format!("Cannot derive cosigner key: {}", e))?;
+            Ok((*key, bitcoin::PublicKey::new(child.public_key)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    cosigners.sort_by_key(|(_, child)| child.to_bytes());
+    Ok(cosigners)
+}
+
+/// `GetAddress.multisig` for the address at `suffix`
+fn redeem_script_type(config: &MultisigConfig, suffix: &[u32]) -> Result<MultisigRedeemScriptType, String> {
+    let cosigners = sorted_cosigners(&config.account_keys()?, suffix)?;
+    Ok(MultisigRedeemScriptType {
+        pubkeys: cosigners
+            .iter()
+            .map(|(key, _)| HdNodePathType { node: hd_node(key), address_n: suffix.to_vec() })
+            .collect(),
+        signatures: vec![Vec::new(); cosigners.len()],
+        m: Some(config.m),
+    })
+}
+
+/// Device script type for a multisig `script_type` ("p2sh" when unset)
+fn multisig_script_type(script_type: Option<&str>) -> Result<InputScriptType, String> {
+    match script_type.unwrap_or("p2sh") {
+        "p2sh" => Ok(InputScriptType::Spendmultisig),
+        "p2wsh" => Ok(InputScriptType::Spendwitness),
+        "p2sh-p2wsh" => Ok(InputScriptType::Spendp2shwitness),
+        other => Err(format!("Unsupported multisig script type: {}", other)),
+    }
+}
+
+/// The address the multisig wallet has at `suffix`, computed on the host so
+/// the device's answer can be checked against it
+pub fn expected_address(config: &MultisigConfig, suffix: &[u32], script_type: Option<&str>, coin_name: &str) -> Result<String, String> {
+    config.validate()?;
+    let cosigners = sorted_cosigners(&config.account_keys()?, suffix)?;
+    let mut builder = Builder::new().push_int(config.m as i64);
+    for (_, child) in &cosigners {
+        builder = builder.push_key(child);
+    }
+    let script: ScriptBuf = builder.push_int(cosigners.len() as i64).push_opcode(OP_CHECKMULTISIG).into_script();
+
+    let network = if coin_name == "Testnet" { Network::Testnet } else { Network::Bitcoin };
+    let address = match multisig_script_type(script_type)? {
+        InputScriptType::Spendwitness => Address::p2wsh(&script, network),
+        InputScriptType::Spendp2shwitness => Address::p2shwsh(&script, network),
+        _ => Address::p2sh(&script, network).map_err(|e| format!("Cannot build p2sh address: {}", e))?,
+    };
+    Ok(address.to_string())
+}
+
+/// Make sure `device_node` (the device's own key at the account path) is the
+/// cosigner the config says it is
+fn check_own_xpub(config: &MultisigConfig, device_node: &HdNodeType) -> Result<(), String> {
+    let ours = decode_xpub(&config.xpubs[config.our_index])?;
+    let matches = device_node.public_key.as_deref() == Some(&ours.public_key.serialize()[..])
+        && device_node.chain_code == ours.chain_code.to_bytes();
+    if matches {
+        Ok(())
+    } else {
+        Err(format!(
+            "This device's xpub is not cosigner #{} of the multisig; check the wallet's cosigner list and account path",
+            config.our_index + 1
+        ))
+    }
+}
+
+/// Ask the device for the multisig address at `path` and check it against the
+/// address computed from the cosigner set
+pub async fn get_multisig_address(
+    queue_handle: &DeviceQueueHandle,
+    path: Vec<u32>,
+    coin_name: String,
+    script_type: Option<&str>,
+    show_display: Option<bool>,
+    config: &MultisigConfig,
+) -> Result<String, String> {
+    config.validate()?;
+    let (account, suffix) = split_path(&path)?;
+    let input_script_type = multisig_script_type(script_type)?;
+
+    let own_key = queue_handle
+        .send_raw(
+            messages::GetPublicKey {
+                address_n: account.to_vec(),
+                coin_name: Some(coin_name.clone()),
+                show_display: Some(false),
+                ..Default::default()
+            }
+            .into(),
+            false,
+        )
+        .await
+        .map_err(|e| format!("Failed to get account xpub: {}", e))?;
+    match own_key {
+        Message::PublicKey(public_key) => check_own_xpub(config, &public_key.node)?,
+        Message::Failure(failure) => return Err(format!("Device returned error: {}", failure.message.unwrap_or_default())),
+        _ => return Err("Unexpected response from device for xpub request".to_string()),
+    }
+
+    let expected = expected_address(config, suffix, script_type, &coin_name)?;
+    let get_address = messages::GetAddress {
+        address_n: path.clone(),
+        coin_name: Some(coin_name),
+        show_display,
+        multisig: Some(redeem_script_type(config, suffix)?),
+        script_type: Some(input_script_type as i32),
+    };
+    let response = queue_handle
+        .send_raw(get_address.into(), false)
+        .await
+        .map_err(|e| format!("Failed to get address: {}", e))?;
+    let address = match response {
+        Message::Address(address) => address.address,
+        Message::Failure(failure) => return Err(format!("Device returned error: {}", failure.message.unwrap_or_default())),
+        _ => return Err("Unexpected response to GetAddress".to_string()),
+    };
+    if address != expected {
+        return Err(format!("Device derived multisig address {} but the cosigner set gives {}", address, expected));
+    }
+    println!("🔐 Multisig {}-of-{} address {}", config.m, config.xpubs.len(), address);
+    Ok(address)
+}
+
+/// Check that `address` belongs to this device's wallet at `path`, as a
+/// single-sig address or, with `multisig`, as an address of that cosigner set
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_address_ownership(
+    unique_id: String,
+    path: String,
+    address: String,
+    script_type: Option<String>,
+    coin: Option<String>,
+    multisig: Option<MultisigConfig>,
+    show_display: Option<bool>,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<bool, String> {
+    let path = crate::commands::parse_derivation_path(&path)?;
+    let coin_name = crate::network::resolve_coin(crate::commands::network_mode(), coin.as_deref(), &path)?;
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    let derived = match multisig {
+        Some(config) => {
+            get_multisig_address(&queue_handle, path, coin_name, script_type.as_deref(), show_display, &config).await?
+        }
+        None => queue_handle
+            .get_address(path, coin_name, crate::device::queue::input_script_type(script_type.as_deref()), show_display)
+            .await
+            .map_err(|e| format!("Failed to get address: {}", e))?,
+    };
+    Ok(derived == address.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Master public keys of BIP32 test vectors 1, 2 and 3
+    const XPUBS: [&str; 3] = [
+        "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+        "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+        "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13",
+    ];
+
+    fn two_of_three(our_index: usize) -> MultisigConfig {
+        MultisigConfig { m: 2, xpubs: XPUBS.iter().map(|x| x.to_string()).collect(), our_index }
+    }
+
+    #[test]
+    fn test_two_of_three_addresses() {
+        let config = two_of_three(1);
+        assert_eq!(
+            expected_address(&config, &[0, 5], Some("p2wsh"), "Bitcoin").unwrap(),
+            "bc1ql8er6yapvmtkuk7y7uu5crg7fcnwcyeqqwv622apm65je780gsdsuuhnn5"
+        );
+        assert_eq!(expected_address(&config, &[0, 5], None, "Bitcoin").unwrap(), "3GXLcZ89kJSqftHcQM95Db8yBFKUj169Uk");
+        assert_eq!(
+            expected_address(&config, &[0, 5], Some("p2sh-p2wsh"), "Bitcoin").unwrap(),
+            "3Q87q7HjfmfEbsobH15URuznmRja6LjbQ9"
+        );
+
+        // Cosigner order in the config doesn't change the address
+        let mut reordered = config.clone();
+        reordered.xpubs.reverse();
+        assert_eq!(
+            expected_address(&reordered, &[0, 5], Some("p2wsh"), "Bitcoin").unwrap(),
+            "bc1ql8er6yapvmtkuk7y7uu5crg7fcnwcyeqqwv622apm65je780gsdsuuhnn5"
+        );
+
+        let redeem = redeem_script_type(&config, &[0, 5]).unwrap();
+        assert_eq!(redeem.m, Some(2));
+        assert_eq!(redeem.pubkeys.len(), 3);
+        assert!(redeem.pubkeys.iter().all(|p| p.address_n == vec![0, 5] && p.node.depth == 0));
+    }
+
+    #[test]
+    fn test_rejects_device_that_is_not_a_cosigner() {
+        let device_node = hd_node(&decode_xpub(XPUBS[1]).unwrap());
+        assert!(check_own_xpub(&two_of_three(1), &device_node).is_ok());
+        let err = check_own_xpub(&two_of_three(0), &device_node).unwrap_err();
+        assert!(err.contains("not cosigner #1"), "{}", err);
+
+        assert!(two_of_three(3).validate().is_err());
+        assert!(MultisigConfig { m: 4, ..two_of_three(0) }.validate().is_err());
+        assert!(split_path(&[0x80000030, 0x80000000, 0x80000000, 0x80000002, 0, 0x80000001]).is_err());
+        assert!(decode_xpub("xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet9").is_err());
+    }
+}
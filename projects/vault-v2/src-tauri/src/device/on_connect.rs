@@ -0,0 +1,263 @@
+//! Operations registered ahead of time to run on a device as soon as it
+//! connects, for "plug in your device to continue" flows that would otherwise
+//! poll from the frontend.
+//!
+//! A registration targets a USB id, a USB serial or the device id a device
+//! reports in its features, so a device can be named before its USB id is
+//! known. It fires once, the first time a matching device answers its feature
+//! probe, and goes through the same queue path as `add_to_device_queue`. Its
+//! outcome is emitted as `device:on-connect-result`; a registration that
+//! doesn't fire before its timeout is reported there as expired.
+
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{DeviceRequest, DeviceRequestWrapper, DeviceResponse};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// An operation waiting for its device
+#[derive(Debug, Clone)]
+pub struct PendingOperation {
+    pub registration_id: String,
+    /// USB id, USB serial or features device id of the device to run on
+    pub target: String,
+    pub request: DeviceRequest,
+    pub expires_at: Instant,
+}
+
+impl PendingOperation {
+    /// Whether `device` (with the device id from its features) is the target
+    pub fn matches(&self, device: &FriendlyUsbDevice, hardware_id: Option<&str>) -> bool {
+        let target = self.target.as_str();
+        device.unique_id == target
+            || device.serial_number.as_deref().is_some_and(|serial| serial.eq_ignore_ascii_case(target))
+            || hardware_id.is_some_and(|id| id.eq_ignore_ascii_case(target))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OnConnectRegistry {
+    pending: Vec<PendingOperation>,
+}
+
+impl OnConnectRegistry {
+    pub fn register(&mut self, target: String, request: DeviceRequest, timeout: Duration, now: Instant) -> PendingOperation {
+        let operation = PendingOperation {
+            registration_id: uuid::Uuid::new_v4().to_string(),
+            target,
+            request,
+            expires_at: now + timeout,
+        };
+        self.pending.push(operation.clone());
+        operation
+    }
+
+    /// Remove a registration; `None` if it already fired, expired or never existed
+    pub fn cancel(&mut self, registration_id: &str) -> Option<PendingOperation> {
+        let index = self.pending.iter().position(|op| op.registration_id == registration_id)?;
+        Some(self.pending.remove(index))
+    }
+
+    /// Remove and return the live registrations `device` matches, in
+    /// registration order. Expired ones are left for their expiry to report.
+    pub fn take_for_device(&mut self, device: &FriendlyUsbDevice, hardware_id: Option<&str>, now: Instant) -> Vec<PendingOperation> {
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|op| op.expires_at > now && op.matches(device, hardware_id));
+        self.pending = pending;
+        due
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+static REGISTRY: once_cell::sync::Lazy<std::sync::Mutex<OnConnectRegistry>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(OnConnectRegistry::default()));
+
+fn with_registry<T>(f: impl FnOnce(&mut OnConnectRegistry) -> T) -> T {
+    match REGISTRY.lock() {
+        Ok(mut registry) => f(&mut registry),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Payload of `device:on-connect-result`; `device_id` is `None` for an expired registration
+pub fn result_payload(
+    operation: &PendingOperation,
+    device_id: Option<&str>,
+    result: Result<Option<DeviceResponse>, String>,
+) -> serde_json::Value {
+    let (success, response, error) = match result {
+        Ok(response) => (true, response, None),
+        Err(e) => (false, None, Some(e)),
+    };
+    serde_json::json!({
+        "registration_id": operation.registration_id,
+        "target": operation.target,
+        "device_id": device_id,
+        "success": success,
+        "response": response,
+        "error": error
+    })
+}
+
+async fn emit_result(app: &AppHandle, payload: serde_json::Value) {
+    if let Err(e) = crate::commands::emit_or_queue_event(app, "device:on-connect-result", payload).await {
+        eprintln!("Failed to emit device:on-connect-result: {}", e);
+    }
+}
+
+async fn execute(app: &AppHandle, device_id: &str, operation: &PendingOperation) -> Result<Option<DeviceResponse>, String> {
+    let wrapper = DeviceRequestWrapper {
+        device_id: device_id.to_string(),
+        request_id: operation.registration_id.clone(),
+        request: operation.request.clone(),
+    };
+    crate::device::queue::add_to_device_queue(wrapper, app.state(), app.state(), app.clone()).await?;
+
+    let response = app
+        .state::<Arc<tokio::sync::Mutex<HashMap<String, DeviceResponse>>>>()
+        .lock()
+        .await
+        .get(&operation.registration_id)
+        .cloned();
+    Ok(response)
+}
+
+/// Run the operations waiting for a device whose feature probe just succeeded
+pub fn run_pending(app: &AppHandle, device: &FriendlyUsbDevice, hardware_id: Option<&str>) {
+    let due = with_registry(|registry| registry.take_for_device(device, hardware_id, Instant::now()));
+    for operation in due {
+        println!("▶️ Running on-connect operation {} on {}", operation.registration_id, device.unique_id);
+        let app = app.clone();
+        let device_id = device.unique_id.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = execute(&app, &device_id, &operation).await;
+            emit_result(&app, result_payload(&operation, Some(&device_id), result)).await;
+        });
+    }
+}
+
+/// Register `operation` to run once on the device named by `target` (a USB id,
+/// USB serial or device id) once it is connected and probed, right away if it
+/// already is. Returns the registration id for `cancel_run_on_connect`.
+#[tauri::command]
+pub async fn run_on_connect(
+    target: String,
+    operation: DeviceRequest,
+    timeout_secs: Option<u64>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let target = target.trim().to_string();
+    if target.is_empty() {
+        return Err("A device id or serial is required".to_string());
+    }
+    let timeout = timeout_secs.map_or(DEFAULT_TIMEOUT, Duration::from_secs);
+    if timeout.is_zero() || timeout > MAX_TIMEOUT {
+        return Err(format!("Timeout must be between 1 and {} seconds", MAX_TIMEOUT.as_secs()));
+    }
+
+    let pending = with_registry(|registry| registry.register(target, operation, timeout, Instant::now()));
+    let registration_id = pending.registration_id.clone();
+    println!("⏳ Registered on-connect operation {} for {} ({}s)", registration_id, pending.target, timeout.as_secs());
+
+    let expiry_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if let Some(expired) = with_registry(|registry| registry.cancel(&pending.registration_id)) {
+            println!("⌛ On-connect operation {} expired", expired.registration_id);
+            let error = format!("No matching device connected within {}s", timeout.as_secs());
+            emit_result(&expiry_app, result_payload(&expired, None, Err(error))).await;
+        }
+    });
+
+    // The device may already be connected and probed
+    for device in keepkey_rust::features::list_connected_devices() {
+        let hardware_id = crate::commands::cached_device_features(&device.unique_id).and_then(|f| f.device_id);
+        if hardware_id.is_some() {
+            run_pending(&app, &device, hardware_id.as_deref());
+        }
+    }
+    Ok(registration_id)
+}
+
+/// Cancel a registration that hasn't fired yet. Returns whether one was cancelled.
+#[tauri::command]
+pub async fn cancel_run_on_connect(registration_id: String) -> Result<bool, String> {
+    Ok(with_registry(|registry| registry.cancel(&registration_id)).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(unique_id: &str, serial: Option<&str>) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(unique_id.to_string(), 0x2b24, 0x0002, None, None, serial.map(str::to_string))
+    }
+
+    #[test]
+    fn test_registered_operation_runs_on_connect() {
+        let now = Instant::now();
+        let mut registry = OnConnectRegistry::default();
+        let by_serial = registry.register("343737340F4736331F003B00".to_string(), DeviceRequest::GetFeatures, DEFAULT_TIMEOUT, now);
+        let by_hardware_id = registry.register(
+            "E5A2F1D8C4B3".to_string(),
+            DeviceRequest::GetXpub { path: "m/84'/0'/0'".to_string() },
+            DEFAULT_TIMEOUT,
+            now,
+        );
+        let other = registry.register("bus9_addr9".to_string(), DeviceRequest::GetFeatures, DEFAULT_TIMEOUT, now);
+
+        // Simulated connect of a device whose USB id wasn't known in advance
+        let connected = device("bus1_addr5", Some("343737340f4736331f003b00"));
+        let due = registry.take_for_device(&connected, Some("E5A2F1D8C4B3"), now + Duration::from_secs(5));
+        let ids: Vec<_> = due.iter().map(|op| op.registration_id.as_str()).collect();
+        assert_eq!(ids, [by_serial.registration_id.as_str(), by_hardware_id.registration_id.as_str()]);
+        assert!(matches!(due[1].request, DeviceRequest::GetXpub { .. }));
+
+        // Fired once: reconnecting the same device runs nothing
+        assert!(registry.take_for_device(&connected, Some("E5A2F1D8C4B3"), now).is_empty());
+        assert_eq!(registry.len(), 1);
+
+        let json = result_payload(&due[0], Some("bus1_addr5"), Ok(None));
+        assert_eq!(json["registration_id"], by_serial.registration_id.as_str());
+        assert_eq!(json["device_id"], "bus1_addr5");
+        assert_eq!(json["success"], true);
+
+        // The exact USB id matches too
+        assert_eq!(registry.take_for_device(&device("bus9_addr9", None), None, now).len(), 1);
+        assert!(registry.is_empty());
+        assert!(registry.cancel(&other.registration_id).is_none());
+    }
+
+    #[test]
+    fn test_expired_and_cancelled_registrations_do_not_run() {
+        let now = Instant::now();
+        let mut registry = OnConnectRegistry::default();
+        let expiring = registry.register("SERIAL".to_string(), DeviceRequest::GetFeatures, Duration::from_secs(30), now);
+        let cancelled = registry.register("SERIAL".to_string(), DeviceRequest::GetFeatures, DEFAULT_TIMEOUT, now);
+
+        assert_eq!(registry.cancel(&cancelled.registration_id).map(|op| op.registration_id), Some(cancelled.registration_id.clone()));
+        assert!(registry.cancel(&cancelled.registration_id).is_none());
+
+        // Past its timeout it stays registered until its expiry reports it
+        let late = now + Duration::from_secs(31);
+        assert!(registry.take_for_device(&device("bus1_addr4", Some("SERIAL")), None, late).is_empty());
+        let expired = registry.cancel(&expiring.registration_id).unwrap();
+        let json = result_payload(&expired, None, Err("No matching device connected within 30s".to_string()));
+        assert_eq!(json["success"], false);
+        assert!(json["device_id"].is_null());
+        assert!(registry.is_empty());
+    }
+}
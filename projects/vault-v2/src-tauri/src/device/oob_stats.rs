@@ -0,0 +1,122 @@
+use keepkey_rust::features::DeviceFeatures;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Key for probes whose firmware version never became known
+const UNKNOWN_VERSION: &str = "unknown";
+
+/// How feature probes of one firmware version were answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OobCounters {
+    /// `GetFeatures` through the device queue answered
+    pub primary_success: u64,
+    /// `GetFeatures` was rejected and OOB bootloader detection was tried
+    pub oob_fallback: u64,
+    pub oob_success: u64,
+    pub oob_failure: u64,
+}
+
+impl OobCounters {
+    fn add(&mut self, other: &OobCounters) {
+        self.primary_success += other.primary_success;
+        self.oob_fallback += other.oob_fallback;
+        self.oob_success += other.oob_success;
+        self.oob_failure += other.oob_failure;
+    }
+}
+
+/// Counts of how often feature probes fall through to OOB bootloader detection,
+/// per firmware version, to tell which firmware ranges still need that path.
+/// Bootloader-mode devices are keyed `bootloader-<version>`; an OOB attempt that
+/// fails never learns a version and is counted under `unknown`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OobStats {
+    pub by_firmware: BTreeMap<String, OobCounters>,
+    pub total: OobCounters,
+}
+
+fn version_key(features: &DeviceFeatures) -> String {
+    if features.bootloader_mode {
+        format!("bootloader-{}", features.version)
+    } else {
+        features.version.clone()
+    }
+}
+
+impl OobStats {
+    fn record(&mut self, version: String, counters: OobCounters) {
+        self.by_firmware.entry(version).or_default().add(&counters);
+        self.total.add(&counters);
+    }
+
+    pub fn record_primary_success(&mut self, features: &DeviceFeatures) {
+        self.record(version_key(features), OobCounters { primary_success: 1, ..Default::default() });
+    }
+
+    pub fn record_oob_success(&mut self, features: &DeviceFeatures) {
+        self.record(version_key(features), OobCounters { oob_fallback: 1, oob_success: 1, ..Default::default() });
+    }
+
+    pub fn record_oob_failure(&mut self) {
+        self.record(UNKNOWN_VERSION.to_string(), OobCounters { oob_fallback: 1, oob_failure: 1, ..Default::default() });
+    }
+}
+
+static OOB_STATS: once_cell::sync::Lazy<std::sync::Mutex<OobStats>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(OobStats::default()));
+
+/// Run `f` against the app-wide counters
+pub fn with_stats<T>(f: impl FnOnce(&mut OobStats) -> T) -> T {
+    match OOB_STATS.lock() {
+        Ok(mut stats) => f(&mut stats),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+pub fn snapshot() -> OobStats {
+    with_stats(|stats| stats.clone())
+}
+
+/// OOB fallback counters since the app started
+#[tauri::command]
+pub async fn get_oob_stats() -> Result<OobStats, String> {
+    Ok(snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(version: &str, bootloader_mode: bool) -> DeviceFeatures {
+        let mut features = crate::commands::convert_features_to_device_features(Default::default());
+        features.version = version.to_string();
+        features.bootloader_mode = bootloader_mode;
+        features
+    }
+
+    #[test]
+    fn test_counts_per_firmware_version() {
+        let mut stats = OobStats::default();
+        stats.record_primary_success(&features("7.10.0", false));
+        stats.record_primary_success(&features("7.10.0", false));
+        stats.record_oob_success(&features("1.0.3", true));
+        stats.record_oob_failure();
+
+        assert_eq!(stats.by_firmware["7.10.0"], OobCounters { primary_success: 2, ..Default::default() });
+        assert_eq!(
+            stats.by_firmware["bootloader-1.0.3"],
+            OobCounters { oob_fallback: 1, oob_success: 1, ..Default::default() }
+        );
+        assert_eq!(stats.by_firmware["unknown"].oob_failure, 1);
+        assert_eq!(
+            stats.total,
+            OobCounters { primary_success: 2, oob_fallback: 2, oob_success: 1, oob_failure: 1 }
+        );
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["total"]["oobFallback"], 2);
+        assert_eq!(json["byFirmware"]["7.10.0"]["primarySuccess"], 2);
+    }
+}
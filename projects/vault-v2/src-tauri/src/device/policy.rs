@@ -0,0 +1,415 @@
+use keepkey_rust::gate::MessageGate;
+use keepkey_rust::messages::{Message, OutputAddressType, SignTx, TxOutputType};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::oneshot;
+
+/// Prefix of the error returned when the policy refuses an operation, so
+/// callers can tell a denial from a device failure
+pub const POLICY_DENIED: &str = "PolicyDenied";
+
+/// How long an operation waits for `resolve_policy_confirmation`
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A sensitive operation about to be dispatched to a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SensitiveOperation {
+    Sign {
+        coin: String,
+        inputs: usize,
+        /// Sats sent to outputs that aren't change
+        spend_amount: u64,
+        change_amount: u64,
+    },
+    Wipe,
+    ChangePin {
+        remove: bool,
+    },
+    ApplySettings {
+        label: Option<String>,
+        use_passphrase: Option<bool>,
+        auto_lock_delay_ms: Option<u32>,
+    },
+    /// Device policy flags turned on or off
+    ApplyPolicies {
+        policies: Vec<PolicyChange>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyChange {
+    pub name: String,
+    pub enabled: bool,
+}
+
+impl SensitiveOperation {
+    /// The operation a message performs on its own, `None` for messages the
+    /// policy doesn't cover. Signing spans several messages; `PolicyGate`
+    /// collects them.
+    pub fn for_message(message: &Message) -> Option<Self> {
+        match message {
+            Message::WipeDevice(_) => Some(SensitiveOperation::Wipe),
+            Message::ChangePin(m) => Some(SensitiveOperation::ChangePin { remove: m.remove.unwrap_or(false) }),
+            Message::ApplySettings(m) => Some(SensitiveOperation::ApplySettings {
+                label: m.label.clone(),
+                use_passphrase: m.use_passphrase,
+                auto_lock_delay_ms: m.auto_lock_delay_ms,
+            }),
+            Message::ApplyPolicies(m) => Some(SensitiveOperation::ApplyPolicies {
+                policies: m
+                    .policy
+                    .iter()
+                    .map(|p| PolicyChange { name: p.policy_name.clone().unwrap_or_default(), enabled: p.enabled.unwrap_or(false) })
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationDetails {
+    pub device_id: String,
+    #[serde(flatten)]
+    pub operation: SensitiveOperation,
+}
+
+/// What an `OperationPolicy` decided about an operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum PolicyDecision {
+    Allow,
+    Deny { reason: String },
+    /// Hold the operation until someone approves it through
+    /// `resolve_policy_confirmation` (a second approver, an admin console, ...)
+    RequireConfirmation,
+}
+
+/// Rules an integrator enforces before sensitive operations reach the device,
+/// e.g. "no wipes during business hours" or "spends over 1 BTC need a second
+/// approval". `PolicyGate` runs it on every message a worker sends, so signs,
+/// wipes, PIN changes, settings and flag changes can't bypass it.
+pub trait OperationPolicy: Send + Sync {
+    fn evaluate(&self, details: &OperationDetails) -> PolicyDecision;
+}
+
+/// The default policy: everything is allowed
+pub struct AllowAll;
+
+impl OperationPolicy for AllowAll {
+    fn evaluate(&self, _details: &OperationDetails) -> PolicyDecision {
+        PolicyDecision::Allow
+    }
+}
+
+static POLICY: once_cell::sync::Lazy<std::sync::RwLock<Arc<dyn OperationPolicy>>> =
+    once_cell::sync::Lazy::new(|| std::sync::RwLock::new(Arc::new(AllowAll)));
+
+/// Operations waiting for `resolve_policy_confirmation`, by approval id
+static PENDING_CONFIRMATIONS: once_cell::sync::Lazy<std::sync::Mutex<HashMap<String, oneshot::Sender<bool>>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Replace the app-wide policy
+pub fn set_operation_policy(policy: Arc<dyn OperationPolicy>) {
+    match POLICY.write() {
+        Ok(mut current) => *current = policy,
+        Err(poisoned) => *poisoned.into_inner() = policy,
+    }
+}
+
+fn current_policy() -> Arc<dyn OperationPolicy> {
+    match POLICY.read() {
+        Ok(policy) => policy.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn policy_denied(reason: &str) -> String {
+    format!("{}: {}", POLICY_DENIED, reason)
+}
+
+fn with_pending<T>(f: impl FnOnce(&mut HashMap<String, oneshot::Sender<bool>>) -> T) -> T {
+    match PENDING_CONFIRMATIONS.lock() {
+        Ok(mut pending) => f(&mut pending),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Ask the policy about `operation` on `device_id`. `Ok` means go ahead; a
+/// denial (or a confirmation that was rejected, never came or can't be asked
+/// for without `app`) is a `PolicyDenied: <reason>` error.
+pub async fn check(app: Option<&AppHandle>, device_id: &str, operation: SensitiveOperation) -> Result<(), String> {
+    let details = OperationDetails { device_id: device_id.to_string(), operation };
+    match current_policy().evaluate(&details) {
+        PolicyDecision::Allow => Ok(()),
+        PolicyDecision::Deny { reason } => {
+            println!("🛑 Policy denied {:?} on {}: {}", details.operation, device_id, reason);
+            Err(policy_denied(&reason))
+        }
+        PolicyDecision::RequireConfirmation => {
+            let Some(app) = app else {
+                return Err(policy_denied("Confirmation required but no one can be asked"));
+            };
+            let approval_id = uuid::Uuid::new_v4().to_string();
+            let (tx, rx) = oneshot::channel();
+            with_pending(|pending| pending.insert(approval_id.clone(), tx));
+
+            println!("⏸️ Policy requires confirmation for {:?} on {} ({})", details.operation, device_id, approval_id);
+            let payload = serde_json::json!({ "approval_id": approval_id, "operation": details });
+            if let Err(e) = app.emit("policy:confirmation-required", payload) {
+                eprintln!("Failed to emit policy:confirmation-required event: {}", e);
+            }
+
+            let approved = tokio::time::timeout(CONFIRMATION_TIMEOUT, rx).await;
+            with_pending(|pending| pending.remove(&approval_id));
+            match approved {
+                Ok(Ok(true)) => Ok(()),
+                Ok(Ok(false)) => Err(policy_denied("Confirmation was rejected")),
+                _ => Err(policy_denied("Confirmation was not given in time")),
+            }
+        }
+    }
+}
+
+/// A transaction being signed, collected from the messages that describe it
+/// until the policy can see the whole spend
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingSign {
+    coin: String,
+    inputs: usize,
+    outputs: usize,
+    seen_outputs: usize,
+    spend_amount: u64,
+    change_amount: u64,
+    /// The policy saw it; outputs the device asks for again while signing don't count
+    checked: bool,
+}
+
+impl PendingSign {
+    fn new(sign_tx: &SignTx) -> Self {
+        PendingSign {
+            coin: sign_tx.coin_name.clone().unwrap_or_else(|| "Bitcoin".to_string()),
+            inputs: sign_tx.inputs_count as usize,
+            outputs: sign_tx.outputs_count as usize,
+            seen_outputs: 0,
+            spend_amount: 0,
+            change_amount: 0,
+            checked: false,
+        }
+    }
+
+    /// Count `outputs` of the unsigned transaction; returns the operation once all are in
+    fn add_outputs(&mut self, outputs: &[TxOutputType]) -> Option<SensitiveOperation> {
+        if self.checked {
+            return None;
+        }
+        for output in outputs {
+            // Change is derived on the device from its path
+            let is_change = output.address_type == Some(OutputAddressType::Change as i32) || !output.address_n.is_empty();
+            if is_change {
+                self.change_amount += output.amount;
+            } else {
+                self.spend_amount += output.amount;
+            }
+            self.seen_outputs += 1;
+        }
+        (self.seen_outputs >= self.outputs).then(|| self.operation())
+    }
+
+    fn operation(&mut self) -> SensitiveOperation {
+        self.checked = true;
+        SensitiveOperation::Sign {
+            coin: self.coin.clone(),
+            inputs: self.inputs,
+            spend_amount: self.spend_amount,
+            change_amount: self.change_amount,
+        }
+    }
+}
+
+/// Checks every raw message a worker's handles send against the policy, by
+/// message type, so no command can skip it. Signing is checked when the last
+/// output of the unsigned transaction is about to be sent: the device has
+/// all amounts by then but hasn't signed anything yet.
+pub struct PolicyGate {
+    app: Option<AppHandle>,
+    signing: Mutex<Option<PendingSign>>,
+}
+
+impl PolicyGate {
+    /// `app` is asked for confirmations; without it they are denied
+    pub fn new(app: Option<AppHandle>) -> Self {
+        PolicyGate { app, signing: Mutex::new(None) }
+    }
+
+    fn operation(&self, message: &Message) -> Option<SensitiveOperation> {
+        let mut signing = match self.signing.lock() {
+            Ok(signing) => signing,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match message {
+            Message::SignTx(sign_tx) => {
+                let pending = signing.insert(PendingSign::new(sign_tx));
+                (pending.outputs == 0).then(|| pending.operation())
+            }
+            Message::TxAck(ack) => {
+                let outputs = ack.tx.as_ref().map(|tx| tx.outputs.as_slice()).unwrap_or_default();
+                if outputs.is_empty() {
+                    return None;
+                }
+                signing.as_mut()?.add_outputs(outputs)
+            }
+            message => SensitiveOperation::for_message(message),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageGate for PolicyGate {
+    async fn check(&self, device_id: &str, message: &Message) -> Result<(), String> {
+        match self.operation(message) {
+            Some(operation) => check(self.app.as_ref(), device_id, operation).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Approve or reject an operation held by `RequireConfirmation`
+#[tauri::command]
+pub async fn resolve_policy_confirmation(approval_id: String, approved: bool) -> Result<(), String> {
+    let sender = with_pending(|pending| pending.remove(&approval_id))
+        .ok_or_else(|| format!("No operation is waiting for approval {}", approval_id))?;
+    sender
+        .send(approved)
+        .map_err(|_| format!("Operation for approval {} is no longer waiting", approval_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Denies wipes and holds spends above a limit
+    struct Limits {
+        max_spend: u64,
+    }
+
+    impl OperationPolicy for Limits {
+        fn evaluate(&self, details: &OperationDetails) -> PolicyDecision {
+            match details.operation {
+                SensitiveOperation::Wipe => PolicyDecision::Deny { reason: "No wipes".to_string() },
+                SensitiveOperation::Sign { spend_amount, .. } if spend_amount > self.max_spend => {
+                    PolicyDecision::RequireConfirmation
+                }
+                _ => PolicyDecision::Allow,
+            }
+        }
+    }
+
+    fn sign(spend_amount: u64) -> OperationDetails {
+        OperationDetails {
+            device_id: "A".to_string(),
+            operation: SensitiveOperation::Sign { coin: "Bitcoin".to_string(), inputs: 1, spend_amount, change_amount: 0 },
+        }
+    }
+
+    #[test]
+    fn test_policy_decisions() {
+        let wipe = SensitiveOperation::for_message(&keepkey_rust::messages::WipeDevice {}.into()).unwrap();
+        assert_eq!(wipe, SensitiveOperation::Wipe);
+        assert_eq!(SensitiveOperation::for_message(&keepkey_rust::messages::GetFeatures::default().into()), None);
+
+        let policy = Limits { max_spend: 100_000_000 };
+        let wipe = OperationDetails { device_id: "A".to_string(), operation: wipe };
+        assert_eq!(policy.evaluate(&wipe), PolicyDecision::Deny { reason: "No wipes".to_string() });
+        assert_eq!(policy.evaluate(&sign(50_000)), PolicyDecision::Allow);
+        assert_eq!(policy.evaluate(&sign(200_000_000)), PolicyDecision::RequireConfirmation);
+        assert_eq!(AllowAll.evaluate(&wipe), PolicyDecision::Allow);
+
+        assert_eq!(policy_denied("No wipes"), "PolicyDenied: No wipes");
+        let json = serde_json::to_value(sign(1)).unwrap();
+        assert_eq!(json["kind"], "sign");
+        assert_eq!(json["spend_amount"], 1);
+    }
+
+    fn output(amount: u64, change: bool) -> TxOutputType {
+        TxOutputType {
+            amount,
+            address_n: if change { vec![0x8000_0054, 0x8000_0000, 0x8000_0000, 1, 0] } else { vec![] },
+            ..Default::default()
+        }
+    }
+
+    fn tx_ack(outputs: Vec<TxOutputType>) -> Message {
+        keepkey_rust::messages::TxAck { tx: Some(keepkey_rust::messages::TransactionType { outputs, ..Default::default() }) }.into()
+    }
+
+    #[test]
+    fn test_gate_sees_whole_spend_before_last_output() {
+        let gate = PolicyGate::new(None);
+        let sign_tx = SignTx { coin_name: Some("Testnet".to_string()), inputs_count: 2, outputs_count: 2, ..Default::default() };
+        assert_eq!(gate.operation(&sign_tx.clone().into()), None);
+        // Inputs and previous transactions don't count
+        assert_eq!(gate.operation(&tx_ack(vec![])), None);
+        assert_eq!(gate.operation(&tx_ack(vec![output(70_000, false)])), None);
+        assert_eq!(
+            gate.operation(&tx_ack(vec![output(20_000, true)])),
+            Some(SensitiveOperation::Sign { coin: "Testnet".to_string(), inputs: 2, spend_amount: 70_000, change_amount: 20_000 })
+        );
+        // The device asks for the outputs again while signing
+        assert_eq!(gate.operation(&tx_ack(vec![output(70_000, false)])), None);
+
+        // A new transaction starts over
+        assert_eq!(gate.operation(&sign_tx.into()), None);
+        assert!(gate.operation(&tx_ack(vec![output(1, false), output(2, false)])).is_some());
+    }
+
+    /// Denies wipes and flag changes on one device
+    struct LockedDown(&'static str);
+
+    impl OperationPolicy for LockedDown {
+        fn evaluate(&self, details: &OperationDetails) -> PolicyDecision {
+            match details.operation {
+                SensitiveOperation::Wipe | SensitiveOperation::ApplyPolicies { .. } if details.device_id == self.0 => {
+                    PolicyDecision::Deny { reason: "Locked down".to_string() }
+                }
+                _ => PolicyDecision::Allow,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_raw_is_refused_by_policy() {
+        use crate::device::mock::{spawn_scripted_worker, MockDevice, MockScenario};
+
+        let device_id = "policy-gate-test";
+        set_operation_policy(Arc::new(LockedDown(device_id)));
+        let handle = spawn_scripted_worker(device_id, MockDevice::new(MockScenario::Ready))
+            .with_message_gate(Arc::new(PolicyGate::new(None)));
+
+        let err = handle.send_raw(keepkey_rust::messages::WipeDevice {}.into(), true).await.unwrap_err();
+        assert_eq!(err.to_string(), "PolicyDenied: Locked down");
+        let apply_policies = keepkey_rust::messages::ApplyPolicies {
+            policy: vec![keepkey_rust::messages::PolicyType { policy_name: Some("AdvancedMode".to_string()), enabled: Some(true) }],
+        };
+        assert!(handle.send_raw(apply_policies.into(), true).await.is_err());
+        // The wipe never reached the device
+        assert_eq!(handle.get_features().await.unwrap().initialized, Some(true));
+        assert!(handle.send_raw(keepkey_rust::messages::GetFeatures::default().into(), true).await.is_ok());
+
+        set_operation_policy(Arc::new(AllowAll));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_confirmation() {
+        assert!(resolve_policy_confirmation("missing".to_string(), true).await.is_err());
+
+        let (tx, rx) = oneshot::channel();
+        with_pending(|pending| pending.insert("id".to_string(), tx));
+        resolve_policy_confirmation("id".to_string(), false).await.unwrap();
+        assert_eq!(rx.await, Ok(false));
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often a connected device whose feature probe failed is probed again.
+///
+/// The first retry waits `initial_backoff`, each later one twice as long up to
+/// `max_backoff`. After `max_attempts` failed probes the device is given up on
+/// and the frontend gets `device:probe-failed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeRetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ProbeRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_PROBE_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+pub const DEFAULT_PROBE_MAX_ATTEMPTS: u32 = 5;
+
+impl ProbeRetryPolicy {
+    pub fn with_max_attempts(max_attempts: u32) -> Self {
+        Self { max_attempts: max_attempts.max(1), ..Self::default() }
+    }
+
+    /// Delay before the probe following the `failures`-th failure
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(16);
+        self.initial_backoff.saturating_mul(1 << exponent).min(self.max_backoff)
+    }
+}
+
+/// What to do after a failed probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    Retry { attempts: u32, after: Duration },
+    /// Terminal: no further probes until the device reconnects
+    GaveUp { attempts: u32 },
+    /// The device disconnected while the probe was running
+    Untracked,
+}
+
+#[derive(Debug)]
+struct ProbeAttempts {
+    failures: u32,
+    next_retry: Instant,
+    in_flight: bool,
+    gave_up: bool,
+}
+
+/// Probe attempts of devices that are connected but haven't answered a feature
+/// probe yet. Devices are tracked from their first probe until it succeeds or
+/// they disconnect.
+#[derive(Debug, Default)]
+pub struct ProbeTracker {
+    policy: ProbeRetryPolicy,
+    devices: HashMap<String, ProbeAttempts>,
+}
+
+impl ProbeTracker {
+    pub fn new(policy: ProbeRetryPolicy) -> Self {
+        Self { policy, devices: HashMap::new() }
+    }
+
+    /// Applies to failures recorded from now on
+    pub fn set_policy(&mut self, policy: ProbeRetryPolicy) {
+        self.policy = policy;
+    }
+
+    /// Record that a probe of `device_id` is starting
+    pub fn start(&mut self, device_id: &str, now: Instant) {
+        let attempts = self.devices.entry(device_id.to_string()).or_insert(ProbeAttempts {
+            failures: 0,
+            next_retry: now,
+            in_flight: false,
+            gave_up: false,
+        });
+        attempts.in_flight = true;
+    }
+
+    /// The probe answered; stop tracking the device
+    pub fn succeeded(&mut self, device_id: &str) {
+        self.devices.remove(device_id);
+    }
+
+    pub fn failed(&mut self, device_id: &str, now: Instant) -> ProbeOutcome {
+        let Some(attempts) = self.devices.get_mut(device_id) else {
+            return ProbeOutcome::Untracked;
+        };
+        attempts.in_flight = false;
+        attempts.failures += 1;
+
+        if attempts.failures >= self.policy.max_attempts {
+            attempts.gave_up = true;
+            return ProbeOutcome::GaveUp { attempts: attempts.failures };
+        }
+        let after = self.policy.backoff(attempts.failures);
+        attempts.next_retry = now + after;
+        ProbeOutcome::Retry { attempts: attempts.failures, after }
+    }
+
+    /// Stop probing a device that can't be probed at all (e.g. DFU mode), without
+    /// reporting it as a probe failure
+    pub fn give_up(&mut self, device_id: &str) {
+        if let Some(attempts) = self.devices.get_mut(device_id) {
+            attempts.in_flight = false;
+            attempts.gave_up = true;
+        }
+    }
+
+    /// Devices whose next probe is due
+    pub fn due(&self, now: Instant) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|(_, a)| !a.in_flight && !a.gave_up && a.next_retry <= now)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Forget a device, e.g. on disconnect
+    pub fn remove(&mut self, device_id: &str) {
+        self.devices.remove(device_id);
+    }
+
+    /// Start over with a device that was given up on (e.g. after its transport
+    /// was reset) and probe it at the next tick. Returns false if it isn't tracked.
+    pub fn reset(&mut self, device_id: &str, now: Instant) -> bool {
+        let Some(attempts) = self.devices.get_mut(device_id) else {
+            return false;
+        };
+        attempts.failures = 0;
+        attempts.gave_up = false;
+        attempts.next_retry = now;
+        true
+    }
+}
+
+static PROBE_TRACKER: once_cell::sync::Lazy<std::sync::Mutex<ProbeTracker>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(ProbeTracker::default()));
+
+/// Run `f` against the app-wide probe tracker
+pub fn with_tracker<T>(f: impl FnOnce(&mut ProbeTracker) -> T) -> T {
+    match PROBE_TRACKER.lock() {
+        Ok(mut tracker) => f(&mut tracker),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+pub fn set_max_attempts(max_attempts: u32) {
+    with_tracker(|tracker| tracker.set_policy(ProbeRetryPolicy::with_max_attempts(max_attempts)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = ProbeRetryPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(16));
+        assert_eq!(policy.backoff(5), Duration::from_secs(30));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(30));
+        assert_eq!(ProbeRetryPolicy::with_max_attempts(0).max_attempts, 1);
+    }
+
+    #[test]
+    fn test_retries_until_gave_up() {
+        let mut tracker = ProbeTracker::new(ProbeRetryPolicy::with_max_attempts(3));
+        let now = Instant::now();
+
+        tracker.start("A", now);
+        // In flight: not due again
+        assert!(tracker.due(now).is_empty());
+
+        assert_eq!(tracker.failed("A", now), ProbeOutcome::Retry { attempts: 1, after: Duration::from_secs(2) });
+        assert!(tracker.due(now).is_empty());
+        assert_eq!(tracker.due(now + Duration::from_secs(2)), vec!["A".to_string()]);
+
+        tracker.start("A", now);
+        assert_eq!(tracker.failed("A", now), ProbeOutcome::Retry { attempts: 2, after: Duration::from_secs(4) });
+        tracker.start("A", now);
+        assert_eq!(tracker.failed("A", now), ProbeOutcome::GaveUp { attempts: 3 });
+        assert!(tracker.due(now + Duration::from_secs(3600)).is_empty());
+    }
+
+    #[test]
+    fn test_success_and_disconnect_reset_attempts() {
+        let mut tracker = ProbeTracker::new(ProbeRetryPolicy::with_max_attempts(2));
+        let now = Instant::now();
+
+        tracker.start("A", now);
+        tracker.failed("A", now);
+        tracker.start("A", now);
+        tracker.succeeded("A");
+        // A later failure starts counting from scratch
+        tracker.start("A", now);
+        assert!(matches!(tracker.failed("A", now), ProbeOutcome::Retry { attempts: 1, .. }));
+
+        tracker.remove("A");
+        tracker.start("A", now);
+        assert!(matches!(tracker.failed("A", now), ProbeOutcome::Retry { attempts: 1, .. }));
+
+        // Failures reported after a disconnect don't resurrect the device
+        tracker.start("B", now);
+        tracker.remove("B");
+        assert_eq!(tracker.failed("B", now), ProbeOutcome::Untracked);
+        assert!(tracker.due(now + Duration::from_secs(60)).iter().all(|id| id != "B"));
+
+        tracker.start("C", now);
+        tracker.give_up("C");
+        assert!(tracker.due(now + Duration::from_secs(60)).iter().all(|id| id != "C"));
+        // A transport reset brings it back
+        assert!(tracker.reset("C", now));
+        assert!(tracker.due(now).contains(&"C".to_string()));
+        assert!(!tracker.reset("unknown", now));
+    }
+}
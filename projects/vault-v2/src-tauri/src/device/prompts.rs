@@ -0,0 +1,130 @@
+//! Forwards the prompts a worker's device shows: every `ButtonRequest` as
+//! `device:button-request`, so the confirmation dialog can say why the device
+//! wants a press (e.g. a fee over the firmware's threshold) instead of a
+//! generic "confirm on device", and every `PinMatrixRequest` as
+//! `device:pin-required`, so the matrix can be labelled for unlocking, a new
+//! PIN or its confirmation. A label change prompt is also forwarded as
+//! `device:confirm-label` with the label the worker sent, not the one the
+//! command was given, so the UI shows what the device is actually showing.
+
+use keepkey_rust::device_queue::DeviceQueueHandle;
+use keepkey_rust::screen_hint::{ButtonPrompt, PinPurpose};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast::error::RecvError;
+
+static APP: once_cell::sync::OnceCell<AppHandle> = once_cell::sync::OnceCell::new();
+
+/// Workers spawned before this (there are none during setup) aren't forwarded
+pub fn init(app: AppHandle) {
+    let _ = APP.set(app);
+}
+
+/// The app handle set by `init`, if any
+pub fn app() -> Option<AppHandle> {
+    APP.get().cloned()
+}
+
+pub fn payload(device_id: &str, prompt: &ButtonPrompt) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "request_type": prompt.request_type,
+        "code": prompt.code,
+        "context": prompt.context,
+    })
+}
+
+pub fn pin_payload(device_id: &str, purpose: PinPurpose) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "purpose": purpose,
+    })
+}
+
+pub fn label_payload(device_id: &str, new_label: &str) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "new_label": new_label,
+    })
+}
+
+/// Emit the button, PIN and label confirmation requests of `handle`'s worker until it shuts down
+pub fn forward_prompts(handle: &DeviceQueueHandle) {
+    let Some(app) = app() else {
+        return;
+    };
+    let device_id = handle.device_id().to_string();
+    let countdown_handle = handle.clone();
+    let countdown_running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut buttons = handle.subscribe_button_prompts();
+    let mut pins = handle.subscribe_pin_prompts();
+    let mut labels = handle.subscribe_label_prompts();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                prompt = buttons.recv() => match prompt {
+                    Ok(prompt) => {
+                        println!("👆 Device {} asks for a press: {:?}", device_id, prompt.request_type);
+                        let _ = app.emit("device:button-request", payload(&device_id, &prompt));
+                        if crate::commands::confirmation_countdown_enabled() {
+                            crate::device::countdown::spawn_countdown(app.clone(), countdown_handle.clone(), countdown_running.clone());
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => println!("⚠️ Missed {} button requests of {}", missed, device_id),
+                    Err(RecvError::Closed) => break,
+                },
+                purpose = pins.recv() => match purpose {
+                    Ok(purpose) => {
+                        println!("🔑 Device {} asks for its PIN: {:?}", device_id, purpose);
+                        let _ = app.emit("device:pin-required", pin_payload(&device_id, purpose));
+                    }
+                    Err(RecvError::Lagged(missed)) => println!("⚠️ Missed {} PIN requests of {}", missed, device_id),
+                    Err(RecvError::Closed) => break,
+                },
+                label = labels.recv() => match label {
+                    Ok(label) => {
+                        println!("🏷️ Device {} asks to confirm label '{}'", device_id, label);
+                        let _ = app.emit("device:confirm-label", label_payload(&device_id, &label));
+                    }
+                    Err(RecvError::Lagged(missed)) => println!("⚠️ Missed {} label confirmations of {}", missed, device_id),
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::screen_hint::ButtonRequestKind;
+
+    #[test]
+    fn test_button_request_payload() {
+        let prompt = ButtonPrompt {
+            request_type: ButtonRequestKind::FeeOverThreshold,
+            code: Some(2),
+            context: Some("0.0012 BTC".to_string()),
+        };
+        assert_eq!(
+            payload("A", &prompt),
+            serde_json::json!({
+                "device_id": "A",
+                "request_type": "fee_over_threshold",
+                "code": 2,
+                "context": "0.0012 BTC"
+            })
+        );
+    }
+
+    #[test]
+    fn test_pin_required_payload() {
+        assert_eq!(
+            pin_payload("A", PinPurpose::ConfirmNewPin),
+            serde_json::json!({ "device_id": "A", "purpose": "confirm_new_pin" })
+        );
+        assert_eq!(
+            label_payload("A", "Cold storage"),
+            serde_json::json!({ "device_id": "A", "new_label": "Cold storage" })
+        );
+    }
+}
@@ -0,0 +1,728 @@
+//! Signing BIP174 PSBTs with the device.
+//!
+//! Every input must be spendable by this device: single-key p2pkh, p2wpkh and
+//! p2sh-p2wpkh, or an m-of-n `OP_CHECKMULTISIG` script in p2sh, p2wsh or
+//! p2sh-p2wsh whose keys include one of the device's. The device's key is found
+//! through the input's BIP32 derivations by master fingerprint. Signatures that
+//! cosigners already added are passed on to the device, and the device's
+//! signature for each input is written back into `partial_sigs`. With
+//! `finalize`, inputs that have enough signatures get their final scriptSig or
+//! witness; the rest stay partially signed for the next cosigner.
+
+use std::collections::HashMap;
+
+use base64::Engine;
+use bitcoin::bip32::Fingerprint;
+use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHBYTES_0, OP_PUSHNUM_1, OP_PUSHNUM_16, OP_RETURN};
+use bitcoin::blockdata::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::psbt::{Input, Psbt};
+use bitcoin::secp256k1::{self, Secp256k1};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Address, Network, PublicKey, Script, ScriptBuf, Transaction, TxOut, Witness};
+use keepkey_rust::messages::{
+    HdNodePathType, HdNodeType, InputScriptType, MultisigRedeemScriptType, OutputAddressType, OutputScriptType, SignTx,
+    TransactionType, TxInputType, TxOutputType,
+};
+use serde::Serialize;
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::network::NetworkMode;
+
+/// Result of `sign_psbt`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignedPsbt {
+    /// Base64 PSBT with the device's signatures (and, if finalized, final scripts)
+    pub psbt: String,
+    /// Every input has a final scriptSig or witness
+    pub complete: bool,
+    /// Hex of the extracted transaction, when finalizing completed every input
+    pub signed_tx: Option<String>,
+}
+
+/// How an input is spent
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Spend {
+    /// One key. `key_script` is the p2pkh script or p2wpkh program the key must hash to.
+    Single { script_type: InputScriptType, key_script: ScriptBuf },
+    /// `m` of `pubkeys` (in script order) in an `OP_CHECKMULTISIG` script
+    Multisig { script_type: InputScriptType, m: usize, pubkeys: Vec<PublicKey>, script: ScriptBuf },
+}
+
+impl Spend {
+    fn script_type(&self) -> InputScriptType {
+        match self {
+            Spend::Single { script_type, .. } | Spend::Multisig { script_type, .. } => *script_type,
+        }
+    }
+
+    /// Whether `key` can sign for this input
+    fn owns(&self, key: &PublicKey) -> bool {
+        match self {
+            Spend::Single { script_type: InputScriptType::Spendaddress, key_script } => {
+                *key_script == ScriptBuf::new_p2pkh(&key.pubkey_hash())
+            }
+            Spend::Single { key_script, .. } => {
+                key.wpubkey_hash().map(|hash| *key_script == ScriptBuf::new_v0_p2wpkh(&hash)).unwrap_or(false)
+            }
+            Spend::Multisig { pubkeys, .. } => pubkeys.contains(key),
+        }
+    }
+
+    fn is_segwit(&self) -> bool {
+        !matches!(self.script_type(), InputScriptType::Spendaddress | InputScriptType::Spendmultisig)
+    }
+
+    /// Script the signature commits to: the scriptCode for segwit inputs, the
+    /// scriptPubKey or redeem script for legacy ones
+    fn script_code(&self, key: &PublicKey) -> ScriptBuf {
+        match self {
+            Spend::Single { .. } => ScriptBuf::new_p2pkh(&key.pubkey_hash()),
+            Spend::Multisig { script, .. } => script.clone(),
+        }
+    }
+}
+
+/// What the device needs to sign a PSBT, and the key it signs each input with
+struct SigningRequest {
+    sign_tx: SignTx,
+    tx_map: HashMap<String, TransactionType>,
+    spends: Vec<Spend>,
+    device_keys: Vec<PublicKey>,
+}
+
+fn decode_psbt(psbt: &str) -> Result<Psbt, String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(psbt.trim())
+        .map_err(|e| format!("PSBT is not valid base64: {}", e))?;
+    Psbt::deserialize(&bytes).map_err(|e| format!("Invalid PSBT: {}", e))
+}
+
+fn encode_psbt(psbt: &Psbt) -> String {
+    base64::engine::general_purpose::STANDARD.encode(psbt.serialize())
+}
+
+/// The output input `index` spends, from its previous transaction if present
+fn prevout(psbt: &Psbt, index: usize) -> Result<TxOut, String> {
+    let outpoint = psbt.unsigned_tx.input[index].previous_output;
+    let input = &psbt.inputs[index];
+    if let Some(prev_tx) = &input.non_witness_utxo {
+        if prev_tx.txid() != outpoint.txid {
+            return Err(format!("Input {} previous transaction does not match its outpoint", index));
+        }
+        return prev_tx
+            .output
+            .get(outpoint.vout as usize)
+            .cloned()
+            .ok_or_else(|| format!("Input {} spends output {} its previous transaction doesn't have", index, outpoint.vout));
+    }
+    input.witness_utxo.clone().ok_or_else(|| format!("Input {} has no UTXO", index))
+}
+
+/// `m` and the keys of an `OP_m <pubkeys> OP_n OP_CHECKMULTISIG` script
+fn parse_multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let small_int = |instruction: &Instruction| match instruction {
+        Instruction::Op(op) if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&op.to_u8()) => {
+            Some((op.to_u8() - OP_PUSHNUM_1.to_u8() + 1) as usize)
+        }
+        _ => None,
+    };
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let [first, keys @ .., n, Instruction::Op(check)] = instructions.as_slice() else {
+        return None;
+    };
+    if *check != OP_CHECKMULTISIG {
+        return None;
+    }
+    let (m, n) = (small_int(first)?, small_int(n)?);
+    let pubkeys = keys
+        .iter()
+        .map(|key| match key {
+            Instruction::PushBytes(bytes) if bytes.len() == 33 => PublicKey::from_slice(bytes.as_bytes()).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (pubkeys.len() == n && m <= n).then_some((m, pubkeys))
+}
+
+fn multisig_spend(index: usize, script: &Script, script_type: InputScriptType) -> Result<Spend, String> {
+    let (m, pubkeys) = parse_multisig(script)
+        .ok_or_else(|| format!("Input {} script is not an m-of-n multisig script", index))?;
+    Ok(Spend::Multisig { script_type, m, pubkeys, script: script.to_owned() })
+}
+
+fn witness_script<'a>(index: usize, input: &'a Input, program: &Script) -> Result<&'a ScriptBuf, String> {
+    let script = input.witness_script.as_ref().ok_or_else(|| format!("Input {} has no witness script", index))?;
+    if script.to_v0_p2wsh() != *program {
+        return Err(format!("Input {} witness script does not match the output it spends", index));
+    }
+    Ok(script)
+}
+
+/// How input `index` spends `prevout`, checked against its redeem and witness scripts
+fn classify_input(index: usize, input: &Input, prevout: &TxOut) -> Result<Spend, String> {
+    let script_pubkey = &prevout.script_pubkey;
+    if script_pubkey.is_p2pkh() {
+        return Ok(Spend::Single { script_type: InputScriptType::Spendaddress, key_script: script_pubkey.clone() });
+    }
+    if script_pubkey.is_v0_p2wpkh() {
+        return Ok(Spend::Single { script_type: InputScriptType::Spendwitness, key_script: script_pubkey.clone() });
+    }
+    if script_pubkey.is_v0_p2wsh() {
+        return multisig_spend(index, witness_script(index, input, script_pubkey)?, InputScriptType::Spendwitness);
+    }
+    if script_pubkey.is_p2sh() {
+        let redeem_script = input.redeem_script.as_ref().ok_or_else(|| format!("Input {} has no redeem script", index))?;
+        if redeem_script.to_p2sh() != *script_pubkey {
+            return Err(format!("Input {} redeem script does not match the output it spends", index));
+        }
+        if redeem_script.is_v0_p2wpkh() {
+            return Ok(Spend::Single { script_type: InputScriptType::Spendp2shwitness, key_script: redeem_script.clone() });
+        }
+        if redeem_script.is_v0_p2wsh() {
+            return multisig_spend(index, witness_script(index, input, redeem_script)?, InputScriptType::Spendp2shwitness);
+        }
+        return multisig_spend(index, redeem_script, InputScriptType::Spendmultisig);
+    }
+    Err(format!("Input {} spends an unsupported script type", index))
+}
+
+/// The device's key for input `index` and its derivation path, from the BIP32
+/// derivations with the device's master fingerprint
+fn device_key(index: usize, input: &Input, spend: &Spend, fingerprint: Fingerprint) -> Result<(PublicKey, Vec<u32>), String> {
+    input
+        .bip32_derivation
+        .iter()
+        .filter(|(_, (key_fingerprint, _))| *key_fingerprint == fingerprint)
+        .map(|(key, (_, path))| (PublicKey::new(*key), path.into_iter().map(|child| u32::from(*child)).collect()))
+        .find(|(key, _)| spend.owns(key))
+        .ok_or_else(|| format!("Input {} has no key from this device (fingerprint {})", index, fingerprint))
+}
+
+/// A multisig cosigner for the device: the bare key, already derived
+fn leaf_node(key: &PublicKey) -> HdNodePathType {
+    HdNodePathType {
+        node: HdNodeType {
+            depth: 0,
+            fingerprint: 0,
+            child_num: 0,
+            chain_code: vec![0; 32],
+            private_key: None,
+            public_key: Some(key.to_bytes()),
+        },
+        address_n: vec![],
+    }
+}
+
+fn previous_transaction(tx: &Transaction) -> Result<TransactionType, String> {
+    let (metadata, inputs, bin_outputs) =
+        crate::commands::parse_transaction_from_hex(&bitcoin::consensus::encode::serialize_hex(tx))?;
+    Ok(TransactionType {
+        version: Some(metadata.0),
+        lock_time: Some(metadata.3),
+        inputs_cnt: Some(metadata.1),
+        outputs_cnt: Some(metadata.2),
+        inputs,
+        bin_outputs,
+        outputs: vec![],
+        extra_data: None,
+        extra_data_len: Some(0),
+        ..Default::default()
+    })
+}
+
+/// The output as the device shows it: an address, or OP_RETURN data
+fn tx_output(index: usize, output: &TxOut, network: Network) -> Result<TxOutputType, String> {
+    if output.script_pubkey.is_op_return() {
+        let data = match output.script_pubkey.instructions().collect::<Result<Vec<_>, _>>().as_deref() {
+            Ok([Instruction::Op(op)]) if *op == OP_RETURN => vec![],
+            Ok([Instruction::Op(op), Instruction::PushBytes(data)]) if *op == OP_RETURN => data.as_bytes().to_vec(),
+            _ => return Err(format!("Output {} is a non-standard OP_RETURN", index)),
+        };
+        return Ok(TxOutputType {
+            amount: output.value,
+            script_type: OutputScriptType::Paytoopreturn as i32,
+            op_return_data: Some(data),
+            ..Default::default()
+        });
+    }
+    let address = Address::from_script(&output.script_pubkey, network)
+        .map_err(|e| format!("Output {} has no address: {}", index, e))?;
+    Ok(TxOutputType {
+        address: Some(address.to_string()),
+        amount: output.value,
+        script_type: OutputScriptType::Paytoaddress as i32,
+        address_type: Some(OutputAddressType::Spend as i32),
+        ..Default::default()
+    })
+}
+
+/// Map `psbt` onto the `SignTx` protocol for the device with master `fingerprint`
+fn signing_request(psbt: &Psbt, fingerprint: Fingerprint, coin_name: &str, network: Network) -> Result<SigningRequest, String> {
+    let tx = &psbt.unsigned_tx;
+    let mut tx_map = HashMap::new();
+    let mut inputs = Vec::with_capacity(tx.input.len());
+    let mut spends = Vec::with_capacity(tx.input.len());
+    let mut device_keys = Vec::with_capacity(tx.input.len());
+
+    for (index, (txin, input)) in tx.input.iter().zip(&psbt.inputs).enumerate() {
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Err(format!("Input {} is already finalized", index));
+        }
+        if input.ecdsa_hash_ty() != Ok(EcdsaSighashType::All) {
+            return Err(format!("Input {} asks for a sighash type other than SIGHASH_ALL", index));
+        }
+        let prevout = prevout(psbt, index)?;
+        let spend = classify_input(index, input, &prevout)?;
+        let (key, address_n) = device_key(index, input, &spend, fingerprint)?;
+
+        // Legacy inputs are signed over the previous transaction, segwit ones over the amount
+        match &input.non_witness_utxo {
+            Some(prev_tx) => {
+                tx_map.insert(txin.previous_output.txid.to_string(), previous_transaction(prev_tx)?);
+            }
+            None if !spend.is_segwit() => {
+                return Err(format!("Legacy input {} missing required previous transaction", index));
+            }
+            None => {}
+        }
+
+        let multisig = match &spend {
+            Spend::Single { .. } => None,
+            Spend::Multisig { m, pubkeys, .. } => Some(MultisigRedeemScriptType {
+                pubkeys: pubkeys.iter().map(leaf_node).collect(),
+                signatures: pubkeys
+                    .iter()
+                    .map(|pubkey| input.partial_sigs.get(pubkey).map(|sig| sig.sig.serialize_der().to_vec()).unwrap_or_default())
+                    .collect(),
+                m: Some(*m as u32),
+            }),
+        };
+        inputs.push(TxInputType {
+            address_n,
+            prev_hash: hex::decode(txin.previous_output.txid.to_string()).map_err(|e| format!("Invalid txid hex: {}", e))?,
+            prev_index: txin.previous_output.vout,
+            sequence: Some(txin.sequence.0),
+            script_type: Some(spend.script_type() as i32),
+            multisig,
+            amount: Some(prevout.value),
+            ..Default::default()
+        });
+        spends.push(spend);
+        device_keys.push(key);
+    }
+
+    let outputs = tx
+        .output
+        .iter()
+        .enumerate()
+        .map(|(index, output)| tx_output(index, output, network))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let version = tx.version as u32;
+    let lock_time = tx.lock_time.to_consensus_u32();
+    let sign_tx = SignTx {
+        coin_name: Some(coin_name.to_string()),
+        inputs_count: inputs.len() as u32,
+        outputs_count: outputs.len() as u32,
+        version: Some(version),
+        lock_time: Some(lock_time),
+        ..Default::default()
+    };
+    tx_map.insert(
+        "unsigned".to_string(),
+        TransactionType {
+            version: Some(version),
+            lock_time: Some(lock_time),
+            inputs_cnt: Some(inputs.len() as u32),
+            outputs_cnt: Some(outputs.len() as u32),
+            inputs,
+            bin_outputs: vec![],
+            outputs,
+            extra_data: None,
+            extra_data_len: Some(0),
+            ..Default::default()
+        },
+    );
+    Ok(SigningRequest { sign_tx, tx_map, spends, device_keys })
+}
+
+/// Check each device signature against the PSBT's sighash and add it to the
+/// input's `partial_sigs`
+fn add_device_signatures(psbt: &mut Psbt, request: &SigningRequest, signatures: &[(u32, Vec<u8>)]) -> Result<(), String> {
+    let secp = Secp256k1::verification_only();
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut signed = Vec::with_capacity(signatures.len());
+
+    for (index, der) in signatures {
+        let index = *index as usize;
+        let (Some(spend), Some(key)) = (request.spends.get(index), request.device_keys.get(index)) else {
+            return Err(format!("Device signed input {} the PSBT doesn't have", index));
+        };
+        let sig = secp256k1::ecdsa::Signature::from_der(der)
+            .map_err(|e| format!("Device returned a malformed signature for input {}: {}", index, e))?;
+        let script_code = spend.script_code(key);
+        let sighash = if spend.is_segwit() {
+            let amount = prevout(psbt, index)?.value;
+            cache
+                .segwit_signature_hash(index, &script_code, amount, EcdsaSighashType::All)
+                .map(|hash| secp256k1::Message::from_slice(hash.as_ref()))
+        } else {
+            cache
+                .legacy_signature_hash(index, &script_code, EcdsaSighashType::All.to_u32())
+                .map(|hash| secp256k1::Message::from_slice(hash.as_ref()))
+        }
+        .map_err(|e| format!("Failed to compute sighash for input {}: {}", index, e))?
+        .map_err(|e| format!("Invalid sighash for input {}: {}", index, e))?;
+        secp.verify_ecdsa(&sighash, &sig, &key.inner)
+            .map_err(|_| format!("Device signature for input {} does not match the PSBT", index))?;
+        signed.push((index, *key, bitcoin::ecdsa::Signature { sig, hash_ty: EcdsaSighashType::All }));
+    }
+
+    if let Some(missing) = (0..request.spends.len()).find(|index| !signed.iter().any(|(signed, _, _)| signed == index)) {
+        return Err(format!("Device returned no signature for input {}", missing));
+    }
+    for (index, key, sig) in signed {
+        psbt.inputs[index].partial_sigs.insert(key, sig);
+    }
+    Ok(())
+}
+
+fn push_bytes(bytes: Vec<u8>) -> Result<PushBytesBuf, String> {
+    PushBytesBuf::try_from(bytes).map_err(|_| "Script element is too large to push".to_string())
+}
+
+/// Give `input` its final scriptSig / witness if it has enough signatures, and
+/// clear the fields BIP174 says a finalizer removes. Returns whether it was finalized.
+fn finalize_input(input: &mut Input, spend: &Spend) -> Result<bool, String> {
+    let (script_sig, witness) = match spend {
+        Spend::Single { script_type, .. } => {
+            let Some((key, sig)) = input.partial_sigs.iter().find(|(key, _)| spend.owns(key)) else {
+                return Ok(false);
+            };
+            match script_type {
+                InputScriptType::Spendaddress => {
+                    let script_sig = Builder::new().push_slice(push_bytes(sig.to_vec())?).push_key(key).into_script();
+                    (Some(script_sig), None)
+                }
+                _ => {
+                    let witness = Witness::from_slice(&[sig.to_vec(), key.to_bytes()]);
+                    (None, Some(witness))
+                }
+            }
+        }
+        Spend::Multisig { script_type, m, pubkeys, script } => {
+            // Signatures in the order of their keys in the script
+            let sigs: Vec<Vec<u8>> =
+                pubkeys.iter().filter_map(|key| input.partial_sigs.get(key)).map(|sig| sig.to_vec()).take(*m).collect();
+            if sigs.len() < *m {
+                return Ok(false);
+            }
+            match script_type {
+                InputScriptType::Spendmultisig => {
+                    let mut builder = Builder::new().push_opcode(OP_PUSHBYTES_0);
+                    for sig in sigs {
+                        builder = builder.push_slice(push_bytes(sig)?);
+                    }
+                    (Some(builder.push_slice(push_bytes(script.to_bytes())?).into_script()), None)
+                }
+                _ => {
+                    // OP_CHECKMULTISIG pops one element too many
+                    let mut items = vec![vec![]];
+                    items.extend(sigs);
+                    items.push(script.to_bytes());
+                    (None, Some(Witness::from_slice(&items)))
+                }
+            }
+        }
+    };
+
+    // Nested segwit spends push the redeem script in the scriptSig
+    let script_sig = match (script_sig, spend.script_type()) {
+        (None, InputScriptType::Spendp2shwitness) => {
+            let redeem_script = input.redeem_script.clone().ok_or("Nested segwit input has no redeem script")?;
+            Some(Builder::new().push_slice(push_bytes(redeem_script.into_bytes())?).into_script())
+        }
+        (script_sig, _) => script_sig,
+    };
+
+    input.final_script_sig = script_sig;
+    input.final_script_witness = witness;
+    input.partial_sigs.clear();
+    input.sighash_type = None;
+    input.redeem_script = None;
+    input.witness_script = None;
+    input.bip32_derivation.clear();
+    Ok(true)
+}
+
+/// Finalize every input that has enough signatures. Returns whether all are final.
+fn finalize_psbt(psbt: &mut Psbt, spends: &[Spend]) -> Result<bool, String> {
+    let mut complete = true;
+    for (input, spend) in psbt.inputs.iter_mut().zip(spends) {
+        complete &= finalize_input(input, spend)?;
+    }
+    Ok(complete)
+}
+
+/// Sign a base64 PSBT with the device. The device's signature for every input
+/// is added to `partial_sigs`; with `finalize`, inputs that then have enough
+/// signatures are finalized, and once all are the transaction is extracted.
+#[tauri::command]
+pub async fn sign_psbt(
+    device_id: String,
+    psbt: String,
+    finalize: bool,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<SignedPsbt, String> {
+    let mut psbt = decode_psbt(&psbt)?;
+    let mode = crate::commands::network_mode();
+    let network = match mode {
+        NetworkMode::Mainnet => Network::Bitcoin,
+        NetworkMode::Testnet | NetworkMode::Regtest => Network::Testnet,
+    };
+
+    if crate::commands::is_device_in_pin_flow(&device_id) {
+        return Err("Device is currently in PIN entry mode. Please complete PIN entry first.".to_string());
+    }
+    crate::commands::check_min_firmware_policy(crate::commands::cached_device_features(&device_id).as_ref())
+        .map_err(|e| e.to_string())?;
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    let fingerprint = queue_handle
+        .get_master_fingerprint()
+        .await
+        .map_err(|e| format!("Failed to get master fingerprint: {}", e))?;
+    let request = signing_request(&psbt, Fingerprint::from(fingerprint.to_be_bytes()), mode.coin_name(), network)?;
+
+    println!("📤 Signing PSBT with {} input(s) on {}", request.spends.len(), device_id);
+    let (_, signatures) =
+        crate::device::queue::run_sign_tx(&queue_handle, request.sign_tx.clone(), &request.tx_map, |_| {}).await?;
+    add_device_signatures(&mut psbt, &request, &signatures)?;
+
+    let complete = finalize && finalize_psbt(&mut psbt, &request.spends)?;
+    let signed_tx = complete.then(|| bitcoin::consensus::encode::serialize_hex(&psbt.clone().extract_tx()));
+    println!("✅ PSBT signed ({} signature(s), complete: {})", signatures.len(), complete);
+    Ok(SignedPsbt { psbt: encode_psbt(&psbt), complete, signed_tx })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::secp256k1::All;
+    use bitcoin::{absolute, OutPoint, Sequence, TxIn, Txid};
+    use std::str::FromStr;
+
+    const SEEDS: [[u8; 32]; 3] = [[1; 32], [2; 32], [3; 32]];
+    /// Cosigner path of every key in the fixture
+    const PATH: &str = "m/48'/0'/0'/2'/0/0";
+    /// The device is the second cosigner
+    const DEVICE: usize = 1;
+
+    struct Cosigner {
+        fingerprint: Fingerprint,
+        secret: secp256k1::SecretKey,
+        key: PublicKey,
+    }
+
+    fn cosigners(secp: &Secp256k1<All>) -> Vec<Cosigner> {
+        SEEDS
+            .iter()
+            .map(|seed| {
+                let master = ExtendedPrivKey::new_master(Network::Bitcoin, seed).unwrap();
+                let child = master.derive_priv(secp, &DerivationPath::from_str(PATH).unwrap()).unwrap();
+                Cosigner {
+                    fingerprint: master.fingerprint(secp),
+                    secret: child.private_key,
+                    key: PublicKey::new(ExtendedPubKey::from_priv(secp, &child).public_key),
+                }
+            })
+            .collect()
+    }
+
+    /// A PSBT spending a 2-of-3 p2wsh output, keys in script order, to one p2wpkh output
+    fn two_of_three_psbt(cosigners: &[Cosigner]) -> Psbt {
+        let mut builder = Builder::new().push_opcode(bitcoin::opcodes::all::OP_PUSHNUM_2);
+        for cosigner in cosigners {
+            builder = builder.push_key(&cosigner.key);
+        }
+        let witness_script = builder.push_opcode(bitcoin::opcodes::all::OP_PUSHNUM_3).push_opcode(OP_CHECKMULTISIG).into_script();
+
+        let tx = Transaction {
+            version: 2,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_str("0f0e0d0c0b0a09080706050403020100f0e0d0c0b0a090807060504030201000").unwrap(),
+                    vout: 1,
+                },
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: ScriptBuf::new_v0_p2wpkh(&cosigners[0].key.wpubkey_hash().unwrap()),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        let input = &mut psbt.inputs[0];
+        input.witness_utxo = Some(TxOut { value: 100_000, script_pubkey: witness_script.to_v0_p2wsh() });
+        input.witness_script = Some(witness_script);
+        for cosigner in cosigners {
+            input
+                .bip32_derivation
+                .insert(cosigner.key.inner, (cosigner.fingerprint, DerivationPath::from_str(PATH).unwrap()));
+        }
+        psbt
+    }
+
+    fn sign(secp: &Secp256k1<All>, psbt: &Psbt, secret: &secp256k1::SecretKey) -> Vec<u8> {
+        let input = &psbt.inputs[0];
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .segwit_signature_hash(
+                0,
+                input.witness_script.as_ref().unwrap(),
+                input.witness_utxo.as_ref().unwrap().value,
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        secp.sign_ecdsa(&secp256k1::Message::from_slice(sighash.as_ref()).unwrap(), secret).serialize_der().to_vec()
+    }
+
+    /// The fixture with the first cosigner's signature, and what the device
+    /// would answer for its signing request
+    struct Cosigned {
+        psbt: Psbt,
+        request: SigningRequest,
+        signatures: Vec<(u32, Vec<u8>)>,
+        cosigners: Vec<Cosigner>,
+    }
+
+    fn cosigned() -> Cosigned {
+        let secp = Secp256k1::new();
+        let cosigners = cosigners(&secp);
+        let mut psbt = two_of_three_psbt(&cosigners);
+        let cosigner_sig = secp256k1::ecdsa::Signature::from_der(&sign(&secp, &psbt, &cosigners[0].secret)).unwrap();
+        psbt.inputs[0]
+            .partial_sigs
+            .insert(cosigners[0].key, bitcoin::ecdsa::Signature { sig: cosigner_sig, hash_ty: EcdsaSighashType::All });
+
+        // Through a base64 round trip, as the frontend hands it over
+        let psbt = decode_psbt(&encode_psbt(&psbt)).unwrap();
+        let request = signing_request(&psbt, cosigners[DEVICE].fingerprint, "Bitcoin", Network::Bitcoin).unwrap();
+        let device_sig = sign(&secp, &psbt, &cosigners[DEVICE].secret);
+        Cosigned { psbt, request, signatures: vec![(0, device_sig)], cosigners }
+    }
+
+    #[test]
+    fn test_two_of_three_signing_request() {
+        let Cosigned { psbt, request, cosigners, .. } = cosigned();
+        assert_eq!(request.device_keys, vec![cosigners[DEVICE].key]);
+
+        let unsigned = &request.tx_map["unsigned"];
+        let input = &unsigned.inputs[0];
+        assert_eq!(input.address_n, vec![0x8000_0030, 0x8000_0000, 0x8000_0000, 0x8000_0002, 0, 0]);
+        assert_eq!(input.script_type, Some(InputScriptType::Spendwitness as i32));
+        assert_eq!(input.amount, Some(100_000));
+        assert_eq!(input.sequence, Some(0xffff_fffd));
+        assert_eq!(hex::encode(&input.prev_hash), psbt.unsigned_tx.input[0].previous_output.txid.to_string());
+
+        let multisig = input.multisig.as_ref().unwrap();
+        assert_eq!(multisig.m, Some(2));
+        let keys: Vec<_> = multisig.pubkeys.iter().map(|node| node.node.public_key.clone().unwrap()).collect();
+        assert_eq!(keys, cosigners.iter().map(|c| c.key.to_bytes()).collect::<Vec<_>>());
+        // The cosigner's signature is passed on, the device's and the third are still empty
+        assert!(!multisig.signatures[0].is_empty());
+        assert!(multisig.signatures[1].is_empty() && multisig.signatures[2].is_empty());
+
+        assert_eq!(unsigned.outputs[0].script_type, OutputScriptType::Paytoaddress as i32);
+        assert_eq!(unsigned.outputs[0].amount, 90_000);
+        assert!(unsigned.outputs[0].address.as_deref().unwrap().starts_with("bc1q"));
+        assert_eq!(request.sign_tx.inputs_count, 1);
+        assert_eq!(request.sign_tx.version, Some(2));
+    }
+
+    #[test]
+    fn test_partially_signed_without_finalize() {
+        let Cosigned { mut psbt, request, signatures, cosigners } = cosigned();
+        add_device_signatures(&mut psbt, &request, &signatures).unwrap();
+
+        let input = &decode_psbt(&encode_psbt(&psbt)).unwrap().inputs[0];
+        assert_eq!(input.partial_sigs.len(), 2);
+        assert!(input.partial_sigs.contains_key(&cosigners[0].key));
+        assert!(input.partial_sigs.contains_key(&cosigners[DEVICE].key));
+        assert!(input.final_script_sig.is_none() && input.final_script_witness.is_none());
+        assert!(input.witness_script.is_some());
+    }
+
+    #[test]
+    fn test_finalize_two_of_three() {
+        let Cosigned { mut psbt, request, signatures, cosigners } = cosigned();
+        add_device_signatures(&mut psbt, &request, &signatures).unwrap();
+        let sigs: Vec<Vec<u8>> =
+            [0, DEVICE].iter().map(|&i| psbt.inputs[0].partial_sigs[&cosigners[i].key].to_vec()).collect();
+        let witness_script = psbt.inputs[0].witness_script.clone().unwrap();
+
+        assert!(finalize_psbt(&mut psbt, &request.spends).unwrap());
+        let input = &psbt.inputs[0];
+        let witness: Vec<Vec<u8>> = input.final_script_witness.as_ref().unwrap().iter().map(|item| item.to_vec()).collect();
+        assert_eq!(witness, vec![vec![], sigs[0].clone(), sigs[1].clone(), witness_script.to_bytes()]);
+        assert!(input.final_script_sig.is_none());
+        assert!(input.partial_sigs.is_empty() && input.witness_script.is_none() && input.bip32_derivation.is_empty());
+        assert!(input.witness_utxo.is_some());
+
+        let tx = psbt.extract_tx();
+        assert_eq!(tx.input[0].witness.len(), 4);
+    }
+
+    #[test]
+    fn test_one_signature_is_not_enough_to_finalize() {
+        let Cosigned { mut psbt, request, .. } = cosigned();
+        assert!(!finalize_psbt(&mut psbt, &request.spends).unwrap());
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+        assert!(psbt.inputs[0].final_script_witness.is_none());
+    }
+
+    #[test]
+    fn test_rejects_bad_device_signatures() {
+        let secp = Secp256k1::new();
+        let Cosigned { mut psbt, request, cosigners, .. } = cosigned();
+        // A signature by another cosigner's key is not the device's
+        let wrong = vec![(0, sign(&secp, &psbt, &cosigners[2].secret))];
+        assert!(add_device_signatures(&mut psbt, &request, &wrong).unwrap_err().contains("does not match"));
+        assert!(add_device_signatures(&mut psbt, &request, &[]).unwrap_err().contains("no signature for input 0"));
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_psbt_the_device_cannot_sign() {
+        let secp = Secp256k1::new();
+        let cosigners = cosigners(&secp);
+        let psbt = two_of_three_psbt(&cosigners);
+        let stranger = Fingerprint::from([0xde, 0xad, 0xbe, 0xef]);
+        let err = signing_request(&psbt, stranger, "Bitcoin", Network::Bitcoin).err().unwrap();
+        assert!(err.contains("no key from this device"), "{}", err);
+
+        let mut mismatched = psbt;
+        mismatched.inputs[0].witness_script = Some(ScriptBuf::new());
+        let err = signing_request(&mismatched, cosigners[DEVICE].fingerprint, "Bitcoin", Network::Bitcoin).err().unwrap();
+        assert!(err.contains("witness script does not match"), "{}", err);
+
+        assert!(decode_psbt("not a psbt").is_err());
+    }
+
+    #[test]
+    fn test_parse_multisig() {
+        let secp = Secp256k1::new();
+        let cosigners = cosigners(&secp);
+        let psbt = two_of_three_psbt(&cosigners);
+        let (m, keys) = parse_multisig(psbt.inputs[0].witness_script.as_ref().unwrap()).unwrap();
+        assert_eq!(m, 2);
+        assert_eq!(keys.len(), 3);
+        assert!(parse_multisig(&ScriptBuf::new_v0_p2wpkh(&cosigners[0].key.wpubkey_hash().unwrap())).is_none());
+    }
+}
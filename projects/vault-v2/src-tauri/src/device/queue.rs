@@ -1,15 +1,20 @@
 use tauri::{State, AppHandle, Emitter};
 use std::sync::Arc;
 use std::collections::HashMap;
-use tokio::sync::RwLock;
 
 
 // Import types needed for DeviceRequestWrapper
-use crate::commands::{DeviceRequestWrapper, DeviceRequest, DeviceResponse, DeviceQueueManager, parse_transaction_from_hex};
+use crate::commands::{BitcoinUtxoOutput, DeviceRequestWrapper, DeviceRequest, DeviceResponse, DeviceQueueManager, DeviceQueueManagerExt, parse_transaction_from_hex};
+use crate::device::lru::DeviceLru;
 
 // Create a cache for device states to remember OOB bootloader status
 lazy_static::lazy_static! {
-    static ref DEVICE_STATE_CACHE: Arc<RwLock<HashMap<String, DeviceStateCache>>> = Arc::new(RwLock::new(HashMap::new()));
+    static ref DEVICE_STATE_CACHE: std::sync::Mutex<DeviceLru<DeviceStateCache>> = std::sync::Mutex::new(DeviceLru::new());
+}
+
+/// Drop the remembered bootloader state of a forgotten device
+pub fn forget_device_state(device_id: &str) {
+    crate::commands::lock_or_recover(&DEVICE_STATE_CACHE, "device state cache").remove(device_id);
 }
 
 #[derive(Debug, Clone)]
@@ -22,11 +27,15 @@ struct DeviceStateCache {
 
 #[tauri::command]
 pub async fn add_to_device_queue(
-    request: DeviceRequestWrapper,
+    mut request: DeviceRequestWrapper,
     queue_manager: State<'_, DeviceQueueManager>,
     last_responses: State<'_, Arc<tokio::sync::Mutex<std::collections::HashMap<String, DeviceResponse>>>>,
     app: AppHandle,
 ) -> Result<String, String> {
+    if request.device_id.is_empty() {
+        request.device_id = crate::device::active::active_device()
+            .ok_or_else(|| "No device given and no active device selected".to_string())?;
+    }
     println!("Adding to device queue: {:?}", request);
     
     // Log the incoming request
@@ -56,25 +65,10 @@ pub async fn add_to_device_queue(
     // --------------------------------------------------------------
     // Get or create (and cache) the per-device queue handle
     // --------------------------------------------------------------
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-
-        if let Some(handle) = manager.get(&request.device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID using high-level API
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == request.device_id)
-                .ok_or_else(|| format!("Device {} not found", request.device_id))?;
-
-            // Spawn a new device worker using the real keepkey_rust implementation
-            let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(request.device_id.clone(), device_info.clone());
-            manager.insert(request.device_id.clone(), handle.clone());
-            handle
-        }
-    };
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&request.device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", request.device_id))?;
 
     // ------------------------------------------------------------------
     // Check if device is in PIN flow BEFORE doing anything else
@@ -94,6 +88,10 @@ pub async fn add_to_device_queue(
         }
     }
     
+    // Coins and paths must belong to the network the app is in
+    let network = crate::commands::network_mode();
+    crate::network::check_request(network, &request.request)?;
+    
     // ------------------------------------------------------------------
     // Pre-flight status check – ensure the device can service this request
     // ------------------------------------------------------------------
@@ -101,16 +99,16 @@ pub async fn add_to_device_queue(
     let raw_features_opt = if crate::commands::is_device_in_pin_flow(&request.device_id) {
         println!("⚠️ Skipping GetFeatures check - device is in PIN flow");
         // Check cache for last known features
-        let cache = DEVICE_STATE_CACHE.read().await;
-        cache.get(&request.device_id).and_then(|state| state.last_features.clone())
+        let cache = crate::commands::lock_or_recover(&DEVICE_STATE_CACHE, "device state cache");
+        cache.peek(&request.device_id).and_then(|state| state.last_features.clone())
     } else {
         // We fetch the current features via the queue (which opens a temporary
         // transport) so that we have accurate mode/version information.
         match keepkey_rust::device_queue::DeviceQueueHandle::get_features(&queue_handle).await {
             Ok(f) => {
                 // Successfully got features, update cache
-                let mut cache = DEVICE_STATE_CACHE.write().await;
-                cache.insert(request.device_id.clone(), DeviceStateCache {
+                let mut cache = crate::commands::lock_or_recover(&DEVICE_STATE_CACHE, "device state cache");
+                cache.insert(&request.device_id, DeviceStateCache {
                     is_oob_bootloader: false,
                     last_features: Some(f.clone()),
                     last_update: std::time::Instant::now(),
@@ -121,8 +119,8 @@ pub async fn add_to_device_queue(
                 eprintln!("⚠️  Unable to fetch features for status check: {e}");
                 
                 // Check if we have cached state for this device
-                let cache = DEVICE_STATE_CACHE.read().await;
-                if let Some(cached_state) = cache.get(&request.device_id) {
+                let cache = crate::commands::lock_or_recover(&DEVICE_STATE_CACHE, "device state cache");
+                if let Some(cached_state) = cache.peek(&request.device_id) {
                     // If we know this is an OOB bootloader from a previous successful check
                     if cached_state.is_oob_bootloader {
                         println!("📋 Using cached OOB bootloader state for device {}", request.device_id);
@@ -146,6 +144,21 @@ pub async fn add_to_device_queue(
         crate::commands::evaluate_device_status(request.device_id.clone(), None)
     };
 
+    // Refuse sensitive operations on firmware below the configured policy minimum,
+    // before anything is sent to the device
+    let is_sensitive = match &request.request {
+        DeviceRequest::SignTransaction { .. } => true,
+        DeviceRequest::GetAddress { show_display, .. } => show_display.unwrap_or(false),
+        _ => false,
+    };
+    if is_sensitive {
+        let converted = raw_features_opt.clone().map(crate::commands::convert_features_to_device_features);
+        if let Err(e) = crate::commands::check_min_firmware_policy(converted.as_ref()) {
+            println!("🚫 Rejecting {request_type} request – {}", e);
+            return Err(e.to_string());
+        }
+    }
+
     // Special handling for devices that might be in OOB bootloader mode
     let _is_likely_oob_bootloader = raw_features_opt.is_none() && request_type != "GetFeatures";
     
@@ -159,8 +172,8 @@ pub async fn add_to_device_queue(
         if device_exists {
             println!("🔧 Device {} exists but GetFeatures failed - likely OOB bootloader, allowing request to proceed", request.device_id);
             // Mark this device as OOB bootloader in cache
-            let mut cache = DEVICE_STATE_CACHE.write().await;
-            cache.insert(request.device_id.clone(), DeviceStateCache {
+            let mut cache = crate::commands::lock_or_recover(&DEVICE_STATE_CACHE, "device state cache");
+            cache.insert(&request.device_id, DeviceStateCache {
                 is_oob_bootloader: true,
                 last_features: None,
                 last_update: std::time::Instant::now(),
@@ -266,8 +279,8 @@ pub async fn add_to_device_queue(
         }
     }
 
-
     // Process the request based on type
+    let started = std::time::Instant::now();
     let result = match request.request {
         DeviceRequest::GetXpub { ref path } => {
             // Parse derivation path
@@ -277,7 +290,7 @@ pub async fn add_to_device_queue(
             let get_public_key = keepkey_rust::messages::Message::GetPublicKey(
                 keepkey_rust::messages::GetPublicKey {
                     address_n: path_parts,
-                    coin_name: Some("Bitcoin".to_string()),
+                    coin_name: Some(network.coin_name().to_string()),
                     script_type: None, // Default script type
                     ecdsa_curve_name: Some("secp256k1".to_string()),
                     show_display: Some(false), // Don't show on device for xpub requests
@@ -309,19 +322,25 @@ pub async fn add_to_device_queue(
                 }
             }
         }
-        DeviceRequest::GetAddress { ref path, ref coin_name, ref script_type, show_display } => {
+        DeviceRequest::GetAddress { ref path, ref coin_name, ref script_type, show_display, ref multisig } => {
             let path_parts = crate::commands::parse_derivation_path(&path)?;
-            let script_type_int = match script_type.as_deref() {
-                Some("p2pkh") => Some(0),       // SPENDADDRESS = 0
-                Some("p2sh-p2wpkh") => Some(4), // SPENDP2SHWITNESS = 4  
-                Some("p2wpkh") => Some(3),      // SPENDWITNESS = 3
-                _ => None,
-            };
-            
-            queue_handle
-                .get_address(path_parts, coin_name.clone(), script_type_int, show_display)
-                .await
-                .map_err(|e| format!("Failed to get address: {}", e))
+            match multisig {
+                Some(config) => {
+                    crate::device::multisig::get_multisig_address(
+                        &queue_handle,
+                        path_parts,
+                        coin_name.clone(),
+                        script_type.as_deref(),
+                        show_display,
+                        config,
+                    )
+                    .await
+                }
+                None => queue_handle
+                    .get_address(path_parts, coin_name.clone(), input_script_type(script_type.as_deref()), show_display)
+                    .await
+                    .map_err(|e| format!("Failed to get address: {}", e)),
+            }
         }
         DeviceRequest::GetFeatures => {
             let features = queue_handle
@@ -344,7 +363,7 @@ pub async fn add_to_device_queue(
             
             Ok(features_json.to_string())
         }
-        DeviceRequest::SignTransaction { ref coin, ref inputs, ref outputs, version, lock_time } => {
+        DeviceRequest::SignTransaction { ref coin, ref inputs, ref outputs, version, lock_time, verify_signatures, verify_change } => {
             // Build transaction map with previous transactions and unsigned transaction
             let mut tx_map = std::collections::HashMap::new();
             
@@ -415,40 +434,17 @@ pub async fn add_to_device_queue(
                 });
             }
 
-            let mut new_tx_outputs = Vec::new();
-            for output in outputs {
-                let script_type = match output.address_type.as_str() {
-                    "change" => {
-                        // For change outputs, use address_n and appropriate script type
-                        match output.script_type.as_deref().unwrap_or("p2pkh") {
-                            "p2pkh" => keepkey_rust::messages::OutputScriptType::Paytoaddress,
-                            "p2sh" => keepkey_rust::messages::OutputScriptType::Paytoscripthash,
-                            "p2wpkh" => keepkey_rust::messages::OutputScriptType::Paytowitness,
-                            _ => keepkey_rust::messages::OutputScriptType::Paytoaddress,
-                        }
-                    },
-                    _ => {
-                        // For spend outputs
-                        keepkey_rust::messages::OutputScriptType::Paytoaddress
-                    }
-                };
+            let new_tx_outputs: Vec<_> = outputs.iter().map(build_tx_output).collect();
 
-                new_tx_outputs.push(keepkey_rust::messages::TxOutputType {
-                    address: if output.address_type == "change" { None } else { Some(output.address.clone()) },
-                    address_n: if output.address_type == "change" { 
-                        output.address_n_list.clone().unwrap_or_default() 
-                    } else { 
-                        vec![] 
-                    },
-                    amount: output.amount,
-                    script_type: script_type as i32,
-                    address_type: Some(if output.address_type == "change" {
-                        keepkey_rust::messages::OutputAddressType::Change as i32
-                    } else {
-                        keepkey_rust::messages::OutputAddressType::Spend as i32
-                    }),
-                    ..Default::default()
-                });
+            // Show each change address on the device before the signing protocol starts
+            if verify_change {
+                for change in outputs.iter().filter_map(crate::device::change::change_address_for) {
+                    println!("🔍 Verifying change address {:?} on device", change.path);
+                    queue_handle
+                        .get_address(change.path, coin.clone(), Some(change.input_script_type as i32), Some(true))
+                        .await
+                        .map_err(|e| format!("Change address verification failed: {}", e))?;
+                }
             }
 
             let unsigned_tx = keepkey_rust::messages::TransactionType {
@@ -467,86 +463,48 @@ pub async fn add_to_device_queue(
             tx_map.insert("unsigned".to_string(), unsigned_tx);
 
             // Start the Bitcoin signing protocol
-            let sign_tx = keepkey_rust::messages::Message::SignTx(
-                keepkey_rust::messages::SignTx {
-                    coin_name: Some(coin.clone()),
-                    inputs_count: inputs.len() as u32,
-                    outputs_count: outputs.len() as u32,
-                    version: Some(version),
-                    lock_time: Some(lock_time),
-                    ..Default::default()
-                }
-            );
+            let sign_tx = keepkey_rust::messages::SignTx {
+                coin_name: Some(coin.clone()),
+                inputs_count: inputs.len() as u32,
+                outputs_count: outputs.len() as u32,
+                version: Some(version),
+                lock_time: Some(lock_time),
+                ..Default::default()
+            };
 
             println!("📤 Sending SignTx message to device");
             
             // Execute the signing protocol
-            let mut current_message = sign_tx;
-            let mut signatures = Vec::new();
-            let mut serialized_tx_parts = Vec::new();
-            
-            let signing_result = loop {
-                let response = queue_handle.send_raw(current_message, false).await
-                    .map_err(|e| format!("Device communication error: {}", e))?;
+            let signing_result = run_sign_tx(&queue_handle, sign_tx, &tx_map, |signed| {
+                crate::device::signing::emit_signing_progress(&app, &request.device_id, &request.request_id, signed, inputs.len());
+            })
+            .await
+            .map(|(serialized_tx, signatures)| {
+                let signed_tx_hex = hex::encode(&serialized_tx);
                 
-                match response {
-                    keepkey_rust::messages::Message::TxRequest(tx_req) => {
-                        // Handle serialized data if present
-                        if let Some(serialized) = &tx_req.serialized {
-                            if let Some(serialized_tx) = &serialized.serialized_tx {
-                                serialized_tx_parts.push(serialized_tx.clone());
-                            }
-                            if let Some(signature) = &serialized.signature {
-                                if let Some(sig_index) = serialized.signature_index {
-                                    signatures.push((sig_index, hex::encode(signature)));
-                                }
-                            }
-                        }
-                        
-                        // Handle the transaction request
-                        match handle_tx_request(tx_req, &tx_map) {
-                            Ok(Some(next_msg)) => current_message = next_msg,
-                            Ok(None) => {
-                                // Transaction finished
-                                let mut serialized_tx = Vec::new();
-                                for part in &serialized_tx_parts {
-                                    serialized_tx.extend_from_slice(part);
-                                }
-                                
-                                let signed_tx_hex = hex::encode(&serialized_tx);
-                                
-                                println!("✅ Transaction signed successfully!");
-                                println!("   Signatures: {}", signatures.len());
-                                println!("   Serialized TX: {} bytes", serialized_tx.len());
-                                println!("📦 Raw Transaction Hex:");
-                                println!("   {}", signed_tx_hex);
-                                
-                                // Log individual signatures
-                                if !signatures.is_empty() {
-                                    println!("📝 Individual Signatures:");
-                                    for (idx, sig) in &signatures {
-                                        println!("   Input {}: {}", idx, sig);
-                                    }
-                                }
-                                
-                                // Don't return early - let the function continue to response creation
-                                break Ok(signed_tx_hex);
-                            }
-                            Err(e) => break Err(e),
-                        }
-                    }
-                    keepkey_rust::messages::Message::Failure(failure) => {
-                        let error = format!("Device returned error: {}", failure.message.unwrap_or_default());
-                        println!("❌ Failed to sign transaction: {}", error);
-                        break Err(error);
-                    }
-                    _ => {
-                        let error = format!("Unexpected response from device: {:?}", response);
-                        println!("❌ Failed to sign transaction: {}", error);
-                        break Err(error);
+                println!("✅ Transaction signed successfully!");
+                println!("   Signatures: {}", signatures.len());
+                println!("   Serialized TX: {} bytes", serialized_tx.len());
+                println!("📦 Raw Transaction Hex:");
+                println!("   {}", signed_tx_hex);
+                
+                // Log individual signatures
+                if !signatures.is_empty() {
+                    println!("📝 Individual Signatures:");
+                    for (idx, sig) in &signatures {
+                        println!("   Input {}: {}", idx, hex::encode(sig));
                     }
                 }
-            };
+                
+                signed_tx_hex
+            });
+            if let Err(error) = &signing_result {
+                println!("❌ Failed to sign transaction: {}", error);
+            }
+            
+            if let (true, Ok(signed_tx)) = (verify_signatures, &signing_result) {
+                report_signature_verification(&app, &request.device_id, &request.request_id, signed_tx, inputs);
+            }
             
             signing_result
         }
@@ -566,6 +524,11 @@ pub async fn add_to_device_queue(
         }
     };
     
+    crate::device::metrics::record_operation(&request.device_id, request_type, result.is_ok(), started.elapsed());
+    if let Err(e) = &result {
+        crate::device::connection::record_operation_error(&app, &request.device_id, e);
+    }
+
     // Create and store the response
     let device_response = match (&request.request, &result) {
         (DeviceRequest::GetXpub { path }, Ok(ref xpub)) => {
@@ -696,11 +659,18 @@ pub async fn add_to_device_queue(
         responses.insert(request.request_id.clone(), device_response.clone());
     }
     
-    // Emit event to frontend with the response
+    // Emit event to frontend with the response. A streamed signed transaction
+    // follows as tx:chunk events instead.
+    let mut emitted_response = device_response.clone();
+    if let DeviceResponse::SignedTransaction { ref mut signed_tx, .. } = emitted_response {
+        if crate::chain::tx_stream::is_streamed(&request.request_id) {
+            signed_tx.clear();
+        }
+    }
     let event_payload = serde_json::json!({
         "device_id": request.device_id,
         "request_id": request.request_id,
-        "response": device_response
+        "response": emitted_response
     });
     
     // EXPLICIT LOGGING FOR SIGNING EVENTS
@@ -763,6 +733,125 @@ pub async fn add_to_device_queue(
     }
 }
 
+/// The `TxOutputType` the device signs for `output`; change outputs are
+/// derived on the device from their path
+pub(crate) fn build_tx_output(output: &BitcoinUtxoOutput) -> keepkey_rust::messages::TxOutputType {
+    match crate::device::change::change_address_for(output) {
+        Some(change) => keepkey_rust::messages::TxOutputType {
+            address: None,
+            address_n: change.path,
+            amount: output.amount,
+            script_type: change.output_script_type as i32,
+            address_type: Some(keepkey_rust::messages::OutputAddressType::Change as i32),
+            ..Default::default()
+        },
+        None => keepkey_rust::messages::TxOutputType {
+            address: Some(output.address.clone()),
+            address_n: vec![],
+            amount: output.amount,
+            script_type: keepkey_rust::messages::OutputScriptType::Paytoaddress as i32,
+            address_type: Some(keepkey_rust::messages::OutputAddressType::Spend as i32),
+            ..Default::default()
+        },
+    }
+}
+
+/// Device script type for a single-sig `script_type`, `None` for the device default
+pub(crate) fn input_script_type(script_type: Option<&str>) -> Option<i32> {
+    match script_type {
+        Some("p2pkh") => Some(0),       // SPENDADDRESS = 0
+        Some("p2sh-p2wpkh") => Some(4), // SPENDP2SHWITNESS = 4
+        Some("p2wpkh") => Some(3),      // SPENDWITNESS = 3
+        _ => None,
+    }
+}
+
+/// Check the signatures of a freshly signed transaction and warn the frontend
+/// with `device:signature-verification-failed` if any don't verify. The signed
+/// transaction is handed back unchanged either way.
+fn report_signature_verification(
+    app: &AppHandle,
+    device_id: &str,
+    request_id: &str,
+    signed_tx: &str,
+    inputs: &[crate::commands::BitcoinUtxoInput],
+) {
+    let amounts: Vec<Option<u64>> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let amount = input.amount.parse::<u64>().ok();
+            if amount.is_none() {
+                println!("⚠️ Cannot verify input {}: amount {:?} is not a number of satoshis", index, input.amount);
+            }
+            amount
+        })
+        .collect();
+    let (failed_inputs, error) = match crate::device::signatures::verify_signed_transaction(signed_tx, &amounts) {
+        Ok(verification) if verification.all_valid() => {
+            println!("✅ Verified {} input signature(s) (nonce determinism not checkable on this firmware)", verification.inputs.len());
+            return;
+        }
+        Ok(verification) => (verification.inputs.into_iter().filter(|check| !check.valid).collect(), None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    
+    println!("⚠️ Signature verification failed for request {}: {:?} {:?}", request_id, failed_inputs, error);
+    let payload = serde_json::json!({
+        "device_id": device_id,
+        "request_id": request_id,
+        "failed_inputs": failed_inputs,
+        "error": error,
+    });
+    if let Err(e) = app.emit("device:signature-verification-failed", &payload) {
+        eprintln!("Failed to emit device:signature-verification-failed event: {}", e);
+    }
+}
+
+/// Drive the `SignTx` protocol to completion, answering the device's requests
+/// from `tx_map` (previous transactions by txid hex, plus `"unsigned"`).
+/// `on_signature` is called with the number of signatures received so far.
+/// Returns the serialized transaction and the device's signature per input index.
+pub(crate) async fn run_sign_tx(
+    queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle,
+    sign_tx: keepkey_rust::messages::SignTx,
+    tx_map: &HashMap<String, keepkey_rust::messages::TransactionType>,
+    mut on_signature: impl FnMut(usize),
+) -> Result<(Vec<u8>, Vec<(u32, Vec<u8>)>), String> {
+    let mut current_message = keepkey_rust::messages::Message::SignTx(sign_tx);
+    let mut signatures = Vec::new();
+    let mut serialized_tx = Vec::new();
+    
+    loop {
+        let response = queue_handle.send_raw(current_message, false).await
+            .map_err(|e| format!("Device communication error: {}", e))?;
+        
+        match response {
+            keepkey_rust::messages::Message::TxRequest(tx_req) => {
+                // Handle serialized data if present
+                if let Some(serialized) = &tx_req.serialized {
+                    if let Some(part) = &serialized.serialized_tx {
+                        serialized_tx.extend_from_slice(part);
+                    }
+                    if let (Some(signature), Some(sig_index)) = (&serialized.signature, serialized.signature_index) {
+                        signatures.push((sig_index, signature.clone()));
+                        on_signature(signatures.len());
+                    }
+                }
+                
+                match handle_tx_request(tx_req, tx_map)? {
+                    Some(next_msg) => current_message = next_msg,
+                    None => return Ok((serialized_tx, signatures)),
+                }
+            }
+            keepkey_rust::messages::Message::Failure(failure) => {
+                return Err(format!("Device returned error: {}", failure.message.unwrap_or_default()));
+            }
+            response => return Err(format!("Unexpected response from device: {:?}", response)),
+        }
+    }
+}
+
 /// Handle transaction request from device during Bitcoin signing protocol
 fn handle_tx_request(
     tx_req: keepkey_rust::messages::TxRequest,
@@ -0,0 +1,124 @@
+//! Time from plug-in to `device:ready`, to see what hotplug detection, the
+//! settle delay and feature caching buy.
+//!
+//! The clock starts when the monitor first sees a device. A device that comes
+//! up needing an update, setup or its PIN doesn't reach ready by itself, so
+//! its first evaluated status is recorded instead; reaching ready later (e.g.
+//! after an unlock) replaces it.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::device::lru::DeviceLru;
+
+/// What the measured time runs up to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadyMilestone {
+    Ready,
+    /// The device came up needing attention; time to its first status
+    FirstStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeToReady {
+    pub time_to_ready_ms: u64,
+    pub milestone: ReadyMilestone,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    connected_at: Instant,
+    measured: Option<(Duration, ReadyMilestone)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReadyTimer {
+    devices: DeviceLru<Timing>,
+}
+
+impl ReadyTimer {
+    /// Start the clock for a device the monitor just found; a reconnect starts over
+    pub fn connected(&mut self, device_id: &str, now: Instant) {
+        self.devices.insert(device_id, Timing { connected_at: now, measured: None });
+    }
+
+    /// Record that the device's status was evaluated. Returns the time to
+    /// ready when this is the device's first ready since it connected.
+    pub fn status(&mut self, device_id: &str, ready: bool, now: Instant) -> Option<Duration> {
+        let timing = self.devices.get_mut(device_id)?;
+        let elapsed = now.saturating_duration_since(timing.connected_at);
+        match (timing.measured, ready) {
+            (Some((_, ReadyMilestone::Ready)), _) => None,
+            (_, true) => {
+                timing.measured = Some((elapsed, ReadyMilestone::Ready));
+                Some(elapsed)
+            }
+            (None, false) => {
+                timing.measured = Some((elapsed, ReadyMilestone::FirstStatus));
+                None
+            }
+            (Some(_), false) => None,
+        }
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<TimeToReady> {
+        let (duration, milestone) = self.devices.peek(device_id)?.measured?;
+        Some(TimeToReady { time_to_ready_ms: duration.as_millis() as u64, milestone })
+    }
+}
+
+static READY_TIMER: once_cell::sync::Lazy<std::sync::Mutex<ReadyTimer>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(ReadyTimer::default()));
+
+pub fn with_timer<T>(f: impl FnOnce(&mut ReadyTimer) -> T) -> T {
+    match READY_TIMER.lock() {
+        Ok(mut timer) => f(&mut timer),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Time from plug-in to ready (or to the first status for a device that needed
+/// attention) of the device's last connection; `None` until it was probed
+#[tauri::command]
+pub async fn get_time_to_ready(unique_id: String) -> Result<Option<TimeToReady>, String> {
+    Ok(with_timer(|timer| timer.get(&unique_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_to_ready() {
+        let start = Instant::now();
+        let mut timer = ReadyTimer::default();
+
+        // Unknown devices have nothing to report
+        assert_eq!(timer.status("A", true, start), None);
+        assert_eq!(timer.get("A"), None);
+
+        timer.connected("A", start);
+        assert_eq!(timer.get("A"), None);
+        assert_eq!(timer.status("A", true, start + Duration::from_millis(820)), Some(Duration::from_millis(820)));
+        assert_eq!(timer.get("A"), Some(TimeToReady { time_to_ready_ms: 820, milestone: ReadyMilestone::Ready }));
+        // Later feature refreshes don't move it
+        assert_eq!(timer.status("A", true, start + Duration::from_secs(60)), None);
+        assert_eq!(timer.get("A").unwrap().time_to_ready_ms, 820);
+
+        // A device that needs an update only ever reports its first status
+        timer.connected("B", start);
+        assert_eq!(timer.status("B", false, start + Duration::from_millis(640)), None);
+        assert_eq!(timer.status("B", false, start + Duration::from_millis(900)), None);
+        assert_eq!(timer.get("B"), Some(TimeToReady { time_to_ready_ms: 640, milestone: ReadyMilestone::FirstStatus }));
+
+        // Unlocked later: ready replaces the first status
+        assert_eq!(timer.status("B", true, start + Duration::from_secs(12)), Some(Duration::from_secs(12)));
+        assert_eq!(timer.get("B").unwrap().milestone, ReadyMilestone::Ready);
+
+        // Reconnecting starts the clock over
+        timer.connected("A", start + Duration::from_secs(100));
+        assert_eq!(timer.get("A"), None);
+        assert_eq!(timer.status("A", true, start + Duration::from_millis(100_450)), Some(Duration::from_millis(450)));
+    }
+}
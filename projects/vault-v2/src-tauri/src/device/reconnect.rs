@@ -0,0 +1,171 @@
+//! Some settings changes (and some firmware quirks) make the device reboot
+//! right after it confirmed the operation. Its worker then holds a dead
+//! transport and every later request times out. After such operations the
+//! device is pinged; if the link is gone, the worker is replaced once the
+//! device has re-enumerated.
+
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// Time a rebooting device gets to drop off the bus before it is pinged
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+const LIVENESS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a rebooting device may take to come back
+pub const REENUMERATION_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// USB details of `device_id` as it is now, taken before an operation that may
+/// reboot it so it can be recognized when it comes back
+pub fn snapshot(device_id: &str) -> Option<FriendlyUsbDevice> {
+    keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .find(|d| d.unique_id == device_id)
+}
+
+/// Whether `candidate` is `original` after re-enumeration: the USB id may
+/// change (e.g. bus/address ids), the serial descriptor and hardware id don't
+pub fn is_same_device(original: &FriendlyUsbDevice, candidate: &FriendlyUsbDevice) -> bool {
+    candidate.unique_id == original.unique_id
+        || (original.serial_number.is_some() && candidate.serial_number == original.serial_number)
+        || crate::device::identity::same_hardware(&original.unique_id, &candidate.unique_id) == Some(true)
+}
+
+/// Wait for `original` to leave the bus and come back, polling `enumerate`.
+/// A device that never drops off is taken as it is once `timeout` is up (its
+/// transport failed without a re-enumeration); one that left and didn't
+/// return gives `None`.
+pub async fn wait_for_reenumeration(
+    original: &FriendlyUsbDevice,
+    timeout: Duration,
+    mut enumerate: impl FnMut() -> Vec<FriendlyUsbDevice>,
+) -> Option<FriendlyUsbDevice> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut left_bus = false;
+    let mut last_seen = None;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        match enumerate().into_iter().find(|d| is_same_device(original, d)) {
+            Some(device) if left_bus => return Some(device),
+            Some(device) => last_seen = Some(device),
+            None => {
+                left_bus = true;
+                last_seen = None;
+            }
+        }
+    }
+    last_seen
+}
+
+/// Check `device_id` still answers after `operation`; if it rebooted, replace
+/// its worker, refetch features and emit `device:reconnected-after-operation`.
+/// Returns the device's (possibly new) USB id.
+pub async fn check_after_operation(
+    app: &AppHandle,
+    queue_manager: &DeviceQueueManager,
+    device_id: &str,
+    operation: &str,
+    before: Option<FriendlyUsbDevice>,
+) -> Result<String, String> {
+    tokio::time::sleep(SETTLE_DELAY).await;
+    let Some(queue_handle) = queue_manager.lock().await.get(device_id).cloned() else {
+        return Ok(device_id.to_string());
+    };
+    match queue_handle
+        .send_raw_with_timeout(crate::device::benchmark::ping("alive".to_string()), true, LIVENESS_TIMEOUT)
+        .await
+    {
+        Ok(_) => return Ok(device_id.to_string()),
+        Err(e) if crate::device::connection::is_transport_error(&e.to_string()) => {
+            println!("🔁 Device {} stopped answering after {}: {}", device_id, operation, e);
+        }
+        Err(e) => return Err(format!("Device {} failed after {}: {}", device_id, operation, e)),
+    }
+
+    // The worker's transport is dead; a fresh one is opened once the device is back
+    queue_manager.remove_and_shutdown(device_id).await;
+    let Some(original) = before else {
+        return Err(format!("Device {} rebooted after {} and can't be recognized when it returns", device_id, operation));
+    };
+    let device = wait_for_reenumeration(&original, REENUMERATION_TIMEOUT, keepkey_rust::features::list_connected_devices)
+        .await
+        .ok_or_else(|| format!("Device {} didn't come back within {}s after {}", device_id, REENUMERATION_TIMEOUT.as_secs(), operation))?;
+
+    let queue_handle = queue_manager.get_or_spawn(&device.unique_id, &device).await;
+    crate::commands::invalidate_cached_features(device_id);
+    let features = queue_handle
+        .get_features()
+        .await
+        .map(crate::commands::convert_features_to_device_features)
+        .map_err(|e| format!("Device {} is back but its features couldn't be read: {}", device.unique_id, e))?;
+    crate::commands::cache_device_features(&device.unique_id, &features);
+
+    println!("✅ Device {} reconnected after {} as {}", device_id, operation, device.unique_id);
+    let _ = app.emit("device:reconnected-after-operation", serde_json::json!({
+        "deviceId": device.unique_id,
+        "previousDeviceId": device_id,
+        "operation": operation,
+        "features": features
+    }));
+    Ok(device.unique_id)
+}
+
+/// Run `check_after_operation` in the background so the command that changed
+/// the setting can return right away
+pub fn spawn_check_after_operation(
+    app: AppHandle,
+    queue_manager: DeviceQueueManager,
+    device_id: String,
+    operation: &'static str,
+    before: Option<FriendlyUsbDevice>,
+) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = check_after_operation(&app, &queue_manager, &device_id, operation, before).await {
+            println!("⚠️ {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(unique_id: &str, serial: Option<&str>) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(unique_id.to_string(), 0x2b24, 0x0002, None, None, serial.map(str::to_string))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reboot_after_apply_settings() {
+        // After ApplySettings the device is still listed for a moment, drops off
+        // the bus, and comes back on a new bus address with the same serial
+        let original = device("bus1_addr4", Some("343737340F4736331F003B00"));
+        let mut scans = vec![
+            vec![original.clone()],
+            vec![],
+            vec![],
+            vec![device("bus1_addr5", Some("343737340F4736331F003B00"))],
+        ]
+        .into_iter();
+        let back = wait_for_reenumeration(&original, REENUMERATION_TIMEOUT, || scans.next().unwrap_or_default()).await;
+        assert_eq!(back.map(|d| d.unique_id).as_deref(), Some("bus1_addr5"));
+
+        // Gone for good
+        let mut scans = vec![vec![original.clone()]].into_iter();
+        assert!(wait_for_reenumeration(&original, REENUMERATION_TIMEOUT, || scans.next().unwrap_or_default()).await.is_none());
+
+        // Never left the bus: the transport is reopened on the same device
+        let back = wait_for_reenumeration(&original, REENUMERATION_TIMEOUT, || vec![original.clone()]).await;
+        assert_eq!(back.map(|d| d.unique_id).as_deref(), Some("bus1_addr4"));
+    }
+
+    #[test]
+    fn test_same_device_after_reenumeration() {
+        let original = device("bus1_addr4", Some("SERIAL"));
+        assert!(is_same_device(&original, &device("bus1_addr4", None)));
+        assert!(is_same_device(&original, &device("bus2_addr9", Some("SERIAL"))));
+        assert!(!is_same_device(&original, &device("bus2_addr9", Some("OTHER"))));
+        assert!(!is_same_device(&device("bus1_addr4", None), &device("bus2_addr9", None)));
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Language notes fall back to when the requested one isn't available
+pub const DEFAULT_NOTES_LOCALE: &str = "en";
+
+/// Markdown release notes per version (without `v`) and language code
+pub type NotesByVersion = HashMap<String, HashMap<String, String>>;
+
+/// Release notes of one firmware version, in the best available language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseNotes {
+    pub version: String,
+    /// Language of `markdown`; differs from the requested one after a fallback
+    pub locale: String,
+    pub markdown: String,
+}
+
+/// `notes` of the releases.json bundled with the app, so they are there
+/// offline. Versions without an entry have no notes to show.
+static BUNDLED_NOTES: once_cell::sync::Lazy<NotesByVersion> = once_cell::sync::Lazy::new(|| {
+    let releases: serde_json::Value = match serde_json::from_str(include_str!("../../firmware/releases.json")) {
+        Ok(releases) => releases,
+        Err(e) => {
+            log::error!("Bundled releases.json is invalid: {}", e);
+            return NotesByVersion::new();
+        }
+    };
+    releases
+        .get("notes")
+        .cloned()
+        .and_then(|notes| serde_json::from_value(notes).ok())
+        .unwrap_or_default()
+});
+
+/// Notes for `version` in `locale`, trying the exact locale (`pt-BR`), then its
+/// language (`pt`), then English
+pub fn notes_for(notes: &NotesByVersion, version: &str, locale: &str) -> Option<ReleaseNotes> {
+    let version = version.trim().trim_start_matches('v');
+    let by_locale = notes.get(version)?;
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    [locale, language, DEFAULT_NOTES_LOCALE].into_iter().find_map(|candidate| {
+        by_locale
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(candidate))
+            .map(|(key, markdown)| ReleaseNotes {
+                version: version.to_string(),
+                locale: key.clone(),
+                markdown: markdown.clone(),
+            })
+    })
+}
+
+/// Bundled release notes for `version`
+pub fn release_notes(version: &str, locale: &str) -> Option<ReleaseNotes> {
+    notes_for(&BUNDLED_NOTES, version, locale)
+}
+
+/// Release notes for a firmware version, in `locale` or the app language;
+/// `None` when the manifest has no notes for that version
+#[tauri::command]
+pub async fn get_firmware_release_notes(version: String, locale: Option<String>) -> Result<Option<ReleaseNotes>, String> {
+    let locale = locale.unwrap_or_else(crate::commands::preferred_language);
+    Ok(release_notes(&version, &locale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_fallback() {
+        let notes: NotesByVersion = serde_json::from_value(serde_json::json!({
+            "7.10.0": { "en": "# 7.10.0\n- Fixes", "pt": "# 7.10.0\n- Correções", "pt-BR": "Notas" }
+        }))
+        .unwrap();
+
+        assert_eq!(notes_for(&notes, "v7.10.0", "pt-BR").unwrap().markdown, "Notas");
+        assert_eq!(notes_for(&notes, "7.10.0", "pt_PT").unwrap().locale, "pt");
+        let fallback = notes_for(&notes, "7.10.0", "de").unwrap();
+        assert_eq!((fallback.locale.as_str(), fallback.markdown.as_str()), ("en", "# 7.10.0\n- Fixes"));
+        assert!(notes_for(&notes, "7.9.0", "en").is_none());
+    }
+}
@@ -0,0 +1,141 @@
+//! "Check my backup": the firmware's recovery dry run. The user enters their
+//! recovery sentence through the same cipher exchange as `start_device_recovery`,
+//! but the device only compares it with the seed it holds and changes nothing.
+
+use keepkey_rust::features::DeviceFeatures;
+use keepkey_rust::messages::{Failure, Message};
+use serde::Serialize;
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt, SeedVerificationSession};
+
+/// `Failure_ActionCancelled` of the device protocol's FailureType
+const FAILURE_ACTION_CANCELLED: i32 = 4;
+
+/// How a dry run ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SeedCheckOutcome {
+    /// The entered sentence is the device's seed
+    Matches,
+    /// A different or invalid sentence was entered
+    Mismatch { reason: String },
+    /// Cancelled on the device (or by the host) before the device compared
+    Aborted,
+}
+
+impl SeedCheckOutcome {
+    /// Outcome of the device's final answer to the exchange; `None` while it is
+    /// still asking for input
+    pub fn from_response(response: &Message) -> Option<Self> {
+        match response {
+            Message::Success(_) => Some(SeedCheckOutcome::Matches),
+            Message::Failure(failure) => Some(Self::from_failure(failure)),
+            _ => None,
+        }
+    }
+
+    pub fn from_failure(failure: &Failure) -> Self {
+        let message = failure.message.clone().unwrap_or_default();
+        if failure.code == Some(FAILURE_ACTION_CANCELLED) || message.to_lowercase().contains("cancel") {
+            SeedCheckOutcome::Aborted
+        } else {
+            SeedCheckOutcome::Mismatch { reason: message }
+        }
+    }
+
+    /// Text for `RecoveryProgress::error`, which the verification wizard shows
+    /// for an unsuccessful check
+    pub fn error_message(&self) -> Option<String> {
+        match self {
+            SeedCheckOutcome::Matches => None,
+            SeedCheckOutcome::Mismatch { reason } if reason.is_empty() => {
+                Some("The recovery sentence doesn't match the one on this device".to_string())
+            }
+            SeedCheckOutcome::Mismatch { reason } => Some(reason.clone()),
+            SeedCheckOutcome::Aborted => Some("Seed check cancelled on the device. Nothing was changed.".to_string()),
+        }
+    }
+}
+
+/// A dry run compares against the stored seed, so there has to be one
+pub fn ensure_initialized(device_id: &str, features: &DeviceFeatures) -> Result<(), String> {
+    if features.initialized {
+        Ok(())
+    } else {
+        Err(format!(
+            "Device {} has no seed to check. Use recovery to restore a wallet onto it instead.",
+            device_id
+        ))
+    }
+}
+
+/// Start checking the user's backup against the device's seed. The exchange then
+/// continues with `send_verification_pin` and `send_verification_character`, the
+/// last of which reports whether the sentence matches. Unlike `start_device_recovery`
+/// this never writes to the device.
+#[tauri::command]
+pub async fn verify_seed(
+    unique_id: String,
+    word_count: u32,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<SeedVerificationSession, String> {
+    println!("🔍 Checking backup of device {} ({} words)", unique_id, word_count);
+
+    let features = match crate::commands::cached_device_features(&unique_id) {
+        Some(features) => features,
+        None => {
+            let queue_handle = queue_manager
+                .get_or_spawn_by_id(&unique_id)
+                .await
+                .ok_or_else(|| format!("Device {} not found", unique_id))?;
+            let features = queue_handle
+                .get_features()
+                .await
+                .map(crate::commands::convert_features_to_device_features)
+                .map_err(|e| format!("Failed to get features for device {}: {}", unique_id, e))?;
+            crate::commands::cache_device_features(&unique_id, &features);
+            features
+        }
+    };
+    ensure_initialized(&unique_id, &features)?;
+
+    crate::commands::start_seed_verification(unique_id, word_count, queue_manager).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(code: Option<i32>, message: &str) -> Message {
+        Message::Failure(Failure { code, message: Some(message.to_string()) })
+    }
+
+    #[test]
+    fn test_dry_run_outcome() {
+        assert_eq!(
+            SeedCheckOutcome::from_response(&Message::Success(Default::default())),
+            Some(SeedCheckOutcome::Matches)
+        );
+        let mismatch = SeedCheckOutcome::from_response(&failure(Some(99), "The seed is valid but does not match the one in the device"));
+        assert_eq!(
+            mismatch,
+            Some(SeedCheckOutcome::Mismatch { reason: "The seed is valid but does not match the one in the device".to_string() })
+        );
+        assert_eq!(SeedCheckOutcome::from_response(&failure(Some(FAILURE_ACTION_CANCELLED), "Aborted")), Some(SeedCheckOutcome::Aborted));
+        assert_eq!(SeedCheckOutcome::from_response(&failure(None, "Recovery cancelled")), Some(SeedCheckOutcome::Aborted));
+        assert_eq!(SeedCheckOutcome::from_response(&Message::CharacterRequest(Default::default())), None);
+
+        assert_eq!(SeedCheckOutcome::Matches.error_message(), None);
+        assert!(SeedCheckOutcome::Aborted.error_message().unwrap().contains("Nothing was changed"));
+    }
+
+    #[test]
+    fn test_refuses_uninitialized_device() {
+        let mut features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features::default());
+        features.initialized = false;
+        assert!(ensure_initialized("dev", &features).is_err());
+        features.initialized = true;
+        assert!(ensure_initialized("dev", &features).is_ok());
+    }
+}
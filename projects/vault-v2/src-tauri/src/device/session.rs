@@ -0,0 +1,482 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::lru::DeviceLru;
+use crate::logging::{log_device_request, log_device_response};
+
+/// Warn the UI when an unlocked device is estimated to lock within this time
+const ABOUT_TO_LOCK_THRESHOLD: Duration = Duration::from_secs(30);
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shortest auto-lock delay the firmware accepts
+const MIN_AUTO_LOCK_DELAY_MS: u32 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    /// Locked as last reported by the device, or because the delay has elapsed since
+    pub locked: bool,
+    pub auto_lock_delay_ms: Option<u32>,
+    /// Estimated from the last successful operation; the device doesn't report it
+    pub idle_for_ms: u64,
+    /// Estimated time until the device locks itself, if it is unlocked
+    pub locks_in_ms: Option<u64>,
+    /// Time left in the session started with `begin_session`, if one is running
+    pub session_expires_in_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Activity {
+    last: Instant,
+    /// `device:about-to-lock` already sent for this idle period
+    warned: bool,
+}
+
+static LAST_ACTIVITY: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<Activity>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+/// Note a successful operation - the device resets its auto-lock timer on every message
+pub fn record_activity(device_id: &str) {
+    crate::commands::lock_or_recover(&LAST_ACTIVITY, "device activity").insert(device_id, Activity { last: Instant::now(), warned: false });
+}
+
+pub fn forget_activity(device_id: &str) {
+    crate::commands::lock_or_recover(&LAST_ACTIVITY, "device activity").remove(device_id);
+}
+
+fn idle_for(device_id: &str) -> Option<Duration> {
+    crate::commands::lock_or_recover(&LAST_ACTIVITY, "device activity").peek(device_id).map(|a| a.last.elapsed())
+}
+
+fn session_info(features: &keepkey_rust::features::DeviceFeatures, idle: Duration) -> SessionInfo {
+    let delay = features.auto_lock_delay_ms.map(|ms| ms.min(u32::MAX as u64) as u32);
+    let reported_unlocked = features.pin_protection && features.pin_cached;
+    let remaining = delay.map(|d| Duration::from_millis(d as u64).saturating_sub(idle));
+    let expired = remaining.is_some_and(|r| r.is_zero());
+
+    SessionInfo {
+        locked: features.pin_protection && (!reported_unlocked || expired),
+        auto_lock_delay_ms: delay,
+        idle_for_ms: idle.as_millis() as u64,
+        locks_in_ms: if reported_unlocked && !expired {
+            remaining.map(|r| r.as_millis() as u64)
+        } else {
+            None
+        },
+        session_expires_in_ms: None,
+    }
+}
+
+/// Lock state, auto-lock delay and estimated idle time, for a "locks in N seconds" indicator
+#[tauri::command]
+pub async fn get_session_info(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<SessionInfo, String> {
+    // Prefer cached features: fetching would itself reset the device's idle timer
+    let features = match crate::commands::cached_device_features(&device_id) {
+        Some(features) => features,
+        None => {
+            let queue_handle = queue_manager
+                .get_or_spawn_by_id(&device_id)
+                .await
+                .ok_or_else(|| format!("Device {} not found", device_id))?;
+            let features = queue_handle
+                .get_features()
+                .await
+                .map(crate::commands::convert_features_to_device_features)
+                .map_err(|e| format!("Failed to get features for device {}: {}", device_id, e))?;
+            crate::commands::cache_device_features(&device_id, &features);
+            record_activity(&device_id);
+            features
+        }
+    };
+
+    let mut info = session_info(&features, idle_for(&device_id).unwrap_or_default());
+    info.session_expires_in_ms = host_session_remaining(&device_id).map(|r| r.as_millis() as u64);
+    Ok(info)
+}
+
+/// Set how long the device stays unlocked without activity
+#[tauri::command]
+pub async fn set_auto_lock_delay(
+    device_id: String,
+    delay_ms: u32,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    println!("Setting auto-lock delay for {}: {}ms", device_id, delay_ms);
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let request_data = serde_json::json!({
+        "device_id": device_id,
+        "delay_ms": delay_ms,
+        "operation": "set_auto_lock_delay"
+    });
+    if let Err(e) = log_device_request(&device_id, &request_id, "SetAutoLockDelay", &request_data).await {
+        eprintln!("Failed to log set auto-lock delay request: {}", e);
+    }
+
+    let result = apply_auto_lock_delay(&app, &device_id, delay_ms, &queue_manager).await;
+
+    let response_data = match &result {
+        Ok(()) => serde_json::json!({ "success": true, "operation": "set_auto_lock_delay" }),
+        Err(e) => serde_json::json!({ "error": e, "operation": "set_auto_lock_delay" }),
+    };
+    if let Err(e) = log_device_response(&device_id, &request_id, result.is_ok(), &response_data, result.as_ref().err().map(|e| e.as_str())).await {
+        eprintln!("Failed to log set auto-lock delay response: {}", e);
+    }
+
+    result
+}
+
+async fn apply_auto_lock_delay(
+    app: &AppHandle,
+    device_id: &str,
+    delay_ms: u32,
+    queue_manager: &DeviceQueueManager,
+) -> Result<(), String> {
+    if delay_ms < MIN_AUTO_LOCK_DELAY_MS {
+        return Err(format!("Auto-lock delay must be at least {} seconds", MIN_AUTO_LOCK_DELAY_MS / 1000));
+    }
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    let apply_settings = keepkey_rust::messages::Message::ApplySettings(keepkey_rust::messages::ApplySettings {
+        language: None,
+        label: None,
+        use_passphrase: None,
+        auto_lock_delay_ms: Some(delay_ms),
+        u2f_counter: None,
+    });
+
+    let before = crate::device::reconnect::snapshot(device_id);
+    match queue_handle.send_raw(apply_settings, true).await {
+        Ok(keepkey_rust::messages::Message::Success(_)) => {
+            println!("✅ Auto-lock delay set for {}", device_id);
+            crate::commands::invalidate_cached_features(device_id);
+            crate::device::reconnect::spawn_check_after_operation(
+                app.clone(),
+                queue_manager.clone(),
+                device_id.to_string(),
+                "set_auto_lock_delay",
+                before,
+            );
+            Ok(())
+        }
+        Ok(keepkey_rust::messages::Message::Failure(failure)) => {
+            Err(format!("Device rejected auto-lock delay: {}", failure.message.unwrap_or_default()))
+        }
+        Ok(other) => Err(format!("Unexpected response from device: {:?}", other.message_type())),
+        Err(e) => Err(format!("Failed to set auto-lock delay: {}", e)),
+    }
+}
+
+/// Periodically emit `device:about-to-lock` for unlocked devices whose estimated
+/// remaining time drops below `ABOUT_TO_LOCK_THRESHOLD` (once per idle period)
+pub fn spawn_lock_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let tracked: Vec<(String, Activity)> = crate::commands::lock_or_recover(&LAST_ACTIVITY, "device activity")
+                .iter()
+                .map(|(id, a)| (id.clone(), *a))
+                .collect();
+
+            for (device_id, activity) in tracked {
+                if activity.warned {
+                    continue;
+                }
+                let Some(features) = crate::commands::cached_device_features(&device_id) else {
+                    continue;
+                };
+                let info = session_info(&features, activity.last.elapsed());
+                let Some(locks_in_ms) = info.locks_in_ms else {
+                    continue;
+                };
+                if locks_in_ms > ABOUT_TO_LOCK_THRESHOLD.as_millis() as u64 {
+                    continue;
+                }
+
+                println!("⏳ Device {} will lock in about {}s", device_id, locks_in_ms / 1000);
+                let _ = app.emit("device:about-to-lock", serde_json::json!({
+                    "deviceId": device_id,
+                    "locksInMs": locks_in_ms,
+                    "autoLockDelayMs": info.auto_lock_delay_ms
+                }));
+                // Only if no new activity happened in the meantime
+                if let Some(current) = crate::commands::lock_or_recover(&LAST_ACTIVITY, "device activity").get_mut(&device_id) {
+                    if current.last == activity.last {
+                        current.warned = true;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Host session length when `begin_session` isn't given one
+const DEFAULT_HOST_SESSION: Duration = Duration::from_secs(10 * 60);
+/// Longest a host session may run before the device has to be unlocked again
+const MAX_HOST_SESSION: Duration = Duration::from_secs(60 * 60);
+
+/// A stretch of reads the user unlocked the device for once
+#[derive(Debug, Clone, Copy)]
+struct HostSession {
+    started: Instant,
+    expires: Instant,
+}
+
+static HOST_SESSIONS: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<HostSession>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+fn with_host_sessions<T>(f: impl FnOnce(&mut DeviceLru<HostSession>) -> T) -> T {
+    match HOST_SESSIONS.lock() {
+        Ok(mut sessions) => f(&mut sessions),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+/// Time left in the device's host session, `None` if none is running
+pub fn host_session_remaining(device_id: &str) -> Option<Duration> {
+    let session = with_host_sessions(|sessions| sessions.peek(device_id).copied())?;
+    Some(session.expires.saturating_duration_since(Instant::now())).filter(|r| !r.is_zero())
+}
+
+fn host_session_ttl(requested_secs: Option<u64>) -> Duration {
+    requested_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_HOST_SESSION)
+        .clamp(Duration::from_millis(MIN_AUTO_LOCK_DELAY_MS as u64), MAX_HOST_SESSION)
+}
+
+/// How often to ping the device so it doesn't auto-lock mid-session: well
+/// within its auto-lock delay
+fn keepalive_interval(auto_lock_delay_ms: Option<u64>) -> Duration {
+    auto_lock_delay_ms
+        .map(|ms| Duration::from_millis(ms / 2))
+        .unwrap_or(Duration::from_secs(60))
+        .max(WATCH_INTERVAL)
+}
+
+/// Result of `begin_session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SessionStart {
+    /// Unlocked; reads without display won't prompt again until the session expires
+    Active {
+        #[serde(rename = "expiresInMs")]
+        expires_in_ms: u64,
+    },
+    /// The device shows the PIN matrix; answer it with `send_pin_matrix_ack`,
+    /// then call `begin_session` again
+    PinRequired,
+    /// The device has passphrase protection; call `begin_session` again with one
+    PassphraseRequired,
+}
+
+/// Unlock the device once (PIN, then passphrase) and keep it unlocked for
+/// `ttl_secs` (10 minutes by default, at most an hour) so a sync flow's
+/// `get_public_key`/`get_address` reads without display don't re-prompt.
+///
+/// The session only spares unlock prompts: signing, and anything shown on the
+/// device, still asks for confirmation on the device every time.
+#[tauri::command]
+pub async fn begin_session(
+    device_id: String,
+    ttl_secs: Option<u64>,
+    passphrase: Option<String>,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<SessionStart, String> {
+    println!("🔓 Starting session for {}", device_id);
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+
+    if let Some(prompt) = unlock(&queue_handle, &device_id, passphrase).await? {
+        return Ok(prompt);
+    }
+
+    let ttl = host_session_ttl(ttl_secs);
+    let started = Instant::now();
+    with_host_sessions(|sessions| sessions.insert(&device_id, HostSession { started, expires: started + ttl }));
+    record_activity(&device_id);
+    let auto_lock_delay_ms = crate::commands::cached_device_features(&device_id).and_then(|f| f.auto_lock_delay_ms);
+    // The cached features predate the unlock
+    crate::commands::invalidate_cached_features(&device_id);
+
+    println!("✅ Session for {} runs for {}s", device_id, ttl.as_secs());
+    let _ = app.emit("device:session-started", serde_json::json!({
+        "deviceId": device_id,
+        "expiresInMs": ttl.as_millis() as u64
+    }));
+
+    spawn_keepalive(app, device_id, started, keepalive_interval(auto_lock_delay_ms), queue_handle);
+
+    Ok(SessionStart::Active { expires_in_ms: ttl.as_millis() as u64 })
+}
+
+/// Run a read that needs the wallet until the device answers it, supplying the
+/// passphrase if asked. Returns the prompt the user still has to answer, if any.
+async fn unlock(
+    queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle,
+    device_id: &str,
+    mut passphrase: Option<String>,
+) -> Result<Option<SessionStart>, String> {
+    use keepkey_rust::messages::{self, Message};
+
+    let mut message: Message = messages::GetAddress {
+        address_n: vec![0x8000_002c, 0x8000_0000, 0x8000_0000, 0, 0],
+        coin_name: Some("Bitcoin".to_string()),
+        script_type: Some(0),
+        show_display: Some(false),
+        ..Default::default()
+    }
+    .into();
+
+    loop {
+        match queue_handle.send_raw(message, true).await {
+            Ok(Message::Address(_)) => return Ok(None),
+            Ok(Message::PinMatrixRequest(_)) => {
+                // Keep the monitor's feature probes away from the PIN prompt
+                crate::commands::mark_device_in_pin_flow(device_id)?;
+                return Ok(Some(SessionStart::PinRequired));
+            }
+            Ok(Message::PassphraseRequest(_)) => match passphrase.take() {
+                Some(passphrase) => message = messages::PassphraseAck { passphrase }.into(),
+                None => {
+                    let _ = queue_handle.send_raw(messages::Cancel {}.into(), true).await;
+                    return Ok(Some(SessionStart::PassphraseRequired));
+                }
+            },
+            Ok(Message::ButtonRequest(_)) => message = messages::ButtonAck::default().into(),
+            Ok(Message::Failure(failure)) => {
+                return Err(format!("Device refused to unlock: {}", failure.message.unwrap_or_default()))
+            }
+            Ok(other) => return Err(format!("Unexpected response from device: {:?}", other.message_type())),
+            Err(e) => return Err(format!("Failed to unlock device {}: {}", device_id, e)),
+        }
+    }
+}
+
+/// Ping the device through its session so it doesn't auto-lock (except in
+/// low-power mode), and clear it once the session expires. Stops as soon as the
+/// session is ended or replaced.
+fn spawn_keepalive(
+    app: AppHandle,
+    device_id: String,
+    started: Instant,
+    interval: Duration,
+    queue_handle: keepkey_rust::device_queue::DeviceQueueHandle,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let Some(session) = with_host_sessions(|sessions| sessions.peek(&device_id).copied()) else {
+                return;
+            };
+            if session.started != started {
+                return;
+            }
+            let remaining = session.expires.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                println!("⌛ Session for {} expired", device_id);
+                close_host_session(&app, &queue_handle, &device_id, "expired").await;
+                return;
+            }
+            tokio::time::sleep(remaining.min(interval)).await;
+            // In low-power mode the device may auto-lock; the session still expires on time
+            if remaining <= interval || crate::event_controller::is_low_power(&app) {
+                continue;
+            }
+
+            let ping = keepkey_rust::messages::Ping { message: Some("keepalive".to_string()), ..Default::default() };
+            if let Err(e) = queue_handle.send_raw(ping.into(), true).await {
+                println!("⚠️ Session keepalive failed for {}: {}", device_id, e);
+                if with_host_sessions(|sessions| sessions.remove(&device_id)).is_some() {
+                    emit_session_ended(&app, &device_id, "disconnected");
+                }
+                return;
+            }
+        }
+    });
+}
+
+fn emit_session_ended(app: &AppHandle, device_id: &str, reason: &str) {
+    let _ = app.emit("device:session-ended", serde_json::json!({
+        "deviceId": device_id,
+        "reason": reason
+    }));
+}
+
+/// Forget the host session and lock the device again (`ClearSession`)
+async fn close_host_session(
+    app: &AppHandle,
+    queue_handle: &keepkey_rust::device_queue::DeviceQueueHandle,
+    device_id: &str,
+    reason: &str,
+) {
+    if with_host_sessions(|sessions| sessions.remove(device_id)).is_none() {
+        return;
+    }
+    match queue_handle.send_raw(keepkey_rust::messages::ClearSession::default().into(), true).await {
+        Ok(keepkey_rust::messages::Message::Success(_)) => println!("🔒 Session cleared on {}", device_id),
+        Ok(other) => println!("⚠️ Unexpected response to ClearSession on {}: {:?}", device_id, other.message_type()),
+        Err(e) => println!("⚠️ Failed to clear session on {}: {}", device_id, e),
+    }
+    crate::commands::invalidate_cached_features(device_id);
+    emit_session_ended(app, device_id, reason);
+}
+
+/// End the session started with `begin_session`; the next read asks for the PIN again
+#[tauri::command]
+pub async fn end_session(
+    device_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&device_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", device_id))?;
+    close_host_session(&app, &queue_handle, &device_id, "ended").await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_session_bounds() {
+        assert_eq!(host_session_ttl(None), DEFAULT_HOST_SESSION);
+        assert_eq!(host_session_ttl(Some(1)), Duration::from_secs(10));
+        assert_eq!(host_session_ttl(Some(24 * 60 * 60)), MAX_HOST_SESSION);
+
+        // Pings land well inside the auto-lock delay, but not more often than the watcher runs
+        assert_eq!(keepalive_interval(Some(60_000)), Duration::from_secs(30));
+        assert_eq!(keepalive_interval(Some(4_000)), WATCH_INTERVAL);
+        assert_eq!(keepalive_interval(None), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_host_session_remaining() {
+        let now = Instant::now();
+        with_host_sessions(|sessions| {
+            sessions.insert("session-running", HostSession { started: now, expires: now + Duration::from_secs(60) });
+            sessions.insert("session-expired", HostSession { started: now, expires: now });
+        });
+        assert!(host_session_remaining("session-running").is_some_and(|r| r > Duration::from_secs(50)));
+        assert_eq!(host_session_remaining("session-expired"), None);
+        assert_eq!(host_session_remaining("session-unknown"), None);
+    }
+}
@@ -0,0 +1,139 @@
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{PublicKey, ScriptBuf, Transaction};
+use serde::Serialize;
+
+/// Result of checking the signature of one input of a signed transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputSignatureCheck {
+    pub input: usize,
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+/// Post-sign check of a transaction returned by the device (`verify_signatures`).
+///
+/// Every input's ECDSA signature is verified against the sighash recomputed from
+/// the signed transaction itself and the pubkey it reveals, so a signature that
+/// doesn't match what was signed - or a non-canonical, high-S one - is caught
+/// before broadcast. Whether the nonce was derived per RFC 6979 can't be checked
+/// from the host: KeepKey firmware has no nonce commitment (anti-klepto) protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignatureVerification {
+    pub inputs: Vec<InputSignatureCheck>,
+    /// Always false until firmware exposes a nonce commitment
+    pub nonce_verified: bool,
+}
+
+impl SignatureVerification {
+    pub fn all_valid(&self) -> bool {
+        self.inputs.iter().all(|check| check.valid)
+    }
+}
+
+/// Verify the signatures of `signed_tx_hex`. `input_amounts` are the spent
+/// amounts in input order, needed for segwit sighashes; a segwit input whose
+/// amount is `None` is reported as not verifiable.
+pub fn verify_signed_transaction(signed_tx_hex: &str, input_amounts: &[Option<u64>]) -> Result<SignatureVerification, String> {
+    let bytes = hex::decode(signed_tx_hex).map_err(|e| format!("Invalid signed transaction hex: {}", e))?;
+    let tx: Transaction = bitcoin::consensus::deserialize(&bytes)
+        .map_err(|e| format!("Failed to parse signed transaction: {}", e))?;
+    if tx.input.len() != input_amounts.len() {
+        return Err(format!("Signed transaction has {} inputs, expected {}", tx.input.len(), input_amounts.len()));
+    }
+
+    let inputs = (0..tx.input.len())
+        .map(|index| match verify_input(&tx, index, input_amounts[index]) {
+            Ok(()) => InputSignatureCheck { input: index, valid: true, error: None },
+            Err(e) => InputSignatureCheck { input: index, valid: false, error: Some(e) },
+        })
+        .collect();
+    Ok(SignatureVerification { inputs, nonce_verified: false })
+}
+
+fn verify_input(tx: &Transaction, index: usize, amount: Option<u64>) -> Result<(), String> {
+    let input = &tx.input[index];
+    let mut cache = SighashCache::new(tx);
+
+    // p2wpkh and p2sh-p2wpkh carry [signature, pubkey] in the witness, p2pkh in the scriptSig
+    let (signature, pubkey, sighash) = if !input.witness.is_empty() {
+        let (signature, pubkey) = match (input.witness.nth(0), input.witness.nth(1)) {
+            (Some(signature), Some(pubkey)) => (parse_signature(signature)?, parse_pubkey(pubkey)?),
+            _ => return Err("Witness is not a single-key spend".to_string()),
+        };
+        let amount = amount.ok_or_else(|| format!("Cannot verify input {}: its amount is unknown", index))?;
+        let script_code = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let sighash = cache
+            .segwit_signature_hash(index, &script_code, amount, signature.hash_ty)
+            .map_err(|e| format!("Failed to compute sighash: {}", e))?;
+        (signature, pubkey, Message::from_slice(sighash.as_ref()))
+    } else {
+        let pushes = input
+            .script_sig
+            .instructions()
+            .map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Ok(bytes.as_bytes().to_vec()),
+                _ => Err("scriptSig is not a single-key spend".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [signature, pubkey] = pushes.as_slice() else {
+            return Err("scriptSig is not a single-key spend".to_string());
+        };
+        let (signature, pubkey) = (parse_signature(signature)?, parse_pubkey(pubkey)?);
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let sighash = cache
+            .legacy_signature_hash(index, &script_pubkey, signature.hash_ty.to_u32())
+            .map_err(|e| format!("Failed to compute sighash: {}", e))?;
+        (signature, pubkey, Message::from_slice(sighash.as_ref()))
+    };
+
+    let message = sighash.map_err(|e| format!("Invalid sighash: {}", e))?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(&message, &signature.sig, &pubkey.inner)
+        .map_err(|_| "Signature does not verify against the input's pubkey".to_string())
+}
+
+fn parse_signature(bytes: &[u8]) -> Result<bitcoin::ecdsa::Signature, String> {
+    bitcoin::ecdsa::Signature::from_slice(bytes).map_err(|e| format!("Malformed signature: {}", e))
+}
+
+fn parse_pubkey(bytes: &[u8]) -> Result<PublicKey, String> {
+    PublicKey::from_slice(bytes).map_err(|e| format!("Malformed pubkey: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input 0 spends p2wpkh (100000 sat), input 1 p2pkh (50000 sat)
+    const SIGNED_TX: &str = "02000000000102000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f0000000000ffffffff202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f010000006a4730440220718fcd944e69ebb8736376752884a2527df4cfdabfc7d91062e5e8b7241b05110220467a8a043806c4f9d69a46fb16f91a56bd699b9d61aec9f7ffc7d14333afd1b9012102466d7fcae563e5cb09a0d1870bb580344804617879a14949cf22285f1bae3f27ffffffff01e022020000000000160014fc7250a211deddc70ee5a2738de5f07817351cef02483045022100e2e1c44cafdcedf7acfbd95990b61009e7d2b570694b5c2932442374f3af656502201d810d5d9d43bb16d74a26e4f5099501c902a6b60ff1d538b5308116788edf0b0121034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa0000000000";
+
+    #[test]
+    fn test_valid_signatures_verify() {
+        let verification = verify_signed_transaction(SIGNED_TX, &[Some(100000), Some(50000)]).unwrap();
+        assert!(verification.all_valid(), "{:?}", verification);
+        assert!(!verification.nonce_verified);
+    }
+
+    #[test]
+    fn test_wrong_amount_fails_segwit_input_only() {
+        // The segwit sighash commits to the amount; the legacy one doesn't
+        let verification = verify_signed_transaction(SIGNED_TX, &[Some(100001), Some(50000)]).unwrap();
+        assert!(!verification.inputs[0].valid);
+        assert!(verification.inputs[1].valid);
+        assert!(!verification.all_valid());
+
+        assert!(verify_signed_transaction(SIGNED_TX, &[Some(100000)]).is_err());
+        assert!(verify_signed_transaction("zz", &[]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_amount_leaves_segwit_input_unverified() {
+        let verification = verify_signed_transaction(SIGNED_TX, &[None, None]).unwrap();
+        assert!(!verification.inputs[0].valid);
+        assert_eq!(verification.inputs[0].error.as_deref(), Some("Cannot verify input 0: its amount is unknown"));
+        // The legacy sighash doesn't need it
+        assert!(verification.inputs[1].valid);
+    }
+}
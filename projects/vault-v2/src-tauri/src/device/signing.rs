@@ -0,0 +1,94 @@
+//! Signing on several devices at once.
+//!
+//! Every device has its own worker, and with it its own transport, screen
+//! hint, prompt forwarding (`device:button-request` carries the device id) and
+//! cancellation flag, so a signing session on one device never waits on or
+//! sees another's. Requests and their responses are keyed by request id, and
+//! the PIN flow and policy confirmations by device and approval id. The only
+//! app-wide cancellation is closing the window (`cancel_on_window_close`);
+//! `cancel_signing` cancels one device's prompt and nothing else.
+
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::DeviceQueueManager;
+
+/// `device:signing-progress` - `signed` of `inputs` input signatures returned so far
+pub fn signing_progress_payload(device_id: &str, request_id: &str, signed: usize, inputs: usize) -> serde_json::Value {
+    serde_json::json!({
+        "device_id": device_id,
+        "request_id": request_id,
+        "signed": signed,
+        "inputs": inputs,
+    })
+}
+
+pub fn emit_signing_progress(app: &AppHandle, device_id: &str, request_id: &str, signed: usize, inputs: usize) {
+    if let Err(e) = app.emit("device:signing-progress", signing_progress_payload(device_id, request_id, signed, inputs)) {
+        eprintln!("Failed to emit device:signing-progress event: {}", e);
+    }
+}
+
+/// Cancel the confirmation `unique_id` shows for a transaction being signed.
+/// The device fails the signing with `ActionCancelled`; signing sessions on
+/// other devices carry on. Returns false when the device shows no prompt.
+#[tauri::command]
+pub async fn cancel_signing(unique_id: String, queue_manager: State<'_, DeviceQueueManager>) -> Result<bool, String> {
+    // Only a running worker can be showing a prompt
+    let handle = queue_manager.lock().await.get(&unique_id).cloned();
+    let Some(handle) = handle else {
+        return Ok(false);
+    };
+    let cancelled = handle.cancel_prompt().await;
+    if cancelled {
+        println!("🚫 Cancelled the signing prompt on {}", unique_id);
+    }
+    Ok(cancelled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{spawn_scripted_worker, MockDevice, MockScenario};
+    use keepkey_rust::device_queue::DeviceQueueHandle;
+    use keepkey_rust::messages::{self, Message};
+    use keepkey_rust::screen_hint::ScreenHint;
+    use std::time::Duration;
+
+    fn start_signing(handle: &DeviceQueueHandle) -> tokio::task::JoinHandle<anyhow::Result<Message>> {
+        let handle = handle.clone();
+        tokio::spawn(async move { handle.send_raw(messages::SignTx::default().into(), false).await })
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_one_device_leaves_the_other_signing() {
+        let first = spawn_scripted_worker("signing-test-a", MockDevice::new(MockScenario::Ready));
+        let second = spawn_scripted_worker("signing-test-b", MockDevice::new(MockScenario::Ready));
+        let (signing_first, signing_second) = (start_signing(&first), start_signing(&second));
+
+        // Both devices ask for their confirmation at the same time
+        while first.screen_hint() != ScreenHint::ConfirmTransaction || second.screen_hint() != ScreenHint::ConfirmTransaction {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(first.cancel_prompt().await);
+
+        match signing_first.await.unwrap() {
+            Ok(Message::Failure(failure)) => assert_eq!(failure.code, Some(4)),
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        match signing_second.await.unwrap() {
+            Ok(Message::TxRequest(request)) => {
+                assert_eq!(request.request_type, Some(messages::RequestType::Txfinished as i32));
+                assert!(request.serialized.and_then(|s| s.serialized_tx).is_some());
+            }
+            other => panic!("unexpected answer: {:?}", other),
+        }
+        assert_eq!(second.screen_hint(), ScreenHint::Idle);
+
+        // The cancel didn't linger: the next signing on the first device goes through
+        assert!(matches!(start_signing(&first).await.unwrap(), Ok(Message::TxRequest(_))));
+
+        let progress = signing_progress_payload(second.device_id(), "request-b", 1, 1);
+        assert_eq!(progress["device_id"], "signing-test-b");
+        assert_eq!(progress["request_id"], "request-b");
+    }
+}
@@ -0,0 +1,245 @@
+use keepkey_rust::features::DeviceFeatures;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+
+use crate::commands::DeviceStatus;
+
+/// Connection state of a device, keyed by its `unique_id`.
+///
+/// ```text
+/// Disconnected -> Connected -> Probing -> { Ready | NeedsUpdate | Locked | Error }
+/// ```
+///
+/// Settled states go back to `Probing` when features are fetched again, `Locked`
+/// and `Ready` switch directly on unlock / auto-lock, `Error` recovers through
+/// `Probing`, and any state except `Disconnected` can drop to `Disconnected`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceState {
+    Disconnected,
+    Connected,
+    Probing,
+    Ready,
+    /// Needs a bootloader/firmware update, setup, or a reboot out of bootloader mode
+    NeedsUpdate,
+    /// Initialized and PIN protected, PIN not entered
+    Locked,
+    Error,
+}
+
+impl DeviceState {
+    pub fn can_transition_to(self, to: DeviceState) -> bool {
+        use DeviceState::*;
+        match (self, to) {
+            (Disconnected, Connected) => true,
+            (Disconnected, _) => false,
+            (_, Disconnected) => true,
+            (Connected, Probing) => true,
+            // DFU-mode devices can't be probed at all
+            (Connected, Error) => true,
+            (Probing, Ready | NeedsUpdate | Locked | Error) => true,
+            (Ready | NeedsUpdate | Locked | Error, Probing) => true,
+            (Locked, Ready) | (Ready, Locked) => true,
+            (Ready | NeedsUpdate | Locked, Error) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The settled state a successful probe ends in
+pub fn state_for_features(features: &DeviceFeatures, status: &DeviceStatus) -> DeviceState {
+    let is_pin_locked = features.initialized && features.pin_protection && !features.pin_cached;
+    if features.bootloader_mode
+        || status.needs_bootloader_update
+        || status.needs_firmware_update
+        || status.needs_initialization
+    {
+        DeviceState::NeedsUpdate
+    } else if is_pin_locked {
+        DeviceState::Locked
+    } else {
+        DeviceState::Ready
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateChange {
+    pub device_id: String,
+    pub from: DeviceState,
+    pub to: DeviceState,
+}
+
+/// Current state of every known device; devices not in the map are `Disconnected`
+#[derive(Debug, Default)]
+pub struct DeviceStateMachine {
+    states: HashMap<String, DeviceState>,
+}
+
+impl DeviceStateMachine {
+    pub fn state(&self, device_id: &str) -> DeviceState {
+        self.states.get(device_id).copied().unwrap_or(DeviceState::Disconnected)
+    }
+
+    /// Move a device to `to`. Returns `Ok(None)` if it is already there and an
+    /// error (leaving the state unchanged) if the transition isn't allowed.
+    pub fn transition(&mut self, device_id: &str, to: DeviceState) -> Result<Option<StateChange>, String> {
+        let from = self.state(device_id);
+        if from == to {
+            return Ok(None);
+        }
+        if !from.can_transition_to(to) {
+            return Err(format!("Invalid state transition for {}: {:?} -> {:?}", device_id, from, to));
+        }
+
+        if to == DeviceState::Disconnected {
+            self.states.remove(device_id);
+        } else {
+            self.states.insert(device_id.to_string(), to);
+        }
+        println!("🔀 Device {} state: {:?} -> {:?}", device_id, from, to);
+        Ok(Some(StateChange { device_id: device_id.to_string(), from, to }))
+    }
+}
+
+static DEVICE_STATES: once_cell::sync::Lazy<std::sync::Mutex<DeviceStateMachine>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceStateMachine::default()));
+
+/// Apply a transition to the app-wide state table
+pub fn transition(device_id: &str, to: DeviceState) -> Result<Option<StateChange>, String> {
+    // Released before recording: metrics checks the state table for pinned devices
+    let change = DEVICE_STATES
+        .lock()
+        .map_err(|_| "Device state table poisoned".to_string())?
+        .transition(device_id, to)?;
+    if change.as_ref().is_some_and(|c| c.to == DeviceState::Connected) {
+        crate::device::metrics::record_connected(device_id);
+    }
+    Ok(change)
+}
+
+/// Drop a device from the state table without a transition, so its next
+/// appearance starts again from `Disconnected`
+pub fn forget(device_id: &str) {
+    let mut states = match DEVICE_STATES.lock() {
+        Ok(states) => states,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    states.states.remove(device_id);
+}
+
+/// Devices in any state but `Disconnected`
+pub fn connected_device_count() -> usize {
+    DEVICE_STATES.lock().map(|states| states.states.len()).unwrap_or(0)
+}
+
+pub fn device_state(device_id: &str) -> DeviceState {
+    DEVICE_STATES
+        .lock()
+        .map(|states| states.state(device_id))
+        .unwrap_or(DeviceState::Disconnected)
+}
+
+/// Payload of `device:state-changed`
+pub fn state_changed_payload(change: &StateChange) -> serde_json::Value {
+    serde_json::json!({
+        "deviceId": change.device_id,
+        "from": change.from,
+        "to": change.to
+    })
+}
+
+/// Settle a device's state after features were fetched outside the monitor
+/// (e.g. by `get_device_info_by_id` after a PIN unlock). Only connected devices
+/// whose probe already completed are updated.
+pub async fn settle_from_features(app: &AppHandle, device_id: &str, features: &DeviceFeatures, status: &DeviceStatus) {
+    let current = device_state(device_id);
+    if !matches!(current, DeviceState::Ready | DeviceState::Locked | DeviceState::NeedsUpdate) {
+        return;
+    }
+
+    let target = state_for_features(features, status);
+    // Anything other than unlock / auto-lock goes through a fresh probe
+    let steps: &[DeviceState] = if current.can_transition_to(target) {
+        &[target]
+    } else {
+        &[DeviceState::Probing, target]
+    };
+
+    for step in steps {
+        match transition(device_id, *step) {
+            Ok(Some(change)) => {
+                if let Err(e) = crate::commands::emit_or_queue_event(app, "device:state-changed", state_changed_payload(&change)).await {
+                    eprintln!("Failed to emit device:state-changed: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                println!("⚠️ {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Authoritative connection state of a device
+#[tauri::command]
+pub async fn get_device_state(device_id: String) -> Result<DeviceState, String> {
+    Ok(device_state(&device_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DeviceState::*;
+
+    fn walk(machine: &mut DeviceStateMachine, states: &[DeviceState]) {
+        for state in states {
+            let change = machine.transition("A", *state).unwrap().unwrap();
+            assert_eq!(change.to, *state);
+            assert_eq!(machine.state("A"), *state);
+        }
+    }
+
+    #[test]
+    fn test_full_lifecycle() {
+        let mut machine = DeviceStateMachine::default();
+        assert_eq!(machine.state("A"), Disconnected);
+
+        // Locked device gets unlocked, re-probed and found to need an update
+        walk(&mut machine, &[Connected, Probing, Locked, Ready, Probing, NeedsUpdate, Disconnected]);
+        assert!(machine.states.is_empty());
+
+        // Probe fails, retry recovers
+        walk(&mut machine, &[Connected, Probing, Error, Probing, Ready, Locked, Disconnected]);
+
+        // DFU-mode device errors without a probe and recovers by reconnecting
+        walk(&mut machine, &[Connected, Error, Disconnected, Connected]);
+
+        // Staying in the same state is not a change
+        assert_eq!(machine.transition("A", Connected).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_transitions_are_rejected() {
+        let mut machine = DeviceStateMachine::default();
+        assert!(machine.transition("A", Ready).is_err());
+        assert!(machine.transition("A", Probing).is_err());
+
+        machine.transition("A", Connected).unwrap();
+        // Must probe before settling
+        assert!(machine.transition("A", Ready).is_err());
+        assert_eq!(machine.state("A"), Connected);
+
+        machine.transition("A", Probing).unwrap();
+        machine.transition("A", Error).unwrap();
+        // Error only recovers through a new probe or a reconnect
+        assert!(machine.transition("A", Ready).is_err());
+        assert!(machine.transition("A", Connected).is_err());
+        assert_eq!(machine.state("A"), Error);
+
+        // Devices are tracked independently
+        assert_eq!(machine.state("B"), Disconnected);
+    }
+}
@@ -0,0 +1,110 @@
+use keepkey_rust::features::DeviceFeatures;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// What the firmware reports about on-device storage.
+///
+/// KeepKey firmware has no storage usage message; `Features.imported` (whether
+/// the seed was loaded from outside rather than generated or recovered on the
+/// device) is all it exposes. A KeepKey holds a single seed, so there are no
+/// key slots to count. Firmware that doesn't report `imported` - and any device
+/// in bootloader mode - is `Unsupported`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StorageStats {
+    #[serde(rename_all = "camelCase")]
+    Supported {
+        /// Seeds stored on the device: 0 or 1
+        seed_count: u32,
+        /// Imported (not generated on the device) seeds: 0 or 1
+        imported_key_count: u32,
+        /// Storage slots, if the firmware ever reports them
+        used_slots: Option<u32>,
+        free_slots: Option<u32>,
+    },
+    Unsupported { reason: String },
+}
+
+pub fn storage_stats_from_features(features: &DeviceFeatures) -> StorageStats {
+    if features.bootloader_mode {
+        return StorageStats::Unsupported { reason: "Device is in bootloader mode".to_string() };
+    }
+    let Some(imported) = features.imported else {
+        return StorageStats::Unsupported {
+            reason: format!("Firmware {} does not report storage information", features.version),
+        };
+    };
+
+    let seed_count = features.initialized as u32;
+    StorageStats::Supported {
+        seed_count,
+        imported_key_count: if imported { seed_count } else { 0 },
+        used_slots: None,
+        free_slots: None,
+    }
+}
+
+/// Storage stats for the settings page, from cached features when available
+#[tauri::command]
+pub async fn get_storage_stats(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<StorageStats, String> {
+    if let Some(features) = crate::commands::cached_device_features(&unique_id) {
+        return Ok(storage_stats_from_features(&features));
+    }
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    let features = queue_handle
+        .get_features()
+        .await
+        .map(crate::commands::convert_features_to_device_features)
+        .map_err(|e| format!("Failed to get features for device {}: {}", unique_id, e))?;
+    crate::commands::cache_device_features(&unique_id, &features);
+
+    Ok(storage_stats_from_features(&features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(imported: Option<bool>) -> DeviceFeatures {
+        let mut features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features {
+            initialized: Some(true),
+            major_version: Some(7),
+            minor_version: Some(10),
+            patch_version: Some(0),
+            ..Default::default()
+        });
+        features.imported = imported;
+        features
+    }
+
+    #[test]
+    fn test_storage_stats_from_features() {
+        assert_eq!(
+            storage_stats_from_features(&features(Some(true))),
+            StorageStats::Supported { seed_count: 1, imported_key_count: 1, used_slots: None, free_slots: None }
+        );
+        assert_eq!(
+            storage_stats_from_features(&features(Some(false))),
+            StorageStats::Supported { seed_count: 1, imported_key_count: 0, used_slots: None, free_slots: None }
+        );
+
+        assert!(matches!(storage_stats_from_features(&features(None)), StorageStats::Unsupported { .. }));
+        let mut bootloader = features(Some(false));
+        bootloader.bootloader_mode = true;
+        assert!(matches!(storage_stats_from_features(&bootloader), StorageStats::Unsupported { .. }));
+
+        // Tagged so the frontend can switch on `status`
+        let json = serde_json::to_value(storage_stats_from_features(&features(Some(true)))).unwrap();
+        assert_eq!(json["status"], "supported");
+        assert_eq!(json["importedKeyCount"], 1);
+    }
+}
@@ -0,0 +1,51 @@
+use keepkey_rust::features::DeviceFeatures;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Health data a device reports about its own hardware.
+///
+/// No KeepKey firmware reports any of this today: `Features` carries no sensor
+/// readings and there is no telemetry message, so every current device yields
+/// `None`. The fields are what appliance operators asked for and are filled in
+/// once firmware exposes them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Telemetry {
+    pub temperature_celsius: Option<f32>,
+    pub supply_voltage_mv: Option<u32>,
+    pub uptime_secs: Option<u64>,
+}
+
+/// Telemetry from a device's features, `None` when the firmware reports none
+pub fn telemetry_from_features(_features: &DeviceFeatures) -> Option<Telemetry> {
+    None
+}
+
+/// Telemetry of every device with cached features, for the support bundle
+pub fn all_telemetry() -> HashMap<String, Telemetry> {
+    crate::commands::all_cached_features()
+        .iter()
+        .filter_map(|(device_id, features)| telemetry_from_features(features).map(|t| (device_id.clone(), t)))
+        .collect()
+}
+
+/// Health telemetry of a device; `None` for devices and firmware without any.
+/// Never talks to the device, so it is safe to poll from a monitoring loop.
+#[tauri::command]
+pub async fn get_device_telemetry(unique_id: String) -> Result<Option<Telemetry>, String> {
+    Ok(crate::commands::cached_device_features(&unique_id)
+        .as_ref()
+        .and_then(telemetry_from_features))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unsupported_is_none_not_an_error() {
+        let features = crate::commands::convert_features_to_device_features(Default::default());
+        assert_eq!(telemetry_from_features(&features), None);
+        assert_eq!(get_device_telemetry("no-such-device".to_string()).await, Ok(None));
+    }
+}
@@ -0,0 +1,220 @@
+//! Detecting KeepKey device nodes the user can't open on Linux.
+//!
+//! Without a udev rule granting access, Linux leaves /dev/bus/usb and hidraw
+//! nodes to root, and the transports only report a generic open failure. The
+//! monitor checks the nodes of every connected KeepKey at startup and after a
+//! device keeps failing with `PERMISSION_DENIED`, and emits
+//! `device:permission-issue` with the rule to install and where to write it.
+//! A node counts as inaccessible when opening it read/write is refused, so
+//! ACLs granted by logind (`uaccess`) are honored; the mode and owner in the
+//! event are what the node actually has.
+
+use serde::Serialize;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+
+/// Where the rules go; the 51- prefix orders them before the default 73-seat rules
+pub const RULES_PATH: &str = "/etc/udev/rules.d/51-usb-keepkey.rules";
+
+/// Rules giving every user access to KeepKeys over both USB and HID
+pub const RULES: &str = "SUBSYSTEM==\"usb\", ATTR{idVendor}==\"2b24\", MODE=\"0666\", GROUP=\"plugdev\"\n\
+KERNEL==\"hidraw*\", ATTRS{idVendor}==\"2b24\", MODE=\"0666\", GROUP=\"plugdev\"\n";
+
+/// Consecutive permission failures of one device before its nodes are checked
+const PERSISTENT_DENIALS: u32 = 3;
+
+/// Shell command that installs `RULES` and applies them to connected devices
+pub fn install_command() -> String {
+    format!(
+        "echo '{}' | sudo tee {} && sudo udevadm control --reload-rules && sudo udevadm trigger",
+        RULES.trim_end(),
+        RULES_PATH
+    )
+}
+
+/// A KeepKey device node this user can't open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeAccess {
+    pub path: String,
+    /// Permission bits in octal, e.g. "0664"
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Payload of `device:permission-issue`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionIssue {
+    pub nodes: Vec<NodeAccess>,
+    pub rules_path: String,
+    pub rules: String,
+    /// Whether a file already exists at `rules_path`; if so it doesn't match
+    /// the device or wasn't applied yet
+    pub rules_installed: bool,
+    pub command: String,
+}
+
+impl PermissionIssue {
+    fn new(nodes: Vec<NodeAccess>) -> Self {
+        PermissionIssue {
+            nodes,
+            rules_path: RULES_PATH.to_string(),
+            rules: RULES.to_string(),
+            rules_installed: std::path::Path::new(RULES_PATH).exists(),
+            command: install_command(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const KEEPKEY_VID: &str = "2b24";
+
+#[cfg(target_os = "linux")]
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Device nodes of every connected KeepKey: the usbfs node of each USB device
+/// and each hidraw node, found through sysfs under `sys` and named under `dev`
+#[cfg(target_os = "linux")]
+fn keepkey_nodes(sys: &Path, dev: &Path) -> Vec<PathBuf> {
+    let mut nodes = Vec::new();
+    for entry in std::fs::read_dir(sys.join("bus/usb/devices")).into_iter().flatten().flatten() {
+        let device = entry.path();
+        if read_trimmed(&device.join("idVendor")).as_deref() != Some(KEEPKEY_VID) {
+            continue;
+        }
+        let number = |name: &str| read_trimmed(&device.join(name)).and_then(|n| n.parse::<u32>().ok());
+        if let (Some(bus), Some(address)) = (number("busnum"), number("devnum")) {
+            nodes.push(dev.join(format!("bus/usb/{:03}/{:03}", bus, address)));
+        }
+    }
+    // HID_ID=<bus>:<vendor>:<product>, with the ids zero padded to 8 digits
+    let hid_vendor = format!(":{:0>8}:", KEEPKEY_VID);
+    for entry in std::fs::read_dir(sys.join("class/hidraw")).into_iter().flatten().flatten() {
+        let uevent = std::fs::read_to_string(entry.path().join("device/uevent")).unwrap_or_default();
+        let is_keepkey = uevent
+            .lines()
+            .filter_map(|line| line.strip_prefix("HID_ID="))
+            .any(|id| id.to_lowercase().contains(&hid_vendor));
+        if is_keepkey {
+            nodes.push(dev.join(entry.file_name()));
+        }
+    }
+    nodes.sort();
+    nodes
+}
+
+/// `node` as the user sees it when opening it is refused; `None` when it opens
+/// or fails for another reason (e.g. the device went away)
+#[cfg(target_os = "linux")]
+fn denied_access(node: &Path) -> Option<NodeAccess> {
+    use std::os::unix::fs::MetadataExt;
+
+    let error = std::fs::OpenOptions::new().read(true).write(true).open(node).err()?;
+    if error.kind() != std::io::ErrorKind::PermissionDenied {
+        return None;
+    }
+    let metadata = std::fs::metadata(node).ok()?;
+    Some(NodeAccess {
+        path: node.display().to_string(),
+        mode: format!("{:04o}", metadata.mode() & 0o7777),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+    })
+}
+
+/// Inaccessible KeepKey nodes, `None` when every connected KeepKey can be
+/// opened. Always `None` outside Linux.
+pub fn check() -> Option<PermissionIssue> {
+    #[cfg(target_os = "linux")]
+    {
+        let nodes: Vec<NodeAccess> = keepkey_nodes(Path::new("/sys"), Path::new("/dev"))
+            .iter()
+            .filter_map(|node| denied_access(node))
+            .collect();
+        if !nodes.is_empty() {
+            println!("🔐 {} KeepKey device node(s) can't be opened; udev rules missing?", nodes.len());
+            return Some(PermissionIssue::new(nodes));
+        }
+    }
+    None
+}
+
+/// Consecutive `PERMISSION_DENIED` failures per device
+static DENIALS: once_cell::sync::Lazy<std::sync::Mutex<crate::device::lru::DeviceLru<u32>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(crate::device::lru::DeviceLru::new()));
+
+/// Count a permission failure of `unique_id`; checks the device nodes once the
+/// failures persist, and only once until the device is reachable again
+pub fn note_permission_denied(unique_id: &str) -> Option<PermissionIssue> {
+    let denials = {
+        let mut denials = crate::commands::lock_or_recover(&DENIALS, "permission denials");
+        let count = denials.get_or_insert_with(unique_id, || 0);
+        *count += 1;
+        *count
+    };
+    if denials == PERSISTENT_DENIALS {
+        check()
+    } else {
+        None
+    }
+}
+
+/// The device was opened (or forgotten): its failures are no longer persistent
+pub fn forget(unique_id: &str) {
+    crate::commands::lock_or_recover(&DENIALS, "permission denials").remove(unique_id);
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_finds_keepkey_nodes_in_sysfs() {
+        let root = std::env::temp_dir().join(format!("keepkey-udev-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (sys, dev) = (root.join("sys"), root.join("dev"));
+
+        let keepkey = sys.join("bus/usb/devices/1-4");
+        write(&keepkey.join("idVendor"), "2b24\n");
+        write(&keepkey.join("busnum"), "1\n");
+        write(&keepkey.join("devnum"), "12\n");
+        let other = sys.join("bus/usb/devices/1-5");
+        write(&other.join("idVendor"), "046d\n");
+        write(&other.join("busnum"), "1\n");
+        write(&other.join("devnum"), "13\n");
+        write(&sys.join("class/hidraw/hidraw3/device/uevent"), "DRIVER=hid-generic\nHID_ID=0003:00002B24:00000002\n");
+        write(&sys.join("class/hidraw/hidraw4/device/uevent"), "HID_ID=0003:0000046D:0000C52B\n");
+
+        assert_eq!(keepkey_nodes(&sys, &dev), vec![dev.join("bus/usb/001/012"), dev.join("hidraw3")]);
+
+        // A node its owner can read and write isn't reported; neither is one
+        // that's gone
+        let node = dev.join("bus/usb/001/012");
+        write(&node, "");
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&node, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(denied_access(&node), None);
+        assert_eq!(denied_access(&dev.join("hidraw3")), None);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_permission_issue_payload() {
+        let issue = PermissionIssue::new(vec![NodeAccess { path: "/dev/hidraw3".to_string(), mode: "0600".to_string(), uid: 0, gid: 0 }]);
+        let json = serde_json::to_value(&issue).unwrap();
+        assert_eq!(json["rulesPath"], RULES_PATH);
+        assert_eq!(json["nodes"][0]["mode"], "0600");
+        assert!(issue.command.contains(RULES_PATH) && issue.command.contains("udevadm trigger"));
+        assert!(issue.rules.contains("ATTRS{idVendor}==\"2b24\""));
+    }
+}
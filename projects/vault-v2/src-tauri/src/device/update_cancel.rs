@@ -0,0 +1,180 @@
+//! Cancelling a firmware update while that is still safe.
+//!
+//! Nothing on the device changes while `update_device_firmware` prepares: it
+//! loads the image, finds the device and checks it is in bootloader mode. The
+//! bootloader erases the firmware once it receives the upload, and from then
+//! on only a completed upload leaves a bootable device. Each update registers
+//! here, checks its cancellation token between the preparation steps and
+//! commits to flashing right before the upload; `cancel_firmware_update` is
+//! refused with `cancel_too_late` after that.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio_util::sync::CancellationToken;
+
+/// Phase of a running firmware update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdatePhase {
+    /// Loading the image and checking the device; safe to cancel
+    Preparing,
+    /// Erasing and uploading: past the point of no return
+    Flashing,
+}
+
+impl UpdatePhase {
+    pub fn cancellable(self) -> bool {
+        self == UpdatePhase::Preparing
+    }
+}
+
+/// Why `cancel_firmware_update` was refused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CancelUpdateError {
+    /// No firmware update is running on the device
+    NotUpdating,
+    /// The erase has begun; stopping now would leave the device without firmware
+    CancelTooLate,
+}
+
+impl std::fmt::Display for CancelUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CancelUpdateError::NotUpdating => write!(f, "No firmware update is running on this device"),
+            CancelUpdateError::CancelTooLate => {
+                write!(f, "The firmware is already being flashed; cancelling now would leave the device without firmware")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CancelUpdateError {}
+
+struct RunningUpdate {
+    /// Tells a guard from a later update of the same device
+    id: u64,
+    phase: UpdatePhase,
+    token: CancellationToken,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+static UPDATES: once_cell::sync::Lazy<std::sync::Mutex<HashMap<String, RunningUpdate>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+fn lock_updates() -> std::sync::MutexGuard<'static, HashMap<String, RunningUpdate>> {
+    crate::commands::lock_or_recover(&UPDATES, "firmware updates")
+}
+
+/// A registered update; dropping it unregisters the update however it ended
+pub struct UpdateGuard {
+    device_id: String,
+    id: u64,
+    token: CancellationToken,
+}
+
+/// Register an update of `device_id` in the preparing phase
+pub fn begin(device_id: &str) -> Result<UpdateGuard, String> {
+    let mut updates = lock_updates();
+    if updates.contains_key(device_id) {
+        return Err(format!("A firmware update of device {} is already running", device_id));
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let token = CancellationToken::new();
+    updates.insert(device_id.to_string(), RunningUpdate { id, phase: UpdatePhase::Preparing, token: token.clone() });
+    Ok(UpdateGuard { device_id: device_id.to_string(), id, token })
+}
+
+impl UpdateGuard {
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves when the update is cancelled, to race against waits
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Enter the flashing phase; false if a cancel got in first. Holds the
+    /// same lock as `cancel`, so a cancel either lands before this or is refused.
+    pub fn commit(&self) -> bool {
+        let mut updates = lock_updates();
+        if self.token.is_cancelled() {
+            return false;
+        }
+        if let Some(update) = updates.get_mut(&self.device_id).filter(|update| update.id == self.id) {
+            update.phase = UpdatePhase::Flashing;
+        }
+        true
+    }
+}
+
+impl Drop for UpdateGuard {
+    fn drop(&mut self) {
+        let mut updates = lock_updates();
+        if updates.get(&self.device_id).map(|update| update.id) == Some(self.id) {
+            updates.remove(&self.device_id);
+        }
+    }
+}
+
+/// Phase of the update running on `device_id`, if any
+pub fn phase(device_id: &str) -> Option<UpdatePhase> {
+    lock_updates().get(device_id).map(|update| update.phase)
+}
+
+/// Cancel the update of `device_id` if it hasn't started flashing
+pub fn cancel(device_id: &str) -> Result<(), CancelUpdateError> {
+    let updates = lock_updates();
+    let update = updates.get(device_id).ok_or(CancelUpdateError::NotUpdating)?;
+    if !update.phase.cancellable() {
+        return Err(CancelUpdateError::CancelTooLate);
+    }
+    update.token.cancel();
+    Ok(())
+}
+
+/// Cancel the firmware update running on `unique_id`. Only honored while the
+/// update prepares (progress phase `preparing`); the update then returns an
+/// error and emits `firmware:cancelled`, with the device untouched.
+#[tauri::command]
+pub async fn cancel_firmware_update(unique_id: String) -> Result<(), CancelUpdateError> {
+    cancel(&unique_id)?;
+    println!("🛑 Cancelling the firmware update of {}", unique_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_in_safe_window_leaves_no_update() {
+        let update = begin("cancel-test").unwrap();
+        assert_eq!(phase("cancel-test"), Some(UpdatePhase::Preparing));
+        assert!(begin("cancel-test").is_err());
+
+        cancel("cancel-test").unwrap();
+        assert!(update.is_cancelled());
+        // The update can no longer reach the erase
+        assert!(!update.commit());
+        drop(update);
+
+        // Nothing is left behind: no running update, and a new one can start
+        assert_eq!(phase("cancel-test"), None);
+        assert_eq!(cancel("cancel-test"), Err(CancelUpdateError::NotUpdating));
+        let again = begin("cancel-test").unwrap();
+        assert!(!again.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_after_commit_is_too_late() {
+        let update = begin("cancel-late-test").unwrap();
+        assert!(update.commit());
+        assert_eq!(phase("cancel-late-test"), Some(UpdatePhase::Flashing));
+        assert_eq!(cancel("cancel-late-test"), Err(CancelUpdateError::CancelTooLate));
+        assert!(!update.is_cancelled());
+    }
+}
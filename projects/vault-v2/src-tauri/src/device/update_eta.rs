@@ -0,0 +1,205 @@
+//! How long a firmware flash will take, for an "About 45 seconds" before the
+//! user starts it.
+//!
+//! The estimate is the model's flash erase time plus the image size over the
+//! best throughput known for the device: the rate of its last completed flash,
+//! else its last `benchmark_device_io`, else a conservative rate for its
+//! hardware revision. The upload is a single message without byte progress,
+//! so during the flash the estimate is refined from the elapsed time: a flash
+//! running past its estimate is assumed to be a quarter of the way from done.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::device::lru::DeviceLru;
+
+/// Erasing the application sectors before the upload, by hardware revision
+fn erase_time(revision: Option<&str>) -> Duration {
+    match revision {
+        Some("legacy") => Duration::from_secs(10),
+        Some("webusb") => Duration::from_secs(7),
+        _ => Duration::from_secs(12),
+    }
+}
+
+/// Upload rate assumed without measurements, in bytes per second. HID-only
+/// hardware moves 64 byte reports one at a time.
+fn default_throughput(revision: Option<&str>) -> f64 {
+    match revision {
+        Some("legacy") => 8_000.0,
+        Some("webusb") => 20_000.0,
+        _ => 6_000.0,
+    }
+}
+
+/// Signature check and reboot after the upload
+const FINISH_TIME: Duration = Duration::from_secs(5);
+
+/// Where the throughput of an estimate came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateBasis {
+    /// The device's last completed flash
+    PreviousFlash,
+    /// Its last `benchmark_device_io`
+    Benchmark,
+    /// Nothing measured: the conservative rate for the hardware revision
+    ModelDefault,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateEstimate {
+    pub estimated_secs: u64,
+    pub erase_secs: u64,
+    pub throughput_bytes_per_sec: f64,
+    pub basis: EstimateBasis,
+}
+
+impl UpdateEstimate {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.estimated_secs)
+    }
+}
+
+/// Estimate flashing `image_size` bytes. `measured` is a known throughput and
+/// where it came from; rates too low to be plausible are ignored.
+pub fn estimate(image_size: usize, revision: Option<&str>, measured: Option<(f64, EstimateBasis)>) -> UpdateEstimate {
+    let (throughput, basis) = measured
+        .filter(|(rate, _)| rate.is_finite() && *rate >= 100.0)
+        .unwrap_or((default_throughput(revision), EstimateBasis::ModelDefault));
+    let erase = erase_time(revision);
+    let transfer = Duration::from_secs_f64(image_size as f64 / throughput);
+    UpdateEstimate {
+        estimated_secs: (erase + transfer + FINISH_TIME).as_secs_f64().ceil() as u64,
+        erase_secs: erase.as_secs(),
+        throughput_bytes_per_sec: throughput,
+        basis,
+    }
+}
+
+/// Remaining time of a flash started `elapsed` ago with `estimated` total
+pub fn remaining(estimated: Duration, elapsed: Duration) -> Duration {
+    if elapsed < estimated {
+        estimated - elapsed
+    } else {
+        // Running long: assume three quarters done rather than counting down from zero
+        elapsed / 3
+    }
+}
+
+/// Upload throughput of each device's last completed flash
+static FLASH_RATES: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<f64>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+/// Record a completed flash of `image_size` bytes that took `took` in all
+pub fn record_flash(device_id: &str, revision: Option<&str>, image_size: usize, took: Duration) {
+    let transfer = took.saturating_sub(erase_time(revision) + FINISH_TIME).max(Duration::from_secs(1));
+    let rate = image_size as f64 / transfer.as_secs_f64();
+    crate::commands::lock_or_recover(&FLASH_RATES, "flash rates").insert(device_id, rate);
+}
+
+pub fn forget(device_id: &str) {
+    crate::commands::lock_or_recover(&FLASH_RATES, "flash rates").remove(device_id);
+}
+
+/// The best throughput known for `device_id`
+fn measured_throughput(device_id: &str) -> Option<(f64, EstimateBasis)> {
+    if let Some(rate) = crate::commands::lock_or_recover(&FLASH_RATES, "flash rates").peek(device_id) {
+        return Some((*rate, EstimateBasis::PreviousFlash));
+    }
+    crate::device::benchmark::last_benchmarks()
+        .get(device_id)
+        .map(|benchmark| (benchmark.throughput_bytes_per_sec, EstimateBasis::Benchmark))
+}
+
+/// Hardware revision of a device from its cached features, as in `get_device_model`
+pub fn device_revision(device_id: &str) -> Option<String> {
+    let features = crate::commands::cached_device_features(device_id)?;
+    crate::device::model::installed_bootloader_version(&features)
+        .map(|version| crate::device::model::revision_from_bootloader_version(&version).to_string())
+}
+
+pub fn estimate_for_device(device_id: &str, image_size: usize) -> UpdateEstimate {
+    estimate(image_size, device_revision(device_id).as_deref(), measured_throughput(device_id))
+}
+
+/// Ticks `firmware:update-eta` while an upload runs
+pub struct FlashClock {
+    pub estimate: UpdateEstimate,
+    started: Instant,
+}
+
+impl FlashClock {
+    pub fn start(estimate: UpdateEstimate) -> Self {
+        Self { estimate, started: Instant::now() }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Payload of `firmware:update-eta`
+    pub fn eta_payload(&self, device_id: &str) -> serde_json::Value {
+        let elapsed = self.elapsed();
+        let remaining = remaining(self.estimate.duration(), elapsed);
+        serde_json::json!({
+            "deviceId": device_id,
+            "elapsedSecs": elapsed.as_secs(),
+            "remainingSecs": remaining.as_secs(),
+            "estimatedSecs": (elapsed + remaining).as_secs()
+        })
+    }
+}
+
+/// Estimated time to flash an image of `image_size` bytes onto `unique_id`
+#[tauri::command]
+pub async fn estimate_update_duration(unique_id: String, image_size: usize) -> Result<UpdateEstimate, String> {
+    if image_size == 0 {
+        return Err("Image size must be greater than zero".to_string());
+    }
+    Ok(estimate_for_device(&unique_id, image_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: usize = 520_000;
+
+    #[test]
+    fn test_estimates() {
+        // Nothing measured: conservative per-revision rates
+        let webusb = estimate(IMAGE, Some("webusb"), None);
+        assert_eq!(webusb.basis, EstimateBasis::ModelDefault);
+        assert_eq!(webusb.estimated_secs, 7 + 26 + 5);
+        let legacy = estimate(IMAGE, Some("legacy"), None);
+        let unknown = estimate(IMAGE, None, None);
+        assert!(unknown.estimated_secs > legacy.estimated_secs && legacy.estimated_secs > webusb.estimated_secs);
+
+        let measured = estimate(IMAGE, Some("webusb"), Some((52_000.0, EstimateBasis::Benchmark)));
+        assert_eq!(measured.basis, EstimateBasis::Benchmark);
+        assert_eq!(measured.estimated_secs, 7 + 10 + 5);
+
+        // Implausible measurements fall back to the default
+        assert_eq!(estimate(IMAGE, Some("webusb"), Some((0.0, EstimateBasis::Benchmark))), webusb);
+    }
+
+    #[test]
+    fn test_previous_flash_beats_model_default() {
+        record_flash("eta-test", Some("webusb"), IMAGE, Duration::from_secs(7 + 13 + 5));
+        let estimate = estimate(IMAGE, Some("webusb"), measured_throughput("eta-test"));
+        assert_eq!(estimate.basis, EstimateBasis::PreviousFlash);
+        assert_eq!(estimate.throughput_bytes_per_sec, 40_000.0);
+        assert_eq!(estimate.estimated_secs, 25);
+    }
+
+    #[test]
+    fn test_remaining_is_refined_when_running_long() {
+        let estimated = Duration::from_secs(40);
+        assert_eq!(remaining(estimated, Duration::from_secs(10)), Duration::from_secs(30));
+        assert_eq!(remaining(estimated, Duration::from_secs(39)), Duration::from_secs(1));
+        // Past the estimate it never claims to be done
+        assert_eq!(remaining(estimated, Duration::from_secs(60)), Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,167 @@
+//! Firmware and bootloader updates performed on each device.
+//!
+//! Every update that reaches the upload is recorded when it finishes, whether
+//! it succeeded or not, with the version it replaced and the one it installed.
+//! Records are keyed by the device's stable id (see `identity`), so the USB id
+//! changing across the bootloader reboot doesn't split a device's history, and
+//! live in ~/.keepkey/update_history/<device id>.jsonl, oldest first.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What an update flashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateKind {
+    Firmware,
+    Bootloader,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateOutcome {
+    Success,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRecord {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub kind: UpdateKind,
+    /// Installed version before the update; `None` when the device didn't report it
+    pub from_version: Option<String>,
+    pub to_version: String,
+    pub outcome: UpdateOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl UpdateRecord {
+    /// Record of an update that just finished with `result`
+    pub fn finished(kind: UpdateKind, from_version: Option<String>, to_version: &str, result: Result<(), &str>) -> Self {
+        UpdateRecord {
+            timestamp: chrono::Utc::now(),
+            kind,
+            from_version,
+            to_version: to_version.to_string(),
+            outcome: if result.is_ok() { UpdateOutcome::Success } else { UpdateOutcome::Failed },
+            error: result.err().map(str::to_string),
+        }
+    }
+}
+
+fn history_dir() -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
+    Ok(home_dir.join(".keepkey").join("update_history"))
+}
+
+/// History file of `device` in `dir`; ids are reduced to file-name safe characters
+fn history_path(dir: &Path, device: &str) -> PathBuf {
+    let name: String = device
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    dir.join(format!("{}.jsonl", name))
+}
+
+fn append_to(dir: &Path, device: &str, record: &UpdateRecord) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create update history directory: {}", e))?;
+    let line = serde_json::to_string(record).map_err(|e| format!("Failed to serialize update record: {}", e))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path(dir, device))
+        .map_err(|e| format!("Failed to open update history: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write update history: {}", e))
+}
+
+/// Records of `device` in `dir`, oldest first. Lines that don't parse (e.g. a
+/// write cut short by a crash) are skipped rather than hiding the rest.
+fn load_from(dir: &Path, device: &str) -> Result<Vec<UpdateRecord>, String> {
+    let path = history_path(dir, device);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let jsonl = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read update history: {}", e))?;
+    Ok(jsonl.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Record the update of `unique_id` that just finished. Failing to write the
+/// history never fails the update itself.
+pub fn record(unique_id: &str, kind: UpdateKind, from_version: Option<String>, to_version: &str, result: Result<(), &str>) {
+    let record = UpdateRecord::finished(kind, from_version, to_version, result);
+    let device = crate::device::identity::stable_id(unique_id);
+    if let Err(e) = history_dir().and_then(|dir| append_to(&dir, &device, &record)) {
+        eprintln!("Failed to record update history for {}: {}", unique_id, e);
+    }
+}
+
+/// Firmware and bootloader updates performed on this device, oldest first
+#[tauri::command]
+pub async fn get_update_history(unique_id: String) -> Result<Vec<UpdateRecord>, String> {
+    load_from(&history_dir()?, &crate::device::identity::stable_id(&unique_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{spawn_scripted_worker, MockDevice, MockScenario};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keepkey-update-history-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn outcome(result: &anyhow::Result<bool>) -> Result<(), &str> {
+        result.as_ref().map(|_| ()).map_err(|_| "upload failed")
+    }
+
+    #[tokio::test]
+    async fn test_simulated_update_is_recorded() {
+        let dir = test_dir("simulated");
+        let handle = spawn_scripted_worker("update-history-test", MockDevice::new(MockScenario::NeedsFirmware));
+
+        // In bootloader mode the features report the bootloader version
+        let features = handle.get_features().await.unwrap();
+        let installed = format!("{}.{}.{}", features.major_version.unwrap(), features.minor_version.unwrap(), features.patch_version.unwrap());
+        let result = handle.update_bootloader("2.1.5".to_string(), vec![0; 16]).await;
+        let record = UpdateRecord::finished(UpdateKind::Bootloader, Some(installed), "2.1.5", outcome(&result));
+        append_to(&dir, handle.device_id(), &record).unwrap();
+
+        let result = handle.update_firmware("7.10.0".to_string(), vec![0; 16]).await;
+        let record = UpdateRecord::finished(UpdateKind::Firmware, None, "7.10.0", outcome(&result));
+        append_to(&dir, handle.device_id(), &record).unwrap();
+
+        let history = load_from(&dir, handle.device_id()).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].kind, UpdateKind::Bootloader);
+        assert_eq!(history[0].from_version.as_deref(), Some("2.1.4"));
+        assert_eq!(history[0].to_version, "2.1.5");
+        assert_eq!(history[0].outcome, UpdateOutcome::Success);
+        assert_eq!(history[1].kind, UpdateKind::Firmware);
+        assert_eq!(history[1].to_version, "7.10.0");
+        assert_eq!(history[1].error, None);
+
+        // Other devices have their own history
+        assert!(load_from(&dir, "another-device").unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_update_and_damaged_lines() {
+        let dir = test_dir("failed");
+        let failed = UpdateRecord::finished(UpdateKind::Firmware, Some("7.9.0".to_string()), "7.10.0", Err("signature check failed"));
+        append_to(&dir, "bus1/addr4", &failed).unwrap();
+        // A record cut short doesn't hide the ones before it
+        std::fs::OpenOptions::new().append(true).open(history_path(&dir, "bus1/addr4")).unwrap().write_all(b"{\"timestamp\":").unwrap();
+
+        let history = load_from(&dir, "bus1/addr4").unwrap();
+        assert_eq!(history, vec![failed]);
+        assert_eq!(history[0].outcome, UpdateOutcome::Failed);
+        assert_eq!(history[0].error.as_deref(), Some("signature check failed"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,4 +1,4 @@
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
 use std::fs;
 use std::path::PathBuf;
 use semver::Version;
@@ -7,11 +7,88 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 use crate::logging::{log_device_request, log_device_response};
-use crate::commands::DeviceQueueManager;
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+use crate::device::update_history::UpdateKind;
+use keepkey_rust::firmware_upload::{classify_upload_error, UploadFailure};
 
 // Track devices that just completed bootloader updates
 pub type BootloaderUpdateTracker = Arc<RwLock<HashMap<String, std::time::Instant>>>;
 
+/// Firmware version each device last reported outside bootloader mode. Unlike
+/// the feature cache it survives the disconnect of the reboot into the
+/// bootloader, which is when a firmware update needs it.
+static INSTALLED_FIRMWARE: once_cell::sync::Lazy<std::sync::Mutex<crate::device::lru::DeviceLru<String>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(crate::device::lru::DeviceLru::new()));
+
+/// Record the installed firmware version from features fetched in firmware mode
+pub fn remember_installed_firmware(device_id: &str, features: &keepkey_rust::features::DeviceFeatures) {
+    if !features.bootloader_mode {
+        crate::commands::lock_or_recover(&INSTALLED_FIRMWARE, "installed firmware").insert(device_id, features.version.clone());
+    }
+}
+
+/// Firmware installed on a device sitting in the bootloader: from the firmware
+/// hash the bootloader reports when it's a known release, else the version
+/// recorded before the reboot
+fn installed_firmware_version(device_id: &str, bootloader_features: Option<&keepkey_rust::messages::Features>) -> Option<String> {
+    bootloader_features
+        .and_then(|features| features.firmware_hash.as_deref())
+        .and_then(|hash| crate::device::firmware_file::firmware_version_from_hash(&hex::encode(hash)))
+        .map(str::to_string)
+        .or_else(|| crate::commands::lock_or_recover(&INSTALLED_FIRMWARE, "installed firmware").get(device_id).cloned())
+}
+
+/// Outcome of the downgrade check before a firmware update
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DowngradeCheck {
+    /// Not older than the installed firmware
+    Proceed,
+    /// A downgrade, or an unknown installed version, that `allow_downgrade` permits
+    Allowed(Option<crate::device::model::FirmwareDowngrade>),
+    /// Refused; carries the downgrade when the installed version is known
+    Refused { error: String, downgrade: Option<crate::device::model::FirmwareDowngrade> },
+}
+
+/// Decide whether flashing `target` may go ahead. Fails closed: when the
+/// installed firmware can't be determined a downgrade can't be ruled out, so
+/// the update is refused unless `allow_downgrade` is set.
+fn check_downgrade(
+    current: Option<&str>,
+    target: &str,
+    bootloader_version: Option<&str>,
+    allow_downgrade: bool,
+) -> DowngradeCheck {
+    let Some(current) = current else {
+        if allow_downgrade {
+            return DowngradeCheck::Allowed(None);
+        }
+        return DowngradeCheck::Refused {
+            error: format!(
+                "The installed firmware version is unknown, so flashing {} could be a downgrade. Retry with allow_downgrade to proceed.",
+                target
+            ),
+            downgrade: None,
+        };
+    };
+    let Some(downgrade) = crate::device::model::check_firmware_downgrade(current, target, bootloader_version) else {
+        return DowngradeCheck::Proceed;
+    };
+    if let Some(reason) = &downgrade.blocked_reason {
+        let error = format!("Cannot downgrade firmware from {} to {}: {}", downgrade.current, downgrade.target, reason);
+        return DowngradeCheck::Refused { error, downgrade: Some(downgrade) };
+    }
+    if allow_downgrade {
+        return DowngradeCheck::Allowed(Some(downgrade));
+    }
+    let error = format!(
+        "Firmware {} is older than the installed {}{}. Retry with allow_downgrade to proceed.",
+        downgrade.target,
+        downgrade.current,
+        if downgrade.will_wipe { " and downgrading will wipe the device" } else { "" }
+    );
+    DowngradeCheck::Refused { error, downgrade: Some(downgrade) }
+}
+
 /// Update device bootloader using the device queue
 #[tauri::command]
 pub async fn update_device_bootloader(
@@ -164,45 +241,28 @@ pub async fn update_device_bootloader(
     println!("📦 Loaded bootloader binary: {} bytes", bootloader_bytes.len());
     
     // Get or create device queue handle
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Find the device by ID
-            let devices = keepkey_rust::features::list_connected_devices();
-            let device_info = devices
-                .iter()
-                .find(|d| d.unique_id == device_id);
-                
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    let error = format!("Device {} not found", device_id);
-                    
-                    // Log the error response
-                    let response_data = serde_json::json!({
-                        "error": error,
-                        "operation": "update_device_bootloader"
-                    });
-                    
-                    if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                        eprintln!("Failed to log bootloader update error response: {}", e);
-                    }
-                    
-                    return Err(error);
-                }
+    let queue_handle = match queue_manager.get_or_spawn_by_id(&device_id).await {
+        Some(handle) => handle,
+        None => {
+            let error = format!("Device {} not found", device_id);
+            
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "update_device_bootloader"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log bootloader update error response: {}", e);
             }
+            
+            return Err(error);
         }
     };
     
-    // Check device features to ensure it's in bootloader mode
+    // Check device features to ensure it's in bootloader mode. In bootloader mode
+    // the reported version is the installed bootloader's.
+    let mut current_bootloader = None;
     match queue_handle.get_features().await {
         Ok(features) => {
             if !features.bootloader_mode.unwrap_or(false) {
@@ -220,12 +280,14 @@ pub async fn update_device_bootloader(
                 
                 return Err(error);
             }
-            println!("✅ Device confirmed in bootloader mode, firmware version: {}", format!(
+            let version = format!(
                 "{}.{}.{}",
                 features.major_version.unwrap_or(0),
                 features.minor_version.unwrap_or(0),
                 features.patch_version.unwrap_or(0)
-            ));
+            );
+            println!("✅ Device confirmed in bootloader mode, firmware version: {}", version);
+            current_bootloader = Some(version);
         }
         Err(e) => {
             let error_str = e.to_string();
@@ -265,6 +327,7 @@ pub async fn update_device_bootloader(
     match queue_handle.update_bootloader(target_version.clone(), bootloader_bytes).await {
         Ok(success) => {
             println!("✅ Bootloader update successful for device {}", device_id);
+            crate::device::update_history::record(&device_id, UpdateKind::Bootloader, current_bootloader, &target_version, Ok(()));
             println!("⚠️  Note: The device will now reboot. It will disconnect and reconnect automatically.");
             println!("    The frontend should wait for the device:connected event before proceeding.");
             
@@ -299,6 +362,7 @@ pub async fn update_device_bootloader(
         Err(e) => {
             let error_msg = e.to_string();
             println!("❌ Bootloader update failed for device {}: {}", device_id, error_msg);
+            crate::device::update_history::record(&device_id, UpdateKind::Bootloader, current_bootloader, &target_version, Err(&error_msg));
             
             // Log the error response
             let response_data = serde_json::json!({
@@ -320,16 +384,20 @@ pub async fn update_device_bootloader(
 pub async fn update_device_firmware(
     device_id: String,
     target_version: String,
+    allow_downgrade: Option<bool>,
     queue_manager: State<'_, DeviceQueueManager>,
+    app: AppHandle,
 ) -> Result<bool, String> {
     println!("🔄 Starting firmware update for device {}: target version {}", device_id, target_version);
     
     let request_id = uuid::Uuid::new_v4().to_string();
+    let allow_downgrade = allow_downgrade.unwrap_or(false);
     
     // Log the request
     let request_data = serde_json::json!({
         "device_id": device_id,
         "target_version": target_version,
+        "allow_downgrade": allow_downgrade,
         "operation": "update_device_firmware"
     });
     
@@ -341,6 +409,10 @@ pub async fn update_device_firmware(
     let _target_semver = Version::parse(&target_version)
         .map_err(|e| format!("Invalid target firmware version: {}", e))?;
     
+    // Cancellable until the upload starts (see update_cancel)
+    let update = crate::device::update_cancel::begin(&device_id)?;
+    emit_firmware_progress(&app, &device_id, 1, "preparing", 0, 0);
+    
     // Load the firmware binary from the firmware directory (bundled with app)
     let firmware_filename = format!("v{}", target_version);
     
@@ -464,77 +536,54 @@ pub async fn update_device_firmware(
     };
     
     println!("📦 Loaded firmware binary: {} bytes", firmware_bytes.len());
+    if update.is_cancelled() {
+        return Err(finish_cancelled(&app, &device_id, &request_id).await);
+    }
     
     // Get or create device queue handle with retry logic for reconnecting devices
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Device might be reconnecting after bootloader update, try with retries
-            let mut device_info = None;
-            let max_retries = 5;
-            
-            for retry in 0..max_retries {
-                if retry > 0 {
-                    // Wait before retry to allow device to reconnect
-                    println!("⏳ Waiting for device {} to reconnect (attempt {}/{})", device_id, retry + 1, max_retries);
-                    drop(manager); // Release lock during sleep
-                    tokio::time::sleep(tokio::time::Duration::from_millis(1000 * retry as u64)).await;
-                    manager = queue_manager.lock().await;
-                    
-                    // Check again if handle was created by event controller
-                    if let Some(handle) = manager.get(&device_id) {
-                        println!("✅ Device queue handle found after waiting");
-                        break;
-                    }
-                }
-                
-                let devices = keepkey_rust::features::list_connected_devices();
-                device_info = devices
-                    .iter()
-                    .find(|d| d.unique_id == device_id)
-                    .cloned();
-                    
-                if device_info.is_some() {
-                    println!("✅ Device {} found on attempt {}", device_id, retry + 1);
-                    break;
-                }
+    let max_retries = 5;
+    let mut queue_handle = None;
+    
+    for retry in 0..max_retries {
+        if retry > 0 {
+            // Wait before retry to allow device to reconnect (the event controller may
+            // also register a handle for it in the meantime)
+            println!("⏳ Waiting for device {} to reconnect (attempt {}/{})", device_id, retry + 1, max_retries);
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(1000 * retry as u64)) => {}
+                _ = update.cancelled() => return Err(finish_cancelled(&app, &device_id, &request_id).await),
             }
+        }
+        
+        if let Some(handle) = queue_manager.get_or_spawn_by_id(&device_id).await {
+            println!("✅ Device {} found on attempt {}", device_id, retry + 1);
+            queue_handle = Some(handle);
+            break;
+        }
+    }
+    
+    let queue_handle = match queue_handle {
+        Some(handle) => handle,
+        None => {
+            let error = format!("Device {} not found after {} retries", device_id, max_retries);
             
-            match device_info {
-                Some(device_info) => {
-                    // Spawn a new device worker
-                    let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(device_id.clone(), device_info.clone());
-                    manager.insert(device_id.clone(), handle.clone());
-                    handle
-                }
-                None => {
-                    // Check if handle was created while we were waiting
-                    if let Some(handle) = manager.get(&device_id) {
-                        handle.clone()
-                    } else {
-                        let error = format!("Device {} not found after {} retries", device_id, max_retries);
-                        
-                        // Log the error response
-                        let response_data = serde_json::json!({
-                            "error": error,
-                            "operation": "update_device_firmware"
-                        });
-                        
-                        if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
-                            eprintln!("Failed to log firmware update error response: {}", e);
-                        }
-                        
-                        return Err(error);
-                    }
-                }
+            // Log the error response
+            let response_data = serde_json::json!({
+                "error": error,
+                "operation": "update_device_firmware"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log firmware update error response: {}", e);
             }
+            
+            return Err(error);
         }
     };
     
     // Check device features to ensure it's in bootloader mode (required for firmware updates)
+    let mut bootloader_version = None;
+    let mut bootloader_features = None;
     match queue_handle.get_features().await {
         Ok(features) => {
             if !features.bootloader_mode.unwrap_or(false) {
@@ -558,6 +607,14 @@ pub async fn update_device_firmware(
                 features.minor_version.unwrap_or(0),
                 features.patch_version.unwrap_or(0)
             ));
+            // Bootloader mode reports the bootloader's own version in the version fields
+            bootloader_version = Some(format!(
+                "{}.{}.{}",
+                features.major_version.unwrap_or(0),
+                features.minor_version.unwrap_or(0),
+                features.patch_version.unwrap_or(0)
+            ));
+            bootloader_features = Some(features);
         }
         Err(e) => {
             let error_str = e.to_string();
@@ -588,21 +645,133 @@ pub async fn update_device_firmware(
         }
     }
     
+    // In bootloader mode the device reports the bootloader's version; the installed
+    // firmware comes from its firmware hash or the version recorded before the reboot
+    let current_firmware = installed_firmware_version(&device_id, bootloader_features.as_ref());
+    let check = check_downgrade(current_firmware.as_deref(), &target_version, bootloader_version.as_deref(), allow_downgrade);
+    let downgrade = match &check {
+        DowngradeCheck::Allowed(downgrade) | DowngradeCheck::Refused { downgrade, .. } => downgrade.as_ref(),
+        DowngradeCheck::Proceed => None,
+    };
+    if let Some(downgrade) = downgrade {
+        println!("⚠️ Firmware downgrade requested for {}: {} -> {} (will wipe: {})", device_id, downgrade.current, downgrade.target, downgrade.will_wipe);
+        let _ = app.emit("firmware:downgrade-warning", serde_json::json!({
+            "deviceId": device_id,
+            "current": downgrade.current,
+            "target": downgrade.target,
+            "will_wipe": downgrade.will_wipe,
+            "blocked_reason": downgrade.blocked_reason
+        }));
+    }
+    match check {
+        DowngradeCheck::Proceed => {}
+        DowngradeCheck::Allowed(None) => {
+            println!("⚠️ Installed firmware version unknown for {}; proceeding because allow_downgrade is set", device_id);
+        }
+        DowngradeCheck::Allowed(Some(_)) => {}
+        DowngradeCheck::Refused { error, downgrade } => {
+            println!("❌ Firmware update refused for {}: {}", device_id, error);
+            let response_data = serde_json::json!({
+                "error": error,
+                "will_wipe": downgrade.as_ref().map(|d| d.will_wipe),
+                "operation": "update_device_firmware"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error)).await {
+                eprintln!("Failed to log firmware update error response: {}", e);
+            }
+            
+            return Err(error);
+        }
+    }
+    
+    // Past this point the bootloader erases the firmware, so the update can't be cancelled
+    if !update.commit() {
+        return Err(finish_cancelled(&app, &device_id, &request_id).await);
+    }
+    
     println!("⚠️  IMPORTANT: Check your KeepKey device screen!");
     println!("    You may need to press the button to confirm the firmware update.");
     println!("    If you see 'Upload' on the device screen, press and hold the button.");
     
-    // Perform the firmware update through the queue  
-    match queue_handle.update_firmware(target_version.clone(), firmware_bytes).await {
+    // Perform the firmware update through the queue. Transport failures are retried
+    // once the device is back in bootloader mode; bootloader rejections never are.
+    let total_bytes = firmware_bytes.len();
+    let estimate = crate::device::update_eta::estimate_for_device(&device_id, total_bytes);
+    println!("⏱️ Estimated firmware update time for {}: {}s ({:?})", device_id, estimate.estimated_secs, estimate.basis);
+    let _ = app.emit("firmware:update-estimate", serde_json::json!({
+        "deviceId": device_id,
+        "targetVersion": target_version,
+        "estimate": estimate
+    }));
+    let clock = Arc::new(crate::device::update_eta::FlashClock::start(estimate));
+    let eta_ticker = {
+        let (app, device_id, clock) = (app.clone(), device_id.clone(), clock.clone());
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(ETA_INTERVAL).await;
+                let _ = app.emit("firmware:update-eta", clock.eta_payload(&device_id));
+            }
+        })
+    };
+    
+    let mut queue_handle = queue_handle;
+    let mut attempt = 1;
+    let result = loop {
+        // No KeepKey bootloader accepts an upload offset, so every attempt re-erases
+        // and restarts from zero (see keepkey_rust::firmware_upload)
+        let resumed_from = 0;
+        emit_firmware_progress(&app, &device_id, attempt, "uploading", resumed_from, total_bytes);
+        
+        match queue_handle.update_firmware(target_version.clone(), firmware_bytes.clone()).await {
+            Ok(success) => break Ok(success),
+            Err(e) => {
+                let error_msg = e.to_string();
+                match classify_upload_error(&error_msg) {
+                    UploadFailure::Fatal => {
+                        println!("❌ Firmware upload rejected, not retrying: {}", error_msg);
+                        break Err(error_msg);
+                    }
+                    UploadFailure::Transient if attempt >= MAX_UPLOAD_ATTEMPTS => {
+                        break Err(format!("{} (gave up after {} attempts)", error_msg, attempt));
+                    }
+                    UploadFailure::Transient => {
+                        println!("⚠️ Transient error during firmware upload (attempt {}/{}): {}", attempt, MAX_UPLOAD_ATTEMPTS, error_msg);
+                        emit_firmware_progress(&app, &device_id, attempt, "reconnecting", 0, total_bytes);
+                        
+                        // Drop the broken worker and wait for the device to come back
+                        queue_manager.remove_and_shutdown(&device_id).await;
+                        match wait_for_bootloader(&device_id, &queue_manager).await {
+                            Some(handle) => queue_handle = handle,
+                            None => break Err(format!("{} (device did not return to bootloader mode)", error_msg)),
+                        }
+                        attempt += 1;
+                    }
+                }
+            }
+        }
+    };
+    
+    eta_ticker.abort();
+    
+    match result {
         Ok(success) => {
             println!("✅ Firmware update successful for device {}", device_id);
+            crate::device::update_history::record(&device_id, UpdateKind::Firmware, current_firmware, &target_version, Ok(()));
+            // Retried uploads include reconnect waits, which would skew the rate
+            if attempt == 1 {
+                let revision = crate::device::update_eta::device_revision(&device_id);
+                crate::device::update_eta::record_flash(&device_id, revision.as_deref(), total_bytes, clock.elapsed());
+            }
             println!("⚠️  Note: The device will now reboot. It will disconnect and reconnect automatically.");
             println!("    The frontend should wait for the device:connected event before proceeding.");
+            emit_firmware_progress(&app, &device_id, attempt, "complete", total_bytes, total_bytes);
             
             // Log the successful response
             let response_data = serde_json::json!({
                 "success": success,
                 "target_version": target_version,
+                "attempts": attempt,
                 "operation": "update_device_firmware"
             });
             
@@ -620,13 +789,15 @@ pub async fn update_device_firmware(
             
             Ok(success)
         }
-        Err(e) => {
-            let error_msg = e.to_string();
+        Err(error_msg) => {
             println!("❌ Firmware update failed for device {}: {}", device_id, error_msg);
+            crate::device::update_history::record(&device_id, UpdateKind::Firmware, current_firmware, &target_version, Err(&error_msg));
+            emit_firmware_progress(&app, &device_id, attempt, "failed", 0, total_bytes);
             
             // Log the error response
             let response_data = serde_json::json!({
                 "error": error_msg,
+                "attempts": attempt,
                 "operation": "update_device_firmware"
             });
             
@@ -637,4 +808,210 @@ pub async fn update_device_firmware(
             Err(format!("Firmware update failed: {}", error_msg))
         }
     }
-} 
\ No newline at end of file
+}
+
+/// Upload attempts before a transient failure is reported to the user
+const MAX_UPLOAD_ATTEMPTS: u32 = 3;
+
+/// How often `firmware:update-eta` refines the estimate during an upload
+const ETA_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// `firmware:update-progress` - `resumedFrom` is the byte offset the attempt started at.
+/// Only the `preparing` phase is `cancellable` with `cancel_firmware_update`.
+fn emit_firmware_progress(app: &AppHandle, device_id: &str, attempt: u32, phase: &str, position: usize, total: usize) {
+    let _ = app.emit("firmware:update-progress", serde_json::json!({
+        "deviceId": device_id,
+        "attempt": attempt,
+        "phase": phase,
+        "cancellable": phase == "preparing",
+        "resumedFrom": if phase == "uploading" { position } else { 0 },
+        "position": position,
+        "total": total,
+        "percent": if total > 0 { position * 100 / total } else { 0 }
+    }));
+}
+
+/// End an update cancelled while preparing. The device was at most asked for its
+/// features, so it is still in bootloader mode with its firmware intact.
+async fn finish_cancelled(app: &AppHandle, device_id: &str, request_id: &str) -> String {
+    println!("🛑 Firmware update of {} cancelled before the erase", device_id);
+    let _ = app.emit("firmware:cancelled", serde_json::json!({
+        "deviceId": device_id,
+        "phase": crate::device::update_cancel::UpdatePhase::Preparing
+    }));
+    
+    let error = "Firmware update cancelled".to_string();
+    let response_data = serde_json::json!({
+        "error": error,
+        "cancelled": true,
+        "operation": "update_device_firmware"
+    });
+    
+    if let Err(e) = log_device_response(device_id, request_id, false, &response_data, Some(&error)).await {
+        eprintln!("Failed to log firmware update cancellation: {}", e);
+    }
+    
+    error
+}
+
+/// Wait for a device to reconnect after a dropped upload and confirm it is still in
+/// bootloader mode
+async fn wait_for_bootloader(device_id: &str, queue_manager: &DeviceQueueManager) -> Option<keepkey_rust::device_queue::DeviceQueueHandle> {
+    for retry in 1..=5u64 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(1000 * retry)).await;
+        let Some(handle) = queue_manager.get_or_spawn_by_id(device_id).await else {
+            println!("⏳ Waiting for device {} to reconnect (attempt {}/5)", device_id, retry);
+            continue;
+        };
+        match handle.get_features().await {
+            Ok(features) if features.bootloader_mode.unwrap_or(false) => return Some(handle),
+            Ok(_) => {
+                println!("❌ Device {} reconnected outside bootloader mode", device_id);
+                return None;
+            }
+            // OOB bootloaders don't answer GetFeatures; let the upload itself find out
+            Err(e) if e.to_string().contains("Unknown message") => return Some(handle),
+            Err(e) => println!("⏳ Device {} not ready yet: {}", device_id, e),
+        }
+    }
+    None
+}
+
+/// Recover a device stuck in raw DFU mode by flashing a full recovery image
+///
+/// `image_path` points at a raw flash image (bootstrap + bootloader) starting at the
+/// beginning of internal flash. keepkey-rust refuses the flash, before writing
+/// anything, unless the image checks out and the device is identified as a
+/// KeepKey. Progress is reported through `device:recovery-progress`.
+#[tauri::command]
+pub async fn recover_from_dfu(
+    device_id: String,
+    image_path: String,
+    app: AppHandle,
+) -> Result<bool, String> {
+    println!("🚑 Starting DFU recovery for device {} with image {}", device_id, image_path);
+    
+    let request_id = uuid::Uuid::new_v4().to_string();
+    
+    // Log the request
+    let request_data = serde_json::json!({
+        "device_id": device_id,
+        "image_path": image_path,
+        "operation": "recover_from_dfu"
+    });
+    
+    if let Err(e) = log_device_request(&device_id, &request_id, "RecoverFromDfu", &request_data).await {
+        eprintln!("Failed to log DFU recovery request: {}", e);
+    }
+    
+    let result = async {
+        let device = keepkey_rust::features::list_dfu_devices()
+            .into_iter()
+            .find(|d| d.unique_id == device_id)
+            .ok_or_else(|| format!("Device {} is not in DFU mode", device_id))?;
+        
+        let image = fs::read(&image_path)
+            .map_err(|e| format!("Failed to read recovery image {}: {}", image_path, e))?;
+        println!("📦 Loaded recovery image: {} bytes", image.len());
+        
+        // DFU transfers are blocking USB control transfers
+        let app_for_progress = app.clone();
+        let progress_device_id = device_id.clone();
+        tokio::task::spawn_blocking(move || {
+            keepkey_rust::features::recover_dfu_device(&device, &image, |written, total| {
+                let _ = app_for_progress.emit("device:recovery-progress", serde_json::json!({
+                    "deviceId": progress_device_id,
+                    "written": written,
+                    "total": total,
+                    "percent": (written * 100 / total.max(1)) as u32
+                }));
+            })
+            .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| format!("DFU recovery task failed: {}", e))?
+    }.await;
+    
+    match result {
+        Ok(()) => {
+            println!("✅ DFU recovery complete for device {} - it will now restart", device_id);
+            
+            let response_data = serde_json::json!({
+                "success": true,
+                "operation": "recover_from_dfu"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, true, &response_data, None).await {
+                eprintln!("Failed to log DFU recovery success response: {}", e);
+            }
+            
+            Ok(true)
+        }
+        Err(error_msg) => {
+            println!("❌ DFU recovery failed for device {}: {}", device_id, error_msg);
+            
+            let response_data = serde_json::json!({
+                "error": error_msg,
+                "operation": "recover_from_dfu"
+            });
+            
+            if let Err(e) = log_device_response(&device_id, &request_id, false, &response_data, Some(&error_msg)).await {
+                eprintln!("Failed to log DFU recovery error response: {}", e);
+            }
+            
+            Err(format!("DFU recovery failed: {}", error_msg))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_installed_version_is_refused() {
+        match check_downgrade(None, "7.7.0", Some("2.1.4"), false) {
+            DowngradeCheck::Refused { error, downgrade: None } => assert!(error.contains("allow_downgrade")),
+            other => panic!("expected refusal, got {:?}", other),
+        }
+        assert_eq!(check_downgrade(None, "7.7.0", Some("2.1.4"), true), DowngradeCheck::Allowed(None));
+    }
+
+    #[test]
+    fn test_known_installed_version() {
+        assert_eq!(check_downgrade(Some("7.9.3"), "7.10.0", Some("2.1.4"), false), DowngradeCheck::Proceed);
+        assert!(matches!(
+            check_downgrade(Some("7.10.0"), "7.7.0", Some("2.1.4"), false),
+            DowngradeCheck::Refused { downgrade: Some(_), .. }
+        ));
+        assert!(matches!(
+            check_downgrade(Some("7.10.0"), "7.7.0", Some("2.1.4"), true),
+            DowngradeCheck::Allowed(Some(_))
+        ));
+        // A downgrade the bootloader blocks is refused even when allowed
+        assert!(matches!(
+            check_downgrade(Some("6.0.0"), "5.10.0", Some("2.0.0"), true),
+            DowngradeCheck::Refused { downgrade: Some(_), .. }
+        ));
+    }
+
+    #[test]
+    fn test_installed_version_survives_feature_cache_clear() {
+        let device_id = "test-installed-firmware";
+        let mut features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features::default());
+        features.version = "7.10.0".to_string();
+        features.bootloader_mode = false;
+        crate::commands::cache_device_features(device_id, &features);
+        // Disconnecting into the bootloader drops the cached features
+        crate::commands::invalidate_cached_features(device_id);
+        assert_eq!(installed_firmware_version(device_id, None).as_deref(), Some("7.10.0"));
+
+        // The bootloader's report of a known firmware hash wins
+        let bootloader = keepkey_rust::messages::Features {
+            bootloader_mode: Some(true),
+            firmware_hash: Some(hex::decode("958764cf3baa53eec0002eab9c54e02ce6f5fdab71e7efbbe723f958e26ff419").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(installed_firmware_version("test-other-device", Some(&bootloader)).as_deref(), Some("7.10.0"));
+    }
+}
@@ -1,22 +1,177 @@
+use keepkey_rust::device_queue::QueueTimeout;
 use keepkey_rust::friendly_usb::FriendlyUsbDevice;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::time::interval;
+use crate::commands::DeviceQueueManagerExt;
+use crate::device::active::ActiveChangeReason;
+use crate::device::attention::AttentionReason;
+use crate::device::state::DeviceState;
+use crate::events::{DeviceEvent, EventEmitter, EventTransformer, SharedEventTransformer};
+// All monitor timing goes through tokio's clock, so tests can pause and advance it
+use tokio::time::{Instant, Interval, MissedTickBehavior};
 use tokio_util::sync::CancellationToken;
 
+/// Why the device monitor stopped; sent to the frontend as `monitor:stopped`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StopReason {
+    /// `EventController::stop` was called
+    Requested,
+    /// The app is exiting
+    AppExit,
+    /// The controller was dropped while running
+    Dropped,
+    /// The monitor task panicked
+    Panic { message: String },
+    /// Listing the USB devices failed
+    EnumerationFailed { message: String },
+}
+
+impl StopReason {
+    /// Whether the monitor stopped without being asked to, and is restarted
+    pub fn is_failure(&self) -> bool {
+        matches!(self, StopReason::Panic { .. } | StopReason::EnumerationFailed { .. })
+    }
+}
+
+/// Restarts after failures before the monitor is left stopped
+pub const MAX_MONITOR_RESTARTS: u32 = 5;
+const MONITOR_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Tunables of the device monitor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventControllerConfig {
+    /// How often connected devices are scanned
+    pub poll_interval: Duration,
+    /// Wait before the first "Scanning for devices...", giving the frontend
+    /// time to register its listeners
+    pub startup_status_delay: Duration,
+    /// How long after the last device disconnects "Scanning for devices..." is shown again
+    pub scanning_delay: Duration,
+    /// Random spread applied to `scanning_delay`, as a fraction (0.2 = ±20%), so
+    /// repeated reconnect cycles don't line up with the poll interval
+    pub scanning_jitter: f64,
+    /// `poll_interval` while in `PowerMode::LowPower`
+    pub low_power_poll_interval: Duration,
+}
+
+/// How hard the monitor works; set by the frontend from the battery state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerMode {
+    #[default]
+    Normal,
+    /// Scan far less often and skip session keepalive pings. Devices are still
+    /// detected, just later; unlike stopping the monitor nothing is torn down.
+    LowPower,
+}
+
+impl Default for EventControllerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(1000),
+            startup_status_delay: Duration::from_millis(500),
+            scanning_delay: Duration::from_millis(1000),
+            scanning_jitter: 0.2,
+            low_power_poll_interval: Duration::from_secs(15),
+        }
+    }
+}
+
+impl EventControllerConfig {
+    fn poll_interval_for(&self, mode: PowerMode) -> Duration {
+        match mode {
+            PowerMode::Normal => self.poll_interval,
+            PowerMode::LowPower => self.low_power_poll_interval,
+        }
+    }
+
+    /// `scanning_delay` spread by up to `scanning_jitter`; `sample` is uniform in [0, 1)
+    fn jittered_scanning_delay(&self, sample: f64) -> Duration {
+        let jitter = self.scanning_jitter.clamp(0.0, 1.0);
+        self.scanning_delay.mul_f64(1.0 - jitter + 2.0 * jitter * sample.clamp(0.0, 1.0))
+    }
+}
+
+/// Run `task` once `delay` has passed from now, cancellable by aborting the
+/// returned handle
+fn spawn_after<F>(delay: Duration, task: F) -> tokio::task::JoinHandle<()>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let deadline = Instant::now() + delay;
+    tokio::spawn(async move {
+        tokio::time::sleep_until(deadline).await;
+        task.await;
+    })
+}
+
+/// Scan timer firing first at `start`, then every `period`. After a stall
+/// (system sleep, a slow enumeration) it fires once and continues a full period
+/// later, rather than tokio's default of firing every missed tick back to back.
+fn poll_timer(start: Instant, period: Duration) -> Interval {
+    let mut timer = tokio::time::interval_at(start, period);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+/// Whether an on-demand rescan comes too soon after the scan at `last_scan`
+fn rescan_debounced(last_scan: Instant) -> bool {
+    last_scan.elapsed() < RESCAN_DEBOUNCE
+}
+
+/// Uniform sample in [0, 1), from the OS randomness behind v4 UUIDs
+fn random_unit() -> f64 {
+    // The low 53 bits are clear of the version and variant fields
+    (uuid::Uuid::new_v4().as_u128() as u64 & ((1u64 << 53) - 1)) as f64 / (1u64 << 53) as f64
+}
+
+/// Last 8 characters of a device id for status lines. The id comes from the
+/// device, so it's cut on char boundaries and cleaned like any other
+/// device-reported string.
+fn short_device_id(unique_id: &str) -> String {
+    let tail: Vec<char> = unique_id.chars().rev().take(8).collect();
+    keepkey_rust::features::sanitize::sanitize_device_string(&tail.into_iter().rev().collect::<String>())
+}
+
 pub struct EventController {
     cancellation_token: CancellationToken,
     task_handle: Option<tauri::async_runtime::JoinHandle<()>>,
     is_running: bool,
+    transformer: SharedEventTransformer,
+    rescan_tx: Option<tokio::sync::mpsc::Sender<()>>,
+    config: EventControllerConfig,
+    power_tx: tokio::sync::watch::Sender<PowerMode>,
+    /// Why the monitor is being cancelled, set before the token is
+    stop_reason: Arc<Mutex<Option<StopReason>>>,
 }
 
 impl EventController {
     pub fn new() -> Self {
+        Self::with_config(EventControllerConfig::default())
+    }
+    
+    pub fn with_config(config: EventControllerConfig) -> Self {
         Self {
             cancellation_token: CancellationToken::new(),
             task_handle: None,
             is_running: false,
+            transformer: crate::events::default_shared_transformer(),
+            rescan_tx: None,
+            config,
+            power_tx: tokio::sync::watch::channel(PowerMode::Normal).0,
+            stop_reason: Arc::new(Mutex::new(None)),
+        }
+    }
+    
+    /// Replace how device events are turned into Tauri emits (rename, reshape or
+    /// suppress by returning `None`). Applies immediately, even while running.
+    #[allow(dead_code)]
+    pub fn set_event_transformer(&self, transformer: EventTransformer) {
+        match self.transformer.write() {
+            Ok(mut guard) => *guard = transformer,
+            Err(poisoned) => *poisoned.into_inner() = transformer,
         }
     }
     
@@ -27,397 +182,105 @@ impl EventController {
         }
         
         let app_handle = app.clone();
+        let emitter = EventEmitter::new(app, self.transformer.clone());
         let cancellation_token = self.cancellation_token.clone();
+        let config = self.config;
+        // Capacity 1: requests made while one is already pending coalesce into it
+        let (rescan_tx, rescan_rx) = tokio::sync::mpsc::channel::<()>(1);
+        self.rescan_tx = Some(rescan_tx);
+        let power_rx = self.power_tx.subscribe();
+        
+        let stop_reason = self.stop_reason.clone();
+        let state = Arc::new(tokio::sync::Mutex::new(MonitorState {
+            rescan_rx,
+            power_rx,
+            last_devices: Vec::new(),
+            first_scan: true,
+        }));
         
         let task_handle = tauri::async_runtime::spawn(async move {
-            let mut interval = interval(Duration::from_millis(1000)); // Check every second
-            let mut last_devices: Vec<FriendlyUsbDevice> = Vec::new();
-            
-            println!("✅ Event controller started - monitoring device connections");
-            
-            // Wait a moment for frontend to set up listeners, then emit initial scanning status
-            tokio::time::sleep(Duration::from_millis(500)).await;
-            println!("📡 Emitting status: Scanning for devices...");
-            let scanning_payload = serde_json::json!({
-                "status": "Scanning for devices..."
-            });
-            println!("📡 Scanning payload: {}", scanning_payload);
-            if let Err(e) = app_handle.emit("status:update", scanning_payload) {
-                println!("❌ Failed to emit scanning status: {}", e);
-            } else {
-                println!("✅ Successfully emitted scanning status");
-            }
-
-            // Test emission after longer delay to check if frontend is listening
-//             let app_for_test = app_handle.clone();
-//             tokio::spawn(async move {
-//                 tokio::time::sleep(Duration::from_millis(3000)).await;
-//                 println!("📡 Test: Emitting delayed test status...");
-//                 let test_payload = serde_json::json!({
-//                     "status": "Test message after 3 seconds"
-//                 });
-//                 println!("📡 Test payload: {}", test_payload);
-//                 if let Err(e) = app_for_test.emit("status:update", test_payload) {
-//                     println!("❌ Failed to emit delayed test status: {}", e);
-//                 } else {
-//                     println!("✅ Successfully emitted delayed test status");
-//                 }
-//             });
-            
-            loop {
-                tokio::select! {
-                    _ = cancellation_token.cancelled() => {
-                        println!("🛑 Event controller shutting down on cancellation signal");
-                        break;
-                    }
-                    _ = interval.tick() => {
-                        // Get current devices using high-level API
-                        let current_devices = keepkey_rust::features::list_connected_devices();
-                        
-                        // Check for newly connected devices
-                        for device in &current_devices {
-                            if !last_devices.iter().any(|d| d.unique_id == device.unique_id) {
-                                // Check if this is a duplicate of an already connected device
-                                let is_duplicate = current_devices.iter().any(|other| {
-                                    other.unique_id != device.unique_id && 
-                                    crate::commands::are_devices_potentially_same(&device.unique_id, &other.unique_id)
-                                });
-                                
-                                if is_duplicate {
-                                    println!("⚠️ Skipping duplicate device: {} (already connected with different ID)", device.unique_id);
-                                    continue;
-                                }
-                                
-                                println!("🔌 Device connected: {} (VID: 0x{:04x}, PID: 0x{:04x})", 
-                                         device.unique_id, device.vid, device.pid);
-                                println!("   Device info: {} - {}", 
-                                         device.manufacturer.as_deref().unwrap_or("Unknown"), 
-                                         device.product.as_deref().unwrap_or("Unknown"));
-                                
-                                // Check if this might be a recovery device reconnecting with a different ID
-                                if let Some(state) = app_handle.try_state::<crate::commands::DeviceQueueManager>() {
-                                    let queue_manager_arc = state.inner().clone();
-                                    let manager = queue_manager_arc.lock().await;
-                                    
-                                    // Check if any existing device might be the same physical device
-                                    for (existing_id, _) in manager.iter() {
-                                        if crate::commands::are_devices_potentially_same(&device.unique_id, existing_id) &&
-                                           crate::commands::is_device_in_recovery_flow(existing_id) {
-                                            println!("🔄 Device {} appears to be recovery device {} reconnecting", 
-                                                    device.unique_id, existing_id);
-                                            let _ = crate::commands::add_recovery_device_alias(&device.unique_id, existing_id);
-                                            
-                                            // Emit special reconnection event
-                                            let _ = app_handle.emit("device:recovery-reconnected", serde_json::json!({
-                                                "new_id": &device.unique_id,
-                                                "original_id": existing_id,
-                                                "status": "reconnected"
-                                            }));
-                                        }
-                                    }
-                                }
-                                
-                                // Emit device found status
-                                let device_short = &device.unique_id[device.unique_id.len().saturating_sub(8)..];
-                                println!("📡 Emitting status: Device found {}", device_short);
-                                let device_found_payload = serde_json::json!({
-                                    "status": format!("Device found {}", device_short)
-                                });
-                                println!("📡 Device found payload: {}", device_found_payload);
-                                if let Err(e) = app_handle.emit("status:update", device_found_payload) {
-                                    println!("❌ Failed to emit device found status: {}", e);
-                                } else {
-                                    println!("✅ Successfully emitted device found status");
-                                }
-                                
-                                // Emit basic device connected event first
-                                let _ = app_handle.emit("device:connected", device);
-                                
-                                // Proactively fetch features and emit device:ready when successful
-                                let app_for_task = app_handle.clone();
-                                let device_for_task = device.clone();
-                                tokio::spawn(async move {
-                                    // Give device a moment to settle after connection
-                                    tokio::time::sleep(Duration::from_millis(500)).await;
-                                    println!("📡 Fetching device features for: {}", device_for_task.unique_id);
-                                    
-                                    // Emit getting features status
-                                    println!("📡 Emitting status: Getting features...");
-                                    if let Err(e) = app_for_task.emit("status:update", serde_json::json!({
-                                        "status": "Getting features..."
-                                    })) {
-                                        println!("❌ Failed to emit getting features status: {}", e);
-                                    }
-                                    
-                                    match try_get_device_features(&device_for_task, &app_for_task).await {
-                                        Ok(features) => {
-                                            let device_label = features.label.as_deref().unwrap_or("Unlabeled");
-                                            let device_version = &features.version;
-                                            
-                                            println!("📡 Got device features: {} v{} ({})", 
-                                                   device_label,
-                                                   device_version,
-                                                   device_for_task.unique_id);
-                                            
-                                            // Emit device info status
-                                            println!("📡 Emitting status: {} v{}", device_label, device_version);
-                                            if let Err(e) = app_for_task.emit("status:update", serde_json::json!({
-                                                "status": format!("{} v{}", device_label, device_version)
-                                            })) {
-                                                println!("❌ Failed to emit device info status: {}", e);
-                                            }
-                                            
-                                            // Evaluate device status to determine if updates are needed
-                                            let status = crate::commands::evaluate_device_status(
-                                                device_for_task.unique_id.clone(), 
-                                                Some(&features)
-                                            );
-                                            
-                                                                        // Check if device is locked with PIN before determining if it's ready
-                            let has_pin_protection = features.pin_protection;
-                            let pin_cached = features.pin_cached;
-                            let is_pin_locked = features.initialized && has_pin_protection && !pin_cached;
-                            
-                            // Emit status updates based on what the device needs
-                            // CRITICAL: Device in bootloader mode is NEVER ready
-                            let is_actually_ready = !features.bootloader_mode &&  // Never ready if in bootloader mode
-                                                   !status.needs_bootloader_update && 
-                                                   !status.needs_firmware_update && 
-                                                   !status.needs_initialization &&
-                                                   !is_pin_locked;  // Device is NOT ready if locked with PIN
-                            
-                            if is_actually_ready {
-                                                println!("✅ Device is fully ready, emitting device:ready event");
-                                                println!("📡 Emitting status: Device ready");
-                                                if let Err(e) = app_for_task.emit("status:update", serde_json::json!({
-                                                    "status": "Device ready"
-                                                })) {
-                                                    println!("❌ Failed to emit device ready status: {}", e);
-                                                }
-                                                                                let ready_payload = serde_json::json!({
-                                    "device": device_for_task,
-                                    "features": features,
-                                    "status": "ready"
-                                });
-                                
-                                // Queue device:ready event as it's important for wallet initialization
-                                if let Err(e) = crate::commands::emit_or_queue_event(&app_for_task, "device:ready", ready_payload).await {
-                                    println!("❌ Failed to emit/queue device:ready event: {}", e);
-                                } else {
-                                    println!("📡 Successfully emitted/queued device:ready for {}", device_for_task.unique_id);
-                                }
-                                            } else {
-                                                                                println!("⚠️ Device connected but needs updates (bootloader_mode: {}, bootloader: {}, firmware: {}, init: {}, pin_locked: {})", 
-                                        features.bootloader_mode,
-                                        status.needs_bootloader_update, 
-                                        status.needs_firmware_update, 
-                                        status.needs_initialization,
-                                        is_pin_locked);
-                                                
-                                                if is_pin_locked {
-                                                    println!("🔒 Device is initialized but locked with PIN - emitting unlock event");
-                                                    
-                                                    // Emit PIN unlock needed event
-                                                    let pin_unlock_payload = serde_json::json!({
-                                                        "deviceId": device_for_task.unique_id,
-                                                        "features": features,
-                                                        "status": status,
-                                                        "needsPinUnlock": true
-                                                    });
-                                                    
-                                                    if let Err(e) = crate::commands::emit_or_queue_event(&app_for_task, "device:pin-unlock-needed", pin_unlock_payload).await {
-                                                        println!("❌ Failed to emit/queue device:pin-unlock-needed event: {}", e);
-                                                    } else {
-                                                        println!("📡 Successfully emitted/queued device:pin-unlock-needed for {}", device_for_task.unique_id);
-                                                    }
-                                                }
-                                                
-                                                // Emit appropriate status message based on what updates are needed
-                                                let status_message = if features.bootloader_mode {
-                                                    if status.needs_bootloader_update {
-                                                        "Device in bootloader mode - update needed"
-                                                    } else {
-                                                        "Device in bootloader mode - reboot needed"
-                                                    }
-                                                } else if is_pin_locked {
-                                                    "Device locked - enter PIN"
-                                                } else if status.needs_bootloader_update && status.needs_firmware_update && status.needs_initialization {
-                                                    "Device needs updates"
-                                                } else if status.needs_bootloader_update {
-                                                    "Bootloader update needed"
-                                                } else if status.needs_firmware_update {
-                                                    "Firmware update needed"
-                                                } else if status.needs_initialization {
-                                                    "Device setup needed"
-                                                } else {
-                                                    "Device ready"
-                                                };
-                                                
-                                                println!("📡 Emitting status: {}", status_message);
-                                                if let Err(e) = app_for_task.emit("status:update", serde_json::json!({
-                                                    "status": status_message
-                                                })) {
-                                                    println!("❌ Failed to emit update status: {}", e);
-                                                }
-                                            }
-                                            
-                                                                        // Emit device:features-updated event with evaluated status (for DeviceUpdateManager)
-                            // This is a critical event that should be queued if frontend isn't ready
-                            let features_payload = serde_json::json!({
-                                "deviceId": device_for_task.unique_id,
-                                "features": features,
-                                "status": status  // Use evaluated status instead of hardcoded "ready"
-                            });
-                            
-                            if let Err(e) = crate::commands::emit_or_queue_event(&app_for_task, "device:features-updated", features_payload).await {
-                                println!("❌ Failed to emit/queue device:features-updated event: {}", e);
-                            } else {
-                                println!("📡 Successfully emitted/queued device:features-updated for {}", device_for_task.unique_id);
-                            }
-                                        }
-                                        Err(e) => {
-                                            println!("❌ Failed to get features for {}: {}", device_for_task.unique_id, e);
-                                            
-                                            // Check for timeout errors specifically
-                                            if e.contains("Timeout while fetching device features") {
-                                                println!("⏱️ Device timeout detected - device may be in invalid state");
-                                                println!("❌ OOPS this should never happen - device communication failed!");
-                                                
-                                                // Log detailed error for debugging
-                                                eprintln!("ERROR: Device timeout indicates invalid state - this should be prevented!");
-                                                eprintln!("Device ID: {}", device_for_task.unique_id);
-                                                eprintln!("Error: {}", e);
-                                                
-                                                // Emit device invalid state event for UI to handle
-                                                let invalid_state_payload = serde_json::json!({
-                                                    "deviceId": device_for_task.unique_id,
-                                                    "error": e,
-                                                    "errorType": "DEVICE_TIMEOUT",
-                                                    "status": "invalid_state"
-                                                });
-                                                let _ = app_for_task.emit("device:invalid-state", &invalid_state_payload);
-                                                
-                                                // Also emit status update
-                                                let _ = app_for_task.emit("status:update", serde_json::json!({
-                                                    "status": "Device timeout - please reconnect"
-                                                }));
-                                            }
-                                            // Check if this is a device access error
-                                            else if e.contains("Device Already In Use") || 
-                                               e.contains("already claimed") ||
-                                               e.contains("🔒") {
-                                                
-                                                let user_friendly_error = if e.contains("🔒") {
-                                                    e.clone()
-                                                } else {
-                                                    format!(
-                                                        "🔒 KeepKey Device Already In Use\n\n\
-                                                        Your KeepKey device is currently being used by another application.\n\n\
-                                                        Common causes:\n\
-                                                        • KeepKey Desktop app is running\n\
-                                                        • KeepKey Bridge is running\n\
-                                                        • Another wallet application is connected\n\
-                                                        • Previous connection wasn't properly closed\n\n\
-                                                        Solutions:\n\
-                                                        1. Close KeepKey Desktop app completely\n\
-                                                        2. Close any other wallet applications\n\
-                                                        3. Unplug and reconnect your KeepKey device\n\
-                                                        4. Try again\n\n\
-                                                        Technical details: {}", e
-                                                    )
-                                                };
-                                                
-                                                // Emit device access error event
-                                                let error_payload = serde_json::json!({
-                                                    "deviceId": device_for_task.unique_id,
-                                                    "error": user_friendly_error,
-                                                    "errorType": "DEVICE_CLAIMED",
-                                                    "status": "error"
-                                                });
-                                                let _ = app_for_task.emit("device:access-error", &error_payload);
-                                            }
-                                        }
-                                    }
-                                });
-                            }
-                        }
-                        
-                        // Check for disconnected devices
-                        for device in &last_devices {
-                            if !current_devices.iter().any(|d| d.unique_id == device.unique_id) {
-                                println!("🔌❌ Device disconnected: {}", device.unique_id);
-                                
-                                // Check if device is in recovery flow before cleaning up
-                                let is_in_recovery = crate::commands::is_device_in_recovery_flow(&device.unique_id);
-                                
-                                if is_in_recovery {
-                                    println!("🛡️ Device {} is in recovery flow - preserving queue and state", device.unique_id);
-                                    // Don't emit disconnection or clean up queue - just wait for reconnection
-                                    continue;
-                                }
-                                
-                                // Emit device disconnected status
-                                println!("📡 Emitting status: Device disconnected");
-                                if let Err(e) = app_handle.emit("status:update", serde_json::json!({
-                                    "status": "Device disconnected"
-                                })) {
-                                    println!("❌ Failed to emit disconnect status: {}", e);
-                                }
-                                
-                                // Clean up device queue for disconnected device
-                                if let Some(state) = app_handle.try_state::<crate::commands::DeviceQueueManager>() {
-                                    let device_id = device.unique_id.clone();
-                                    // Clone the underlying Arc so it outlives this scope
-                                    let queue_manager_arc = state.inner().clone();
-                                    tokio::spawn(async move {
-                                        println!("♻️ Cleaning up device queue for disconnected device: {}", device_id);
-                                        let mut manager = queue_manager_arc.lock().await;
-                                        if let Some(handle) = manager.remove(&device_id) {
-                                            let _ = handle.shutdown().await;
-                                            println!("✅ Device queue cleaned up for: {}", device_id);
-                                        }
-                                    });
-                                }
-                                
-                                let _ = app_handle.emit("device:disconnected", &device.unique_id);
-                            }
-                        }
-                        
-                        // If no devices connected after checking disconnections, emit scanning status
-                        if current_devices.is_empty() && !last_devices.is_empty() {
-                            // After a short delay, go back to scanning
-                            let app_for_scanning = app_handle.clone();
-                            tokio::spawn(async move {
-                                tokio::time::sleep(Duration::from_millis(1000)).await;
-                                println!("📡 Emitting status: Scanning for devices... (after disconnect)");
-                                if let Err(e) = app_for_scanning.emit("status:update", serde_json::json!({
-                                    "status": "Scanning for devices..."
-                                })) {
-                                    println!("❌ Failed to emit scanning status after disconnect: {}", e);
-                                }
-                            });
-                        }
-                        
-                        last_devices = current_devices;
-                    }
-                }
-            }
-            
-            println!("✅ Event controller stopped cleanly");
+            let token = cancellation_token.clone();
+            let app = app_handle.clone();
+            supervise(
+                move || {
+                    let host = MonitorHost { app: Some(app_handle.clone()), list_devices: Arc::new(list_usb_devices) };
+                    tokio::spawn(run_monitor(host, emitter.clone(), cancellation_token.clone(), config, state.clone()))
+                },
+                token,
+                stop_reason,
+                |reason, restarting| emit_monitor_stopped(&app, reason, restarting),
+            )
+            .await;
         });
         
         self.task_handle = Some(task_handle);
         self.is_running = true;
     }
+
+    /// Ask the monitor to scan now instead of at the next tick. Returns false if
+    /// the monitor isn't running.
+    pub fn request_rescan(&self) -> bool {
+        let Some(rescan_tx) = self.rescan_tx.as_ref().filter(|_| self.is_running) else {
+            return false;
+        };
+        match rescan_tx.try_send(()) {
+            Ok(()) => true,
+            // A rescan is already pending; this request is served by it
+            Err(tokio::sync::mpsc::error::TrySendError::Full(())) => true,
+            Err(tokio::sync::mpsc::error::TrySendError::Closed(())) => false,
+        }
+    }
+    
+    /// Switch between normal and low-power polling. Leaving low-power mode scans
+    /// right away. Returns whether the mode changed.
+    pub fn set_power_mode(&self, mode: PowerMode) -> bool {
+        self.power_tx.send_if_modified(|current| {
+            let changed = *current != mode;
+            *current = mode;
+            changed
+        })
+    }
     
-    pub fn stop(&mut self) {
+    pub fn power_mode(&self) -> PowerMode {
+        *self.power_tx.borrow()
+    }
+    
+    /// Token that is cancelled when the controller stops, for services that
+    /// must shut down together with device monitoring
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.cancellation_token.child_token()
+    }
+    
+    /// Cancel the monitor and hand its task back so the caller can await it.
+    /// Used by `shutdown_device_services`; `stop` only detaches a waiter.
+    pub fn stop_for_shutdown(&mut self) -> Option<tauri::async_runtime::JoinHandle<()>> {
+        if self.is_running {
+            println!("🛑 Stopping event controller for shutdown...");
+            self.record_stop_reason(StopReason::AppExit);
+            self.cancellation_token.cancel();
+            self.is_running = false;
+        }
+        self.task_handle.take()
+    }
+    
+    fn record_stop_reason(&self, reason: StopReason) {
+        match self.stop_reason.lock() {
+            Ok(mut guard) => *guard = Some(reason),
+            Err(poisoned) => *poisoned.into_inner() = Some(reason),
+        }
+    }
+    
+    /// Cancel the monitor and detach a waiter for its task; `reason` is
+    /// reported in `monitor:stopped`
+    pub fn stop(&mut self, reason: StopReason) {
         if !self.is_running {
             return;
         }
         
-        println!("🛑 Stopping event controller...");
+        println!("🛑 Stopping event controller ({:?})...", reason);
         
         // Cancel the background task
+        self.record_stop_reason(reason);
         self.cancellation_token.cancel();
         self.is_running = false;
         
@@ -435,19 +298,655 @@ impl EventController {
     }
 }
 
+/// Monitor state that outlives a single run, so a restarted monitor carries on
+/// where the crashed one left off instead of reporting every device anew
+struct MonitorState {
+    rescan_rx: tokio::sync::mpsc::Receiver<()>,
+    power_rx: tokio::sync::watch::Receiver<PowerMode>,
+    last_devices: Vec<FriendlyUsbDevice>,
+    first_scan: bool,
+}
+
+/// What the monitor scans and whose worker queues it manages. Tests run it
+/// without an app, against a scripted device list.
+#[derive(Clone)]
+struct MonitorHost {
+    app: Option<AppHandle>,
+    /// Lists the plugged-in USB devices; a panic stops the run with
+    /// `StopReason::EnumerationFailed`
+    list_devices: Arc<dyn Fn() -> Vec<FriendlyUsbDevice> + Send + Sync>,
+}
+
+impl MonitorHost {
+    fn queue_manager(&self) -> Option<crate::commands::DeviceQueueManager> {
+        let app = self.app.as_ref()?;
+        app.try_state::<crate::commands::DeviceQueueManager>().map(|state| state.inner().clone())
+    }
+}
+
+/// KeepKeys in normal mode, then those stuck in DFU mode
+fn list_usb_devices() -> Vec<FriendlyUsbDevice> {
+    let mut devices = keepkey_rust::features::list_connected_devices();
+    // Devices stuck in DFU mode enumerate with the STM32 VID, so scan for them separately
+    devices.extend(keepkey_rust::features::list_dfu_devices());
+    devices
+}
+
+/// One run of the device monitor. Returns why it stopped by itself, `None`
+/// when it was cancelled.
+async fn run_monitor(
+    host: MonitorHost,
+    emitter: EventEmitter,
+    cancellation_token: CancellationToken,
+    config: EventControllerConfig,
+    state: Arc<tokio::sync::Mutex<MonitorState>>,
+) -> Option<StopReason> {
+    let mut state = state.lock().await;
+    let MonitorState { rescan_rx, power_rx, last_devices, first_scan } = &mut *state;
+
+    let mut interval = poll_timer(Instant::now(), config.poll_interval_for(*power_rx.borrow_and_update()));
+    let mut last_scan = Instant::now();
+    let coalesce_initial_scan = crate::commands::coalesce_initial_scan_enabled();
+    // Delayed "Scanning for devices..." after the last disconnect; dropped
+    // if a device shows up before it fires
+    let mut pending_scanning: Option<tokio::task::JoinHandle<()>> = None;
+    
+    println!("✅ Event controller started - monitoring device connections");
+    
+    // Wait a moment for frontend to set up listeners, then emit initial scanning status
+    if *first_scan {
+        tokio::time::sleep(config.startup_status_delay).await;
+        emitter.status("Scanning for devices...").await;
+        // Missing udev rules look like a device that never shows up; say so up front
+        if let Some(issue) = crate::device::udev::check() {
+            emitter.emit(DeviceEvent::PermissionIssue { issue }).await;
+        }
+    }
+    
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                println!("🛑 Event controller shutting down on cancellation signal");
+                break;
+            }
+            _ = interval.tick() => {}
+            Some(()) = rescan_rx.recv() => {
+                if rescan_debounced(last_scan) {
+                    println!("⏭️ Rescan requested {:?} after the last scan - skipping", last_scan.elapsed());
+                    continue;
+                }
+                println!("🔍 Rescanning devices on request");
+                // The scan below replaces the next scheduled one
+                interval.reset();
+            }
+            Ok(()) = power_rx.changed() => {
+                let mode = *power_rx.borrow_and_update();
+                let period = config.poll_interval_for(mode);
+                println!("🔋 Power mode {:?} - polling every {:?}", mode, period);
+                interval = poll_timer(Instant::now() + period, period);
+                if mode == PowerMode::LowPower {
+                    continue;
+                }
+                // Back to normal: catch up on what happened while polling slowly
+                println!("🔍 Rescanning devices after leaving low-power mode");
+            }
+        }
+        last_scan = Instant::now();
+        
+        // Get current devices using high-level API
+        // rusb errors surface as panics; catch them here so they're told apart from a monitor bug
+        let mut current_devices = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (host.list_devices)())) {
+            Ok(devices) => devices,
+            Err(panic) => return Some(StopReason::EnumerationFailed { message: panic_message(panic) }),
+        };
+        // Virtual devices of the mock-device feature go through the same path
+        current_devices.extend(crate::device::mock::connected_devices());
+        
+        // Opt-in: report the devices present at startup as one snapshot
+        if *first_scan {
+            *first_scan = false;
+            if coalesce_initial_scan {
+                emitter.begin_initial_scan(current_devices.iter().map(|d| d.unique_id.clone()).collect()).await;
+            }
+        }
+        emitter.flush_expired_initial_scan().await;
+        
+        if !current_devices.is_empty() {
+            if let Some(pending) = pending_scanning.take() {
+                pending.abort();
+            }
+        }
+        
+        // Forgotten devices that are still plugged in go through the connect path again
+        let forgotten = crate::device::forget::take_forgotten();
+        if !forgotten.is_empty() {
+            last_devices.retain(|d| !forgotten.contains(&d.unique_id));
+        }
+        
+        // Check for newly connected devices
+        for device in &current_devices {
+            if !last_devices.iter().any(|d| d.unique_id == device.unique_id) {
+                // A DFU-mode device can't answer GetFeatures or Initialize - go straight to recovery
+                if device.is_dfu_mode() {
+                    println!("🚑 Device {} is in DFU mode (VID: 0x{:04x}, PID: 0x{:04x}) - recovery needed", 
+                             device.unique_id, device.vid, device.pid);
+                    emitter.set_state(&device.unique_id, DeviceState::Connected).await;
+                    emitter.set_state(&device.unique_id, DeviceState::Error).await;
+                    emit_recovery_needed(&emitter, device).await;
+                    emitter.settle_initial_scan(&device.unique_id).await;
+                    continue;
+                }
+                
+                // Check if this is a duplicate of an already connected device
+                let is_duplicate = current_devices.iter().any(|other| {
+                    other.unique_id != device.unique_id && 
+                    crate::commands::are_devices_potentially_same(&device.unique_id, &other.unique_id)
+                });
+                
+                if is_duplicate {
+                    println!("⚠️ Skipping duplicate device: {} (already connected with different ID)", device.unique_id);
+                    emitter.settle_initial_scan(&device.unique_id).await;
+                    continue;
+                }
+                
+                println!("🔌 Device connected: {} (VID: 0x{:04x}, PID: 0x{:04x})", 
+                         device.unique_id, device.vid, device.pid);
+                println!("   Device info: {} - {}", 
+                         device.manufacturer.as_deref().unwrap_or("Unknown"), 
+                         device.product.as_deref().unwrap_or("Unknown"));
+                
+                // Check if this might be a recovery device reconnecting with a different ID
+                if let Some(queue_manager_arc) = host.queue_manager() {
+                    let recovery_ids: Vec<String> = {
+                        let manager = queue_manager_arc.lock().await;
+                        manager.keys()
+                            .filter(|existing_id| {
+                                crate::commands::are_devices_potentially_same(&device.unique_id, existing_id) &&
+                                crate::commands::is_device_in_recovery_flow(existing_id)
+                            })
+                            .cloned()
+                            .collect()
+                    };
+                    
+                    // Check if any existing device might be the same physical device
+                    for existing_id in recovery_ids {
+                        println!("🔄 Device {} appears to be recovery device {} reconnecting", 
+                                device.unique_id, existing_id);
+                        let _ = crate::commands::add_recovery_device_alias(&device.unique_id, &existing_id);
+                        
+                        // Emit special reconnection event
+                        emitter.emit(DeviceEvent::RecoveryReconnected {
+                            new_id: device.unique_id.clone(),
+                            original_id: existing_id,
+                        }).await;
+                    }
+                }
+                
+                // Emit device found status
+                let device_short = short_device_id(&device.unique_id);
+                emitter.status(format!("Device found {}", device_short)).await;
+                
+                // Emit basic device connected event first
+                crate::device::ready_timing::with_timer(|timer| timer.connected(&device.unique_id, Instant::now().into_std()));
+                emitter.emit(DeviceEvent::Connected { device: device.clone() }).await;
+                emitter.set_state(&device.unique_id, DeviceState::Connected).await;
+                restore_active_device(&emitter, device).await;
+                
+                // Proactively fetch features, once the device had a moment to settle,
+                // and emit device:ready when successful
+                if let Some(app) = &host.app {
+                    spawn_probe(app, &emitter, device, Duration::from_millis(500));
+                }
+            }
+        }
+        
+        // Re-probe connected devices whose earlier probes failed, once their backoff is up
+        let due = crate::device::probe::with_tracker(|tracker| tracker.due(Instant::now().into_std()));
+        if let Some(app) = &host.app {
+            for device in current_devices.iter().filter(|d| due.contains(&d.unique_id)) {
+                println!("🔁 Re-probing device {}", device.unique_id);
+                spawn_probe(app, &emitter, device, Duration::ZERO);
+            }
+        }
+        
+        // Stop workers that sat unused; the device stays connected and the
+        // next request for it spawns a new worker
+        if let (Some(idle_after), Some(queue_manager)) = (crate::commands::worker_idle_timeout(), host.queue_manager()) {
+            for device_id in queue_manager.reap_idle(idle_after).await {
+                println!("💤 Stopped idle worker for device {}", device_id);
+            }
+        }
+        
+        // Check for disconnected devices
+        for device in last_devices.iter() {
+            if !current_devices.iter().any(|d| d.unique_id == device.unique_id) {
+                println!("🔌❌ Device disconnected: {}", device.unique_id);
+                
+                // Check if device is in recovery flow before cleaning up
+                let is_in_recovery = crate::commands::is_device_in_recovery_flow(&device.unique_id);
+                
+                if is_in_recovery {
+                    println!("🛡️ Device {} is in recovery flow - preserving queue and state", device.unique_id);
+                    // Don't emit disconnection or clean up queue - just wait for reconnection
+                    continue;
+                }
+                
+                // Emit device disconnected status
+                emitter.status("Device disconnected").await;
+                crate::commands::invalidate_cached_features(&device.unique_id);
+                crate::device::probe::with_tracker(|tracker| tracker.remove(&device.unique_id));
+                crate::device::attention::forget(&device.unique_id);
+                
+                // Clean up device queue for disconnected device
+                if let Some(queue_manager_arc) = host.queue_manager() {
+                    let device_id = device.unique_id.clone();
+                    tokio::spawn(async move {
+                        println!("♻️ Cleaning up device queue for disconnected device: {}", device_id);
+                        if queue_manager_arc.remove_and_shutdown(&device_id).await {
+                            println!("✅ Device queue cleaned up for: {}", device_id);
+                        }
+                    });
+                }
+                
+                emitter.emit(DeviceEvent::Disconnected { device_id: device.unique_id.clone() }).await;
+                emitter.set_state(&device.unique_id, DeviceState::Disconnected).await;
+                
+                // The UI has to pick a new active device
+                if crate::device::active::clear_if(&device.unique_id) {
+                    emitter.emit(DeviceEvent::ActiveChanged {
+                        device_id: None,
+                        previous: Some(device.unique_id.clone()),
+                        reason: ActiveChangeReason::Disconnected,
+                    }).await;
+                }
+            }
+        }
+        
+        // If no devices connected after checking disconnections, emit scanning status
+        if current_devices.is_empty() && !last_devices.is_empty() {
+            // After a short delay, go back to scanning
+            let emitter_for_scanning = emitter.clone();
+            let delay = config.jittered_scanning_delay(random_unit());
+            if let Some(previous) = pending_scanning.replace(spawn_after(delay, async move {
+                emitter_for_scanning.status("Scanning for devices...").await;
+            })) {
+                previous.abort();
+            }
+        }
+        
+        *last_devices = current_devices;
+    }
+    
+    println!("✅ Event controller stopped cleanly");
+    None
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+fn recorded_reason(stop_reason: &Mutex<Option<StopReason>>) -> StopReason {
+    let recorded = match stop_reason.lock() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    recorded.unwrap_or(StopReason::Requested)
+}
+
+/// Run the monitor via `run` until it's stopped on purpose. A run that fails
+/// (see `StopReason::is_failure`) is restarted after a short delay, at most
+/// `MAX_MONITOR_RESTARTS` times. `on_stop` gets every stop and whether a
+/// restart follows. Returns the final reason.
+async fn supervise(
+    mut run: impl FnMut() -> tokio::task::JoinHandle<Option<StopReason>>,
+    cancellation_token: CancellationToken,
+    stop_reason: Arc<Mutex<Option<StopReason>>>,
+    mut on_stop: impl FnMut(&StopReason, bool),
+) -> StopReason {
+    let mut restarts = 0;
+    loop {
+        let reason = match run().await {
+            Ok(Some(reason)) => reason,
+            Err(e) if e.is_panic() => StopReason::Panic { message: panic_message(e.into_panic()) },
+            // Cancelled: whoever cancelled recorded why
+            Ok(None) | Err(_) => recorded_reason(&stop_reason),
+        };
+        let restarting = reason.is_failure() && restarts < MAX_MONITOR_RESTARTS && !cancellation_token.is_cancelled();
+        on_stop(&reason, restarting);
+        if !restarting {
+            return reason;
+        }
+        
+        restarts += 1;
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return reason,
+            _ = tokio::time::sleep(MONITOR_RESTART_DELAY) => {}
+        }
+        println!("🔁 Restarting device monitor ({}/{})", restarts, MAX_MONITOR_RESTARTS);
+    }
+}
+
+/// Payload of `monitor:stopped`
+pub fn monitor_stopped_payload(reason: &StopReason, restarting: bool) -> serde_json::Value {
+    serde_json::json!({
+        "reason": reason,
+        "restarting": restarting
+    })
+}
+
+fn emit_monitor_stopped(app: &AppHandle, reason: &StopReason, restarting: bool) {
+    if reason.is_failure() {
+        eprintln!("💥 Device monitor stopped: {:?} (restarting: {})", reason, restarting);
+    } else {
+        println!("✅ Device monitor stopped: {:?}", reason);
+    }
+    let payload = monitor_stopped_payload(reason, restarting);
+    let summary = crate::event_log::summarize(&payload);
+    let outcome = match app.emit("monitor:stopped", &payload) {
+        Ok(()) => crate::event_log::EmitOutcome::Emitted,
+        Err(e) => crate::event_log::EmitOutcome::Failed { error: e.to_string() },
+    };
+    // Kept with the device events so support bundles show why monitoring ended
+    crate::event_log::record("monitor:stopped", None, None, summary, outcome);
+}
+
 impl Drop for EventController {
     fn drop(&mut self) {
-        self.stop();
+        self.stop(StopReason::Dropped);
+    }
+}
+
+/// Evaluate freshly fetched features and tell the frontend what the device needs
+async fn handle_device_features(emitter: &EventEmitter, device: &FriendlyUsbDevice, features: keepkey_rust::features::DeviceFeatures) {
+    crate::commands::cache_device_features(&device.unique_id, &features);
+    crate::device::udev::forget(&device.unique_id);
+    let device_label = keepkey_rust::features::naming::display_name_for_features(&features, Some(&device.unique_id));
+    let device_version = &features.version;
+    
+    println!("📡 Got device features: {} v{} ({})", 
+           device_label,
+           device_version,
+           device.unique_id);
+    
+    // Emit device info status
+    emitter.status(format!("{} v{}", device_label, device_version)).await;
+    
+    // Evaluate device status to determine if updates are needed
+    let status = crate::commands::evaluate_device_status(
+        device.unique_id.clone(), 
+        Some(&features)
+    );
+    
+    // Check if device is locked with PIN before determining if it's ready
+    let has_pin_protection = features.pin_protection;
+    let pin_cached = features.pin_cached;
+    let is_pin_locked = features.initialized && has_pin_protection && !pin_cached;
+    
+    // Emit status updates based on what the device needs
+    // CRITICAL: Device in bootloader mode is NEVER ready
+    let is_actually_ready = !features.bootloader_mode &&  // Never ready if in bootloader mode
+                           !status.needs_bootloader_update && 
+                           !status.needs_firmware_update && 
+                           !status.needs_initialization &&
+                           !is_pin_locked;  // Device is NOT ready if locked with PIN
+    let time_to_ready = crate::device::ready_timing::with_timer(|timer| {
+        timer.status(&device.unique_id, is_actually_ready, Instant::now().into_std())
+    });
+    
+    if is_actually_ready {
+        println!("✅ Device is fully ready, emitting device:ready event");
+        emitter.status("Device ready").await;
+        
+        // Queued if the frontend isn't ready, as it's important for wallet initialization
+        emitter.emit(DeviceEvent::Ready {
+            device: device.clone(),
+            features: features.clone(),
+            time_to_ready_ms: time_to_ready.map(|elapsed| elapsed.as_millis() as u64),
+        }).await;
+    } else {
+        println!("⚠️ Device connected but needs updates (bootloader_mode: {}, bootloader: {}, firmware: {}, init: {}, pin_locked: {})", 
+                features.bootloader_mode,
+                status.needs_bootloader_update, 
+                status.needs_firmware_update, 
+                status.needs_initialization,
+                is_pin_locked);
+        
+        if is_pin_locked {
+            println!("🔒 Device is initialized but locked with PIN - emitting unlock event");
+            
+            emitter.emit(DeviceEvent::PinUnlockNeeded {
+                device_id: device.unique_id.clone(),
+                features: features.clone(),
+                status: status.clone(),
+            }).await;
+        }
+        
+        // Emit appropriate status message based on what updates are needed
+        let status_message = if features.bootloader_mode {
+            if status.needs_bootloader_update {
+                "Device in bootloader mode - update needed"
+            } else {
+                "Device in bootloader mode - reboot needed"
+            }
+        } else if is_pin_locked {
+            "Device locked - enter PIN"
+        } else if status.needs_bootloader_update && status.needs_firmware_update && status.needs_initialization {
+            "Device needs updates"
+        } else if status.needs_bootloader_update {
+            "Bootloader update needed"
+        } else if status.needs_firmware_update {
+            "Firmware update needed"
+        } else if status.needs_initialization {
+            "Device setup needed"
+        } else {
+            "Device ready"
+        };
+        
+        emitter.status(status_message).await;
+    }
+    
+    emitter.set_state(&device.unique_id, crate::device::state::state_for_features(&features, &status)).await;
+    emit_needs_attention(emitter, &device.unique_id, &crate::device::attention::attention_reasons(&features, &status)).await;
+    if let Some(mismatch) = status.version_mismatch.clone() {
+        println!(
+            "⚠️ Firmware {} on {} needs bootloader {} but has {}",
+            mismatch.firmware, device.unique_id, mismatch.required_bootloader, mismatch.bootloader
+        );
+        emitter.emit(DeviceEvent::IncompatibleVersions { device_id: device.unique_id.clone(), mismatch }).await;
+    }
+    
+    // Emit device:features-updated event with evaluated status (for DeviceUpdateManager)
+    // This is a critical event that should be queued if frontend isn't ready
+    emitter.emit(DeviceEvent::FeaturesUpdated {
+        device_id: device.unique_id.clone(),
+        features,
+        status,  // Use evaluated status instead of hardcoded "ready"
+        force: false,
+    }).await;
+}
+
+/// Fetch a device's features in the background after `settle_delay`.
+///
+/// Attempts are recorded in the probe tracker: a failed probe is retried by the
+/// monitor with backoff, and once the retry policy gives up the frontend gets
+/// `device:probe-failed` so it can offer a transport reset.
+fn spawn_probe(app: &AppHandle, emitter: &EventEmitter, device: &FriendlyUsbDevice, settle_delay: Duration) {
+    crate::device::probe::with_tracker(|tracker| tracker.start(&device.unique_id, Instant::now().into_std()));
+    
+    let app = app.clone();
+    let emitter = emitter.clone();
+    let device = device.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(settle_delay).await;
+        println!("📡 Fetching device features for: {}", device.unique_id);
+        
+        emitter.status("Getting features...").await;
+        emitter.set_state(&device.unique_id, DeviceState::Probing).await;
+        
+        match try_get_device_features(&device, &app).await {
+            Ok(features) => {
+                crate::device::probe::with_tracker(|tracker| tracker.succeeded(&device.unique_id));
+                let hardware_id = features.device_id.clone();
+                handle_device_features(&emitter, &device, features).await;
+                crate::device::on_connect::run_pending(&app, &device, hardware_id.as_deref());
+            }
+            Err(e) => {
+                handle_device_features_error(&emitter, &device, e.to_string()).await;
+                handle_probe_failure(&app, &emitter, &device, e).await;
+            }
+        }
+        emitter.settle_initial_scan(&device.unique_id).await;
+    });
+}
+
+/// Schedule a retry for a failed probe, or report that the device was given up on
+async fn handle_probe_failure(app: &AppHandle, emitter: &EventEmitter, device: &FriendlyUsbDevice, e: ProbeError) {
+    use crate::device::probe::ProbeOutcome;
+    
+    // The USB handle may be wedged: stop the worker so the retry opens a fresh one
+    if e.is_transport_failure() {
+        if let Some(queue_manager) = app.try_state::<crate::commands::DeviceQueueManager>() {
+            println!("🔌 Replacing the worker of device {} after a transport failure", device.unique_id);
+            queue_manager.inner().clone().remove_and_shutdown(&device.unique_id).await;
+        }
+    }
+    let e = e.to_string();
+    // The PIN flow owns the device and fetches features itself
+    if e.contains("PIN flow") {
+        crate::device::probe::with_tracker(|tracker| tracker.succeeded(&device.unique_id));
+        return;
+    }
+    // Retrying won't help a DFU-mode device; device:recovery-needed was already emitted
+    if e.contains(DFU_MODE_ERROR) {
+        crate::device::probe::with_tracker(|tracker| tracker.give_up(&device.unique_id));
+        return;
+    }
+    
+    match crate::device::probe::with_tracker(|tracker| tracker.failed(&device.unique_id, Instant::now().into_std())) {
+        ProbeOutcome::Retry { attempts, after } => {
+            println!("⏳ Probe {} of device {} failed - retrying in {:?}", attempts, device.unique_id, after);
+        }
+        ProbeOutcome::GaveUp { attempts } => {
+            println!("🛑 Giving up on device {} after {} failed probes", device.unique_id, attempts);
+            emitter.status("Device not responding - try resetting the connection").await;
+            emitter.emit(DeviceEvent::ProbeFailed {
+                device_id: device.unique_id.clone(),
+                attempts,
+                error: e,
+            }).await;
+            emit_needs_attention(emitter, &device.unique_id, &[AttentionReason::Unreachable]).await;
+        }
+        ProbeOutcome::Untracked => {}
+    }
+}
+
+/// Make a reconnecting device active again if it was the active one last time
+/// (matched by serial) and nothing else has been selected since
+async fn restore_active_device(emitter: &EventEmitter, device: &FriendlyUsbDevice) {
+    if crate::device::active::active_device().is_some() {
+        return;
+    }
+    let serial = device.serial_number.as_deref().unwrap_or(&device.unique_id);
+    if crate::commands::last_active_device_serial().as_deref() != Some(serial) {
+        return;
+    }
+    if let Some(previous) = crate::device::active::select(&device.unique_id) {
+        println!("⭐ Restored active device {}", device.unique_id);
+        emitter.emit(DeviceEvent::ActiveChanged {
+            device_id: Some(device.unique_id.clone()),
+            previous,
+            reason: ActiveChangeReason::Restored,
+        }).await;
+    }
+}
+
+/// Emit `device:needs-attention` if the device's reasons changed since the last report
+async fn emit_needs_attention(emitter: &EventEmitter, device_id: &str, reasons: &[AttentionReason]) {
+    if let Some(reasons) = crate::device::attention::update(device_id, reasons) {
+        emitter.emit(DeviceEvent::NeedsAttention { device_id: device_id.to_string(), reasons }).await;
+    }
+}
+
+/// Report a failed feature fetch to the frontend in a form it can act on
+async fn handle_device_features_error(emitter: &EventEmitter, device: &FriendlyUsbDevice, e: String) {
+    println!("❌ Failed to get features for {}: {}", device.unique_id, e);
+    emitter.set_state(&device.unique_id, DeviceState::Error).await;
+    
+    // Device turned out to be in DFU mode during OOB detection
+    if e.contains(DFU_MODE_ERROR) {
+        emit_recovery_needed(emitter, device).await;
+    }
+    // Check for timeout errors specifically
+    else if e.contains("Timeout while fetching device features") {
+        println!("⏱️ Device timeout detected - device may be in invalid state");
+        println!("❌ OOPS this should never happen - device communication failed!");
+        
+        // Log detailed error for debugging
+        eprintln!("ERROR: Device timeout indicates invalid state - this should be prevented!");
+        eprintln!("Device ID: {}", device.unique_id);
+        eprintln!("Error: {}", e);
+        
+        // Emit device invalid state event for UI to handle
+        emitter.emit(DeviceEvent::InvalidState {
+            device_id: device.unique_id.clone(),
+            error: e,
+            error_type: "DEVICE_TIMEOUT".to_string(),
+        }).await;
+        
+        // Also emit status update
+        emitter.status("Device timeout - please reconnect").await;
+    }
+    // Check if this is a device access error
+    else if let Some(kind) = crate::device::access_error::classify(&e) {
+        
+        let user_friendly_error = if crate::instance_lock::is_secondary() {
+            crate::instance_lock::MANAGED_ELSEWHERE_MESSAGE.to_string()
+        } else if e.contains("🔒") || kind != crate::device::access_error::AccessErrorKind::DeviceClaimed {
+            e.clone()
+        } else {
+            format!(
+                "🔒 KeepKey Device Already In Use\n\n\
+                Your KeepKey device is currently being used by another application.\n\n\
+                Common causes:\n\
+                • KeepKey Desktop app is running\n\
+                • KeepKey Bridge is running\n\
+                • Another wallet application is connected\n\
+                • Previous connection wasn't properly closed\n\n\
+                Solutions:\n\
+                1. Close KeepKey Desktop app completely\n\
+                2. Close any other wallet applications\n\
+                3. Unplug and reconnect your KeepKey device\n\
+                4. Try again\n\n\
+                Technical details: {}", e
+            )
+        };
+        
+        // Emit device access error event
+        emitter.emit(DeviceEvent::AccessError {
+            device_id: device.unique_id.clone(),
+            error: user_friendly_error,
+            kind,
+        }).await;
+        
+        if kind == crate::device::access_error::AccessErrorKind::PermissionDenied {
+            if let Some(issue) = crate::device::udev::note_permission_denied(&device.unique_id) {
+                emitter.emit(DeviceEvent::PermissionIssue { issue }).await;
+            }
+        }
     }
 }
 
 /// Try to get device features without blocking the event loop
 /// Returns features if successful, error message if failed
 /// This function handles OOB bootloader detection by trying Initialize message when GetFeatures fails
-async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHandle) -> Result<keepkey_rust::features::DeviceFeatures, String> {
+async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHandle) -> Result<keepkey_rust::features::DeviceFeatures, ProbeError> {
     // Check if device is in PIN flow - if so, skip automatic feature fetching to avoid interference
     if crate::commands::is_device_in_pin_flow(&device.unique_id) {
-        return Err("Device is in PIN flow - skipping automatic feature fetch".to_string());
+        return Err(ProbeError::Failed("Device is in PIN flow - skipping automatic feature fetch".to_string()));
     }
     
     // Use the shared device queue manager to prevent race conditions
@@ -455,26 +954,11 @@ async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHan
         let queue_manager = queue_manager_state.inner().clone();
         
         // Get or create a single device queue handle for this device
-        let queue_handle = {
-            let mut manager = queue_manager.lock().await;
-            
-            if let Some(handle) = manager.get(&device.unique_id) {
-                // Use existing handle to prevent multiple workers
-                handle.clone()
-            } else {
-                // Create a new worker only if one doesn't exist
-                let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(
-                    device.unique_id.clone(),
-                    device.clone()
-                );
-                manager.insert(device.unique_id.clone(), handle.clone());
-                handle
-            }
-        };
+        let queue_handle = queue_manager.get_or_spawn(&device.unique_id, device).await;
         
         // Double-check PIN flow status before making the call (race condition protection)
         if crate::commands::is_device_in_pin_flow(&device.unique_id) {
-            return Err("Device entered PIN flow - aborting feature fetch".to_string());
+            return Err(ProbeError::Failed("Device entered PIN flow - aborting feature fetch".to_string()));
         }
         
         // Try to get features with retry logic for timeout resilience
@@ -484,17 +968,18 @@ async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHan
             
             // Check PIN flow status before each attempt
             if crate::commands::is_device_in_pin_flow(&device.unique_id) {
-                return Err("Device entered PIN flow during feature fetch".to_string());
+                return Err(ProbeError::Failed("Device entered PIN flow during feature fetch".to_string()));
             }
             
-            match tokio::time::timeout(Duration::from_secs(5), queue_handle.get_features()).await {
-                Ok(Ok(raw_features)) => {
+            match queue_handle.get_features().await {
+                Ok(raw_features) => {
                     println!("✅ Successfully got features for device {} on attempt {}", device.unique_id, attempt);
                     // Convert features to our DeviceFeatures format
                     let device_features = crate::commands::convert_features_to_device_features(raw_features);
+                    crate::device::oob_stats::with_stats(|stats| stats.record_primary_success(&device_features));
                     return Ok(device_features);
                 }
-                Ok(Err(e)) => {
+                Err(e) if !e.is::<QueueTimeout>() => {
                     let error_str = e.to_string();
                     
                     // Check if this looks like an OOB bootloader that doesn't understand GetFeatures
@@ -508,21 +993,31 @@ async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHan
                         match try_oob_bootloader_detection(device).await {
                             Ok(features) => {
                                 println!("✅ Successfully detected OOB bootloader mode for device {}", device.unique_id);
+                                crate::device::oob_stats::with_stats(|stats| stats.record_oob_success(&features));
                                 return Ok(features);
                             }
                             Err(oob_err) => {
                                 println!("❌ OOB bootloader detection also failed for {}: {}", device.unique_id, oob_err);
-                                last_error = Some(format!("Failed to get device features: {} (OOB attempt: {})", error_str, oob_err));
+                                crate::device::oob_stats::with_stats(|stats| stats.record_oob_failure());
+                                let error = format!("Failed to get device features: {} (OOB attempt: {})", error_str, oob_err);
+                                // No point in more attempts over the same handle
+                                if oob_err.is_transport_failure() {
+                                    return Err(ProbeError::Transport(error));
+                                }
+                                last_error = Some(error);
                             }
                         }
                     } else {
                         println!("⚠️ Failed to get features for device {} on attempt {}: {}", device.unique_id, attempt, error_str);
+                        crate::device::connection::record_operation_error(app_handle, &device.unique_id, &error_str);
                         last_error = Some(format!("Failed to get device features: {}", error_str));
                     }
                 }
                 Err(_) => {
                     println!("⏱️ Timeout getting features for device {} on attempt {}", device.unique_id, attempt);
-                    last_error = Some("Timeout while fetching device features".to_string());
+                    let error = "Timeout while fetching device features".to_string();
+                    crate::device::connection::record_operation_error(app_handle, &device.unique_id, &error);
+                    last_error = Some(error);
                 }
             }
             
@@ -536,34 +1031,37 @@ async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHan
         
         // All attempts failed
         match last_error {
-            Some(err) => Err(err),
-            None => Err(format!("All feature fetch attempts failed for device {}", device.unique_id))
+            Some(err) => Err(ProbeError::Failed(err)),
+            None => Err(ProbeError::Failed(format!("All feature fetch attempts failed for device {}", device.unique_id)))
         }
     } else {
-        // Fallback to the old method if queue manager is not available
-        println!("⚠️ DeviceQueueManager not available, using fallback method");
-        
-        // Check PIN flow status before fallback too
-        if crate::commands::is_device_in_pin_flow(&device.unique_id) {
-            return Err("Device is in PIN flow - skipping fallback feature fetch".to_string());
-        }
-        
-        // Create a temporary device queue to fetch features
-        // This is a non-blocking operation that will fail fast if device is busy
-        let queue_handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(
-            device.unique_id.clone(),
-            device.clone()
-        );
-        
-        // Try to get features with a timeout
-        match tokio::time::timeout(Duration::from_secs(30), queue_handle.get_features()).await {
-            Ok(Ok(raw_features)) => {
-                // Convert features to our DeviceFeatures format
-                let device_features = crate::commands::convert_features_to_device_features(raw_features);
-                Ok(device_features)
-            }
-            Ok(Err(e)) => Err(format!("Failed to get device features: {}", e)),
-            Err(_) => Err("Timeout while fetching device features".to_string()),
+        // spawn_event_controller refuses to start without the manager, so this is
+        // unreachable in the app; never spawn an untracked worker here, it would
+        // hold the USB handle with nothing to reap it
+        Err(ProbeError::Failed("DeviceQueueManager not initialized - cannot fetch device features".to_string()))
+    }
+}
+
+/// Why a probe got no features
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ProbeError {
+    /// The device couldn't be read (no answer, a timeout, in use elsewhere, ...)
+    Failed(String),
+    /// The USB stack is in an unknown state, e.g. OOB detection panicked in
+    /// rusb. The retry runs on a fresh worker.
+    Transport(String),
+}
+
+impl ProbeError {
+    fn is_transport_failure(&self) -> bool {
+        matches!(self, ProbeError::Transport(_))
+    }
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeError::Failed(e) | ProbeError::Transport(e) => write!(f, "{}", e),
         }
     }
 }
@@ -571,21 +1069,24 @@ async fn try_get_device_features(device: &FriendlyUsbDevice, app_handle: &AppHan
 /// Try to detect OOB bootloader mode using the proven keepkey-rust methods
 /// This handles the case where older bootloaders don't understand GetFeatures messages
 /// Uses the documented OOB detection heuristics from docs/usb/oob_mode_detection.md
-async fn try_oob_bootloader_detection(device: &FriendlyUsbDevice) -> Result<keepkey_rust::features::DeviceFeatures, String> {
+async fn try_oob_bootloader_detection(device: &FriendlyUsbDevice) -> Result<keepkey_rust::features::DeviceFeatures, OobDetectionError> {
+    // Raw DFU devices don't speak the protobuf protocol at all, Initialize included
+    if device.is_dfu_mode() {
+        println!("🚑 Device {} is in DFU mode - skipping OOB detection", device.unique_id);
+        return Err(OobDetectionError::Detection(format!("{} (VID: 0x{:04x}, PID: 0x{:04x})", DFU_MODE_ERROR, device.vid, device.pid)));
+    }
+    
     println!("🔧 Attempting OOB bootloader detection via HID for device {}", device.unique_id);
     
     // Use keepkey-rust's proven fallback method that handles OOB bootloaders correctly
-    let result = tokio::task::spawn_blocking({
+    let result = run_blocking_detection({
         let device = device.clone();
-        move || -> Result<keepkey_rust::features::DeviceFeatures, String> {
-            // Use the robust USB/HID fallback helper which includes retries and OOB heuristics
-            keepkey_rust::features::get_device_features_with_fallback(&device)
-                .map_err(|e| e.to_string())
-        }
+        // Use the robust USB/HID fallback helper which includes retries and OOB heuristics
+        move || keepkey_rust::features::get_device_features_with_fallback(&device).map_err(|e| e.to_string())
     }).await;
     
     match result {
-        Ok(Ok(features)) => {
+        Ok(features) => {
             // Apply OOB detection heuristics from docs/usb/oob_mode_detection.md
             let likely_oob_bootloader = 
                 features.bootloader_mode ||
@@ -603,13 +1104,199 @@ async fn try_oob_bootloader_detection(device: &FriendlyUsbDevice) -> Result<keep
             
             Ok(features)
         }
-        Ok(Err(e)) => Err(e),
-        Err(e) => Err(format!("Task execution error: {}", e)),
+        Err(e) => {
+            if e.is_transport_failure() {
+                eprintln!("❌ OOB detection for {} failed at the transport level: {}", device.unique_id, e);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Why blocking OOB detection produced no features
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OobDetectionError {
+    /// Detection ran and failed (no answer, unexpected response, ...)
+    Detection(String),
+    /// The blocking task panicked, e.g. inside rusb. The USB stack is in an
+    /// unknown state, so the probe fails with `ProbeError::Transport` and is
+    /// retried on a fresh worker.
+    TaskPanicked(String),
+    /// The task was cancelled before it finished (runtime shutting down)
+    TaskCancelled,
+}
+
+impl OobDetectionError {
+    fn is_transport_failure(&self) -> bool {
+        matches!(self, OobDetectionError::TaskPanicked(_))
+    }
+}
+
+impl std::fmt::Display for OobDetectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OobDetectionError::Detection(e) => write!(f, "{}", e),
+            OobDetectionError::TaskPanicked(payload) => write!(f, "Transport failure: OOB detection panicked: {}", payload),
+            OobDetectionError::TaskCancelled => write!(f, "OOB detection was cancelled"),
+        }
+    }
+}
+
+/// Run a blocking detection closure off the async runtime, keeping a panic in
+/// it apart from an ordinary detection failure
+async fn run_blocking_detection<F>(detect: F) -> Result<keepkey_rust::features::DeviceFeatures, OobDetectionError>
+where
+    F: FnOnce() -> Result<keepkey_rust::features::DeviceFeatures, String> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(detect).await {
+        Ok(result) => result.map_err(OobDetectionError::Detection),
+        Err(e) if e.is_panic() => {
+            let payload = panic_message(e.into_panic());
+            eprintln!("💥 OOB detection task panicked: {}", payload);
+            Err(OobDetectionError::TaskPanicked(payload))
+        }
+        Err(_) => Err(OobDetectionError::TaskCancelled),
+    }
+}
+
+/// Error reported for devices that came back in raw DFU mode
+const DFU_MODE_ERROR: &str = "Device is in DFU mode";
+
+/// Tell the frontend a device needs a DFU recovery flash before it can be used
+async fn emit_recovery_needed(emitter: &EventEmitter, device: &FriendlyUsbDevice) {
+    emitter.emit(DeviceEvent::RecoveryNeeded {
+        device: device.clone(),
+        reason: "dfu_mode".to_string(),
+        instructions: vec![
+            "Your KeepKey was left in recovery (DFU) mode, most likely by an interrupted update.".to_string(),
+            "Keep the device plugged in and do not press any buttons.".to_string(),
+            "Select a recovery image and start recovery - this rewrites the bootloader.".to_string(),
+            "When recovery finishes, unplug and reconnect your KeepKey, then reinstall firmware if prompted.".to_string(),
+        ],
+    }).await;
+    emitter.status("Device in recovery mode - recovery needed").await;
+}
+
+/// How long shutdown waits for the monitor task before draining workers anyway
+const MONITOR_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long shutdown waits for device workers to stop
+const QUEUE_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Stop device monitoring and all device workers on app exit.
+///
+/// The order matters and must be kept:
+/// 1. cancel the monitor, so it spawns no further workers or disconnect cleanups
+/// 2. await the monitor task (bounded by `MONITOR_STOP_TIMEOUT`)
+/// 3. drain the `DeviceQueueManager`
+///
+/// Disconnect cleanups that are still in flight at step 3 are safe: neither they
+/// nor `shutdown_all` hold the manager lock while awaiting a worker, and each
+/// handle is removed exactly once, so whoever removes it stops it.
+pub async fn shutdown_device_services(app: &AppHandle) {
+    println!("🛑 Shutting down device services...");
+    
+    let monitor = app
+        .try_state::<Arc<Mutex<EventController>>>()
+        .and_then(|controller| lock_controller(&controller).stop_for_shutdown());
+    let queue_manager = app
+        .try_state::<crate::commands::DeviceQueueManager>()
+        .map(|state| state.inner().clone());
+    
+    shutdown_in_order(monitor, queue_manager).await;
+}
+
+async fn shutdown_in_order(
+    monitor: Option<tauri::async_runtime::JoinHandle<()>>,
+    queue_manager: Option<crate::commands::DeviceQueueManager>,
+) {
+    if let Some(monitor) = monitor {
+        match tokio::time::timeout(MONITOR_STOP_TIMEOUT, monitor).await {
+            Ok(_) => println!("✅ Event controller task stopped"),
+            Err(_) => println!("⚠️ Event controller task did not stop within {:?}, continuing shutdown", MONITOR_STOP_TIMEOUT),
+        }
+    }
+    
+    if let Some(queue_manager) = queue_manager {
+        match tokio::time::timeout(QUEUE_DRAIN_TIMEOUT, queue_manager.shutdown_all()).await {
+            Ok(count) => println!("✅ Stopped {} device worker(s)", count),
+            Err(_) => println!("⚠️ Device workers did not stop within {:?}", QUEUE_DRAIN_TIMEOUT),
+        }
+    }
+}
+
+/// Cancel the prompts devices are showing for the window that just closed, so
+/// nobody is left with a device waiting on a button press. Events aren't
+/// scoped to windows, so every device's prompt is cancelled.
+pub async fn cancel_prompts_on_window_close(app: &AppHandle) {
+    if !crate::commands::cancel_on_window_close_enabled() {
+        return;
+    }
+    let Some(queue_manager) = app.try_state::<crate::commands::DeviceQueueManager>() else {
+        return;
+    };
+    let handles: Vec<_> = queue_manager.lock().await.values().cloned().collect();
+    for handle in handles {
+        if handle.cancel_prompt().await {
+            println!("🚫 Cancelled prompt on {} after the window closed", handle.device_id());
+        }
     }
 }
 
-// Create and manage event controller with proper Arc<Mutex<>> wrapper
-pub fn spawn_event_controller(app: &AppHandle) -> Arc<Mutex<EventController>> {
+/// Minimum time between a scan and an on-demand rescan; requests inside it are
+/// dropped, since the devices were just scanned
+const RESCAN_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Scan for connected/disconnected devices now rather than at the next poll
+#[tauri::command]
+pub async fn rescan_devices(app: AppHandle) -> Result<(), String> {
+    let controller = app
+        .try_state::<Arc<Mutex<EventController>>>()
+        .ok_or("Device monitor is not running")?;
+    let requested = lock_controller(&controller).request_rescan();
+    if requested {
+        Ok(())
+    } else {
+        Err("Device monitor is not running".to_string())
+    }
+}
+
+/// Set the monitor's power mode; the frontend calls this when the battery runs
+/// low or the laptop is plugged back in
+#[tauri::command]
+pub async fn set_power_mode(mode: PowerMode, app: AppHandle) -> Result<(), String> {
+    let controller = app
+        .try_state::<Arc<Mutex<EventController>>>()
+        .ok_or("Device monitor is not running")?;
+    let changed = lock_controller(&controller).set_power_mode(mode);
+    if changed {
+        println!("🔋 Power mode set to {:?}", mode);
+    }
+    Ok(())
+}
+
+/// Whether the device monitor is in low-power mode
+pub fn is_low_power(app: &AppHandle) -> bool {
+    let Some(controller) = app.try_state::<Arc<Mutex<EventController>>>() else {
+        return false;
+    };
+    let mode = lock_controller(&controller).power_mode();
+    mode == PowerMode::LowPower
+}
+
+/// Lock the controller held in app state. A panic while it was locked doesn't
+/// stop the monitor task, so the controller is recovered rather than left
+/// unusable for stopping and rescanning.
+pub fn lock_controller(controller: &Mutex<EventController>) -> std::sync::MutexGuard<'_, EventController> {
+    crate::commands::lock_or_recover(controller, "event controller")
+}
+
+/// Start the device monitor. The `DeviceQueueManager` must already be in app
+/// state: every worker the monitor spawns is tracked there so it can be reaped.
+pub fn spawn_event_controller(app: &AppHandle) -> Result<Arc<Mutex<EventController>>, String> {
+    if app.try_state::<crate::commands::DeviceQueueManager>().is_none() {
+        return Err("DeviceQueueManager must be managed before the event controller starts".to_string());
+    }
+    
     let mut controller = EventController::new();
     controller.start(app);
     
@@ -618,5 +1305,542 @@ pub fn spawn_event_controller(app: &AppHandle) -> Arc<Mutex<EventController>> {
     // Store the controller in app state so it can be properly cleaned up
     app.manage(controller_arc.clone());
     
-    controller_arc
+    Ok(controller_arc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepkey_rust::device_queue::{DeviceCmd, DeviceQueueHandle};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A worker that only answers Shutdown, slowly, and counts how often it was stopped
+    fn fake_worker(device_id: String, stops: Arc<AtomicUsize>) -> DeviceQueueHandle {
+        let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                if let DeviceCmd::Shutdown { respond_to } = cmd {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    stops.fetch_add(1, Ordering::SeqCst);
+                    let _ = respond_to.send(Ok(()));
+                    break;
+                }
+            }
+        });
+        DeviceQueueHandle::new(device_id, cmd_tx)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_shutdown_races_disconnect_cleanups() {
+        for _ in 0..20 {
+            let stops = Arc::new(AtomicUsize::new(0));
+            let queue_manager: crate::commands::DeviceQueueManager = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+            {
+                let mut manager = queue_manager.lock().await;
+                for i in 0..16 {
+                    let id = format!("device-{}", i);
+                    manager.insert(id.clone(), fake_worker(id, stops.clone()));
+                }
+            }
+
+            // A monitor that is mid-tick when it gets cancelled
+            let token = CancellationToken::new();
+            let monitor_token = token.clone();
+            let monitor = tauri::async_runtime::spawn(async move {
+                monitor_token.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            });
+
+            // Disconnect cleanups for half the devices, racing the shutdown
+            let cleanups: Vec<_> = (0..8)
+                .map(|i| {
+                    let queue_manager = queue_manager.clone();
+                    tokio::spawn(async move { queue_manager.remove_and_shutdown(&format!("device-{}", i)).await })
+                })
+                .collect();
+
+            token.cancel();
+            tokio::time::timeout(Duration::from_secs(5), shutdown_in_order(Some(monitor), Some(queue_manager.clone())))
+                .await
+                .expect("shutdown deadlocked");
+            for cleanup in cleanups {
+                tokio::time::timeout(Duration::from_secs(5), cleanup)
+                    .await
+                    .expect("disconnect cleanup deadlocked")
+                    .unwrap();
+            }
+
+            // Every worker stopped exactly once and nothing is left registered
+            assert_eq!(stops.load(Ordering::SeqCst), 16);
+            assert!(queue_manager.lock().await.is_empty());
+        }
+    }
+
+    /// A worker that answers every request after `delay`, like a device waiting on
+    /// its button or a slow USB transfer
+    fn slow_worker(device_id: String, delay: Duration) -> DeviceQueueHandle {
+        let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel(8);
+        tokio::spawn(async move {
+            while let Some(cmd) = cmd_rx.recv().await {
+                tokio::time::sleep(delay).await;
+                match cmd {
+                    DeviceCmd::GetFeatures { respond_to, .. } => {
+                        let _ = respond_to.send(Ok(keepkey_rust::messages::Features::default()));
+                    }
+                    DeviceCmd::SendRaw { respond_to, .. } => {
+                        let _ = respond_to.send(Ok(keepkey_rust::messages::Success::default().into()));
+                    }
+                    DeviceCmd::Shutdown { respond_to } => {
+                        let _ = respond_to.send(Ok(()));
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        });
+        DeviceQueueHandle::new(device_id, cmd_tx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_device_does_not_block_other_devices() {
+        let queue_manager: crate::commands::DeviceQueueManager = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+        {
+            let mut manager = queue_manager.lock().await;
+            manager.insert("slow".to_string(), slow_worker("slow".to_string(), Duration::from_millis(500)));
+            manager.insert("fast".to_string(), slow_worker("fast".to_string(), Duration::ZERO));
+        }
+
+        // Start a long operation on the slow device first
+        let slow = {
+            let queue_manager = queue_manager.clone();
+            tokio::spawn(async move {
+                let handle = queue_manager.get_or_spawn_by_id("slow").await.unwrap();
+                handle.send_raw(keepkey_rust::messages::Ping::default().into(), true).await.unwrap();
+                Instant::now()
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let started = Instant::now();
+        let handle = queue_manager.get_or_spawn_by_id("fast").await.unwrap();
+        handle.get_features().await.unwrap();
+        let fast_done = Instant::now();
+        // On the paused clock only the slow worker's delay moves time forward
+        assert!(fast_done - started < Duration::from_millis(480), "fast device waited on the slow one");
+
+        let slow_done = tokio::time::timeout(Duration::from_secs(5), slow).await.unwrap().unwrap();
+        assert!(fast_done < slow_done, "fast device finished after the slow one");
+
+        queue_manager.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_reap_idle_skips_workers_in_use() {
+        let queue_manager: crate::commands::DeviceQueueManager = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+        {
+            let mut manager = queue_manager.lock().await;
+            for id in ["reap-idle", "reap-busy", "reap-recent"] {
+                manager.insert(id.to_string(), slow_worker(id.to_string(), Duration::ZERO));
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // A caller holding a handle keeps its worker alive however long it's idle
+        let busy = queue_manager.lock().await.get("reap-busy").cloned().unwrap();
+        let recent = queue_manager.lock().await.get("reap-recent").cloned().unwrap();
+        recent.get_features().await.unwrap();
+        drop(recent);
+
+        assert_eq!(queue_manager.reap_idle(Duration::from_millis(40)).await, vec!["reap-idle".to_string()]);
+        let manager = queue_manager.lock().await;
+        assert!(!manager.contains_key("reap-idle"));
+        assert!(manager.contains_key("reap-busy"));
+        assert!(manager.contains_key("reap-recent"));
+        drop(manager);
+        busy.get_features().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oob_detection_panic_is_a_transport_failure() {
+        let err = run_blocking_detection(|| panic!("rusb: device handle poisoned")).await.unwrap_err();
+        assert_eq!(err, OobDetectionError::TaskPanicked("rusb: device handle poisoned".to_string()));
+        assert!(err.is_transport_failure());
+
+        let err = run_blocking_detection(|| Err("No response to Initialize".to_string())).await.unwrap_err();
+        assert_eq!(err, OobDetectionError::Detection("No response to Initialize".to_string()));
+        assert!(!err.is_transport_failure());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_delayed_scanning_status_on_paused_clock() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let config = EventControllerConfig { scanning_jitter: 0.0, ..Default::default() };
+
+        let first = tx.clone();
+        spawn_after(config.scanning_delay, async move { first.send("scanning").unwrap() });
+        tokio::time::advance(config.scanning_delay - Duration::from_millis(1)).await;
+        tokio::task::yield_now().await;
+        assert!(rx.try_recv().is_err(), "fired before its delay");
+        tokio::time::advance(Duration::from_millis(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(rx.try_recv(), Ok("scanning"));
+
+        // A device reconnecting before the delay cancels the pending emit
+        let second = tx.clone();
+        let pending = spawn_after(config.scanning_delay, async move { second.send("stale").unwrap() });
+        tokio::time::advance(config.scanning_delay / 2).await;
+        pending.abort();
+        tokio::time::advance(config.scanning_delay).await;
+        tokio::task::yield_now().await;
+        assert!(rx.try_recv().is_err(), "aborted emit still fired");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rescan_debounce_on_paused_clock() {
+        let last_scan = Instant::now();
+        assert!(rescan_debounced(last_scan));
+        tokio::time::advance(RESCAN_DEBOUNCE - Duration::from_millis(1)).await;
+        assert!(rescan_debounced(last_scan));
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(!rescan_debounced(last_scan));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_poll_timer_does_not_burst() {
+        let period = Duration::from_millis(100);
+        let mut timer = poll_timer(Instant::now(), period);
+        timer.tick().await;
+
+        // The loop stalls for ten periods, e.g. while the system sleeps
+        tokio::time::advance(period * 10).await;
+        let resumed = Instant::now();
+        timer.tick().await;
+        assert_eq!(Instant::now(), resumed, "the stall is followed by one scan right away");
+
+        // ...and not by the nine missed ones: the next scan is a full period later
+        assert!(tokio::time::timeout(period / 2, timer.tick()).await.is_err(), "missed ticks fired in a burst");
+        timer.tick().await;
+        assert_eq!(Instant::now(), resumed + period);
+        timer.tick().await;
+        assert_eq!(Instant::now(), resumed + period * 2);
+    }
+
+    const MONITOR_TEST_DEVICE: &str = "monitor-test-kk";
+
+    /// Let the monitor task run until it waits on the clock again
+    async fn let_monitor_run() {
+        for _ in 0..20 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// The monitor's emits since the last call, as "status: ..." and "connected"/"disconnected"
+    fn monitor_emits(emitted: &mut tokio::sync::mpsc::UnboundedReceiver<crate::events::EmitSpec>) -> Vec<String> {
+        let mut emits = Vec::new();
+        while let Ok(spec) = emitted.try_recv() {
+            match spec.event.as_str() {
+                "status:update" => emits.push(format!("status: {}", spec.payload["status"].as_str().unwrap_or_default())),
+                "device:connected" if spec.payload["unique_id"] == MONITOR_TEST_DEVICE => emits.push("connected".to_string()),
+                "device:disconnected" if spec.payload == MONITOR_TEST_DEVICE => emits.push("disconnected".to_string()),
+                _ => {}
+            }
+        }
+        emits
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_monitor_scans_and_reports_on_paused_clock() {
+        let config = EventControllerConfig { scanning_jitter: 0.0, ..Default::default() };
+        let period = config.poll_interval;
+        // The scripted device list: counts its scans, the device shows up while plugged
+        let scan_count = Arc::new(AtomicUsize::new(0));
+        let plugged = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let list_devices = {
+            let (scan_count, plugged) = (scan_count.clone(), plugged.clone());
+            move || {
+                scan_count.fetch_add(1, Ordering::SeqCst);
+                if plugged.load(Ordering::SeqCst) {
+                    vec![FriendlyUsbDevice::new(MONITOR_TEST_DEVICE.to_string(), 0x2b24, 0x0002, None, None, None)]
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+        let scans = || scan_count.load(Ordering::SeqCst);
+        let plug = |state: bool| plugged.store(state, Ordering::SeqCst);
+        let (rescan_tx, rescan_rx) = tokio::sync::mpsc::channel(1);
+        let (_power_tx, power_rx) = tokio::sync::watch::channel(PowerMode::Normal);
+        let state = Arc::new(tokio::sync::Mutex::new(MonitorState {
+            rescan_rx,
+            power_rx,
+            last_devices: Vec::new(),
+            first_scan: true,
+        }));
+        let host = MonitorHost { app: None, list_devices: Arc::new(list_devices) };
+        let (emitter, mut emitted) = EventEmitter::detached(crate::events::default_shared_transformer());
+        let token = CancellationToken::new();
+        let monitor = tokio::spawn(run_monitor(host, emitter, token.clone(), config, state));
+
+        // Nothing before the frontend had time to listen
+        let_monitor_run().await;
+        tokio::time::advance(config.startup_status_delay - Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 0);
+        assert!(monitor_emits(&mut emitted).is_empty());
+
+        // Then the scanning status and the first scan
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 1);
+        assert_eq!(monitor_emits(&mut emitted), vec!["status: Scanning for devices..."]);
+
+        // One scan per poll; a device plugged in between is reported by the next
+        plug(true);
+        tokio::time::advance(period - Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 1);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 2);
+        assert_eq!(monitor_emits(&mut emitted), vec!["status: Device found -test-kk", "connected"]);
+
+        // A rescan right after a scan is debounced...
+        rescan_tx.try_send(()).unwrap();
+        let_monitor_run().await;
+        assert_eq!(scans(), 2);
+
+        // ...one after the debounce scans now and pushes the next poll back a full period
+        tokio::time::advance(RESCAN_DEBOUNCE).await;
+        rescan_tx.try_send(()).unwrap();
+        let_monitor_run().await;
+        assert_eq!(scans(), 3);
+        assert!(monitor_emits(&mut emitted).is_empty(), "a known device was reported again");
+        tokio::time::advance(period - Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 3);
+
+        // Unplugged: disconnected right away, back to scanning after the delay
+        plug(false);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 4);
+        assert_eq!(monitor_emits(&mut emitted), vec!["status: Device disconnected", "disconnected"]);
+        tokio::time::advance(config.scanning_delay).await;
+        let_monitor_run().await;
+        assert_eq!(scans(), 5);
+        assert_eq!(monitor_emits(&mut emitted), vec!["status: Scanning for devices..."]);
+
+        // Cancelling stops the run without a reason to report
+        token.cancel();
+        assert_eq!(monitor.await.unwrap(), None);
+        crate::device::state::forget(MONITOR_TEST_DEVICE);
+    }
+
+    #[test]
+    fn test_scanning_delay_jitter() {
+        let config = EventControllerConfig::default();
+        assert_eq!(config.jittered_scanning_delay(0.0), Duration::from_millis(800));
+        assert_eq!(config.jittered_scanning_delay(0.5), Duration::from_millis(1000));
+        for _ in 0..100 {
+            let delay = config.jittered_scanning_delay(random_unit());
+            assert!(delay >= Duration::from_millis(800) && delay < Duration::from_millis(1200), "{:?}", delay);
+        }
+
+        let fixed = EventControllerConfig { scanning_delay: Duration::from_millis(250), scanning_jitter: 0.0, ..Default::default() };
+        assert_eq!(fixed.jittered_scanning_delay(0.9), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_short_device_id_is_char_safe() {
+        assert_eq!(short_device_id("343737340F4736331F003B00"), "1F003B00");
+        assert_eq!(short_device_id("KK12"), "KK12");
+        // Multibyte serials are cut on char boundaries instead of panicking
+        assert_eq!(short_device_id("serial-ÄÖÜäöüß€"), "ÄÖÜäöüß€");
+        assert_eq!(short_device_id("🔑🔑🔑🔑🔑🔑🔑🔑🔑"), "🔑🔑🔑🔑🔑🔑🔑🔑");
+        // and cleaned like other device strings
+        assert_eq!(short_device_id("ab\u{1b}[2Jcd\n"), "b[2Jcd");
+    }
+
+    #[test]
+    fn test_rescan_requests_coalesce() {
+        let mut controller = EventController::new();
+        assert!(!controller.request_rescan(), "no rescan without a running monitor");
+
+        let (rescan_tx, mut rescan_rx) = tokio::sync::mpsc::channel(1);
+        controller.rescan_tx = Some(rescan_tx);
+        controller.is_running = true;
+
+        // A burst of requests queues a single scan
+        for _ in 0..10 {
+            assert!(controller.request_rescan());
+        }
+        assert!(rescan_rx.try_recv().is_ok());
+        assert!(rescan_rx.try_recv().is_err());
+
+        drop(rescan_rx);
+        assert!(!controller.request_rescan());
+        // Nothing to stop: keep Drop from spawning on a missing runtime
+        controller.is_running = false;
+    }
+
+    #[test]
+    fn test_poisoned_controller_lock_recovers() {
+        let controller = Arc::new(Mutex::new(EventController::new()));
+        let poisoner = controller.clone();
+        let panicked = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("panic while holding the controller");
+        })
+        .join();
+        assert!(panicked.is_err());
+        assert!(controller.is_poisoned());
+
+        assert!(lock_controller(&controller).set_power_mode(PowerMode::LowPower));
+        assert_eq!(lock_controller(&controller).power_mode(), PowerMode::LowPower);
+        // Recovered for good: plain lock() works again
+        assert!(!controller.is_poisoned());
+        assert!(controller.lock().is_ok());
+    }
+
+    #[test]
+    fn test_stop_reasons_are_recorded() {
+        let recorded = |controller: &EventController| recorded_reason(&controller.stop_reason);
+        
+        let mut controller = EventController::new();
+        controller.is_running = true;
+        controller.stop(StopReason::Requested);
+        assert_eq!(recorded(&controller), StopReason::Requested);
+        assert!(controller.cancellation_token.is_cancelled());
+        
+        let mut controller = EventController::new();
+        controller.is_running = true;
+        assert!(controller.stop_for_shutdown().is_none());
+        assert_eq!(recorded(&controller), StopReason::AppExit);
+        
+        // Dropping a running controller stops it implicitly
+        let mut controller = EventController::new();
+        controller.is_running = true;
+        let stop_reason = controller.stop_reason.clone();
+        drop(controller);
+        assert_eq!(recorded_reason(&stop_reason), StopReason::Dropped);
+        
+        // Stopping a stopped controller doesn't overwrite why it stopped
+        let mut controller = EventController::new();
+        controller.is_running = true;
+        controller.stop(StopReason::Requested);
+        let stop_reason = controller.stop_reason.clone();
+        drop(controller);
+        assert_eq!(recorded_reason(&stop_reason), StopReason::Requested);
+    }
+    
+    /// Runs for `supervise` that follow `script`, one entry per run: `Err` panics,
+    /// `Ok(None)` records the given reason as a stop request and cancels
+    fn scripted_runs(
+        script: Vec<Result<Option<StopReason>, StopReason>>,
+        token: CancellationToken,
+        stop_reason: Arc<Mutex<Option<StopReason>>>,
+    ) -> impl FnMut() -> tokio::task::JoinHandle<Option<StopReason>> {
+        let mut script = script.into_iter();
+        move || {
+            let step = script.next().expect("monitor restarted more often than scripted");
+            let (token, stop_reason) = (token.clone(), stop_reason.clone());
+            tokio::spawn(async move {
+                match step {
+                    Ok(Some(reason)) => Some(reason),
+                    Ok(None) => panic!("monitor bug"),
+                    Err(requested) => {
+                        *stop_reason.lock().unwrap() = Some(requested);
+                        token.cancel();
+                        None
+                    }
+                }
+            })
+        }
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_restart_the_monitor() {
+        let token = CancellationToken::new();
+        let stop_reason = Arc::new(Mutex::new(None));
+        let enumeration = StopReason::EnumerationFailed { message: "LIBUSB_ERROR_IO".to_string() };
+        let script = vec![Ok(None), Ok(Some(enumeration.clone())), Err(StopReason::Requested)];
+        let mut stops = Vec::new();
+        let last = supervise(
+            scripted_runs(script, token.clone(), stop_reason.clone()),
+            token,
+            stop_reason,
+            |reason, restarting| stops.push((reason.clone(), restarting)),
+        )
+        .await;
+        
+        assert_eq!(last, StopReason::Requested);
+        assert_eq!(
+            stops,
+            vec![
+                (StopReason::Panic { message: "monitor bug".to_string() }, true),
+                (enumeration, true),
+                (StopReason::Requested, false),
+            ]
+        );
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_deliberate_stops_do_not_restart() {
+        for reason in [StopReason::Requested, StopReason::AppExit, StopReason::Dropped] {
+            let token = CancellationToken::new();
+            let stop_reason = Arc::new(Mutex::new(None));
+            let mut stops = Vec::new();
+            supervise(
+                scripted_runs(vec![Err(reason.clone())], token.clone(), stop_reason.clone()),
+                token,
+                stop_reason,
+                |reason, restarting| stops.push((reason.clone(), restarting)),
+            )
+            .await;
+            assert_eq!(stops, vec![(reason, false)]);
+        }
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_restarts_are_capped() {
+        let token = CancellationToken::new();
+        let stop_reason = Arc::new(Mutex::new(None));
+        let script = (0..=MAX_MONITOR_RESTARTS).map(|_| Ok(None)).collect();
+        let mut stops = Vec::new();
+        let last = supervise(
+            scripted_runs(script, token.clone(), stop_reason.clone()),
+            token,
+            stop_reason,
+            |_, restarting| stops.push(restarting),
+        )
+        .await;
+        
+        assert!(matches!(last, StopReason::Panic { .. }));
+        assert_eq!(stops.len() as u32, MAX_MONITOR_RESTARTS + 1);
+        assert_eq!(stops.iter().filter(|restarting| **restarting).count() as u32, MAX_MONITOR_RESTARTS);
+        assert!(!stops[stops.len() - 1]);
+        
+        let json = monitor_stopped_payload(&last, false);
+        assert_eq!(json["reason"]["kind"], "panic");
+        assert_eq!(json["reason"]["message"], "monitor bug");
+        assert_eq!(json["restarting"], false);
+        assert_eq!(monitor_stopped_payload(&StopReason::AppExit, false)["reason"], serde_json::json!({ "kind": "app_exit" }));
+    }
+    
+    #[test]
+    fn test_power_mode_switches_poll_interval() {
+        let controller = EventController::new();
+        let mut power_rx = controller.power_tx.subscribe();
+        assert_eq!(controller.power_mode(), PowerMode::Normal);
+        // Re-setting the current mode doesn't wake the monitor
+        assert!(!controller.set_power_mode(PowerMode::Normal));
+        assert!(!power_rx.has_changed().unwrap());
+
+        assert!(controller.set_power_mode(PowerMode::LowPower));
+        assert!(power_rx.has_changed().unwrap());
+        assert_eq!(controller.config.poll_interval_for(*power_rx.borrow_and_update()), Duration::from_secs(15));
+        assert!(controller.set_power_mode(PowerMode::Normal));
+        assert_eq!(controller.config.poll_interval_for(*power_rx.borrow_and_update()), Duration::from_millis(1000));
+    }
 }
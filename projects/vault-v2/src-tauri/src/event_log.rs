@@ -0,0 +1,175 @@
+//! Always-on memory of the device events the monitor emitted, for "my device
+//! didn't show up" reports: the exact connect/probe/disconnect sequence, with
+//! payload summaries, suppressed events and failed emits.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 500;
+
+/// Payload summaries are cut to this many characters
+const SUMMARY_MAX_LEN: usize = 200;
+/// String values in a summary are cut to this many characters
+const SUMMARY_VALUE_MAX_LEN: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum EmitOutcome {
+    Emitted,
+    /// The event transformer dropped it
+    Suppressed,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub event: String,
+    pub device_id: Option<String>,
+    pub sequence: Option<u64>,
+    pub summary: String,
+    pub outcome: EmitOutcome,
+}
+
+/// Ring buffer of the most recent entries
+#[derive(Debug)]
+pub struct EventLog {
+    entries: VecDeque<EventLogEntry>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: VecDeque::new(), capacity: capacity.max(1) }
+    }
+
+    pub fn push(&mut self, entry: EventLogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Change the capacity, dropping the oldest entries if it shrinks
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The last `limit` entries (all when `None`), oldest first
+    pub fn recent(&self, limit: Option<usize>) -> Vec<EventLogEntry> {
+        let skip = limit.map_or(0, |limit| self.entries.len().saturating_sub(limit));
+        self.entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+static EVENT_LOG: once_cell::sync::Lazy<std::sync::Mutex<EventLog>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(EventLog::new(DEFAULT_EVENT_LOG_CAPACITY)));
+
+pub fn with_log<T>(f: impl FnOnce(&mut EventLog) -> T) -> T {
+    match EVENT_LOG.lock() {
+        Ok(mut log) => f(&mut log),
+        Err(poisoned) => f(&mut poisoned.into_inner()),
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((cut, _)) => format!("{}…", &s[..cut]),
+        None => s.to_string(),
+    }
+}
+
+/// One line describing a payload: scalar fields with their values, nested
+/// ones by size
+pub fn summarize(payload: &serde_json::Value) -> String {
+    use serde_json::Value;
+
+    let describe = |value: &Value| match value {
+        Value::String(s) => format!("{:?}", truncate(s, SUMMARY_VALUE_MAX_LEN)),
+        Value::Array(items) => format!("[{}]", items.len()),
+        Value::Object(fields) => format!("{{{}}}", fields.len()),
+        other => other.to_string(),
+    };
+    let summary = match payload {
+        Value::Object(fields) => fields
+            .iter()
+            .filter(|(key, _)| key.as_str() != "sequence")
+            .map(|(key, value)| format!("{}={}", key, describe(value)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => describe(other),
+    };
+    truncate(&summary, SUMMARY_MAX_LEN)
+}
+
+pub fn record(event: &str, device_id: Option<&str>, sequence: Option<u64>, summary: String, outcome: EmitOutcome) {
+    let entry = EventLogEntry {
+        timestamp: chrono::Utc::now(),
+        event: event.to_string(),
+        device_id: device_id.map(str::to_string),
+        sequence,
+        summary,
+        outcome,
+    };
+    with_log(|log| log.push(entry));
+}
+
+/// The most recent device events, oldest first
+#[tauri::command]
+pub async fn get_recent_events(limit: Option<usize>) -> Result<Vec<EventLogEntry>, String> {
+    Ok(with_log(|log| log.recent(limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(event: &str) -> EventLogEntry {
+        EventLogEntry {
+            timestamp: chrono::Utc::now(),
+            event: event.to_string(),
+            device_id: None,
+            sequence: None,
+            summary: String::new(),
+            outcome: EmitOutcome::Emitted,
+        }
+    }
+
+    fn names(entries: &[EventLogEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.event.as_str()).collect()
+    }
+
+    #[test]
+    fn test_event_log_is_bounded() {
+        let mut log = EventLog::new(3);
+        for event in ["a", "b", "c", "d"] {
+            log.push(entry(event));
+        }
+        assert_eq!(names(&log.recent(None)), ["b", "c", "d"]);
+        assert_eq!(names(&log.recent(Some(2))), ["c", "d"]);
+
+        log.set_capacity(1);
+        assert_eq!(names(&log.recent(None)), ["d"]);
+    }
+
+    #[test]
+    fn test_summarize_payload() {
+        let payload = serde_json::json!({
+            "deviceId": "343737340F4736331F003B00",
+            "features": { "label": "KeepKey", "version": "7.10.0" },
+            "initialized": true,
+            "reasons": ["locked"],
+            "sequence": 7
+        });
+        assert_eq!(
+            summarize(&payload),
+            "deviceId=\"343737340F4736331F003B00\", features={2}, initialized=true, reasons=[1]"
+        );
+        assert_eq!(summarize(&serde_json::json!("bare-id")), "\"bare-id\"");
+        assert!(summarize(&serde_json::json!({ "error": "x".repeat(500) })).chars().count() <= SUMMARY_MAX_LEN + 1);
+    }
+}
@@ -0,0 +1,872 @@
+use keepkey_rust::features::DeviceFeatures;
+use keepkey_rust::friendly_usb::FriendlyUsbDevice;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::DeviceStatus;
+use crate::device::access_error::AccessErrorKind;
+use crate::device::active::ActiveChangeReason;
+use crate::device::attention::AttentionReason;
+use crate::device::model::VersionMismatch;
+use crate::device::state::{DeviceState, StateChange};
+use crate::device::udev::PermissionIssue;
+use crate::event_log::EmitOutcome;
+
+/// Everything the device monitor reports to the frontend.
+///
+/// The monitor only decides *what* happened; the event name and payload are
+/// produced by the `EventTransformer`, so integrators can rename, reshape or
+/// drop events without touching the detection logic.
+#[derive(Debug, Clone)]
+pub enum DeviceEvent {
+    /// Human-readable status line for the splash screen / status bar
+    StatusUpdate { status: String },
+    Connected { device: FriendlyUsbDevice },
+    Disconnected { device_id: String },
+    /// A device in the recovery flow came back with a different ID
+    RecoveryReconnected { new_id: String, original_id: String },
+    /// The device can't be used until it's recovered (e.g. stuck in DFU mode)
+    RecoveryNeeded { device: FriendlyUsbDevice, reason: String, instructions: Vec<String> },
+    /// `time_to_ready_ms` is set on the first ready since the device connected
+    Ready { device: FriendlyUsbDevice, features: DeviceFeatures, time_to_ready_ms: Option<u64> },
+    PinUnlockNeeded { device_id: String, features: DeviceFeatures, status: DeviceStatus },
+    /// Skipped when identical to the device's last one, unless `force` is set
+    FeaturesUpdated { device_id: String, features: DeviceFeatures, status: DeviceStatus, force: bool },
+    InvalidState { device_id: String, error: String, error_type: String },
+    AccessError { device_id: String, error: String, kind: AccessErrorKind },
+    /// The device moved to a new `DeviceState`
+    StateChanged { change: StateChange },
+    /// Every feature probe of a connected device failed; no more retries until it reconnects
+    ProbeFailed { device_id: String, attempts: u32, error: String },
+    /// The set of things the user must act on for this device changed
+    NeedsAttention { device_id: String, reasons: Vec<AttentionReason> },
+    /// The firmware requires a newer bootloader than the one installed
+    IncompatibleVersions { device_id: String, mismatch: VersionMismatch },
+    /// Every device found by the first scan, once all of them were probed
+    /// (only with `coalesce_initial_scan`)
+    InitialSnapshot { devices: Vec<DeviceWithStatus> },
+    /// The device used when an operation doesn't name one changed; `device_id`
+    /// is `None` when the selection was cleared
+    ActiveChanged { device_id: Option<String>, previous: Option<String>, reason: ActiveChangeReason },
+    /// KeepKey device nodes this user can't open (Linux, missing udev rules)
+    PermissionIssue { issue: PermissionIssue },
+}
+
+impl DeviceEvent {
+    /// Events the frontend must not miss - these are queued until it reports ready
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            DeviceEvent::Ready { .. }
+                | DeviceEvent::PinUnlockNeeded { .. }
+                | DeviceEvent::FeaturesUpdated { .. }
+                | DeviceEvent::StateChanged { .. }
+                | DeviceEvent::ProbeFailed { .. }
+                | DeviceEvent::NeedsAttention { .. }
+                | DeviceEvent::IncompatibleVersions { .. }
+                | DeviceEvent::InitialSnapshot { .. }
+                | DeviceEvent::ActiveChanged { .. }
+                | DeviceEvent::PermissionIssue { .. }
+        )
+    }
+
+    /// Events that only make sense after the frontend has seen `device:connected`
+    /// for the device; these are held back until it has been emitted
+    pub fn requires_connected(&self) -> bool {
+        matches!(
+            self,
+            DeviceEvent::Ready { .. }
+                | DeviceEvent::PinUnlockNeeded { .. }
+                | DeviceEvent::FeaturesUpdated { .. }
+                | DeviceEvent::InvalidState { .. }
+                | DeviceEvent::AccessError { .. }
+                | DeviceEvent::ProbeFailed { .. }
+                | DeviceEvent::NeedsAttention { .. }
+                | DeviceEvent::IncompatibleVersions { .. }
+        )
+    }
+
+    /// The device this event is about, if any
+    pub fn device_id(&self) -> Option<&str> {
+        match self {
+            DeviceEvent::StatusUpdate { .. }
+            | DeviceEvent::InitialSnapshot { .. }
+            | DeviceEvent::ActiveChanged { .. }
+            | DeviceEvent::PermissionIssue { .. } => None,
+            DeviceEvent::Connected { device }
+            | DeviceEvent::RecoveryNeeded { device, .. }
+            | DeviceEvent::Ready { device, .. } => Some(&device.unique_id),
+            DeviceEvent::RecoveryReconnected { new_id, .. } => Some(new_id),
+            DeviceEvent::StateChanged { change } => Some(&change.device_id),
+            DeviceEvent::Disconnected { device_id }
+            | DeviceEvent::PinUnlockNeeded { device_id, .. }
+            | DeviceEvent::FeaturesUpdated { device_id, .. }
+            | DeviceEvent::InvalidState { device_id, .. }
+            | DeviceEvent::AccessError { device_id, .. }
+            | DeviceEvent::ProbeFailed { device_id, .. }
+            | DeviceEvent::NeedsAttention { device_id, .. }
+            | DeviceEvent::IncompatibleVersions { device_id, .. } => Some(device_id),
+        }
+    }
+}
+
+/// A concrete Tauri emit: event name plus JSON payload
+#[derive(Debug, Clone)]
+pub struct EmitSpec {
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+impl EmitSpec {
+    pub fn new(event: &str, payload: serde_json::Value) -> Self {
+        Self { event: event.to_string(), payload }
+    }
+}
+
+/// How many emitted events a slow tap subscriber may fall behind before it
+/// starts missing them
+const EVENT_TAP_CAPACITY: usize = 256;
+
+/// Every device event emitted to the frontend, after transforming and
+/// sequencing, so other transports (the `event-ws` WebSocket server) forward
+/// exactly what the Tauri frontend sees
+static EVENT_TAP: once_cell::sync::Lazy<tokio::sync::broadcast::Sender<EmitSpec>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::broadcast::channel(EVENT_TAP_CAPACITY).0);
+
+/// Receive every device event emitted from now on
+#[cfg_attr(not(feature = "event-ws"), allow(dead_code))]
+pub fn subscribe_emitted() -> tokio::sync::broadcast::Receiver<EmitSpec> {
+    EVENT_TAP.subscribe()
+}
+
+fn publish_emitted(event: &str, payload: &serde_json::Value) {
+    if EVENT_TAP.receiver_count() > 0 {
+        let _ = EVENT_TAP.send(EmitSpec::new(event, payload.clone()));
+    }
+}
+
+/// Maps a device event to what gets emitted; returning `None` suppresses the event
+pub type EventTransformer = Box<dyn Fn(&DeviceEvent) -> Option<EmitSpec> + Send + Sync>;
+
+/// Name shown for the device, per the configured `device_name_strategy`
+fn display_name(features: &DeviceFeatures, device_id: &str) -> String {
+    keepkey_rust::features::naming::display_name_for_features(features, Some(device_id))
+}
+
+/// The event names and payloads the vault frontend listens for
+pub fn default_event_transformer(event: &DeviceEvent) -> Option<EmitSpec> {
+    let spec = match event {
+        DeviceEvent::StatusUpdate { status } => {
+            EmitSpec::new("status:update", serde_json::json!({ "status": status }))
+        }
+        DeviceEvent::Connected { device } => {
+            EmitSpec::new("device:connected", serde_json::json!(device))
+        }
+        DeviceEvent::Disconnected { device_id } => {
+            EmitSpec::new("device:disconnected", serde_json::json!(device_id))
+        }
+        DeviceEvent::RecoveryReconnected { new_id, original_id } => EmitSpec::new(
+            "device:recovery-reconnected",
+            serde_json::json!({
+                "new_id": new_id,
+                "original_id": original_id,
+                "status": "reconnected"
+            }),
+        ),
+        DeviceEvent::RecoveryNeeded { device, reason, instructions } => EmitSpec::new(
+            "device:recovery-needed",
+            serde_json::json!({
+                "deviceId": device.unique_id,
+                "reason": reason,
+                "vid": device.vid,
+                "pid": device.pid,
+                "instructions": instructions
+            }),
+        ),
+        DeviceEvent::Ready { device, features, time_to_ready_ms } => EmitSpec::new(
+            "device:ready",
+            serde_json::json!({
+                "device": device,
+                "features": features,
+                "display_name": display_name(features, &device.unique_id),
+                "status": "ready",
+                "time_to_ready_ms": time_to_ready_ms
+            }),
+        ),
+        DeviceEvent::PinUnlockNeeded { device_id, features, status } => EmitSpec::new(
+            "device:pin-unlock-needed",
+            serde_json::json!({
+                "deviceId": device_id,
+                "features": features,
+                "display_name": display_name(features, device_id),
+                "status": status,
+                "needsPinUnlock": true
+            }),
+        ),
+        DeviceEvent::FeaturesUpdated { device_id, features, status, .. } => EmitSpec::new(
+            "device:features-updated",
+            serde_json::json!({
+                "deviceId": device_id,
+                "features": features,
+                "display_name": display_name(features, device_id),
+                "status": status
+            }),
+        ),
+        DeviceEvent::InvalidState { device_id, error, error_type } => EmitSpec::new(
+            "device:invalid-state",
+            serde_json::json!({
+                "deviceId": device_id,
+                "error": error,
+                "errorType": error_type,
+                "status": "invalid_state"
+            }),
+        ),
+        DeviceEvent::AccessError { device_id, error, kind } => EmitSpec::new(
+            "device:access-error",
+            serde_json::json!({
+                "deviceId": device_id,
+                "error": error,
+                "errorType": kind,
+                "hints": crate::device::access_error::hints(*kind),
+                "status": "error"
+            }),
+        ),
+        DeviceEvent::StateChanged { change } => {
+            EmitSpec::new("device:state-changed", crate::device::state::state_changed_payload(change))
+        }
+        DeviceEvent::ProbeFailed { device_id, attempts, error } => EmitSpec::new(
+            "device:probe-failed",
+            serde_json::json!({
+                "deviceId": device_id,
+                "attempts": attempts,
+                "lastError": error,
+                "recoveryAction": "reset_device_queue",
+                "status": "probe_failed"
+            }),
+        ),
+        DeviceEvent::NeedsAttention { device_id, reasons } => {
+            EmitSpec::new("device:needs-attention", crate::device::attention::needs_attention_payload(device_id, reasons))
+        }
+        DeviceEvent::IncompatibleVersions { device_id, mismatch } => EmitSpec::new(
+            "device:incompatible-versions",
+            serde_json::json!({
+                "unique_id": device_id,
+                "firmware": mismatch.firmware,
+                "bootloader": mismatch.bootloader,
+                "required_bootloader": mismatch.required_bootloader
+            }),
+        ),
+        DeviceEvent::InitialSnapshot { devices } => {
+            EmitSpec::new("devices:initial-snapshot", serde_json::json!({ "devices": devices }))
+        }
+        DeviceEvent::ActiveChanged { device_id, previous, reason } => EmitSpec::new(
+            "device:active-changed",
+            crate::device::active::active_changed_payload(device_id.as_deref(), previous.as_deref(), *reason),
+        ),
+        DeviceEvent::PermissionIssue { issue } => EmitSpec::new("device:permission-issue", serde_json::json!(issue)),
+    };
+    Some(spec)
+}
+
+/// Transformer shared between the controller (which lets integrators replace it)
+/// and every task that emits
+pub type SharedEventTransformer = Arc<RwLock<EventTransformer>>;
+
+pub fn default_shared_transformer() -> SharedEventTransformer {
+    let transformer: EventTransformer = Box::new(default_event_transformer);
+    Arc::new(RwLock::new(transformer))
+}
+
+#[derive(Debug, Default)]
+struct DeviceSequence {
+    last: u64,
+    connected: bool,
+    pending: Vec<DeviceEvent>,
+    /// Hash of the features and status of the last `FeaturesUpdated`
+    features_hash: Option<u64>,
+}
+
+fn features_hash(features: &DeviceFeatures, status: &DeviceStatus) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    serde_json::to_string(&(features, status)).unwrap_or_default().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Orders device events and assigns each a per-device sequence number.
+///
+/// Sequence numbers increase monotonically per device for the lifetime of the
+/// app (they are not reset on reconnect), so the frontend can drop anything
+/// older than the last event it processed. Events that need a prior
+/// `Connected` (see `DeviceEvent::requires_connected`) are buffered until it
+/// arrives and then released after it, in order; a `Disconnected` drops them.
+///
+/// Every successful probe reports `FeaturesUpdated`, so one identical to the
+/// device's previous one is dropped (before it takes a sequence number) unless
+/// it is forced or `emit_identical_features` is set. A reconnect starts over.
+///
+/// A device's state is dropped once it disconnects, so ids that never come
+/// back don't pile up. Its next event continues from the highest sequence
+/// number any dropped device reached, which keeps each device's numbers
+/// increasing.
+#[derive(Debug, Default)]
+pub struct EventSequencer {
+    devices: HashMap<String, DeviceSequence>,
+    /// Highest sequence number of the devices dropped so far
+    floor: u64,
+    pub emit_identical_features: bool,
+}
+
+impl EventSequencer {
+    /// Returns the events that may be emitted now, in order, with their sequence
+    /// numbers (`None` for events not tied to a device)
+    pub fn sequence(&mut self, event: DeviceEvent) -> Vec<(DeviceEvent, Option<u64>)> {
+        let Some(device_id) = event.device_id().map(str::to_string) else {
+            return vec![(event, None)];
+        };
+        let floor = self.floor;
+        let state = self.devices.entry(device_id.clone()).or_insert_with(|| DeviceSequence { last: floor, ..Default::default() });
+
+        if let DeviceEvent::FeaturesUpdated { device_id, features, status, force } = &event {
+            let hash = features_hash(features, status);
+            if !force && !self.emit_identical_features && state.features_hash == Some(hash) {
+                println!("🔁 Features of {} unchanged, not emitting device:features-updated", device_id);
+                return Vec::new();
+            }
+            state.features_hash = Some(hash);
+        }
+
+        match event {
+            DeviceEvent::Connected { .. } => {
+                state.connected = true;
+                let mut ready = Vec::with_capacity(state.pending.len() + 1);
+                state.last += 1;
+                ready.push((event, Some(state.last)));
+                for pending in std::mem::take(&mut state.pending) {
+                    state.last += 1;
+                    ready.push((pending, Some(state.last)));
+                }
+                ready
+            }
+            DeviceEvent::Disconnected { .. } => {
+                if !state.pending.is_empty() {
+                    println!("🗑️ Dropping {} event(s) for a device that disconnected before it was announced", state.pending.len());
+                }
+                let sequence = state.last + 1;
+                self.devices.remove(&device_id);
+                self.floor = self.floor.max(sequence);
+                vec![(event, Some(sequence))]
+            }
+            event if event.requires_connected() && !state.connected => {
+                state.pending.push(event);
+                Vec::new()
+            }
+            event => {
+                state.last += 1;
+                vec![(event, Some(state.last))]
+            }
+        }
+    }
+}
+
+/// One device in `devices:initial-snapshot`
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceWithStatus {
+    pub device: FriendlyUsbDevice,
+    pub features: Option<DeviceFeatures>,
+    pub status: Option<DeviceStatus>,
+    pub state: DeviceState,
+    /// Sequence number of the last event folded into this entry; later events
+    /// for the device continue from it
+    pub sequence: Option<u64>,
+}
+
+/// How long the first scan may hold events back before the snapshot is sent
+/// with whatever has been probed so far
+pub const INITIAL_SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Collects the events of the devices found by the first scan into one snapshot.
+///
+/// `Connected`, `Ready`, `FeaturesUpdated` and `StateChanged` are folded into
+/// the snapshot; events the frontend has to act on (PIN unlock, recovery,
+/// errors) are deferred and emitted right after it. Events of devices that
+/// connect later, and status lines, are not held.
+#[derive(Debug)]
+pub struct InitialScan {
+    members: HashSet<String>,
+    pending: HashSet<String>,
+    devices: Vec<DeviceWithStatus>,
+    deferred: Vec<(DeviceEvent, Option<u64>)>,
+    started: Instant,
+}
+
+impl InitialScan {
+    pub fn new(device_ids: impl IntoIterator<Item = String>) -> Self {
+        let members: HashSet<String> = device_ids.into_iter().collect();
+        Self {
+            pending: members.clone(),
+            members,
+            devices: Vec::new(),
+            deferred: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Take `event` into the snapshot; returns it if it should be emitted now
+    pub fn absorb(&mut self, event: DeviceEvent, sequence: Option<u64>) -> Option<(DeviceEvent, Option<u64>)> {
+        let Some(device_id) = event.device_id().filter(|id| self.members.contains(*id)).map(str::to_string) else {
+            return Some((event, sequence));
+        };
+        let entry = self.devices.iter_mut().find(|d| d.device.unique_id == device_id);
+
+        match (event, entry) {
+            (DeviceEvent::Connected { device }, _) => {
+                self.devices.retain(|d| d.device.unique_id != device_id);
+                self.devices.push(DeviceWithStatus {
+                    device,
+                    features: None,
+                    status: None,
+                    state: DeviceState::Connected,
+                    sequence,
+                });
+            }
+            (DeviceEvent::FeaturesUpdated { features, status, .. }, Some(entry)) => {
+                entry.features = Some(features);
+                entry.status = Some(status);
+                entry.sequence = sequence;
+            }
+            (DeviceEvent::StateChanged { change }, Some(entry)) => {
+                entry.state = change.to;
+                entry.sequence = sequence;
+            }
+            (DeviceEvent::Ready { .. }, Some(entry)) => entry.sequence = sequence,
+            (DeviceEvent::Disconnected { .. }, _) => {
+                // Gone before the frontend heard of it
+                self.devices.retain(|d| d.device.unique_id != device_id);
+                self.deferred.retain(|(e, _)| e.device_id() != Some(device_id.as_str()));
+                self.members.remove(&device_id);
+                self.pending.remove(&device_id);
+            }
+            (event, _) => self.deferred.push((event, sequence)),
+        }
+        None
+    }
+
+    /// The device's first probe finished (or it can't be probed)
+    pub fn settle(&mut self, device_id: &str) {
+        self.pending.remove(device_id);
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.started.elapsed() >= INITIAL_SNAPSHOT_TIMEOUT
+    }
+
+    /// The snapshot event followed by the deferred events, in emit order
+    pub fn finish(self) -> Vec<(DeviceEvent, Option<u64>)> {
+        let mut events = Vec::with_capacity(self.deferred.len() + 1);
+        events.push((DeviceEvent::InitialSnapshot { devices: self.devices }, None));
+        events.extend(self.deferred);
+        events
+    }
+}
+
+/// Where an emitter's events go
+#[derive(Clone)]
+enum EmitTarget {
+    App(AppHandle),
+    /// Tests: a channel of their own, instead of the frontend and the tap
+    #[cfg(test)]
+    Channel(tokio::sync::mpsc::UnboundedSender<EmitSpec>),
+}
+
+/// Sends device events to the frontend through the configured transformer.
+///
+/// Every device event carries a `sequence` field in its payload (when the
+/// payload is an object - `device:disconnected` keeps its bare device id
+/// payload for compatibility, but still consumes a sequence number).
+#[derive(Clone)]
+pub struct EventEmitter {
+    target: EmitTarget,
+    transformer: SharedEventTransformer,
+    // Async mutex held across emits so sequence order matches emission order
+    sequencer: Arc<tokio::sync::Mutex<EventSequencer>>,
+    // Only touched while the sequencer lock is held
+    initial_scan: Arc<std::sync::Mutex<Option<InitialScan>>>,
+}
+
+impl EventEmitter {
+    pub fn new(app: &AppHandle, transformer: SharedEventTransformer) -> Self {
+        Self::with_target(EmitTarget::App(app.clone()), transformer)
+    }
+
+    /// An emitter without a frontend, for driving the monitor in tests. What it
+    /// emits goes to the returned receiver only, not to the event tap.
+    #[cfg(test)]
+    pub fn detached(transformer: SharedEventTransformer) -> (Self, tokio::sync::mpsc::UnboundedReceiver<EmitSpec>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (Self::with_target(EmitTarget::Channel(tx), transformer), rx)
+    }
+
+    fn with_target(target: EmitTarget, transformer: SharedEventTransformer) -> Self {
+        Self {
+            target,
+            transformer,
+            sequencer: Arc::new(tokio::sync::Mutex::new(EventSequencer {
+                emit_identical_features: !crate::commands::dedupe_features_updated_enabled(),
+                ..Default::default()
+            })),
+            initial_scan: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    pub async fn emit(&self, event: DeviceEvent) {
+        let mut sequencer = self.sequencer.lock().await;
+        self.emit_sequenced(&mut sequencer, event).await;
+    }
+
+    /// Move a device to `to` and emit `device:state-changed`. The transition is
+    /// applied under the sequencer lock so state changes are emitted in the order
+    /// they happened; invalid transitions are logged and not applied.
+    pub async fn set_state(&self, device_id: &str, to: DeviceState) {
+        let mut sequencer = self.sequencer.lock().await;
+        match crate::device::state::transition(device_id, to) {
+            Ok(Some(change)) => self.emit_sequenced(&mut sequencer, DeviceEvent::StateChanged { change }).await,
+            Ok(None) => {}
+            Err(e) => println!("⚠️ {}", e),
+        }
+    }
+
+    async fn emit_sequenced(&self, sequencer: &mut EventSequencer, event: DeviceEvent) {
+        let ready = sequencer.sequence(event);
+        if ready.is_empty() {
+            println!("⏸️ Holding device event until device:connected has been emitted");
+        }
+        for (event, sequence) in ready {
+            let passed = match self.lock_initial_scan().as_mut() {
+                Some(scan) => scan.absorb(event, sequence),
+                None => Some((event, sequence)),
+            };
+            if let Some((event, sequence)) = passed {
+                self.emit_one(event, sequence).await;
+            }
+        }
+    }
+
+    fn lock_initial_scan(&self) -> std::sync::MutexGuard<'_, Option<InitialScan>> {
+        match self.initial_scan.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Hold back the events of `device_ids` (the devices found by the first
+    /// scan) until each has been settled, then emit `devices:initial-snapshot`
+    pub async fn begin_initial_scan(&self, device_ids: Vec<String>) {
+        let _sequencer = self.sequencer.lock().await;
+        println!("🧺 Coalescing events of {} initially connected device(s)", device_ids.len());
+        *self.lock_initial_scan() = Some(InitialScan::new(device_ids));
+        self.finish_initial_scan_if(|scan| scan.is_complete()).await;
+    }
+
+    /// Mark a device of the initial scan as probed
+    pub async fn settle_initial_scan(&self, device_id: &str) {
+        let _sequencer = self.sequencer.lock().await;
+        if let Some(scan) = self.lock_initial_scan().as_mut() {
+            scan.settle(device_id);
+        }
+        self.finish_initial_scan_if(|scan| scan.is_complete()).await;
+    }
+
+    /// Send the snapshot with what's known if probing is taking too long
+    pub async fn flush_expired_initial_scan(&self) {
+        let _sequencer = self.sequencer.lock().await;
+        self.finish_initial_scan_if(|scan| {
+            if scan.is_expired() {
+                println!("⏱️ Initial scan still probing after {:?} - sending snapshot now", INITIAL_SNAPSHOT_TIMEOUT);
+            }
+            scan.is_expired()
+        }).await;
+    }
+
+    /// Callers hold the sequencer lock
+    async fn finish_initial_scan_if(&self, done: impl FnOnce(&InitialScan) -> bool) {
+        let finished = {
+            let mut guard = self.lock_initial_scan();
+            if guard.as_ref().is_some_and(done) { guard.take() } else { None }
+        };
+        if let Some(scan) = finished {
+            for (event, sequence) in scan.finish() {
+                self.emit_one(event, sequence).await;
+            }
+        }
+    }
+
+    async fn emit_one(&self, event: DeviceEvent, sequence: Option<u64>) {
+        let spec = {
+            let transformer = match self.transformer.read() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            (*transformer)(&event)
+        };
+
+        let Some(mut spec) = spec else {
+            println!("🔇 Event suppressed by transformer (device: {})", event.device_id().unwrap_or("none"));
+            // No event name without a spec, so log the variant
+            let variant: String = format!("{:?}", event).chars().take_while(|c| c.is_alphanumeric()).collect();
+            crate::event_log::record(&variant, event.device_id(), sequence, String::new(), EmitOutcome::Suppressed);
+            return;
+        };
+
+        if let (Some(sequence), Some(payload)) = (sequence, spec.payload.as_object_mut()) {
+            payload.insert("sequence".to_string(), serde_json::json!(sequence));
+        }
+        // Stable identity to key frontend state off; `deviceId` is the USB id
+        if let (Some(unique_id), Some(payload)) = (event.device_id(), spec.payload.as_object_mut()) {
+            payload
+                .entry("device_id")
+                .or_insert_with(|| serde_json::json!(crate::device::identity::stable_id(unique_id)));
+        }
+
+        if let DeviceEvent::Disconnected { device_id } = &event {
+            crate::payload_limits::forget_withheld_features(device_id);
+        }
+        // Oversized payloads go out without their features, which the frontend pulls
+        let withheld_for = event.device_id().filter(|unique_id| {
+            let limit = crate::payload_limits::limit_for(&spec.event);
+            let Some(features) = crate::payload_limits::withhold_features(&mut spec.payload, limit) else {
+                return false;
+            };
+            println!("📦 {} payload over {} bytes, withholding features of {}", spec.event, limit, unique_id);
+            crate::payload_limits::store_withheld_features(unique_id, features);
+            true
+        });
+
+        let summary = crate::event_log::summarize(&spec.payload);
+        let result = if event.is_critical() {
+            // Critical events are queued if the frontend isn't listening yet
+            let result = self.deliver(true, &spec.event, spec.payload).await;
+            match &result {
+                Err(e) => println!("❌ Failed to emit/queue {} event: {}", spec.event, e),
+                Ok(()) => println!("📡 Successfully emitted/queued {}", spec.event),
+            }
+            result
+        } else {
+            let result = self.deliver(false, &spec.event, spec.payload).await;
+            if let Err(e) = &result {
+                println!("❌ Failed to emit {} event: {}", spec.event, e);
+            }
+            result
+        };
+        let outcome = match result {
+            Ok(()) => EmitOutcome::Emitted,
+            Err(error) => EmitOutcome::Failed { error },
+        };
+        crate::event_log::record(&spec.event, event.device_id(), sequence, summary, outcome);
+
+        if let Some(unique_id) = withheld_for {
+            let payload = serde_json::json!({ "unique_id": unique_id });
+            if let Err(e) = self.deliver(event.is_critical(), "device:features-available", payload).await {
+                println!("❌ Failed to emit device:features-available: {}", e);
+            }
+        }
+    }
+
+    /// Emit to the frontend and the event tap; `queue` holds the event until
+    /// the frontend is listening
+    async fn deliver(&self, queue: bool, event: &str, payload: serde_json::Value) -> Result<(), String> {
+        match &self.target {
+            EmitTarget::App(app) => {
+                publish_emitted(event, &payload);
+                if queue {
+                    crate::commands::emit_or_queue_event(app, event, payload).await
+                } else {
+                    app.emit(event, payload).map_err(|e| e.to_string())
+                }
+            }
+            #[cfg(test)]
+            EmitTarget::Channel(tx) => {
+                let _ = tx.send(EmitSpec::new(event, payload));
+                Ok(())
+            }
+        }
+    }
+
+    /// Convenience for `DeviceEvent::StatusUpdate`
+    pub async fn status(&self, status: impl Into<String>) {
+        let status = status.into();
+        println!("📡 Emitting status: {}", status);
+        self.emit(DeviceEvent::StatusUpdate { status }).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str) -> FriendlyUsbDevice {
+        FriendlyUsbDevice::new(id.to_string(), 0x2b24, 0x0002, None, None, None)
+    }
+
+    fn features() -> DeviceFeatures {
+        crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features::default())
+    }
+
+    fn names(ready: &[(DeviceEvent, Option<u64>)]) -> Vec<(&'static str, Option<u64>)> {
+        ready
+            .iter()
+            .map(|(event, seq)| {
+                let name = match event {
+                    DeviceEvent::Connected { .. } => "connected",
+                    DeviceEvent::Ready { .. } => "ready",
+                    DeviceEvent::Disconnected { .. } => "disconnected",
+                    DeviceEvent::StatusUpdate { .. } => "status",
+                    _ => "other",
+                };
+                (name, *seq)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_emitted_events_reach_tap_subscribers() {
+        // Nobody listening: publishing is a no-op
+        publish_emitted("device:ready", &serde_json::json!({}));
+
+        let mut first = subscribe_emitted();
+        let mut second = subscribe_emitted();
+        publish_emitted("device:ready", &serde_json::json!({ "device_id": "kk-1", "sequence": 4 }));
+        for tap in [&mut first, &mut second] {
+            let spec = tap.recv().await.unwrap();
+            assert_eq!(spec.event, "device:ready");
+            assert_eq!(spec.payload["sequence"], 4);
+        }
+    }
+
+    #[test]
+    fn test_connected_always_precedes_ready() {
+        let mut sequencer = EventSequencer::default();
+        let a = device("A");
+
+        // Feature fetch for A wins the race against the connected emit
+        let ready = sequencer.sequence(DeviceEvent::Ready { device: a.clone(), features: features(), time_to_ready_ms: None });
+        assert!(ready.is_empty());
+
+        // Another device and status lines are not held back
+        let ready = sequencer.sequence(DeviceEvent::Connected { device: device("B") });
+        assert_eq!(names(&ready), vec![("connected", Some(1))]);
+        let ready = sequencer.sequence(DeviceEvent::StatusUpdate { status: "Getting features...".to_string() });
+        assert_eq!(names(&ready), vec![("status", None)]);
+
+        let ready = sequencer.sequence(DeviceEvent::Connected { device: a.clone() });
+        assert_eq!(names(&ready), vec![("connected", Some(1)), ("ready", Some(2))]);
+
+        // In order once connected
+        let ready = sequencer.sequence(DeviceEvent::Ready { device: a.clone(), features: features(), time_to_ready_ms: None });
+        assert_eq!(names(&ready), vec![("ready", Some(3))]);
+
+        // Sequence keeps increasing across reconnects; stale pending events are dropped
+        let ready = sequencer.sequence(DeviceEvent::Disconnected { device_id: "A".to_string() });
+        assert_eq!(names(&ready), vec![("disconnected", Some(4))]);
+        assert!(sequencer.sequence(DeviceEvent::Ready { device: a.clone(), features: features(), time_to_ready_ms: None }).is_empty());
+        sequencer.sequence(DeviceEvent::Disconnected { device_id: "A".to_string() });
+        let ready = sequencer.sequence(DeviceEvent::Connected { device: a });
+        assert_eq!(names(&ready), vec![("connected", Some(6))]);
+    }
+
+    #[test]
+    fn test_disconnected_devices_are_dropped() {
+        let mut sequencer = EventSequencer::default();
+        for i in 0..100 {
+            let id = format!("keepkey_bus1_addr{}", i);
+            sequencer.sequence(DeviceEvent::Connected { device: device(&id) });
+            sequencer.sequence(DeviceEvent::Disconnected { device_id: id });
+        }
+        assert!(sequencer.devices.is_empty());
+
+        // A device seen before continues past its last number
+        let ready = sequencer.sequence(DeviceEvent::Connected { device: device("keepkey_bus1_addr0") });
+        assert!(matches!(ready[..], [(DeviceEvent::Connected { .. }, Some(sequence))] if sequence > 2));
+        assert_eq!(sequencer.devices.len(), 1);
+    }
+
+    #[test]
+    fn test_identical_features_are_emitted_once() {
+        let mut sequencer = EventSequencer::default();
+        let status = crate::commands::evaluate_device_status("A".to_string(), None);
+        let probed = |features: DeviceFeatures, force: bool| DeviceEvent::FeaturesUpdated {
+            device_id: "A".to_string(),
+            features,
+            status: status.clone(),
+            force,
+        };
+        sequencer.sequence(DeviceEvent::Connected { device: device("A") });
+
+        // Two probes with the same answer: one emission
+        assert_eq!(sequencer.sequence(probed(features(), false)).len(), 1);
+        assert!(sequencer.sequence(probed(features(), false)).is_empty());
+
+        // A real change still goes out, and so does a forced repeat
+        let mut relabeled = features();
+        relabeled.label = Some("Savings".to_string());
+        assert_eq!(sequencer.sequence(probed(relabeled.clone(), false)).len(), 1);
+        assert!(sequencer.sequence(probed(relabeled.clone(), false)).is_empty());
+        let ready = sequencer.sequence(probed(relabeled.clone(), true));
+        assert!(matches!(ready[..], [(DeviceEvent::FeaturesUpdated { .. }, Some(4))]));
+
+        // After a reconnect the first report is new again
+        sequencer.sequence(DeviceEvent::Disconnected { device_id: "A".to_string() });
+        sequencer.sequence(DeviceEvent::Connected { device: device("A") });
+        assert_eq!(sequencer.sequence(probed(relabeled.clone(), false)).len(), 1);
+
+        // Turned off, every report is emitted
+        sequencer.emit_identical_features = true;
+        assert_eq!(sequencer.sequence(probed(relabeled, false)).len(), 1);
+    }
+
+    #[test]
+    fn test_initial_scan_coalesces_into_snapshot() {
+        let mut scan = InitialScan::new(vec!["A".to_string(), "B".to_string()]);
+        let status = crate::commands::evaluate_device_status("A".to_string(), None);
+
+        // Status lines and devices that connect later pass straight through
+        assert!(scan.absorb(DeviceEvent::StatusUpdate { status: "Scanning".to_string() }, None).is_some());
+        assert!(scan.absorb(DeviceEvent::Connected { device: device("C") }, Some(1)).is_some());
+
+        assert!(scan.absorb(DeviceEvent::Connected { device: device("A") }, Some(1)).is_none());
+        let change = StateChange { device_id: "A".to_string(), from: DeviceState::Connected, to: DeviceState::Probing };
+        assert!(scan.absorb(DeviceEvent::StateChanged { change }, Some(2)).is_none());
+        assert!(scan.absorb(DeviceEvent::FeaturesUpdated { device_id: "A".to_string(), features: features(), status: status.clone(), force: false }, Some(3)).is_none());
+        assert!(scan.absorb(DeviceEvent::PinUnlockNeeded { device_id: "A".to_string(), features: features(), status }, Some(4)).is_none());
+        scan.settle("A");
+        assert!(!scan.is_complete());
+
+        // B disconnects before it was probed: dropped from the snapshot
+        assert!(scan.absorb(DeviceEvent::Connected { device: device("B") }, Some(1)).is_none());
+        assert!(scan.absorb(DeviceEvent::Disconnected { device_id: "B".to_string() }, Some(2)).is_none());
+        assert!(scan.is_complete());
+
+        let events = scan.finish();
+        assert_eq!(events.len(), 2);
+        let DeviceEvent::InitialSnapshot { devices } = &events[0].0 else { panic!("snapshot must come first") };
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device.unique_id, "A");
+        assert_eq!(devices[0].state, DeviceState::Probing);
+        assert!(devices[0].features.is_some());
+        assert_eq!(devices[0].sequence, Some(3));
+        // The PIN prompt follows the snapshot, keeping its sequence number
+        assert!(matches!(events[1], (DeviceEvent::PinUnlockNeeded { .. }, Some(4))));
+
+        // No initial devices: the snapshot is empty and sent right away
+        let scan = InitialScan::new(Vec::new());
+        assert!(scan.is_complete());
+        assert!(matches!(&scan.finish()[0].0, DeviceEvent::InitialSnapshot { devices } if devices.is_empty()));
+    }
+}
@@ -0,0 +1,148 @@
+//! Cross-process advisory lock so only one vault-v2 instance talks to KeepKeys.
+//!
+//! The owner writes its PID and a heartbeat timestamp to ~/.keepkey/vault-v2.lock
+//! and refreshes the heartbeat every `HEARTBEAT_INTERVAL`. A lockfile whose
+//! heartbeat is older than `STALE_AFTER` was left by a crashed instance and is
+//! taken over. A second instance never touches USB; it reports that another
+//! window is managing the device instead of surfacing access errors.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const STALE_AFTER: Duration = Duration::from_secs(20);
+
+pub const MANAGED_ELSEWHERE_MESSAGE: &str = "KeepKey is managed by another vault-v2 window. Close this window, or close the other one and restart.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockInfo {
+    pid: u32,
+    heartbeat: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceStatus {
+    pub primary: bool,
+    /// PID of the instance managing devices, when it isn't this one
+    pub owner_pid: Option<u32>,
+}
+
+static STATUS: once_cell::sync::OnceCell<InstanceStatus> = once_cell::sync::OnceCell::new();
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn lock_path() -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
+    let dir = home_dir.join(".keepkey");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create .keepkey directory: {}", e))?;
+    Ok(dir.join("vault-v2.lock"))
+}
+
+fn read_lock(path: &PathBuf) -> Option<LockInfo> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn is_stale(info: &LockInfo) -> bool {
+    now_secs().saturating_sub(info.heartbeat) > STALE_AFTER.as_secs()
+}
+
+/// Create the lockfile only if nobody else did in the meantime
+fn create_lock(path: &PathBuf) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let info = LockInfo { pid: std::process::id(), heartbeat: now_secs() };
+    file.write_all(serde_json::to_string(&info).unwrap_or_default().as_bytes())
+}
+
+fn try_acquire() -> Result<InstanceStatus, String> {
+    let path = lock_path()?;
+
+    for _ in 0..2 {
+        match create_lock(&path) {
+            Ok(()) => return Ok(InstanceStatus { primary: true, owner_pid: None }),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                match read_lock(&path) {
+                    Some(info) if info.pid == std::process::id() => {
+                        return Ok(InstanceStatus { primary: true, owner_pid: None });
+                    }
+                    Some(info) if !is_stale(&info) => {
+                        return Ok(InstanceStatus { primary: false, owner_pid: Some(info.pid) });
+                    }
+                    stale => {
+                        // Left behind by a crash (or unreadable); take it over
+                        println!("🧹 Removing stale instance lock {:?}", stale);
+                        let _ = fs::remove_file(&path);
+                    }
+                }
+            }
+            Err(e) => return Err(format!("Failed to create instance lock: {}", e)),
+        }
+    }
+
+    // Another instance won the race to replace the stale lock
+    Ok(InstanceStatus { primary: false, owner_pid: read_lock(&path).map(|i| i.pid) })
+}
+
+/// Decide whether this instance manages devices. Call once at startup; if the
+/// lockfile can't be used at all we act as primary rather than locking the user out.
+pub fn acquire() -> InstanceStatus {
+    let status = try_acquire().unwrap_or_else(|e| {
+        eprintln!("⚠️ {} - continuing without an instance lock", e);
+        InstanceStatus { primary: true, owner_pid: None }
+    });
+    if status.primary {
+        println!("🔐 This vault-v2 instance manages connected KeepKeys");
+        spawn_heartbeat();
+    } else {
+        println!("🔒 Another vault-v2 instance (pid {:?}) manages connected KeepKeys", status.owner_pid);
+    }
+    let _ = STATUS.set(status.clone());
+    status
+}
+
+fn spawn_heartbeat() {
+    tauri::async_runtime::spawn(async {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Ok(path) = lock_path() else { continue };
+            match read_lock(&path) {
+                Some(info) if info.pid == std::process::id() => {
+                    let info = LockInfo { heartbeat: now_secs(), ..info };
+                    let _ = fs::write(&path, serde_json::to_string(&info).unwrap_or_default());
+                }
+                _ => {
+                    println!("⚠️ Instance lock was taken over by another process; stopping heartbeat");
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Remove the lockfile if this instance owns it
+pub fn release() {
+    if !is_secondary() {
+        if let Ok(path) = lock_path() {
+            if read_lock(&path).is_some_and(|info| info.pid == std::process::id()) {
+                let _ = fs::remove_file(&path);
+                println!("🔓 Instance lock released");
+            }
+        }
+    }
+}
+
+/// True when another instance manages devices
+pub fn is_secondary() -> bool {
+    STATUS.get().is_some_and(|s| !s.primary)
+}
+
+#[tauri::command]
+pub async fn get_instance_status() -> Result<InstanceStatus, String> {
+    Ok(STATUS.get().cloned().unwrap_or(InstanceStatus { primary: true, owner_pid: None }))
+}
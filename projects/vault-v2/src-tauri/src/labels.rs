@@ -0,0 +1,233 @@
+//! Address labels stored per seed in BIP-329 format.
+//!
+//! Labels are keyed by the wallet's master fingerprint (first 4 bytes of
+//! hash160 of the master public key), so they follow the seed to any device
+//! it is loaded on. Each wallet's labels live in
+//! ~/.keepkey/labels/<fingerprint>.jsonl, one BIP-329 record per line, which
+//! is also the export format.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::State;
+
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// One BIP-329 record. Fields this app doesn't use (e.g. `spendable`) are kept
+/// so a re-export doesn't lose them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bip329Record {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressLabel {
+    pub address: String,
+    pub label: String,
+    pub origin: Option<String>,
+}
+
+fn labels_path(fingerprint: &str) -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
+    let dir = home_dir.join(".keepkey").join("labels");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create labels directory: {}", e))?;
+    Ok(dir.join(format!("{}.jsonl", fingerprint)))
+}
+
+/// Parse BIP-329 JSONL; blank lines are ignored
+pub fn parse_jsonl(jsonl: &str) -> Result<Vec<Bip329Record>, String> {
+    jsonl
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| format!("Invalid BIP-329 record on line {}: {}", i + 1, e))
+        })
+        .collect()
+}
+
+pub fn to_jsonl(records: &[Bip329Record]) -> String {
+    records
+        .iter()
+        .filter_map(|record| serde_json::to_string(record).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Insert or replace records with the same type and ref; a record without a
+/// label removes the existing one
+pub fn merge_records(records: &mut Vec<Bip329Record>, updates: Vec<Bip329Record>) {
+    for update in updates {
+        records.retain(|r| !(r.kind == update.kind && r.reference == update.reference));
+        if update.label.as_deref().is_some_and(|l| !l.is_empty()) {
+            records.push(update);
+        }
+    }
+}
+
+fn load_records(fingerprint: &str) -> Result<Vec<Bip329Record>, String> {
+    let path = labels_path(fingerprint)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let jsonl = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read labels: {}", e))?;
+    parse_jsonl(&jsonl)
+}
+
+fn save_records(fingerprint: &str, records: &[Bip329Record]) -> Result<(), String> {
+    std::fs::write(labels_path(fingerprint)?, to_jsonl(records)).map_err(|e| format!("Failed to write labels: {}", e))
+}
+
+/// Output descriptor prefix for the script type implied by a BIP-44 style purpose
+fn descriptor_for_purpose(purpose: u32) -> Option<(&'static str, i32)> {
+    match purpose & !0x8000_0000 {
+        44 => Some(("pkh", 0)),     // SPENDADDRESS
+        49 => Some(("sh(wpkh", 4)), // SPENDP2SHWITNESS
+        84 => Some(("wpkh", 3)),    // SPENDWITNESS
+        _ => None,
+    }
+}
+
+/// BIP-329 `origin` for an address path: the descriptor of its account, e.g.
+/// `wpkh([d34db33f/84'/0'/0'])`
+fn origin_for(fingerprint: &str, path: &[u32]) -> Option<String> {
+    let (descriptor, _) = descriptor_for_purpose(*path.first()?)?;
+    let account: Vec<String> = path
+        .iter()
+        .take(3)
+        .map(|i| if i & 0x8000_0000 != 0 { format!("{}'", i & !0x8000_0000) } else { i.to_string() })
+        .collect();
+    let close = if descriptor.starts_with("sh(") { "))" } else { ")" };
+    Some(format!("{}([{}/{}]{}", descriptor, fingerprint, account.join("/"), close))
+}
+
+/// Label the address at `path` on this device's wallet. The address is derived on
+/// the device, so the label always refers to an address the seed controls.
+#[tauri::command]
+pub async fn label_address(
+    unique_id: String,
+    path: String,
+    label: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<AddressLabel, String> {
+    let path_parts = crate::commands::parse_derivation_path(&path)?;
+    let script_type = path_parts.first().and_then(|p| descriptor_for_purpose(*p)).map(|(_, script_type)| script_type);
+    let coin_name = crate::network::resolve_coin(crate::commands::network_mode(), None, &path_parts)?;
+    let fingerprint = crate::commands::master_fingerprint_hex(&unique_id, &queue_manager).await?;
+
+    let queue_handle = queue_manager
+        .get_or_spawn_by_id(&unique_id)
+        .await
+        .ok_or_else(|| format!("Device {} not found", unique_id))?;
+    let address = queue_handle
+        .get_address(path_parts.clone(), coin_name, script_type, Some(false))
+        .await
+        .map_err(|e| format!("Failed to get address: {}", e))?;
+
+    let record = Bip329Record {
+        kind: "addr".to_string(),
+        reference: address.clone(),
+        label: Some(label.clone()),
+        origin: origin_for(&fingerprint, &path_parts),
+        extra: serde_json::Map::new(),
+    };
+    let origin = record.origin.clone();
+
+    let mut records = load_records(&fingerprint)?;
+    merge_records(&mut records, vec![record]);
+    save_records(&fingerprint, &records)?;
+
+    println!("🏷️ Labeled {} ({}) for wallet {}", address, path, fingerprint);
+    Ok(AddressLabel { address, label, origin })
+}
+
+/// Address labels for the wallet loaded on this device
+#[tauri::command]
+pub async fn get_address_labels(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<Vec<AddressLabel>, String> {
+    let fingerprint = crate::commands::master_fingerprint_hex(&unique_id, &queue_manager).await?;
+    Ok(load_records(&fingerprint)?
+        .into_iter()
+        .filter(|r| r.kind == "addr")
+        .filter_map(|r| Some(AddressLabel { address: r.reference, label: r.label?, origin: r.origin }))
+        .collect())
+}
+
+/// All labels for this device's wallet as BIP-329 JSONL
+#[tauri::command]
+pub async fn export_address_labels(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<String, String> {
+    let fingerprint = crate::commands::master_fingerprint_hex(&unique_id, &queue_manager).await?;
+    Ok(to_jsonl(&load_records(&fingerprint)?))
+}
+
+/// Merge BIP-329 JSONL into this device's wallet labels; returns how many records
+/// were imported. Nothing is imported if any line is invalid.
+#[tauri::command]
+pub async fn import_address_labels(
+    unique_id: String,
+    jsonl: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<usize, String> {
+    let imported = parse_jsonl(&jsonl)?;
+    let count = imported.len();
+    let fingerprint = crate::commands::master_fingerprint_hex(&unique_id, &queue_manager).await?;
+
+    let mut records = load_records(&fingerprint)?;
+    merge_records(&mut records, imported);
+    save_records(&fingerprint, &records)?;
+
+    println!("🏷️ Imported {} label record(s) for wallet {}", count, fingerprint);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_for_path() {
+        let path = crate::commands::parse_derivation_path("m/84'/0'/0'/0/5").unwrap();
+        assert_eq!(origin_for("3442193e", &path).as_deref(), Some("wpkh([3442193e/84'/0'/0'])"));
+        let path = crate::commands::parse_derivation_path("m/49'/0'/0'/0/0").unwrap();
+        assert_eq!(origin_for("3442193e", &path).as_deref(), Some("sh(wpkh([3442193e/49'/0'/0']))"));
+        let path = crate::commands::parse_derivation_path("m/1'/2").unwrap();
+        assert_eq!(origin_for("3442193e", &path), None);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip_and_merge() {
+        let jsonl = r#"{"type":"addr","ref":"bc1qexample","label":"Savings"}
+
+{"type":"output","ref":"abcd:0","label":"Change","spendable":false}
+"#;
+        let mut records = parse_jsonl(jsonl).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].extra.get("spendable"), Some(&serde_json::json!(false)));
+        assert_eq!(parse_jsonl(&to_jsonl(&records)).unwrap(), records);
+
+        merge_records(&mut records, parse_jsonl(r#"{"type":"addr","ref":"bc1qexample","label":"Cold storage"}"#).unwrap());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records.iter().find(|r| r.reference == "bc1qexample").unwrap().label.as_deref(), Some("Cold storage"));
+
+        // An empty label removes the record
+        merge_records(&mut records, parse_jsonl(r#"{"type":"addr","ref":"bc1qexample","label":""}"#).unwrap());
+        assert_eq!(records.len(), 1);
+
+        let err = parse_jsonl("{\"type\":\"addr\",\"ref\":\"x\"}\nnot json").unwrap_err();
+        assert!(err.contains("line 2"));
+    }
+}
@@ -2,12 +2,21 @@ use tauri::{Emitter, Manager};
 
 // Modules for better organization
 
+pub mod chain;
 mod commands;
+mod descriptors;
 mod device;
 mod event_controller;
+mod event_log;
+mod events;
+mod instance_lock;
+mod labels;
 mod logging;
+mod network;
+mod payload_limits;
 mod slip132;
 mod server;
+mod support;
 
 // Re-export commonly used types
 
@@ -212,12 +221,78 @@ pub fn run() {
                 std::collections::HashMap::<String, std::time::Instant>::new()
             ));
             
+            device::prompts::init(app.handle().clone());
             app.manage(device_queue_manager.clone());
             app.manage(last_responses);
             app.manage(bootloader_tracker);
             
-            // Start event controller with proper management
-            let _event_controller = event_controller::spawn_event_controller(&app.handle());
+            // Name unlabeled devices the way the user configured before anything is emitted
+            commands::apply_device_name_strategy_from_config();
+            // ...and give up on unresponsive devices after the configured number of probes
+            commands::apply_probe_policy_from_config();
+            commands::apply_worker_idle_timeout_from_config();
+            commands::apply_tx_chunk_size_from_config();
+            commands::apply_event_log_capacity_from_config();
+            commands::apply_device_cache_capacity_from_config();
+            commands::apply_transport_config_from_config();
+            commands::apply_timeout_mode_from_config();
+            commands::apply_event_payload_limits_from_config();
+            // Frontend development without hardware (mock-device feature only)
+            device::mock::enable_from_args();
+            
+            // Only one instance may drive USB; a second window explains why it sees no device
+            let instance = instance_lock::acquire();
+            if instance.primary {
+                // Warn the UI before an unlocked device auto-locks
+                device::session::spawn_lock_watcher(app.handle().clone());
+                
+                // Start event controller with proper management
+                let _event_controller = event_controller::spawn_event_controller(&app.handle())?;
+                
+                // Start the optional JSON-RPC bridge; it stops together with the event controller
+                #[cfg(feature = "bridge")]
+                {
+                    let bridge_shutdown = event_controller::lock_controller(&_event_controller).shutdown_token();
+                    let bridge_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = server::bridge::start_bridge(bridge_handle.clone(), bridge_shutdown).await {
+                            log::error!("❌ Bridge error: {}", e);
+                            let _ = bridge_handle.emit("server:error", serde_json::json!({
+                                "error": format!("Bridge failed to start: {}", e)
+                            }));
+                        }
+                    });
+                }
+                
+                // Device events for remote UIs; also stops with the event controller
+                #[cfg(feature = "event-ws")]
+                {
+                    let ws_shutdown = event_controller::lock_controller(&_event_controller).shutdown_token();
+                    let ws_handle = app.handle().clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = server::event_ws::start_event_ws(ws_shutdown).await {
+                            log::error!("❌ Event WebSocket error: {}", e);
+                            let _ = ws_handle.emit("server:error", serde_json::json!({
+                                "error": format!("Event WebSocket failed to start: {}", e)
+                            }));
+                        }
+                    });
+                }
+            } else {
+                let conflict_handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let payload = serde_json::json!({
+                        "ownerPid": instance.owner_pid,
+                        "message": instance_lock::MANAGED_ELSEWHERE_MESSAGE
+                    });
+                    if let Err(e) = commands::emit_or_queue_event(&conflict_handle, "instance:managed-elsewhere", payload).await {
+                        eprintln!("Failed to emit instance conflict: {}", e);
+                    }
+                    let _ = conflict_handle.emit("status:update", serde_json::json!({
+                        "status": instance_lock::MANAGED_ELSEWHERE_MESSAGE
+                    }));
+                });
+            }
             
             // Start background log cleanup task
             let _app_handle = app.handle().clone();
@@ -247,7 +322,9 @@ pub fn run() {
                     }
                 };
                 
-                if api_enabled {
+                if api_enabled && instance_lock::is_secondary() {
+                    log::info!("API is enabled but another vault-v2 instance manages devices - not starting server");
+                } else if api_enabled {
                     log::info!("🚀 API is enabled in preferences, starting server...");
                     
                     if let Err(e) = server::start_server(server_queue_manager).await {
@@ -282,12 +359,68 @@ pub fn run() {
             // New device commands (all go through queue)
             commands::get_device_status,
             commands::get_device_info_by_id,
+            commands::get_device_model,
+            commands::get_bootloader_policy,
+            commands::get_network_mode,
+            commands::set_network_mode,
+            commands::is_device_initialized,
+            commands::get_master_fingerprint,
+            commands::get_device_screen_hint,
+            commands::set_active_device,
+            commands::get_active_device,
+            commands::get_transport_config,
+            commands::set_transport_config,
+            device::forget::forget_device,
             commands::wipe_device,
             commands::set_device_label,
+            commands::apply_flags,
+            instance_lock::get_instance_status,
+            device::psbt::sign_psbt,
+            device::state::get_device_state,
+            device::ready_timing::get_time_to_ready,
+            event_controller::rescan_devices,
+            event_controller::set_power_mode,
+            device::storage::get_storage_stats,
+            device::oob_stats::get_oob_stats,
+            device::metrics::metrics_text,
+            device::capabilities::supports_operation,
+            device::capabilities::get_firmware_flags,
+            device::mock::enable_mock_device,
+            device::mock::disable_mock_device,
+            device::release_notes::get_firmware_release_notes,
+            device::firmware_file::inspect_firmware_file,
+            device::on_connect::run_on_connect,
+            device::on_connect::cancel_run_on_connect,
+            device::telemetry::get_device_telemetry,
+            device::policy::resolve_policy_confirmation,
+            device::change::verify_change_address,
+            device::identity::get_device_id,
+            device::feature_cache::clear_feature_cache,
+            device::entropy::check_entropy_quality,
+            device::compare::compare_devices,
+            device::multisig::verify_address_ownership,
+            chain::broadcast::broadcast_transaction,
+            chain::broadcast::sign_and_broadcast,
+            device::signing::cancel_signing,
+            chain::tx_stream::sign_transaction_streaming,
+            chain::discovery::discover_accounts,
+            chain::receive::get_next_receive_address,
+            descriptors::export_descriptors,
+            labels::label_address,
+            labels::get_address_labels,
+            labels::export_address_labels,
+            labels::import_address_labels,
+            device::session::get_session_info,
+            device::session::set_auto_lock_delay,
+            device::session::begin_session,
+            device::session::end_session,
             commands::get_connected_devices_with_features,
             // Update commands
             device::updates::update_device_bootloader,
             device::updates::update_device_firmware,
+            device::update_cancel::cancel_firmware_update,
+            device::update_history::get_update_history,
+            device::updates::recover_from_dfu,
             // PIN creation commands
             commands::initialize_device_pin,
             commands::send_pin_matrix_response,
@@ -304,6 +437,13 @@ pub fn run() {
             // Logging commands
             commands::get_device_log_path,
             commands::get_recent_device_logs,
+            device::benchmark::benchmark_device_io,
+            device::update_eta::estimate_update_duration,
+            device::connection::diagnose_connection,
+            event_log::get_recent_events,
+            payload_limits::get_withheld_features,
+            support::export_support_bundle,
+            support::export_device_features,
             commands::cleanup_device_logs,
             // Configuration and onboarding commands
             commands::is_first_time_install,
@@ -316,6 +456,8 @@ pub fn run() {
             commands::get_api_enabled,
             commands::set_api_enabled,
             commands::get_api_status,
+            commands::get_min_firmware_policy_setting,
+            commands::set_min_firmware_policy_setting,
             commands::restart_app,
             // Test commands
             commands::test_device_queue,
@@ -330,12 +472,28 @@ pub fn run() {
             commands::cancel_recovery_session,
             // Seed verification commands (dry run recovery)
             commands::start_seed_verification,
+            device::seed_check::verify_seed,
             commands::send_verification_character,
             commands::send_verification_pin,
             commands::get_verification_status,
             commands::cancel_seed_verification,
             commands::force_cleanup_seed_verification
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                let app = window.app_handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    event_controller::cancel_prompts_on_window_close(&app).await;
+                });
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Monitor first, then device workers - see shutdown_device_services
+                tauri::async_runtime::block_on(event_controller::shutdown_device_services(app));
+                instance_lock::release();
+            }
+        });
 }
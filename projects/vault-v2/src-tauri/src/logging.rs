@@ -273,6 +273,11 @@ pub async fn log_device_response(
     response_data: &serde_json::Value,
     error: Option<&str>,
 ) -> Result<(), String> {
+    // Every completed device operation passes through here, which makes it the
+    // one place to track activity for the auto-lock estimate
+    if success {
+        crate::device::session::record_activity(device_id);
+    }
     let logger = get_device_logger();
     logger.log_response(device_id, request_id, success, response_data, error).await
 }
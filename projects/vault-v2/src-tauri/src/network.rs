@@ -0,0 +1,199 @@
+//! Which Bitcoin network the app works on.
+//!
+//! The mode decides the coin sent to the device, which BIP44 coin type paths
+//! must use and which chain backend is queried. Requests for the other
+//! network are refused rather than silently derived on the wrong chain.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::commands::DeviceRequest;
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Purposes whose second component is a SLIP-44 coin type
+const COIN_TYPE_PURPOSES: [u32; 5] = [44, 48, 49, 84, 86];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkMode {
+    #[default]
+    Mainnet,
+    Testnet,
+    /// Local regtest node. The device has no regtest coin, so it works with
+    /// the Testnet coin: native segwit addresses it shows start with `tb1`.
+    Regtest,
+}
+
+impl NetworkMode {
+    pub fn from_config(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "mainnet" | "bitcoin" => Some(NetworkMode::Mainnet),
+            "testnet" => Some(NetworkMode::Testnet),
+            "regtest" => Some(NetworkMode::Regtest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetworkMode::Mainnet => "mainnet",
+            NetworkMode::Testnet => "testnet",
+            NetworkMode::Regtest => "regtest",
+        }
+    }
+
+    /// Coin name the firmware knows the network by
+    pub fn coin_name(self) -> &'static str {
+        match self {
+            NetworkMode::Mainnet => "Bitcoin",
+            NetworkMode::Testnet | NetworkMode::Regtest => "Testnet",
+        }
+    }
+
+    /// SLIP-44 coin type of the network's derivation paths
+    pub fn coin_type(self) -> u32 {
+        match self {
+            NetworkMode::Mainnet => 0,
+            NetworkMode::Testnet | NetworkMode::Regtest => 1,
+        }
+    }
+
+    /// Esplora instance used when none is configured for the network
+    pub fn default_esplora_url(self) -> &'static str {
+        match self {
+            NetworkMode::Mainnet => "https://blockstream.info/api",
+            NetworkMode::Testnet => "https://blockstream.info/testnet/api",
+            // electrs' default HTTP port for regtest
+            NetworkMode::Regtest => "http://127.0.0.1:3002",
+        }
+    }
+
+    /// Config key of the network's Esplora URL
+    pub fn esplora_url_key(self) -> &'static str {
+        match self {
+            NetworkMode::Mainnet => "esplora_url",
+            NetworkMode::Testnet => "esplora_url_testnet",
+            NetworkMode::Regtest => "esplora_url_regtest",
+        }
+    }
+}
+
+impl fmt::Display for NetworkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Refuse a path whose coin type belongs to another network, e.g. m/84'/0'/..
+/// in testnet mode. Paths without a BIP44-style purpose aren't checked.
+pub fn check_path(mode: NetworkMode, path: &[u32]) -> Result<(), String> {
+    let [purpose, coin_type, ..] = path else {
+        return Ok(());
+    };
+    if purpose & HARDENED == 0 || !COIN_TYPE_PURPOSES.contains(&(purpose & !HARDENED)) {
+        return Ok(());
+    }
+    let coin_type = coin_type & !HARDENED;
+    if coin_type != mode.coin_type() {
+        return Err(format!(
+            "Path uses coin type {}' but the app is in {} mode, which uses {}'",
+            coin_type,
+            mode,
+            mode.coin_type()
+        ));
+    }
+    Ok(())
+}
+
+/// Refuse a coin of another network
+pub fn check_coin(mode: NetworkMode, coin_name: &str) -> Result<(), String> {
+    if coin_name.trim().eq_ignore_ascii_case(mode.coin_name()) {
+        Ok(())
+    } else {
+        Err(format!("Coin {} doesn't belong to {} mode (expected {})", coin_name, mode, mode.coin_name()))
+    }
+}
+
+/// Coin to send the device for a request at `path`: the requested one, or the
+/// mode's when none was given. Errors if either belongs to another network.
+pub fn resolve_coin(mode: NetworkMode, coin: Option<&str>, path: &[u32]) -> Result<String, String> {
+    if let Some(coin) = coin {
+        check_coin(mode, coin)?;
+    }
+    check_path(mode, path)?;
+    Ok(mode.coin_name().to_string())
+}
+
+/// Check every coin and path of a device request against `mode`
+pub fn check_request(mode: NetworkMode, request: &DeviceRequest) -> Result<(), String> {
+    match request {
+        DeviceRequest::GetXpub { path } => check_path(mode, &crate::commands::parse_derivation_path(path)?),
+        DeviceRequest::GetAddress { path, coin_name, .. } => {
+            resolve_coin(mode, Some(coin_name), &crate::commands::parse_derivation_path(path)?).map(|_| ())
+        }
+        DeviceRequest::SignTransaction { coin, inputs, outputs, .. } => {
+            check_coin(mode, coin)?;
+            for input in inputs {
+                check_path(mode, &input.address_n_list)?;
+            }
+            for path in outputs.iter().filter_map(|output| output.address_n_list.as_ref()) {
+                check_path(mode, path)?;
+            }
+            Ok(())
+        }
+        DeviceRequest::GetFeatures | DeviceRequest::SendRaw { .. } => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> Vec<u32> {
+        crate::commands::parse_derivation_path(s).unwrap()
+    }
+
+    fn get_address(path: &str, coin_name: &str) -> DeviceRequest {
+        DeviceRequest::GetAddress {
+            path: path.to_string(),
+            coin_name: coin_name.to_string(),
+            script_type: Some("p2wpkh".to_string()),
+            show_display: None,
+            multisig: None,
+        }
+    }
+
+    #[test]
+    fn test_paths_must_match_network() {
+        // A testnet request rejects a mainnet path and vice versa
+        assert!(check_path(NetworkMode::Testnet, &path("m/84'/0'/0'/0/0")).is_err());
+        assert!(check_path(NetworkMode::Mainnet, &path("m/84'/1'/0'/0/0")).is_err());
+        assert!(check_path(NetworkMode::Testnet, &path("m/84'/1'/0'/0/0")).is_ok());
+        assert!(check_path(NetworkMode::Regtest, &path("m/44'/1'/0'")).is_ok());
+        assert!(check_path(NetworkMode::Mainnet, &path("m/48'/0'/0'/2'")).is_ok());
+        // Paths without a coin type aren't the network's business
+        assert!(check_path(NetworkMode::Testnet, &path("m/0/1")).is_ok());
+        assert!(check_path(NetworkMode::Testnet, &[]).is_ok());
+    }
+
+    #[test]
+    fn test_requests_checked_against_mode() {
+        assert!(check_request(NetworkMode::Testnet, &get_address("m/84'/1'/0'/0/0", "Testnet")).is_ok());
+        assert!(check_request(NetworkMode::Testnet, &get_address("m/84'/0'/0'/0/0", "Testnet")).is_err());
+        assert!(check_request(NetworkMode::Mainnet, &get_address("m/84'/1'/0'/0/0", "Bitcoin")).is_err());
+        assert!(check_request(NetworkMode::Mainnet, &get_address("m/84'/0'/0'/0/0", "Testnet")).is_err());
+        assert!(check_request(NetworkMode::Testnet, &DeviceRequest::GetXpub { path: "m/84'/0'/0'".to_string() }).is_err());
+
+        assert_eq!(resolve_coin(NetworkMode::Regtest, None, &path("m/84'/1'/0'/1/3")).unwrap(), "Testnet");
+        assert_eq!(resolve_coin(NetworkMode::Mainnet, Some("bitcoin"), &path("m/84'/0'/0'/1/3")).unwrap(), "Bitcoin");
+    }
+
+    #[test]
+    fn test_network_mode_config() {
+        assert_eq!(NetworkMode::from_config(" Testnet "), Some(NetworkMode::Testnet));
+        assert_eq!(NetworkMode::from_config("bitcoin"), Some(NetworkMode::Mainnet));
+        assert_eq!(NetworkMode::from_config("signet"), None);
+        assert_eq!(serde_json::to_value(NetworkMode::Regtest).unwrap(), "regtest");
+    }
+}
@@ -0,0 +1,149 @@
+//! Keeps the event channel lean: a device event whose payload is larger than
+//! its limit goes out without its `features`, followed by
+//! `device:features-available`, and the frontend pulls them with
+//! `get_withheld_features`.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::device::lru::DeviceLru;
+
+/// Bytes of serialized payload an event may carry by default. Normal features
+/// payloads are a few KiB, so they always go out inline.
+pub const DEFAULT_EVENT_PAYLOAD_LIMIT: usize = 256 * 1024;
+
+/// Key of the per-event limits object, e.g.
+/// `{ "default": 262144, "device:features-updated": 16384 }`
+pub const EVENT_PAYLOAD_LIMITS_KEY: &str = "event_payload_limits";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadLimits {
+    pub default: usize,
+    pub per_event: HashMap<String, usize>,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self { default: DEFAULT_EVENT_PAYLOAD_LIMIT, per_event: HashMap::new() }
+    }
+}
+
+impl PayloadLimits {
+    /// Parse the `event_payload_limits` object; `None` if any limit isn't a
+    /// positive whole number
+    pub fn from_config(value: &Value) -> Option<Self> {
+        let mut limits = Self::default();
+        for (event, limit) in value.as_object()? {
+            let limit = limit.as_u64().and_then(|n| usize::try_from(n).ok()).filter(|n| *n > 0)?;
+            if event == "default" {
+                limits.default = limit;
+            } else {
+                limits.per_event.insert(event.clone(), limit);
+            }
+        }
+        Some(limits)
+    }
+
+    pub fn limit_for(&self, event: &str) -> usize {
+        self.per_event.get(event).copied().unwrap_or(self.default)
+    }
+}
+
+static PAYLOAD_LIMITS: once_cell::sync::Lazy<std::sync::Mutex<PayloadLimits>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(PayloadLimits::default()));
+
+/// Features taken out of the last oversized event of each device
+static WITHHELD_FEATURES: once_cell::sync::Lazy<std::sync::Mutex<DeviceLru<Value>>> =
+    once_cell::sync::Lazy::new(|| std::sync::Mutex::new(DeviceLru::new()));
+
+pub fn set_payload_limits(limits: PayloadLimits) {
+    match PAYLOAD_LIMITS.lock() {
+        Ok(mut current) => *current = limits,
+        Err(poisoned) => *poisoned.into_inner() = limits,
+    }
+}
+
+pub fn limit_for(event: &str) -> usize {
+    match PAYLOAD_LIMITS.lock() {
+        Ok(limits) => limits.limit_for(event),
+        Err(poisoned) => poisoned.into_inner().limit_for(event),
+    }
+}
+
+/// Take `features` out of `payload` if it serializes to more than `limit`
+/// bytes. Returns the features taken out; payloads without them are left as
+/// they are, however large.
+pub fn withhold_features(payload: &mut Value, limit: usize) -> Option<Value> {
+    let size = serde_json::to_vec(payload).map(|bytes| bytes.len()).unwrap_or(0);
+    if size <= limit {
+        return None;
+    }
+    let fields = payload.as_object_mut()?;
+    let features = fields.remove("features")?;
+    fields.insert("featuresWithheld".to_string(), Value::Bool(true));
+    Some(features)
+}
+
+pub fn store_withheld_features(unique_id: &str, features: Value) {
+    crate::commands::lock_or_recover(&WITHHELD_FEATURES, "withheld features").insert(unique_id, features);
+}
+
+pub fn forget_withheld_features(unique_id: &str) {
+    crate::commands::lock_or_recover(&WITHHELD_FEATURES, "withheld features").remove(unique_id);
+}
+
+/// Features left out of an oversized event, announced by `device:features-available`
+#[tauri::command]
+pub async fn get_withheld_features(unique_id: String) -> Result<Value, String> {
+    let withheld = WITHHELD_FEATURES
+        .lock()
+        .map_err(|_| "Failed to lock withheld features".to_string())?
+        .peek(&unique_id)
+        .cloned();
+    match withheld {
+        Some(features) => Ok(features),
+        None => crate::commands::cached_device_features(&unique_id)
+            .map(|features| serde_json::json!(features))
+            .ok_or_else(|| format!("No features available for device {}", unique_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oversized_raw_features_are_withheld() {
+        let raw_features = serde_json::json!({
+            "vendor": "keepkey.com",
+            "label": "KeepKey",
+            "policies": (0..2000).map(|i| serde_json::json!({ "policy_name": format!("Policy{}", i), "enabled": true })).collect::<Vec<_>>()
+        });
+        let mut payload = serde_json::json!({ "deviceId": "A", "features": raw_features.clone(), "status": "ready" });
+
+        let withheld = withhold_features(&mut payload, 16 * 1024);
+        assert_eq!(withheld, Some(raw_features));
+        assert_eq!(payload["featuresWithheld"], true);
+        assert!(payload.get("features").is_none());
+        assert_eq!(payload["status"], "ready");
+
+        // A normal features payload goes out inline under the default limit
+        let features = crate::commands::convert_features_to_device_features(keepkey_rust::messages::Features::default());
+        let mut payload = serde_json::json!({ "deviceId": "A", "features": features });
+        assert_eq!(withhold_features(&mut payload, DEFAULT_EVENT_PAYLOAD_LIMIT), None);
+        assert!(payload.get("features").is_some());
+    }
+
+    #[test]
+    fn test_payload_limits_config() {
+        let limits = PayloadLimits::from_config(&serde_json::json!({
+            "default": 65536,
+            "device:features-updated": 4096
+        }))
+        .unwrap();
+        assert_eq!(limits.limit_for("device:features-updated"), 4096);
+        assert_eq!(limits.limit_for("device:ready"), 65536);
+        assert_eq!(PayloadLimits::from_config(&serde_json::json!({ "device:ready": 0 })), None);
+        assert_eq!(PayloadLimits::from_config(&serde_json::json!(5)), None);
+    }
+}
@@ -0,0 +1,418 @@
+//! Optional JSON-RPC bridge for third-party wallet software.
+//!
+//! Compiled only with the `bridge` feature. Binds to localhost, checks the
+//! request origin and asks the user to confirm sensitive calls in the vault UI
+//! before they reach the device. See `docs/json-rpc-bridge.md` for the schema.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Listener, Manager};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::commands::{lock_or_recover, DeviceQueueManager, DeviceQueueManagerExt, DeviceRequest, DeviceRequestWrapper, DeviceResponse};
+
+pub const BRIDGE_ADDR: &str = "127.0.0.1:1647";
+
+/// How long a confirmation prompt stays open before the request is rejected
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Origins that are always allowed in addition to the configured list
+const DEFAULT_ALLOWED_ORIGINS: &[&str] = &["http://localhost:1647", "http://127.0.0.1:1647"];
+
+/// Which calls need an explicit user approval in the vault UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationPolicy {
+    /// Every call, including read-only ones, is confirmed
+    Always,
+    /// Only calls that reveal addresses or sign are confirmed (default)
+    Sensitive,
+}
+
+impl ConfirmationPolicy {
+    fn from_preference(value: Option<&str>) -> Self {
+        match value {
+            Some("always") => ConfirmationPolicy::Always,
+            _ => ConfirmationPolicy::Sensitive,
+        }
+    }
+
+    fn requires_confirmation(&self, method: &str) -> bool {
+        match self {
+            ConfirmationPolicy::Always => true,
+            // Signing is never allowed silently, whatever the policy
+            ConfirmationPolicy::Sensitive => matches!(method, "get_address" | "sign_transaction"),
+        }
+    }
+}
+
+pub struct BridgeState {
+    pub app: AppHandle,
+    pub device_queue_manager: DeviceQueueManager,
+    pub allowed_origins: Vec<String>,
+    pub confirmation_policy: ConfirmationPolicy,
+}
+
+// Confirmation prompts waiting for a `bridge:confirm-response` from the frontend
+static PENDING_CONFIRMATIONS: Lazy<Mutex<HashMap<String, oneshot::Sender<bool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[allow(dead_code)]
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcResponse {
+    jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+}
+
+impl RpcResponse {
+    fn ok(id: Option<Value>, result: Value) -> Self {
+        Self { jsonrpc: "2.0".to_string(), result: Some(result), error: None, id }
+    }
+
+    fn err(id: Option<Value>, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            result: None,
+            error: Some(RpcError { code, message: message.into() }),
+            id,
+        }
+    }
+}
+
+// JSON-RPC error codes (standard range plus bridge-specific ones)
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const DEVICE_ERROR: i32 = -32000;
+const USER_REJECTED: i32 = -32001;
+const ORIGIN_NOT_ALLOWED: i32 = -32002;
+
+#[derive(Debug, Deserialize)]
+struct DeviceParams {
+    device_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetAddressParams {
+    device_id: Option<String>,
+    path: String,
+    #[serde(default = "default_coin_name")]
+    coin_name: String,
+    script_type: Option<String>,
+    show_display: Option<bool>,
+    multisig: Option<crate::device::multisig::MultisigConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignTransactionParams {
+    device_id: Option<String>,
+    #[serde(default = "default_coin_name")]
+    coin: String,
+    inputs: Vec<crate::commands::BitcoinUtxoInput>,
+    outputs: Vec<crate::commands::BitcoinUtxoOutput>,
+    #[serde(default = "default_tx_version")]
+    version: u32,
+    #[serde(default)]
+    lock_time: u32,
+    #[serde(default)]
+    verify_signatures: bool,
+    #[serde(default)]
+    verify_change: bool,
+}
+
+fn default_coin_name() -> String {
+    crate::commands::network_mode().coin_name().to_string()
+}
+
+fn default_tx_version() -> u32 {
+    1
+}
+
+/// Start the bridge server and keep it running until `shutdown` is cancelled
+pub async fn start_bridge(app: AppHandle, shutdown: CancellationToken) -> Result<(), Box<dyn std::error::Error>> {
+    let device_queue_manager = app
+        .try_state::<DeviceQueueManager>()
+        .ok_or("DeviceQueueManager not available")?
+        .inner()
+        .clone();
+
+    let mut allowed_origins: Vec<String> = DEFAULT_ALLOWED_ORIGINS.iter().map(|o| o.to_string()).collect();
+    if let Ok(Some(configured)) = crate::commands::get_preference("bridge_allowed_origins".to_string()).await {
+        allowed_origins.extend(
+            configured
+                .split(',')
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty()),
+        );
+    }
+    let policy_pref = crate::commands::get_preference("bridge_confirmation_policy".to_string()).await.ok().flatten();
+    let confirmation_policy = ConfirmationPolicy::from_preference(policy_pref.as_deref());
+
+    // Route confirmation answers from the frontend back to the waiting request
+    let listener_id = app.listen("bridge:confirm-response", |event| {
+        let Ok(payload) = serde_json::from_str::<Value>(event.payload()) else {
+            warn!("Ignoring malformed bridge:confirm-response payload");
+            return;
+        };
+        let request_id = payload.get("requestId").and_then(|v| v.as_str()).unwrap_or_default();
+        let approved = payload.get("approved").and_then(|v| v.as_bool()).unwrap_or(false);
+        if let Some(sender) = lock_or_recover(&PENDING_CONFIRMATIONS, "bridge confirmations").remove(request_id) {
+            let _ = sender.send(approved);
+        }
+    });
+
+    let state = Arc::new(BridgeState {
+        app: app.clone(),
+        device_queue_manager,
+        allowed_origins,
+        confirmation_policy,
+    });
+
+    let router = Router::new()
+        .route("/", post(rpc_handle))
+        .route("/rpc", post(rpc_handle))
+        .route("/metrics", get(metrics_handle))
+        .with_state(state);
+
+    let listener = TcpListener::bind(BRIDGE_ADDR).await?;
+    info!("🌉 JSON-RPC bridge listening on http://{}", BRIDGE_ADDR);
+
+    let result = axum::serve(listener, router)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await;
+
+    app.unlisten(listener_id);
+    // Reject anything still waiting on the user
+    lock_or_recover(&PENDING_CONFIRMATIONS, "bridge confirmations").clear();
+    info!("🌉 JSON-RPC bridge stopped");
+
+    result.map_err(|e| e.into())
+}
+
+/// Reject requests from pages that aren't explicitly allowed, and requests that
+/// reached us through a rebound hostname
+fn check_origin(state: &BridgeState, headers: &HeaderMap) -> Result<Option<String>, String> {
+    if let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) {
+        let host_name = host.rsplit_once(':').map(|(h, _)| h).unwrap_or(host);
+        if host_name != "127.0.0.1" && host_name != "localhost" {
+            return Err(format!("Host {} is not allowed", host));
+        }
+    }
+
+    match headers.get(header::ORIGIN).and_then(|o| o.to_str().ok()) {
+        // Native applications don't send an Origin header
+        None => Ok(None),
+        Some(origin) => {
+            let origin = origin.trim_end_matches('/');
+            if state.allowed_origins.iter().any(|allowed| allowed == origin) {
+                Ok(Some(origin.to_string()))
+            } else {
+                Err(format!("Origin {} is not allowed", origin))
+            }
+        }
+    }
+}
+
+/// Prometheus scrape target; same Host/Origin checks as the RPC routes
+async fn metrics_handle(State(state): State<Arc<BridgeState>>, headers: HeaderMap) -> impl IntoResponse {
+    if let Err(e) = check_origin(&state, &headers) {
+        warn!("🚫 Metrics request rejected: {}", e);
+        return (StatusCode::FORBIDDEN, [(header::CONTENT_TYPE, "text/plain; charset=utf-8")], e);
+    }
+    let text = crate::device::metrics::metrics_text_for(&state.device_queue_manager).await;
+    (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text)
+}
+
+async fn rpc_handle(
+    State(state): State<Arc<BridgeState>>,
+    headers: HeaderMap,
+    body: String,
+) -> impl IntoResponse {
+    let origin = match check_origin(&state, &headers) {
+        Ok(origin) => origin,
+        Err(e) => {
+            warn!("🚫 Bridge request rejected: {}", e);
+            return (StatusCode::FORBIDDEN, Json(RpcResponse::err(None, ORIGIN_NOT_ALLOWED, e)));
+        }
+    };
+
+    let request: RpcRequest = match serde_json::from_str(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            error!("Invalid bridge request: {}", e);
+            return (StatusCode::OK, Json(RpcResponse::err(None, PARSE_ERROR, "Parse error")));
+        }
+    };
+
+    info!("🌉 Bridge request: {} (origin: {})", request.method, origin.as_deref().unwrap_or("none"));
+
+    if state.confirmation_policy.requires_confirmation(&request.method)
+        && !request_confirmation(&state.app, origin.as_deref(), &request.method, &request.params).await
+    {
+        return (StatusCode::OK, Json(RpcResponse::err(request.id, USER_REJECTED, "Request rejected by user")));
+    }
+
+    let response = match dispatch(&state, &request.method, request.params).await {
+        Ok(result) => RpcResponse::ok(request.id, result),
+        Err((code, message)) => RpcResponse::err(request.id, code, message),
+    };
+    (StatusCode::OK, Json(response))
+}
+
+/// Ask the user to approve a bridge call and wait for the answer.
+/// Unanswered prompts are treated as rejections.
+async fn request_confirmation(app: &AppHandle, origin: Option<&str>, method: &str, params: &Value) -> bool {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = oneshot::channel();
+    lock_or_recover(&PENDING_CONFIRMATIONS, "bridge confirmations").insert(request_id.clone(), tx);
+
+    let payload = json!({
+        "requestId": request_id,
+        "origin": origin,
+        "method": method,
+        "params": params,
+    });
+    if let Err(e) = app.emit("bridge:confirm-request", &payload) {
+        error!("Failed to emit bridge:confirm-request: {}", e);
+        lock_or_recover(&PENDING_CONFIRMATIONS, "bridge confirmations").remove(&request_id);
+        return false;
+    }
+
+    match tokio::time::timeout(CONFIRMATION_TIMEOUT, rx).await {
+        Ok(Ok(approved)) => approved,
+        _ => {
+            lock_or_recover(&PENDING_CONFIRMATIONS, "bridge confirmations").remove(&request_id);
+            warn!("⏱️ Bridge confirmation {} timed out or was dropped", request_id);
+            false
+        }
+    }
+}
+
+async fn dispatch(state: &BridgeState, method: &str, params: Value) -> Result<Value, (i32, String)> {
+    match method {
+        "list_devices" => {
+            let devices: Vec<_> = keepkey_rust::features::list_connected_devices()
+                .into_iter()
+                .filter(|d| d.is_keepkey)
+                .collect();
+            Ok(json!(devices))
+        }
+        "get_features" => {
+            let params: DeviceParams = parse_params(params)?;
+            let device_id = resolve_device_id(params.device_id)?;
+            let queue_handle = state
+                .device_queue_manager
+                .get_or_spawn_by_id(&device_id)
+                .await
+                .ok_or((DEVICE_ERROR, format!("Device {} not found", device_id)))?;
+            let raw_features = queue_handle
+                .get_features()
+                .await
+                .map_err(|e| (DEVICE_ERROR, format!("Failed to get features: {}", e)))?;
+            Ok(json!(crate::commands::convert_features_to_device_features(raw_features)))
+        }
+        "get_address" => {
+            let params: GetAddressParams = parse_params(params)?;
+            let device_id = resolve_device_id(params.device_id)?;
+            let request = DeviceRequest::GetAddress {
+                path: params.path,
+                coin_name: params.coin_name,
+                script_type: params.script_type,
+                show_display: params.show_display,
+                multisig: params.multisig,
+            };
+            match queue_request(state, device_id, request).await? {
+                DeviceResponse::Address { address, .. } => Ok(json!({ "address": address })),
+                _ => Err((DEVICE_ERROR, "Unexpected response for get_address".to_string())),
+            }
+        }
+        "sign_transaction" => {
+            let params: SignTransactionParams = parse_params(params)?;
+            let device_id = resolve_device_id(params.device_id)?;
+            let request = DeviceRequest::SignTransaction {
+                coin: params.coin,
+                inputs: params.inputs,
+                outputs: params.outputs,
+                version: params.version,
+                lock_time: params.lock_time,
+                verify_signatures: params.verify_signatures,
+                verify_change: params.verify_change,
+            };
+            match queue_request(state, device_id, request).await? {
+                DeviceResponse::SignedTransaction { signed_tx, txid, .. } => Ok(json!({ "signed_tx": signed_tx, "txid": txid })),
+                _ => Err((DEVICE_ERROR, "Unexpected response for sign_transaction".to_string())),
+            }
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, (i32, String)> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+/// Use the requested device, else the active one, else the first connected KeepKey
+fn resolve_device_id(device_id: Option<String>) -> Result<String, (i32, String)> {
+    if let Some(device_id) = device_id.or_else(crate::device::active::active_device) {
+        return Ok(device_id);
+    }
+    keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .find(|d| d.is_keepkey)
+        .map(|d| d.unique_id)
+        .ok_or((DEVICE_ERROR, "No KeepKey devices connected".to_string()))
+}
+
+/// Run a request through the same queue path the vault UI uses, so PIN and
+/// update gating apply to bridge callers too
+async fn queue_request(state: &BridgeState, device_id: String, request: DeviceRequest) -> Result<DeviceResponse, (i32, String)> {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let wrapper = DeviceRequestWrapper {
+        device_id,
+        request_id: request_id.clone(),
+        request,
+    };
+
+    crate::device::queue::add_to_device_queue(wrapper, state.app.state(), state.app.state(), state.app.clone())
+        .await
+        .map_err(|e| (DEVICE_ERROR, e))?;
+
+    let last_responses = state
+        .app
+        .state::<Arc<tokio::sync::Mutex<HashMap<String, DeviceResponse>>>>();
+    let response = last_responses.lock().await.get(&request_id).cloned();
+    response.ok_or((DEVICE_ERROR, "No response recorded for request".to_string()))
+}
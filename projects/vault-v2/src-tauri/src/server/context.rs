@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use tracing::info;
 
+use crate::commands::lock_or_recover;
+
 /// Global context for the currently selected device
 static DEVICE_CONTEXT: Lazy<Mutex<Option<DeviceContext>>> = Lazy::new(|| Mutex::new(None));
 
@@ -40,13 +42,13 @@ pub struct SetContextRequest {
 
 /// Get the current device context
 pub async fn get_context() -> axum::Json<ContextResponse> {
-    let context = DEVICE_CONTEXT.lock().unwrap().clone();
+    let context = lock_or_recover(&DEVICE_CONTEXT, "device context").clone();
     axum::Json(ContextResponse { context })
 }
 
 /// Set the current device context
 pub async fn set_context(payload: axum::Json<SetContextRequest>) -> axum::http::StatusCode {
-    let mut context = DEVICE_CONTEXT.lock().unwrap();
+    let mut context = lock_or_recover(&DEVICE_CONTEXT, "device context");
     *context = Some(DeviceContext {
         device_id: payload.device_id.clone(),
         btc_address: payload.btc_address.clone(),
@@ -66,7 +68,7 @@ pub async fn set_context(payload: axum::Json<SetContextRequest>) -> axum::http::
 /// Clear the current device context
 #[allow(dead_code)]
 pub async fn clear_context() -> axum::http::StatusCode {
-    let mut context = DEVICE_CONTEXT.lock().unwrap();
+    let mut context = lock_or_recover(&DEVICE_CONTEXT, "device context");
     *context = None;
     axum::http::StatusCode::NO_CONTENT
 }
@@ -74,16 +76,16 @@ pub async fn clear_context() -> axum::http::StatusCode {
 /// Get the current device ID from context
 #[allow(dead_code)]
 pub fn get_current_device_id() -> Option<String> {
-    DEVICE_CONTEXT.lock().unwrap().as_ref().map(|c| c.device_id.clone())
+    lock_or_recover(&DEVICE_CONTEXT, "device context").as_ref().map(|c| c.device_id.clone())
 }
 
 /// Get the current Bitcoin address from context
 #[allow(dead_code)]
 pub fn get_current_btc_address() -> Option<String> {
-    DEVICE_CONTEXT.lock().unwrap().as_ref().and_then(|c| c.btc_address.clone())
+    lock_or_recover(&DEVICE_CONTEXT, "device context").as_ref().and_then(|c| c.btc_address.clone())
 }
 
 /// Get both device ID and Bitcoin address from context
 pub fn get_current_context_info() -> Option<(String, Option<String>)> {
-    DEVICE_CONTEXT.lock().unwrap().as_ref().map(|c| (c.device_id.clone(), c.btc_address.clone()))
+    lock_or_recover(&DEVICE_CONTEXT, "device context").as_ref().map(|c| (c.device_id.clone(), c.btc_address.clone()))
 } 
\ No newline at end of file
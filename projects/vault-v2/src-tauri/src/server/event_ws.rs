@@ -0,0 +1,176 @@
+//! Optional WebSocket feed of device events for UIs running outside the app
+//! (remote management from a browser).
+//!
+//! Compiled only with the `event-ws` feature. Forwards every device event the
+//! `EventEmitter` sends to the Tauri frontend, from the same tap, so both see
+//! the same events with the same sequence numbers. Clients must present the
+//! `event_ws_token` preference; the feed is read-only. See
+//! `docs/device-events-websocket.md` for the schema.
+
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::events::EmitSpec;
+
+/// Default listen address; set `event_ws_bind` to serve other hosts
+pub const EVENT_WS_ADDR: &str = "127.0.0.1:1648";
+
+const TOKEN_KEY: &str = "event_ws_token";
+const BIND_KEY: &str = "event_ws_bind";
+
+struct EventWsState {
+    token: String,
+    shutdown: CancellationToken,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConnectParams {
+    token: Option<String>,
+}
+
+/// The configured token, or a new random one saved as the preference so it
+/// survives restarts and can be copied into the remote UI
+async fn load_or_create_token() -> Result<String, String> {
+    if let Some(token) = crate::commands::get_preference(TOKEN_KEY.to_string()).await?.filter(|t| !t.trim().is_empty()) {
+        return Ok(token.trim().to_string());
+    }
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    crate::commands::set_preference(TOKEN_KEY.to_string(), token.clone()).await?;
+    info!("🔑 Generated an event WebSocket token (preference {})", TOKEN_KEY);
+    Ok(token)
+}
+
+/// Whether a connect request carries the token, as `?token=` (browsers can't
+/// set headers on a WebSocket) or an `Authorization: Bearer` header
+fn authorized(expected: &str, query_token: Option<&str>, headers: &HeaderMap) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    [query_token, bearer]
+        .into_iter()
+        .flatten()
+        .any(|token| crate::device::compare::constant_time_eq(token.trim().as_bytes(), expected.as_bytes()))
+}
+
+/// A text frame: `{ "event": "device:ready", "payload": { ... } }`
+fn event_frame(spec: &EmitSpec) -> String {
+    serde_json::json!({ "event": spec.event, "payload": spec.payload }).to_string()
+}
+
+/// Sent in place of the events a slow client missed
+fn lagged_frame(missed: u64) -> String {
+    serde_json::json!({ "event": "ws:lagged", "payload": { "missed": missed } }).to_string()
+}
+
+/// Start the WebSocket server and keep it running until `shutdown` is cancelled
+pub async fn start_event_ws(shutdown: CancellationToken) -> Result<(), Box<dyn std::error::Error>> {
+    let token = load_or_create_token().await?;
+    let addr = crate::commands::get_preference(BIND_KEY.to_string())
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| EVENT_WS_ADDR.to_string());
+
+    let state = Arc::new(EventWsState { token, shutdown: shutdown.clone() });
+    let router = Router::new().route("/events", get(connect_handle)).with_state(state);
+
+    let listener = TcpListener::bind(&addr).await?;
+    info!("📡 Device event WebSocket listening on ws://{}/events", addr);
+
+    let result = axum::serve(listener, router)
+        .with_graceful_shutdown(async move { shutdown.cancelled().await })
+        .await;
+    info!("📡 Device event WebSocket stopped");
+
+    result.map_err(|e| e.into())
+}
+
+async fn connect_handle(
+    ws: WebSocketUpgrade,
+    Query(params): Query<ConnectParams>,
+    headers: HeaderMap,
+    State(state): State<Arc<EventWsState>>,
+) -> Response {
+    if !authorized(&state.token, params.token.as_deref(), &headers) {
+        warn!("Rejected an event WebSocket connection without a valid token");
+        return (StatusCode::UNAUTHORIZED, "Missing or invalid token").into_response();
+    }
+    // Subscribe before the upgrade so nothing emitted in between is missed
+    let events = crate::events::subscribe_emitted();
+    let shutdown = state.shutdown.clone();
+    ws.on_upgrade(move |socket| forward_events(socket, events, shutdown))
+}
+
+async fn forward_events(
+    mut socket: WebSocket,
+    mut events: tokio::sync::broadcast::Receiver<EmitSpec>,
+    shutdown: CancellationToken,
+) {
+    info!("📡 Event WebSocket client connected");
+    loop {
+        let frame = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            incoming = socket.recv() => match incoming {
+                // Read-only feed: anything but a close is ignored
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            event = events.recv() => match event {
+                Ok(spec) => event_frame(&spec),
+                Err(RecvError::Lagged(missed)) => lagged_frame(missed),
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if socket.send(Message::Text(frame)).await.is_err() {
+            break;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+    info!("📡 Event WebSocket client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connections_need_the_token() {
+        let token = "5f0c3e1a9b7d4e2f8a6c1b3d5e7f9a0b";
+        let none = HeaderMap::new();
+        assert!(authorized(token, Some(token), &none));
+        assert!(!authorized(token, None, &none));
+        assert!(!authorized(token, Some(""), &none));
+        assert!(!authorized(token, Some("5f0c3e1a9b7d4e2f8a6c1b3d5e7f9a0c"), &none));
+
+        let mut bearer = HeaderMap::new();
+        bearer.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        assert!(authorized(token, None, &bearer));
+        let mut basic = HeaderMap::new();
+        basic.insert(header::AUTHORIZATION, format!("Basic {}", token).parse().unwrap());
+        assert!(!authorized(token, None, &basic));
+    }
+
+    #[test]
+    fn test_frames() {
+        let spec = EmitSpec::new("device:ready", serde_json::json!({ "device_id": "kk-1", "sequence": 7 }));
+        let frame: serde_json::Value = serde_json::from_str(&event_frame(&spec)).unwrap();
+        assert_eq!(frame, serde_json::json!({ "event": "device:ready", "payload": { "device_id": "kk-1", "sequence": 7 } }));
+
+        let frame: serde_json::Value = serde_json::from_str(&lagged_frame(12)).unwrap();
+        assert_eq!(frame["event"], "ws:lagged");
+        assert_eq!(frame["payload"]["missed"], 12);
+    }
+}
@@ -1,6 +1,10 @@
 pub mod routes;
 pub mod context;
 pub mod proxy;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+#[cfg(feature = "event-ws")]
+pub mod event_ws;
 
 use axum::{
     Router,
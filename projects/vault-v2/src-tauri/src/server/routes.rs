@@ -4,12 +4,14 @@ use axum::{
     Json,
     response::IntoResponse,
 };
+use keepkey_rust::device_queue::QueueTimeout;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::sync::Arc;
 use tracing::{info, error, warn};
 use utoipa::ToSchema;
 
+use crate::commands::DeviceQueueManagerExt;
 use crate::server::ServerState;
 use crate::server::context::{self};
 
@@ -152,28 +154,11 @@ pub async fn api_list_devices(State(state): State<Arc<ServerState>>) -> Result<J
     
     for device in devices {
         // For each device, try to get features through the queue
-        let queue_handle = {
-            let mut manager = queue_manager.lock().await;
-            
-            if let Some(handle) = manager.get(&device.unique_id) {
-                handle.clone()
-            } else {
-                // Spawn a new device worker if not exists
-                let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(
-                    device.unique_id.clone(), 
-                    device.clone()
-                );
-                manager.insert(device.unique_id.clone(), handle.clone());
-                handle
-            }
-        };
+        let queue_handle = queue_manager.get_or_spawn(&device.unique_id, &device).await;
         
         // Try to get features through the queue (non-blocking, with timeout)
-        let keepkey_info = match tokio::time::timeout(
-            std::time::Duration::from_millis(500),
-            queue_handle.get_features()
-        ).await {
-            Ok(Ok(raw_features)) => {
+        let keepkey_info = match queue_handle.get_features_with_timeout(std::time::Duration::from_millis(500)).await {
+            Ok(raw_features) => {
                 let features = crate::commands::convert_features_to_device_features(raw_features);
                 Some(KeepKeyInfo {
                     label: features.label.clone(),
@@ -186,7 +171,7 @@ pub async fn api_list_devices(State(state): State<Arc<ServerState>>) -> Result<J
                     bootloader_mode: features.bootloader_mode,
                 })
             }
-            Ok(Err(e)) => {
+            Err(e) if !e.is::<QueueTimeout>() => {
                 warn!("Failed to get features for device {} through queue: {}", device.unique_id, e);
                 None
             }
@@ -257,21 +242,7 @@ pub async fn api_get_features(State(state): State<Arc<ServerState>>) -> Result<J
     
     // Get or create device queue handle
     let queue_manager = &state.device_queue_manager;
-    let queue_handle = {
-        let mut manager = queue_manager.lock().await;
-        
-        if let Some(handle) = manager.get(&device_id) {
-            handle.clone()
-        } else {
-            // Spawn a new device worker
-            let handle = keepkey_rust::device_queue::DeviceQueueFactory::spawn_worker(
-                device_id.clone(), 
-                device.clone()
-            );
-            manager.insert(device_id.clone(), handle.clone());
-            handle
-        }
-    };
+    let queue_handle = queue_manager.get_or_spawn(&device_id, device).await;
     
     // Get device features through the queue
     match queue_handle.get_features().await {
@@ -0,0 +1,186 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::State;
+
+use crate::device::benchmark::IoBenchmark;
+use crate::device::connection::ConnectionDiagnosis;
+use crate::device::oob_stats::OobStats;
+use crate::device::storage::StorageStats;
+use crate::device::telemetry::Telemetry;
+use crate::event_log::EventLogEntry;
+use crate::commands::{DeviceQueueManager, DeviceQueueManagerExt};
+
+/// How many device log entries go into a bundle
+const BUNDLE_LOG_ENTRIES: usize = 500;
+
+/// Everything support needs to diagnose a device problem, in one JSON file
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupportBundle {
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub connected_devices: Vec<keepkey_rust::friendly_usb::FriendlyUsbDevice>,
+    pub io_benchmarks: HashMap<String, IoBenchmark>,
+    /// Last `diagnose_connection` result per device
+    pub connection_diagnoses: HashMap<String, ConnectionDiagnosis>,
+    /// From the features last seen for each device
+    pub storage_stats: HashMap<String, StorageStats>,
+    /// How often feature probes needed the OOB bootloader fallback
+    pub oob_stats: OobStats,
+    /// Health telemetry of devices whose firmware reports it
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub telemetry: HashMap<String, Telemetry>,
+    pub recent_device_logs: Vec<serde_json::Value>,
+    /// Device events the monitor emitted, oldest first
+    pub recent_events: Vec<EventLogEntry>,
+}
+
+pub async fn collect_support_bundle(app: &tauri::AppHandle) -> SupportBundle {
+    let recent_device_logs = crate::commands::get_recent_device_logs(Some(BUNDLE_LOG_ENTRIES))
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to read device logs for support bundle: {}", e);
+            Vec::new()
+        });
+
+    SupportBundle {
+        generated_at: chrono::Utc::now(),
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        connected_devices: keepkey_rust::features::list_connected_devices(),
+        io_benchmarks: crate::device::benchmark::last_benchmarks(),
+        connection_diagnoses: crate::device::connection::last_diagnoses(),
+        storage_stats: crate::commands::all_cached_features()
+            .iter()
+            .map(|(device_id, features)| (device_id.clone(), crate::device::storage::storage_stats_from_features(features)))
+            .collect(),
+        oob_stats: crate::device::oob_stats::snapshot(),
+        telemetry: crate::device::telemetry::all_telemetry(),
+        recent_device_logs,
+        recent_events: crate::event_log::with_log(|log| log.recent(None)),
+    }
+}
+
+fn support_bundle_dir() -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
+    let dir = home_dir.join(".keepkey").join("support");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create support directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Write a support bundle to ~/.keepkey/support and return its path
+#[tauri::command]
+pub async fn export_support_bundle(app: tauri::AppHandle) -> Result<String, String> {
+    let bundle = collect_support_bundle(&app).await;
+
+    let path = support_bundle_dir()?.join(format!(
+        "support-bundle-{}.json",
+        bundle.generated_at.format("%Y%m%d-%H%M%S")
+    ));
+    let json = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| format!("Failed to serialize support bundle: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write support bundle: {}", e))?;
+
+    println!("📦 Support bundle written to {}", path.display());
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Every protobuf field of `features`, bytes hex encoded
+#[cfg(feature = "raw-features")]
+fn raw_features_json(features: &keepkey_rust::messages::Features) -> serde_json::Value {
+    serde_json::json!({
+        "vendor": features.vendor,
+        "major_version": features.major_version,
+        "minor_version": features.minor_version,
+        "patch_version": features.patch_version,
+        "bootloader_mode": features.bootloader_mode,
+        "device_id": features.device_id,
+        "pin_protection": features.pin_protection,
+        "passphrase_protection": features.passphrase_protection,
+        "language": features.language,
+        "label": features.label,
+        "coins": features.coins.iter().map(|coin| coin.coin_name.clone()).collect::<Vec<_>>(),
+        "initialized": features.initialized,
+        "revision": features.revision.as_ref().map(hex::encode),
+        "bootloader_hash": features.bootloader_hash.as_ref().map(hex::encode),
+        "imported": features.imported,
+        "pin_cached": features.pin_cached,
+        "passphrase_cached": features.passphrase_cached,
+        "policies": features
+            .policies
+            .iter()
+            .map(|p| serde_json::json!({ "policy_name": p.policy_name, "enabled": p.enabled }))
+            .collect::<Vec<_>>(),
+        "model": features.model,
+        "firmware_variant": features.firmware_variant,
+        "firmware_hash": features.firmware_hash.as_ref().map(hex::encode),
+        "no_backup": features.no_backup,
+        "wipe_code_protection": features.wipe_code_protection,
+        "auto_lock_delay_ms": features.auto_lock_delay_ms,
+    })
+}
+
+/// Everything a device reports about itself, as JSON to paste into a bug
+/// report. Features aren't secret, so nothing is redacted. Devices that can't
+/// answer (DFU mode, a failed read) still export their USB details and the
+/// last features seen, with the error.
+#[tauri::command]
+pub async fn export_device_features(
+    unique_id: String,
+    queue_manager: State<'_, DeviceQueueManager>,
+) -> Result<serde_json::Value, String> {
+    let usb = keepkey_rust::features::list_connected_devices()
+        .into_iter()
+        .chain(keepkey_rust::features::list_dfu_devices())
+        .find(|d| d.unique_id == unique_id);
+
+    let mut error = None;
+    let mut raw = None;
+    if usb.as_ref().is_some_and(|d| d.is_dfu_mode()) {
+        error = Some("Device is in DFU mode and can't report features".to_string());
+    } else if let Some(queue_handle) = queue_manager.get_or_spawn_by_id(&unique_id).await {
+        match queue_handle.get_features_with_timeout(Duration::from_secs(30)).await {
+            Ok(features) => raw = Some(features),
+            Err(e) => error = Some(format!("Failed to get features: {}", e)),
+        }
+    }
+
+    let (features, source) = match &raw {
+        Some(raw) => (Some(crate::commands::convert_features_to_device_features(raw.clone())), Some("device")),
+        None => match crate::commands::cached_device_features(&unique_id) {
+            Some(cached) => (Some(cached), Some("cache")),
+            None => (None, None),
+        },
+    };
+    if usb.is_none() && features.is_none() {
+        return Err(format!("Device {} not found", unique_id));
+    }
+
+    let status = features
+        .as_ref()
+        .map(|f| crate::commands::evaluate_device_status(unique_id.clone(), Some(f)));
+    #[allow(unused_mut)]
+    let mut export = serde_json::json!({
+        "deviceId": unique_id,
+        "exportedAt": chrono::Utc::now(),
+        "usb": usb,
+        "features": features,
+        "featuresSource": source,
+        "status": status,
+        "error": error,
+    });
+    #[cfg(feature = "raw-features")]
+    {
+        export["rawFeatures"] = raw.as_ref().map(raw_features_json).unwrap_or(serde_json::Value::Null);
+    }
+    #[cfg(not(feature = "raw-features"))]
+    let _ = raw;
+
+    println!("🧾 Exported features of {} ({})", unique_id, source.unwrap_or("unavailable"));
+    Ok(export)
+}
@@ -4,7 +4,7 @@ import { FirmwareUpdateDialog } from './FirmwareUpdateDialog'
 import { SetupWizard } from './SetupWizard'
 import { EnterBootloaderModeDialog } from './EnterBootloaderModeDialog'
 import { PinUnlockDialog } from './PinUnlockDialog'
-import type { DeviceStatus, DeviceFeatures } from '../types/device'
+import type { DeviceStatus, DeviceFeatures, DeviceAccessError } from '../types/device'
 import { listen } from '@tauri-apps/api/event'
 import { invoke } from '@tauri-apps/api/core'
 import { useWallet } from '../contexts/WalletContext'
@@ -343,12 +343,7 @@ export const DeviceUpdateManager = ({ onComplete, onSetupWizardActiveChange }: D
       })
 
       // Listen for device access errors
-      const accessErrorUnsubscribe = listen<{
-        deviceId: string
-        error: string
-        errorType: string
-        status: string
-      }>('device:access-error', (event) => {
+      const accessErrorUnsubscribe = listen<DeviceAccessError>('device:access-error', (event) => {
         console.log('Device access error received:', event.payload)
         // Clear any pending dialogs when there's an access error
         setShowBootloaderUpdate(false)
@@ -68,8 +68,8 @@ const SeedVerificationWizard: React.FC<SeedVerificationWizardProps> = ({
     setSelectedWordCount(wordCount);
 
     try {
-      const verificationSession = await invoke<VerificationSession>('start_seed_verification', {
-        deviceId,
+      const verificationSession = await invoke<VerificationSession>('verify_seed', {
+        uniqueId: deviceId,
         wordCount
       });
 
@@ -100,8 +100,8 @@ const SeedVerificationWizard: React.FC<SeedVerificationWizardProps> = ({
           
           // Retry the verification
           console.log('Retrying seed verification...');
-          const retrySession = await invoke<VerificationSession>('start_seed_verification', {
-            deviceId,
+          const retrySession = await invoke<VerificationSession>('verify_seed', {
+            uniqueId: deviceId,
             wordCount
           });
 
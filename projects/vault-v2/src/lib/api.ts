@@ -663,7 +663,9 @@ export class DeviceQueueAPI {
     outputs: any[],
     version: number = 1,
     lockTime: number = 0,
-    requestId: string
+    requestId: string,
+    verifySignatures: boolean = false,
+    verifyChange: boolean = false
   ): Promise<string> {
     // Validation: deviceId must be present and valid
     if (!deviceId || typeof deviceId !== 'string' || deviceId.trim() === '') {
@@ -684,7 +686,9 @@ export class DeviceQueueAPI {
             inputs,
             outputs,
             version,
-            lock_time: lockTime
+            lock_time: lockTime,
+            verify_signatures: verifySignatures,
+            verify_change: verifyChange
           }
         }
       };
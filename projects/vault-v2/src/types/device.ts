@@ -8,10 +8,360 @@ export interface BootloaderCheck {
   needsUpdate: boolean
 }
 
+export type UpdateSeverity = 'recommended' | 'mandatory'
+
 export interface FirmwareCheck {
   currentVersion: string
   latestVersion: string
   needsUpdate: boolean
+  severity?: UpdateSeverity
+  requiredVersion?: string  // Set when a min_firmware_policy is configured
+  releaseNotes?: ReleaseNotes | null  // Notes of latestVersion when an update is offered
+}
+
+// Payload of policy:confirmation-required; the operation waits until
+// resolve_policy_confirmation(approvalId, approved) is called. A denied
+// operation fails with an error starting with "PolicyDenied:"
+export interface PolicyConfirmationRequired {
+  approval_id: string
+  operation: {
+    device_id: string
+    kind: 'sign' | 'wipe' | 'change_pin' | 'apply_settings'
+    [detail: string]: unknown
+  }
+}
+
+// Result of get_bootloader_policy, derived from the bootloader version
+export interface BootloaderPolicy {
+  bootloaderVersion: string | null
+  minFirmwareVersion: string | null
+  requiresSignature: boolean
+  vendorKeys: number[]  // signing key slots compiled into the bootloader
+}
+
+// Cosigner set passed as `multisig` to GetAddress and verify_address_ownership;
+// scriptType is then 'p2sh', 'p2wsh' or 'p2sh-p2wsh'
+export interface MultisigConfig {
+  m: number
+  xpubs: string[]   // account xpubs of every cosigner, any SLIP-132 prefix
+  our_index: number // which of xpubs is this device
+}
+
+// Result of get_device_telemetry (null when the firmware reports none)
+export interface Telemetry {
+  temperatureCelsius: number | null
+  supplyVoltageMv: number | null
+  uptimeSecs: number | null
+}
+
+// Markdown release notes from the bundled firmware manifest; locale is the
+// language actually returned (falls back to English)
+export interface ReleaseNotes {
+  version: string
+  locale: string
+  markdown: string
+}
+
+// Result of inspect_firmware_file; version and model are only known for
+// images that match a release in the bundled manifest
+export interface FirmwareMetadata {
+  version: string | null
+  model: string | null
+  signatureValid: boolean
+  size: number
+  hash: string  // sha256 of the code after the 256 byte header
+}
+
+// Rejection of inspect_firmware_file
+export type FirmwareFileError =
+  | { kind: 'io'; message: string }
+  | { kind: 'not_firmware' }
+  | { kind: 'truncated'; expected: number; actual: number }
+  | { kind: 'trailing_data'; expected: number; actual: number }
+
+// Payload of firmware:downgrade-warning; update_device_firmware refuses the
+// downgrade unless called with allowDowngrade: true (and never when blocked)
+export interface FirmwareDowngradeWarning {
+  deviceId: string
+  current: string
+  target: string
+  will_wipe: boolean
+  blocked_reason?: string | null  // Set when the bootloader will refuse the image
+}
+
+// Result of estimate_update_duration, also sent as firmware:update-estimate
+// just before a flash starts
+export interface UpdateEstimate {
+  estimatedSecs: number
+  eraseSecs: number
+  throughputBytesPerSec: number
+  basis: 'previous_flash' | 'benchmark' | 'model_default'
+}
+
+export interface FirmwareUpdateEstimate {
+  deviceId: string
+  targetVersion: string
+  estimate: UpdateEstimate
+}
+
+// Payload of firmware:update-progress. Only the 'preparing' phase is
+// cancellable; after it the bootloader erases the firmware.
+export interface FirmwareUpdateProgress {
+  deviceId: string
+  attempt: number
+  phase: 'preparing' | 'uploading' | 'reconnecting' | 'complete' | 'failed'
+  cancellable: boolean
+  resumedFrom: number
+  position: number
+  total: number
+  percent: number
+}
+
+// Rejection of cancel_firmware_update
+export type CancelUpdateError =
+  | { kind: 'not_updating' }
+  | { kind: 'cancel_too_late' }
+
+// Payload of firmware:cancelled; the device is left in bootloader mode with
+// its firmware untouched
+export interface FirmwareCancelled {
+  deviceId: string
+  phase: 'preparing'
+}
+
+// One entry of get_update_history, oldest first. fromVersion is null when the
+// installed version wasn't known before the update.
+export interface UpdateRecord {
+  timestamp: string
+  kind: 'firmware' | 'bootloader'
+  fromVersion: string | null
+  toVersion: string
+  outcome: 'success' | 'failed'
+  error?: string
+}
+
+// Payload of firmware:update-eta, sent every 2s during the upload
+export interface FirmwareUpdateEta {
+  deviceId: string
+  elapsedSecs: number
+  remainingSecs: number
+  estimatedSecs: number
+}
+
+// Returned by get_device_state; see src-tauri/src/device/state.rs for allowed transitions
+export type DeviceState =
+  | 'disconnected'
+  | 'connected'
+  | 'probing'
+  | 'ready'
+  | 'needs_update'
+  | 'locked'
+  | 'error'
+
+// Payload of device:state-changed
+export interface DeviceStateChange {
+  deviceId: string
+  from: DeviceState
+  to: DeviceState
+  sequence?: number
+}
+
+// Payload of device:probe-failed, emitted once every feature probe of a
+// connected device has failed; reset_device_queue gives it a fresh set of attempts
+export interface DeviceProbeFailed {
+  deviceId: string
+  attempts: number
+  lastError: string
+  recoveryAction: 'reset_device_queue'
+  status: 'probe_failed'
+  sequence?: number
+}
+
+export type AttentionReason =
+  | 'firmware_update'
+  | 'bootloader_update'
+  | 'firmware_bootloader_mismatch'
+  | 'not_initialized'
+  | 'locked'
+  | 'unreachable'
+  | 'update_available'
+
+// Payload of device:needs-attention, emitted when a device's reasons change;
+// an empty list means the device no longer needs attention
+export interface DeviceNeedsAttention {
+  unique_id: string
+  reasons: AttentionReason[]
+  sequence?: number
+}
+
+export type AccessErrorType =
+  | 'DEVICE_CLAIMED'
+  | 'PERMISSION_DENIED'
+  | 'DRIVER_MISSING'
+  | 'TRANSPORT_RESET'
+  | 'TIMEOUT'
+
+// A step the user can take to resolve a device access error; `command` is a
+// shell command that applies the fix (e.g. installing udev rules on Linux)
+export interface AccessErrorHint {
+  id: string
+  title: string
+  detail: string
+  command?: string
+}
+
+// Payload of device:access-error, emitted when a device can't be opened
+export interface DeviceAccessError {
+  deviceId: string
+  error: string
+  errorType: AccessErrorType
+  hints: AccessErrorHint[]
+  status: 'error'
+  sequence?: number
+}
+
+// Payload of device:permission-issue (Linux only): KeepKey device nodes this
+// user can't open, emitted at startup and after repeated PERMISSION_DENIED
+// errors. Writing `rules` to `rulesPath` (or running `command`) fixes it.
+export interface DevicePermissionIssue {
+  nodes: { path: string; mode: string; uid: number; gid: number }[]
+  rulesPath: string
+  rules: string
+  rulesInstalled: boolean
+  command: string
+  sequence?: number
+}
+
+// Payload of device:signature-verification-failed, a warning emitted when a
+// transaction signed with verify_signatures has signatures that don't verify
+export interface SignatureVerificationFailed {
+  device_id: string
+  request_id: string
+  failed_inputs: { input: number; valid: boolean; error: string | null }[]
+  error: string | null
+}
+
+// Payload of device:signing-progress, emitted as each input signature comes
+// back. Sessions on different devices are independent; cancel one with
+// cancel_signing(uniqueId).
+export interface SigningProgress {
+  device_id: string
+  request_id: string
+  signed: number
+  inputs: number
+}
+
+// Result of check_entropy_quality: monobit and runs tests over raw GetEntropy
+// output. A smoke test for an obviously broken RNG, not a NIST suite.
+export interface EntropyTestResult {
+  pValue: number
+  passed: boolean
+}
+
+export interface EntropyReport {
+  uniqueId: string
+  samples: number
+  bytes: number
+  monobit: EntropyTestResult
+  runs: EntropyTestResult
+  repeatedSamples: boolean
+  passed: boolean
+}
+
+// Payload of operation:countdown, sent every second while the device shows a
+// prompt of a request waiting on the user (confirmation_countdown preference);
+// stops once the prompt is answered or cancelled
+export interface OperationCountdown {
+  unique_id: string
+  remaining_ms: number
+}
+
+// Payload of tx:broadcast, emitted once a signed transaction was accepted
+export interface TxBroadcast {
+  txid: string
+}
+
+// Result of sign_and_broadcast
+export interface SignedAndBroadcast {
+  signed_tx: string
+  txid: string
+}
+
+// Payload of tx:chunk from sign_transaction_streaming; join data in index order
+export interface TxChunk {
+  request_id: string
+  index: number
+  total_chunks: number
+  data: string
+}
+
+// Payload of tx:complete and result of sign_transaction_streaming; size is in bytes
+export interface StreamedTransaction {
+  request_id: string
+  total_chunks: number
+  size: number
+}
+
+// Payload of device:active-changed; unique_id is null when the active device
+// disconnected and the user should pick another
+export interface DeviceActiveChanged {
+  unique_id: string | null
+  previous: string | null
+  reason: 'selected' | 'disconnected' | 'restored' | 'forgotten'
+  sequence?: number
+}
+
+// Returned by get_device_screen_hint: the prompt currently on the device screen
+export type ScreenHint =
+  | 'idle'
+  | 'confirm_output'
+  | 'confirm_fee'
+  | 'confirm_transaction'
+  | 'show_address'
+  | 'sign_message'
+  | 'enter_pin'
+  | 'enter_passphrase'
+  | 'enter_recovery_word'
+  | 'confirm_recovery_word'
+  | 'confirm_reset'
+  | 'confirm_wipe'
+  | 'confirm_firmware_update'
+  | 'confirm_pin_change'
+  | 'confirm_settings'
+  | 'confirm_action'
+
+// Returned by get_oob_stats: how often feature probes needed the OOB bootloader
+// fallback, keyed by firmware version ('bootloader-<version>' in bootloader mode)
+export interface OobCounters {
+  primarySuccess: number
+  oobFallback: number
+  oobSuccess: number
+  oobFailure: number
+}
+
+export interface OobStats {
+  byFirmware: Record<string, OobCounters>
+  total: OobCounters
+}
+
+// Returned by get_storage_stats, tagged by status
+export type StorageStats =
+  | {
+      status: 'supported'
+      seedCount: number
+      importedKeyCount: number
+      usedSlots?: number | null  // Not reported by any KeepKey firmware yet
+      freeSlots?: number | null
+    }
+  | { status: 'unsupported'; reason: string }
+
+// Returned by the get_device_model command
+export interface DeviceModelInfo {
+  model: string              // e.g. "K1-14AM", "unknown" if undetectable
+  revision?: string          // "legacy" (HID-only, v1 bootloader) or "webusb"
+  bootloaderVersion?: string
+  firmwareVersion?: string
+  firmwareRevision?: string  // SCM revision of the running firmware (hex)
 }
 
 export interface InitializationCheck {
@@ -32,6 +382,20 @@ export interface DeviceStatus {
   bootloaderCheck?: BootloaderCheck
   firmwareCheck?: FirmwareCheck
   initializationCheck?: InitializationCheck
+  versionMismatch?: VersionMismatch  // Update the bootloader before anything else
+}
+
+// Firmware installed on a bootloader older than it requires
+export interface VersionMismatch {
+  firmware: string
+  bootloader: string
+  required_bootloader: string
+}
+
+// Payload of device:incompatible-versions
+export interface DeviceIncompatibleVersions extends VersionMismatch {
+  unique_id: string
+  sequence?: number
 }
 
 export interface DeviceFeatures {
@@ -57,4 +421,300 @@ export interface DeviceFeatures {
   wipeCodeProtection: boolean
   autoLockDelayMs?: number
   policies: string[]
-} 
\ No newline at end of file
+  flags: number  // Enabled policies as bits, see apply_flags
+  networks: string[]  // Networks the firmware supports: "mainnet", "testnet"
+} 
+// One entry of devices:initial-snapshot, emitted once at startup when the
+// coalesce_initial_scan preference is on; per-device events follow it and
+// continue from each entry's sequence
+export interface DeviceWithStatus {
+  device: {
+    unique_id: string
+    name: string
+    vid: number
+    pid: number
+    manufacturer?: string | null
+    product?: string | null
+    serial_number?: string | null
+    is_keepkey: boolean
+  }
+  features: DeviceFeatures | null
+  status: DeviceStatus | null
+  state: DeviceState
+  sequence: number | null
+}
+
+export interface DevicesInitialSnapshot {
+  devices: DeviceWithStatus[]
+}
+
+// Result of begin_session; on pin_required answer the PIN matrix with
+// send_pin_matrix_ack, on passphrase_required pass one, then call it again
+export type SessionStart =
+  | { status: 'active'; expiresInMs: number }
+  | { status: 'pin_required' }
+  | { status: 'passphrase_required' }
+
+// Payload of device:session-ended; reads without display prompt again from here
+export interface DeviceSessionEnded {
+  deviceId: string
+  reason: 'expired' | 'ended' | 'disconnected'
+}
+
+// Network the app works on, see get_network_mode/set_network_mode. Regtest
+// uses the device's Testnet coin, so native segwit addresses show as tb1...
+export type NetworkMode = 'mainnet' | 'testnet' | 'regtest'
+
+// Payload of network:changed; cached addresses and xpubs were dropped
+export interface NetworkChanged {
+  mode: NetworkMode
+  previous: NetworkMode
+}
+
+export type ConnectionQuality = 'good' | 'marginal' | 'poor'
+
+// Result of diagnose_connection
+export interface ConnectionDiagnosis {
+  deviceId: string
+  quality: ConnectionQuality
+  roundTrips: number
+  errors: number
+  avgLatencyMs: number
+  latencyStddevMs: number
+  suggestion?: string | null
+  timestamp: string
+}
+
+// Payload of device:connection-degraded, emitted when transport errors pile up
+// during normal use
+export interface DeviceConnectionDegraded {
+  deviceId: string
+  errors: number
+  windowSecs: number
+  suggestion: string
+}
+
+// Argument of set_power_mode; low_power polls for devices far less often
+export type PowerMode = 'normal' | 'low_power'
+
+export type EmitOutcome =
+  | { status: 'emitted' }
+  | { status: 'suppressed' }
+  | { status: 'failed'; error: string }
+
+// Entry of get_recent_events, the in-memory log of device events
+export interface EventLogEntry {
+  timestamp: string
+  event: string
+  deviceId?: string | null
+  sequence?: number | null
+  summary: string
+  outcome: EmitOutcome
+}
+
+// Payload of device:features-available, emitted after an event whose payload
+// was over its size limit went out with featuresWithheld: true instead of its
+// features. Fetch them with get_withheld_features.
+export interface DeviceFeaturesAvailable {
+  unique_id: string
+}
+
+// Entry of discover_accounts: an account with transaction history
+export interface DiscoveredAccount {
+  account: number
+  path: string
+  xpub: string
+  firstUsedIndex: number
+}
+
+// Result of get_next_receive_address: the first receive address of the
+// account without on-chain history
+export interface AddressInfo {
+  address: string
+  path: string
+  index: number
+}
+
+// Result of export_descriptors: BIP-380 descriptors (with checksum) of an
+// account's receive and change chains, importable with Bitcoin Core's
+// importdescriptors
+export interface WalletDescriptors {
+  external: string
+  internal: string
+}
+
+// Payload of device:reconnected-after-operation: the device rebooted after a
+// settings change and is usable again, possibly under a new deviceId
+export interface DeviceReconnectedAfterOperation {
+  deviceId: string
+  previousDeviceId: string
+  operation: string
+  features: DeviceFeatures
+}
+
+// Argument of supports_operation
+export type DeviceOperation =
+  | 'get_features'
+  | 'get_address'
+  | 'get_xpub'
+  | 'segwit_address'
+  | 'sign_transaction'
+  | 'segwit_signing'
+  | 'sign_message'
+  | 'change_label'
+  | 'change_pin'
+  | 'auto_lock_delay'
+  | 'apply_policies'
+  | 'wipe_device'
+  | 'reset_device'
+  | 'recover_device'
+  | 'seed_check'
+  | 'change_wipe_code'
+
+// Result of supports_operation; 'unknown' means try the operation and handle
+// its failure
+export type OperationSupport = 'supported' | 'unsupported' | 'unknown'
+
+// Named flag bits; FirmwareFlagsReport.bits is what apply_flags takes
+export interface FirmwareFlags {
+  shapeshift: boolean
+  pin_caching: boolean
+  experimental: boolean
+  advanced_mode: boolean
+}
+
+export interface FlagStatus {
+  policy: string
+  bit: number
+  state: 'enabled' | 'disabled' | 'unsupported'
+  min_firmware: string
+}
+
+// Result of get_firmware_flags
+export interface FirmwareFlagsReport {
+  bits: number
+  enabled: FirmwareFlags
+  flags: FlagStatus[]
+}
+
+// ButtonRequestType of the device protocol, as request_type of device:button-request
+export type ButtonRequestKind =
+  | 'other'
+  | 'fee_over_threshold'
+  | 'confirm_output'
+  | 'reset_device'
+  | 'confirm_word'
+  | 'wipe_device'
+  | 'protect_call'
+  | 'sign_tx'
+  | 'firmware_check'
+  | 'address'
+  | 'firmware_erase'
+  | 'confirm_transfer_to_account'
+  | 'confirm_transfer_to_node_path'
+  | 'change_label'
+  | 'change_language'
+  | 'enable_passphrase'
+  | 'disable_passphrase'
+  | 'encrypt_and_sign_message'
+  | 'encrypt_message'
+  | 'import_private_key'
+  | 'import_recovery_sentence'
+  | 'sign_identity'
+  | 'ping'
+  | 'remove_pin'
+  | 'change_pin'
+  | 'create_pin'
+  | 'get_entropy'
+  | 'sign_message'
+  | 'apply_policies'
+  | 'auto_lock_delay_ms'
+  | 'u2f_counter'
+  | 'confirm_eos_action'
+  | 'confirm_eos_budget'
+  | 'confirm_memo'
+  | 'remove_wipe_code'
+  | 'change_wipe_code'
+  | 'create_wipe_code'
+  | 'unknown'
+
+// Payload of device:button-request, sent each time the device waits for a
+// press. context is the request's data (e.g. the amount being confirmed).
+export interface DeviceButtonRequest {
+  device_id: string
+  request_type: ButtonRequestKind
+  code: number | null
+  context: string | null
+}
+
+// Payload of device:pin-required, sent for each PIN matrix the device shows.
+// Changing the PIN asks for unlock (current PIN), then new_pin and
+// confirm_new_pin.
+export type PinPurpose = 'unlock' | 'new_pin' | 'confirm_new_pin'
+
+export interface DevicePinRequired {
+  device_id: string
+  purpose: PinPurpose
+}
+
+// Payload of device:confirm-label, sent when the device shows a new label for
+// confirmation. new_label is what the device received, which is what the user
+// should compare against what they typed.
+export interface DeviceConfirmLabel {
+  device_id: string
+  new_label: string
+}
+
+// Scenario of enable_mock_device / disable_mock_device (mock-device builds only)
+export type MockScenario = 'ready' | 'needs_firmware' | 'needs_init' | 'locked' | 'access_error'
+
+// get_time_to_ready: plug-in to device:ready, or to the first status for a
+// device that came up needing an update, setup or its PIN. device:ready
+// carries the same time as time_to_ready_ms on the first ready after connect.
+export interface TimeToReady {
+  time_to_ready_ms: number
+  milestone: 'ready' | 'first_status'
+}
+
+// Error of compare_devices. unavailable lists the devices that couldn't derive
+// the key (locked ones can be unlocked and compared again); answered the ones
+// that did.
+export interface UnavailableDevice {
+  device_id: string
+  locked: boolean
+  message: string
+}
+
+export type CompareDevicesError =
+  | { kind: 'invalid_path'; message: string }
+  | { kind: 'same_device' }
+  | { kind: 'unavailable'; unavailable: UnavailableDevice[]; answered: string[] }
+
+// Payload of monitor:stopped. Panics and USB enumeration failures restart the
+// monitor (restarting: true) a few times; the other reasons are deliberate.
+export type MonitorStopReason =
+  | { kind: 'requested' }
+  | { kind: 'app_exit' }
+  | { kind: 'dropped' }
+  | { kind: 'panic'; message: string }
+  | { kind: 'enumeration_failed'; message: string }
+
+export interface MonitorStopped {
+  reason: MonitorStopReason
+  restarting: boolean
+}
+
+// Payload of device:forgotten, sent after forget_device dropped the host's
+// state for a device. A still-connected device is then reported as new.
+export interface DeviceForgotten {
+  unique_id: string
+  was_connected: boolean
+}
+
+// get_transport_config / set_transport_config. More write retries ride out
+// flaky hubs but delay real errors; changes apply to newly spawned workers.
+export interface TransportConfig {
+  writeRetries: number
+  writeTimeout: number | null // ms, null keeps each message's own timeout
+  retryDelay: number // ms
+}
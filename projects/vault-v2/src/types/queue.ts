@@ -36,4 +36,15 @@ export interface QueueStatus {
   active_operations: number;
   status: string;
   last_response?: DeviceResponse;
-} 
\ No newline at end of file
+} 
+// Payload of device:on-connect-result, sent once per run_on_connect
+// registration: when it ran on a matching device, or when it expired
+// (device_id null) before one connected.
+export interface OnConnectResult {
+  registration_id: string;
+  target: string;
+  device_id: string | null;
+  success: boolean;
+  response?: DeviceResponse | null;
+  error?: string | null;
+}
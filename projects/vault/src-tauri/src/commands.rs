@@ -1289,6 +1289,8 @@ pub fn convert_features_to_device_features(raw_features: keepkey_rust::messages:
         passphrase_cached: raw_features.passphrase_cached.unwrap_or(false),
         wipe_code_protection: raw_features.wipe_code_protection.unwrap_or(false),
         auto_lock_delay_ms: raw_features.auto_lock_delay_ms.map(|ms| ms as u64),
+        flags: keepkey_rust::features::flags::flags_from_policy_types(&raw_features.policies),
+        networks: keepkey_rust::features::networks_from_coins(&raw_features.coins),
         policies: raw_features
             .policies
             .into_iter()
@@ -3688,6 +3690,8 @@ pub async fn test_bootloader_mode_device_status() -> Result<String, String> {
         wipe_code_protection: false,
         auto_lock_delay_ms: None,
         policies: vec![],
+        flags: 0,
+        networks: Vec::new(),
     };
     
     // Test the evaluation
@@ -3741,6 +3745,8 @@ pub async fn test_oob_device_status_evaluation() -> Result<String, String> {
         wipe_code_protection: false,
         auto_lock_delay_ms: None,
         policies: vec![],
+        flags: 0,
+        networks: Vec::new(),
     };
     
     // Test the evaluation